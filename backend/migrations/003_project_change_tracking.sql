@@ -0,0 +1,25 @@
+-- Project change tracking
+-- Deleting a task or board doesn't leave a newer updated_at behind, so touch the
+-- owning project instead. Conditional GET (ETag) responses derive from these timestamps.
+
+CREATE OR REPLACE FUNCTION touch_project_updated_at()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE projects SET updated_at = NOW() WHERE id = OLD.project_id;
+    RETURN OLD;
+END;
+$$ language 'plpgsql';
+
+DO $$ BEGIN
+    CREATE TRIGGER touch_project_on_task_delete AFTER DELETE ON tasks
+        FOR EACH ROW EXECUTE FUNCTION touch_project_updated_at();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TRIGGER touch_project_on_board_delete AFTER DELETE ON boards
+        FOR EACH ROW EXECUTE FUNCTION touch_project_updated_at();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
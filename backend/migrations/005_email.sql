@@ -0,0 +1,29 @@
+-- Email delivery
+-- Log of every message the email job attempted, and per-user template opt-outs.
+
+DO $$ BEGIN
+    CREATE TYPE email_status AS ENUM ('sent', 'failed', 'skipped');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS email_log (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    recipient VARCHAR(255) NOT NULL,
+    template VARCHAR(50) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    status email_status NOT NULL,
+    error TEXT,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_log_created_at ON email_log(created_at);
+CREATE INDEX IF NOT EXISTS idx_email_log_user_id ON email_log(user_id);
+
+CREATE TABLE IF NOT EXISTS email_opt_outs (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    template VARCHAR(50) NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    PRIMARY KEY (user_id, template)
+);
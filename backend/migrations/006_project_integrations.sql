@@ -0,0 +1,25 @@
+-- Per-project third party integrations (Slack, GitHub, ...)
+-- `config` holds integration specific settings such as webhook URLs,
+-- `events` the list of event names the integration is subscribed to.
+
+CREATE TABLE IF NOT EXISTS project_integrations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    kind VARCHAR(50) NOT NULL,
+    config JSONB NOT NULL DEFAULT '{}',
+    events JSONB NOT NULL DEFAULT '[]',
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    created_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    UNIQUE(project_id, kind)
+);
+
+CREATE INDEX IF NOT EXISTS idx_project_integrations_project_id ON project_integrations(project_id);
+
+DO $$ BEGIN
+    CREATE TRIGGER update_project_integrations_updated_at BEFORE UPDATE ON project_integrations
+        FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
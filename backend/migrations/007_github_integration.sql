@@ -0,0 +1,64 @@
+-- GitHub integration: per-project task numbers (referenced as "SC-142" in
+-- commits and PRs) and the commits/PRs linked to each task
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS number INTEGER;
+
+UPDATE tasks t
+SET number = numbered.rn
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY project_id ORDER BY created_at, id) AS rn
+    FROM tasks
+) numbered
+WHERE t.id = numbered.id AND t.number IS NULL;
+
+ALTER TABLE tasks ALTER COLUMN number SET NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tasks_project_number ON tasks(project_id, number);
+
+-- Assign the next number in the project; the project row lock serializes
+-- concurrent inserts so numbers are never handed out twice
+CREATE OR REPLACE FUNCTION assign_task_number()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.number IS NULL THEN
+        PERFORM 1 FROM projects WHERE id = NEW.project_id FOR UPDATE;
+        SELECT COALESCE(MAX(number), 0) + 1 INTO NEW.number FROM tasks WHERE project_id = NEW.project_id;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+DO $$ BEGIN
+    CREATE TRIGGER assign_task_number_before_insert BEFORE INSERT ON tasks
+        FOR EACH ROW EXECUTE FUNCTION assign_task_number();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE task_link_kind AS ENUM ('commit', 'pull_request');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS task_links (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    kind task_link_kind NOT NULL,
+    external_id VARCHAR(100) NOT NULL, -- commit sha or "owner/repo#123"
+    url TEXT NOT NULL,
+    title TEXT NOT NULL,
+    author VARCHAR(255),
+    state VARCHAR(20), -- pull requests only: open, closed, merged
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    UNIQUE(task_id, kind, external_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_links_task_id ON task_links(task_id);
+
+DO $$ BEGIN
+    CREATE TRIGGER update_task_links_updated_at BEFORE UPDATE ON task_links
+        FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
@@ -0,0 +1,55 @@
+-- Email digests
+-- Per-user digest schedule; `last_digest_sent_at` is claimed atomically by the
+-- scheduler so a digest period is never sent twice. Tasks also record when
+-- they were last (re)assigned for the "newly assigned" digest section.
+
+DO $$ BEGIN
+    CREATE TYPE digest_frequency AS ENUM ('off', 'daily', 'weekly');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS digest_preferences (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    frequency digest_frequency NOT NULL DEFAULT 'off',
+    timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+    last_digest_sent_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_digest_preferences_frequency ON digest_preferences(frequency) WHERE frequency <> 'off';
+
+DO $$ BEGIN
+    CREATE TRIGGER update_digest_preferences_updated_at BEFORE UPDATE ON digest_preferences
+        FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS assigned_at TIMESTAMPTZ;
+UPDATE tasks SET assigned_at = created_at WHERE assigned_to IS NOT NULL AND assigned_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_tasks_assigned_to_due_date ON tasks(assigned_to, due_date);
+
+CREATE OR REPLACE FUNCTION track_task_assignment()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        IF NEW.assigned_to IS NOT NULL THEN
+            NEW.assigned_at = NOW();
+        END IF;
+    ELSIF NEW.assigned_to IS DISTINCT FROM OLD.assigned_to THEN
+        NEW.assigned_at = CASE WHEN NEW.assigned_to IS NULL THEN NULL ELSE NOW() END;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+DO $$ BEGIN
+    CREATE TRIGGER track_task_assignment_before_write BEFORE INSERT OR UPDATE ON tasks
+        FOR EACH ROW EXECUTE FUNCTION track_task_assignment();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE INDEX IF NOT EXISTS idx_task_comments_created_at ON task_comments(created_at);
@@ -0,0 +1,22 @@
+-- Per-team quota overrides set by instance admins. NULL columns fall back to
+-- the instance defaults from the environment (see src/quotas.rs).
+
+CREATE TABLE IF NOT EXISTS team_limits (
+    team_id UUID PRIMARY KEY REFERENCES teams(id) ON DELETE CASCADE,
+    max_projects INTEGER,
+    max_tasks_per_project INTEGER,
+    max_members INTEGER,
+    max_attachment_bytes_per_project BIGINT,
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+DO $$ BEGIN
+    CREATE TRIGGER update_team_limits_updated_at BEFORE UPDATE ON team_limits
+        FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE INDEX IF NOT EXISTS idx_teams_created_by ON teams(created_by);
@@ -0,0 +1,6 @@
+-- Per-board settings such as column WIP limits and whether they are enforced
+-- (strict) or only reported (advisory). See BoardConfig in models.rs.
+
+ALTER TABLE boards ADD COLUMN IF NOT EXISTS config JSONB NOT NULL DEFAULT '{}';
+
+CREATE INDEX IF NOT EXISTS idx_tasks_project_status ON tasks(project_id, status);
@@ -0,0 +1,12 @@
+-- When each user last opened each task. A task is unread for a user when it
+-- changed, or got comments from someone else, after their last read.
+
+CREATE TABLE IF NOT EXISTS task_reads (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    last_read_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, task_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_reads_task_id ON task_reads(task_id);
+CREATE INDEX IF NOT EXISTS idx_task_comments_task_created ON task_comments(task_id, created_at);
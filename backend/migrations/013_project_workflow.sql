@@ -0,0 +1,4 @@
+-- Per-project workflow rules, e.g. which statuses a Done task can be re-opened
+-- to. An empty object allows every transition.
+
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS workflow JSONB NOT NULL DEFAULT '{}';
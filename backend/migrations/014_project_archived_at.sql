@@ -0,0 +1,5 @@
+-- When a project was archived; NULL while it is active
+
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ;
+
+UPDATE projects SET archived_at = updated_at WHERE is_active = false AND archived_at IS NULL;
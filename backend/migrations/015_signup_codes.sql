@@ -0,0 +1,12 @@
+-- Admin-generated codes that allow registration while public registration is
+-- closed (ALLOW_PUBLIC_REGISTRATION=false). NULL limits mean unlimited.
+
+CREATE TABLE IF NOT EXISTS signup_codes (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    code VARCHAR(64) NOT NULL UNIQUE,
+    max_uses INTEGER,
+    uses INTEGER NOT NULL DEFAULT 0,
+    expires_at TIMESTAMPTZ,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+-- Read-only project role; guests can additionally comment
+ALTER TYPE project_role ADD VALUE IF NOT EXISTS 'viewer';
@@ -0,0 +1,3 @@
+-- How a board splits its columns into rows (swimlanes). See SwimlaneConfig in models.rs.
+
+ALTER TABLE boards ADD COLUMN IF NOT EXISTS swimlane_config JSONB NOT NULL DEFAULT '{}';
@@ -0,0 +1,34 @@
+-- Task positions are scoped to a project's status column and kept as a dense
+-- 1..n sequence per column; see positions.rs.
+
+-- Renumbering doesn't edit tasks, so it mustn't make them look updated (or
+-- unread). Reordering code sets simplecards.reordering for its transaction.
+CREATE OR REPLACE FUNCTION update_task_updated_at()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF current_setting('simplecards.reordering', true) = 'on' THEN
+        RETURN NEW;
+    END IF;
+    NEW.updated_at = NOW();
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+DROP TRIGGER IF EXISTS update_tasks_updated_at ON tasks;
+CREATE TRIGGER update_tasks_updated_at BEFORE UPDATE ON tasks
+    FOR EACH ROW EXECUTE FUNCTION update_task_updated_at();
+
+SET LOCAL simplecards.reordering = 'on';
+
+UPDATE tasks t
+SET position = r.position
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY project_id, status ORDER BY position, created_at, id)::int AS position
+    FROM tasks
+) r
+WHERE t.id = r.id AND t.position <> r.position;
+
+SET LOCAL simplecards.reordering = 'off';
+
+DROP INDEX IF EXISTS idx_tasks_position;
+CREATE INDEX IF NOT EXISTS idx_tasks_column_position ON tasks(project_id, status, position);
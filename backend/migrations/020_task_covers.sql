@@ -0,0 +1,3 @@
+-- Optional card cover shown on the board
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS cover_color VARCHAR(7);
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS cover_emoji VARCHAR(32);
@@ -0,0 +1,35 @@
+-- Project icons, and the text color (light or dark) that reads best on the
+-- project color. Colors are stored as uppercase #RRGGBB; see utils/colors.rs.
+
+DO $$ BEGIN
+    CREATE TYPE contrast_text AS ENUM ('light', 'dark');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS icon VARCHAR(32);
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS contrast_text contrast_text;
+
+UPDATE projects SET color = UPPER(color) WHERE color <> UPPER(color);
+UPDATE tasks SET cover_color = UPPER(cover_color) WHERE cover_color <> UPPER(cover_color);
+
+-- Same rule as colors::contrast_text: dark text wherever it contrasts more
+-- than white text (WCAG relative luminance)
+WITH channels AS (
+    SELECT id,
+           ('x' || substr(color, 2, 2))::bit(8)::int / 255.0 AS r,
+           ('x' || substr(color, 4, 2))::bit(8)::int / 255.0 AS g,
+           ('x' || substr(color, 6, 2))::bit(8)::int / 255.0 AS b
+    FROM projects
+    WHERE color ~ '^#[0-9A-F]{6}$' AND contrast_text IS NULL
+), luminance AS (
+    SELECT id,
+           0.2126 * CASE WHEN r <= 0.03928 THEN r / 12.92 ELSE power((r + 0.055) / 1.055, 2.4) END
+         + 0.7152 * CASE WHEN g <= 0.03928 THEN g / 12.92 ELSE power((g + 0.055) / 1.055, 2.4) END
+         + 0.0722 * CASE WHEN b <= 0.03928 THEN b / 12.92 ELSE power((b + 0.055) / 1.055, 2.4) END AS l
+    FROM channels
+)
+UPDATE projects p
+SET contrast_text = CASE WHEN (l + 0.05) / 0.05 >= 1.05 / (l + 0.05) THEN 'dark' ELSE 'light' END::contrast_text
+FROM luminance
+WHERE p.id = luminance.id;
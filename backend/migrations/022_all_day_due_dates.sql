@@ -0,0 +1,3 @@
+-- All-day due dates are stored as UTC midnight of the day and are due at the
+-- end of that day in the assignee's timezone
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS is_all_day BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,23 @@
+-- Actions worth reviewing later. While an instance admin impersonates a user
+-- every request is recorded with the admin as actor and the user as subject.
+
+DO $$ BEGIN
+    CREATE TYPE audit_action AS ENUM ('impersonation_started', 'impersonated_request');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    action audit_action NOT NULL,
+    actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    subject_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    method VARCHAR(16),
+    path TEXT,
+    -- Response status, NULL until the request has finished
+    status SMALLINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_audit_log_actor ON audit_log(actor_id, created_at DESC);
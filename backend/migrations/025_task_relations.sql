@@ -0,0 +1,26 @@
+-- Soft links between tasks. Each pair is stored once: "A duplicates B" is the
+-- same row as "B is duplicated by A", and "relates to" reads the same from
+-- both tasks. The inverse is computed when reading (see TaskRelationQueries).
+
+DO $$ BEGIN
+    CREATE TYPE task_relation_kind AS ENUM ('relates_to', 'duplicates');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS task_relations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    -- For duplicates, the source is the duplicate
+    source_task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    target_task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    kind task_relation_kind NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    CHECK (source_task_id <> target_task_id)
+);
+
+-- At most one relation per pair of tasks, whichever way round it was created
+CREATE UNIQUE INDEX IF NOT EXISTS idx_task_relations_pair
+    ON task_relations (LEAST(source_task_id, target_task_id), GREATEST(source_task_id, target_task_id));
+CREATE INDEX IF NOT EXISTS idx_task_relations_source ON task_relations(source_task_id);
+CREATE INDEX IF NOT EXISTS idx_task_relations_target ON task_relations(target_task_id);
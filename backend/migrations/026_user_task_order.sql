@@ -0,0 +1,12 @@
+-- Each user's own ordering of their assigned tasks ("My Tasks", sort=manual).
+-- Tasks that are done or no longer assigned to the user are pruned when the
+-- ordering is next read or written, rather than on every task update.
+
+CREATE TABLE IF NOT EXISTS user_task_order (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (user_id, task_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_task_order_task_id ON user_task_order(task_id);
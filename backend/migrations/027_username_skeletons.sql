@@ -0,0 +1,6 @@
+-- Registration rejects usernames that look like an existing one (see
+-- validation::username_skeleton). The expression must match
+-- USERNAME_SKELETON_SQL exactly for the index to be used.
+
+CREATE INDEX IF NOT EXISTS idx_users_username_skeleton
+    ON users ((translate(replace(replace(lower(username), 'rn', 'm'), 'vv', 'w'), '01i', 'oll')));
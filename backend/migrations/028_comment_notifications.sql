@@ -0,0 +1,11 @@
+-- Notifications about new comments. Comments on the same task are counted into
+-- one unread notification while they keep coming (see notifications.rs), so
+-- notifications now have a count and the time they last changed.
+
+ALTER TYPE notification_kind ADD VALUE IF NOT EXISTS 'task_commented';
+
+ALTER TABLE notifications ADD COLUMN IF NOT EXISTS count INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE notifications ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ DEFAULT NOW();
+UPDATE notifications SET updated_at = created_at WHERE updated_at <> created_at;
+
+CREATE INDEX IF NOT EXISTS idx_notifications_user_task ON notifications(user_id, task_id, updated_at DESC);
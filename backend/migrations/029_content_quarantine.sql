@@ -0,0 +1,13 @@
+-- Moderation: instance admins can hide a user's tasks and comments (e.g. spam
+-- from a compromised account) pending review. Quarantined rows stay in place
+-- so they can be restored, but are left out of lists.
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS quarantined_at TIMESTAMPTZ;
+ALTER TABLE task_comments ADD COLUMN IF NOT EXISTS quarantined_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_created_by ON tasks(created_by, created_at);
+CREATE INDEX IF NOT EXISTS idx_task_comments_user ON task_comments(user_id, created_at);
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'content_quarantined';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'content_restored';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'content_purged';
@@ -0,0 +1,7 @@
+-- Task picker (GET /projects/:id/tasks/picker): trigram index so title
+-- substring matches (ILIKE '%q%') don't scan every task of the project.
+-- Numbers are matched through idx_tasks_project_number (migration 007).
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_title_trgm ON tasks USING gin (title gin_trgm_ops);
@@ -0,0 +1,5 @@
+-- GET /api/dashboard: unread notification counts and per-team project counts
+-- without scanning every notification or project
+
+CREATE INDEX IF NOT EXISTS idx_notifications_user_unread ON notifications(user_id) WHERE read_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_projects_team_active ON projects(team_id) WHERE is_active = true;
@@ -0,0 +1,16 @@
+-- Trash: deleting a project or board only marks it deleted. Trashed rows are
+-- left out of every listing and membership check, can be restored by an admin
+-- and are purged by the trash sweep job once the retention period is over.
+--
+-- delete_batch_id groups the rows removed by one delete, so restoring a project
+-- brings back the boards that went with it but not ones trashed on their own before.
+
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS delete_batch_id UUID;
+
+ALTER TABLE boards ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE boards ADD COLUMN IF NOT EXISTS delete_batch_id UUID;
+
+CREATE INDEX IF NOT EXISTS idx_projects_deleted_at ON projects(deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_boards_deleted_at ON boards(deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_boards_delete_batch ON boards(delete_batch_id) WHERE delete_batch_id IS NOT NULL;
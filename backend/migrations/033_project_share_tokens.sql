@@ -0,0 +1,12 @@
+-- Share tokens give read access to a project without signing in, for now only
+-- to its status badge. Project admins create them and revoke one by deleting it.
+
+CREATE TABLE IF NOT EXISTS project_share_tokens (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    token VARCHAR(64) NOT NULL UNIQUE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_project_share_tokens_project ON project_share_tokens(project_id);
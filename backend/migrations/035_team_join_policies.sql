@@ -0,0 +1,34 @@
+-- What happens to project membership when someone joins a team. Teams without
+-- a row here add new members to no projects. The policy only applies when a
+-- member joins; existing project memberships are never changed by it.
+
+DO $$ BEGIN
+    CREATE TYPE auto_add_policy AS ENUM ('none', 'all', 'selected');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS team_settings (
+    team_id UUID PRIMARY KEY REFERENCES teams(id) ON DELETE CASCADE,
+    auto_add_to_projects auto_add_policy NOT NULL DEFAULT 'none',
+    default_project_role project_role NOT NULL DEFAULT 'member',
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+DO $$ BEGIN
+    CREATE TRIGGER update_team_settings_updated_at BEFORE UPDATE ON team_settings
+        FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+-- Projects new members join under the 'selected' policy
+CREATE TABLE IF NOT EXISTS team_auto_add_projects (
+    team_id UUID NOT NULL REFERENCES team_settings(team_id) ON DELETE CASCADE,
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    PRIMARY KEY (team_id, project_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_team_auto_add_projects_project ON team_auto_add_projects(project_id);
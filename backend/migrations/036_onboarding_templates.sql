@@ -0,0 +1,5 @@
+-- Per-project onboarding checklist: task definitions that are created for,
+-- and assigned to, each new project member (see OnboardingTemplate). An empty
+-- object means no checklist.
+
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS onboarding_template JSONB NOT NULL DEFAULT '{}';
@@ -0,0 +1,17 @@
+-- Virus scanning of attachments (see scanning.rs). Attachments stored before
+-- scanning existed count as clean; new ones start out pending when a scanner
+-- is configured.
+
+DO $$ BEGIN
+    CREATE TYPE attachment_scan_status AS ENUM ('pending', 'clean', 'infected', 'failed');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+ALTER TABLE task_attachments ADD COLUMN IF NOT EXISTS scan_status attachment_scan_status NOT NULL DEFAULT 'clean';
+ALTER TABLE task_attachments ALTER COLUMN scan_status SET DEFAULT 'pending';
+-- Signature name for infected files, the error for failed scans
+ALTER TABLE task_attachments ADD COLUMN IF NOT EXISTS scan_detail TEXT;
+ALTER TABLE task_attachments ADD COLUMN IF NOT EXISTS scanned_at TIMESTAMPTZ;
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'attachment_quarantined';
@@ -0,0 +1,31 @@
+-- Scheduled status reports per project. The scheduler claims due reports by
+-- setting last_sent_at, so a period is never reported twice. Reports can be
+-- posted as a pinned system comment; only the latest one stays pinned.
+
+DO $$ BEGIN
+    CREATE TYPE report_cadence AS ENUM ('daily', 'weekly');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+ALTER TABLE task_comments ADD COLUMN IF NOT EXISTS is_system BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE task_comments ADD COLUMN IF NOT EXISTS pinned BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS project_reports (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    cadence report_cadence NOT NULL,
+    -- ISO weekday (1 = Monday) weekly reports go out on
+    weekday SMALLINT NOT NULL DEFAULT 5 CHECK (weekday BETWEEN 1 AND 7),
+    hour SMALLINT NOT NULL DEFAULT 16 CHECK (hour BETWEEN 0 AND 23),
+    timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+    sections JSONB NOT NULL,
+    recipients JSONB NOT NULL,
+    last_comment_id UUID REFERENCES task_comments(id) ON DELETE SET NULL,
+    last_sent_at TIMESTAMPTZ,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_project_reports_project ON project_reports(project_id);
@@ -0,0 +1,176 @@
+-- Maintained counts for team and project details and the dashboard, instead of
+-- COUNT(*) over members, projects and tasks on every request. Triggers keep them
+-- in step with the rows in the same transaction, always as `c = c + delta` so
+-- concurrent writers only queue on the counter row and never lose an update.
+-- The counters live in their own tables so increments don't contend with
+-- updates to the team or project row itself. The maintenance `counters` check
+-- finds and repairs drift.
+
+CREATE TABLE IF NOT EXISTS team_counters (
+    team_id UUID PRIMARY KEY REFERENCES teams(id) ON DELETE CASCADE,
+    member_count BIGINT NOT NULL DEFAULT 0,
+    -- Active projects that aren't in the trash
+    project_count BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS project_counters (
+    project_id UUID PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+    member_count BIGINT NOT NULL DEFAULT 0,
+    -- Quarantined tasks aren't counted (see migration 029)
+    task_count BIGINT NOT NULL DEFAULT 0,
+    open_task_count BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE OR REPLACE FUNCTION create_team_counters()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO team_counters (team_id) VALUES (NEW.id) ON CONFLICT DO NOTHING;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE OR REPLACE FUNCTION create_project_counters()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO project_counters (project_id) VALUES (NEW.id) ON CONFLICT DO NOTHING;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE OR REPLACE FUNCTION count_team_members()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        UPDATE team_counters SET member_count = member_count - 1 WHERE team_id = OLD.team_id;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE team_counters SET member_count = member_count + 1 WHERE team_id = NEW.team_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE OR REPLACE FUNCTION count_team_projects()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') AND COALESCE(OLD.is_active, false) AND OLD.deleted_at IS NULL THEN
+        UPDATE team_counters SET project_count = project_count - 1 WHERE team_id = OLD.team_id;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') AND COALESCE(NEW.is_active, false) AND NEW.deleted_at IS NULL THEN
+        UPDATE team_counters SET project_count = project_count + 1 WHERE team_id = NEW.team_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE OR REPLACE FUNCTION count_project_members()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        UPDATE project_counters SET member_count = member_count - 1 WHERE project_id = OLD.project_id;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE project_counters SET member_count = member_count + 1 WHERE project_id = NEW.project_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE OR REPLACE FUNCTION count_project_tasks()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') AND OLD.quarantined_at IS NULL THEN
+        UPDATE project_counters
+        SET task_count = task_count - 1,
+            open_task_count = open_task_count - CASE WHEN OLD.status <> 'done' THEN 1 ELSE 0 END
+        WHERE project_id = OLD.project_id;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') AND NEW.quarantined_at IS NULL THEN
+        UPDATE project_counters
+        SET task_count = task_count + 1,
+            open_task_count = open_task_count + CASE WHEN NEW.status <> 'done' THEN 1 ELSE 0 END
+        WHERE project_id = NEW.project_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+-- Updates only fire when a counted attribute changes, so ordinary edits (and
+-- the updated_at touches from migration 003) never take a counter row lock
+DO $$ BEGIN
+    CREATE TRIGGER create_team_counters AFTER INSERT ON teams
+        FOR EACH ROW EXECUTE FUNCTION create_team_counters();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TRIGGER create_project_counters AFTER INSERT ON projects
+        FOR EACH ROW EXECUTE FUNCTION create_project_counters();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TRIGGER count_team_members_on_change AFTER INSERT OR DELETE ON team_members
+        FOR EACH ROW EXECUTE FUNCTION count_team_members();
+    CREATE TRIGGER count_team_members_on_move AFTER UPDATE OF team_id ON team_members
+        FOR EACH ROW WHEN (OLD.team_id IS DISTINCT FROM NEW.team_id)
+        EXECUTE FUNCTION count_team_members();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TRIGGER count_team_projects_on_change AFTER INSERT OR DELETE ON projects
+        FOR EACH ROW EXECUTE FUNCTION count_team_projects();
+    CREATE TRIGGER count_team_projects_on_update AFTER UPDATE OF team_id, is_active, deleted_at ON projects
+        FOR EACH ROW WHEN (
+            OLD.team_id IS DISTINCT FROM NEW.team_id
+            OR OLD.is_active IS DISTINCT FROM NEW.is_active
+            OR (OLD.deleted_at IS NULL) <> (NEW.deleted_at IS NULL)
+        )
+        EXECUTE FUNCTION count_team_projects();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TRIGGER count_project_members_on_change AFTER INSERT OR DELETE ON project_members
+        FOR EACH ROW EXECUTE FUNCTION count_project_members();
+    CREATE TRIGGER count_project_members_on_move AFTER UPDATE OF project_id ON project_members
+        FOR EACH ROW WHEN (OLD.project_id IS DISTINCT FROM NEW.project_id)
+        EXECUTE FUNCTION count_project_members();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TRIGGER count_project_tasks_on_change AFTER INSERT OR DELETE ON tasks
+        FOR EACH ROW EXECUTE FUNCTION count_project_tasks();
+    CREATE TRIGGER count_project_tasks_on_update AFTER UPDATE OF project_id, status, quarantined_at ON tasks
+        FOR EACH ROW WHEN (
+            OLD.project_id IS DISTINCT FROM NEW.project_id
+            OR (OLD.status = 'done') <> (NEW.status = 'done')
+            OR (OLD.quarantined_at IS NULL) <> (NEW.quarantined_at IS NULL)
+        )
+        EXECUTE FUNCTION count_project_tasks();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+-- Existing teams and projects start from their current counts
+INSERT INTO team_counters (team_id, member_count, project_count)
+SELECT t.id,
+       (SELECT COUNT(*) FROM team_members tm WHERE tm.team_id = t.id),
+       (SELECT COUNT(*) FROM projects p WHERE p.team_id = t.id AND p.is_active = true AND p.deleted_at IS NULL)
+FROM teams t
+ON CONFLICT DO NOTHING;
+
+INSERT INTO project_counters (project_id, member_count, task_count, open_task_count)
+SELECT p.id,
+       (SELECT COUNT(*) FROM project_members pm WHERE pm.project_id = p.id),
+       (SELECT COUNT(*) FROM tasks t WHERE t.project_id = p.id AND t.quarantined_at IS NULL),
+       (SELECT COUNT(*) FROM tasks t WHERE t.project_id = p.id AND t.quarantined_at IS NULL AND t.status <> 'done')
+FROM projects p
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,37 @@
+-- Stale task detection. Projects configure per-status day thresholds (see
+-- StalenessRules); the staleness job marks open tasks that have sat in their
+-- status for longer by setting `stale_at`, and nudges the assignee once.
+-- There's no task history, so the time of the last status change is kept on
+-- the task itself.
+
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS staleness JSONB NOT NULL DEFAULT '{}';
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS status_changed_at TIMESTAMPTZ;
+UPDATE tasks SET status_changed_at = COALESCE(updated_at, created_at, NOW()) WHERE status_changed_at IS NULL;
+ALTER TABLE tasks ALTER COLUMN status_changed_at SET DEFAULT NOW();
+ALTER TABLE tasks ALTER COLUMN status_changed_at SET NOT NULL;
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS stale_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_stale ON tasks(project_id) WHERE stale_at IS NOT NULL;
+
+ALTER TYPE notification_kind ADD VALUE IF NOT EXISTS 'task_stale';
+
+-- A status change restarts the clock and ends staleness right away, rather
+-- than at the next scan
+CREATE OR REPLACE FUNCTION track_task_status_change()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.status_changed_at = NOW();
+    NEW.stale_at = NULL;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+DO $$ BEGIN
+    CREATE TRIGGER track_task_status_change BEFORE UPDATE OF status ON tasks
+        FOR EACH ROW WHEN (OLD.status IS DISTINCT FROM NEW.status)
+        EXECUTE FUNCTION track_task_status_change();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
@@ -0,0 +1,5 @@
+-- SHA-256 of each stored attachment, hex encoded. Downloads use it as a
+-- strong ETag. Attachments stored before this have none and are revalidated
+-- by Last-Modified only.
+
+ALTER TABLE task_attachments ADD COLUMN IF NOT EXISTS content_sha256 TEXT;
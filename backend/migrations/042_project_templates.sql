@@ -0,0 +1,29 @@
+-- Project templates: snapshots of a project's structure (settings and boards,
+-- no tasks or members) that new projects can start from. Team templates are
+-- usable by the team's members right away; instance templates are usable by
+-- everyone once an instance admin approved them. The snapshot is copied at
+-- publish time, so later edits to the source project don't change it.
+
+DO $$ BEGIN
+    CREATE TYPE template_visibility AS ENUM ('team', 'instance');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS project_templates (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    visibility template_visibility NOT NULL,
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    source_project_id UUID REFERENCES projects(id) ON DELETE SET NULL,
+    document JSONB NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    -- Set when publishing to the team, and by the approving admin for instance templates
+    approved_at TIMESTAMPTZ,
+    approved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_project_templates_team ON project_templates(team_id);
+CREATE INDEX IF NOT EXISTS idx_project_templates_pending ON project_templates(created_at) WHERE approved_at IS NULL;
@@ -0,0 +1,4 @@
+-- Merging a duplicate account into another (see UserQueries::merge_into) is
+-- recorded with the surviving account as actor and the merged one as subject.
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'accounts_merged';
@@ -0,0 +1,22 @@
+-- @mentions in comments and task descriptions (see mentions.rs). Every user a
+-- mention reaches gets a row, including those reached through a group token
+-- such as @project, which is kept in `group_token` for clients to render.
+-- Description mentions have no comment and notify each user once per task.
+
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS mention_settings JSONB NOT NULL DEFAULT '{}';
+
+ALTER TYPE notification_kind ADD VALUE IF NOT EXISTS 'task_mentioned';
+
+CREATE TABLE IF NOT EXISTS task_mentions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    comment_id UUID REFERENCES task_comments(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    mentioned_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    group_token VARCHAR(20),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_mentions_task ON task_mentions(task_id, created_at);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_task_mentions_description
+    ON task_mentions(task_id, user_id) WHERE comment_id IS NULL;
@@ -0,0 +1,16 @@
+-- Board automation rules (see BoardAutomations in models.rs and automations.rs).
+-- `automation_cursors` maps each round-robin rule's id to the user it assigned
+-- last; it is kept apart from the rules so saving them doesn't reset turns.
+
+ALTER TABLE boards ADD COLUMN IF NOT EXISTS automations JSONB NOT NULL DEFAULT '{}';
+ALTER TABLE boards ADD COLUMN IF NOT EXISTS automation_cursors JSONB NOT NULL DEFAULT '{}';
+
+-- Rules that did something on a task, so their comment is only posted once
+CREATE TABLE IF NOT EXISTS task_automation_runs (
+    task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    rule_id UUID NOT NULL,
+    board_id UUID NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+    run_count INTEGER NOT NULL DEFAULT 1,
+    last_run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (task_id, rule_id)
+);
@@ -0,0 +1,16 @@
+-- Every status change of a task, for cycle-time analytics (see analytics.rs).
+-- `changed_by` is NULL for changes made by integrations. There's no task
+-- history to backfill from (see 040), so tasks have transitions from here on
+-- and the analytics only cover tasks completed after this migration.
+
+CREATE TABLE IF NOT EXISTS task_status_transitions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    from_status task_status NOT NULL,
+    to_status task_status NOT NULL,
+    changed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_status_transitions_task ON task_status_transitions(task_id, changed_at);
+CREATE INDEX IF NOT EXISTS idx_task_status_transitions_done ON task_status_transitions(changed_at) WHERE to_status = 'done';
@@ -0,0 +1,18 @@
+-- Data retention. Teams set how old comments and attachments may get (see
+-- RetentionPolicy); the retention sweep hides older ones by setting
+-- `expired_at` and deletes them for good once the grace period is over.
+-- Until then a more lenient policy brings them back. Pinned comments are
+-- never expired.
+
+ALTER TABLE teams ADD COLUMN IF NOT EXISTS retention JSONB NOT NULL DEFAULT '{}';
+
+ALTER TABLE task_comments ADD COLUMN IF NOT EXISTS expired_at TIMESTAMPTZ;
+ALTER TABLE task_attachments ADD COLUMN IF NOT EXISTS expired_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_task_comments_expired ON task_comments(expired_at) WHERE expired_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_task_attachments_expired ON task_attachments(expired_at) WHERE expired_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_task_attachments_created_at ON task_attachments(created_at);
+
+-- One entry per team and sweep that changed anything, with the counts in `details`
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'retention_applied';
+ALTER TABLE audit_log ADD COLUMN IF NOT EXISTS details JSONB;
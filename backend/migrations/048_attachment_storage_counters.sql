@@ -0,0 +1,91 @@
+-- Attachment storage per project, maintained like the counters from migration
+-- 039 so uploads and usage reports don't sum task_attachments every time.
+-- Expired attachments count until the retention sweep deletes them.
+--
+-- Attachments only reference their task, so their counts follow the task: a
+-- task moved to another project takes its attachments along, and a deleted
+-- task subtracts them before the cascade removes the rows (whose own trigger
+-- then no longer finds the task and leaves the counters alone).
+
+ALTER TABLE project_counters ADD COLUMN IF NOT EXISTS attachment_count BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE project_counters ADD COLUMN IF NOT EXISTS attachment_bytes BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_project_counters_attachment_bytes ON project_counters(attachment_bytes DESC);
+
+CREATE OR REPLACE FUNCTION count_project_attachments()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        UPDATE project_counters
+        SET attachment_count = attachment_count - 1,
+            attachment_bytes = attachment_bytes - OLD.size_bytes
+        WHERE project_id = (SELECT project_id FROM tasks WHERE id = OLD.task_id);
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE project_counters
+        SET attachment_count = attachment_count + 1,
+            attachment_bytes = attachment_bytes + NEW.size_bytes
+        WHERE project_id = (SELECT project_id FROM tasks WHERE id = NEW.task_id);
+    END IF;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+-- Runs before a task is deleted and after it moves. Deleting only goes ahead
+-- when a BEFORE trigger returns the row; for the AFTER trigger it's ignored.
+CREATE OR REPLACE FUNCTION count_task_attachments()
+RETURNS TRIGGER AS $$
+DECLARE
+    moved_count BIGINT;
+    moved_bytes BIGINT;
+BEGIN
+    SELECT COUNT(*), COALESCE(SUM(size_bytes), 0) INTO moved_count, moved_bytes
+    FROM task_attachments WHERE task_id = OLD.id;
+    IF moved_count = 0 THEN
+        RETURN OLD;
+    END IF;
+
+    UPDATE project_counters
+    SET attachment_count = attachment_count - moved_count,
+        attachment_bytes = attachment_bytes - moved_bytes
+    WHERE project_id = OLD.project_id;
+    IF TG_OP = 'UPDATE' THEN
+        UPDATE project_counters
+        SET attachment_count = attachment_count + moved_count,
+            attachment_bytes = attachment_bytes + moved_bytes
+        WHERE project_id = NEW.project_id;
+    END IF;
+    RETURN OLD;
+END;
+$$ language 'plpgsql';
+
+DO $$ BEGIN
+    CREATE TRIGGER count_project_attachments_on_change AFTER INSERT OR DELETE ON task_attachments
+        FOR EACH ROW EXECUTE FUNCTION count_project_attachments();
+    CREATE TRIGGER count_project_attachments_on_update AFTER UPDATE OF task_id, size_bytes ON task_attachments
+        FOR EACH ROW WHEN (OLD.task_id IS DISTINCT FROM NEW.task_id OR OLD.size_bytes IS DISTINCT FROM NEW.size_bytes)
+        EXECUTE FUNCTION count_project_attachments();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TRIGGER count_task_attachments_on_delete BEFORE DELETE ON tasks
+        FOR EACH ROW EXECUTE FUNCTION count_task_attachments();
+    CREATE TRIGGER count_task_attachments_on_move AFTER UPDATE OF project_id ON tasks
+        FOR EACH ROW WHEN (OLD.project_id IS DISTINCT FROM NEW.project_id)
+        EXECUTE FUNCTION count_task_attachments();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+-- Existing projects start from their current totals
+UPDATE project_counters c
+SET attachment_count = a.attachment_count, attachment_bytes = a.attachment_bytes
+FROM (
+    SELECT t.project_id, COUNT(*) AS attachment_count, SUM(x.size_bytes) AS attachment_bytes
+    FROM task_attachments x
+    JOIN tasks t ON t.id = x.task_id
+    GROUP BY t.project_id
+) a
+WHERE c.project_id = a.project_id;
@@ -0,0 +1,17 @@
+-- Personal API tokens, e.g. for widgets embedded in a wiki. A token acts as
+-- the user who created it, limited to its scopes and, if `project_ids` is set,
+-- to those projects. Only the SHA-256 of the token is stored; deleting the
+-- row revokes it.
+
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    project_ids UUID[],
+    last_used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_tokens_user ON api_tokens(user_id);
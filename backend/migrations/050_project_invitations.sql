@@ -0,0 +1,21 @@
+-- Invitations to a single project for people outside its team, such as
+-- contractors. Accepting one, on registration or with an existing account,
+-- makes the user a project member without any team membership, which only
+-- guests and viewers may be. Only the SHA-256 of the invitation token is
+-- stored; an invitation can be accepted once, until it expires.
+
+CREATE TABLE IF NOT EXISTS project_invitations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    email VARCHAR(255) NOT NULL,
+    role project_role NOT NULL DEFAULT 'guest' CHECK (role IN ('guest', 'viewer')),
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    invited_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    accepted_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    accepted_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_project_invitations_pending
+    ON project_invitations(project_id, LOWER(email)) WHERE accepted_at IS NULL;
@@ -0,0 +1,4 @@
+-- Profile activity pages list a user's status changes next to the tasks and
+-- comments they created, which 029 already indexes by author.
+
+CREATE INDEX IF NOT EXISTS idx_task_status_transitions_changed_by ON task_status_transitions(changed_by, changed_at);
@@ -0,0 +1,18 @@
+-- Quick switcher (GET /quick-switch): when each user last opened each
+-- project, written at most once a minute from project and board pages, and
+-- trigram indexes for matching team and project names (task titles have one
+-- since 030).
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE TABLE IF NOT EXISTS project_access (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    last_accessed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, project_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_project_access_project_id ON project_access(project_id);
+CREATE INDEX IF NOT EXISTS idx_task_reads_user_last_read ON task_reads(user_id, last_read_at DESC);
+CREATE INDEX IF NOT EXISTS idx_projects_name_trgm ON projects USING gin (name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_teams_name_trgm ON teams USING gin (name gin_trgm_ops);
@@ -0,0 +1,29 @@
+-- Stored copies of a task as it was at one point, e.g. when it shipped. The
+-- rendered task is kept in `data` and never changes afterwards; the trigger
+-- rejects updates so the copy can be trusted for audits.
+
+CREATE TABLE IF NOT EXISTS task_snapshots (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    note VARCHAR(200),
+    data JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_snapshots_task_id ON task_snapshots(task_id, created_at);
+
+CREATE OR REPLACE FUNCTION prevent_task_snapshot_update()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'task snapshots are immutable';
+END;
+$$ language 'plpgsql';
+
+DO $$ BEGIN
+    CREATE TRIGGER task_snapshots_immutable BEFORE UPDATE ON task_snapshots
+        FOR EACH ROW EXECUTE FUNCTION prevent_task_snapshot_update();
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
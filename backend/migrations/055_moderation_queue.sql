@@ -0,0 +1,38 @@
+-- Content screening: tasks and comments that score as likely spam are held
+-- for review instead of being published. Held rows are quarantined (see 029)
+-- until an instance admin approves them; the queue keeps why they were held.
+
+DO $$ BEGIN
+    CREATE TYPE moderation_status AS ENUM ('pending', 'approved', 'rejected');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE held_content_type AS ENUM ('task', 'comment');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS moderation_queue (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    content_type held_content_type NOT NULL,
+    task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    -- Set for comments
+    comment_id UUID REFERENCES task_comments(id) ON DELETE CASCADE,
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    score REAL NOT NULL,
+    reasons TEXT[] NOT NULL DEFAULT '{}',
+    language VARCHAR(8),
+    status moderation_status NOT NULL DEFAULT 'pending',
+    reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_moderation_queue_status ON moderation_queue(status, created_at);
+CREATE INDEX IF NOT EXISTS idx_moderation_queue_task_id ON moderation_queue(task_id);
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'content_approved';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'content_rejected';
@@ -0,0 +1,47 @@
+-- Team audit exports (see audit_export.rs): tasks created, status changes and
+-- comments in the team's projects, plus the audit entries recorded for the
+-- team. Those now carry the team and, when a request caused them, the
+-- client's address.
+
+ALTER TABLE audit_log ADD COLUMN IF NOT EXISTS team_id UUID REFERENCES teams(id) ON DELETE SET NULL;
+ALTER TABLE audit_log ADD COLUMN IF NOT EXISTS ip_address VARCHAR(45);
+CREATE INDEX IF NOT EXISTS idx_audit_log_team ON audit_log(team_id, created_at) WHERE team_id IS NOT NULL;
+
+-- Retention sweeps kept their team in the details (see 047)
+UPDATE audit_log a SET team_id = t.id
+FROM teams t
+WHERE a.action = 'retention_applied' AND a.team_id IS NULL AND t.id::text = a.details->>'team_id';
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'audit_exported';
+
+DO $$ BEGIN
+    CREATE TYPE audit_export_status AS ENUM ('pending', 'ready', 'failed');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+-- Exports too large to stream from the request, written by a background job
+CREATE TABLE IF NOT EXISTS audit_exports (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    requested_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    format VARCHAR(8) NOT NULL,
+    from_at TIMESTAMPTZ NOT NULL,
+    to_at TIMESTAMPTZ NOT NULL,
+    status audit_export_status NOT NULL DEFAULT 'pending',
+    entry_count BIGINT,
+    -- Below UPLOAD_DIR, once ready
+    storage_path TEXT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_exports_team ON audit_exports(team_id, created_at DESC);
+
+-- Team-level notifications, such as a finished export, have no project
+ALTER TABLE notifications ALTER COLUMN project_id DROP NOT NULL;
+ALTER TABLE notifications ADD COLUMN IF NOT EXISTS team_id UUID REFERENCES teams(id) ON DELETE CASCADE;
+ALTER TABLE notifications ADD COLUMN IF NOT EXISTS audit_export_id UUID REFERENCES audit_exports(id) ON DELETE CASCADE;
+
+ALTER TYPE notification_kind ADD VALUE IF NOT EXISTS 'audit_export_ready';
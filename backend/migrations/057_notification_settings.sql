@@ -0,0 +1,32 @@
+-- Notification levels per user, project and event type (see
+-- notification_settings.rs). Projects without a setting for an event type use
+-- the user's default for it, and users without a default get everything.
+
+DO $$ BEGIN
+    CREATE TYPE notification_event_type AS ENUM ('mention', 'assignment', 'comment', 'reminder', 'digest');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE notification_level AS ENUM ('all', 'mentions_only', 'none');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS notification_settings (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    event_type notification_event_type NOT NULL,
+    level notification_level NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, project_id, event_type)
+);
+
+CREATE TABLE IF NOT EXISTS notification_defaults (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    event_type notification_event_type NOT NULL,
+    level notification_level NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, event_type)
+);
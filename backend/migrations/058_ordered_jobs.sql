@@ -0,0 +1,14 @@
+-- Ordered jobs (see jobs::enqueue_ordered): jobs sharing an ordering key run
+-- one at a time in the order they were queued. Each gets the next sequence
+-- number of its key, which receivers can use to drop repeated deliveries.
+
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS ordering_key VARCHAR(200);
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS sequence BIGINT;
+
+CREATE INDEX IF NOT EXISTS idx_jobs_ordering ON jobs(ordering_key, sequence) WHERE ordering_key IS NOT NULL;
+
+-- The last sequence number handed out per key
+CREATE TABLE IF NOT EXISTS job_sequences (
+    ordering_key VARCHAR(200) PRIMARY KEY,
+    last_sequence BIGINT NOT NULL
+);
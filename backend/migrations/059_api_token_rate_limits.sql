@@ -0,0 +1,4 @@
+-- Per-user override of the hourly request quota of each of the user's API
+-- tokens, set by instance admins. NULL uses API_TOKEN_RATE_LIMIT.
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS api_token_rate_limit INTEGER CHECK (api_token_rate_limit > 0);
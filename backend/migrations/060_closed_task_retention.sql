@@ -0,0 +1,40 @@
+-- Closed task retention. Teams can also set how many days tasks may stay done
+-- (see RetentionPolicy); the retention sweep archives older ones by setting
+-- `expired_at`, which hides them everywhere, and deletes them for good once
+-- the grace period is over. Tasks related to a task that is still open are
+-- kept.
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS expired_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_expired ON tasks(expired_at) WHERE expired_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_tasks_done_since ON tasks(project_id, status_changed_at) WHERE status = 'done';
+
+-- Archived tasks leave the project counters like quarantined ones
+CREATE OR REPLACE FUNCTION count_project_tasks()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') AND OLD.quarantined_at IS NULL AND OLD.expired_at IS NULL THEN
+        UPDATE project_counters
+        SET task_count = task_count - 1,
+            open_task_count = open_task_count - CASE WHEN OLD.status <> 'done' THEN 1 ELSE 0 END
+        WHERE project_id = OLD.project_id;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') AND NEW.quarantined_at IS NULL AND NEW.expired_at IS NULL THEN
+        UPDATE project_counters
+        SET task_count = task_count + 1,
+            open_task_count = open_task_count + CASE WHEN NEW.status <> 'done' THEN 1 ELSE 0 END
+        WHERE project_id = NEW.project_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+DROP TRIGGER IF EXISTS count_project_tasks_on_update ON tasks;
+CREATE TRIGGER count_project_tasks_on_update AFTER UPDATE OF project_id, status, quarantined_at, expired_at ON tasks
+    FOR EACH ROW WHEN (
+        OLD.project_id IS DISTINCT FROM NEW.project_id
+        OR (OLD.status = 'done') <> (NEW.status = 'done')
+        OR (OLD.quarantined_at IS NULL) <> (NEW.quarantined_at IS NULL)
+        OR (OLD.expired_at IS NULL) <> (NEW.expired_at IS NULL)
+    )
+    EXECUTE FUNCTION count_project_tasks();
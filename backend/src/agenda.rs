@@ -0,0 +1,355 @@
+// Printable weekly agenda: a user's assigned tasks due in an ISO week, grouped
+// by the day they're due in the user's (digest) timezone, as a PDF or as HTML
+// for clients that print themselves. Generation has to finish within
+// TIME_BUDGET, and documents are cached per user, week and format for an hour,
+// so changes made in the meantime show up in the next hour's agenda.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::database::models::{AgendaTask, TaskStatus};
+use crate::utils::html::escape_html;
+use crate::utils::pdf::{Font, TextDocument};
+
+pub const CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
+pub const TIME_BUDGET: std::time::Duration = std::time::Duration::from_secs(10);
+// Entries are pruned once this many agendas are cached
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IsoWeek {
+    pub year: i32,
+    pub week: u32,
+}
+
+impl IsoWeek {
+    // "2024-W23"; None for weeks that don't exist, such as 2023-W53
+    pub fn parse(value: &str) -> Option<IsoWeek> {
+        let (year, week) = value.split_once(['W', 'w'])?;
+        let year = year.strip_suffix('-')?;
+        if year.len() != 4 || week.len() != 2 {
+            return None;
+        }
+        let week = IsoWeek { year: year.parse().ok()?, week: week.parse().ok()? };
+        NaiveDate::from_isoywd_opt(week.year, week.week, Weekday::Mon).map(|_| week)
+    }
+
+    pub fn containing(date: NaiveDate) -> IsoWeek {
+        let week = date.iso_week();
+        IsoWeek { year: week.year(), week: week.week() }
+    }
+
+    pub fn monday(&self) -> NaiveDate {
+        NaiveDate::from_isoywd_opt(self.year, self.week, Weekday::Mon).expect("parsed weeks exist")
+    }
+
+    pub fn days(&self) -> impl Iterator<Item = NaiveDate> {
+        self.monday().iter_days().take(7)
+    }
+}
+
+impl fmt::Display for IsoWeek {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-W{:02}", self.year, self.week)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AgendaFormat {
+    Pdf,
+    Html,
+}
+
+impl AgendaFormat {
+    pub fn parse(value: &str) -> Option<AgendaFormat> {
+        match value {
+            "pdf" => Some(AgendaFormat::Pdf),
+            "html" => Some(AgendaFormat::Html),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            AgendaFormat::Pdf => "application/pdf",
+            AgendaFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AgendaFormat::Pdf => "pdf",
+            AgendaFormat::Html => "html",
+        }
+    }
+}
+
+// Stored due dates that can fall into the week in some timezone. Timezones
+// are less than a day off UTC, and all-day dates are stored as UTC midnight.
+pub fn due_window(week: IsoWeek) -> (DateTime<Utc>, DateTime<Utc>) {
+    let monday = Utc.from_utc_datetime(&week.monday().and_time(NaiveTime::MIN));
+    (monday - Duration::days(1), monday + Duration::days(8))
+}
+
+// The day a task is due: all-day dates name it, others fall on a day in the
+// user's timezone
+pub fn due_day(task: &AgendaTask, timezone: Tz) -> NaiveDate {
+    if task.is_all_day {
+        task.due_date.date_naive()
+    } else {
+        task.due_date.with_timezone(&timezone).date_naive()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgendaEntry {
+    // "All day" or the local time, e.g. "14:30"
+    pub time: String,
+    pub task: AgendaTask,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgendaDay {
+    pub date: NaiveDate,
+    pub entries: Vec<AgendaEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agenda {
+    pub week: IsoWeek,
+    // All seven days, Monday first, including those without tasks
+    pub days: Vec<AgendaDay>,
+}
+
+// `tasks` in due order, as TaskQueries::get_assigned_tasks_due_between returns
+// them. All-day tasks come first on their day; tasks outside the week are left out.
+pub fn build(week: IsoWeek, tasks: Vec<AgendaTask>, timezone: Tz) -> Agenda {
+    let mut days: Vec<AgendaDay> = week.days().map(|date| AgendaDay { date, entries: Vec::new() }).collect();
+
+    for task in tasks {
+        let date = due_day(&task, timezone);
+        let Some(day) = days.iter_mut().find(|day| day.date == date) else {
+            continue;
+        };
+        let time = if task.is_all_day {
+            "All day".to_string()
+        } else {
+            task.due_date.with_timezone(&timezone).format("%H:%M").to_string()
+        };
+        day.entries.push(AgendaEntry { time, task });
+    }
+    for day in &mut days {
+        day.entries.sort_by_key(|entry| !entry.task.is_all_day);
+    }
+
+    Agenda { week, days }
+}
+
+fn date_range(week: IsoWeek) -> String {
+    let monday = week.monday();
+    format!("{} - {}", monday.format("%b %-d"), (monday + Duration::days(6)).format("%b %-d, %Y"))
+}
+
+fn details(task: &AgendaTask) -> String {
+    let mut details = format!("{}, {}", task.project_name, task.priority.name());
+    if task.status == TaskStatus::Done {
+        details.push_str(", done");
+    }
+    details
+}
+
+pub fn render_pdf(agenda: &Agenda) -> Vec<u8> {
+    let mut document = TextDocument::default();
+    document.line(Font::Bold, 18.0, 0.0, &format!("Agenda {}", agenda.week));
+    document.line(Font::Regular, 10.0, 0.0, &date_range(agenda.week));
+
+    for day in &agenda.days {
+        document.space(8.0);
+        document.line(Font::Bold, 13.0, 0.0, &day.date.format("%A, %B %-d").to_string());
+        if day.entries.is_empty() {
+            document.line(Font::Regular, 10.0, 12.0, "Nothing due");
+        }
+        for entry in &day.entries {
+            let line = format!("{}  {} ({})", entry.time, entry.task.title, details(&entry.task));
+            document.line(Font::Regular, 10.0, 12.0, &line);
+        }
+    }
+
+    document.finish()
+}
+
+pub fn render_html(agenda: &Agenda) -> String {
+    let mut days = String::new();
+    for day in &agenda.days {
+        days.push_str(&format!("<h2>{}</h2>\n", day.date.format("%A, %B %-d")));
+        if day.entries.is_empty() {
+            days.push_str("<p class=\"empty\">Nothing due</p>\n");
+            continue;
+        }
+        days.push_str("<ul>\n");
+        for entry in &day.entries {
+            days.push_str(&format!(
+                "<li><span class=\"time\">{}</span> {} <span class=\"details\">({})</span></li>\n",
+                entry.time,
+                escape_html(&entry.task.title),
+                escape_html(&details(&entry.task))
+            ));
+        }
+        days.push_str("</ul>\n");
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Agenda {week}</title>
+<style>
+body {{ font-family: Helvetica, Arial, sans-serif; margin: 2em; }}
+h2 {{ font-size: 1.1em; margin-bottom: 0.3em; page-break-after: avoid; }}
+ul {{ margin-top: 0; }}
+.time {{ display: inline-block; min-width: 4.5em; }}
+.details, .empty {{ color: #555; }}
+</style>
+</head>
+<body>
+<h1>Agenda {week}</h1>
+<p>{range}</p>
+{days}</body>
+</html>
+"#,
+        week = agenda.week,
+        range = date_range(agenda.week),
+        days = days,
+    )
+}
+
+type AgendaKey = (Uuid, IsoWeek, AgendaFormat);
+
+// Rendered agendas per user, week and format
+#[derive(Debug, Default)]
+pub struct AgendaCache {
+    entries: Mutex<HashMap<AgendaKey, (Instant, Vec<u8>)>>,
+}
+
+impl AgendaCache {
+    pub fn get(&self, key: AgendaKey) -> Option<Vec<u8>> {
+        self.get_at(key, Instant::now())
+    }
+
+    fn get_at(&self, key: AgendaKey, now: Instant) -> Option<Vec<u8>> {
+        let entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries
+            .get(&key)
+            .filter(|(cached_at, _)| now.duration_since(*cached_at) < CACHE_TTL)
+            .map(|(_, document)| document.clone())
+    }
+
+    pub fn insert(&self, key: AgendaKey, document: Vec<u8>) {
+        self.insert_at(key, document, Instant::now())
+    }
+
+    fn insert_at(&self, key: AgendaKey, document: Vec<u8>, now: Instant) {
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if entries.len() >= PRUNE_THRESHOLD {
+            entries.retain(|_, (cached_at, _)| now.duration_since(*cached_at) < CACHE_TTL);
+        }
+        entries.insert(key, (now, document));
+    }
+}
+
+pub fn agenda_cache() -> &'static AgendaCache {
+    static INSTANCE: OnceLock<AgendaCache> = OnceLock::new();
+    INSTANCE.get_or_init(AgendaCache::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::TaskPriority;
+
+    fn task(title: &str, due_date: DateTime<Utc>, is_all_day: bool) -> AgendaTask {
+        AgendaTask {
+            id: Uuid::new_v4(),
+            project_id: Uuid::new_v4(),
+            project_name: "Website".to_string(),
+            number: 1,
+            title: title.to_string(),
+            status: TaskStatus::Todo,
+            priority: TaskPriority::High,
+            due_date,
+            is_all_day,
+        }
+    }
+
+    fn week() -> IsoWeek {
+        IsoWeek { year: 2024, week: 23 }
+    }
+
+    #[test]
+    fn test_parse_iso_weeks() {
+        assert_eq!(IsoWeek::parse("2024-W23"), Some(week()));
+        assert_eq!(IsoWeek::parse("2020-w53"), Some(IsoWeek { year: 2020, week: 53 }));
+        for invalid in ["2023-W53", "2024-W00", "2024-W3", "2024W23", "24-W23", "2024-23", ""] {
+            assert_eq!(IsoWeek::parse(invalid), None, "{}", invalid);
+        }
+        assert_eq!(week().to_string(), "2024-W23");
+        assert_eq!(week().monday(), NaiveDate::from_ymd_opt(2024, 6, 3).unwrap());
+        assert_eq!(IsoWeek::containing(NaiveDate::from_ymd_opt(2024, 6, 9).unwrap()), week());
+    }
+
+    #[test]
+    fn test_tasks_are_grouped_by_local_due_day() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        let tasks = vec![
+            // Monday 23:30 UTC is already Tuesday in Berlin
+            task("Late call", Utc.with_ymd_and_hms(2024, 6, 3, 23, 30, 0).unwrap(), false),
+            task("Launch", Utc.with_ymd_and_hms(2024, 6, 4, 0, 0, 0).unwrap(), true),
+            task("Next week", Utc.with_ymd_and_hms(2024, 6, 10, 0, 0, 0).unwrap(), true),
+        ];
+
+        let agenda = build(week(), tasks, berlin);
+        assert_eq!(agenda.days.len(), 7);
+        assert!(agenda.days[0].entries.is_empty());
+        let tuesday: Vec<(&str, &str)> = agenda.days[1]
+            .entries
+            .iter()
+            .map(|entry| (entry.time.as_str(), entry.task.title.as_str()))
+            .collect();
+        assert_eq!(tuesday, vec![("All day", "Launch"), ("01:30", "Late call")]);
+        assert!(agenda.days.iter().all(|day| day.entries.iter().all(|entry| entry.task.title != "Next week")));
+    }
+
+    #[test]
+    fn test_renderings_list_the_tasks() {
+        let agenda = build(week(), vec![task("Fix <login>", Utc.with_ymd_and_hms(2024, 6, 5, 0, 0, 0).unwrap(), true)], Tz::UTC);
+
+        let html = render_html(&agenda);
+        assert!(html.contains("<h1>Agenda 2024-W23</h1>"));
+        assert!(html.contains("Jun 3 - Jun 9, 2024"));
+        assert!(html.contains("Fix &lt;login&gt; <span class=\"details\">(Website, High)</span>"));
+        assert!(html.contains("<h2>Wednesday, June 5</h2>"));
+
+        let pdf = String::from_utf8_lossy(&render_pdf(&agenda)).into_owned();
+        assert!(pdf.contains("(All day  Fix <login> \\(Website, High\\)) Tj"));
+        assert!(pdf.contains("(Wednesday, June 5) Tj"));
+    }
+
+    #[test]
+    fn test_cache_expires() {
+        let cache = AgendaCache::default();
+        let key = (Uuid::new_v4(), week(), AgendaFormat::Pdf);
+        let start = Instant::now();
+
+        assert_eq!(cache.get_at(key, start), None);
+        cache.insert_at(key, b"%PDF".to_vec(), start);
+        assert_eq!(cache.get_at(key, start + CACHE_TTL / 2), Some(b"%PDF".to_vec()));
+        assert_eq!(cache.get_at((key.0, key.1, AgendaFormat::Html), start), None);
+        assert_eq!(cache.get_at(key, start + CACHE_TTL), None);
+    }
+}
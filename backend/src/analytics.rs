@@ -0,0 +1,101 @@
+// Cycle-time analytics. Status changes are recorded in task_status_transitions
+// (see TaskQueries::update_task and move_task), and ProjectQueries::get_cycle_times
+// works out the time completed tasks spent in each status from them. This
+// module only renders the result as CSV for spreadsheets.
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+
+use crate::database::models::CycleTimeReport;
+use crate::utils::csv::csv_field;
+
+// Window used when the request leaves out `from`
+pub const DEFAULT_WINDOW: Duration = Duration::days(30);
+
+const CSV_HEADER: [&str; 12] = [
+    "task_id",
+    "number",
+    "title",
+    "created_at",
+    "started_at",
+    "completed_at",
+    "lead_time_seconds",
+    "cycle_time_seconds",
+    "todo_seconds",
+    "in_progress_seconds",
+    "review_seconds",
+    "done_seconds",
+];
+
+// One row per task, in the report's order
+pub fn cycle_time_csv(report: &CycleTimeReport) -> String {
+    let mut csv = CSV_HEADER.join(",");
+    csv.push_str("\r\n");
+    for task in &report.tasks {
+        let fields = [
+            task.task_id.to_string(),
+            task.number.to_string(),
+            csv_field(&task.title),
+            timestamp(task.created_at),
+            timestamp(task.started_at),
+            timestamp(task.completed_at),
+            task.lead_time_seconds.to_string(),
+            task.cycle_time_seconds.to_string(),
+            task.time_in_status.todo.to_string(),
+            task.time_in_status.in_progress.to_string(),
+            task.time_in_status.review.to_string(),
+            task.time_in_status.done.to_string(),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::{CycleTimeTask, DurationPercentiles, StatusDurations};
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_cycle_time_csv() {
+        let created_at = Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap();
+        let task_id = Uuid::new_v4();
+        let report = CycleTimeReport {
+            from: created_at - DEFAULT_WINDOW,
+            to: created_at + Duration::days(1),
+            completed: 1,
+            cycle_time: DurationPercentiles { p50: Some(7200), p85: Some(7200), p95: Some(7200) },
+            lead_time: DurationPercentiles { p50: Some(10800), p85: Some(10800), p95: Some(10800) },
+            tasks: vec![CycleTimeTask {
+                task_id,
+                number: 42,
+                title: "Ship it, finally".to_string(),
+                created_at,
+                started_at: created_at + Duration::hours(1),
+                completed_at: created_at + Duration::hours(3),
+                lead_time_seconds: 10800,
+                cycle_time_seconds: 7200,
+                time_in_status: StatusDurations { todo: 3600, in_progress: 5400, review: 1800, done: 0 },
+            }],
+        };
+
+        let csv = cycle_time_csv(&report);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER.join(","));
+        assert_eq!(
+            lines[1],
+            format!(
+                "{},42,\"Ship it, finally\",2024-06-03T09:00:00Z,2024-06-03T10:00:00Z,2024-06-03T12:00:00Z,10800,7200,3600,5400,1800,0",
+                task_id
+            )
+        );
+        assert_eq!(lines[2], "");
+    }
+}
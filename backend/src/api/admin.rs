@@ -0,0 +1,716 @@
+use axum::{
+    body::Bytes,
+    extract::{Extension, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::middleware::CurrentUser;
+use crate::backup::{self, ExportOptions};
+use crate::auth::registration::{self, RegistrationPolicy};
+use crate::database::{models::{AuditAction, CreateAnnouncementRequest, CreateSignupCodeRequest, HeldContentType, JobStatus, ModerationQueueQuery, ModerationStatus, SetFeatureFlagRequest, SetTeamFlagRequest, TeamLimitOverrides, TokenRateLimitRequest, UpdateAnnouncementRequest, UserContent, UserSummary}, queries::{AnnouncementQueries, ApiTokenQueries, AuditQueries, CounterQueries, EmailQueries, FeatureFlagQueries, JobQueries, ModerationQueries, ModerationQueueQueries, QuotaQueries, SignupCodeQueries, TaskCommentQueries, TaskQueries, TeamQueries, UserQueries}};
+use crate::database::timing::{self, QueryStats};
+use crate::flags;
+use crate::maintenance;
+use crate::mentions;
+use crate::quotas::{self, Limits};
+use crate::user_import;
+use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Path, Query};
+use crate::utils::i18n::Message;
+use crate::utils::rate_limit;
+use crate::utils::validation;
+use crate::websocket::events::WebSocketEvent;
+use crate::websocket::origin::Origin;
+use crate::wip;
+
+#[derive(Debug, Deserialize)]
+pub struct JobListQuery {
+    pub status: Option<JobStatus>,
+    pub job_type: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailListQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StorageReportQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricsResponse {
+    // Query function timings since this instance started
+    pub queries: Vec<QueryStats>,
+    pub slow_query_ms: u128,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub include_password_hashes: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceQuery {
+    // Limits checks and repairs to one project
+    pub project_id: Option<Uuid>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentQuery {
+    // Either end may be left open
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModerationReport {
+    pub dry_run: bool,
+    pub task_count: usize,
+    pub comment_count: usize,
+    #[serde(flatten)]
+    pub content: UserContent,
+}
+
+impl ModerationReport {
+    fn new(dry_run: bool, content: UserContent) -> Self {
+        ModerationReport { dry_run, task_count: content.tasks.len(), comment_count: content.comments.len(), content }
+    }
+}
+
+// Instance-level admin endpoints are limited to users in `instance_admins`,
+// and never available through an impersonation token
+pub async fn ensure_instance_admin(app_state: &crate::AppState, current_user: &CurrentUser) -> Result<(), AppError> {
+    current_user.ensure_not_impersonated()?;
+    if !UserQueries::is_instance_admin(app_state.database.pool(), current_user.id()).await? {
+        return Err(AppError::Forbidden("Instance admin access required".to_string()));
+    }
+
+    Ok(())
+}
+
+pub async fn list_jobs(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<JobListQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let jobs = JobQueries::list_jobs(
+        app_state.database.pool(),
+        query.status,
+        query.job_type.as_deref(),
+        limit,
+    ).await?;
+
+    Ok(Json(jobs))
+}
+
+pub async fn list_emails(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<EmailListQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let emails = EmailQueries::get_recent_emails(app_state.database.pool(), limit).await?;
+
+    Ok(Json(emails))
+}
+
+// The projects using the most attachment storage
+pub async fn get_storage_report(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<StorageReportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let limit = query.limit.unwrap_or(20).clamp(1, 500);
+    let projects = CounterQueries::get_top_storage_projects(app_state.database.pool(), limit).await?;
+
+    Ok(Json(projects))
+}
+
+// Per-instance, so a dashboard has to collect it from every instance
+pub async fn get_metrics(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    Ok(Json(MetricsResponse {
+        queries: timing::query_stats(),
+        slow_query_ms: timing::slow_query_threshold().as_millis(),
+    }))
+}
+
+pub async fn list_audit_log(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let entries = AuditQueries::get_recent_entries(app_state.database.pool(), limit).await?;
+
+    Ok(Json(entries))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImpersonationResponse {
+    pub access_token: String,
+    pub expires_in: i64,
+    pub user: UserSummary,
+}
+
+// Issues a short-lived access token that acts as `user_id`, for support to
+// see what the user sees. Requests made with it are audited and can't delete
+// anything (see auth::middleware).
+pub async fn impersonate_user(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    if user_id == current_user.id() {
+        return Err(AppError::Validation("Cannot impersonate yourself".to_string()));
+    }
+    let user = UserQueries::get_user_by_id(app_state.database.pool(), user_id).await?;
+    if !user.is_active {
+        return Err(AppError::Validation("Cannot impersonate a deactivated user".to_string()));
+    }
+
+    AuditQueries::record(
+        app_state.database.pool(),
+        AuditAction::ImpersonationStarted,
+        current_user.id(),
+        user.id,
+        None,
+        None,
+    ).await?;
+
+    let access_token = app_state.jwt_service
+        .generate_impersonation_token(user.id, &user.username, current_user.id())
+        .map_err(|e| AppError::InternalServer(format!("Failed to generate impersonation token: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(ImpersonationResponse {
+        access_token,
+        expires_in: app_state.jwt_service.get_impersonation_token_expiry(),
+        user: user.into(),
+    })))
+}
+
+#[derive(Debug, Serialize)]
+pub struct TeamLimitsResponse {
+    pub team_id: Uuid,
+    pub overrides: TeamLimitOverrides,
+    pub effective: Limits,
+}
+
+// Replaces the team's overrides; a null field falls back to the instance default
+pub async fn update_team_limits(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(team_id): Path<Uuid>,
+    Json(request): Json<TeamLimitOverrides>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    // Make sure the team exists
+    TeamQueries::get_team_by_id(app_state.database.pool(), team_id).await?;
+
+    let values = [
+        request.max_projects.map(i64::from),
+        request.max_tasks_per_project.map(i64::from),
+        request.max_members.map(i64::from),
+        request.max_attachment_bytes_per_project,
+    ];
+    if values.iter().flatten().any(|value| *value <= 0) {
+        return Err(AppError::Validation("Limits must be positive".to_string()));
+    }
+
+    QuotaQueries::set_team_limits(app_state.database.pool(), team_id, &request, current_user.id()).await?;
+
+    Ok(Json(TeamLimitsResponse {
+        team_id,
+        effective: quotas::team_limits(app_state.database.pool(), team_id).await?,
+        overrides: request,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenRateLimitResponse {
+    pub user_id: Uuid,
+    // None when the instance default applies
+    pub requests_per_hour: Option<i32>,
+    pub effective: u32,
+}
+
+// Sets how many requests per hour each of the user's API tokens may make
+pub async fn update_token_rate_limit(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(user_id): Path<Uuid>,
+    Json(request): Json<TokenRateLimitRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    if request.requests_per_hour.is_some_and(|limit| limit <= 0) {
+        return Err(AppError::Validation("Rate limit must be positive".to_string()));
+    }
+
+    ApiTokenQueries::set_rate_limit(app_state.database.pool(), user_id, request.requests_per_hour).await?;
+
+    Ok(Json(TokenRateLimitResponse {
+        user_id,
+        requests_per_hour: request.requests_per_hour,
+        effective: rate_limit::token_max_requests(request.requests_per_hour),
+    }))
+}
+
+pub async fn list_flags(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let flags = FeatureFlagQueries::list_flags(app_state.database.pool()).await?;
+
+    Ok(Json(flags))
+}
+
+// Creates the flag or sets its default for teams without an override
+pub async fn set_flag(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(name): Path<String>,
+    Json(request): Json<SetFeatureFlagRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+    flags::validate_flag_name(&name)?;
+
+    FeatureFlagQueries::set_flag(app_state.database.pool(), &name, &request, current_user.id()).await?;
+    app_state.flags.refresh().await?;
+
+    Ok(Json(app_state.flags.evaluate(None)))
+}
+
+pub async fn set_team_flag(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path((name, team_id)): Path<(String, Uuid)>,
+    Json(request): Json<SetTeamFlagRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    // Make sure the team exists
+    TeamQueries::get_team_by_id(app_state.database.pool(), team_id).await?;
+
+    FeatureFlagQueries::set_team_override(app_state.database.pool(), &name, team_id, request.enabled, current_user.id()).await?;
+    app_state.flags.refresh().await?;
+
+    Ok(Json(app_state.flags.evaluate(Some(team_id))))
+}
+
+// The team goes back to the flag's default
+pub async fn remove_team_flag(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path((name, team_id)): Path<(String, Uuid)>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    FeatureFlagQueries::remove_team_override(app_state.database.pool(), &name, team_id).await?;
+    app_state.flags.refresh().await?;
+
+    Ok(Json(app_state.flags.evaluate(Some(team_id))))
+}
+
+// Codes let people register while ALLOW_PUBLIC_REGISTRATION is off
+pub async fn create_signup_code(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<CreateSignupCodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    if request.max_uses.is_some_and(|max_uses| max_uses <= 0) {
+        return Err(AppError::Validation("max_uses must be positive".to_string()));
+    }
+    if request.expires_at.is_some_and(|expires_at| expires_at <= chrono::Utc::now()) {
+        return Err(AppError::Validation("expires_at must be in the future".to_string()));
+    }
+
+    let code = SignupCodeQueries::create_signup_code(
+        app_state.database.pool(),
+        &registration::generate_signup_code(),
+        request.max_uses,
+        request.expires_at,
+        current_user.id(),
+    ).await?;
+
+    Ok((StatusCode::CREATED, Json(code)))
+}
+
+// Full instance backup, streamed as NDJSON (see backup.rs for the format)
+pub async fn export_instance(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let options = ExportOptions {
+        team_id: None,
+        include_password_hashes: query.include_password_hashes,
+    };
+
+    Ok(backup::export_response(app_state.database.pool().clone(), options))
+}
+
+// Restores an instance or team archive into an instance without teams
+pub async fn import_archive(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let report = backup::import(app_state.database.pool(), &body, query.dry_run).await?;
+
+    let status = if query.dry_run { StatusCode::OK } else { StatusCode::CREATED };
+    Ok((status, Json(report)))
+}
+
+// Creates accounts from a CSV of email, username, display_name and optional
+// team, reporting on every row
+pub async fn import_users(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let rows = user_import::parse(&body)?;
+    let inviter = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?;
+    let policy = RegistrationPolicy::new(&app_state.config.features);
+    let report = user_import::import(app_state.database.pool(), &policy, rows, &inviter).await?;
+
+    Ok(Json(report))
+}
+
+pub async fn run_consistency_checks(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<MaintenanceQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let report = maintenance::check(app_state.database.pool(), &app_state.config.attachments, query.project_id).await?;
+
+    Ok(Json(report))
+}
+
+// Fixes what the repairable checks find; dry_run only reports it
+pub async fn repair_consistency(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<MaintenanceQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let report = maintenance::repair(app_state.database.pool(), &app_state.config.attachments, query.project_id, query.dry_run).await?;
+
+    Ok(Json(report))
+}
+
+async fn moderation_target(app_state: &crate::AppState, current_user: &CurrentUser, user_id: Uuid, query: &ContentQuery) -> Result<(), AppError> {
+    ensure_instance_admin(app_state, current_user).await?;
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from >= to {
+            return Err(AppError::Invalid(Message::new("invalid_date_range")));
+        }
+    }
+
+    // Make sure the user exists
+    UserQueries::get_user_by_id(app_state.database.pool(), user_id).await?;
+    Ok(())
+}
+
+// Hides the user's tasks and comments in the range from everyone but instance
+// admins, and tells open boards to drop them
+pub async fn quarantine_user_content(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(user_id): Path<Uuid>,
+    Query(query): Query<ContentQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    moderation_target(&app_state, &current_user, user_id, &query).await?;
+
+    let pool = app_state.database.pool();
+    if query.dry_run {
+        let content = ModerationQueries::find_content(pool, user_id, query.from, query.to, false).await?;
+        return Ok(Json(ModerationReport::new(true, content)));
+    }
+
+    let content = ModerationQueries::set_quarantined(pool, user_id, query.from, query.to, true).await?;
+    AuditQueries::record(pool, AuditAction::ContentQuarantined, current_user.id(), user_id, None, None).await?;
+    tracing::info!("Quarantined {} tasks and {} comments by user {}", content.tasks.len(), content.comments.len(), user_id);
+
+    // Comments on quarantined tasks go with the task on the client
+    for task in &content.tasks {
+        let event = WebSocketEvent::TaskDeleted { task_id: task.id, project_id: task.project_id, client_mutation_id: None };
+        app_state.websocket.broadcast_to_project(task.project_id, event, None).await;
+    }
+    for comment in &content.comments {
+        let event = WebSocketEvent::CommentDeleted {
+            comment_id: comment.id,
+            task_id: comment.task_id,
+            project_id: comment.project_id,
+            client_mutation_id: None,
+        };
+        app_state.websocket.broadcast_to_project(comment.project_id, event, None).await;
+    }
+
+    Ok(Json(ModerationReport::new(false, content)))
+}
+
+// Undoes a quarantine; clients see the content again on their next fetch
+pub async fn restore_user_content(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(user_id): Path<Uuid>,
+    Query(query): Query<ContentQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    moderation_target(&app_state, &current_user, user_id, &query).await?;
+
+    let pool = app_state.database.pool();
+    if query.dry_run {
+        let content = ModerationQueries::find_content(pool, user_id, query.from, query.to, true).await?;
+        return Ok(Json(ModerationReport::new(true, content)));
+    }
+
+    let content = ModerationQueries::set_quarantined(pool, user_id, query.from, query.to, false).await?;
+    AuditQueries::record(pool, AuditAction::ContentRestored, current_user.id(), user_id, None, None).await?;
+
+    Ok(Json(ModerationReport::new(false, content)))
+}
+
+// Deletes quarantined content for good once it has been reviewed. Content
+// that isn't quarantined is never touched.
+pub async fn purge_user_content(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(user_id): Path<Uuid>,
+    Query(query): Query<ContentQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    moderation_target(&app_state, &current_user, user_id, &query).await?;
+
+    let pool = app_state.database.pool();
+    if query.dry_run {
+        let content = ModerationQueries::find_content(pool, user_id, query.from, query.to, true).await?;
+        return Ok(Json(ModerationReport::new(true, content)));
+    }
+
+    let content = ModerationQueries::purge_quarantined(pool, user_id, query.from, query.to).await?;
+    AuditQueries::record(pool, AuditAction::ContentPurged, current_user.id(), user_id, None, None).await?;
+    tracing::info!("Purged {} tasks and {} comments by user {}", content.tasks.len(), content.comments.len(), user_id);
+
+    Ok(Json(ModerationReport::new(false, content)))
+}
+
+// Content screening held, oldest first; pending unless asked otherwise
+pub async fn list_held_content(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<ModerationQueueQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let status = query.status.unwrap_or(ModerationStatus::Pending);
+    let items = ModerationQueueQueries::list(app_state.database.pool(), status, limit).await?;
+
+    Ok(Json(items))
+}
+
+// Publishes held content as if it had just been created, keeping its
+// original timestamp
+pub async fn approve_held_content(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(item_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let pool = app_state.database.pool();
+    let item = ModerationQueueQueries::get(pool, item_id).await?;
+    let Some(author_id) = item.author.as_ref().map(|author| author.id) else {
+        return Err(AppError::Conflict("The author's account no longer exists".to_string()));
+    };
+    let task = TaskQueries::get_task_by_id(pool, item.task_id).await?;
+    // Counted while the task is still held, so the column goes up by one
+    let wip_check = wip::count_create(pool, task.project_id, task.status).await?;
+
+    if !ModerationQueueQueries::review(pool, item_id, current_user.id(), ModerationStatus::Approved).await? {
+        return Err(AppError::Conflict("Already reviewed".to_string()));
+    }
+    AuditQueries::record(pool, AuditAction::ContentApproved, current_user.id(), author_id, None, None).await?;
+
+    let origin = Origin::default();
+    match (item.content_type, item.comment_id) {
+        (HeldContentType::Comment, Some(comment_id)) => {
+            let comment = TaskCommentQueries::get_comment_by_id(pool, comment_id).await?;
+            let mentions = mentions::parse(&comment.content);
+            crate::api::comments::announce_created_comment(&app_state, &task, &comment, &mentions, &origin).await?;
+        }
+        _ => {
+            let description = task.description.as_deref().unwrap_or_default();
+            crate::api::tasks::announce_created_task(&app_state, author_id, &origin, &task, None, &wip_check).await?;
+            mentions::notify(&app_state, &task, None, description, &mentions::parse(description), author_id).await;
+        }
+    }
+
+    Ok(Json(ModerationQueueQueries::get(pool, item_id).await?))
+}
+
+// The content stays quarantined, so it can be purged with the rest of the
+// author's quarantined content
+pub async fn reject_held_content(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(item_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let pool = app_state.database.pool();
+    let item = ModerationQueueQueries::get(pool, item_id).await?;
+    if !ModerationQueueQueries::review(pool, item_id, current_user.id(), ModerationStatus::Rejected).await? {
+        return Err(AppError::Conflict("Already reviewed".to_string()));
+    }
+    if let Some(author) = &item.author {
+        AuditQueries::record(pool, AuditAction::ContentRejected, current_user.id(), author.id, None, None).await?;
+    }
+
+    Ok(Json(ModerationQueueQueries::get(pool, item_id).await?))
+}
+
+fn validate_announcement(message: &str, starts_at: DateTime<Utc>, ends_at: Option<DateTime<Utc>>) -> Result<(), AppError> {
+    validation::validate_announcement_message(message)?;
+    if ends_at.is_some_and(|ends_at| ends_at <= starts_at) {
+        return Err(AppError::Invalid(Message::new("invalid_date_range")));
+    }
+    Ok(())
+}
+
+pub async fn list_announcements(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let announcements = AnnouncementQueries::list_announcements(app_state.database.pool()).await?;
+
+    Ok(Json(announcements))
+}
+
+// Pushed to every open connection right away, even when it starts later
+pub async fn create_announcement(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<CreateAnnouncementRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let starts_at = request.starts_at.unwrap_or_else(Utc::now);
+    validate_announcement(&request.message, starts_at, request.ends_at)?;
+
+    let announcement = AnnouncementQueries::create_announcement(
+        app_state.database.pool(),
+        request.message.trim(),
+        request.severity,
+        starts_at,
+        request.ends_at,
+        request.dismissible.unwrap_or(true),
+        current_user.id(),
+    ).await?;
+
+    app_state.websocket.broadcast_to_all(WebSocketEvent::Announcement(announcement.clone())).await;
+
+    Ok((StatusCode::CREATED, Json(announcement)))
+}
+
+pub async fn update_announcement(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(announcement_id): Path<Uuid>,
+    Json(request): Json<UpdateAnnouncementRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    let pool = app_state.database.pool();
+    let mut announcement = AnnouncementQueries::get_announcement(pool, announcement_id).await?;
+    if let Some(message) = request.message {
+        announcement.message = message.trim().to_string();
+    }
+    if let Some(severity) = request.severity {
+        announcement.severity = severity;
+    }
+    if let Some(starts_at) = request.starts_at {
+        announcement.starts_at = starts_at;
+    }
+    if let Some(ends_at) = request.ends_at {
+        announcement.ends_at = ends_at;
+    }
+    if let Some(dismissible) = request.dismissible {
+        announcement.dismissible = dismissible;
+    }
+    validate_announcement(&announcement.message, announcement.starts_at, announcement.ends_at)?;
+
+    let announcement = AnnouncementQueries::update_announcement(pool, &announcement).await?;
+
+    // Clients replace the banner with the same id, or drop it once ends_at has passed
+    app_state.websocket.broadcast_to_all(WebSocketEvent::Announcement(announcement.clone())).await;
+
+    Ok(Json(announcement))
+}
+
+pub async fn delete_announcement(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(announcement_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_instance_admin(&app_state, &current_user).await?;
+
+    AnnouncementQueries::delete_announcement(app_state.database.pool(), announcement_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,40 @@
+// Announcements as seen by signed-in users. Instance admins manage them through
+// the admin API (see api::admin).
+
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use uuid::Uuid;
+
+use crate::auth::middleware::CurrentUser;
+use crate::database::queries::AnnouncementQueries;
+use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Path};
+
+// Running announcements the user hasn't dismissed, latest start first
+pub async fn get_active_announcements(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let announcements = AnnouncementQueries::get_active_announcements(app_state.database.pool(), current_user.id()).await?;
+
+    Ok(Json(announcements))
+}
+
+pub async fn dismiss_announcement(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(announcement_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    let announcement = AnnouncementQueries::get_announcement(pool, announcement_id).await?;
+    if !announcement.dismissible {
+        return Err(AppError::Validation("This announcement can't be dismissed".to_string()));
+    }
+
+    AnnouncementQueries::dismiss_announcement(pool, current_user.id(), announcement_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
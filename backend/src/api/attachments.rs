@@ -0,0 +1,80 @@
+use axum::{
+    extract::{Extension, Request, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::attachments;
+use crate::config::DownloadMode;
+use crate::auth::authz::{self, Permission, Resource};
+use crate::auth::middleware::CurrentUser;
+use crate::database::models::AttachmentScanStatus;
+use crate::database::queries::{AttachmentQueries, TaskQueries};
+use crate::utils::errors::AppError;
+use crate::utils::extractors::{Path, Query};
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    // Instance admins can fetch pending or quarantined files anyway
+    #[serde(default)]
+    pub override_scan: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignedDownloadQuery {
+    pub expires: i64,
+    pub signature: String,
+}
+
+pub async fn download_attachment(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(attachment_id): Path<Uuid>,
+    Query(query): Query<DownloadQuery>,
+    request: Request,
+) -> Result<Response, AppError> {
+    let pool = app_state.database.pool();
+    let attachment = AttachmentQueries::get_attachment(pool, attachment_id).await?;
+    let task = TaskQueries::get_task_by_id(pool, attachment.task_id).await?;
+
+    authz::require_resource_role(&app_state, Resource::Attachment, task.project_id, current_user.id(), Permission::ViewProject).await?;
+
+    if attachment.scan_status.blocks_download() {
+        if query.override_scan {
+            crate::api::admin::ensure_instance_admin(&app_state, &current_user).await?;
+        } else if attachment.scan_status == AttachmentScanStatus::Pending {
+            return Err(AppError::Locked("Attachment is still being scanned for viruses".to_string()));
+        } else {
+            return Err(AppError::Locked("Attachment was quarantined because it is infected".to_string()));
+        }
+    }
+
+    let config = &app_state.config.attachments;
+    match config.download_mode {
+        DownloadMode::Signed => {
+            let expires = chrono::Utc::now().timestamp() + config.signed_url_ttl_seconds;
+            let url = attachments::signed_download_url(config, attachment.id, expires);
+            Ok((StatusCode::FOUND, [(header::LOCATION, url)]).into_response())
+        }
+        DownloadMode::Proxy => Ok(attachments::serve_attachment(config, &attachment, request, attachments::PROXY_CACHE_CONTROL).await),
+    }
+}
+
+// Public: the signature from download_attachment is the permission check
+pub async fn download_signed_attachment(
+    State(app_state): State<crate::AppState>,
+    Path(attachment_id): Path<Uuid>,
+    Query(query): Query<SignedDownloadQuery>,
+    request: Request,
+) -> Result<Response, AppError> {
+    let config = &app_state.config.attachments;
+    if !attachments::verify_signed_download(config, attachment_id, query.expires, &query.signature) {
+        return Err(AppError::Forbidden("Download link is invalid or has expired".to_string()));
+    }
+
+    let attachment = AttachmentQueries::get_attachment(app_state.database.pool(), attachment_id).await?;
+
+    Ok(attachments::serve_attachment(config, &attachment, request, attachments::SIGNED_CACHE_CONTROL).await)
+}
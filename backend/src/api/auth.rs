@@ -1,22 +1,85 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
 use axum::{
     extract::State,
     http::StatusCode,
     response::IntoResponse,
-    Json,
 };
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::time::Duration;
+use tracing::warn;
 
-use crate::auth::{jwt::JwtService, password};
-use crate::database::{connection::Database, models::{CreateUserRequest, LoginRequest, LoginResponse, UserSummary}, queries::UserQueries};
-use crate::utils::{errors::AppError, validation};
+use crate::auth::{jwt::{JwtService, TokenError}, password, registration::{self, RegistrationPolicy}, tokens};
+use crate::database::{connection::Database, models::{CreateUserRequest, LoginRequest, LoginResponse, UserSummary}, queries::{PasswordResetQueries, UserQueries}};
+use crate::invitations;
+use crate::utils::{
+    errors::{AppError, FieldError},
+    extractors::{Json, Query},
+    rate_limit::{self, ClientIp},
+    validation,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct AvailabilityQuery {
+    pub username: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AvailabilityResponse {
+    pub available: bool,
+    // Format problems, so the form can show them inline
+    pub errors: Vec<FieldError>,
+}
+
+fn field_errors(field: &str, result: Result<(), AppError>) -> Vec<FieldError> {
+    match result {
+        Err(AppError::Invalid(reason)) => vec![FieldError::new(field, reason)],
+        _ => Vec::new(),
+    }
+}
+
+// Unauthenticated pre-registration check for one username or email. Rate limited
+// per IP and slowed by a random delay to make enumeration expensive; with
+// PRIVACY_STRICT emails are always reported available.
+pub async fn check_availability(
+    State(app_state): State<crate::AppState>,
+    ClientIp(ip): ClientIp,
+    Query(query): Query<AvailabilityQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    rate_limit::availability_limiter().check(ip)?;
+
+    // 50-250ms, so response times don't tell taken from free
+    tokio::time::sleep(Duration::from_millis(50 + u64::from(OsRng.next_u32() % 200))).await;
+
+    let pool = app_state.database.pool();
+    let (errors, taken) = match (query.username, query.email) {
+        (Some(username), None) => {
+            let errors = field_errors("username", validation::validate_username(&username));
+            let taken = errors.is_empty() && UserQueries::check_username_exists(pool, &username).await?;
+            (errors, taken)
+        }
+        (None, Some(email)) => {
+            let errors = field_errors("email", validation::validate_email(&email));
+            let taken = errors.is_empty()
+                && !app_state.config.features.privacy_strict
+                && UserQueries::check_email_exists(pool, &email).await?;
+            (errors, taken)
+        }
+        _ => return Err(AppError::Validation("Pass either username or email".to_string())),
+    };
+
+    Ok(Json(AvailabilityResponse { available: errors.is_empty() && !taken, errors }))
+}
 
 pub async fn register(
     State(app_state): State<crate::AppState>,
-    Json(request): Json<CreateUserRequest>,
+    Json(mut request): Json<CreateUserRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let db = &app_state.database;
     let jwt_service = &app_state.jwt_service;
     // Validate input
+    request.display_name = validation::normalize_name(&request.display_name);
     validation::validate_email(&request.email)?;
     validation::validate_username(&request.username)?;
     validation::validate_password(&request.password)?;
@@ -28,9 +91,27 @@ pub async fn register(
     }
 
     if UserQueries::check_username_exists(db.pool(), &request.username).await? {
-        return Err(AppError::Conflict("Username already exists".to_string()));
+        return Err(AppError::Conflict("Username is taken or too similar to an existing one".to_string()));
     }
 
+    // Checked up front so a bad token doesn't leave an account behind
+    let invitation = match request.invitation.as_deref() {
+        Some(token) => Some(
+            invitations::find(db.pool(), token)
+                .await?
+                .ok_or_else(|| AppError::Validation("Invitation is invalid, expired or already used".to_string()))?,
+        ),
+        None => None,
+    };
+
+    registration::check_can_register(
+        db.pool(),
+        &RegistrationPolicy::new(&app_state.config.features),
+        &request.email,
+        request.signup_code.as_deref(),
+        invitation.is_some(),
+    ).await?;
+
     // Hash password
     let password_hash = password::hash_password(&request.password)
         .map_err(|e| AppError::InternalServer(format!("Failed to hash password: {}", e)))?;
@@ -38,6 +119,14 @@ pub async fn register(
     // Create user
     let user = UserQueries::create_user(db.pool(), &request, &password_hash).await?;
 
+    // The account exists either way; an invitation used up in the meantime
+    // only means the user isn't added to the project
+    if let Some(token) = request.invitation.as_deref() {
+        if let Err(e) = invitations::accept(&app_state, token, user.id).await {
+            warn!("Failed to accept the invitation of new user {}: {}", user.id, e);
+        }
+    }
+
     // Generate tokens
     let access_token = jwt_service
         .generate_access_token(user.id, &user.username)
@@ -115,7 +204,10 @@ pub async fn refresh_token(
     // Verify refresh token
     let claims = jwt_service
         .verify_token(refresh_token)
-        .map_err(|_| AppError::Unauthorized("Invalid or expired refresh token".to_string()))?;
+        .map_err(|e| match e {
+            TokenError::Expired => AppError::Unauthorized("Refresh token expired".to_string()),
+            TokenError::Invalid(_) => AppError::Unauthorized("Invalid refresh token".to_string()),
+        })?;
 
     // Verify it's a refresh token
     if !matches!(claims.token_type, crate::auth::jwt::TokenType::Refresh) {
@@ -143,6 +235,30 @@ pub async fn refresh_token(
     Ok(Json(response))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+// Sets the password with the token from a password reset email, such as the
+// one imported accounts are invited with. The token works once.
+pub async fn reset_password(
+    State(app_state): State<crate::AppState>,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    validation::validate_password(&request.password)?;
+
+    let password_hash = password::hash_password(&request.password)
+        .map_err(|e| AppError::InternalServer(format!("Failed to hash password: {}", e)))?;
+
+    PasswordResetQueries::reset_password(app_state.database.pool(), &tokens::hash_token(&request.token), &password_hash)
+        .await?
+        .ok_or_else(|| AppError::Validation("Reset link is invalid, expired or already used".to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn logout() -> Result<impl IntoResponse, AppError> {
     // For JWT-based auth, logout is typically handled client-side
     // by removing the tokens from storage
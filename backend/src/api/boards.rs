@@ -1,51 +1,76 @@
 use axum::{
-    extract::{Extension, State, Path},
-    response::IntoResponse,
-    Json,
-    http::StatusCode,
+    extract::{Extension, State},
+    response::{IntoResponse, Response},
+    http::{header, HeaderMap, StatusCode},
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::auth::authz::{self, Permission, ProjectEditor, ProjectMember, Resource};
 use crate::auth::middleware::CurrentUser;
+use crate::board_export::BoardExport;
 use crate::database::{
-    models::{CreateBoardRequest, UpdateBoardRequest, Board, TaskStatus, UserSummary},
+    models::{AutomationAction, BoardAutomations, CreateBoardRequest, UpdateBoardRequest, Board, Task, TaskGroupCount, TaskListFilter, TaskStatus, UserSummary},
     queries::{BoardQueries, ProjectQueries, TaskQueries, UserQueries}
 };
-use crate::utils::errors::AppError;
+use crate::quick_switch;
+use crate::swimlanes::{self, Swimlane};
+use crate::utils::errors::{AppError, FieldError};
+use crate::utils::etag::ETag;
+use crate::utils::extractors::{Json, Path, Query};
+use crate::utils::fields::SparseFields;
+use crate::utils::i18n::Message;
 use crate::utils::validation;
 use crate::websocket::events::{WebSocketEvent, BoardEventData};
+use crate::websocket::origin::{Mutation, Origin};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BoardWithTasks {
     #[serde(flatten)]
     pub board: Board,
-    pub tasks: Vec<crate::database::models::Task>,
+    pub tasks: Vec<Task>,
+    // Tasks per status column, for the column headers
+    pub task_counts: Vec<TaskGroupCount>,
+    // Rows from the board's swimlane config; omitted when the board has none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub swimlanes: Vec<Swimlane>,
+    // Who has the board open right now; kept current by BoardViewersChanged
+    #[serde(default)]
+    pub viewers: Vec<UserSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BoardDetailsQuery {
+    pub fields: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BoardExportQuery {
+    pub format: Option<String>,
 }
 
 pub async fn create_board(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
-    Path(project_id): Path<Uuid>,
-    Json(request): Json<CreateBoardRequest>,
+    ProjectEditor(project_id): ProjectEditor,
+    origin: Origin,
+    Json(Mutation { body: request, client_mutation_id }): Json<Mutation<CreateBoardRequest>>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project member (at least editor role required)
-    let user_role = ProjectQueries::get_user_project_role(
-        app_state.database.pool(),
-        project_id,
-        current_user.id(),
-    ).await?;
-
-    use crate::database::models::ProjectRole;
-    if !matches!(user_role, Some(ProjectRole::Admin) | Some(ProjectRole::Editor)) {
-        return Err(AppError::Forbidden("Need editor or admin role to create boards".to_string()));
-    }
-
+    let origin = origin.with_body(client_mutation_id)?;
     // Validate input
     validation::validate_board_name(&request.name)?;
     if let Some(ref description) = request.description {
         validation::validate_board_description(description)?;
     }
+    if let Some(ref columns) = request.columns {
+        validation::validate_board_columns(columns)?;
+    }
+    if let Some(ref config) = request.config {
+        validation::validate_board_config(config)?;
+    }
+    if let Some(ref swimlane_config) = request.swimlane_config {
+        validation::validate_swimlane_config(swimlane_config)?;
+    }
 
     let board = BoardQueries::create_board(
         app_state.database.pool(),
@@ -53,32 +78,88 @@ pub async fn create_board(
         &request,
         current_user.id(),
     ).await?;
+    announce_created_board(&app_state, &board, current_user.id(), &origin).await?;
 
-    // Broadcast board creation to WebSocket subscribers
-    let user = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?;
+    Ok((StatusCode::CREATED, Json(board)))
+}
+
+// Broadcast board creation to WebSocket subscribers
+async fn announce_created_board(
+    app_state: &crate::AppState,
+    board: &Board,
+    user_id: Uuid,
+    origin: &Origin,
+) -> Result<(), AppError> {
+    let user = UserQueries::get_user_by_id(app_state.database.pool(), user_id).await?;
     let user_summary: UserSummary = user.into();
-    
+
     let event = WebSocketEvent::BoardCreated(BoardEventData {
         board: board.clone(),
-        project_id,
+        project_id: board.project_id,
         user: user_summary,
+        client_mutation_id: origin.client_mutation_id.clone(),
     });
-    
-    app_state.websocket.broadcast_to_project(project_id, event, Some(current_user.id())).await;
 
-    Ok((StatusCode::CREATED, Json(board)))
+    app_state.websocket.broadcast_to_project(board.project_id, event, origin.connection_id).await;
+    Ok(())
 }
 
-pub async fn get_project_boards(
+// The board's setup as YAML (see board_export), for importing elsewhere
+pub async fn export_board(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
-    Path(project_id): Path<Uuid>,
-) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project member
-    if !ProjectQueries::is_project_member(app_state.database.pool(), project_id, current_user.id()).await? {
-        return Err(AppError::Forbidden("Must be a project member to view boards".to_string()));
+    Path(board_id): Path<Uuid>,
+    Query(query): Query<BoardExportQuery>,
+) -> Result<Response, AppError> {
+    let format = query.format.as_deref().unwrap_or("yaml");
+    if format != "yaml" {
+        return Err(AppError::Invalid(Message::new("unsupported_export_format").with("format", format)));
     }
 
+    let board = BoardQueries::get_board_by_id(app_state.database.pool(), board_id).await?;
+    authz::require_resource_role(&app_state, Resource::Board, board.project_id, current_user.id(), Permission::ViewProject).await?;
+
+    let yaml = BoardExport::from_board(&board).to_yaml()?;
+    let slug: String = board
+        .name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let filename = if slug.is_empty() { "board".to_string() } else { slug };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/yaml".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.yaml\"", filename)),
+        ],
+        yaml,
+    )
+        .into_response())
+}
+
+// Creates a board from an export; the body is the YAML document itself
+pub async fn import_board(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    ProjectEditor(project_id): ProjectEditor,
+    origin: Origin,
+    body: String,
+) -> Result<impl IntoResponse, AppError> {
+    let request = BoardExport::from_yaml(&body)?.into_create_request();
+    validation::validate_board_fields(&request)?;
+
+    let board = BoardQueries::create_board(app_state.database.pool(), project_id, &request, current_user.id()).await?;
+    announce_created_board(&app_state, &board, current_user.id(), &origin).await?;
+
+    Ok((StatusCode::CREATED, Json(board)))
+}
+
+pub async fn get_project_boards(
+    State(app_state): State<crate::AppState>,
+    ProjectMember(project_id): ProjectMember,
+) -> Result<impl IntoResponse, AppError> {
     let boards = BoardQueries::get_project_boards(app_state.database.pool(), project_id).await?;
 
     Ok(Json(boards))
@@ -88,52 +169,87 @@ pub async fn get_board_details(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
     Path(board_id): Path<Uuid>,
-) -> Result<impl IntoResponse, AppError> {
+    Query(query): Query<BoardDetailsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
     let board = BoardQueries::get_board_by_id(app_state.database.pool(), board_id).await?;
 
-    // Check if user is project member
-    if !ProjectQueries::is_project_member(app_state.database.pool(), board.project_id, current_user.id()).await? {
-        return Err(AppError::Forbidden("Not a project member".to_string()));
+    authz::require_resource_role(&app_state, Resource::Board, board.project_id, current_user.id(), Permission::ViewProject).await?;
+    quick_switch::record_access(app_state.database.pool(), current_user.id(), board.project_id).await;
+
+    // Sparse fieldsets apply to the embedded tasks, the board itself is small
+    let fields = SparseFields::parse(query.fields.as_deref(), Task::FIELDS)?;
+
+    let last_modified = ProjectQueries::get_project_last_modified(app_state.database.pool(), board.project_id).await?;
+    let etag = ETag::weak_from_timestamp(last_modified);
+    if etag.matches(&headers) {
+        return Ok(etag.not_modified());
     }
 
     // Get all tasks for this project to show on the board
-    let tasks = TaskQueries::get_project_tasks(app_state.database.pool(), board.project_id).await?;
+    let tasks = TaskQueries::get_project_tasks(app_state.database.pool(), board.project_id, false).await?;
+    let task_counts = TaskQueries::count_tasks_by_status(app_state.database.pool(), board.project_id, &TaskListFilter::default()).await?;
+
+    let swimlanes = swimlanes::group_tasks(&board.swimlane_config, &tasks);
+
+    let viewer_ids = app_state.websocket.board_viewers(board.id).await;
+    let viewers = if viewer_ids.is_empty() {
+        Vec::new()
+    } else {
+        ProjectQueries::get_project_members(app_state.database.pool(), board.project_id)
+            .await?
+            .into_iter()
+            .filter_map(|(_, user)| viewer_ids.contains(&user.id).then_some(user))
+            .collect()
+    };
 
     let board_with_tasks = BoardWithTasks {
         board,
         tasks,
+        task_counts,
+        swimlanes,
+        viewers,
     };
 
-    Ok(Json(board_with_tasks))
+    let mut body = serde_json::to_value(&board_with_tasks)?;
+    if let Some(fields) = &fields {
+        if let Some(tasks) = body.get_mut("tasks") {
+            *tasks = fields.apply(tasks.take());
+        }
+    }
+
+    Ok(etag.json(body))
 }
 
 pub async fn update_board(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
     Path(board_id): Path<Uuid>,
-    Json(request): Json<UpdateBoardRequest>,
+    origin: Origin,
+    Json(Mutation { body: request, client_mutation_id }): Json<Mutation<UpdateBoardRequest>>,
 ) -> Result<impl IntoResponse, AppError> {
+    let origin = origin.with_body(client_mutation_id)?;
     let board = BoardQueries::get_board_by_id(app_state.database.pool(), board_id).await?;
 
-    // Check if user is project member (at least editor role required)
-    let user_role = ProjectQueries::get_user_project_role(
-        app_state.database.pool(),
-        board.project_id,
-        current_user.id(),
-    ).await?;
-
-    use crate::database::models::ProjectRole;
-    if !matches!(user_role, Some(ProjectRole::Admin) | Some(ProjectRole::Editor)) {
-        return Err(AppError::Forbidden("Need editor or admin role to update boards".to_string()));
-    }
+    authz::require_resource_role(&app_state, Resource::Board, board.project_id, current_user.id(), Permission::EditBoards).await?;
 
     // Validate input
     if let Some(ref name) = request.name {
         validation::validate_board_name(name)?;
     }
-    if let Some(ref description) = request.description {
+    if let Some(Some(ref description)) = request.description {
         validation::validate_board_description(description)?;
     }
+    // Renaming a column only changes its label; tasks stay in their status column
+    if let Some(ref columns) = request.columns {
+        validation::validate_board_columns(columns)?;
+    }
+    if let Some(ref config) = request.config {
+        validation::validate_board_config(config)?;
+    }
+    if let Some(ref swimlane_config) = request.swimlane_config {
+        validation::validate_swimlane_config(swimlane_config)?;
+    }
 
     let updated_board = BoardQueries::update_board(app_state.database.pool(), board_id, &request).await?;
 
@@ -145,41 +261,86 @@ pub async fn update_board(
         board: updated_board.clone(),
         project_id: board.project_id,
         user: user_summary,
+        client_mutation_id: origin.client_mutation_id,
     });
     
-    app_state.websocket.broadcast_to_project(board.project_id, event, Some(current_user.id())).await;
+    app_state.websocket.broadcast_to_project(board.project_id, event, origin.connection_id).await;
 
     Ok(Json(updated_board))
 }
 
-pub async fn delete_board(
+// The board's automation rules (see automations.rs)
+pub async fn get_board_automations(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
     Path(board_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    let board = BoardQueries::get_board_by_id(app_state.database.pool(), board_id).await?;
+    let pool = app_state.database.pool();
+    let board = BoardQueries::get_board_by_id(pool, board_id).await?;
+    authz::require_resource_role(&app_state, Resource::Board, board.project_id, current_user.id(), Permission::ViewProject).await?;
 
-    // Check if user is project admin
-    let user_role = ProjectQueries::get_user_project_role(
-        app_state.database.pool(),
-        board.project_id,
-        current_user.id(),
-    ).await?;
+    let automations = BoardQueries::get_board_automations(pool, board_id).await?;
+    Ok(Json(automations))
+}
 
-    use crate::database::models::ProjectRole;
-    if !matches!(user_role, Some(ProjectRole::Admin)) {
-        return Err(AppError::Forbidden("Only project admins can delete boards".to_string()));
+// Replaces the board's automation rules. Rules sent without an id get one;
+// keeping a rule's id keeps its place in the round-robin rotation.
+pub async fn update_board_automations(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(board_id): Path<Uuid>,
+    Json(mut request): Json<BoardAutomations>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    let board = BoardQueries::get_board_by_id(pool, board_id).await?;
+    authz::require_resource_role(&app_state, Resource::Board, board.project_id, current_user.id(), Permission::ManageProject).await?;
+
+    validation::validate_board_automations(&mut request)?;
+
+    let mut errors = Vec::new();
+    for (index, rule) in request.rules.iter().enumerate() {
+        for (action_index, action) in rule.actions.iter().enumerate() {
+            let AutomationAction::AssignRoundRobin { user_ids } = action else {
+                continue;
+            };
+            for user_id in user_ids {
+                if !ProjectQueries::is_project_member(pool, board.project_id, *user_id).await? {
+                    errors.push(FieldError::new(
+                        &format!("rules[{}].actions[{}].user_ids", index, action_index),
+                        Message::new("automation_user_not_member").with("user", user_id.to_string()),
+                    ));
+                }
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(AppError::InvalidFields(errors));
     }
 
+    BoardQueries::update_board_automations(pool, board_id, &request).await?;
+    Ok(Json(request))
+}
+
+pub async fn delete_board(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(board_id): Path<Uuid>,
+    origin: Origin,
+) -> Result<impl IntoResponse, AppError> {
+    let board = BoardQueries::get_board_by_id(app_state.database.pool(), board_id).await?;
+
+    authz::require_resource_role(&app_state, Resource::Board, board.project_id, current_user.id(), Permission::DeleteBoards).await?;
+
     BoardQueries::delete_board(app_state.database.pool(), board_id).await?;
 
     // Broadcast board deletion to WebSocket subscribers
     let event = WebSocketEvent::BoardDeleted { 
         board_id, 
-        project_id: board.project_id 
+        project_id: board.project_id,
+        client_mutation_id: origin.client_mutation_id,
     };
     
-    app_state.websocket.broadcast_to_project(board.project_id, event, Some(current_user.id())).await;
+    app_state.websocket.broadcast_to_project(board.project_id, event, origin.connection_id).await;
 
     Ok(StatusCode::NO_CONTENT)
 }
\ No newline at end of file
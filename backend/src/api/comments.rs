@@ -1,20 +1,28 @@
 use axum::{
-    extract::{Extension, State, Path},
-    response::IntoResponse,
-    Json,
+    extract::{Extension, State},
+    response::{IntoResponse, Response},
     http::StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::auth::authz::{self, Permission, Resource};
 use crate::auth::middleware::CurrentUser;
 use crate::database::{
-    models::{CreateTaskCommentRequest, TaskComment, UserSummary},
-    queries::{TaskCommentQueries, TaskQueries, ProjectQueries, UserQueries}
+    models::{PendingModeration, CommentContext, ConvertCommentRequest, CreateTaskCommentRequest, CreateTaskRequest, Task, TaskComment, TaskRelationType, TaskStatus, UserSummary},
+    queries::{ModerationQueueQueries, ProjectQueries, TaskCommentQueries, TaskMentionQueries, TaskQueries, TaskRelationQueries, UserQueries}
 };
+use crate::integrations::slack::{self, blocks::Notification};
+use crate::mentions::{self, Mentions};
+use crate::notifications;
+use crate::screening;
 use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Path, Query};
+use crate::utils::search::{self, Snippet};
 use crate::utils::validation;
 use crate::websocket::events::{WebSocketEvent, CommentEventData};
+use crate::websocket::origin::{Mutation, Origin};
+use crate::wip;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TaskCommentResponse {
@@ -22,25 +30,33 @@ pub struct TaskCommentResponse {
     pub task_id: Uuid,
     pub user: UserSummary,
     pub content: String,
+    // Posted by a scheduled report; pinned ones are listed first
+    pub is_system: bool,
+    pub pinned: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    // Held by content screening, only listed for its author
+    pub pending_moderation: bool,
 }
 
 pub async fn create_task_comment(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
     Path(task_id): Path<Uuid>,
-    Json(request): Json<CreateTaskCommentRequest>,
-) -> Result<impl IntoResponse, AppError> {
+    origin: Origin,
+    Json(Mutation { body: request, client_mutation_id }): Json<Mutation<CreateTaskCommentRequest>>,
+) -> Result<Response, AppError> {
+    let origin = origin.with_body(client_mutation_id)?;
     let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
 
-    // Check if user is project member
-    if !ProjectQueries::is_project_member(app_state.database.pool(), task.project_id, current_user.id()).await? {
-        return Err(AppError::Forbidden("Must be a project member to comment on tasks".to_string()));
-    }
+    authz::require_resource_role(&app_state, Resource::Task, task.project_id, current_user.id(), Permission::Comment).await?;
 
     // Validate input
     validation::validate_task_comment(&request.content)?;
+    let mentions = mentions::parse(&request.content);
+    mentions::check(app_state.database.pool(), task.project_id, current_user.id(), &mentions).await?;
+    let project = ProjectQueries::get_project_by_id(app_state.database.pool(), task.project_id).await?;
+    let held = screening::check(&app_state, project.team_id, &request.content).await;
 
     let comment = TaskCommentQueries::create_comment(
         app_state.database.pool(),
@@ -49,35 +65,148 @@ pub async fn create_task_comment(
         &request,
     ).await?;
 
+    if let Some(screening) = held {
+        ModerationQueueQueries::hold(app_state.database.pool(), task_id, Some(comment.id), task.project_id, current_user.id(), &screening).await?;
+        return Ok((StatusCode::ACCEPTED, Json(PendingModeration { content: comment, pending_moderation: true })).into_response());
+    }
+
+    announce_created_comment(&app_state, &task, &comment, &mentions, &origin).await?;
+
+    Ok((StatusCode::CREATED, Json(comment)).into_response())
+}
+
+pub(crate) async fn announce_created_comment(
+    app_state: &crate::AppState,
+    task: &Task,
+    comment: &TaskComment,
+    mentions: &Mentions,
+    origin: &Origin,
+) -> Result<(), AppError> {
     // Broadcast comment creation to WebSocket subscribers
-    let user = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?;
+    let user = UserQueries::get_user_by_id(app_state.database.pool(), comment.user_id).await?;
     let user_summary: UserSummary = user.into();
+
+    slack::notify(app_state.database.pool(), task.project_id, &user_summary, Notification::CommentAdded { task, comment }).await;
     
+    notifications::notify_comment(app_state, task, &user_summary).await;
+    mentions::notify(app_state, task, Some(comment.id), &comment.content, mentions, comment.user_id).await;
+
     let event = WebSocketEvent::CommentCreated(CommentEventData {
         comment: comment.clone(),
-        task_id,
+        task_id: task.id,
         project_id: task.project_id,
         user: user_summary,
+        client_mutation_id: origin.client_mutation_id.clone(),
     });
     
-    app_state.websocket.broadcast_to_project(task.project_id, event, Some(current_user.id())).await;
+    app_state.websocket.broadcast_to_project(task.project_id, event, origin.connection_id).await;
+
+    Ok(())
+}
+
+// Everyone the task's comments and description mention, oldest first
+pub async fn get_task_mentions(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(task_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
+
+    authz::require_resource_role(&app_state, Resource::Task, task.project_id, current_user.id(), Permission::ViewProject).await?;
+
+    let mentions = TaskMentionQueries::get_task_mentions(app_state.database.pool(), task_id).await?;
+
+    Ok(Json(mentions))
+}
+
+const MAX_SEARCH_RESULTS: i64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct CommentSearchQuery {
+    pub q: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentListQuery {
+    // Instance admins only
+    #[serde(default)]
+    pub include_quarantined: bool,
+    // Pages through the thread; without a limit every comment is returned
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub offset: i64,
+}
+
+// Page size comment deep links are resolved against
+const COMMENT_PAGE_SIZE: i64 = 20;
+const MAX_COMMENT_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Serialize)]
+pub struct CommentContextResponse {
+    #[serde(flatten)]
+    pub context: CommentContext,
+    // 1-based page of COMMENT_PAGE_SIZE comments the comment is on
+    pub page: i64,
+    pub page_size: i64,
+}
+
+// Enough to scroll to the comment in the thread
+#[derive(Debug, Serialize)]
+pub struct CommentSearchResult {
+    pub comment_id: Uuid,
+    pub user: UserSummary,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub snippet: Snippet,
+}
+
+pub async fn search_task_comments(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(task_id): Path<Uuid>,
+    Query(query): Query<CommentSearchQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
 
-    Ok((StatusCode::CREATED, Json(comment)))
+    authz::require_resource_role(&app_state, Resource::Task, task.project_id, current_user.id(), Permission::ViewProject).await?;
+
+    let q = query.q.trim();
+    validation::validate_search_query(q)?;
+
+    let matches = TaskCommentQueries::search_task_comments(app_state.database.pool(), task_id, q, MAX_SEARCH_RESULTS).await?;
+
+    let results: Vec<CommentSearchResult> = matches.into_iter().map(|(comment, user)| CommentSearchResult {
+        comment_id: comment.id,
+        user,
+        created_at: comment.created_at,
+        snippet: search::snippet(&comment.content, q),
+    }).collect();
+
+    Ok(Json(results))
 }
 
 pub async fn get_task_comments(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
     Path(task_id): Path<Uuid>,
+    Query(query): Query<CommentListQuery>,
 ) -> Result<impl IntoResponse, AppError> {
     let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
 
-    // Check if user is project member
-    if !ProjectQueries::is_project_member(app_state.database.pool(), task.project_id, current_user.id()).await? {
-        return Err(AppError::Forbidden("Must be a project member to view task comments".to_string()));
+    authz::require_resource_role(&app_state, Resource::Task, task.project_id, current_user.id(), Permission::ViewProject).await?;
+    if query.include_quarantined {
+        crate::api::admin::ensure_instance_admin(&app_state, &current_user).await?;
     }
 
-    let comments = TaskCommentQueries::get_task_comments(app_state.database.pool(), task_id).await?;
+    let held = ModerationQueueQueries::pending_comment_ids(app_state.database.pool(), task_id, current_user.id()).await?;
+    let limit = query.limit.map(|limit| limit.clamp(1, MAX_COMMENT_PAGE_SIZE));
+    let comments = TaskCommentQueries::get_task_comments(
+        app_state.database.pool(),
+        task_id,
+        query.include_quarantined,
+        &held,
+        limit,
+        query.offset.max(0),
+    ).await?;
 
     // Fetch user details for each comment
     let mut comment_responses = Vec::new();
@@ -88,22 +217,46 @@ pub async fn get_task_comments(
             task_id: comment.task_id,
             user: user.into(),
             content: comment.content,
+            is_system: comment.is_system,
+            pinned: comment.pinned,
             created_at: comment.created_at,
             updated_at: comment.updated_at,
+            pending_moderation: held.contains(&comment.id),
         });
     }
 
     Ok(Json(comment_responses))
 }
 
+// Resolves a comment permalink to its task, project and place in the thread
+pub async fn get_comment_context(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(comment_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let context = TaskCommentQueries::get_comment_context(app_state.database.pool(), comment_id).await?;
+
+    authz::require_resource_role(&app_state, Resource::Comment, context.project_id, current_user.id(), Permission::ViewProject).await?;
+
+    Ok(Json(CommentContextResponse {
+        page: context.offset / COMMENT_PAGE_SIZE + 1,
+        page_size: COMMENT_PAGE_SIZE,
+        context,
+    }))
+}
+
 pub async fn delete_task_comment(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
     Path(comment_id): Path<Uuid>,
+    origin: Origin,
 ) -> Result<impl IntoResponse, AppError> {
     // Get comment details before deletion for broadcasting
     let comment = TaskCommentQueries::get_comment_by_id(app_state.database.pool(), comment_id).await?;
     let task = TaskQueries::get_task_by_id(app_state.database.pool(), comment.task_id).await?;
+
+    authz::require_resource_role(&app_state, Resource::Comment, task.project_id, current_user.id(), Permission::ViewProject).await?;
+    authz::ensure_project_active(app_state.database.pool(), task.project_id).await?;
     
     // The delete_comment function checks if the user owns the comment
     TaskCommentQueries::delete_comment(
@@ -117,9 +270,86 @@ pub async fn delete_task_comment(
         comment_id, 
         task_id: comment.task_id,
         project_id: task.project_id,
+        client_mutation_id: origin.client_mutation_id,
     };
     
-    app_state.websocket.broadcast_to_project(task.project_id, event, Some(current_user.id())).await;
+    app_state.websocket.broadcast_to_project(task.project_id, event, origin.connection_id).await;
 
     Ok(StatusCode::NO_CONTENT)
-}
\ No newline at end of file
+}
+// Turns an action item from a discussion into its own task: the first line
+// becomes the title, the whole comment the description. The new task is
+// related to the comment's task and both get a system comment about it.
+pub async fn convert_comment_to_task(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(comment_id): Path<Uuid>,
+    origin: Origin,
+    Json(Mutation { body: request, client_mutation_id }): Json<Mutation<ConvertCommentRequest>>,
+) -> Result<impl IntoResponse, AppError> {
+    let origin = origin.with_body(client_mutation_id)?;
+    let pool = app_state.database.pool();
+    let comment = TaskCommentQueries::get_comment_by_id(pool, comment_id).await?;
+    let source = TaskQueries::get_task_by_id(pool, comment.task_id).await?;
+    let project_id = source.project_id;
+
+    authz::require_resource_role(&app_state, Resource::Comment, project_id, current_user.id(), Permission::CreateTasks).await?;
+
+    if let Some(assigned_to) = request.assigned_to {
+        if !ProjectQueries::is_project_member(pool, project_id, assigned_to).await? {
+            return Err(AppError::Validation("Assigned user must be a project member".to_string()));
+        }
+    }
+
+    let mut task_request = CreateTaskRequest {
+        title: validation::task_title_from_text(&comment.content),
+        description: Some(comment.content.clone()),
+        assigned_to: request.assigned_to,
+        priority: None,
+        due_date: None,
+        tags: None,
+        cover_color: None,
+        cover_emoji: None,
+    };
+    validation::validate_create_task(&mut task_request)?;
+
+    let project = ProjectQueries::get_project_by_id(pool, project_id).await?;
+    crate::quotas::check_can_create_task(pool, project.team_id, project_id).await?;
+    let wip_check = wip::check_create(pool, project_id, TaskStatus::Todo).await?;
+
+    let task = TaskQueries::create_task(pool, project_id, &task_request, current_user.id()).await?;
+    TaskRelationQueries::create_relation(pool, task.id, &source, TaskRelationType::RelatesTo, current_user.id()).await?;
+
+    crate::api::tasks::announce_created_task(&app_state, current_user.id(), &origin, &task, None, &wip_check).await?;
+
+    let user: UserSummary = UserQueries::get_user_by_id(pool, current_user.id()).await?.into();
+    let notes = [
+        (&source, format!("Converted a comment into task #{} {}", task.number, task.title)),
+        (&task, format!("Created from a comment on task #{} {}", source.number, source.title)),
+    ];
+    for (on, content) in notes {
+        post_system_comment(&app_state, on, &user, &content).await?;
+    }
+
+    Ok((StatusCode::CREATED, Json(task)))
+}
+
+async fn post_system_comment(
+    app_state: &crate::AppState,
+    task: &Task,
+    user: &UserSummary,
+    content: &str,
+) -> Result<(), AppError> {
+    let comment = TaskCommentQueries::create_system_comment(app_state.database.pool(), task.id, user.id, content).await?;
+
+    let event = WebSocketEvent::CommentCreated(CommentEventData {
+        comment,
+        task_id: task.id,
+        project_id: task.project_id,
+        user: user.clone(),
+        client_mutation_id: None,
+    });
+    app_state.websocket.broadcast_to_project(task.project_id, event, None).await;
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+// Home screen summary: everything the landing page needs in one request. The
+// sections are independent queries run concurrently; `?sections=` picks which
+// ones to load.
+
+use axum::{
+    extract::{Extension, State},
+    response::IntoResponse,
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::middleware::CurrentUser;
+use crate::database::{models::{DueDay, TeamProjectCount}, queries::DashboardQueries};
+use crate::due_dates;
+use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Query};
+use crate::utils::fields::SparseFields;
+
+#[derive(Debug, Deserialize)]
+pub struct DashboardQuery {
+    // Comma separated section names; all sections when left out
+    pub sections: Option<String>,
+}
+
+// Skipped sections are left out of the response
+#[derive(Debug, Serialize)]
+pub struct DashboardResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub teams: Option<Vec<TeamProjectCount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_this_week: Option<Vec<DueDay>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overdue_count: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unread_notification_count: Option<i64>,
+}
+
+impl DashboardResponse {
+    pub const SECTIONS: &'static [&'static str] = &["teams", "due_this_week", "overdue_count", "unread_notification_count"];
+}
+
+// Runs `query` only when section `name` was asked for
+async fn section<T, F>(sections: Option<&SparseFields>, name: &str, query: F) -> Result<Option<T>, AppError>
+where
+    F: std::future::Future<Output = Result<T, AppError>>,
+{
+    if sections.is_none_or(|sections| sections.includes(name)) {
+        query.await.map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+pub async fn get_dashboard(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<DashboardQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let sections = SparseFields::parse(query.sections.as_deref(), DashboardResponse::SECTIONS)?;
+    let sections = sections.as_ref();
+    let pool = app_state.database.pool();
+    let user_id = current_user.id();
+    let now = Utc::now();
+
+    let (teams, due_this_week, overdue_count, unread_notification_count) = tokio::try_join!(
+        section(sections, "teams", DashboardQueries::get_user_teams_with_project_counts(pool, user_id)),
+        section(
+            sections,
+            "due_this_week",
+            DashboardQueries::get_tasks_due_by_day(pool, user_id, now, now + Duration::days(due_dates::UPCOMING_DAYS)),
+        ),
+        section(sections, "overdue_count", DashboardQueries::count_overdue_tasks(pool, user_id, now)),
+        section(sections, "unread_notification_count", DashboardQueries::count_unread_notifications(pool, user_id)),
+    )?;
+
+    Ok(Json(DashboardResponse { teams, due_this_week, overdue_count, unread_notification_count }))
+}
@@ -0,0 +1,44 @@
+use axum::{
+    extract::{Extension, State},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+
+use crate::auth::authz;
+use crate::auth::middleware::CurrentUser;
+use crate::database::queries::UserQueries;
+use crate::utils::errors::AppError;
+use crate::utils::extractors::Json;
+use crate::websocket::schemas::{self, SentBy};
+
+#[derive(Debug, Deserialize)]
+pub struct TestEventRequest {
+    #[serde(rename = "type")]
+    pub event_type: String,
+}
+
+// JSON schemas of every WebSocket event (see websocket/schemas.rs)
+pub async fn get_event_schemas() -> impl IntoResponse {
+    Json(schemas::registry())
+}
+
+// Sends a sample event to the caller's own connections only, so nobody else
+// sees the made-up task. Returns what was sent.
+pub async fn send_test_event(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    authz::ProjectMember(project_id): authz::ProjectMember,
+    Json(request): Json<TestEventRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if schemas::sent_by(&request.event_type) == SentBy::Client {
+        return Err(AppError::BadRequest(format!("{} is only sent by clients", request.event_type)));
+    }
+
+    let user = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?;
+    let event = schemas::sample(&request.event_type, project_id, &user.into())
+        .ok_or_else(|| AppError::BadRequest(format!("Unknown event type {}", request.event_type)))?;
+
+    app_state.websocket.send_to_user(current_user.id(), event.clone()).await;
+
+    Ok(Json(event))
+}
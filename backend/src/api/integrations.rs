@@ -0,0 +1,546 @@
+use axum::{
+    body::Bytes,
+    extract::{Extension, FromRequest, Multipart, Request, State},
+    response::IntoResponse,
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+};
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::authz::{self, Permission};
+use crate::auth::middleware::CurrentUser;
+use crate::database::{
+    models::{ProjectIntegration, UserSummary},
+    queries::{IntegrationQueries, ProjectQueries, UserQueries}
+};
+use crate::integrations::email_inbox::{self, EmailInboxConfig, InboundAttachment, InboundEmail, UnknownSenderPolicy};
+use crate::integrations::github::{self, GithubConfig, TransitionRules, WebhookOutcome};
+use crate::integrations::slack::{self, blocks::Notification, SlackConfig, SlackEvent};
+use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Path};
+use crate::utils::validation;
+
+#[derive(Debug, Deserialize)]
+pub struct SlackIntegrationRequest {
+    // Required when creating the integration, optional on later updates
+    pub webhook_url: Option<String>,
+    pub events: Option<Vec<SlackEvent>>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SlackIntegrationResponse {
+    pub project_id: Uuid,
+    pub webhook_url: String,
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GithubIntegrationRequest {
+    pub repo: String,
+    // Required when creating the integration, optional on later updates
+    pub webhook_secret: Option<String>,
+    pub task_key: Option<String>,
+    pub transitions: Option<TransitionRules>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GithubIntegrationResponse {
+    pub project_id: Uuid,
+    pub repo: String,
+    pub task_key: String,
+    pub transitions: TransitionRules,
+    pub enabled: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailIntegrationRequest {
+    pub unknown_senders: Option<UnknownSenderPolicy>,
+    // Required when unknown senders go to an inbox user
+    pub inbox_user_id: Option<Uuid>,
+    // Issue a new address, the old one stops working
+    #[serde(default)]
+    pub regenerate_address: bool,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmailIntegrationResponse {
+    pub project_id: Uuid,
+    pub address: String,
+    pub unknown_senders: UnknownSenderPolicy,
+    pub inbox_user_id: Option<Uuid>,
+    pub enabled: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TestNotificationResponse {
+    pub job_id: Uuid,
+}
+
+impl From<ProjectIntegration> for SlackIntegrationResponse {
+    fn from(integration: ProjectIntegration) -> Self {
+        let webhook_url = SlackConfig::from_integration(&integration)
+            .map(|config| mask_webhook_url(&config.webhook_url))
+            .unwrap_or_default();
+
+        SlackIntegrationResponse {
+            project_id: integration.project_id,
+            webhook_url,
+            events: integration.events,
+            enabled: integration.enabled,
+            created_at: integration.created_at,
+            updated_at: integration.updated_at,
+        }
+    }
+}
+
+// The last path segment of a Slack webhook URL is the secret token
+fn mask_webhook_url(url: &str) -> String {
+    match url.rsplit_once('/') {
+        Some((prefix, _)) => format!("{}/****", prefix),
+        None => "****".to_string(),
+    }
+}
+
+async fn ensure_project_admin(app_state: &crate::AppState, project_id: Uuid, current_user: &CurrentUser) -> Result<(), AppError> {
+    authz::require_project_role(app_state.database.pool(), project_id, current_user.id(), Permission::ManageProject).await?;
+
+    Ok(())
+}
+
+pub async fn get_slack_integration(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_project_admin(&app_state, project_id, &current_user).await?;
+
+    let integration = IntegrationQueries::get_integration(app_state.database.pool(), project_id, slack::INTEGRATION_KIND)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Slack integration not configured".to_string()))?;
+
+    Ok(Json(SlackIntegrationResponse::from(integration)))
+}
+
+pub async fn configure_slack_integration(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+    Json(request): Json<SlackIntegrationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_project_admin(&app_state, project_id, &current_user).await?;
+
+    let existing = IntegrationQueries::get_integration(app_state.database.pool(), project_id, slack::INTEGRATION_KIND).await?;
+
+    let webhook_url = match (request.webhook_url, existing.as_ref().and_then(SlackConfig::from_integration)) {
+        (Some(url), _) => {
+            let url = url.trim().to_string();
+            validation::validate_slack_webhook_url(&url)?;
+            url
+        }
+        (None, Some(config)) => config.webhook_url,
+        (None, None) => return Err(AppError::Validation("webhook_url is required".to_string())),
+    };
+
+    // Subscribe to every event unless a filter is given
+    let events: Vec<String> = match request.events {
+        Some(events) => events.iter().map(|event| event.name().to_string()).collect(),
+        None => match existing.as_ref() {
+            Some(integration) => integration.events.clone(),
+            None => SlackEvent::ALL.iter().map(|event| event.name().to_string()).collect(),
+        },
+    };
+    let enabled = request
+        .enabled
+        .or(existing.as_ref().map(|integration| integration.enabled))
+        .unwrap_or(true);
+
+    let config = serde_json::to_value(SlackConfig { webhook_url })?;
+    let integration = IntegrationQueries::upsert_integration(
+        app_state.database.pool(),
+        project_id,
+        slack::INTEGRATION_KIND,
+        config,
+        &events,
+        enabled,
+        current_user.id(),
+    ).await?;
+    slack::resume_deliveries(app_state.database.pool(), project_id).await?;
+
+    let status = if existing.is_some() { StatusCode::OK } else { StatusCode::CREATED };
+    Ok((status, Json(SlackIntegrationResponse::from(integration))))
+}
+
+pub async fn delete_slack_integration(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_project_admin(&app_state, project_id, &current_user).await?;
+
+    IntegrationQueries::delete_integration(app_state.database.pool(), project_id, slack::INTEGRATION_KIND).await?;
+    slack::resume_deliveries(app_state.database.pool(), project_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn test_slack_integration(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_project_admin(&app_state, project_id, &current_user).await?;
+
+    if IntegrationQueries::get_integration(app_state.database.pool(), project_id, slack::INTEGRATION_KIND).await?.is_none() {
+        return Err(AppError::NotFound("Slack integration not configured".to_string()));
+    }
+
+    let user = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?;
+    let user_summary: UserSummary = user.into();
+
+    // Delivered by the job worker like any other message; the job shows up in /admin/jobs
+    let job = slack::queue_message(app_state.database.pool(), project_id, &user_summary, &Notification::Test, true).await?;
+
+    Ok((StatusCode::ACCEPTED, Json(TestNotificationResponse { job_id: job.id })))
+}
+
+// The webhook secret is write-only and never returned
+fn github_response(integration: ProjectIntegration, config: GithubConfig) -> GithubIntegrationResponse {
+    GithubIntegrationResponse {
+        project_id: integration.project_id,
+        repo: config.repo,
+        task_key: config.task_key,
+        transitions: config.transitions,
+        enabled: integration.enabled,
+        created_at: integration.created_at,
+        updated_at: integration.updated_at,
+    }
+}
+
+fn github_config(integration: &ProjectIntegration) -> Option<GithubConfig> {
+    serde_json::from_value(integration.config.clone()).ok()
+}
+
+pub async fn get_github_integration(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_project_admin(&app_state, project_id, &current_user).await?;
+
+    let integration = IntegrationQueries::get_integration(app_state.database.pool(), project_id, github::INTEGRATION_KIND)
+        .await?
+        .ok_or_else(|| AppError::NotFound("GitHub integration not configured".to_string()))?;
+    let config = github_config(&integration)
+        .ok_or_else(|| AppError::InternalServer("Invalid GitHub integration config".to_string()))?;
+
+    Ok(Json(github_response(integration, config)))
+}
+
+pub async fn configure_github_integration(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+    Json(request): Json<GithubIntegrationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_project_admin(&app_state, project_id, &current_user).await?;
+
+    let existing = IntegrationQueries::get_integration(app_state.database.pool(), project_id, github::INTEGRATION_KIND).await?;
+    let existing_config = existing.as_ref().and_then(github_config);
+
+    let repo = request.repo.trim().to_string();
+    validation::validate_github_repo(&repo)?;
+
+    let webhook_secret = match (request.webhook_secret, existing_config.as_ref()) {
+        (Some(secret), _) => {
+            if secret.len() < 16 {
+                return Err(AppError::Validation("Webhook secret must be at least 16 characters".to_string()));
+            }
+            secret
+        }
+        (None, Some(config)) => config.webhook_secret.clone(),
+        (None, None) => return Err(AppError::Validation("webhook_secret is required".to_string())),
+    };
+
+    let task_key = request
+        .task_key
+        .or(existing_config.as_ref().map(|config| config.task_key.clone()))
+        .unwrap_or_else(|| github::DEFAULT_TASK_KEY.to_string());
+    validation::validate_task_key(&task_key)?;
+
+    let config = GithubConfig {
+        repo,
+        webhook_secret,
+        task_key,
+        transitions: request
+            .transitions
+            .or(existing_config.map(|config| config.transitions))
+            .unwrap_or_default(),
+    };
+    let enabled = request
+        .enabled
+        .or(existing.as_ref().map(|integration| integration.enabled))
+        .unwrap_or(true);
+
+    let integration = IntegrationQueries::upsert_integration(
+        app_state.database.pool(),
+        project_id,
+        github::INTEGRATION_KIND,
+        serde_json::to_value(&config)?,
+        &[],
+        enabled,
+        current_user.id(),
+    ).await?;
+
+    let status = if existing.is_some() { StatusCode::OK } else { StatusCode::CREATED };
+    Ok((status, Json(github_response(integration, config))))
+}
+
+pub async fn delete_github_integration(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_project_admin(&app_state, project_id, &current_user).await?;
+
+    IntegrationQueries::delete_integration(app_state.database.pool(), project_id, github::INTEGRATION_KIND).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Unauthenticated endpoint called by GitHub. The repository in the payload
+// selects the project integrations, and the request must be signed with one
+// of their secrets.
+pub async fn github_webhook(
+    State(app_state): State<crate::AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let event = headers
+        .get("x-github-event")
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| AppError::BadRequest("Missing X-GitHub-Event header".to_string()))?
+        .to_string();
+    let signature = headers.get("x-hub-signature-256").and_then(|value| value.to_str().ok());
+
+    let payload: serde_json::Value = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("Malformed JSON: {}", e)))?;
+    let repo = payload["repository"]["full_name"]
+        .as_str()
+        .ok_or_else(|| AppError::BadRequest("Payload has no repository".to_string()))?;
+
+    let integrations = IntegrationQueries::find_integrations_by_config(
+        app_state.database.pool(),
+        github::INTEGRATION_KIND,
+        "repo",
+        repo,
+    ).await?;
+
+    let verified: Vec<(ProjectIntegration, GithubConfig)> = integrations
+        .into_iter()
+        .filter_map(|integration| {
+            let config = github_config(&integration)?;
+            github::verify_signature(&config.webhook_secret, &body, signature).then_some((integration, config))
+        })
+        .collect();
+
+    if verified.is_empty() {
+        return Err(AppError::Unauthorized("Invalid webhook signature".to_string()));
+    }
+
+    let mut outcome = WebhookOutcome::default();
+    for (integration, config) in verified.iter().filter(|(integration, _)| integration.enabled) {
+        let result = github::process_event(app_state.database.pool(), integration.project_id, config, &event, &payload).await?;
+        outcome.linked += result.linked;
+        outcome.transitioned += result.transitioned;
+    }
+
+    Ok(Json(outcome))
+}
+
+fn email_response(integration: ProjectIntegration, config: EmailInboxConfig) -> EmailIntegrationResponse {
+    EmailIntegrationResponse {
+        project_id: integration.project_id,
+        address: email_inbox::inbox_address(&config.token),
+        unknown_senders: config.unknown_senders,
+        inbox_user_id: config.inbox_user_id,
+        enabled: integration.enabled,
+        created_at: integration.created_at,
+        updated_at: integration.updated_at,
+    }
+}
+
+pub async fn get_email_integration(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_project_admin(&app_state, project_id, &current_user).await?;
+
+    let integration = IntegrationQueries::get_integration(app_state.database.pool(), project_id, email_inbox::INTEGRATION_KIND)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Email integration not configured".to_string()))?;
+    let config = EmailInboxConfig::from_integration(&integration)
+        .ok_or_else(|| AppError::InternalServer("Invalid email integration config".to_string()))?;
+
+    Ok(Json(email_response(integration, config)))
+}
+
+pub async fn configure_email_integration(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+    Json(request): Json<EmailIntegrationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_project_admin(&app_state, project_id, &current_user).await?;
+
+    let existing = IntegrationQueries::get_integration(app_state.database.pool(), project_id, email_inbox::INTEGRATION_KIND).await?;
+    let existing_config = existing.as_ref().and_then(EmailInboxConfig::from_integration);
+
+    let token = match existing_config.as_ref() {
+        Some(config) if !request.regenerate_address => config.token.clone(),
+        _ => email_inbox::generate_token(),
+    };
+    let unknown_senders = request
+        .unknown_senders
+        .or(existing_config.as_ref().map(|config| config.unknown_senders))
+        .unwrap_or_default();
+    let inbox_user_id = request
+        .inbox_user_id
+        .or(existing_config.as_ref().and_then(|config| config.inbox_user_id));
+
+    if let Some(inbox_user_id) = inbox_user_id {
+        if !ProjectQueries::is_project_member(app_state.database.pool(), project_id, inbox_user_id).await? {
+            return Err(AppError::Validation("Inbox user must be a project member".to_string()));
+        }
+    } else if unknown_senders == UnknownSenderPolicy::InboxUser {
+        return Err(AppError::Validation("inbox_user_id is required when unknown senders go to the inbox user".to_string()));
+    }
+
+    let config = EmailInboxConfig { token, unknown_senders, inbox_user_id };
+    let enabled = request
+        .enabled
+        .or(existing.as_ref().map(|integration| integration.enabled))
+        .unwrap_or(true);
+
+    let integration = IntegrationQueries::upsert_integration(
+        app_state.database.pool(),
+        project_id,
+        email_inbox::INTEGRATION_KIND,
+        serde_json::to_value(&config)?,
+        &[],
+        enabled,
+        current_user.id(),
+    ).await?;
+
+    let status = if existing.is_some() { StatusCode::OK } else { StatusCode::CREATED };
+    Ok((status, Json(email_response(integration, config))))
+}
+
+pub async fn delete_email_integration(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_project_admin(&app_state, project_id, &current_user).await?;
+
+    IntegrationQueries::delete_integration(app_state.database.pool(), project_id, email_inbox::INTEGRATION_KIND).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Unauthenticated endpoint for inbound mail. Mailgun posts multipart form
+// data signed with the webhook signing key; anything else is JSON from an SES
+// forwarder signed with INBOUND_EMAIL_SECRET. Mail that can't become a task
+// still gets a 200 so the provider doesn't retry it.
+pub async fn email_inbound(
+    State(app_state): State<crate::AppState>,
+    request: Request,
+) -> Result<impl IntoResponse, AppError> {
+    let is_multipart = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"));
+
+    let email = if is_multipart {
+        let multipart = Multipart::from_request(request, &())
+            .await
+            .map_err(|e| AppError::BadRequest(e.body_text()))?;
+        mailgun_email(multipart).await?
+    } else {
+        forwarded_email(request).await?
+    };
+
+    let outcome = email_inbox::process(&app_state, email).await?;
+
+    Ok(Json(outcome))
+}
+
+async fn mailgun_email(mut multipart: Multipart) -> Result<InboundEmail, AppError> {
+    let signing_key = email_inbox::mailgun_signing_key()
+        .ok_or_else(|| AppError::Unauthorized("Inbound email is not configured".to_string()))?;
+
+    let mut fields = HashMap::new();
+    let mut attachments = Vec::new();
+    while let Some(field) = multipart.next_field().await.map_err(|e| AppError::BadRequest(e.body_text()))? {
+        let name = field.name().unwrap_or_default().to_string();
+        if name.starts_with("attachment-") {
+            let filename = field.file_name().unwrap_or("attachment").to_string();
+            let content_type = field.content_type().unwrap_or_default().to_string();
+            let data = field.bytes().await.map_err(|e| AppError::BadRequest(e.body_text()))?;
+            attachments.push(InboundAttachment { filename, content_type, data: data.to_vec() });
+        } else {
+            let value = field.text().await.map_err(|e| AppError::BadRequest(e.body_text()))?;
+            fields.insert(name, value);
+        }
+    }
+
+    let field = |name: &str| fields.get(name).map(String::as_str).unwrap_or_default();
+    let verified = email_inbox::verify_mailgun_signature(
+        &signing_key,
+        field("timestamp"),
+        field("token"),
+        field("signature"),
+        chrono::Utc::now().timestamp(),
+    );
+    if !verified {
+        return Err(AppError::Unauthorized("Invalid webhook signature".to_string()));
+    }
+
+    InboundEmail::from_mailgun(&fields, attachments)
+        .ok_or_else(|| AppError::BadRequest("Message has no sender".to_string()))
+}
+
+async fn forwarded_email(request: Request) -> Result<InboundEmail, AppError> {
+    let secret = email_inbox::forwarder_secret()
+        .ok_or_else(|| AppError::Unauthorized("Inbound email is not configured".to_string()))?;
+    let signature = request
+        .headers()
+        .get("x-inbound-signature")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let body = Bytes::from_request(request, &())
+        .await
+        .map_err(|e| AppError::BadRequest(e.body_text()))?;
+    if !github::verify_signature(&secret, &body, signature.as_deref()) {
+        return Err(AppError::Unauthorized("Invalid webhook signature".to_string()));
+    }
+
+    let payload: serde_json::Value = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("Malformed JSON: {}", e)))?;
+
+    InboundEmail::from_forwarded_json(&payload)
+        .ok_or_else(|| AppError::BadRequest("Message has no sender".to_string()))
+}
@@ -0,0 +1,80 @@
+// Project invitations (see crate::invitations). Project admins send, list and
+// revoke them; any signed-in user holding a token can accept it.
+
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::auth::authz::ProjectAdmin;
+use crate::auth::middleware::CurrentUser;
+use crate::auth::tokens;
+use crate::database::{
+    models::{AcceptInvitationRequest, CreateProjectInvitationRequest, CreatedProjectInvitation},
+    queries::{ProjectInvitationQueries, ProjectQueries, UserQueries},
+};
+use crate::invitations;
+use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Path};
+use crate::utils::validation;
+
+pub async fn get_project_invitations(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+) -> Result<impl IntoResponse, AppError> {
+    let invitations = ProjectInvitationQueries::get_pending_invitations(app_state.database.pool(), project_id).await?;
+
+    Ok(Json(invitations))
+}
+
+pub async fn create_project_invitation(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    ProjectAdmin(project_id): ProjectAdmin,
+    Json(mut request): Json<CreateProjectInvitationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    validation::validate_project_invitation(&mut request)?;
+    let pool = app_state.database.pool();
+    let project = ProjectQueries::get_project_by_id(pool, project_id).await?;
+    let inviter = UserQueries::get_user_by_id(pool, current_user.id()).await?;
+
+    let token = invitations::generate_token();
+    let invitation = ProjectInvitationQueries::create_invitation(
+        pool,
+        project_id,
+        &request.email,
+        request.role,
+        &tokens::hash_token(&token),
+        current_user.id(),
+        invitations::expires_at(Utc::now()),
+    ).await?;
+    invitations::send(pool, &invitation, &project, &inviter, &token).await?;
+    invitations::notify_invitee(&app_state, &invitation, &project, &inviter).await;
+
+    Ok((StatusCode::CREATED, Json(CreatedProjectInvitation { invitation, token })))
+}
+
+pub async fn delete_project_invitation(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+    Path((_, invitation_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, AppError> {
+    ProjectInvitationQueries::delete_invitation(app_state.database.pool(), project_id, invitation_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// For existing accounts; new ones accept theirs when registering
+pub async fn accept_invitation(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<AcceptInvitationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    current_user.ensure_not_impersonated()?;
+    let member = invitations::accept(&app_state, &request.token, current_user.id()).await?;
+
+    Ok(Json(member))
+}
@@ -0,0 +1,43 @@
+use axum::{
+    extract::State,
+    http::header::CACHE_CONTROL,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::config::PublicConfig;
+use crate::utils::colors;
+use crate::utils::extractors::{Json, Query};
+
+#[derive(Debug, Deserialize)]
+pub struct ConfigQuery {
+    // Evaluate feature flags for this team instead of the instance defaults
+    pub team_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigResponse<'a> {
+    #[serde(flatten)]
+    pub config: &'a PublicConfig,
+    pub flags: BTreeMap<String, bool>,
+}
+
+// Named colors clients can offer; any of the keys is accepted wherever a color is
+pub async fn get_palette() -> impl IntoResponse {
+    Json(colors::palette())
+}
+
+// Limits, policies and enabled features of this instance (see config.rs).
+// They change rarely, so clients may cache them for a while.
+pub async fn get_config(
+    State(app_state): State<crate::AppState>,
+    Query(query): Query<ConfigQuery>,
+) -> Response {
+    let response = ConfigResponse {
+        config: &app_state.public_config,
+        flags: app_state.flags.evaluate(query.team_id),
+    };
+    ([(CACHE_CONTROL, "public, max-age=300")], Json(response)).into_response()
+}
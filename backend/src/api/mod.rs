@@ -5,4 +5,18 @@ pub mod teams;
 pub mod projects;
 pub mod tasks;
 pub mod boards;
-pub mod comments;
\ No newline at end of file
+pub mod comments;
+pub mod admin;
+pub mod integrations;
+pub mod meta;
+pub mod dashboard;
+pub mod trash;
+pub mod attachments;
+pub mod sharing;
+pub mod announcements;
+pub mod reports;
+pub mod templates;
+pub mod events;
+pub mod invitations;
+pub mod quick_switch;
+pub mod task_snapshots;
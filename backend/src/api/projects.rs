@@ -1,24 +1,39 @@
 use axum::{
-    extract::{Extension, State, Path},
-    response::IntoResponse,
-    Json,
-    http::StatusCode,
+    extract::{Extension, State},
+    response::{IntoResponse, Response},
+    http::{header, StatusCode},
 };
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
+use crate::analytics;
+use crate::assignment::{self, CandidateStats, ScoreFactors};
+use crate::auth::authz::{self, Permission, ProjectAdmin};
 use crate::auth::middleware::CurrentUser;
 use crate::database::{
-    models::{CreateProjectRequest, ProjectRole, ProjectMember, UserSummary},
-    queries::{ProjectQueries, TeamQueries, UserQueries}
+    models::{
+        AttachmentStorage, ContrastText, CreateProjectRequest, DetailsEmbed, DetailsQuery, OnboardingTemplate, ProjectRole, ProjectMember, ProjectStatusFilter, ProjectWorkflow,
+        ProjectCounters, MentionSettings, NotificationEventType, NotificationLevel, StalenessRules, TaskWorkload, UserSummary, EMBEDDED_MEMBERS,
+    },
+    queries::{CounterQueries, NotificationSettingsQueries, ProjectQueries, TaskQueries, TemplateQueries, UserQueries}
 };
+use crate::integrations::slack::{self, blocks::Notification};
+use crate::notification_settings::{self, settings_cache};
+use crate::quick_switch;
 use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Path, Query};
+use crate::utils::i18n::Message;
 use crate::utils::validation;
+use crate::websocket::events::{MemberAddedEventData, WebSocketEvent};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AddProjectMemberRequest {
     pub user_id: Uuid,
     pub role: ProjectRole,
+    // Don't create the project's onboarding tasks for this member
+    #[serde(default)]
+    pub skip_onboarding: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,6 +41,64 @@ pub struct UpdateProjectMemberRequest {
     pub role: ProjectRole,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ProjectListQuery {
+    pub status: Option<ProjectStatusFilter>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProjectMembersQuery {
+    pub role: Option<ProjectRole>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectMembersPage {
+    pub members: Vec<ProjectMemberResponse>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+// Limits the workload to tasks due in [from, to)
+#[derive(Debug, Deserialize)]
+pub struct WorkloadQuery {
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssignmentSuggestionsQuery {
+    pub task_id: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssignmentSuggestionResponse {
+    pub user: UserSummary,
+    pub role: ProjectRole,
+    pub score: f64,
+    pub factors: ScoreFactors,
+    // The task's labels this member completed tasks with before
+    pub matched_labels: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CycleTimeQuery {
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    // json (default) or csv
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberWorkloadResponse {
+    pub user: UserSummary,
+    pub role: ProjectRole,
+    #[serde(flatten)]
+    pub workload: TaskWorkload,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ProjectDetailsResponse {
     pub id: Uuid,
@@ -34,12 +107,35 @@ pub struct ProjectDetailsResponse {
     pub team_id: Uuid,
     pub created_by: Uuid,
     pub color: Option<String>,
+    pub contrast_text: Option<ContrastText>,
+    pub icon: Option<String>,
     pub is_active: bool,
+    pub archived_at: Option<chrono::DateTime<chrono::Utc>>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub counts: ProjectCounters,
+    // Active members, of which `members` holds the first EMBEDDED_MEMBERS
+    pub member_count: i64,
     pub members: Vec<ProjectMemberResponse>,
 }
 
+// What the current user may do in a project, so clients can hide controls
+#[derive(Debug, Serialize)]
+pub struct MyPermissionsResponse {
+    pub project_id: Uuid,
+    pub role: ProjectRole,
+    pub permissions: Vec<Permission>,
+}
+
+// Attachment storage against the team's per-project limit
+#[derive(Debug, Serialize)]
+pub struct ProjectUsageResponse {
+    pub project_id: Uuid,
+    #[serde(flatten)]
+    pub storage: AttachmentStorage,
+    pub max_attachment_bytes: i64,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ProjectMemberResponse {
     pub id: Uuid,
@@ -48,6 +144,17 @@ pub struct ProjectMemberResponse {
     pub joined_at: chrono::DateTime<chrono::Utc>,
 }
 
+impl From<(ProjectMember, UserSummary)> for ProjectMemberResponse {
+    fn from((member, user): (ProjectMember, UserSummary)) -> Self {
+        Self {
+            id: member.id,
+            user,
+            role: member.role,
+            joined_at: member.joined_at,
+        }
+    }
+}
+
 pub async fn create_project(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
@@ -57,24 +164,38 @@ pub async fn create_project(
     // Set team_id from path
     request.team_id = team_id;
 
-    // Check if user is team member
-    if !TeamQueries::is_team_member(app_state.database.pool(), team_id, current_user.id()).await? {
-        return Err(AppError::Forbidden("Must be a team member to create projects".to_string()));
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::CreateProjects).await?;
+
+    let template = match request.template_id {
+        Some(template_id) => {
+            Some(TemplateQueries::get_usable_template(app_state.database.pool(), template_id, current_user.id()).await?)
+        }
+        None => None,
+    };
+    if let Some(ref template) = template {
+        template.document.apply_defaults(&mut request);
     }
 
     // Validate input
+    request.name = validation::normalize_name(&request.name);
     validation::validate_project_name(&request.name)?;
     if let Some(ref description) = request.description {
         validation::validate_project_description(description)?;
     }
-    if let Some(ref color) = request.color {
-        validation::validate_hex_color(color)?;
+    if let Some(ref mut color) = request.color {
+        *color = validation::normalize_color(color)?;
     }
+    if let Some(ref icon) = request.icon {
+        validation::validate_emoji(icon)?;
+    }
+
+    crate::quotas::check_can_create_project(app_state.database.pool(), team_id).await?;
 
     let project = ProjectQueries::create_project(
         app_state.database.pool(),
         &request,
         current_user.id(),
+        template.as_ref().map(|template| &template.document),
     ).await?;
 
     Ok((StatusCode::CREATED, Json(project)))
@@ -85,10 +206,7 @@ pub async fn get_team_projects(
     Extension(current_user): Extension<CurrentUser>,
     Path(team_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is team member
-    if !TeamQueries::is_team_member(app_state.database.pool(), team_id, current_user.id()).await? {
-        return Err(AppError::Forbidden("Must be a team member to view projects".to_string()));
-    }
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ViewTeam).await?;
 
     let projects = ProjectQueries::get_team_projects(app_state.database.pool(), team_id).await?;
 
@@ -98,10 +216,12 @@ pub async fn get_team_projects(
 pub async fn get_user_projects(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<ProjectListQuery>,
 ) -> Result<impl IntoResponse, AppError> {
     let projects = ProjectQueries::get_user_projects(
         app_state.database.pool(),
         current_user.id(),
+        query.status.unwrap_or_default(),
     ).await?;
 
     Ok(Json(projects))
@@ -110,22 +230,22 @@ pub async fn get_user_projects(
 pub async fn get_project_details(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
-    Path(project_id): Path<Uuid>,
+    authz::ProjectMember(project_id): authz::ProjectMember,
+    Query(query): Query<DetailsQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project member
-    if !ProjectQueries::is_project_member(app_state.database.pool(), project_id, current_user.id()).await? {
-        return Err(AppError::Forbidden("Not a project member".to_string()));
-    }
-
     let project = ProjectQueries::get_project_by_id(app_state.database.pool(), project_id).await?;
-    let members_data = ProjectQueries::get_project_members(app_state.database.pool(), project_id).await?;
+    quick_switch::record_access(app_state.database.pool(), current_user.id(), project_id).await;
+    let (members_data, member_count) = match query.embed {
+        Some(DetailsEmbed::Members) => {
+            let members = ProjectQueries::get_project_members(app_state.database.pool(), project_id).await?;
+            let count = members.len() as i64;
+            (members, count)
+        }
+        None => ProjectQueries::list_project_members(app_state.database.pool(), project_id, None, EMBEDDED_MEMBERS, 0).await?,
+    };
+    let counts = CounterQueries::get_project_counters(app_state.database.pool(), project_id).await?;
 
-    let members = members_data.into_iter().map(|(member, user)| ProjectMemberResponse {
-        id: member.id,
-        user,
-        role: member.role,
-        joined_at: member.joined_at,
-    }).collect();
+    let members = members_data.into_iter().map(ProjectMemberResponse::from).collect();
 
     let response = ProjectDetailsResponse {
         id: project.id,
@@ -134,39 +254,205 @@ pub async fn get_project_details(
         team_id: project.team_id,
         created_by: project.created_by,
         color: project.color,
+        contrast_text: project.contrast_text,
+        icon: project.icon,
         is_active: project.is_active,
+        archived_at: project.archived_at,
         created_at: project.created_at,
         updated_at: project.updated_at,
+        counts,
+        member_count,
         members,
     };
 
     Ok(Json(response))
 }
 
-pub async fn update_project(
+pub async fn get_project_members(
     State(app_state): State<crate::AppState>,
-    Extension(current_user): Extension<CurrentUser>,
-    Path(project_id): Path<Uuid>,
-    Json(request): Json<CreateProjectRequest>,
+    authz::ProjectMember(project_id): authz::ProjectMember,
+    Query(query): Query<ProjectMembersQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project admin
-    let user_role = ProjectQueries::get_user_project_role(
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(50).clamp(1, 200);
+
+    let (members, total) = ProjectQueries::list_project_members(
         app_state.database.pool(),
         project_id,
-        current_user.id(),
+        query.role,
+        per_page,
+        (page - 1).saturating_mul(per_page),
     ).await?;
 
-    if !matches!(user_role, Some(ProjectRole::Admin)) {
-        return Err(AppError::Forbidden("Only project admins can update projects".to_string()));
+    Ok(Json(ProjectMembersPage {
+        members: members.into_iter().map(ProjectMemberResponse::from).collect(),
+        total,
+        page,
+        per_page,
+    }))
+}
+
+// Open tasks per member, so editors can see who has room for more work. Only
+// reads, so unlike ProjectEditor it stays available in archived projects.
+pub async fn get_project_workload(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+    Query(query): Query<WorkloadQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let role = authz::require_project_role(app_state.database.pool(), project_id, current_user.id(), Permission::ViewProject).await?;
+    if !Permission::EditTasks.allows_project_role(&role) {
+        return Err(AppError::Forbidden("Need editor or admin role to view the workload".to_string()));
+    }
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from >= to {
+            return Err(AppError::Invalid(Message::new("invalid_date_range")));
+        }
     }
 
+    let workload = ProjectQueries::get_project_workload(app_state.database.pool(), project_id, query.from, query.to).await?;
+    let members: Vec<MemberWorkloadResponse> = workload
+        .into_iter()
+        .map(|(member, user, workload)| MemberWorkloadResponse { user, role: member.role, workload })
+        .collect();
+
+    Ok(Json(members))
+}
+
+// Members who could take a task, best fit first (see assignment.rs). Only
+// members who can work on tasks are suggested.
+pub async fn get_assignment_suggestions(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+    Query(query): Query<AssignmentSuggestionsQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    let role = authz::require_project_role(pool, project_id, current_user.id(), Permission::ViewProject).await?;
+    if !Permission::EditTasks.allows_project_role(&role) {
+        return Err(AppError::Forbidden("Need editor or admin role to get assignment suggestions".to_string()));
+    }
+
+    let task = TaskQueries::get_task_by_id(pool, query.task_id).await?;
+    if task.project_id != project_id {
+        return Err(AppError::NotFound("Task not found".to_string()));
+    }
+    let labels = task.tags.unwrap_or_default();
+
+    let since = chrono::Utc::now() - assignment::ACTIVITY_WINDOW;
+    let members = ProjectQueries::get_assignment_candidates(pool, project_id, since).await?;
+    let mut completed_labels = ProjectQueries::get_completed_labels(pool, project_id, task.id, &labels).await?;
+
+    let candidates = members
+        .into_iter()
+        .filter(|(member, ..)| Permission::EditOwnTasks.allows_project_role(&member.role))
+        .map(|(member, user, open_tasks, recent_actions)| {
+            let stats = CandidateStats {
+                open_tasks,
+                completed_labels: completed_labels.remove(&member.user_id).unwrap_or_default(),
+                recent_actions,
+            };
+            ((user, member.role), stats)
+        })
+        .collect();
+
+    let suggestions: Vec<AssignmentSuggestionResponse> = assignment::rank(candidates, &labels)
+        .into_iter()
+        .map(|suggestion| {
+            let (user, role) = suggestion.candidate;
+            AssignmentSuggestionResponse {
+                user,
+                role,
+                score: suggestion.score,
+                factors: suggestion.factors,
+                matched_labels: suggestion.matched_labels,
+            }
+        })
+        .collect();
+
+    Ok(Json(suggestions))
+}
+
+pub async fn get_project_usage(
+    State(app_state): State<crate::AppState>,
+    authz::ProjectMember(project_id): authz::ProjectMember,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    let project = ProjectQueries::get_project_by_id(pool, project_id).await?;
+    let limits = crate::quotas::team_limits(pool, project.team_id).await?;
+
+    Ok(Json(ProjectUsageResponse {
+        project_id,
+        storage: CounterQueries::get_attachment_storage(pool, project_id).await?,
+        max_attachment_bytes: limits.max_attachment_bytes_per_project,
+    }))
+}
+
+// Time in each status and cycle/lead time of the tasks completed in the
+// window, the last 30 days by default
+pub async fn get_cycle_time_analytics(
+    State(app_state): State<crate::AppState>,
+    authz::ProjectMember(project_id): authz::ProjectMember,
+    Query(query): Query<CycleTimeQuery>,
+) -> Result<Response, AppError> {
+    let format = query.format.as_deref().unwrap_or("json");
+    if format != "json" && format != "csv" {
+        return Err(AppError::Invalid(Message::new("unsupported_export_format").with("format", format)));
+    }
+    let to = query.to.unwrap_or_else(chrono::Utc::now);
+    let from = query.from.unwrap_or(to - analytics::DEFAULT_WINDOW);
+    if from >= to {
+        return Err(AppError::Invalid(Message::new("invalid_date_range")));
+    }
+
+    let report = ProjectQueries::get_cycle_times(app_state.database.pool(), project_id, from, to).await?;
+    if format == "json" {
+        return Ok(Json(report).into_response());
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"cycle-time-{}.csv\"", project_id)),
+        ],
+        analytics::cycle_time_csv(&report),
+    )
+        .into_response())
+}
+
+pub async fn get_my_permissions(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let role = authz::require_project_role(app_state.database.pool(), project_id, current_user.id(), Permission::ViewProject).await?;
+
+    // Archived projects only keep the permissions that don't change content
+    let is_active = ProjectQueries::is_project_active(app_state.database.pool(), project_id).await?;
+    let permissions = Permission::for_project_role(&role)
+        .into_iter()
+        .filter(|permission| is_active || !permission.is_content_mutation())
+        .collect();
+
+    Ok(Json(MyPermissionsResponse { project_id, role, permissions }))
+}
+
+pub async fn update_project(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+    Json(mut request): Json<CreateProjectRequest>,
+) -> Result<impl IntoResponse, AppError> {
     // Validate input
+    request.name = validation::normalize_name(&request.name);
     validation::validate_project_name(&request.name)?;
     if let Some(ref description) = request.description {
         validation::validate_project_description(description)?;
     }
-    if let Some(ref color) = request.color {
-        validation::validate_hex_color(color)?;
+    if let Some(ref mut color) = request.color {
+        *color = validation::normalize_color(color)?;
+    }
+    if let Some(ref icon) = request.icon {
+        validation::validate_emoji(icon)?;
     }
 
     let project = ProjectQueries::update_project(app_state.database.pool(), project_id, &request).await?;
@@ -174,43 +460,130 @@ pub async fn update_project(
     Ok(Json(project))
 }
 
-pub async fn archive_project(
+pub async fn get_project_workflow(
+    State(app_state): State<crate::AppState>,
+    authz::ProjectMember(project_id): authz::ProjectMember,
+) -> Result<impl IntoResponse, AppError> {
+    let workflow = ProjectQueries::get_project_workflow(app_state.database.pool(), project_id).await?;
+
+    Ok(Json(workflow))
+}
+
+pub async fn update_project_workflow(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+    Json(workflow): Json<ProjectWorkflow>,
+) -> Result<impl IntoResponse, AppError> {
+    validation::validate_project_workflow(&workflow)?;
+
+    ProjectQueries::update_project_workflow(app_state.database.pool(), project_id, &workflow).await?;
+
+    Ok(Json(workflow))
+}
+
+pub async fn get_onboarding_template(
+    State(app_state): State<crate::AppState>,
+    authz::ProjectMember(project_id): authz::ProjectMember,
+) -> Result<impl IntoResponse, AppError> {
+    let template = ProjectQueries::get_onboarding_template(app_state.database.pool(), project_id).await?;
+
+    Ok(Json(template))
+}
+
+pub async fn update_onboarding_template(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+    Json(mut template): Json<OnboardingTemplate>,
+) -> Result<impl IntoResponse, AppError> {
+    validation::validate_onboarding_template(&mut template)?;
+
+    ProjectQueries::update_onboarding_template(app_state.database.pool(), project_id, &template).await?;
+
+    Ok(Json(template))
+}
+
+pub async fn get_staleness_rules(
+    State(app_state): State<crate::AppState>,
+    authz::ProjectMember(project_id): authz::ProjectMember,
+) -> Result<impl IntoResponse, AppError> {
+    let rules = ProjectQueries::get_staleness_rules(app_state.database.pool(), project_id).await?;
+
+    Ok(Json(rules))
+}
+
+// Takes effect at the next scan, which also unmarks tasks the new rules no
+// longer consider stale
+pub async fn update_staleness_rules(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+    Json(rules): Json<StalenessRules>,
+) -> Result<impl IntoResponse, AppError> {
+    validation::validate_staleness_rules(&rules)?;
+
+    ProjectQueries::update_staleness_rules(app_state.database.pool(), project_id, &rules).await?;
+
+    Ok(Json(rules))
+}
+
+pub async fn get_mention_settings(
+    State(app_state): State<crate::AppState>,
+    authz::ProjectMember(project_id): authz::ProjectMember,
+) -> Result<impl IntoResponse, AppError> {
+    let settings = ProjectQueries::get_mention_settings(app_state.database.pool(), project_id).await?;
+
+    Ok(Json(settings))
+}
+
+pub async fn update_mention_settings(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+    Json(settings): Json<MentionSettings>,
+) -> Result<impl IntoResponse, AppError> {
+    ProjectQueries::update_mention_settings(app_state.database.pool(), project_id, &settings).await?;
+
+    Ok(Json(settings))
+}
+
+// The caller's own levels in the project, falling back to their defaults
+pub async fn get_notification_settings(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
-    Path(project_id): Path<Uuid>,
+    authz::ProjectMember(project_id): authz::ProjectMember,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project admin
-    let user_role = ProjectQueries::get_user_project_role(
-        app_state.database.pool(),
-        project_id,
-        current_user.id(),
-    ).await?;
+    let settings = notification_settings::load(app_state.database.pool(), current_user.id()).await?;
+    Ok(Json(settings.project_settings(project_id)))
+}
 
-    if !matches!(user_role, Some(ProjectRole::Admin)) {
-        return Err(AppError::Forbidden("Only project admins can archive projects".to_string()));
-    }
+// Only the event types in the body change; null goes back to the caller's default
+pub async fn update_notification_settings(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    authz::ProjectMember(project_id): authz::ProjectMember,
+    Json(levels): Json<BTreeMap<NotificationEventType, Option<NotificationLevel>>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    NotificationSettingsQueries::set_project_settings(pool, current_user.id(), project_id, &levels).await?;
+    settings_cache().forget(current_user.id());
 
+    let settings = notification_settings::load(pool, current_user.id()).await?;
+    Ok(Json(settings.project_settings(project_id)))
+}
+
+pub async fn archive_project(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+) -> Result<impl IntoResponse, AppError> {
     ProjectQueries::archive_project(app_state.database.pool(), project_id).await?;
 
+    app_state.websocket.close_project(project_id, WebSocketEvent::ProjectArchived { project_id }).await;
+
     Ok(StatusCode::NO_CONTENT)
 }
 
 pub async fn activate_project(
     State(app_state): State<crate::AppState>,
-    Extension(current_user): Extension<CurrentUser>,
-    Path(project_id): Path<Uuid>,
+    ProjectAdmin(project_id): ProjectAdmin,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project admin
-    let user_role = ProjectQueries::get_user_project_role(
-        app_state.database.pool(),
-        project_id,
-        current_user.id(),
-    ).await?;
-
-    if !matches!(user_role, Some(ProjectRole::Admin)) {
-        return Err(AppError::Forbidden("Only project admins can activate projects".to_string()));
-    }
-
     ProjectQueries::activate_project(app_state.database.pool(), project_id).await?;
 
     Ok(StatusCode::NO_CONTENT)
@@ -218,96 +591,119 @@ pub async fn activate_project(
 
 pub async fn delete_project(
     State(app_state): State<crate::AppState>,
-    Extension(current_user): Extension<CurrentUser>,
-    Path(project_id): Path<Uuid>,
+    ProjectAdmin(project_id): ProjectAdmin,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project admin
-    let user_role = ProjectQueries::get_user_project_role(
-        app_state.database.pool(),
-        project_id,
-        current_user.id(),
-    ).await?;
-
-    if !matches!(user_role, Some(ProjectRole::Admin)) {
-        return Err(AppError::Forbidden("Only project admins can delete projects".to_string()));
-    }
-
     ProjectQueries::delete_project(app_state.database.pool(), project_id).await?;
 
+    app_state.websocket.close_project(project_id, WebSocketEvent::ProjectDeleted { project_id }).await;
+
     Ok(StatusCode::NO_CONTENT)
 }
 
 pub async fn add_project_member(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
-    Path(project_id): Path<Uuid>,
+    ProjectAdmin(project_id): ProjectAdmin,
     Json(request): Json<AddProjectMemberRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project admin
-    let user_role = ProjectQueries::get_user_project_role(
-        app_state.database.pool(),
-        project_id,
-        current_user.id(),
-    ).await?;
-
-    if !matches!(user_role, Some(ProjectRole::Admin)) {
-        return Err(AppError::Forbidden("Only project admins can add members".to_string()));
-    }
-
-    // Get project to check team membership
+    // Users outside the team can only join as guests or viewers
     let project = ProjectQueries::get_project_by_id(app_state.database.pool(), project_id).await?;
-    
-    // Check if target user is a team member
-    if !TeamQueries::is_team_member(app_state.database.pool(), project.team_id, request.user_id).await? {
-        return Err(AppError::Validation("User must be a team member to join project".to_string()));
-    }
+    UserQueries::get_user_by_id(app_state.database.pool(), request.user_id).await?;
+    authz::check_outside_team_role(app_state.database.pool(), project.team_id, request.user_id, &request.role).await?;
 
     // Check if user is already a project member
     if ProjectQueries::is_project_member(app_state.database.pool(), project_id, request.user_id).await? {
         return Err(AppError::Conflict("User is already a project member".to_string()));
     }
 
-    let member = ProjectQueries::add_project_member(
+    // Onboarding tasks count towards the task quota but not WIP limits
+    let template = if request.skip_onboarding {
+        OnboardingTemplate::default()
+    } else {
+        ProjectQueries::get_onboarding_template(app_state.database.pool(), project_id).await?
+    };
+    if !template.tasks.is_empty() {
+        crate::quotas::check_can_create_tasks(app_state.database.pool(), project.team_id, project_id, template.tasks.len() as i64).await?;
+    }
+
+    let (member, onboarding_tasks) = ProjectQueries::add_project_member_with_onboarding(
         app_state.database.pool(),
         project_id,
         request.user_id,
-        request.role,
+        request.role.clone(),
+        &template,
+        current_user.id(),
     ).await?;
 
+    let actor: UserSummary = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?.into();
+    let joined: UserSummary = UserQueries::get_user_by_id(app_state.database.pool(), request.user_id).await?.into();
+    slack::notify(
+        app_state.database.pool(),
+        project_id,
+        &actor,
+        Notification::MemberJoined { member: &joined, role: &request.role },
+    ).await;
+
+    let event = WebSocketEvent::MemberAdded(MemberAddedEventData {
+        project_id,
+        member: joined,
+        role: request.role,
+        onboarding_tasks,
+        user: actor,
+    });
+    app_state.websocket.broadcast_to_project(project_id, event, None).await;
+
     Ok((StatusCode::CREATED, Json(member)))
 }
 
-pub async fn remove_project_member(
+// Any member can leave. Their tasks in the project are unassigned and their
+// WebSocket subscription to it ends.
+pub async fn leave_project(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
-    Path((project_id, user_id)): Path<(Uuid, Uuid)>,
+    Path(project_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project admin or removing themselves
-    let user_role = ProjectQueries::get_user_project_role(
-        app_state.database.pool(),
+    authz::require_project_role(app_state.database.pool(), project_id, current_user.id(), Permission::ViewProject).await?;
+
+    leave(&app_state, project_id, current_user.id()).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn leave(app_state: &crate::AppState, project_id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+    let unassigned_task_ids = ProjectQueries::leave_project(app_state.database.pool(), project_id, user_id).await?;
+
+    app_state.websocket.remove_member(
         project_id,
-        current_user.id(),
-    ).await?;
+        user_id,
+        WebSocketEvent::MemberRemoved { project_id, user_id, unassigned_task_ids },
+    ).await;
 
-    let is_admin = matches!(user_role, Some(ProjectRole::Admin));
-    let is_self = current_user.id() == user_id;
+    Ok(())
+}
 
-    if !is_admin && !is_self {
-        return Err(AppError::Forbidden("Can only remove yourself or be project admin".to_string()));
+pub async fn remove_project_member(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path((project_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, AppError> {
+    // Removing yourself is leaving; only admins can remove others
+    if current_user.id() == user_id {
+        authz::require_project_role(app_state.database.pool(), project_id, user_id, Permission::ViewProject).await?;
+        leave(&app_state, project_id, user_id).await?;
+        return Ok(StatusCode::NO_CONTENT);
     }
 
-    // Prevent removing the last admin
-    if user_id == current_user.id() && is_admin {
-        let members = ProjectQueries::get_project_members(app_state.database.pool(), project_id).await?;
-        let admin_count = members.iter().filter(|(member, _)| matches!(member.role, ProjectRole::Admin)).count();
-        
-        if admin_count <= 1 {
-            return Err(AppError::Validation("Cannot remove the last admin from project".to_string()));
-        }
-    }
+    authz::require_project_role(app_state.database.pool(), project_id, current_user.id(), Permission::ManageProject).await?;
 
     ProjectQueries::remove_project_member(app_state.database.pool(), project_id, user_id).await?;
 
+    app_state.websocket.remove_member(
+        project_id,
+        user_id,
+        WebSocketEvent::MemberRemoved { project_id, user_id, unassigned_task_ids: Vec::new() },
+    ).await;
+
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -317,16 +713,7 @@ pub async fn update_project_member_role(
     Path((project_id, user_id)): Path<(Uuid, Uuid)>,
     Json(request): Json<UpdateProjectMemberRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project admin
-    let user_role = ProjectQueries::get_user_project_role(
-        app_state.database.pool(),
-        project_id,
-        current_user.id(),
-    ).await?;
-
-    if !matches!(user_role, Some(ProjectRole::Admin)) {
-        return Err(AppError::Forbidden("Only project admins can update member roles".to_string()));
-    }
+    authz::require_project_role(app_state.database.pool(), project_id, current_user.id(), Permission::ManageProject).await?;
 
     // Prevent demoting the last admin
     if !matches!(request.role, ProjectRole::Admin) {
@@ -342,6 +729,9 @@ pub async fn update_project_member_role(
         }
     }
 
+    let project = ProjectQueries::get_project_by_id(app_state.database.pool(), project_id).await?;
+    authz::check_outside_team_role(app_state.database.pool(), project.team_id, user_id, &request.role).await?;
+
     let member = ProjectQueries::update_project_member_role(
         app_state.database.pool(),
         project_id,
@@ -0,0 +1,44 @@
+// The cmd-K switcher, see quick_switch.rs
+
+use axum::{
+    extract::{Extension, State},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::auth::middleware::CurrentUser;
+use crate::database::queries::QuickSwitchQueries;
+use crate::quick_switch;
+use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Query};
+use crate::utils::validation;
+
+// Candidates of each kind handed to the ranking
+const CANDIDATES_PER_KIND: i64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct QuickSwitchQuery {
+    #[serde(default)]
+    pub q: String,
+}
+
+pub async fn quick_switch(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<QuickSwitchQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let q = query.q.trim();
+    validation::validate_picker_query(q)?;
+
+    let now = Utc::now();
+    let candidates = QuickSwitchQueries::get_candidates(
+        app_state.database.pool(),
+        current_user.id(),
+        q,
+        now - quick_switch::RECENT_TASKS,
+        CANDIDATES_PER_KIND,
+    ).await?;
+
+    Ok(Json(quick_switch::rank(candidates, q, now)))
+}
@@ -0,0 +1,101 @@
+// Scheduled project reports. Members can list and preview them; setting them
+// up and removing them is for project admins.
+
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::auth::authz::{ProjectAdmin, ProjectMember};
+use crate::auth::middleware::CurrentUser;
+use crate::database::{
+    models::CreateProjectReportRequest,
+    queries::{ProjectQueries, ReportQueries, TaskQueries},
+};
+use crate::integrations::app_base_url;
+use crate::reports;
+use crate::utils::errors::{AppError, FieldError};
+use crate::utils::extractors::{Json, Path};
+use crate::utils::i18n::Message;
+use crate::utils::validation;
+
+#[derive(Debug, Serialize)]
+pub struct ReportPreview {
+    pub markdown: String,
+}
+
+pub async fn get_project_reports(
+    State(app_state): State<crate::AppState>,
+    ProjectMember(project_id): ProjectMember,
+) -> Result<impl IntoResponse, AppError> {
+    let reports = ReportQueries::get_project_reports(app_state.database.pool(), project_id).await?;
+
+    Ok(Json(reports))
+}
+
+pub async fn create_project_report(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    ProjectAdmin(project_id): ProjectAdmin,
+    Json(mut request): Json<CreateProjectReportRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    validation::validate_project_report(&mut request)?;
+
+    let mut errors = Vec::new();
+    for user_id in &request.recipients.user_ids {
+        if !ProjectQueries::is_project_member(pool, project_id, *user_id).await? {
+            errors.push(FieldError::new(
+                "recipients.user_ids",
+                Message::new("report_recipient_not_member").with("user", user_id.to_string()),
+            ));
+        }
+    }
+    if let Some(task_id) = request.recipients.comment_task_id {
+        let in_project = match TaskQueries::get_task_by_id(pool, task_id).await {
+            Ok(task) => task.project_id == project_id,
+            Err(AppError::NotFound(_)) => false,
+            Err(e) => return Err(e),
+        };
+        if !in_project {
+            errors.push(FieldError::new("recipients.comment_task_id", Message::new("report_task_not_in_project")));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(AppError::InvalidFields(errors));
+    }
+
+    let report = ReportQueries::create_report(pool, project_id, &request, current_user.id()).await?;
+
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+pub async fn delete_project_report(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+    Path((_, report_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, AppError> {
+    ReportQueries::delete_report(app_state.database.pool(), project_id, report_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// The report as it would look if it were sent now, covering one period
+pub async fn preview_project_report(
+    State(app_state): State<crate::AppState>,
+    ProjectMember(project_id): ProjectMember,
+    Path((_, report_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    let report = ReportQueries::get_report(pool, project_id, report_id).await?;
+
+    let now = Utc::now();
+    let aggregates = reports::gather(pool, project_id, now - reports::period_length(report.cadence), now).await?;
+    let markdown = reports::render_report(&report, &aggregates, &app_base_url());
+
+    Ok(Json(ReportPreview { markdown }))
+}
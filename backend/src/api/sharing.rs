@@ -0,0 +1,109 @@
+// Share tokens and the public badges they unlock. Token management is for
+// project admins; the badge endpoints take no auth and are rate limited per IP.
+
+use axum::{
+    extract::{Extension, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::authz::ProjectAdmin;
+use crate::auth::middleware::CurrentUser;
+use crate::database::queries::ShareTokenQueries;
+use crate::sharing::{self, BadgeMetric, BadgeSummary};
+use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Path, Query};
+use crate::utils::rate_limit::{self, ClientIp};
+
+// Lets browsers and READMEs cache badges as long as the server does
+const BADGE_CACHE_CONTROL: &str = "public, max-age=60";
+
+#[derive(Debug, Deserialize)]
+pub struct BadgeQuery {
+    #[serde(default)]
+    pub metric: BadgeMetric,
+}
+
+pub async fn get_share_tokens(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+) -> Result<impl IntoResponse, AppError> {
+    let tokens = ShareTokenQueries::get_project_tokens(app_state.database.pool(), project_id).await?;
+
+    Ok(Json(tokens))
+}
+
+pub async fn create_share_token(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    ProjectAdmin(project_id): ProjectAdmin,
+) -> Result<impl IntoResponse, AppError> {
+    let token = ShareTokenQueries::create_token(
+        app_state.database.pool(),
+        project_id,
+        &sharing::generate_token(),
+        current_user.id(),
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(token)))
+}
+
+pub async fn revoke_share_token(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+    Path((_, token_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, AppError> {
+    let token = ShareTokenQueries::delete_token(app_state.database.pool(), project_id, token_id).await?;
+    sharing::badge_cache().forget(&token.token);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn badge_summary(app_state: &crate::AppState, ip: std::net::IpAddr, token: &str) -> Result<BadgeSummary, AppError> {
+    rate_limit::badge_limiter().check(ip)?;
+
+    let cache = sharing::badge_cache();
+    let stats = match cache.get(token) {
+        Some(stats) => stats,
+        None => {
+            let stats = ShareTokenQueries::get_badge_stats(app_state.database.pool(), token).await?;
+            cache.insert(token, stats.clone());
+            stats
+        }
+    };
+
+    stats
+        .map(BadgeSummary::from)
+        .ok_or_else(|| AppError::NotFound("Share token not found".to_string()))
+}
+
+pub async fn get_badge_svg(
+    State(app_state): State<crate::AppState>,
+    ClientIp(ip): ClientIp,
+    Path(share_token): Path<String>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let summary = badge_summary(&app_state, ip, &share_token).await?;
+    let (label, message, color) = sharing::badge_text(&summary, query.metric);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, BADGE_CACHE_CONTROL),
+        ],
+        sharing::render_badge(label, &message, color),
+    ))
+}
+
+pub async fn get_badge_json(
+    State(app_state): State<crate::AppState>,
+    ClientIp(ip): ClientIp,
+    Path(share_token): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let summary = badge_summary(&app_state, ip, &share_token).await?;
+
+    Ok(([(header::CACHE_CONTROL, BADGE_CACHE_CONTROL)], Json(summary)))
+}
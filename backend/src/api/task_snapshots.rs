@@ -0,0 +1,118 @@
+// Printable task renderings and stored snapshots, see task_snapshots.rs
+
+use axum::{
+    extract::{Extension, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::authz::{self, Permission, Resource};
+use crate::auth::middleware::CurrentUser;
+use crate::database::models::{CreateTaskSnapshotRequest, StoredTaskSnapshot, TaskSnapshot};
+use crate::database::queries::{TaskQueries, TaskSnapshotQueries};
+use crate::task_snapshots::{self, SnapshotFormat};
+use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Path, Query};
+use crate::utils::i18n::Message;
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotQuery {
+    // json (default) or html
+    pub format: Option<String>,
+    // Include the comments; only for the live rendering
+    #[serde(default)]
+    pub comments: bool,
+}
+
+impl SnapshotQuery {
+    fn format(&self) -> Result<SnapshotFormat, AppError> {
+        let format = self.format.as_deref().unwrap_or("json");
+        SnapshotFormat::parse(format)
+            .ok_or_else(|| AppError::Invalid(Message::new("unsupported_export_format").with("format", format)))
+    }
+}
+
+fn respond(format: SnapshotFormat, snapshot: &TaskSnapshot, stored: Option<StoredTaskSnapshot>) -> Response {
+    match format {
+        SnapshotFormat::Html => (
+            [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+            task_snapshots::render_html(snapshot, stored.as_ref()),
+        )
+            .into_response(),
+        SnapshotFormat::Json => match stored {
+            Some(stored) => Json(stored).into_response(),
+            None => Json(snapshot).into_response(),
+        },
+    }
+}
+
+// The task as it is now
+pub async fn get_task_snapshot(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(task_id): Path<Uuid>,
+    Query(query): Query<SnapshotQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let format = query.format()?;
+    let pool = app_state.database.pool();
+    let task = TaskQueries::get_task_by_id(pool, task_id).await?;
+    authz::require_resource_role(&app_state, Resource::Task, task.project_id, current_user.id(), Permission::ViewProject).await?;
+
+    let snapshot = task_snapshots::capture(pool, task, query.comments).await?;
+
+    Ok(respond(format, &snapshot, None))
+}
+
+pub async fn create_task_snapshot(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(task_id): Path<Uuid>,
+    Json(request): Json<CreateTaskSnapshotRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let note = task_snapshots::validate_note(request.note)?;
+    let pool = app_state.database.pool();
+    let task = TaskQueries::get_task_by_id(pool, task_id).await?;
+    authz::require_resource_role(&app_state, Resource::Task, task.project_id, current_user.id(), Permission::ViewProject).await?;
+
+    let project_id = task.project_id;
+    let snapshot = task_snapshots::capture(pool, task, request.include_comments).await?;
+    let stored = TaskSnapshotQueries::create(pool, project_id, current_user.id(), note.as_deref(), &snapshot).await?;
+
+    Ok((StatusCode::CREATED, Json(stored)))
+}
+
+pub async fn list_task_snapshots(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(task_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    let task = TaskQueries::get_task_by_id(pool, task_id).await?;
+    authz::require_resource_role(&app_state, Resource::Task, task.project_id, current_user.id(), Permission::ViewProject).await?;
+
+    let snapshots = TaskSnapshotQueries::list(pool, task_id).await?;
+
+    Ok(Json(snapshots))
+}
+
+pub async fn get_stored_task_snapshot(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path((task_id, snapshot_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<SnapshotQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let format = query.format()?;
+    let pool = app_state.database.pool();
+    let task = TaskQueries::get_task_by_id(pool, task_id).await?;
+    authz::require_resource_role(&app_state, Resource::Task, task.project_id, current_user.id(), Permission::ViewProject).await?;
+
+    let mut stored = TaskSnapshotQueries::get(pool, task_id, snapshot_id).await?;
+    let snapshot = stored.snapshot.clone().ok_or_else(|| AppError::NotFound("Snapshot not found".to_string()))?;
+    if format == SnapshotFormat::Html {
+        stored.snapshot = None;
+    }
+
+    Ok(respond(format, &snapshot, Some(stored)))
+}
@@ -1,20 +1,36 @@
 use axum::{
-    extract::{Extension, State, Path, Query},
-    response::IntoResponse,
-    Json,
-    http::StatusCode,
+    extract::{Extension, State},
+    response::{IntoResponse, Response},
+    http::{HeaderMap, StatusCode},
 };
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
+use crate::auth::authz::{self, Permission, ProjectAdmin, ProjectContributor, ProjectMember, Resource};
 use crate::auth::middleware::CurrentUser;
+use crate::automations;
+use crate::due_dates;
+use crate::duplicates;
 use crate::database::{
-    models::{CreateTaskRequest, UpdateTaskRequest, Task, MoveTaskRequest, TaskStatus, TaskPriority, UserSummary},
-    queries::{TaskQueries, ProjectQueries, UserQueries}
+    models::{PendingModeration, CreateProjectTaskRequest, CreateColumnTaskRequest, UpdateTaskRequest, Task, TaskLink, TaskRelation, TaskRelationType, CreateTaskRelationRequest, MoveTaskRequest, TaskStatus, TaskPriority, UserSummary, TaskListFilter, TaskGroupBy, TaskGroupCount, UnreadFilter, TaskReadState, TaskSort, SortOrder, ColumnOrder, DueFilter, SetTaskOrderRequest, PickerScope, RelatedTask, NudgeTaskRequest, TaskComment},
+    queries::{ModerationQueueQueries, TaskQueries, TaskLinkQueries, TaskRelationQueries, ProjectQueries, BoardQueries, UserQueries, TaskReadQueries, TaskOrderQueries, DigestQueries, StalenessQueries}
 };
+use crate::integrations::slack::{self, blocks::Notification};
+use crate::mentions;
+use crate::notifications;
+use crate::positions;
+use crate::quick_switch;
+use crate::screening::{self, Screening};
 use crate::utils::errors::AppError;
+use crate::utils::etag::ETag;
+use crate::utils::extractors::{Json, Path, Query};
+use crate::utils::fields::SparseFields;
+use crate::utils::search;
 use crate::utils::validation;
-use crate::websocket::events::{WebSocketEvent, TaskEventData, TaskMoveEventData};
+use crate::websocket::events::{WebSocketEvent, TaskEventData, BoardColumn, CommentEventData, TaskMoveEventData, TasksReorderedEventData};
+use crate::websocket::origin::{Mutation, Origin};
+use crate::wip::{self, WipCheck};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TaskFilters {
@@ -22,24 +38,106 @@ pub struct TaskFilters {
     pub priority: Option<TaskPriority>,
     pub assigned_to: Option<Uuid>,
     pub tag: Option<String>,
+    pub unread: Option<bool>,
+    // Marked by the staleness job, see staleness.rs
+    pub stale: Option<bool>,
+    pub fields: Option<String>,
+    // Instance admins only
+    #[serde(default)]
+    pub include_quarantined: bool,
+}
+
+impl TaskFilters {
+    pub fn list_filter(&self, user_id: Uuid) -> TaskListFilter {
+        TaskListFilter {
+            status: self.status,
+            priority: self.priority,
+            assigned_to: self.assigned_to,
+            tag: self.tag.clone(),
+            unread: self.unread.map(|unread| UnreadFilter { user_id, unread }),
+        }
+    }
+}
+
+// Task list entry with the current user's read state
+#[derive(Debug, Serialize)]
+pub struct TaskListItem {
+    #[serde(flatten)]
+    pub task: Task,
+    pub unread: bool,
+    pub unread_comment_count: i64,
+    pub stale: bool,
+}
+
+impl TaskListItem {
+    pub const FIELDS: &'static [&'static str] = &[
+        "id", "title", "description", "project_id", "created_by", "assigned_to",
+        "status", "priority", "due_date", "is_all_day", "tags", "position", "number", "created_at", "updated_at",
+        "unread", "unread_comment_count", "stale",
+    ];
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TaskCountsQuery {
+    pub status: Option<TaskStatus>,
+    pub priority: Option<TaskPriority>,
+    pub assigned_to: Option<Uuid>,
+    pub tag: Option<String>,
+    pub unread: Option<bool>,
+    pub group_by: Option<TaskGroupBy>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TaskPickerQuery {
+    #[serde(default)]
+    pub q: String,
+    // Comma separated task ids, e.g. the task being linked from
+    pub exclude: Option<String>,
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub scope: PickerScope,
+}
+
+const MAX_PICKER_RESULTS: i64 = 10;
+
+#[derive(Debug, Deserialize)]
+pub struct AssignedTasksQuery {
+    #[serde(default)]
+    pub sort: TaskSort,
+    // Defaults depend on the sort key, see TaskSort::default_order
+    pub order: Option<SortOrder>,
+    // Open tasks past due, or due within the next week
+    pub due: Option<DueFilter>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskCountsResponse {
+    pub group_by: TaskGroupBy,
+    pub counts: Vec<TaskGroupCount>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskDetailsResponse {
+    #[serde(flatten)]
+    pub task: Task,
+    // Commits and pull requests referencing the task (GitHub integration)
+    pub links: Vec<TaskLink>,
+    // Relations to other tasks, as seen from this one
+    pub relations: Vec<TaskRelation>,
+    // Held by content screening; only its author sees it until it's approved
+    pub pending_moderation: bool,
 }
 
 pub async fn create_task(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
-    Path(project_id): Path<Uuid>,
-    Json(request): Json<CreateTaskRequest>,
-) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project member
-    if !ProjectQueries::is_project_member(app_state.database.pool(), project_id, current_user.id()).await? {
-        return Err(AppError::Forbidden("Must be a project member to create tasks".to_string()));
-    }
-
+    ProjectContributor(project_id): ProjectContributor,
+    origin: Origin,
+    Json(Mutation { body: CreateProjectTaskRequest { task: mut request, check_duplicates, force }, client_mutation_id }): Json<Mutation<CreateProjectTaskRequest>>,
+) -> Result<Response, AppError> {
+    let origin = origin.with_body(client_mutation_id)?;
     // Validate input
-    validation::validate_task_title(&request.title)?;
-    if let Some(ref description) = request.description {
-        validation::validate_task_description(description)?;
-    }
+    validation::validate_create_task(&mut request)?;
 
     // Validate assigned user is a project member if provided
     if let Some(assigned_to) = request.assigned_to {
@@ -47,6 +145,14 @@ pub async fn create_task(
             return Err(AppError::Validation("Assigned user must be a project member".to_string()));
         }
     }
+    let mentions = mentions::parse(request.description.as_deref().unwrap_or_default());
+
+    let project = ProjectQueries::get_project_by_id(app_state.database.pool(), project_id).await?;
+    crate::quotas::check_can_create_task(app_state.database.pool(), project.team_id, project_id).await?;
+    duplicates::check(app_state.database.pool(), project_id, &request.title, check_duplicates, force).await?;
+    let wip_check = wip::check_create(app_state.database.pool(), project_id, TaskStatus::Todo).await?;
+    mentions::check(app_state.database.pool(), project_id, current_user.id(), &mentions).await?;
+    let held = screening::check(&app_state, project.team_id, &screening::task_text(&request.title, request.description.as_deref())).await;
 
     let task = TaskQueries::create_task(
         app_state.database.pool(),
@@ -55,53 +161,232 @@ pub async fn create_task(
         current_user.id(),
     ).await?;
 
+    if let Some(screening) = held {
+        return hold_created_task(&app_state, task, &screening).await;
+    }
+
+    announce_created_task(&app_state, current_user.id(), &origin, &task, None, &wip_check).await?;
+    mentions::notify(&app_state, &task, None, task.description.as_deref().unwrap_or_default(), &mentions, current_user.id()).await;
+
+    Ok((StatusCode::CREATED, Json(task)).into_response())
+}
+
+// Quick-add from a board column: the task is created in the column's status at
+// the requested end, so clients get one TaskCreated instead of a create and a move
+pub async fn create_column_task(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path((board_id, column_id)): Path<(Uuid, TaskStatus)>,
+    origin: Origin,
+    Json(Mutation { body: mut request, client_mutation_id }): Json<Mutation<CreateColumnTaskRequest>>,
+) -> Result<Response, AppError> {
+    let origin = origin.with_body(client_mutation_id)?;
+    let board = BoardQueries::get_board_by_id(app_state.database.pool(), board_id).await?;
+    let project_id = board.project_id;
+
+    authz::require_resource_role(&app_state, Resource::Board, project_id, current_user.id(), Permission::CreateTasks).await?;
+
+    validation::validate_create_task(&mut request.task)?;
+    if let Some(assigned_to) = request.task.assigned_to {
+        if !ProjectQueries::is_project_member(app_state.database.pool(), project_id, assigned_to).await? {
+            return Err(AppError::Validation("Assigned user must be a project member".to_string()));
+        }
+    }
+    let mentions = mentions::parse(request.task.description.as_deref().unwrap_or_default());
+
+    // Same rules as creating the task in Todo and moving it over
+    if column_id != TaskStatus::Todo {
+        let workflow = ProjectQueries::get_project_workflow(app_state.database.pool(), project_id).await?;
+        validation::validate_status_transition(&workflow, TaskStatus::Todo, column_id)?;
+    }
+
+    let project = ProjectQueries::get_project_by_id(app_state.database.pool(), project_id).await?;
+    crate::quotas::check_can_create_task(app_state.database.pool(), project.team_id, project_id).await?;
+    let wip_check = wip::check_create(app_state.database.pool(), project_id, column_id).await?;
+    mentions::check(app_state.database.pool(), project_id, current_user.id(), &mentions).await?;
+    let held = screening::check(&app_state, project.team_id, &screening::task_text(&request.task.title, request.task.description.as_deref())).await;
+
+    let task = TaskQueries::create_task_in_column(
+        app_state.database.pool(),
+        project_id,
+        &request.task,
+        current_user.id(),
+        column_id,
+        request.placement,
+    ).await?;
+
+    if let Some(screening) = held {
+        return hold_created_task(&app_state, task, &screening).await;
+    }
+
+    let column = BoardColumn { board_id, column_id };
+    announce_created_task(&app_state, current_user.id(), &origin, &task, Some(column), &wip_check).await?;
+    mentions::notify(&app_state, &task, None, task.description.as_deref().unwrap_or_default(), &mentions, current_user.id()).await;
+
+    Ok((StatusCode::CREATED, Json(task)).into_response())
+}
+
+// Queues a new task that content screening flagged instead of announcing it.
+// The author gets it back marked as pending.
+async fn hold_created_task(app_state: &crate::AppState, task: Task, screening: &Screening) -> Result<Response, AppError> {
+    ModerationQueueQueries::hold(app_state.database.pool(), task.id, None, task.project_id, task.created_by, screening).await?;
+
+    Ok((StatusCode::ACCEPTED, Json(PendingModeration { content: task, pending_moderation: true })).into_response())
+}
+
+pub(crate) async fn announce_created_task(
+    app_state: &crate::AppState,
+    author_id: Uuid,
+    origin: &Origin,
+    task: &Task,
+    column: Option<BoardColumn>,
+    wip_check: &WipCheck,
+) -> Result<(), AppError> {
+    TaskReadQueries::mark_task_read(app_state.database.pool(), author_id, task.id).await?;
+
     // Broadcast task creation to WebSocket subscribers
-    let user = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?;
+    let user = UserQueries::get_user_by_id(app_state.database.pool(), author_id).await?;
     let user_summary: UserSummary = user.into();
-    
+
+    slack::notify(app_state.database.pool(), task.project_id, &user_summary, Notification::TaskCreated { task }).await;
+    notifications::notify_assignment_change(app_state, None, task, &user_summary).await;
+
     let event = WebSocketEvent::TaskCreated(TaskEventData {
         task: task.clone(),
-        project_id,
+        project_id: task.project_id,
         user: user_summary,
+        column,
+        client_mutation_id: origin.client_mutation_id.clone(),
     });
-    
-    app_state.websocket.broadcast_to_project(project_id, event, None).await;
 
-    Ok((StatusCode::CREATED, Json(task)))
+    app_state.websocket.broadcast_to_project(task.project_id, event, origin.connection_id).await;
+    broadcast_wip_changes(app_state, task.project_id, wip_check).await;
+
+    Ok(())
 }
 
 pub async fn get_project_tasks(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
-    Path(project_id): Path<Uuid>,
+    ProjectMember(project_id): ProjectMember,
     Query(filters): Query<TaskFilters>,
-) -> Result<impl IntoResponse, AppError> {
-    // Check if user is project member
-    if !ProjectQueries::is_project_member(app_state.database.pool(), project_id, current_user.id()).await? {
-        return Err(AppError::Forbidden("Must be a project member to view tasks".to_string()));
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let fields = SparseFields::parse(filters.fields.as_deref(), TaskListItem::FIELDS)?;
+    if filters.include_quarantined {
+        crate::api::admin::ensure_instance_admin(&app_state, &current_user).await?;
     }
 
-    let mut tasks = TaskQueries::get_project_tasks(app_state.database.pool(), project_id).await?;
+    // Read state is per user, so reads and new comments change the ETag too
+    let last_modified = ProjectQueries::get_project_last_modified(app_state.database.pool(), project_id).await?;
+    let read_version = TaskReadQueries::get_read_state_version(app_state.database.pool(), project_id, current_user.id()).await?;
+    let etag = ETag::weak_from_timestamp(read_version.map_or(last_modified, |version| version.max(last_modified)));
+    if etag.matches(&headers) {
+        return Ok(etag.not_modified());
+    }
+
+    let tasks = TaskQueries::get_project_tasks(app_state.database.pool(), project_id, filters.include_quarantined).await?;
+    let mut read_states: HashMap<Uuid, TaskReadState> = TaskReadQueries::get_read_states(app_state.database.pool(), project_id, current_user.id())
+        .await?
+        .into_iter()
+        .map(|state| (state.task_id, state))
+        .collect();
+    let stale: HashSet<Uuid> = StalenessQueries::get_stale_task_ids(app_state.database.pool(), project_id)
+        .await?
+        .into_iter()
+        .collect();
 
     // Apply filters
-    if let Some(status) = filters.status {
-        tasks.retain(|task| task.status == status);
-    }
-    if let Some(priority) = filters.priority {
-        tasks.retain(|task| task.priority == priority);
-    }
-    if let Some(assigned_to) = filters.assigned_to {
-        tasks.retain(|task| task.assigned_to == Some(assigned_to));
+    let filter = filters.list_filter(current_user.id());
+    let items: Vec<TaskListItem> = tasks
+        .into_iter()
+        .filter(|task| filter.matches(task))
+        .map(|task| {
+            let state = read_states.remove(&task.id);
+            TaskListItem {
+                unread: state.as_ref().is_none_or(|state| state.unread),
+                unread_comment_count: state.map_or(0, |state| state.unread_comment_count),
+                stale: stale.contains(&task.id),
+                task,
+            }
+        })
+        .filter(|item| filter.unread.is_none_or(|unread| item.unread == unread.unread))
+        .filter(|item| filters.stale.is_none_or(|stale| item.stale == stale))
+        .collect();
+
+    Ok(etag.json(SparseFields::project(fields.as_ref(), &items)?))
+}
+
+// Lightweight typeahead for linking tasks, matching titles and task numbers
+pub async fn pick_tasks(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    ProjectMember(project_id): ProjectMember,
+    Query(query): Query<TaskPickerQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let q = query.q.trim();
+    validation::validate_picker_query(q)?;
+
+    let exclude = query
+        .exclude
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| Uuid::parse_str(id).map_err(|_| AppError::Validation(format!("Invalid task id in exclude: {}", id))))
+        .collect::<Result<Vec<Uuid>, AppError>>()?;
+
+    let project_ids = match query.scope {
+        PickerScope::Project => vec![project_id],
+        PickerScope::Team => ProjectQueries::get_member_team_project_ids(app_state.database.pool(), project_id, current_user.id()).await?,
+    };
+
+    let limit = query.limit.unwrap_or(MAX_PICKER_RESULTS).clamp(1, MAX_PICKER_RESULTS);
+    let tasks: Vec<RelatedTask> = TaskQueries::pick_tasks(
+        app_state.database.pool(),
+        &project_ids,
+        &exclude,
+        q,
+        search::task_number(q),
+        limit,
+    ).await?;
+
+    Ok(Json(tasks))
+}
+
+// Counts for board column headers, with the same filters as the task list
+pub async fn get_task_counts(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    ProjectMember(project_id): ProjectMember,
+    Query(query): Query<TaskCountsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let mut last_modified = ProjectQueries::get_project_last_modified(app_state.database.pool(), project_id).await?;
+    if query.unread.is_some() {
+        let read_version = TaskReadQueries::get_read_state_version(app_state.database.pool(), project_id, current_user.id()).await?;
+        last_modified = read_version.map_or(last_modified, |version| version.max(last_modified));
     }
-    if let Some(tag) = filters.tag {
-        tasks.retain(|task| {
-            task.tags.as_ref()
-                .map(|tags| tags.contains(&tag))
-                .unwrap_or(false)
-        });
+    let etag = ETag::weak_from_timestamp(last_modified);
+    if etag.matches(&headers) {
+        return Ok(etag.not_modified());
     }
 
-    Ok(Json(tasks))
+    let filter = TaskListFilter {
+        status: query.status,
+        priority: query.priority,
+        assigned_to: query.assigned_to,
+        tag: query.tag,
+        unread: query.unread.map(|unread| UnreadFilter { user_id: current_user.id(), unread }),
+    };
+    let group_by = query.group_by.unwrap_or_default();
+    let counts = match group_by {
+        TaskGroupBy::Status => TaskQueries::count_tasks_by_status(app_state.database.pool(), project_id, &filter).await?,
+        _ => TaskQueries::count_tasks_grouped(app_state.database.pool(), project_id, &filter, group_by).await?,
+    };
+
+    Ok(etag.json(TaskCountsResponse { group_by, counts }))
 }
 
 pub async fn get_task_details(
@@ -111,62 +396,217 @@ pub async fn get_task_details(
 ) -> Result<impl IntoResponse, AppError> {
     let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
 
-    // Check if user is project member
-    if !ProjectQueries::is_project_member(app_state.database.pool(), task.project_id, current_user.id()).await? {
-        return Err(AppError::Forbidden("Not a project member".to_string()));
+    authz::require_resource_role(&app_state, Resource::Task, task.project_id, current_user.id(), Permission::ViewProject).await?;
+    let pending_moderation = ModerationQueueQueries::is_task_pending(app_state.database.pool(), task_id).await?;
+    if pending_moderation && task.created_by != current_user.id() {
+        return Err(AppError::NotFound("Task not found".to_string()));
     }
+    quick_switch::record_access(app_state.database.pool(), current_user.id(), task.project_id).await;
+
+    let links = TaskLinkQueries::get_task_links(app_state.database.pool(), task_id).await?;
+    let relations = TaskRelationQueries::get_task_relations(app_state.database.pool(), task_id, current_user.id()).await?;
 
-    Ok(Json(task))
+    Ok(Json(TaskDetailsResponse { task, links, relations, pending_moderation }))
 }
 
-pub async fn update_task(
+#[derive(Debug, Serialize)]
+pub struct TaskRelationResponse {
+    #[serde(flatten)]
+    pub relation: TaskRelation,
+    // The duplicate, when close_duplicate moved it to Done
+    pub closed_task: Option<Task>,
+}
+
+// Links the task to another one, which may be in a different project as long
+// as the caller is a member there too
+// Resolves a task link to the project, board and column to open it in
+pub async fn get_task_context(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
     Path(task_id): Path<Uuid>,
-    Json(request): Json<UpdateTaskRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let context = TaskQueries::get_task_context(app_state.database.pool(), task_id).await?;
+
+    authz::require_resource_role(&app_state, Resource::Task, context.project.id, current_user.id(), Permission::ViewProject).await?;
+
+    Ok(Json(context))
+}
+
+pub async fn create_task_relation(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(task_id): Path<Uuid>,
+    origin: Origin,
+    Json(request): Json<CreateTaskRelationRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
 
-    // Check if user is project member (at least editor role required for updates)
-    let user_role = ProjectQueries::get_user_project_role(
+    authz::require_task_edit(&app_state, &task, current_user.id()).await?;
+
+    if request.task_id == task_id {
+        return Err(AppError::Validation("A task can't be related to itself".to_string()));
+    }
+    let related = TaskQueries::get_task_by_id(app_state.database.pool(), request.task_id).await?;
+    authz::require_resource_role(&app_state, Resource::Task, related.project_id, current_user.id(), Permission::ViewProject).await?;
+
+    let duplicate = match request.relation {
+        TaskRelationType::Duplicates => Some(&task),
+        TaskRelationType::IsDuplicatedBy => Some(&related),
+        TaskRelationType::RelatesTo => None,
+    };
+    // Checked up front so a close that isn't allowed doesn't leave the relation behind
+    let close = match (request.close_duplicate, duplicate) {
+        (false, _) => None,
+        (true, None) => {
+            return Err(AppError::Validation("close_duplicate only applies to duplicates".to_string()));
+        }
+        (true, Some(duplicate)) if duplicate.status == TaskStatus::Done => None,
+        (true, Some(duplicate)) => {
+            authz::require_task_edit(&app_state, duplicate, current_user.id()).await?;
+            let workflow = ProjectQueries::get_project_workflow(app_state.database.pool(), duplicate.project_id).await?;
+            validation::validate_status_transition(&workflow, duplicate.status, TaskStatus::Done)?;
+            let wip_check = wip::check_move(app_state.database.pool(), duplicate.project_id, duplicate.status, TaskStatus::Done).await?;
+            Some((duplicate, wip_check))
+        }
+    };
+
+    let relation = TaskRelationQueries::create_relation(
         app_state.database.pool(),
-        task.project_id,
+        task_id,
+        &related,
+        request.relation,
         current_user.id(),
     ).await?;
 
-    use crate::database::models::ProjectRole;
-    if !matches!(user_role, Some(ProjectRole::Admin) | Some(ProjectRole::Editor)) {
-        return Err(AppError::Forbidden("Need editor or admin role to update tasks".to_string()));
-    }
+    let closed_task = match close {
+        Some((duplicate, wip_check)) => Some(close_duplicate(&app_state, &current_user, &origin, duplicate, &wip_check).await?),
+        None => None,
+    };
+
+    Ok((StatusCode::CREATED, Json(TaskRelationResponse { relation, closed_task })))
+}
+
+// Moves a duplicate to Done the way a status update would
+async fn close_duplicate(
+    app_state: &crate::AppState,
+    current_user: &CurrentUser,
+    origin: &Origin,
+    duplicate: &Task,
+    wip_check: &WipCheck,
+) -> Result<Task, AppError> {
+    let request = UpdateTaskRequest {
+        title: None,
+        description: None,
+        assigned_to: None,
+        status: Some(TaskStatus::Done),
+        priority: None,
+        due_date: None,
+        tags: None,
+        cover_color: None,
+        cover_emoji: None,
+    };
+    let closed = TaskQueries::update_task(app_state.database.pool(), duplicate.id, &request, current_user.id()).await?;
+
+    let user = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?;
+    let user_summary: UserSummary = user.into();
+
+    slack::notify(app_state.database.pool(), duplicate.project_id, &user_summary, Notification::TaskCompleted { task: &closed }).await;
+
+    let event = WebSocketEvent::TaskUpdated(TaskEventData {
+        task: closed.clone(),
+        project_id: duplicate.project_id,
+        user: user_summary,
+        column: None,
+        client_mutation_id: origin.client_mutation_id.clone(),
+    });
+    app_state.websocket.broadcast_to_project(duplicate.project_id, event, origin.connection_id).await;
+    broadcast_wip_changes(app_state, duplicate.project_id, wip_check).await;
+    compact_positions_if_fragmented(app_state, duplicate.project_id).await;
+
+    Ok(closed)
+}
+
+pub async fn delete_task_relation(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path((task_id, related_task_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
+
+    authz::require_task_edit(&app_state, &task, current_user.id()).await?;
+
+    TaskRelationQueries::delete_relation(app_state.database.pool(), task_id, related_task_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn update_task(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(task_id): Path<Uuid>,
+    origin: Origin,
+    Json(Mutation { body: mut request, client_mutation_id }): Json<Mutation<UpdateTaskRequest>>,
+) -> Result<impl IntoResponse, AppError> {
+    let origin = origin.with_body(client_mutation_id)?;
+    let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
+
+    authz::require_task_edit(&app_state, &task, current_user.id()).await?;
 
     // Validate input
-    if let Some(ref title) = request.title {
-        validation::validate_task_title(title)?;
-    }
-    if let Some(ref description) = request.description {
-        validation::validate_task_description(description)?;
-    }
+    let workflow = ProjectQueries::get_project_workflow(app_state.database.pool(), task.project_id).await?;
+    validation::validate_update_task(&mut request, task.status, &workflow)?;
 
     // Validate assigned user is a project member if provided
-    if let Some(assigned_to) = request.assigned_to {
+    if let Some(Some(assigned_to)) = request.assigned_to {
         if !ProjectQueries::is_project_member(app_state.database.pool(), task.project_id, assigned_to).await? {
             return Err(AppError::Validation("Assigned user must be a project member".to_string()));
         }
     }
 
-    let updated_task = TaskQueries::update_task(app_state.database.pool(), task_id, &request).await?;
+    // Status changes move the task between columns, so WIP limits apply
+    let wip_check = wip::check_move(
+        app_state.database.pool(),
+        task.project_id,
+        task.status,
+        request.status.unwrap_or(task.status),
+    ).await?;
+
+    // Only mentions the edit adds notify anyone
+    let mentions = match &request.description {
+        Some(Some(description)) => {
+            mentions::parse(description).added_since(&mentions::parse(task.description.as_deref().unwrap_or_default()))
+        }
+        _ => mentions::Mentions::default(),
+    };
+    mentions::check(app_state.database.pool(), task.project_id, current_user.id(), &mentions).await?;
+
+    let updated_task = TaskQueries::update_task(app_state.database.pool(), task_id, &request, current_user.id()).await?;
+    // Users' own changes don't make a task unread for them
+    TaskReadQueries::mark_task_read(app_state.database.pool(), current_user.id(), task_id).await?;
 
     // Broadcast task update to WebSocket subscribers
     let user = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?;
     let user_summary: UserSummary = user.into();
+
+    if task.status != TaskStatus::Done && updated_task.status == TaskStatus::Done {
+        slack::notify(app_state.database.pool(), task.project_id, &user_summary, Notification::TaskCompleted { task: &updated_task }).await;
+    }
+    notifications::notify_assignment_change(&app_state, task.assigned_to, &updated_task, &user_summary).await;
+    mentions::notify(&app_state, &updated_task, None, updated_task.description.as_deref().unwrap_or_default(), &mentions, current_user.id()).await;
     
     let event = WebSocketEvent::TaskUpdated(TaskEventData {
         task: updated_task.clone(),
         project_id: task.project_id,
         user: user_summary,
+        column: None,
+        client_mutation_id: origin.client_mutation_id,
     });
     
-    app_state.websocket.broadcast_to_project(task.project_id, event, Some(current_user.id())).await;
+    app_state.websocket.broadcast_to_project(task.project_id, event, origin.connection_id).await;
+    broadcast_wip_changes(&app_state, task.project_id, &wip_check).await;
+    if updated_task.status != task.status {
+        compact_positions_if_fragmented(&app_state, task.project_id).await;
+    }
 
     Ok(Json(updated_task))
 }
@@ -175,33 +615,29 @@ pub async fn delete_task(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
     Path(task_id): Path<Uuid>,
+    origin: Origin,
 ) -> Result<impl IntoResponse, AppError> {
     let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
 
-    // Check if user is project admin or task creator
-    let user_role = ProjectQueries::get_user_project_role(
-        app_state.database.pool(),
-        task.project_id,
-        current_user.id(),
-    ).await?;
-
-    use crate::database::models::ProjectRole;
-    let is_admin = matches!(user_role, Some(ProjectRole::Admin));
-    let is_task_creator = task.created_by == current_user.id();
-
-    if !is_admin && !is_task_creator {
-        return Err(AppError::Forbidden("Only project admins or task creators can delete tasks".to_string()));
-    }
+    // Task creators can delete their own tasks as long as they can still edit them
+    let permission = if task.created_by == current_user.id() {
+        Permission::EditOwnTasks
+    } else {
+        Permission::DeleteAnyTask
+    };
+    authz::require_resource_role(&app_state, Resource::Task, task.project_id, current_user.id(), permission).await?;
 
     TaskQueries::delete_task(app_state.database.pool(), task_id).await?;
 
     // Broadcast task deletion to WebSocket subscribers
     let event = WebSocketEvent::TaskDeleted { 
         task_id, 
-        project_id: task.project_id 
+        project_id: task.project_id,
+        client_mutation_id: origin.client_mutation_id,
     };
     
-    app_state.websocket.broadcast_to_project(task.project_id, event, Some(current_user.id())).await;
+    app_state.websocket.broadcast_to_project(task.project_id, event, origin.connection_id).await;
+    compact_positions_if_fragmented(&app_state, task.project_id).await;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -210,58 +646,261 @@ pub async fn move_task(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
     Path(task_id): Path<Uuid>,
-    Json(request): Json<MoveTaskRequest>,
+    origin: Origin,
+    Json(Mutation { body: request, client_mutation_id }): Json<Mutation<MoveTaskRequest>>,
 ) -> Result<impl IntoResponse, AppError> {
+    let origin = origin.with_body(client_mutation_id)?;
     let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
 
-    // Check if user is project member (at least editor role required)
-    let user_role = ProjectQueries::get_user_project_role(
-        app_state.database.pool(),
-        task.project_id,
-        current_user.id(),
-    ).await?;
-
-    use crate::database::models::ProjectRole;
-    if !matches!(user_role, Some(ProjectRole::Admin) | Some(ProjectRole::Editor)) {
-        return Err(AppError::Forbidden("Need editor or admin role to move tasks".to_string()));
-    }
+    authz::require_task_edit(&app_state, &task, current_user.id()).await?;
 
     let from_status = task.status.clone();
     let to_status = request.status.clone();
-    
-    let updated_task = TaskQueries::move_task(
+
+    let workflow = ProjectQueries::get_project_workflow(app_state.database.pool(), task.project_id).await?;
+    validation::validate_status_transition(&workflow, from_status, to_status)?;
+
+    // Rejected with 409 when a strict board's column is full
+    let wip_check = wip::check_move(app_state.database.pool(), task.project_id, from_status, to_status).await?;
+    let rules = automations::rules_for_move(
+        BoardQueries::get_project_automations(app_state.database.pool(), task.project_id).await?,
+        from_status,
+        to_status,
+    );
+
+    let (updated_task, automation_comments) = TaskQueries::move_task(
         app_state.database.pool(),
         task_id,
         request.status,
         request.position,
+        &rules,
+        current_user.id(),
     ).await?;
+    after_move(&app_state, &current_user, &origin, &task, &updated_task, &wip_check, automation_comments).await?;
+
+    Ok(Json(updated_task))
+}
+
+#[derive(Debug, Serialize)]
+pub struct NudgeTaskResponse {
+    // False when the task was already at the top, bottom or outer column
+    pub moved: bool,
+    pub task: Task,
+    // New order of the columns the nudge changed
+    pub columns: Vec<ColumnOrder>,
+}
+
+// Keyboard reordering: the server picks the neighbour or adjacent column, so
+// clients don't need to know positions they haven't loaded
+pub async fn nudge_task(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(task_id): Path<Uuid>,
+    origin: Origin,
+    Json(Mutation { body: request, client_mutation_id }): Json<Mutation<NudgeTaskRequest>>,
+) -> Result<impl IntoResponse, AppError> {
+    let origin = origin.with_body(client_mutation_id)?;
+    let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
+
+    authz::require_task_edit(&app_state, &task, current_user.id()).await?;
+
+    let from_status = task.status;
+    let Some(to_status) = from_status.adjacent(request.direction) else {
+        return Ok(Json(NudgeTaskResponse { moved: false, task, columns: Vec::new() }));
+    };
+
+    let workflow = ProjectQueries::get_project_workflow(app_state.database.pool(), task.project_id).await?;
+    validation::validate_status_transition(&workflow, from_status, to_status)?;
+    let wip_check = wip::check_move(app_state.database.pool(), task.project_id, from_status, to_status).await?;
+    let rules = automations::rules_for_move(
+        BoardQueries::get_project_automations(app_state.database.pool(), task.project_id).await?,
+        from_status,
+        to_status,
+    );
+
+    let Some((updated_task, columns, automation_comments)) =
+        TaskQueries::nudge_task(app_state.database.pool(), task_id, request.direction, &rules, current_user.id()).await?
+    else {
+        return Ok(Json(NudgeTaskResponse { moved: false, task, columns: Vec::new() }));
+    };
+    after_move(&app_state, &current_user, &origin, &task, &updated_task, &wip_check, automation_comments).await?;
+
+    Ok(Json(NudgeTaskResponse { moved: true, task: updated_task, columns }))
+}
+
+// Everything a move does besides the move itself: the mover has seen the
+// task, Slack hears about completions and other clients get one TaskMoved.
+// When automation rules ran, they also get the changed task and the rules'
+// comments, and a new assignee is notified.
+async fn after_move(
+    app_state: &crate::AppState,
+    current_user: &CurrentUser,
+    origin: &Origin,
+    task: &Task,
+    updated_task: &Task,
+    wip_check: &WipCheck,
+    automation_comments: Vec<TaskComment>,
+) -> Result<(), AppError> {
+    let from_status = task.status;
+    let to_status = updated_task.status;
+    TaskReadQueries::mark_task_read(app_state.database.pool(), current_user.id(), updated_task.id).await?;
 
     // Broadcast task move to WebSocket subscribers
     let user = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?;
     let user_summary: UserSummary = user.into();
-    
+
+    if from_status != TaskStatus::Done && to_status == TaskStatus::Done {
+        slack::notify(app_state.database.pool(), updated_task.project_id, &user_summary, Notification::TaskCompleted { task: updated_task }).await;
+    }
+
     let event = WebSocketEvent::TaskMoved(TaskMoveEventData {
-        task_id,
+        task_id: updated_task.id,
         from_status,
         to_status,
-        position: request.position,
-        project_id: task.project_id,
-        user: user_summary,
+        position: updated_task.position,
+        project_id: updated_task.project_id,
+        user: user_summary.clone(),
+        client_mutation_id: origin.client_mutation_id.clone(),
     });
-    
-    app_state.websocket.broadcast_to_project(task.project_id, event, Some(current_user.id())).await;
 
-    Ok(Json(updated_task))
+    app_state.websocket.broadcast_to_project(updated_task.project_id, event, origin.connection_id).await;
+    broadcast_wip_changes(app_state, updated_task.project_id, wip_check).await;
+
+    if automation_comments.is_empty() {
+        return Ok(());
+    }
+    notifications::notify_assignment_change(app_state, task.assigned_to, updated_task, &user_summary).await;
+    let event = WebSocketEvent::TaskUpdated(TaskEventData {
+        task: updated_task.clone(),
+        project_id: updated_task.project_id,
+        user: user_summary.clone(),
+        column: None,
+        client_mutation_id: origin.client_mutation_id.clone(),
+    });
+    app_state.websocket.broadcast_to_project(updated_task.project_id, event, origin.connection_id).await;
+    for comment in automation_comments {
+        let event = WebSocketEvent::CommentCreated(CommentEventData {
+            comment,
+            task_id: updated_task.id,
+            project_id: updated_task.project_id,
+            user: user_summary.clone(),
+            client_mutation_id: None,
+        });
+        app_state.websocket.broadcast_to_project(updated_task.project_id, event, None).await;
+    }
+
+    Ok(())
+}
+
+// Called by clients when a task is opened
+pub async fn mark_task_read(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(task_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let task = TaskQueries::get_task_by_id(app_state.database.pool(), task_id).await?;
+
+    authz::require_resource_role(&app_state, Resource::Task, task.project_id, current_user.id(), Permission::ViewProject).await?;
+
+    TaskReadQueries::mark_task_read(app_state.database.pool(), current_user.id(), task_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn mark_project_read(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    ProjectMember(project_id): ProjectMember,
+) -> Result<impl IntoResponse, AppError> {
+    TaskReadQueries::mark_project_read(app_state.database.pool(), current_user.id(), project_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Serialize)]
+pub struct NormalizePositionsResponse {
+    pub project_id: Uuid,
+    // Tasks whose position changed
+    pub updated: u64,
+    pub columns: Vec<ColumnOrder>,
+}
+
+// Maintenance endpoint: rewrites every column's positions to 1..n
+pub async fn normalize_task_positions(
+    State(app_state): State<crate::AppState>,
+    ProjectAdmin(project_id): ProjectAdmin,
+) -> Result<impl IntoResponse, AppError> {
+    let (updated, columns) = TaskQueries::compact_positions(app_state.database.pool(), project_id).await?;
+    if updated > 0 {
+        broadcast_reorder(&app_state, project_id, columns.clone()).await;
+    }
+
+    Ok(Json(NormalizePositionsResponse { project_id, updated, columns }))
+}
+
+// Run after changes that leave gaps in a column. The change itself already
+// succeeded, so a failed compaction is only logged and retried next time.
+async fn compact_positions_if_fragmented(app_state: &crate::AppState, project_id: Uuid) {
+    match positions::compact_if_fragmented(app_state.database.pool(), project_id).await {
+        Ok(Some(columns)) => broadcast_reorder(app_state, project_id, columns).await,
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Compacting task positions of project {} failed: {}", project_id, e),
+    }
+}
+
+async fn broadcast_reorder(app_state: &crate::AppState, project_id: Uuid, columns: Vec<ColumnOrder>) {
+    let event = WebSocketEvent::TasksReordered(TasksReorderedEventData { project_id, columns });
+    app_state.websocket.broadcast_to_project(project_id, event, None).await;
+}
+
+// Column status goes to every client, including the one that moved the task
+async fn broadcast_wip_changes(app_state: &crate::AppState, project_id: Uuid, wip_check: &WipCheck) {
+    for column in wip_check.changed_columns() {
+        app_state.websocket.broadcast_to_project(project_id, WebSocketEvent::ColumnWipStatusChanged(column), None).await;
+    }
 }
 
 pub async fn get_user_assigned_tasks(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<AssignedTasksQuery>,
 ) -> Result<impl IntoResponse, AppError> {
+    if query.sort == TaskSort::Manual {
+        TaskOrderQueries::prune(app_state.database.pool(), current_user.id()).await?;
+    }
+
     let tasks = TaskQueries::get_user_assigned_tasks(
         app_state.database.pool(),
         current_user.id(),
+        query.sort,
+        query.order.unwrap_or_else(|| query.sort.default_order()),
     ).await?;
 
+    let tasks = match query.due {
+        Some(filter) => {
+            // All-day dates end at midnight in the user's (digest) timezone
+            let preferences = DigestQueries::get_preferences(app_state.database.pool(), current_user.id()).await?;
+            let timezone = preferences.timezone.parse().unwrap_or(chrono_tz::Tz::UTC);
+            let now = chrono::Utc::now();
+            tasks.into_iter().filter(|task| due_dates::matches(filter, task, now, timezone)).collect()
+        }
+        None => tasks,
+    };
+
     Ok(Json(tasks))
+}
+
+// Sets the order for GET /api/tasks?sort=manual. Tasks left out of the list
+// lose their place and follow the listed ones.
+pub async fn set_my_task_order(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<SetTaskOrderRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    validation::validate_task_order(&request.task_ids)?;
+
+    TaskOrderQueries::prune(app_state.database.pool(), current_user.id()).await?;
+    TaskOrderQueries::set_order(app_state.database.pool(), current_user.id(), &request.task_ids).await?;
+
+    Ok(StatusCode::NO_CONTENT)
 }
\ No newline at end of file
@@ -1,20 +1,28 @@
 use axum::{
-    extract::{Extension, State, Path},
-    response::IntoResponse,
-    Json,
+    extract::{Extension, Request, State},
+    response::{IntoResponse, Response},
     http::StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::audit_export;
+use crate::auth::authz::{self, Permission};
 use crate::auth::middleware::CurrentUser;
+use crate::backup::{self, ExportOptions};
 use crate::database::{
     connection::Database,
-    models::{CreateTeamRequest, TeamRole, TeamMember, UserSummary},
-    queries::{TeamQueries, UserQueries}
+    models::{AuditAction, AuditExport, AuditExportFormat, AuditExportQuery, AutoAddPolicy, CreateTeamRequest, DetailsEmbed, DetailsQuery, ProjectRole, ProjectTaskCount, RetentionPolicy, TeamCounters, TeamRole, TeamMember, UpdateTeamSettingsRequest, UserSummary, EMBEDDED_MEMBERS},
+    queries::{AuditExportQueries, AuditQueries, CounterQueries, ProjectQueries, QuotaQueries, RetentionQueries, TeamQueries, TeamSettingsQueries, UserQueries}
 };
+use crate::quotas::{self, Limits};
+use crate::retention;
 use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Path, Query};
+use crate::utils::i18n::Message;
+use crate::utils::rate_limit::{audit_export_limiter, ClientIp};
 use crate::utils::validation;
+use crate::websocket::events::WebSocketEvent;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AddTeamMemberRequest {
@@ -35,9 +43,27 @@ pub struct TeamDetailsResponse {
     pub created_by: Uuid,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub counts: TeamCounters,
+    // Active members, of which `members` holds the first EMBEDDED_MEMBERS
+    pub member_count: i64,
     pub members: Vec<TeamMemberResponse>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct TeamMembersQuery {
+    pub role: Option<TeamRole>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TeamMembersPage {
+    pub members: Vec<TeamMemberResponse>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
 #[derive(Debug, Serialize)]
 pub struct TeamMemberResponse {
     pub id: Uuid,
@@ -46,17 +72,42 @@ pub struct TeamMemberResponse {
     pub joined_at: chrono::DateTime<chrono::Utc>,
 }
 
+impl From<(TeamMember, UserSummary)> for TeamMemberResponse {
+    fn from((member, user): (TeamMember, UserSummary)) -> Self {
+        Self {
+            id: member.id,
+            user,
+            role: member.role,
+            joined_at: member.joined_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TeamUsageResponse {
+    pub team_id: Uuid,
+    pub limits: Limits,
+    pub projects: i64,
+    pub members: i64,
+    // Across all of the team's projects
+    pub attachment_bytes: i64,
+    pub tasks_per_project: Vec<ProjectTaskCount>,
+}
+
 pub async fn create_team(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
-    Json(request): Json<CreateTeamRequest>,
+    Json(mut request): Json<CreateTeamRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     // Validate input
+    request.name = validation::normalize_name(&request.name);
     validation::validate_team_name(&request.name)?;
     if let Some(ref description) = request.description {
         validation::validate_team_description(description)?;
     }
 
+    quotas::check_can_create_team(app_state.database.pool(), current_user.id()).await?;
+
     let team = TeamQueries::create_team(
         app_state.database.pool(),
         &request,
@@ -82,21 +133,22 @@ pub async fn get_team_details(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
     Path(team_id): Path<Uuid>,
+    Query(query): Query<DetailsQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is team member
-    if !TeamQueries::is_team_member(app_state.database.pool(), team_id, current_user.id()).await? {
-        return Err(AppError::Forbidden("Not a team member".to_string()));
-    }
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ViewTeam).await?;
 
     let team = TeamQueries::get_team_by_id(app_state.database.pool(), team_id).await?;
-    let members_data = TeamQueries::get_team_members(app_state.database.pool(), team_id).await?;
+    let (members_data, member_count) = match query.embed {
+        Some(DetailsEmbed::Members) => {
+            let members = TeamQueries::get_team_members(app_state.database.pool(), team_id).await?;
+            let count = members.len() as i64;
+            (members, count)
+        }
+        None => TeamQueries::list_team_members(app_state.database.pool(), team_id, None, EMBEDDED_MEMBERS, 0).await?,
+    };
+    let counts = CounterQueries::get_team_counters(app_state.database.pool(), team_id).await?;
 
-    let members = members_data.into_iter().map(|(member, user)| TeamMemberResponse {
-        id: member.id,
-        user,
-        role: member.role,
-        joined_at: member.joined_at,
-    }).collect();
+    let members = members_data.into_iter().map(TeamMemberResponse::from).collect();
 
     let response = TeamDetailsResponse {
         id: team.id,
@@ -105,30 +157,51 @@ pub async fn get_team_details(
         created_by: team.created_by,
         created_at: team.created_at,
         updated_at: team.updated_at,
+        counts,
+        member_count,
         members,
     };
 
     Ok(Json(response))
 }
 
-pub async fn update_team(
+pub async fn get_team_members(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
     Path(team_id): Path<Uuid>,
-    Json(request): Json<CreateTeamRequest>,
+    Query(query): Query<TeamMembersQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is team admin
-    let user_role = TeamQueries::get_user_team_role(
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ViewTeam).await?;
+
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(50).clamp(1, 200);
+
+    let (members, total) = TeamQueries::list_team_members(
         app_state.database.pool(),
         team_id,
-        current_user.id(),
+        query.role,
+        per_page,
+        (page - 1).saturating_mul(per_page),
     ).await?;
 
-    if !matches!(user_role, Some(TeamRole::Admin)) {
-        return Err(AppError::Forbidden("Only team admins can update teams".to_string()));
-    }
+    Ok(Json(TeamMembersPage {
+        members: members.into_iter().map(TeamMemberResponse::from).collect(),
+        total,
+        page,
+        per_page,
+    }))
+}
+
+pub async fn update_team(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(team_id): Path<Uuid>,
+    Json(mut request): Json<CreateTeamRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ManageTeam).await?;
 
     // Validate input
+    request.name = validation::normalize_name(&request.name);
     validation::validate_team_name(&request.name)?;
     if let Some(ref description) = request.description {
         validation::validate_team_description(description)?;
@@ -144,19 +217,17 @@ pub async fn delete_team(
     Extension(current_user): Extension<CurrentUser>,
     Path(team_id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is team admin
-    let user_role = TeamQueries::get_user_team_role(
-        app_state.database.pool(),
-        team_id,
-        current_user.id(),
-    ).await?;
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ManageTeam).await?;
 
-    if !matches!(user_role, Some(TeamRole::Admin)) {
-        return Err(AppError::Forbidden("Only team admins can delete teams".to_string()));
-    }
+    // Projects are removed by the cascade, so collect them first
+    let project_ids = ProjectQueries::get_team_project_ids(app_state.database.pool(), team_id).await?;
 
     TeamQueries::delete_team(app_state.database.pool(), team_id).await?;
 
+    for project_id in project_ids {
+        app_state.websocket.close_project(project_id, WebSocketEvent::ProjectDeleted { project_id }).await;
+    }
+
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -166,16 +237,7 @@ pub async fn add_team_member(
     Path(team_id): Path<Uuid>,
     Json(request): Json<AddTeamMemberRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is team admin
-    let user_role = TeamQueries::get_user_team_role(
-        app_state.database.pool(),
-        team_id,
-        current_user.id(),
-    ).await?;
-
-    if !matches!(user_role, Some(TeamRole::Admin)) {
-        return Err(AppError::Forbidden("Only team admins can add members".to_string()));
-    }
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ManageTeam).await?;
 
     // Check if target user exists
     UserQueries::get_user_by_id(app_state.database.pool(), request.user_id).await?;
@@ -185,6 +247,8 @@ pub async fn add_team_member(
         return Err(AppError::Conflict("User is already a team member".to_string()));
     }
 
+    quotas::check_can_add_team_member(app_state.database.pool(), team_id).await?;
+
     let member = TeamQueries::add_team_member(
         app_state.database.pool(),
         team_id,
@@ -200,19 +264,11 @@ pub async fn remove_team_member(
     Extension(current_user): Extension<CurrentUser>,
     Path((team_id, user_id)): Path<(Uuid, Uuid)>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is team admin or removing themselves
-    let user_role = TeamQueries::get_user_team_role(
-        app_state.database.pool(),
-        team_id,
-        current_user.id(),
-    ).await?;
-
-    let is_admin = matches!(user_role, Some(TeamRole::Admin));
+    // Any member can leave, only admins can remove others
     let is_self = current_user.id() == user_id;
-
-    if !is_admin && !is_self {
-        return Err(AppError::Forbidden("Can only remove yourself or be team admin".to_string()));
-    }
+    let permission = if is_self { Permission::ViewTeam } else { Permission::ManageTeam };
+    let user_role = authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), permission).await?;
+    let is_admin = Permission::ManageTeam.allows_team_role(&user_role);
 
     // Prevent removing the last admin
     if user_id == current_user.id() && is_admin {
@@ -235,16 +291,7 @@ pub async fn update_team_member_role(
     Path((team_id, user_id)): Path<(Uuid, Uuid)>,
     Json(request): Json<UpdateTeamMemberRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Check if user is team admin
-    let user_role = TeamQueries::get_user_team_role(
-        app_state.database.pool(),
-        team_id,
-        current_user.id(),
-    ).await?;
-
-    if !matches!(user_role, Some(TeamRole::Admin)) {
-        return Err(AppError::Forbidden("Only team admins can update member roles".to_string()));
-    }
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ManageTeam).await?;
 
     // Prevent demoting the last admin
     if matches!(request.role, TeamRole::Member) {
@@ -268,4 +315,193 @@ pub async fn update_team_member_role(
     ).await?;
 
     Ok(Json(member))
-}
\ No newline at end of file
+}
+
+pub async fn get_team_usage(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(team_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ViewTeam).await?;
+
+    let pool = app_state.database.pool();
+    let tasks_per_project = QuotaQueries::get_team_task_counts(pool, team_id).await?;
+
+    Ok(Json(TeamUsageResponse {
+        team_id,
+        limits: quotas::team_limits(pool, team_id).await?,
+        projects: tasks_per_project.len() as i64,
+        members: QuotaQueries::count_team_members(pool, team_id).await?,
+        attachment_bytes: tasks_per_project.iter().map(|project| project.attachment_bytes).sum(),
+        tasks_per_project,
+    }))
+}
+
+pub async fn get_team_settings(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(team_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ViewTeam).await?;
+
+    let settings = TeamSettingsQueries::get_settings(app_state.database.pool(), team_id).await?;
+
+    Ok(Json(settings))
+}
+
+// Only affects members who join from now on; nobody is added to or removed
+// from projects when the policy changes
+pub async fn update_team_settings(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(team_id): Path<Uuid>,
+    Json(request): Json<UpdateTeamSettingsRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ManageTeam).await?;
+
+    if request.auto_add_to_projects != AutoAddPolicy::Selected && !request.project_ids.is_empty() {
+        return Err(AppError::Validation("project_ids can only be set for the selected policy".to_string()));
+    }
+
+    let settings = TeamSettingsQueries::update_settings(
+        app_state.database.pool(),
+        team_id,
+        request.auto_add_to_projects,
+        &request.project_ids,
+        request.default_project_role.unwrap_or(ProjectRole::Member),
+        current_user.id(),
+    ).await?;
+
+    Ok(Json(settings))
+}
+
+pub async fn get_retention_policy(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(team_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ViewTeam).await?;
+
+    let policy = RetentionQueries::get_policy(app_state.database.pool(), team_id).await?;
+
+    Ok(Json(policy))
+}
+
+// Takes effect at the next retention sweep; check the preview first
+pub async fn update_retention_policy(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(team_id): Path<Uuid>,
+    Json(policy): Json<RetentionPolicy>,
+) -> Result<impl IntoResponse, AppError> {
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ManageTeam).await?;
+    validation::validate_retention_policy(&policy)?;
+
+    RetentionQueries::update_policy(app_state.database.pool(), team_id, &policy).await?;
+
+    Ok(Json(policy))
+}
+
+// What the next sweep would expire, bring back and delete under the saved policy
+pub async fn preview_retention(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(team_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ManageTeam).await?;
+
+    let preview = retention::preview(app_state.database.pool(), team_id, chrono::Utc::now()).await?;
+
+    Ok(Json(preview))
+}
+
+// Backup of one team's data for team admins; never includes password hashes
+pub async fn export_team(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(team_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ManageTeam).await?;
+
+    let options = ExportOptions {
+        team_id: Some(team_id),
+        include_password_hashes: false,
+    };
+
+    Ok(backup::export_response(app_state.database.pool().clone(), options))
+}
+
+// Everything the team did in [from, to). Small exports are streamed; larger
+// ones are written by a job (202 with the export), which notifies the admin
+// when the file can be downloaded.
+pub async fn export_audit_log(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(team_id): Path<Uuid>,
+    ClientIp(ip): ClientIp,
+    query: Result<Query<AuditExportQuery>, AppError>,
+) -> Result<Response, AppError> {
+    let pool = app_state.database.pool();
+    // Before the query, so non-admins learn nothing from its errors
+    authz::require_team_role(pool, team_id, current_user.id(), Permission::ManageTeam).await?;
+    let Query(query) = query?;
+    if query.from >= query.to {
+        return Err(AppError::Invalid(Message::new("invalid_date_range")));
+    }
+    audit_export_limiter().check(team_id)?;
+
+    let format = query.format.unwrap_or(AuditExportFormat::Csv);
+    let entries = AuditExportQueries::count_entries(pool, team_id, query.from, query.to).await?;
+    let details = serde_json::json!({
+        "from": query.from,
+        "to": query.to,
+        "format": format.name(),
+        "entries": entries,
+    });
+    let path = format!("/api/teams/{}/audit/export", team_id);
+    AuditQueries::record_team(
+        pool,
+        AuditAction::AuditExported,
+        Some(current_user.id()),
+        team_id,
+        Some(&path),
+        Some(&ip.to_string()),
+        Some(&details),
+    )
+    .await?;
+
+    if entries <= audit_export::sync_limit() {
+        return Ok(audit_export::response(pool.clone(), team_id, query.from, query.to, format));
+    }
+    let export = AuditExportQueries::create_export(pool, team_id, current_user.id(), format, query.from, query.to).await?;
+    audit_export::queue(pool, export.id).await?;
+    Ok((StatusCode::ACCEPTED, Json(export)).into_response())
+}
+
+async fn team_audit_export(app_state: &crate::AppState, current_user: &CurrentUser, team_id: Uuid, export_id: Uuid) -> Result<AuditExport, AppError> {
+    let pool = app_state.database.pool();
+    authz::require_team_role(pool, team_id, current_user.id(), Permission::ManageTeam).await?;
+    let export = AuditExportQueries::get_export(pool, export_id).await?;
+    if export.team_id != team_id {
+        return Err(AppError::NotFound("Export not found".to_string()));
+    }
+    Ok(export)
+}
+
+pub async fn get_audit_export(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path((team_id, export_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(team_audit_export(&app_state, &current_user, team_id, export_id).await?))
+}
+
+pub async fn download_audit_export(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path((team_id, export_id)): Path<(Uuid, Uuid)>,
+    request: Request,
+) -> Result<Response, AppError> {
+    let export = team_audit_export(&app_state, &current_user, team_id, export_id).await?;
+    audit_export::serve(&app_state.config.attachments, &export, request).await
+}
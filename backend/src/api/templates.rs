@@ -0,0 +1,97 @@
+// Project templates (see project_templates.rs). Project admins publish, team
+// admins and instance admins unpublish, and instance templates only become
+// usable once an instance admin approved them.
+
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use uuid::Uuid;
+
+use crate::auth::authz::{self, Permission, ProjectAdmin};
+use crate::auth::middleware::CurrentUser;
+use crate::database::{
+    models::PublishTemplateRequest,
+    queries::{ProjectQueries, TemplateQueries},
+};
+use crate::project_templates;
+use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Path};
+use crate::utils::validation;
+
+// Snapshots the project as it is now; later edits don't change the template
+pub async fn publish_template(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    ProjectAdmin(project_id): ProjectAdmin,
+    Json(mut request): Json<PublishTemplateRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    validation::validate_publish_template(&mut request)?;
+
+    let project = ProjectQueries::get_project_by_id(pool, project_id).await?;
+    let document = project_templates::snapshot(pool, project_id).await?;
+    let template = TemplateQueries::publish_template(
+        pool,
+        project.team_id,
+        project.id,
+        &request,
+        &document,
+        current_user.id(),
+    ).await?;
+
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+pub async fn get_templates(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let templates = TemplateQueries::get_user_templates(app_state.database.pool(), current_user.id()).await?;
+
+    Ok(Json(templates))
+}
+
+pub async fn unpublish_template(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(template_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    let template = TemplateQueries::get_template(pool, template_id).await?;
+
+    if authz::require_team_role(pool, template.team_id, current_user.id(), Permission::ManageTeam).await.is_err() {
+        crate::api::admin::ensure_instance_admin(&app_state, &current_user).await?;
+    }
+
+    TemplateQueries::delete_template(pool, template.id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Instance templates waiting for approval
+pub async fn get_pending_templates(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    crate::api::admin::ensure_instance_admin(&app_state, &current_user).await?;
+
+    let templates = TemplateQueries::get_pending_templates(app_state.database.pool()).await?;
+
+    Ok(Json(templates))
+}
+
+pub async fn approve_template(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(template_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    crate::api::admin::ensure_instance_admin(&app_state, &current_user).await?;
+
+    let template = TemplateQueries::approve_template(app_state.database.pool(), template_id, current_user.id())
+        .await?
+        .ok_or_else(|| AppError::NotFound("No template waiting for approval".to_string()))?;
+
+    Ok(Json(template))
+}
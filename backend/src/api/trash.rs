@@ -0,0 +1,82 @@
+// Trash views and restores. Deleting a project or board only moves it to the
+// trash; admins can bring it back until the trash sweep purges it.
+
+use axum::{
+    extract::{Extension, State},
+    response::IntoResponse,
+};
+use uuid::Uuid;
+
+use crate::auth::authz::{self, Permission, ProjectMember};
+use crate::auth::middleware::CurrentUser;
+use crate::database::{
+    models::UserSummary,
+    queries::{TrashQueries, UserQueries},
+};
+use crate::trash;
+use crate::utils::errors::AppError;
+use crate::utils::extractors::{Json, Path};
+use crate::websocket::events::{BoardEventData, WebSocketEvent};
+use crate::websocket::origin::Origin;
+
+pub async fn get_team_trash(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(team_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ViewTeam).await?;
+
+    let projects = TrashQueries::get_trashed_projects(app_state.database.pool(), team_id, trash::retention_days()).await?;
+
+    Ok(Json(projects))
+}
+
+// Also restores the boards deleted along with the project, but not boards that
+// were already in the trash when the project was deleted
+pub async fn restore_project(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path((team_id, project_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, AppError> {
+    authz::require_team_role(app_state.database.pool(), team_id, current_user.id(), Permission::ManageTeam).await?;
+
+    let project = TrashQueries::restore_project(app_state.database.pool(), team_id, project_id).await?;
+
+    Ok(Json(project))
+}
+
+pub async fn get_board_trash(
+    State(app_state): State<crate::AppState>,
+    ProjectMember(project_id): ProjectMember,
+) -> Result<impl IntoResponse, AppError> {
+    let boards = TrashQueries::get_trashed_boards(app_state.database.pool(), project_id, trash::retention_days()).await?;
+
+    Ok(Json(boards))
+}
+
+pub async fn restore_board(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path((project_id, board_id)): Path<(Uuid, Uuid)>,
+    origin: Origin,
+) -> Result<impl IntoResponse, AppError> {
+    // Whoever may delete boards may bring them back
+    authz::require_project_role(app_state.database.pool(), project_id, current_user.id(), Permission::DeleteBoards).await?;
+
+    let board = TrashQueries::restore_board(app_state.database.pool(), project_id, board_id).await?;
+
+    // Clients add it back like a new board
+    let user = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?;
+    let user_summary: UserSummary = user.into();
+
+    let event = WebSocketEvent::BoardCreated(BoardEventData {
+        board: board.clone(),
+        project_id,
+        user: user_summary,
+        client_mutation_id: origin.client_mutation_id,
+    });
+
+    app_state.websocket.broadcast_to_project(project_id, event, origin.connection_id).await;
+
+    Ok(Json(board))
+}
@@ -1,33 +1,413 @@
 use axum::{
     extract::{Extension, State},
+    http::{header, StatusCode},
     response::IntoResponse,
-    Json,
 };
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
 
-use crate::auth::middleware::CurrentUser;
-use crate::database::{connection::Database, models::{UpdateUserRequest, UserSummary}, queries::UserQueries};
-use crate::utils::errors::AppError;
+use crate::agenda::{self, AgendaFormat, IsoWeek};
+use crate::auth::{middleware::CurrentUser, password, tokens};
+use crate::database::{models::{ApiTokenWithQuota, CreateApiTokenRequest, CreatedApiToken, DigestPreferences, MergeAccountRequest, NotificationEventType, NotificationLevel, TokenQuota, UpdateDigestPreferencesRequest, UpdateEmailPreferencesRequest, UpdateUserRequest, UserActivity, UserSummary}, queries::{ApiTokenQueries, DigestQueries, EmailQueries, ProjectQueries, NotificationQueries, NotificationSettingsQueries, TaskQueries, UserActivityQueries, UserQueries}};
+use crate::email::{digest::{self, Digest}, templates::EmailTemplate};
+use crate::integrations::app_base_url;
+use crate::notification_settings::{self, settings_cache};
+use crate::utils::errors::{AppError, FieldError};
+use crate::utils::extractors::{Json, Path, Query};
+use crate::utils::i18n::Message;
+use crate::utils::rate_limit;
+use crate::utils::validation;
+
+#[derive(Debug, Serialize)]
+pub struct CurrentUserResponse {
+    #[serde(flatten)]
+    pub user: UserSummary,
+    // The admin behind an impersonation token, so the UI can show a banner
+    pub impersonated_by: Option<UserSummary>,
+}
 
 pub async fn get_current_user(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
 ) -> Result<impl IntoResponse, AppError> {
     let user = UserQueries::get_user_by_id(app_state.database.pool(), current_user.id()).await?;
-    let user_summary: UserSummary = user.into();
-    Ok(Json(user_summary))
+    let impersonated_by = match current_user.impersonated_by() {
+        Some(admin_id) => Some(UserQueries::get_user_by_id(app_state.database.pool(), admin_id).await?.into()),
+        None => None,
+    };
+
+    Ok(Json(CurrentUserResponse {
+        user: user.into(),
+        impersonated_by,
+    }))
 }
 
 pub async fn update_current_user(
     State(app_state): State<crate::AppState>,
     Extension(current_user): Extension<CurrentUser>,
-    Json(request): Json<UpdateUserRequest>,
+    Json(mut request): Json<UpdateUserRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     // Validate display name if provided
-    if let Some(ref display_name) = request.display_name {
+    if let Some(ref mut display_name) = request.display_name {
+        *display_name = crate::utils::validation::normalize_name(display_name);
         crate::utils::validation::validate_display_name(display_name)?;
     }
 
     let updated_user = UserQueries::update_user(app_state.database.pool(), current_user.id(), &request).await?;
     let user_summary: UserSummary = updated_user.into();
     Ok(Json(user_summary))
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmailPreferencesResponse {
+    pub opted_out: Vec<String>,
+    pub available: Vec<&'static str>,
+}
+
+pub async fn get_email_preferences(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let opted_out = EmailQueries::get_opted_out_templates(app_state.database.pool(), current_user.id()).await?;
+
+    Ok(Json(EmailPreferencesResponse {
+        opted_out,
+        available: EmailTemplate::ALL.iter().filter(|t| !t.is_transactional()).map(|t| t.name()).collect(),
+    }))
+}
+
+// The caller's default level per event type, used in projects without a setting of their own
+pub async fn get_notification_settings(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let settings = notification_settings::load(app_state.database.pool(), current_user.id()).await?;
+    Ok(Json(settings.default_settings()))
+}
+
+// Only the event types in the body change; null goes back to `all`
+pub async fn update_notification_settings(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(levels): Json<BTreeMap<NotificationEventType, Option<NotificationLevel>>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    NotificationSettingsQueries::set_defaults(pool, current_user.id(), &levels).await?;
+    settings_cache().forget(current_user.id());
+
+    let settings = notification_settings::load(pool, current_user.id()).await?;
+    Ok(Json(settings.default_settings()))
+}
+
+// Most recent first; the WebSocket delivers new ones while connected
+pub async fn get_notifications(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let notifications = NotificationQueries::get_user_notifications(app_state.database.pool(), current_user.id(), 50).await?;
+
+    Ok(Json(notifications))
+}
+
+pub async fn mark_notifications_read(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    NotificationQueries::mark_notifications_read(app_state.database.pool(), current_user.id()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Merges a duplicate account into the caller's. Knowing the other account's
+// password proves it's theirs; it is deactivated afterwards.
+pub async fn merge_account(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<MergeAccountRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    current_user.ensure_not_impersonated()?;
+    let pool = app_state.database.pool();
+
+    // Forbidden rather than Unauthorized, the caller's own session is fine
+    let invalid = || AppError::Forbidden("Invalid email or password".to_string());
+    let other = UserQueries::get_user_by_email(pool, &request.email).await.map_err(|_| invalid())?;
+    let is_valid = password::verify_password(&request.password, &other.password_hash)
+        .map_err(|e| AppError::InternalServer(format!("Failed to verify password: {}", e)))?;
+    if !is_valid {
+        return Err(invalid());
+    }
+    if other.id == current_user.id() {
+        return Err(AppError::BadRequest("Can't merge an account into itself".to_string()));
+    }
+
+    let merge = UserQueries::merge_into(pool, current_user.id(), other.id).await?;
+    Ok(Json(merge))
+}
+
+pub async fn get_digest_preferences(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let preferences = DigestQueries::get_preferences(app_state.database.pool(), current_user.id()).await?;
+    Ok(Json(preferences))
+}
+
+pub async fn update_digest_preferences(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<UpdateDigestPreferencesRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let current = DigestQueries::get_preferences(app_state.database.pool(), current_user.id()).await?;
+
+    let timezone = request.timezone.unwrap_or(current.timezone);
+    crate::utils::validation::validate_timezone(&timezone)?;
+
+    let preferences: DigestPreferences = DigestQueries::set_preferences(
+        app_state.database.pool(),
+        current_user.id(),
+        request.frequency.unwrap_or(current.frequency),
+        &timezone,
+    ).await?;
+
+    Ok(Json(preferences))
+}
+
+#[derive(Debug, Serialize)]
+pub struct DigestPreviewResponse {
+    pub subject: String,
+    pub body: String,
+    pub digest: Digest,
+}
+
+// Renders the digest the user would get next, without sending it or moving
+// the last_digest_sent_at marker
+pub async fn preview_digest(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    let user = UserQueries::get_user_by_id(pool, current_user.id()).await?;
+    let preferences = DigestQueries::get_preferences(pool, user.id).await?;
+
+    let digest = digest::build_digest(pool, user.id, &user.username, preferences.frequency, preferences.last_digest_sent_at).await?;
+    let rendered = EmailTemplate::DailyDigest
+        .render(&digest.template_context(&user.display_name, &app_base_url()))
+        .map_err(AppError::InternalServer)?;
+
+    Ok(Json(DigestPreviewResponse {
+        subject: rendered.subject,
+        body: rendered.body,
+        digest,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AgendaQuery {
+    // ISO week such as 2024-W23; the current one by default
+    pub week: Option<String>,
+    // pdf (default) or html
+    pub format: Option<String>,
+}
+
+// The caller's assigned tasks due in a week, printable (see agenda.rs)
+pub async fn get_agenda(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(query): Query<AgendaQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let format_name = query.format.as_deref().unwrap_or("pdf");
+    let format = AgendaFormat::parse(format_name)
+        .ok_or_else(|| AppError::Invalid(Message::new("unsupported_export_format").with("format", format_name)))?;
+
+    let pool = app_state.database.pool();
+    let preferences = DigestQueries::get_preferences(pool, current_user.id()).await?;
+    let timezone = preferences.timezone.parse().unwrap_or(chrono_tz::Tz::UTC);
+    let week = match query.week.as_deref() {
+        Some(week) => IsoWeek::parse(week).ok_or_else(|| AppError::Invalid(Message::new("invalid_week")))?,
+        None => IsoWeek::containing(chrono::Utc::now().with_timezone(&timezone).date_naive()),
+    };
+
+    let key = (current_user.id(), week, format);
+    let document = match agenda::agenda_cache().get(key) {
+        Some(document) => document,
+        None => {
+            let generate = async {
+                let (from, to) = agenda::due_window(week);
+                let tasks = TaskQueries::get_assigned_tasks_due_between(pool, current_user.id(), from, to).await?;
+                // Rendering is CPU work, kept off the async workers
+                tokio::task::spawn_blocking(move || {
+                    let agenda = agenda::build(week, tasks, timezone);
+                    match format {
+                        AgendaFormat::Pdf => agenda::render_pdf(&agenda),
+                        AgendaFormat::Html => agenda::render_html(&agenda).into_bytes(),
+                    }
+                })
+                .await
+                .map_err(|e| AppError::InternalServer(format!("Agenda rendering failed: {}", e)))
+            };
+            let document = tokio::time::timeout(agenda::TIME_BUDGET, generate)
+                .await
+                .map_err(|_| AppError::InternalServer("Agenda generation took too long".to_string()))??;
+            agenda::agenda_cache().insert(key, document.clone());
+            document
+        }
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("inline; filename=\"agenda-{}.{}\"", week, format.extension())),
+            (header::CACHE_CONTROL, format!("private, max-age={}", agenda::CACHE_TTL.as_secs())),
+        ],
+        document,
+    ))
+}
+
+pub async fn update_email_preferences(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<UpdateEmailPreferencesRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    for name in &request.opted_out {
+        match EmailTemplate::from_name(name) {
+            Some(template) if !template.is_transactional() => {}
+            Some(_) => return Err(AppError::Validation(format!("Cannot opt out of {} emails", name))),
+            None => return Err(AppError::Validation(format!("Unknown email type: {}", name))),
+        }
+    }
+
+    EmailQueries::set_opted_out_templates(app_state.database.pool(), current_user.id(), &request.opted_out).await?;
+    let opted_out = EmailQueries::get_opted_out_templates(app_state.database.pool(), current_user.id()).await?;
+
+    Ok(Json(EmailPreferencesResponse {
+        opted_out,
+        available: EmailTemplate::ALL.iter().filter(|t| !t.is_transactional()).map(|t| t.name()).collect(),
+    }))
+}
+
+pub async fn get_api_tokens(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    let tokens = ApiTokenQueries::get_user_tokens(pool, current_user.id()).await?;
+    let max_requests = rate_limit::token_max_requests(ApiTokenQueries::get_rate_limit(pool, current_user.id()).await?);
+
+    let now = chrono::Utc::now();
+    let mut with_quotas = Vec::with_capacity(tokens.len());
+    for api_token in tokens {
+        let quota = app_state.quotas.peek_token(api_token.id, max_requests).await;
+        let quota = TokenQuota {
+            limit: quota.limit,
+            remaining: quota.remaining,
+            reset_at: now + chrono::Duration::from_std(quota.reset_after).unwrap_or_default(),
+        };
+        with_quotas.push(ApiTokenWithQuota { api_token, quota });
+    }
+    Ok(Json(with_quotas))
+}
+
+// The secret is in the response only this once
+pub async fn create_api_token(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(mut request): Json<CreateApiTokenRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    current_user.ensure_not_impersonated()?;
+    validation::validate_api_token(&mut request)?;
+    let pool = app_state.database.pool();
+
+    let mut errors = Vec::new();
+    for project_id in request.project_ids.iter().flatten() {
+        if !ProjectQueries::is_project_member(pool, *project_id, current_user.id()).await? {
+            errors.push(FieldError::new(
+                "project_ids",
+                Message::new("token_project_not_member").with("project", project_id.to_string()),
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(AppError::InvalidFields(errors));
+    }
+
+    let token = tokens::generate_token();
+    let api_token = ApiTokenQueries::create_token(
+        pool,
+        current_user.id(),
+        &request.name,
+        &tokens::hash_token(&token),
+        &request.scopes,
+        request.project_ids.as_deref(),
+    ).await?;
+
+    Ok((StatusCode::CREATED, Json(CreatedApiToken { api_token, token })))
+}
+
+pub async fn delete_api_token(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(token_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    ApiTokenQueries::delete_token(app_state.database.pool(), current_user.id(), token_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserActivityQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserActivityEntry {
+    #[serde(flatten)]
+    pub activity: UserActivity,
+    // The day of `at` in the viewer's timezone, for grouping entries by day
+    pub day: chrono::NaiveDate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserActivityPage {
+    pub user: UserSummary,
+    pub activity: Vec<UserActivityEntry>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+// A user's recent activity, limited to the projects the caller is a member of
+// too. Users without any shared projects get an empty page.
+pub async fn get_user_activity(
+    State(app_state): State<crate::AppState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Path(user_id): Path<Uuid>,
+    Query(query): Query<UserActivityQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = app_state.database.pool();
+    let user = UserQueries::get_user_by_id(pool, user_id).await?;
+
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(50).clamp(1, 200);
+    let (activity, total) = UserActivityQueries::get_activity(
+        pool,
+        current_user.id(),
+        user_id,
+        per_page,
+        (page - 1).saturating_mul(per_page),
+    ).await?;
+
+    let preferences = DigestQueries::get_preferences(pool, current_user.id()).await?;
+    let timezone = preferences.timezone.parse().unwrap_or(chrono_tz::Tz::UTC);
+    let activity = activity
+        .into_iter()
+        .map(|activity| UserActivityEntry { day: activity.at.with_timezone(&timezone).date_naive(), activity })
+        .collect();
+
+    Ok(Json(UserActivityPage {
+        user: user.into(),
+        activity,
+        total,
+        page,
+        per_page,
+    }))
+}
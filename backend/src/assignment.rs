@@ -0,0 +1,174 @@
+// Assignment suggestions: who could take a task. ProjectQueries collects each
+// candidate's open tasks, recent activity in the project and the task's labels
+// they've completed tasks with before; `rank` turns those into a score here,
+// returning every factor so the ranking can be explained.
+//
+// Each factor scores from 0 to 1 and is weighted:
+// - load: 1 / (1 + open tasks across all projects), so free people come first
+// - labels: share of the task's labels the candidate completed tasks with
+// - activity: status changes and comments in the project within
+//   ACTIVITY_WINDOW, full at FULL_ACTIVITY
+
+use chrono::Duration;
+use serde::Serialize;
+use std::cmp::Ordering;
+
+pub const ACTIVITY_WINDOW: Duration = Duration::days(14);
+const FULL_ACTIVITY: i64 = 10;
+
+const LOAD_WEIGHT: f64 = 0.5;
+const LABELS_WEIGHT: f64 = 0.4;
+const ACTIVITY_WEIGHT: f64 = 0.1;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CandidateStats {
+    pub open_tasks: i64,
+    // The task's labels the candidate completed tasks with in the project
+    pub completed_labels: Vec<String>,
+    pub recent_actions: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct Factor {
+    // What was counted: open tasks, matching labels or recent actions
+    pub value: i64,
+    pub score: f64,
+    pub weight: f64,
+}
+
+impl Factor {
+    fn new(value: i64, score: f64, weight: f64) -> Self {
+        Factor { value, score: round(score), weight }
+    }
+
+    fn points(&self) -> f64 {
+        self.score * self.weight
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct ScoreFactors {
+    pub load: Factor,
+    pub labels: Factor,
+    pub activity: Factor,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion<T> {
+    pub candidate: T,
+    pub score: f64,
+    pub factors: ScoreFactors,
+    pub matched_labels: Vec<String>,
+}
+
+fn round(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+// Scores one candidate for a task with `task_labels` labels
+pub fn score(stats: &CandidateStats, task_labels: usize) -> (f64, ScoreFactors) {
+    let open_tasks = stats.open_tasks.max(0);
+    let matched = stats.completed_labels.len().min(task_labels);
+    let actions = stats.recent_actions.max(0);
+
+    let factors = ScoreFactors {
+        load: Factor::new(open_tasks, 1.0 / (1.0 + open_tasks as f64), LOAD_WEIGHT),
+        labels: Factor::new(
+            matched as i64,
+            if task_labels == 0 { 0.0 } else { matched as f64 / task_labels as f64 },
+            LABELS_WEIGHT,
+        ),
+        activity: Factor::new(actions, actions.min(FULL_ACTIVITY) as f64 / FULL_ACTIVITY as f64, ACTIVITY_WEIGHT),
+    };
+    let total = factors.load.points() + factors.labels.points() + factors.activity.points();
+    (round(total), factors)
+}
+
+// Best fit first; ties go to whoever has fewer open tasks, then keep their order
+pub fn rank<T>(candidates: Vec<(T, CandidateStats)>, task_labels: &[String]) -> Vec<Suggestion<T>> {
+    let mut suggestions: Vec<Suggestion<T>> = candidates
+        .into_iter()
+        .map(|(candidate, stats)| {
+            let (score, factors) = score(&stats, task_labels.len());
+            let matched_labels = task_labels
+                .iter()
+                .filter(|label| stats.completed_labels.contains(label))
+                .cloned()
+                .collect();
+            Suggestion { candidate, score, factors, matched_labels }
+        })
+        .collect();
+
+    suggestions.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(Ordering::Equal)
+            .then(a.factors.load.value.cmp(&b.factors.load.value))
+    });
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(open_tasks: i64, completed_labels: &[&str], recent_actions: i64) -> CandidateStats {
+        CandidateStats {
+            open_tasks,
+            completed_labels: completed_labels.iter().map(|label| label.to_string()).collect(),
+            recent_actions,
+        }
+    }
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|label| label.to_string()).collect()
+    }
+
+    #[test]
+    fn test_score_weighs_every_factor() {
+        let (total, factors) = score(&stats(3, &["backend"], 5), 2);
+        assert_eq!(factors.load, Factor { value: 3, score: 0.25, weight: 0.5 });
+        assert_eq!(factors.labels, Factor { value: 1, score: 0.5, weight: 0.4 });
+        assert_eq!(factors.activity, Factor { value: 5, score: 0.5, weight: 0.1 });
+        assert_eq!(total, 0.375);
+    }
+
+    #[test]
+    fn test_idle_candidate_scores_on_load_alone() {
+        let (total, factors) = score(&CandidateStats::default(), 0);
+        assert_eq!(factors.load.score, 1.0);
+        assert_eq!((factors.labels.score, factors.activity.score), (0.0, 0.0));
+        assert_eq!(total, 0.5);
+    }
+
+    #[test]
+    fn test_activity_is_capped() {
+        let (_, busy) = score(&stats(0, &[], 40), 0);
+        let (_, full) = score(&stats(0, &[], FULL_ACTIVITY), 0);
+        assert_eq!(busy.activity.score, 1.0);
+        assert_eq!(busy.activity.points(), full.activity.points());
+        assert_eq!(busy.activity.value, 40);
+    }
+
+    #[test]
+    fn test_rank_orders_by_score_then_load() {
+        let task_labels = labels(&["backend", "api"]);
+        let ranked = rank(
+            vec![
+                ("swamped", stats(9, &["backend", "api"], 10)),
+                ("specialist", stats(1, &["api", "backend"], 3)),
+                ("newcomer", stats(0, &[], 0)),
+                ("twin", stats(0, &[], 0)),
+            ],
+            &task_labels,
+        );
+
+        let order: Vec<&str> = ranked.iter().map(|suggestion| suggestion.candidate).collect();
+        assert_eq!(order, ["specialist", "swamped", "newcomer", "twin"]);
+        // In the task's label order
+        assert_eq!(ranked[0].matched_labels, ["backend", "api"]);
+        assert!(ranked[2].matched_labels.is_empty());
+        assert_eq!(ranked[0].score, 0.68);
+        assert_eq!(ranked[1].score, 0.55);
+    }
+}
@@ -0,0 +1,306 @@
+// Task attachments. Files are written below UPLOAD_DIR as
+// `<task_id>/<attachment_id>`; the original filename is only kept as metadata,
+// so client supplied names never end up in a filesystem path.
+//
+// Downloads are checked once by GET /api/attachments/:id/download. In the
+// default `signed` mode it redirects to /files/signed/:id, whose HMAC signature
+// stands in for the permission check until it expires, so range requests don't
+// repeat it. `proxy` mode streams the file from the checked request instead.
+//
+// A stored file never changes, so downloads carry its SHA-256 as a strong
+// ETag. Signed URLs may be cached by the browser for good; proxied downloads
+// are revalidated so the permission check runs every time.
+//
+// With virus scanning enabled (see scanning.rs) new attachments start out
+// `pending` and can't be downloaded until they are scanned clean. Infected
+// files are moved below `quarantine/` instead of being deleted.
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+use uuid::Uuid;
+
+use crate::config::AttachmentConfig;
+use crate::database::{
+    models::{AttachmentScanStatus, Task, TaskAttachment},
+    queries::{AttachmentQueries, CounterQueries, ProjectQueries},
+};
+use crate::utils::errors::AppError;
+use crate::utils::etag::ETag;
+
+const MAX_FILENAME_LENGTH: usize = 255;
+const QUARANTINE_DIR: &str = "quarantine";
+// Private either way: a shared cache would keep serving a signed URL after it expired
+pub const SIGNED_CACHE_CONTROL: &str = "private, max-age=31536000, immutable";
+pub const PROXY_CACHE_CONTROL: &str = "private, no-cache";
+
+fn download_mac(key: &str, attachment_id: Uuid, expires: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}:{}", attachment_id, expires).as_bytes());
+    mac
+}
+
+pub fn sign_download(key: &str, attachment_id: Uuid, expires: i64) -> String {
+    hex::encode(download_mac(key, attachment_id, expires).finalize().into_bytes())
+}
+
+pub fn verify_download(key: &str, attachment_id: Uuid, expires: i64, signature: &str, now: i64) -> bool {
+    if now > expires {
+        return false;
+    }
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    download_mac(key, attachment_id, expires).verify_slice(&signature).is_ok()
+}
+
+// Unauthenticated URL for the attachment, valid until `expires` (unix seconds)
+pub fn signed_download_url(config: &AttachmentConfig, attachment_id: Uuid, expires: i64) -> String {
+    format!(
+        "/files/signed/{}?expires={}&signature={}",
+        attachment_id,
+        expires,
+        sign_download(&config.url_signing_key, attachment_id, expires),
+    )
+}
+
+pub fn verify_signed_download(config: &AttachmentConfig, attachment_id: Uuid, expires: i64, signature: &str) -> bool {
+    verify_download(&config.url_signing_key, attachment_id, expires, signature, chrono::Utc::now().timestamp())
+}
+
+// `attachment` disposition with an ASCII fallback name and the exact name in RFC 5987 form
+pub fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if (c.is_ascii_graphic() || c == ' ') && c != '"' && c != '\\' { c } else { '_' })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+}
+
+pub fn content_hash(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+// Streams the stored file. ServeFile answers range requests and
+// If-Modified-Since from the request headers; If-None-Match is checked against
+// the content hash first, which takes precedence.
+pub async fn serve_attachment(
+    config: &AttachmentConfig,
+    attachment: &TaskAttachment,
+    request: Request,
+    cache_control: &'static str,
+) -> Response {
+    let etag = attachment.content_sha256.as_deref().map(ETag::strong);
+    if let Some(ref etag) = etag {
+        if etag.matches(request.headers()) {
+            let mut response = StatusCode::NOT_MODIFIED.into_response();
+            apply_cache_headers(&mut response, Some(etag), cache_control);
+            return response;
+        }
+    }
+
+    let path = config.upload_dir.join(&attachment.storage_path);
+    let mut response = ServeFile::new(path)
+        .oneshot(request)
+        .await
+        .unwrap_or_else(|never| match never {});
+
+    if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+        apply_cache_headers(&mut response, etag.as_ref(), cache_control);
+    }
+    if response.status().is_success() {
+        let headers = response.headers_mut();
+        let content_type = HeaderValue::from_str(&attachment.content_type)
+            .unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream"));
+        headers.insert(header::CONTENT_TYPE, content_type);
+        if let Ok(disposition) = HeaderValue::from_str(&content_disposition(&attachment.filename)) {
+            headers.insert(header::CONTENT_DISPOSITION, disposition);
+        }
+        headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    }
+    response.map(Body::new)
+}
+
+fn apply_cache_headers<B>(response: &mut axum::http::Response<B>, etag: Option<&ETag>, cache_control: &'static str) {
+    let headers = response.headers_mut();
+    if let Some(value) = etag.and_then(|etag| HeaderValue::from_str(etag.as_str()).ok()) {
+        headers.insert(header::ETAG, value);
+    }
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(cache_control));
+}
+
+// Moves a stored file to another path below UPLOAD_DIR
+pub async fn move_file(config: &AttachmentConfig, from: &str, to: &str) -> Result<(), AppError> {
+    let target = config.upload_dir.join(to);
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| AppError::InternalServer(format!("Failed to create upload directory: {}", e)))?;
+    }
+    tokio::fs::rename(config.upload_dir.join(from), &target)
+        .await
+        .map_err(|e| AppError::InternalServer(format!("Failed to move attachment: {}", e)))
+}
+
+// Deletes a stored file; one that is already gone counts as deleted
+pub async fn remove_file(config: &AttachmentConfig, storage_path: &str) -> Result<(), AppError> {
+    match tokio::fs::remove_file(config.upload_dir.join(storage_path)).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(AppError::InternalServer(format!("Failed to delete attachment: {}", e)))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Moves an infected file out of the task directories; returns its new storage path
+pub async fn quarantine_file(config: &AttachmentConfig, storage_path: &str) -> Result<String, AppError> {
+    let quarantined = format!("{}/{}", QUARANTINE_DIR, storage_path);
+    move_file(config, storage_path, &quarantined).await?;
+    Ok(quarantined)
+}
+
+// Display name for an uploaded file: no directories or control characters
+pub fn clean_filename(filename: &str) -> String {
+    let base = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_FILENAME_LENGTH)
+        .collect();
+    let cleaned = cleaned.trim();
+
+    if cleaned.is_empty() || cleaned == "." || cleaned == ".." {
+        "attachment".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+// Checks the project's attachment quota, writes the file and records it.
+// The attachment stays pending until the queued virus scan finishes.
+pub async fn store_attachment(
+    app_state: &crate::AppState,
+    task: &Task,
+    uploaded_by: Option<Uuid>,
+    filename: &str,
+    content_type: &str,
+    data: &[u8],
+) -> Result<TaskAttachment, AppError> {
+    let pool = app_state.database.pool();
+    let size_bytes = data.len() as i64;
+
+    let project = ProjectQueries::get_project_by_id(pool, task.project_id).await?;
+    let storage = CounterQueries::get_attachment_storage(pool, task.project_id).await?;
+    crate::quotas::check_attachment_storage(pool, project.team_id, storage.attachment_bytes, size_bytes).await?;
+
+    let attachment_id = Uuid::new_v4();
+    let storage_path = format!("{}/{}", task.id, attachment_id);
+    let path = app_state.config.attachments.upload_dir.join(&storage_path);
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| AppError::InternalServer(format!("Failed to create upload directory: {}", e)))?;
+    }
+    tokio::fs::write(&path, data)
+        .await
+        .map_err(|e| AppError::InternalServer(format!("Failed to store attachment: {}", e)))?;
+
+    let content_type = if content_type.trim().is_empty() { "application/octet-stream" } else { content_type.trim() };
+    let scan_status = if crate::scanning::scanning_enabled() {
+        AttachmentScanStatus::Pending
+    } else {
+        AttachmentScanStatus::Clean
+    };
+    let result = AttachmentQueries::create_attachment(
+        pool,
+        attachment_id,
+        task.id,
+        uploaded_by,
+        &clean_filename(filename),
+        content_type,
+        size_bytes,
+        &storage_path,
+        &content_hash(data),
+        scan_status,
+    ).await;
+
+    // Don't leave orphaned files behind
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+    let attachment = result?;
+
+    if attachment.scan_status == AttachmentScanStatus::Pending {
+        crate::scanning::queue_scan(pool, attachment.id).await?;
+    }
+    Ok(attachment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_filename() {
+        assert_eq!(clean_filename("report.pdf"), "report.pdf");
+        assert_eq!(clean_filename("../../etc/passwd"), "passwd");
+        assert_eq!(clean_filename("C:\\Users\\bob\\notes.txt"), "notes.txt");
+        assert_eq!(clean_filename("bad\u{0}name\n.txt"), "badname.txt");
+        assert_eq!(clean_filename(".."), "attachment");
+        assert_eq!(clean_filename(""), "attachment");
+    }
+
+    #[test]
+    fn test_signed_download_urls() {
+        let id = Uuid::new_v4();
+        let now = 1_700_000_000;
+        let signature = sign_download("key", id, now + 60);
+
+        assert!(verify_download("key", id, now + 60, &signature, now));
+        assert!(verify_download("key", id, now + 60, &signature, now + 60));
+        // Expired
+        assert!(!verify_download("key", id, now + 60, &signature, now + 61));
+        // Tampered expiry, attachment or signature
+        assert!(!verify_download("key", id, now + 3600, &signature, now));
+        assert!(!verify_download("key", Uuid::new_v4(), now + 60, &signature, now));
+        let mut tampered = signature.clone();
+        tampered.replace_range(0..1, if signature.starts_with('0') { "1" } else { "0" });
+        assert!(!verify_download("key", id, now + 60, &tampered, now));
+        assert!(!verify_download("key", id, now + 60, "not-hex", now));
+        // Signed with another key
+        assert!(!verify_download("other", id, now + 60, &signature, now));
+    }
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(content_hash(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(content_hash(b"hello there"), content_hash(b"hello there"));
+        assert_ne!(content_hash(b"hello there"), content_hash(b"hello there!"));
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(content_disposition("report.pdf"), "attachment; filename=\"report.pdf\"; filename*=UTF-8''report.pdf");
+        assert_eq!(
+            content_disposition("Über \"plan\".txt"),
+            "attachment; filename=\"_ber _plan_.txt\"; filename*=UTF-8''%C3%9Cber%20%22plan%22.txt"
+        );
+    }
+}
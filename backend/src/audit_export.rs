@@ -0,0 +1,329 @@
+// Team audit exports: the tasks created, status changes and comments in a
+// team's projects plus the audit entries recorded for the team, oldest first,
+// as CSV or JSON lines. Exports of up to AUDIT_EXPORT_SYNC_LIMIT entries
+// (default 10,000) are streamed from the request; larger ones are written
+// below UPLOAD_DIR by a job, which notifies the admin who asked once the file
+// can be downloaded.
+
+use async_trait::async_trait;
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::env;
+use tokio::io::AsyncWriteExt;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::attachments;
+use crate::config::AttachmentConfig;
+use crate::database::{
+    connection::Database,
+    models::{AuditExport, AuditExportEntry, AuditExportFormat, AuditExportStatus, Job},
+    queries::{AuditExportQueries, NotificationQueries},
+};
+use crate::jobs::{self, JobHandler};
+use crate::utils::csv::csv_field;
+use crate::utils::errors::AppError;
+use crate::websocket::events::{AuditExportNotificationData, WebSocketEvent};
+use crate::websocket::handler::WebSocketState;
+
+pub const AUDIT_EXPORT_JOB: &str = "audit_export";
+const DEFAULT_SYNC_LIMIT: i64 = 10_000;
+// Entries fetched per query, which bounds memory per export
+const BATCH_SIZE: i64 = 500;
+const EXPORT_DIR: &str = "audit-exports";
+
+const CSV_HEADER: [&str; 10] = [
+    "at",
+    "action",
+    "actor_id",
+    "actor",
+    "ip_address",
+    "entity_type",
+    "entity_id",
+    "project_id",
+    "project_name",
+    "summary",
+];
+
+pub fn sync_limit() -> i64 {
+    env::var("AUDIT_EXPORT_SYNC_LIMIT")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(DEFAULT_SYNC_LIMIT)
+}
+
+pub fn content_type(format: AuditExportFormat) -> &'static str {
+    match format {
+        AuditExportFormat::Csv => "text/csv; charset=utf-8",
+        AuditExportFormat::Jsonl => "application/x-ndjson",
+    }
+}
+
+pub fn filename(team_id: Uuid, from: DateTime<Utc>, to: DateTime<Utc>, format: AuditExportFormat) -> String {
+    format!("audit-{}-{}-{}.{}", team_id, from.format("%Y%m%d"), to.format("%Y%m%d"), format.name())
+}
+
+pub fn download_url(export: &AuditExport) -> String {
+    format!("/api/teams/{}/audit/exports/{}/download", export.team_id, export.id)
+}
+
+fn csv_header() -> String {
+    format!("{}\r\n", CSV_HEADER.join(","))
+}
+
+fn optional(value: Option<impl ToString>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+// The lines of a batch of entries
+pub fn render(entries: &[AuditExportEntry], format: AuditExportFormat) -> String {
+    let mut lines = String::new();
+    for entry in entries {
+        match format {
+            AuditExportFormat::Csv => {
+                let fields = [
+                    entry.at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    entry.action.clone(),
+                    optional(entry.actor_id),
+                    csv_field(entry.actor.as_deref().unwrap_or_default()),
+                    optional(entry.ip_address.as_deref()),
+                    entry.entity_type.clone(),
+                    optional(entry.entity_id),
+                    optional(entry.project_id),
+                    csv_field(entry.project_name.as_deref().unwrap_or_default()),
+                    csv_field(&entry.summary),
+                ];
+                lines.push_str(&fields.join(","));
+                lines.push_str("\r\n");
+            }
+            AuditExportFormat::Jsonl => {
+                lines.push_str(&serde_json::to_string(entry).unwrap_or_default());
+                lines.push('\n');
+            }
+        }
+    }
+    lines
+}
+
+struct ExportState {
+    pool: PgPool,
+    team_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    format: AuditExportFormat,
+    started: bool,
+    // The last entry written, None before the first batch or after the last
+    after: Option<(DateTime<Utc>, Uuid)>,
+}
+
+// Next chunk of the export: the CSV header with the first batch, then a batch at a time
+async fn next_chunk(mut state: ExportState) -> Result<Option<(Bytes, ExportState)>, AppError> {
+    if state.started && state.after.is_none() {
+        return Ok(None);
+    }
+    let mut chunk = String::new();
+    if !state.started && state.format == AuditExportFormat::Csv {
+        chunk.push_str(&csv_header());
+    }
+    state.started = true;
+
+    let batch = AuditExportQueries::get_entries(&state.pool, state.team_id, state.from, state.to, state.after, BATCH_SIZE).await?;
+    state.after = batch.last().filter(|_| batch.len() as i64 == BATCH_SIZE).map(|last| (last.at, last.id));
+    chunk.push_str(&render(&batch, state.format));
+
+    if chunk.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some((Bytes::from(chunk), state)))
+}
+
+pub fn stream(
+    pool: PgPool,
+    team_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    format: AuditExportFormat,
+) -> impl Stream<Item = Result<Bytes, AppError>> {
+    let state = ExportState { pool, team_id, from, to, format, started: false, after: None };
+    stream::try_unfold(state, next_chunk)
+}
+
+// Streamed download of the export
+pub fn response(pool: PgPool, team_id: Uuid, from: DateTime<Utc>, to: DateTime<Utc>, format: AuditExportFormat) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, content_type(format).to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename(team_id, from, to, format))),
+        ],
+        Body::from_stream(stream(pool, team_id, from, to, format)),
+    )
+        .into_response()
+}
+
+// Download of an export the job wrote
+pub async fn serve(config: &AttachmentConfig, export: &AuditExport, request: Request) -> Result<Response, AppError> {
+    let storage_path = match export.status {
+        AuditExportStatus::Pending => return Err(AppError::Conflict("Export is not ready yet".to_string())),
+        AuditExportStatus::Failed => return Err(AppError::Conflict("Export failed".to_string())),
+        AuditExportStatus::Ready => export.storage_path.as_deref().unwrap_or_default(),
+    };
+
+    let mut response = ServeFile::new(config.upload_dir.join(storage_path))
+        .oneshot(request)
+        .await
+        .unwrap_or_else(|never| match never {});
+    if response.status().is_success() {
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type(export.format)));
+        if let Ok(disposition) = HeaderValue::from_str(&format!(
+            "attachment; filename=\"{}\"",
+            filename(export.team_id, export.from, export.to, export.format)
+        )) {
+            headers.insert(header::CONTENT_DISPOSITION, disposition);
+        }
+    }
+    Ok(response.map(Body::new))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditExportPayload {
+    pub export_id: Uuid,
+}
+
+pub async fn queue(pool: &PgPool, export_id: Uuid) -> Result<Job, AppError> {
+    jobs::enqueue(pool, AUDIT_EXPORT_JOB, &AuditExportPayload { export_id }).await
+}
+
+pub struct AuditExportJob {
+    database: Database,
+    websocket: WebSocketState,
+    attachments: AttachmentConfig,
+}
+
+impl AuditExportJob {
+    pub fn new(database: Database, websocket: WebSocketState, attachments: AttachmentConfig) -> Self {
+        Self { database, websocket, attachments }
+    }
+
+    // Writes the export below UPLOAD_DIR; returns the number of entries
+    async fn write(&self, export: &AuditExport, storage_path: &str) -> anyhow::Result<i64> {
+        let path = self.attachments.upload_dir.join(storage_path);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::File::create(&path).await?;
+        if export.format == AuditExportFormat::Csv {
+            file.write_all(csv_header().as_bytes()).await?;
+        }
+
+        let mut written = 0;
+        let mut after = None;
+        loop {
+            let batch =
+                AuditExportQueries::get_entries(self.database.pool(), export.team_id, export.from, export.to, after, BATCH_SIZE).await?;
+            file.write_all(render(&batch, export.format).as_bytes()).await?;
+            written += batch.len() as i64;
+            match batch.last() {
+                Some(last) if batch.len() as i64 == BATCH_SIZE => after = Some((last.at, last.id)),
+                _ => break,
+            }
+        }
+        file.flush().await?;
+        Ok(written)
+    }
+}
+
+#[async_trait]
+impl JobHandler for AuditExportJob {
+    async fn run(&self, job: &Job) -> anyhow::Result<()> {
+        let payload: AuditExportPayload = serde_json::from_value(job.payload.clone())?;
+        let pool = self.database.pool();
+
+        // Gone with its team, or already written by an earlier attempt
+        let export = match AuditExportQueries::get_export(pool, payload.export_id).await {
+            Err(AppError::NotFound(_)) => return Ok(()),
+            result => result?,
+        };
+        if export.status != AuditExportStatus::Pending {
+            return Ok(());
+        }
+
+        let storage_path = format!("{}/{}/{}.{}", EXPORT_DIR, export.team_id, export.id, export.format.name());
+        let entry_count = match self.write(&export, &storage_path).await {
+            Ok(entry_count) => entry_count,
+            Err(e) => {
+                // A partial file is never served
+                if let Err(e) = attachments::remove_file(&self.attachments, &storage_path).await {
+                    warn!("Failed to delete a partial audit export: {}", e);
+                }
+                if job.attempts < job.max_attempts {
+                    return Err(e);
+                }
+                AuditExportQueries::fail_export(pool, export.id, &e.to_string()).await?;
+                return Ok(());
+            }
+        };
+
+        let export = AuditExportQueries::complete_export(pool, export.id, entry_count, &storage_path).await?;
+        if let Some(user_id) = export.requested_by {
+            let notification = NotificationQueries::create_export_notification(pool, &export, user_id).await?;
+            let event = WebSocketEvent::AuditExportReady(AuditExportNotificationData {
+                notification,
+                download_url: download_url(&export),
+                export,
+            });
+            self.websocket.send_to_user(user_id, event).await;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_render_csv_and_jsonl() {
+        let at = Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap();
+        let actor_id = Uuid::new_v4();
+        let entry = AuditExportEntry {
+            id: Uuid::new_v4(),
+            at,
+            action: "status_changed".to_string(),
+            actor_id: Some(actor_id),
+            actor: Some("ada".to_string()),
+            ip_address: None,
+            entity_type: "task".to_string(),
+            entity_id: None,
+            project_id: None,
+            project_name: Some("Web, mobile".to_string()),
+            summary: "=Moved task #3".to_string(),
+        };
+
+        let csv = render(std::slice::from_ref(&entry), AuditExportFormat::Csv);
+        assert_eq!(
+            csv,
+            format!("2024-06-03T09:00:00Z,status_changed,{},ada,,task,,,\"Web, mobile\",'=Moved task #3\r\n", actor_id)
+        );
+
+        let jsonl = render(&[entry.clone(), entry], AuditExportFormat::Jsonl);
+        let lines: Vec<&str> = jsonl.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let line: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(line["summary"], "=Moved task #3");
+        assert_eq!(line["ip_address"], serde_json::Value::Null);
+        assert!(line.get("id").is_none());
+    }
+}
@@ -0,0 +1,614 @@
+// Role-based permission checks shared by the project and team handlers.
+// Each Permission maps to the set of roles that hold it, so the rules live in
+// one place instead of a `matches!` block per handler.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, Method},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::future::Future;
+use uuid::Uuid;
+
+use crate::auth::middleware::CurrentUser;
+use crate::database::{
+    models::{ProjectRole, Task, TeamRole},
+    queries::{ProjectQueries, TeamQueries},
+};
+use crate::utils::errors::AppError;
+use crate::utils::extractors::Path;
+
+// Project role matrix, each role adding to the one before:
+//   Viewer  read the project
+//   Guest   + comment
+//   Member  + create tasks, edit and delete their own tasks
+//   Editor  + edit any task, create and edit boards
+//   Admin   + delete any task or board, manage the project
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    // Project permissions
+    ViewProject,
+    Comment,
+    CreateTasks,
+    EditOwnTasks,
+    EditTasks,
+    DeleteAnyTask,
+    EditBoards,
+    DeleteBoards,
+    ManageProject,
+    // Team permissions
+    ViewTeam,
+    CreateProjects,
+    ManageTeam,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 12] = [
+        Permission::ViewProject,
+        Permission::Comment,
+        Permission::CreateTasks,
+        Permission::EditOwnTasks,
+        Permission::EditTasks,
+        Permission::DeleteAnyTask,
+        Permission::EditBoards,
+        Permission::DeleteBoards,
+        Permission::ManageProject,
+        Permission::ViewTeam,
+        Permission::CreateProjects,
+        Permission::ManageTeam,
+    ];
+
+    // Project roles holding this permission; empty for team permissions
+    pub fn project_roles(&self) -> &'static [ProjectRole] {
+        use ProjectRole::*;
+        match self {
+            Permission::ViewProject => &[Admin, Editor, Member, Guest, Viewer],
+            Permission::Comment => &[Admin, Editor, Member, Guest],
+            Permission::CreateTasks | Permission::EditOwnTasks => &[Admin, Editor, Member],
+            Permission::EditTasks | Permission::EditBoards => &[Admin, Editor],
+            Permission::DeleteAnyTask | Permission::DeleteBoards | Permission::ManageProject => &[Admin],
+            Permission::ViewTeam | Permission::CreateProjects | Permission::ManageTeam => &[],
+        }
+    }
+
+    // Project permissions held by a role, in matrix order
+    pub fn for_project_role(role: &ProjectRole) -> Vec<Permission> {
+        Permission::ALL
+            .into_iter()
+            .filter(|permission| permission.allows_project_role(role))
+            .collect()
+    }
+
+    // Team roles holding this permission; empty for project permissions
+    pub fn team_roles(&self) -> &'static [TeamRole] {
+        use TeamRole::*;
+        match self {
+            Permission::ViewTeam | Permission::CreateProjects => &[Admin, Member],
+            Permission::ManageTeam => &[Admin],
+            _ => &[],
+        }
+    }
+
+    pub fn allows_project_role(&self, role: &ProjectRole) -> bool {
+        self.project_roles().contains(role)
+    }
+
+    pub fn allows_team_role(&self, role: &TeamRole) -> bool {
+        self.team_roles().contains(role)
+    }
+
+    // Permissions that change tasks, boards or comments, which archived projects don't allow.
+    // Project management stays available so admins can re-activate.
+    pub fn is_content_mutation(&self) -> bool {
+        matches!(
+            self,
+            Permission::Comment
+                | Permission::CreateTasks
+                | Permission::EditOwnTasks
+                | Permission::EditTasks
+                | Permission::DeleteAnyTask
+                | Permission::EditBoards
+                | Permission::DeleteBoards
+        )
+    }
+
+    fn denied_message(&self) -> &'static str {
+        match self {
+            Permission::ViewProject => "Must be a project member",
+            Permission::Comment => "Viewers can't comment on tasks",
+            Permission::CreateTasks => "Need member, editor or admin role to create tasks",
+            Permission::EditOwnTasks => "Need member, editor or admin role to edit tasks",
+            Permission::EditTasks => "Need editor or admin role to edit other people's tasks",
+            Permission::DeleteAnyTask => "Only project admins or task creators can delete tasks",
+            Permission::EditBoards => "Need editor or admin role to edit boards",
+            Permission::DeleteBoards => "Only project admins can delete boards",
+            Permission::ManageProject => "Only project admins can manage the project",
+            Permission::ViewTeam => "Must be a team member",
+            Permission::CreateProjects => "Must be a team member to create projects",
+            Permission::ManageTeam => "Only team admins can manage the team",
+        }
+    }
+}
+
+// What an API token may read. Tokens never get more than their user's project
+// role allows; scopes only narrow that down to a few read-only routes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "read:tasks")]
+    ReadTasks,
+    #[serde(rename = "read:boards")]
+    ReadBoards,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 2] = [Scope::ReadTasks, Scope::ReadBoards];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ReadTasks => "read:tasks",
+            Scope::ReadBoards => "read:boards",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Scope> {
+        Scope::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+}
+
+// Every GET route of the authenticated API (paths as in build_app, without
+// /api) and the scope an API token needs for it. Routes without a scope, and
+// every other method, are only open to signed-in sessions.
+pub const TOKEN_ROUTES: &[(&str, Option<Scope>)] = &[
+    ("/users/me", None),
+    ("/users/me/email-preferences", None),
+    ("/users/me/digest", None),
+    ("/users/me/notifications", None),
+    ("/users/me/notification-settings", None),
+    ("/users/me/agenda.pdf", None),
+    ("/users/me/tokens", None),
+    ("/users/:user_id/activity", None),
+    ("/dashboard", None),
+    ("/quick-switch", None),
+    ("/teams", None),
+    ("/teams/:team_id", None),
+    ("/teams/:team_id/members", None),
+    ("/teams/:team_id/usage", None),
+    ("/teams/:team_id/settings", None),
+    ("/teams/:team_id/retention", None),
+    ("/teams/:team_id/retention/preview", None),
+    ("/teams/:team_id/export", None),
+    ("/teams/:team_id/audit/export", None),
+    ("/teams/:team_id/audit/exports/:export_id", None),
+    ("/teams/:team_id/audit/exports/:export_id/download", None),
+    ("/teams/:team_id/trash", None),
+    ("/teams/:team_id/projects", None),
+    ("/projects", None),
+    ("/projects/:project_id", None),
+    ("/projects/:project_id/my-permissions", None),
+    ("/projects/:project_id/workflow", None),
+    ("/projects/:project_id/onboarding-template", None),
+    ("/projects/:project_id/staleness", None),
+    ("/projects/:project_id/mention-settings", None),
+    ("/projects/:project_id/notification-settings", None),
+    ("/projects/:project_id/members", None),
+    ("/projects/:project_id/invitations", None),
+    ("/projects/:project_id/usage", None),
+    ("/projects/:project_id/workload", None),
+    ("/projects/:project_id/assignment-suggestions", None),
+    ("/projects/:project_id/analytics/cycle-time", None),
+    ("/projects/:project_id/reports", None),
+    ("/projects/:project_id/reports/:report_id/preview", None),
+    ("/events/schemas", None),
+    ("/templates", None),
+    ("/projects/:project_id/integrations/slack", None),
+    ("/projects/:project_id/integrations/github", None),
+    ("/projects/:project_id/integrations/email", None),
+    ("/projects/:project_id/tasks", Some(Scope::ReadTasks)),
+    ("/projects/:project_id/tasks/counts", Some(Scope::ReadTasks)),
+    ("/projects/:project_id/tasks/picker", None),
+    // Across all of the user's projects
+    ("/tasks", None),
+    ("/tasks/:task_id", Some(Scope::ReadTasks)),
+    ("/tasks/:task_id/context", None),
+    ("/tasks/:task_id/snapshot", Some(Scope::ReadTasks)),
+    ("/tasks/:task_id/snapshots", Some(Scope::ReadTasks)),
+    ("/tasks/:task_id/snapshots/:snapshot_id", Some(Scope::ReadTasks)),
+    ("/projects/:project_id/boards", Some(Scope::ReadBoards)),
+    ("/projects/:project_id/trash/boards", None),
+    ("/projects/:project_id/share-tokens", None),
+    ("/boards/:board_id", Some(Scope::ReadBoards)),
+    ("/boards/:board_id/export", None),
+    ("/boards/:board_id/automations", None),
+    ("/tasks/:task_id/comments", None),
+    ("/tasks/:task_id/mentions", None),
+    ("/tasks/:task_id/comments/search", None),
+    ("/comments/:comment_id/context", None),
+    ("/attachments/:attachment_id/download", None),
+    ("/announcements/active", None),
+    ("/admin/jobs", None),
+    ("/admin/emails", None),
+    ("/admin/storage", None),
+    ("/admin/announcements", None),
+    ("/admin/templates", None),
+    ("/admin/export", None),
+    ("/admin/audit-log", None),
+    ("/admin/metrics", None),
+    ("/admin/flags", None),
+    ("/admin/moderation", None),
+];
+
+// What an API token was limited to when it was created
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenScope {
+    pub scopes: Vec<Scope>,
+    // None for all of the user's projects
+    pub project_ids: Option<Vec<Uuid>>,
+}
+
+impl TokenScope {
+    // `path` is the matched route, with or without the /api prefix
+    pub fn check_route(&self, method: &Method, path: &str) -> Result<(), AppError> {
+        let path = path.strip_prefix("/api").unwrap_or(path);
+        let required = TOKEN_ROUTES
+            .iter()
+            .find(|(route, _)| *route == path)
+            .and_then(|(_, scope)| *scope)
+            .filter(|_| method == Method::GET);
+
+        match required {
+            Some(scope) if self.scopes.contains(&scope) => Ok(()),
+            Some(scope) => Err(AppError::Forbidden(format!("Token is missing the {} scope", scope.as_str()))),
+            None => Err(AppError::Forbidden("Not available to API tokens".to_string())),
+        }
+    }
+}
+
+tokio::task_local! {
+    static TOKEN_PROJECTS: Vec<Uuid>;
+}
+
+// Runs a request made with an API token, which only passes the project checks
+// below for the token's projects
+pub async fn limit_to_projects<F: Future>(project_ids: Option<Vec<Uuid>>, request: F) -> F::Output {
+    match project_ids {
+        Some(project_ids) => TOKEN_PROJECTS.scope(project_ids, request).await,
+        None => request.await,
+    }
+}
+
+fn token_allows_project(project_id: Uuid) -> bool {
+    TOKEN_PROJECTS.try_with(|project_ids| project_ids.contains(&project_id)).unwrap_or(true)
+}
+
+// Returns the user's project role if it holds the permission, Forbidden otherwise.
+// Non-members are treated like a role without any permissions. Content mutations
+// additionally require the project to be active.
+pub async fn require_project_role(
+    pool: &PgPool,
+    project_id: Uuid,
+    user_id: Uuid,
+    permission: Permission,
+) -> Result<ProjectRole, AppError> {
+    let role = ProjectQueries::get_user_project_role(pool, project_id, user_id).await?;
+    check_project_role(pool, project_id, role, permission).await
+}
+
+async fn check_project_role(
+    pool: &PgPool,
+    project_id: Uuid,
+    role: Option<ProjectRole>,
+    permission: Permission,
+) -> Result<ProjectRole, AppError> {
+    if !token_allows_project(project_id) {
+        return Err(AppError::Forbidden("Token is not valid for this project".to_string()));
+    }
+    let role = match role {
+        Some(role) if permission.allows_project_role(&role) => role,
+        _ => return Err(AppError::Forbidden(permission.denied_message().to_string())),
+    };
+
+    if permission.is_content_mutation() {
+        ensure_project_active(pool, project_id).await?;
+    }
+    Ok(role)
+}
+
+// Entities handlers load by id before they know the project
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Task,
+    Board,
+    Comment,
+    Attachment,
+}
+
+impl Resource {
+    // Same error as the queries return for a missing id
+    pub fn not_found(&self) -> AppError {
+        let name = match self {
+            Resource::Task => "Task",
+            Resource::Board => "Board",
+            Resource::Comment => "Comment",
+            Resource::Attachment => "Attachment",
+        };
+        AppError::NotFound(format!("{} not found", name))
+    }
+}
+
+// With HIDE_INACCESSIBLE_RESOURCES (on by default) users outside a project get
+// the same 404 for its tasks, boards, comments and attachments as for ids that
+// don't exist, so probing ids doesn't reveal what exists. Off, they get a 403.
+fn hidden(resource: Resource, role: Option<&ProjectRole>, hide: bool) -> Option<AppError> {
+    (role.is_none() && hide).then(|| resource.not_found())
+}
+
+// require_project_role for an entity already loaded by id. Members lacking the
+// permission still get a 403, since they can see the entity anyway.
+pub async fn require_resource_role(
+    app_state: &crate::AppState,
+    resource: Resource,
+    project_id: Uuid,
+    user_id: Uuid,
+    permission: Permission,
+) -> Result<ProjectRole, AppError> {
+    let pool = app_state.database.pool();
+    let role = ProjectQueries::get_user_project_role(pool, project_id, user_id).await?;
+    if let Some(error) = hidden(resource, role.as_ref(), app_state.config.features.hide_inaccessible_resources) {
+        return Err(error);
+    }
+    check_project_role(pool, project_id, role, permission).await
+}
+
+// Archived projects are read-only until re-activated
+pub async fn ensure_project_active(pool: &PgPool, project_id: Uuid) -> Result<(), AppError> {
+    if !ProjectQueries::is_project_active(pool, project_id).await? {
+        return Err(AppError::ProjectArchived(
+            "Project is archived; re-activate it to make changes".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Members may only change tasks they created; editors and admins any task
+pub async fn require_task_edit(app_state: &crate::AppState, task: &Task, user_id: Uuid) -> Result<ProjectRole, AppError> {
+    let permission = if task.created_by == user_id {
+        Permission::EditOwnTasks
+    } else {
+        Permission::EditTasks
+    };
+    require_resource_role(app_state, Resource::Task, task.project_id, user_id, permission).await
+}
+
+pub async fn require_team_role(
+    pool: &PgPool,
+    team_id: Uuid,
+    user_id: Uuid,
+    permission: Permission,
+) -> Result<TeamRole, AppError> {
+    match TeamQueries::get_user_team_role(pool, team_id, user_id).await? {
+        Some(role) if permission.allows_team_role(&role) => Ok(role),
+        _ => Err(AppError::Forbidden(permission.denied_message().to_string())),
+    }
+}
+
+// Project members outside the project's team, such as invited contractors,
+// may only be guests or viewers
+pub async fn check_outside_team_role(
+    pool: &PgPool,
+    team_id: Uuid,
+    user_id: Uuid,
+    role: &ProjectRole,
+) -> Result<(), AppError> {
+    if !role.allowed_outside_team() && !TeamQueries::is_team_member(pool, team_id, user_id).await? {
+        return Err(AppError::Validation(
+            "Only guests and viewers can be project members without being on its team".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ProjectPath {
+    project_id: Uuid,
+}
+
+// Checks the permission for the `:project_id` route parameter before the handler runs
+async fn authorize_project(
+    parts: &mut Parts,
+    state: &crate::AppState,
+    permission: Permission,
+) -> Result<Uuid, AppError> {
+    let Path(ProjectPath { project_id }) = Path::<ProjectPath>::from_request_parts(parts, state).await?;
+    let current_user = parts
+        .extensions
+        .get::<CurrentUser>()
+        .ok_or_else(|| AppError::Unauthorized("Missing authenticated user".to_string()))?;
+
+    require_project_role(state.database.pool(), project_id, current_user.id(), permission).await?;
+    Ok(project_id)
+}
+
+// Any project role, including viewers
+pub struct ProjectMember(pub Uuid);
+
+// Roles that can create tasks
+pub struct ProjectContributor(pub Uuid);
+
+// Roles that can edit every task and board
+pub struct ProjectEditor(pub Uuid);
+
+// Project admins only
+pub struct ProjectAdmin(pub Uuid);
+
+#[async_trait]
+impl FromRequestParts<crate::AppState> for ProjectMember {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &crate::AppState) -> Result<Self, Self::Rejection> {
+        authorize_project(parts, state, Permission::ViewProject).await.map(ProjectMember)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<crate::AppState> for ProjectContributor {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &crate::AppState) -> Result<Self, Self::Rejection> {
+        authorize_project(parts, state, Permission::CreateTasks).await.map(ProjectContributor)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<crate::AppState> for ProjectEditor {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &crate::AppState) -> Result<Self, Self::Rejection> {
+        authorize_project(parts, state, Permission::EditTasks).await.map(ProjectEditor)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<crate::AppState> for ProjectAdmin {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &crate::AppState) -> Result<Self, Self::Rejection> {
+        authorize_project(parts, state, Permission::ManageProject).await.map(ProjectAdmin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT_ROLES: [ProjectRole; 5] = [
+        ProjectRole::Admin,
+        ProjectRole::Editor,
+        ProjectRole::Member,
+        ProjectRole::Guest,
+        ProjectRole::Viewer,
+    ];
+    const TEAM_ROLES: [TeamRole; 2] = [TeamRole::Admin, TeamRole::Member];
+
+    // One row per permission: which project roles (Admin, Editor, Member, Guest, Viewer)
+    // and team roles (Admin, Member) hold it
+    fn expected(permission: Permission) -> ([bool; 5], [bool; 2]) {
+        match permission {
+            Permission::ViewProject => ([true, true, true, true, true], [false, false]),
+            Permission::Comment => ([true, true, true, true, false], [false, false]),
+            Permission::CreateTasks => ([true, true, true, false, false], [false, false]),
+            Permission::EditOwnTasks => ([true, true, true, false, false], [false, false]),
+            Permission::EditTasks => ([true, true, false, false, false], [false, false]),
+            Permission::DeleteAnyTask => ([true, false, false, false, false], [false, false]),
+            Permission::EditBoards => ([true, true, false, false, false], [false, false]),
+            Permission::DeleteBoards => ([true, false, false, false, false], [false, false]),
+            Permission::ManageProject => ([true, false, false, false, false], [false, false]),
+            Permission::ViewTeam => ([false, false, false, false, false], [true, true]),
+            Permission::CreateProjects => ([false, false, false, false, false], [true, true]),
+            Permission::ManageTeam => ([false, false, false, false, false], [true, false]),
+        }
+    }
+
+    #[test]
+    fn test_permission_matrix() {
+        for permission in Permission::ALL {
+            let (project, team) = expected(permission);
+
+            for (role, allowed) in PROJECT_ROLES.iter().zip(project) {
+                assert_eq!(permission.allows_project_role(role), allowed, "{:?} for project {:?}", permission, role);
+            }
+            for (role, allowed) in TEAM_ROLES.iter().zip(team) {
+                assert_eq!(permission.allows_team_role(role), allowed, "{:?} for team {:?}", permission, role);
+            }
+        }
+    }
+
+    #[test]
+    fn test_roles_are_cumulative() {
+        for pair in PROJECT_ROLES.windows(2) {
+            let higher = Permission::for_project_role(&pair[0]);
+            let lower = Permission::for_project_role(&pair[1]);
+
+            assert!(lower.iter().all(|permission| higher.contains(permission)), "{:?} vs {:?}", pair[0], pair[1]);
+            assert!(higher.len() > lower.len(), "{:?} adds nothing over {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_role_ranks_follow_the_matrix() {
+        for pair in PROJECT_ROLES.windows(2) {
+            assert!(pair[0].rank() > pair[1].rank(), "{:?} vs {:?}", pair[0], pair[1]);
+        }
+        for pair in TEAM_ROLES.windows(2) {
+            assert!(pair[0].rank() > pair[1].rank(), "{:?} vs {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_non_members_see_not_found() {
+        let hidden_error = hidden(Resource::Task, None, true);
+        assert!(matches!(hidden_error, Some(AppError::NotFound(ref message)) if message == "Task not found"));
+
+        // Members get the regular permission check, and hiding can be turned off
+        assert!(hidden(Resource::Board, Some(&ProjectRole::Viewer), true).is_none());
+        assert!(hidden(Resource::Comment, None, false).is_none());
+    }
+
+    // Every GET route in build_app needs an entry in TOKEN_ROUTES, so new
+    // routes are never opened to API tokens by accident
+    #[test]
+    fn test_every_route_is_classified_for_tokens() {
+        let source = include_str!("../lib.rs");
+        let start = source.find("let protected_routes").unwrap();
+        let end = source.find("let public_routes").unwrap();
+        let routes: Vec<&str> = source[start..end]
+            .lines()
+            .filter(|line| line.contains("get("))
+            .filter_map(|line| line.trim().strip_prefix(".route(\"")?.split('"').next())
+            .collect();
+
+        assert!(!routes.is_empty());
+        for route in &routes {
+            assert!(TOKEN_ROUTES.iter().any(|(path, _)| path == route), "{} isn't in TOKEN_ROUTES", route);
+        }
+        for (path, _) in TOKEN_ROUTES {
+            assert!(routes.contains(path), "{} in TOKEN_ROUTES isn't a GET route", path);
+        }
+    }
+
+    #[test]
+    fn test_tokens_need_the_route_scope() {
+        let token = TokenScope { scopes: vec![Scope::ReadTasks], project_ids: None };
+        assert!(token.check_route(&Method::GET, "/api/projects/:project_id/tasks").is_ok());
+        assert!(token.check_route(&Method::GET, "/tasks/:task_id").is_ok());
+
+        match token.check_route(&Method::GET, "/api/boards/:board_id") {
+            Err(AppError::Forbidden(message)) => assert_eq!(message, "Token is missing the read:boards scope"),
+            other => panic!("expected forbidden, got {:?}", other),
+        }
+        // Read scopes don't cover writes on the same path, nor unscoped routes
+        assert!(token.check_route(&Method::POST, "/api/projects/:project_id/tasks").is_err());
+        assert!(token.check_route(&Method::GET, "/api/projects").is_err());
+        assert!(token.check_route(&Method::GET, "/api/unknown").is_err());
+    }
+
+    #[test]
+    fn test_scopes_round_trip() {
+        for scope in Scope::ALL {
+            assert_eq!(Scope::parse(scope.as_str()), Some(scope));
+            assert_eq!(serde_json::to_value(scope).unwrap(), scope.as_str());
+        }
+        assert_eq!(Scope::parse("write:tasks"), None);
+    }
+
+    #[test]
+    fn test_archived_projects_stay_manageable() {
+        assert!(Permission::EditTasks.is_content_mutation());
+        assert!(Permission::CreateTasks.is_content_mutation());
+        assert!(Permission::Comment.is_content_mutation());
+        assert!(!Permission::ViewProject.is_content_mutation());
+        assert!(!Permission::ManageProject.is_content_mutation());
+    }
+}
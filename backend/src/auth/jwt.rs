@@ -1,17 +1,24 @@
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, encode, errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
-use std::env;
+use std::fmt;
 use uuid::Uuid;
 use chrono::{Duration, Utc};
 use anyhow::{Result, anyhow};
 
+use crate::config::JwtConfig;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,      // user id
     pub username: String,
     pub exp: i64,         // expiration timestamp
     pub iat: i64,         // issued at timestamp
+    pub iss: String,      // JWT_ISSUER of the instance that issued the token
+    pub aud: String,      // JWT_AUDIENCE it was issued for
     pub token_type: TokenType,
+    // Admin user id when the token was issued for impersonating `sub`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,34 +27,47 @@ pub enum TokenType {
     Refresh,
 }
 
+// Why a token was rejected. Clients refresh on `Expired` and sign in again
+// on `Invalid`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenError {
+    Expired,
+    Invalid(String),
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::Expired => write!(f, "Token expired"),
+            TokenError::Invalid(_) => write!(f, "Invalid token"),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct JwtService {
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
+    issuer: String,
+    audience: String,
+    // Clock skew tolerated on exp and iat, in seconds
+    leeway: u64,
     access_token_expiry: Duration,
     refresh_token_expiry: Duration,
+    impersonation_token_expiry: Duration,
 }
 
 impl JwtService {
-    pub fn new() -> Result<Self> {
-        let secret = env::var("JWT_SECRET")
-            .unwrap_or_else(|_| "your-super-secret-jwt-key-for-development-only".to_string());
-        
-        let access_expiry = env::var("JWT_EXPIRATION")
-            .unwrap_or_else(|_| "3600".to_string())
-            .parse::<i64>()
-            .unwrap_or(3600);
-            
-        let refresh_expiry = env::var("REFRESH_TOKEN_EXPIRATION")
-            .unwrap_or_else(|_| "604800".to_string())
-            .parse::<i64>()
-            .unwrap_or(604800);
-
+    pub fn new(config: &JwtConfig) -> Result<Self> {
         Ok(JwtService {
-            encoding_key: EncodingKey::from_secret(secret.as_ref()),
-            decoding_key: DecodingKey::from_secret(secret.as_ref()),
-            access_token_expiry: Duration::seconds(access_expiry),
-            refresh_token_expiry: Duration::seconds(refresh_expiry),
+            encoding_key: EncodingKey::from_secret(config.secret.as_bytes()),
+            decoding_key: DecodingKey::from_secret(config.secret.as_bytes()),
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            leeway: config.leeway_seconds,
+            access_token_expiry: Duration::seconds(config.access_token_expiry_seconds),
+            refresh_token_expiry: Duration::seconds(config.refresh_token_expiry_seconds),
+            impersonation_token_expiry: Duration::seconds(config.impersonation_token_expiry_seconds),
         })
     }
 
@@ -60,7 +80,10 @@ impl JwtService {
             username: username.to_string(),
             exp: exp.timestamp(),
             iat: now.timestamp(),
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
             token_type: TokenType::Access,
+            impersonator: None,
         };
 
         encode(&Header::default(), &claims, &self.encoding_key)
@@ -76,19 +99,54 @@ impl JwtService {
             username: username.to_string(),
             exp: exp.timestamp(),
             iat: now.timestamp(),
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
             token_type: TokenType::Refresh,
+            impersonator: None,
         };
 
         encode(&Header::default(), &claims, &self.encoding_key)
             .map_err(|e| anyhow!("Failed to generate refresh token: {}", e))
     }
 
-    pub fn verify_token(&self, token: &str) -> Result<Claims> {
-        let token_data = decode::<Claims>(
-            token,
-            &self.decoding_key,
-            &Validation::default(),
-        ).map_err(|e| anyhow!("Failed to verify token: {}", e))?;
+    // Short-lived access token for `user_id` on behalf of an instance admin.
+    // There is no matching refresh token.
+    pub fn generate_impersonation_token(&self, user_id: Uuid, username: &str, impersonator: Uuid) -> Result<String> {
+        let now = Utc::now();
+        let exp = now + self.impersonation_token_expiry;
+
+        let claims = Claims {
+            sub: user_id.to_string(),
+            username: username.to_string(),
+            exp: exp.timestamp(),
+            iat: now.timestamp(),
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            token_type: TokenType::Access,
+            impersonator: Some(impersonator.to_string()),
+        };
+
+        encode(&Header::default(), &claims, &self.encoding_key)
+            .map_err(|e| anyhow!("Failed to generate impersonation token: {}", e))
+    }
+
+    // Checks the signature, expiry, issuer and audience, and that the token
+    // wasn't issued in the future
+    pub fn verify_token(&self, token: &str) -> std::result::Result<Claims, TokenError> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        validation.leeway = self.leeway;
+
+        let token_data = decode::<Claims>(token, &self.decoding_key, &validation).map_err(|e| match e.kind() {
+            ErrorKind::ExpiredSignature => TokenError::Expired,
+            _ => TokenError::Invalid(e.to_string()),
+        })?;
+
+        if token_data.claims.iat > Utc::now().timestamp() + self.leeway as i64 {
+            return Err(TokenError::Invalid("Token issued in the future".to_string()));
+        }
 
         Ok(token_data.claims)
     }
@@ -96,15 +154,20 @@ impl JwtService {
     pub fn get_access_token_expiry(&self) -> i64 {
         self.access_token_expiry.num_seconds()
     }
+
+    pub fn get_impersonation_token_expiry(&self) -> i64 {
+        self.impersonation_token_expiry.num_seconds()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
 
     #[test]
     fn test_jwt_generation_and_verification() {
-        let jwt_service = JwtService::new().unwrap();
+        let jwt_service = JwtService::new(&Config::test_default().jwt).unwrap();
         let user_id = Uuid::new_v4();
         let username = "testuser";
 
@@ -114,5 +177,104 @@ mod tests {
         assert_eq!(claims.sub, user_id.to_string());
         assert_eq!(claims.username, username);
         assert!(matches!(claims.token_type, TokenType::Access));
+        assert_eq!(claims.impersonator, None);
+    }
+
+    #[test]
+    fn test_impersonation_token_names_the_admin() {
+        let jwt_service = JwtService::new(&Config::test_default().jwt).unwrap();
+        let user_id = Uuid::new_v4();
+        let admin_id = Uuid::new_v4();
+
+        let token = jwt_service.generate_impersonation_token(user_id, "testuser", admin_id).unwrap();
+        let claims = jwt_service.verify_token(&token).unwrap();
+
+        assert_eq!(claims.sub, user_id.to_string());
+        assert_eq!(claims.impersonator, Some(admin_id.to_string()));
+        assert!(matches!(claims.token_type, TokenType::Access));
+        assert!(claims.exp - claims.iat <= jwt_service.get_impersonation_token_expiry());
+    }
+
+    // Claims as `jwt_service` would issue them, with iat and exp the given
+    // number of seconds from now
+    fn claims_at(jwt_service: &JwtService, iat_offset: i64, exp_offset: i64) -> Claims {
+        let now = Utc::now().timestamp();
+        Claims {
+            sub: Uuid::new_v4().to_string(),
+            username: "testuser".to_string(),
+            exp: now + exp_offset,
+            iat: now + iat_offset,
+            iss: jwt_service.issuer.clone(),
+            aud: jwt_service.audience.clone(),
+            token_type: TokenType::Access,
+            impersonator: None,
+        }
+    }
+
+    fn sign(jwt_service: &JwtService, claims: &Claims) -> String {
+        encode(&Header::default(), claims, &jwt_service.encoding_key).unwrap()
+    }
+
+    #[test]
+    fn test_expired_token_is_reported_as_expired() {
+        let jwt_service = JwtService::new(&Config::test_default().jwt).unwrap();
+        let leeway = jwt_service.leeway as i64;
+
+        let token = sign(&jwt_service, &claims_at(&jwt_service, -3600, -leeway - 10));
+        assert_eq!(jwt_service.verify_token(&token).unwrap_err(), TokenError::Expired);
+
+        // Within the clock skew leeway
+        let token = sign(&jwt_service, &claims_at(&jwt_service, -3600, -leeway / 2));
+        assert!(jwt_service.verify_token(&token).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_other_issuer_and_audience() {
+        let jwt_service = JwtService::new(&Config::test_default().jwt).unwrap();
+
+        let mut claims = claims_at(&jwt_service, 0, 3600);
+        claims.iss = "staging".to_string();
+        assert!(matches!(jwt_service.verify_token(&sign(&jwt_service, &claims)), Err(TokenError::Invalid(_))));
+
+        let mut claims = claims_at(&jwt_service, 0, 3600);
+        claims.aud = "another-service".to_string();
+        assert!(matches!(jwt_service.verify_token(&sign(&jwt_service, &claims)), Err(TokenError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_rejects_tokens_without_issuer_or_audience() {
+        let jwt_service = JwtService::new(&Config::test_default().jwt).unwrap();
+        let now = Utc::now().timestamp();
+        let claims = serde_json::json!({
+            "sub": Uuid::new_v4().to_string(),
+            "username": "testuser",
+            "exp": now + 3600,
+            "iat": now,
+            "token_type": "Access",
+        });
+        let token = encode(&Header::default(), &claims, &jwt_service.encoding_key).unwrap();
+
+        assert!(matches!(jwt_service.verify_token(&token), Err(TokenError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_rejects_token_issued_in_the_future() {
+        let jwt_service = JwtService::new(&Config::test_default().jwt).unwrap();
+        let leeway = jwt_service.leeway as i64;
+
+        let token = sign(&jwt_service, &claims_at(&jwt_service, leeway + 60, 3600));
+        assert!(matches!(jwt_service.verify_token(&token), Err(TokenError::Invalid(_))));
+
+        let token = sign(&jwt_service, &claims_at(&jwt_service, leeway / 2, 3600));
+        assert!(jwt_service.verify_token(&token).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_token_signed_with_another_secret() {
+        let jwt_service = JwtService::new(&Config::test_default().jwt).unwrap();
+        let claims = claims_at(&jwt_service, 0, 3600);
+        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(b"someone-elses-secret")).unwrap();
+
+        assert!(matches!(jwt_service.verify_token(&token), Err(TokenError::Invalid(_))));
     }
 }
\ No newline at end of file
@@ -1,18 +1,21 @@
 use axum::{
-    extract::{Request, State},
-    http::{HeaderMap, StatusCode},
+    extract::{MatchedPath, OriginalUri, Request, State},
+    http::{HeaderMap, HeaderValue, Method},
     middleware::Next,
-    response::Response,
-    Json,
+    response::{IntoResponse, Response},
 };
-use serde_json::json;
+use tracing::{warn, Instrument};
 use uuid::Uuid;
 
-use crate::auth::jwt::{JwtService, Claims, TokenType};
+use crate::auth::authz::{self, TokenScope};
+use crate::auth::jwt::TokenType;
+use crate::auth::tokens;
+use crate::database::{models::AuditAction, queries::{ApiTokenQueries, AuditQueries}};
 use crate::utils::errors::AppError;
+use crate::utils::rate_limit::{self, Quota, Quotas};
 
 pub async fn auth_middleware(
-    State(jwt_service): State<JwtService>,
+    State(app_state): State<crate::AppState>,
     headers: HeaderMap,
     mut req: Request,
     next: Next,
@@ -31,10 +34,40 @@ pub async fn auth_middleware(
     // Extract token
     let token = &auth_header[7..]; // Remove "Bearer " prefix
 
+    let (current_user, bucket) = if token.starts_with(tokens::TOKEN_PREFIX) {
+        api_token_user(&app_state, token).await?
+    } else {
+        let current_user = session_user(&app_state, token)?;
+        let bucket = Bucket::Session(current_user.id);
+        (current_user, bucket)
+    };
+    let user_id = current_user.id;
+    let impersonated_by = current_user.impersonated_by;
+    let token_scope = current_user.token.clone();
+
+    // Add user info to request extensions
+    req.extensions_mut().insert(current_user);
+
+    let quota = bucket.take(&app_state.quotas).await;
+    let mut response = match quota {
+        Err(exhausted) => AppError::RateLimited(exhausted.reset_after.as_secs().max(1)).into_response(),
+        Ok(_) => match (impersonated_by, token_scope) {
+            (Some(admin_id), _) => impersonated_request(&app_state, admin_id, user_id, req, next)
+                .await
+                .unwrap_or_else(IntoResponse::into_response),
+            (None, Some(scope)) => scoped_request(scope, req, next).await.unwrap_or_else(IntoResponse::into_response),
+            (None, None) => next.run(req).await,
+        },
+    };
+    set_quota_headers(&mut response, quota.unwrap_or_else(|exhausted| exhausted));
+    Ok(response)
+}
+
+fn session_user(app_state: &crate::AppState, token: &str) -> Result<CurrentUser, AppError> {
     // Verify token
-    let claims = jwt_service
+    let claims = app_state.jwt_service
         .verify_token(token)
-        .map_err(|_| AppError::Unauthorized("Invalid or expired token".to_string()))?;
+        .map_err(|e| AppError::Unauthorized(e.to_string()))?;
 
     // Only allow access tokens for API endpoints
     if !matches!(claims.token_type, TokenType::Access) {
@@ -45,19 +78,117 @@ pub async fn auth_middleware(
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| AppError::Unauthorized("Invalid user ID in token".to_string()))?;
 
-    // Add user info to request extensions
-    req.extensions_mut().insert(CurrentUser {
+    let impersonated_by = claims.impersonator
+        .map(|admin_id| Uuid::parse_str(&admin_id))
+        .transpose()
+        .map_err(|_| AppError::Unauthorized("Invalid impersonator in token".to_string()))?;
+
+    Ok(CurrentUser {
         id: user_id,
         username: claims.username,
-    });
+        impersonated_by,
+        token: None,
+    })
+}
+
+async fn api_token_user(app_state: &crate::AppState, token: &str) -> Result<(CurrentUser, Bucket), AppError> {
+    let (user_id, username, api_token, limit_override) = ApiTokenQueries::authenticate(app_state.database.pool(), &tokens::hash_token(token))
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid token".to_string()))?;
+
+    let bucket = Bucket::Token { token_id: api_token.id, max_requests: rate_limit::token_max_requests(limit_override) };
+    let current_user = CurrentUser {
+        id: user_id,
+        username,
+        impersonated_by: None,
+        token: Some(TokenScope { scopes: api_token.scopes, project_ids: api_token.project_ids }),
+    };
+    Ok((current_user, bucket))
+}
+
+// The quota a request counts against: the session bucket of its user, or the
+// bucket of the API token it was made with
+enum Bucket {
+    Session(Uuid),
+    Token { token_id: Uuid, max_requests: u32 },
+}
+
+impl Bucket {
+    async fn take(&self, quotas: &Quotas) -> Result<Quota, Quota> {
+        match *self {
+            Bucket::Session(user_id) => quotas.take_session(user_id).await,
+            Bucket::Token { token_id, max_requests } => quotas.take_token(token_id, max_requests).await,
+        }
+    }
+}
+
+// Runs a request made with an API token: only the routes its scopes cover,
+// and only in its projects
+async fn scoped_request(scope: TokenScope, req: Request, next: Next) -> Result<Response, AppError> {
+    let path = req.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string()).unwrap_or_default();
+    scope.check_route(req.method(), &path)?;
+
+    Ok(authz::limit_to_projects(scope.project_ids, next.run(req)).await)
+}
+
+// X-RateLimit-Reset is the end of the window in Unix seconds
+fn set_quota_headers(response: &mut Response, quota: Quota) {
+    let reset = chrono::Utc::now().timestamp() + quota.reset_after.as_secs() as i64;
+    let headers = response.headers_mut();
+    headers.insert("x-ratelimit-limit", HeaderValue::from(quota.limit));
+    headers.insert("x-ratelimit-remaining", HeaderValue::from(quota.remaining));
+    headers.insert("x-ratelimit-reset", HeaderValue::from(reset));
+}
+
+// Runs a request made with an impersonation token. The request is written to
+// the audit log before it runs, so nothing happens unrecorded.
+async fn impersonated_request(
+    app_state: &crate::AppState,
+    admin_id: Uuid,
+    user_id: Uuid,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let pool = app_state.database.pool();
+    let method = req.method().clone();
+    let path = req.extensions().get::<OriginalUri>().map_or_else(|| req.uri().path(), |uri| uri.path()).to_string();
+
+    let entry_id = AuditQueries::record(
+        pool,
+        AuditAction::ImpersonatedRequest,
+        admin_id,
+        user_id,
+        Some(method.as_str()),
+        Some(&path),
+    ).await?;
+
+    let response = if blocked_while_impersonating(&method) {
+        AppError::Forbidden("Not allowed while impersonating a user".to_string()).into_response()
+    } else {
+        let span = tracing::info_span!("impersonation", impersonator = %admin_id, user_id = %user_id);
+        next.run(req).instrument(span).await
+    };
 
-    Ok(next.run(req).await)
+    if let Err(e) = AuditQueries::set_status(pool, entry_id, response.status().as_u16() as i16).await {
+        warn!("Recording the status of impersonated request {} failed: {}", entry_id, e);
+    }
+    Ok(response)
+}
+
+// Support looks, it doesn't destroy. Deletes are refused outright; handlers
+// for other sensitive changes check CurrentUser::ensure_not_impersonated.
+fn blocked_while_impersonating(method: &Method) -> bool {
+    method == Method::DELETE
 }
 
 #[derive(Debug, Clone)]
 pub struct CurrentUser {
     pub id: Uuid,
     pub username: String,
+    // The instance admin acting as this user
+    pub impersonated_by: Option<Uuid>,
+    // Set for requests made with an API token rather than a session
+    pub token: Option<TokenScope>,
 }
 
 impl CurrentUser {
@@ -68,4 +199,27 @@ impl CurrentUser {
     pub fn username(&self) -> &str {
         &self.username
     }
+
+    pub fn impersonated_by(&self) -> Option<Uuid> {
+        self.impersonated_by
+    }
+
+    pub fn ensure_not_impersonated(&self) -> Result<(), AppError> {
+        match self.impersonated_by {
+            Some(_) => Err(AppError::Forbidden("Not allowed while impersonating a user".to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_deletes_are_blocked_outright() {
+        assert!(blocked_while_impersonating(&Method::DELETE));
+        assert!(!blocked_while_impersonating(&Method::GET));
+        assert!(!blocked_while_impersonating(&Method::PUT));
+    }
 }
\ No newline at end of file
@@ -1,4 +1,7 @@
 // Authentication and authorization module
+pub mod authz;
 pub mod jwt;
 pub mod password;
+pub mod registration;
+pub mod tokens;
 pub mod middleware;
\ No newline at end of file
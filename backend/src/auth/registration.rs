@@ -0,0 +1,116 @@
+// Who may register. Instances can close public registration, so new accounts
+// need an admin-generated signup code, and limit sign-ups to email domains.
+// The domain list applies with or without a code.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use sqlx::PgPool;
+
+use crate::config::Features;
+use crate::database::queries::SignupCodeQueries;
+use crate::utils::errors::AppError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegistrationPolicy {
+    pub allow_public: bool,
+    // Lowercase domains; empty allows every domain
+    pub allowed_domains: Vec<String>,
+    // Never reveal whether an email is registered before sign-up (PRIVACY_STRICT)
+    pub privacy_strict: bool,
+}
+
+impl Default for RegistrationPolicy {
+    fn default() -> Self {
+        RegistrationPolicy {
+            allow_public: true,
+            allowed_domains: Vec::new(),
+            privacy_strict: false,
+        }
+    }
+}
+
+impl RegistrationPolicy {
+    pub fn new(features: &Features) -> Self {
+        RegistrationPolicy {
+            allow_public: features.allow_public_registration,
+            allowed_domains: features.registration_allowed_domains.clone(),
+            privacy_strict: features.privacy_strict,
+        }
+    }
+
+    pub fn check_email_domain(&self, email: &str) -> Result<(), AppError> {
+        if self.allowed_domains.is_empty() {
+            return Ok(());
+        }
+
+        let domain = email.rsplit_once('@').map(|(_, domain)| domain.to_lowercase()).unwrap_or_default();
+        if !self.allowed_domains.contains(&domain) {
+            return Err(AppError::EmailDomainNotAllowed(format!(
+                "Registration is limited to {} email addresses",
+                self.allowed_domains.join(", ")
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Runs before the account is created. A signup code is only required while
+// public registration is closed and the user wasn't invited to a project, but
+// a code that is sent is always redeemed.
+pub async fn check_can_register(
+    pool: &PgPool,
+    policy: &RegistrationPolicy,
+    email: &str,
+    signup_code: Option<&str>,
+    invited: bool,
+) -> Result<(), AppError> {
+    policy.check_email_domain(email)?;
+
+    match signup_code.map(str::trim).filter(|code| !code.is_empty()) {
+        Some(code) if !SignupCodeQueries::redeem_signup_code(pool, code).await? => {
+            return Err(AppError::Validation("Signup code is invalid, expired or used up".to_string()));
+        }
+        Some(_) => {}
+        None if !policy.allow_public && !invited => {
+            return Err(AppError::RegistrationClosed(
+                "Registration on this instance requires an invitation".to_string(),
+            ));
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+pub fn generate_signup_code() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_domains() {
+        let policy = RegistrationPolicy {
+            allow_public: true,
+            allowed_domains: vec!["example.com".to_string()],
+            ..Default::default()
+        };
+
+        assert!(policy.check_email_domain("alice@Example.com").is_ok());
+        assert!(matches!(
+            policy.check_email_domain("mallory@example.com.evil.io"),
+            Err(AppError::EmailDomainNotAllowed(_))
+        ));
+        assert!(RegistrationPolicy::default().check_email_domain("anyone@anywhere.dev").is_ok());
+    }
+
+    #[test]
+    fn test_generated_codes_are_unique() {
+        let code = generate_signup_code();
+
+        assert_eq!(code.len(), 32);
+        assert_ne!(code, generate_signup_code());
+    }
+}
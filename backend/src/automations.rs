@@ -0,0 +1,246 @@
+// Board automation rules. When a task moves into another column, the rules of
+// the project's boards that trigger on that column run inside the move's
+// transaction (see TaskQueries::move_task). Actions change the assignee, labels
+// and priority or post a comment, but never move the task, so a rule can't set
+// off further rules. Each action skips what is already done, which makes
+// moving a task into the same column again harmless: a task assigned to
+// someone in the rotation stays with them, labels aren't added twice and a
+// rule's comment is only posted the first time it runs on the task. Every rule
+// that did something is recorded on the task as a system comment.
+
+use uuid::Uuid;
+
+use crate::database::models::{AutomationAction, AutomationRule, AutomationTrigger, BoardAutomations, Task, TaskPriority, TaskStatus};
+use crate::utils::validation::MAX_TASK_TAGS;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoardRule {
+    pub board_id: Uuid,
+    pub rule: AutomationRule,
+}
+
+// The rules to run when a task moves from `from` to `to`, board by board in
+// the order given. Moves within a column run none.
+pub fn rules_for_move(boards: Vec<(Uuid, BoardAutomations)>, from: TaskStatus, to: TaskStatus) -> Vec<BoardRule> {
+    if from == to {
+        return Vec::new();
+    }
+    boards
+        .into_iter()
+        .flat_map(|(board_id, automations)| automations.rules.into_iter().map(move |rule| BoardRule { board_id, rule }))
+        .filter(|board_rule| board_rule.rule.trigger == AutomationTrigger::MovedToColumn { column_id: to })
+        .collect()
+}
+
+// What a rule would change on a task
+#[derive(Debug, Default, PartialEq)]
+pub struct RulePlan {
+    pub assign_to: Option<Uuid>,
+    pub add_labels: Vec<String>,
+    pub priority: Option<TaskPriority>,
+    pub comment: Option<String>,
+}
+
+impl RulePlan {
+    pub fn is_empty(&self) -> bool {
+        self.assign_to.is_none() && self.add_labels.is_empty() && self.priority.is_none() && self.comment.is_none()
+    }
+}
+
+// `members` are the rotation's users that are project members and
+// `last_assignee` is whoever the rule assigned last; `first_run` is whether
+// the rule hasn't done anything on this task before
+pub fn plan(rule: &AutomationRule, task: &Task, members: &[Uuid], last_assignee: Option<Uuid>, first_run: bool) -> RulePlan {
+    let mut plan = RulePlan::default();
+    let mut tags = task.tags.clone().unwrap_or_default();
+
+    for action in &rule.actions {
+        match action {
+            AutomationAction::AssignRoundRobin { user_ids } => {
+                if !task.assigned_to.is_some_and(|assignee| members.contains(&assignee)) {
+                    plan.assign_to = next_in_rotation(user_ids, members, last_assignee);
+                }
+            }
+            AutomationAction::AddLabel { label } => {
+                let label = label.trim();
+                if tags.len() < MAX_TASK_TAGS && !tags.iter().any(|tag| tag.eq_ignore_ascii_case(label)) {
+                    tags.push(label.to_string());
+                    plan.add_labels.push(label.to_string());
+                }
+            }
+            AutomationAction::SetPriority { priority } => {
+                if task.priority != *priority {
+                    plan.priority = Some(*priority);
+                }
+            }
+            AutomationAction::PostSystemComment { content } => {
+                if first_run {
+                    plan.comment = Some(content.trim().to_string());
+                }
+            }
+        }
+    }
+
+    plan
+}
+
+// The member after `last` in the configured order, starting over at the top;
+// users who left the project are skipped
+pub fn next_in_rotation(user_ids: &[Uuid], members: &[Uuid], last: Option<Uuid>) -> Option<Uuid> {
+    let start = last
+        .and_then(|last| user_ids.iter().position(|user_id| *user_id == last))
+        .map_or(0, |index| index + 1);
+    (0..user_ids.len())
+        .map(|offset| user_ids[(start + offset) % user_ids.len()])
+        .find(|user_id| members.contains(user_id))
+}
+
+// The system comment recording a rule run. `assignee` is the display name of
+// the user the plan assigns.
+pub fn history_comment(rule: &AutomationRule, plan: &RulePlan, assignee: Option<&str>) -> String {
+    let mut changes = Vec::new();
+    if let Some(assignee) = assignee {
+        changes.push(format!("assigned to {}", assignee));
+    }
+    for label in &plan.add_labels {
+        changes.push(format!("added label \"{}\"", label));
+    }
+    if let Some(priority) = plan.priority {
+        changes.push(format!("set priority to {}", priority.name()));
+    }
+
+    let summary = if changes.is_empty() {
+        format!("Automation \"{}\" ran.", rule.name)
+    } else {
+        format!("Automation \"{}\": {}.", rule.name, changes.join(", "))
+    };
+    match plan.comment {
+        Some(ref comment) => format!("{}\n\n{}", comment, summary),
+        None => summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn task(assigned_to: Option<Uuid>, tags: &[&str]) -> Task {
+        Task {
+            id: Uuid::new_v4(),
+            title: "Review login".to_string(),
+            description: None,
+            project_id: Uuid::new_v4(),
+            created_by: Uuid::new_v4(),
+            assigned_to,
+            status: TaskStatus::Review,
+            priority: TaskPriority::Medium,
+            due_date: None,
+            is_all_day: false,
+            tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+            cover_color: None,
+            cover_emoji: None,
+            position: 1,
+            number: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn rule(actions: Vec<AutomationAction>) -> AutomationRule {
+        AutomationRule {
+            id: Uuid::new_v4(),
+            name: "QA handoff".to_string(),
+            trigger: AutomationTrigger::MovedToColumn { column_id: TaskStatus::Review },
+            actions,
+        }
+    }
+
+    #[test]
+    fn test_rules_for_move() {
+        let board_id = Uuid::new_v4();
+        let review = rule(vec![AutomationAction::SetPriority { priority: TaskPriority::High }]);
+        let mut done = review.clone();
+        done.trigger = AutomationTrigger::MovedToColumn { column_id: TaskStatus::Done };
+        let boards = || vec![(board_id, BoardAutomations { rules: vec![review.clone(), done.clone()] })];
+
+        assert_eq!(
+            rules_for_move(boards(), TaskStatus::InProgress, TaskStatus::Review),
+            vec![BoardRule { board_id, rule: review.clone() }]
+        );
+        assert!(rules_for_move(boards(), TaskStatus::Review, TaskStatus::Review).is_empty());
+        assert!(rules_for_move(boards(), TaskStatus::Review, TaskStatus::Todo).is_empty());
+    }
+
+    #[test]
+    fn test_rotation_takes_turns_between_members() {
+        let (alice, bob, carol) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let rotation = [alice, bob, carol];
+
+        assert_eq!(next_in_rotation(&rotation, &rotation, None), Some(alice));
+        assert_eq!(next_in_rotation(&rotation, &rotation, Some(alice)), Some(bob));
+        assert_eq!(next_in_rotation(&rotation, &rotation, Some(carol)), Some(alice));
+        // Bob left the project
+        assert_eq!(next_in_rotation(&rotation, &[alice, carol], Some(alice)), Some(carol));
+        assert_eq!(next_in_rotation(&rotation, &[], Some(alice)), None);
+    }
+
+    #[test]
+    fn test_plan_skips_what_is_done() {
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let rule = rule(vec![
+            AutomationAction::AssignRoundRobin { user_ids: vec![alice, bob] },
+            AutomationAction::AddLabel { label: " needs-review ".to_string() },
+            AutomationAction::SetPriority { priority: TaskPriority::High },
+            AutomationAction::PostSystemComment { content: "Ready for QA".to_string() },
+        ]);
+
+        let plan = plan(&rule, &task(None, &["ui"]), &[alice, bob], Some(alice), true);
+        assert_eq!(
+            plan,
+            RulePlan {
+                assign_to: Some(bob),
+                add_labels: vec!["needs-review".to_string()],
+                priority: Some(TaskPriority::High),
+                comment: Some("Ready for QA".to_string()),
+            }
+        );
+
+        // Moved back in later: already with the rotation, labelled and High
+        let mut again = task(Some(bob), &["Needs-Review"]);
+        again.priority = TaskPriority::High;
+        assert!(super::plan(&rule, &again, &[alice, bob], Some(bob), false).is_empty());
+
+        // Assignees outside the rotation are replaced
+        let outsider = Uuid::new_v4();
+        let handed_over = task(Some(outsider), &["needs-review"]);
+        assert_eq!(super::plan(&rule, &handed_over, &[alice, bob], Some(bob), false).assign_to, Some(alice));
+    }
+
+    #[test]
+    fn test_labels_stop_at_the_tag_limit() {
+        let tags: Vec<String> = (0..MAX_TASK_TAGS).map(|i| format!("tag{}", i)).collect();
+        let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+        let rule = rule(vec![AutomationAction::AddLabel { label: "needs-review".to_string() }]);
+
+        assert!(plan(&rule, &task(None, &tags), &[], None, true).is_empty());
+    }
+
+    #[test]
+    fn test_history_comment() {
+        let rule = rule(Vec::new());
+        let plan = RulePlan {
+            assign_to: Some(Uuid::new_v4()),
+            add_labels: vec!["needs-review".to_string()],
+            priority: Some(TaskPriority::High),
+            comment: None,
+        };
+        assert_eq!(
+            history_comment(&rule, &plan, Some("Alice")),
+            "Automation \"QA handoff\": assigned to Alice, added label \"needs-review\", set priority to High."
+        );
+
+        let plan = RulePlan { comment: Some("Ready for QA".to_string()), ..RulePlan::default() };
+        assert_eq!(history_comment(&rule, &plan, None), "Ready for QA\n\nAutomation \"QA handoff\" ran.");
+    }
+}
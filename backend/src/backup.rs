@@ -0,0 +1,526 @@
+// Instance and team backups as NDJSON: a header line, one line per record in
+// dependency order (users before the teams they created, and so on) and an end
+// line with per-section counts so truncated archives are detected on import.
+//
+// Records are exported with `to_jsonb(row)` and restored with
+// `jsonb_populate_record`, so an archive can only be imported by the same
+// format version. Imports give every record a new id; users are matched to
+// existing accounts by email.
+
+use axum::{
+    body::{Body, Bytes},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures_util::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+use crate::auth::password;
+use crate::utils::errors::AppError;
+
+pub const FORMAT: &str = "simplecards-export";
+pub const FORMAT_VERSION: u32 = 1;
+// Rows fetched per query while exporting, which bounds memory per request
+const BATCH_SIZE: i64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Section {
+    Users,
+    Teams,
+    TeamMembers,
+    Projects,
+    ProjectMembers,
+    Boards,
+    Tasks,
+    TaskComments,
+}
+
+impl Section {
+    // Export and import order; every section only references earlier ones
+    pub const ALL: [Section; 8] = [
+        Section::Users,
+        Section::Teams,
+        Section::TeamMembers,
+        Section::Projects,
+        Section::ProjectMembers,
+        Section::Boards,
+        Section::Tasks,
+        Section::TaskComments,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Section::Users => "users",
+            Section::Teams => "teams",
+            Section::TeamMembers => "team_members",
+            Section::Projects => "projects",
+            Section::ProjectMembers => "project_members",
+            Section::Boards => "boards",
+            Section::Tasks => "tasks",
+            Section::TaskComments => "task_comments",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Section> {
+        Section::ALL.into_iter().find(|section| section.name() == name)
+    }
+
+    // Sections are named after their tables
+    fn table(&self) -> &'static str {
+        self.name()
+    }
+
+    // Columns holding ids of other exported records, remapped on import
+    fn references(&self) -> &'static [(&'static str, Section)] {
+        match self {
+            Section::Users => &[],
+            Section::Teams => &[("created_by", Section::Users)],
+            Section::TeamMembers => &[("team_id", Section::Teams), ("user_id", Section::Users)],
+            Section::Projects => &[("team_id", Section::Teams), ("created_by", Section::Users)],
+            Section::ProjectMembers => &[("project_id", Section::Projects), ("user_id", Section::Users)],
+            Section::Boards => &[("project_id", Section::Projects), ("created_by", Section::Users)],
+            Section::Tasks => &[
+                ("project_id", Section::Projects),
+                ("created_by", Section::Users),
+                ("assigned_to", Section::Users),
+            ],
+            Section::TaskComments => &[("task_id", Section::Tasks), ("user_id", Section::Users)],
+        }
+    }
+
+    // Rows of `t` belonging to team $1. Users are everyone a team record points at,
+    // so the archive imports without dangling references.
+    fn team_filter(&self) -> &'static str {
+        match self {
+            Section::Users => {
+                "t.id IN (
+                    SELECT created_by FROM teams WHERE id = $1
+                    UNION SELECT user_id FROM team_members WHERE team_id = $1
+                    UNION SELECT created_by FROM projects WHERE team_id = $1
+                    UNION SELECT pm.user_id FROM project_members pm JOIN projects p ON p.id = pm.project_id WHERE p.team_id = $1
+                    UNION SELECT b.created_by FROM boards b JOIN projects p ON p.id = b.project_id WHERE p.team_id = $1
+                    UNION SELECT x.created_by FROM tasks x JOIN projects p ON p.id = x.project_id WHERE p.team_id = $1
+                    UNION SELECT x.assigned_to FROM tasks x JOIN projects p ON p.id = x.project_id WHERE p.team_id = $1
+                    UNION SELECT c.user_id FROM task_comments c JOIN tasks x ON x.id = c.task_id
+                        JOIN projects p ON p.id = x.project_id WHERE p.team_id = $1
+                )"
+            }
+            Section::Teams => "t.id = $1",
+            Section::TeamMembers | Section::Projects => "t.team_id = $1",
+            Section::ProjectMembers | Section::Boards | Section::Tasks => {
+                "t.project_id IN (SELECT id FROM projects WHERE team_id = $1)"
+            }
+            Section::TaskComments => {
+                "t.task_id IN (SELECT x.id FROM tasks x JOIN projects p ON p.id = x.project_id WHERE p.team_id = $1)"
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExportOptions {
+    // None exports the whole instance
+    pub team_id: Option<Uuid>,
+    pub include_password_hashes: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+    exported_at: chrono::DateTime<chrono::Utc>,
+    team_id: Option<Uuid>,
+    includes_password_hashes: bool,
+}
+
+enum Stage {
+    Header,
+    Records { section: usize, after: Option<Uuid> },
+    Done,
+}
+
+struct ExportState {
+    pool: PgPool,
+    options: ExportOptions,
+    stage: Stage,
+    counts: BTreeMap<&'static str, u64>,
+}
+
+fn line(kind: &str, value: impl Serialize) -> String {
+    let mut line = json!({ "type": kind, "data": value }).to_string();
+    line.push('\n');
+    line
+}
+
+// One batch of a section after `after`, ordered by id
+async fn fetch_batch(
+    pool: &PgPool,
+    section: Section,
+    options: &ExportOptions,
+    after: Option<Uuid>,
+) -> Result<Vec<(Uuid, Value)>, AppError> {
+    let strip = if section == Section::Users && !options.include_password_hashes {
+        " - 'password_hash'"
+    } else {
+        ""
+    };
+    let sql = format!(
+        "SELECT t.id, to_jsonb(t){strip} AS data FROM {table} t
+         WHERE ($1::uuid IS NULL OR {filter})
+           AND ($2::uuid IS NULL OR t.id > $2)
+         ORDER BY t.id
+         LIMIT $3",
+        table = section.table(),
+        filter = section.team_filter(),
+    );
+
+    let rows = sqlx::query(&sql)
+        .bind(options.team_id)
+        .bind(after)
+        .bind(BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.into_iter().map(|row| (row.get("id"), row.get("data"))).collect())
+}
+
+// Next chunk of the archive: the header, a batch of records or the end line
+async fn next_chunk(mut state: ExportState) -> Result<Option<(Bytes, ExportState)>, AppError> {
+    loop {
+        match state.stage {
+            Stage::Header => {
+                let header = Header {
+                    format: FORMAT.to_string(),
+                    version: FORMAT_VERSION,
+                    exported_at: chrono::Utc::now(),
+                    team_id: state.options.team_id,
+                    includes_password_hashes: state.options.include_password_hashes,
+                };
+                state.stage = Stage::Records { section: 0, after: None };
+                return Ok(Some((Bytes::from(line("header", header)), state)));
+            }
+            Stage::Records { section, .. } if section >= Section::ALL.len() => {
+                let chunk = line("end", &state.counts);
+                state.stage = Stage::Done;
+                return Ok(Some((Bytes::from(chunk), state)));
+            }
+            Stage::Records { section, after } => {
+                let current = Section::ALL[section];
+                let batch = fetch_batch(&state.pool, current, &state.options, after).await?;
+                let Some((last_id, _)) = batch.last() else {
+                    state.stage = Stage::Records { section: section + 1, after: None };
+                    continue;
+                };
+
+                state.stage = Stage::Records { section, after: Some(*last_id) };
+                *state.counts.entry(current.name()).or_default() += batch.len() as u64;
+                let chunk: String = batch.iter().map(|(_, data)| line(current.name(), data)).collect();
+                return Ok(Some((Bytes::from(chunk), state)));
+            }
+            Stage::Done => return Ok(None),
+        }
+    }
+}
+
+// Streams the archive table by table, one batch in memory at a time
+pub fn export(pool: PgPool, options: ExportOptions) -> impl Stream<Item = Result<Bytes, AppError>> {
+    let state = ExportState {
+        pool,
+        options,
+        stage: Stage::Header,
+        counts: BTreeMap::new(),
+    };
+    stream::try_unfold(state, next_chunk)
+}
+
+// Streamed download of an archive
+pub fn export_response(pool: PgPool, options: ExportOptions) -> Response {
+    let filename = match options.team_id {
+        Some(team_id) => format!("simplecards-team-{}-{}.ndjson", team_id, chrono::Utc::now().format("%Y%m%d")),
+        None => format!("simplecards-export-{}.ndjson", chrono::Utc::now().format("%Y%m%d")),
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(export(pool, options)),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    // Records created (or that would be created) per section
+    pub created: BTreeMap<&'static str, u64>,
+    // Archived users that already have an account with the same email
+    pub matched_users: u64,
+}
+
+#[derive(Debug, Default)]
+struct Archive {
+    records: BTreeMap<Section, Vec<Value>>,
+}
+
+fn parse_archive(body: &[u8]) -> Result<Archive, AppError> {
+    let text = std::str::from_utf8(body).map_err(|_| AppError::BadRequest("Archive is not UTF-8".to_string()))?;
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty()).enumerate();
+    let invalid = |number: usize, message: &str| AppError::BadRequest(format!("Line {}: {}", number + 1, message));
+
+    let (_, first) = lines.next().ok_or_else(|| AppError::BadRequest("Archive is empty".to_string()))?;
+    let first: Value = serde_json::from_str(first).map_err(|_| invalid(0, "malformed JSON"))?;
+    if first["type"] != "header" {
+        return Err(invalid(0, "expected the archive header"));
+    }
+    let header: Header = serde_json::from_value(first["data"].clone()).map_err(|_| invalid(0, "malformed header"))?;
+    if header.format != FORMAT || header.version != FORMAT_VERSION {
+        return Err(AppError::Validation(format!(
+            "Unsupported archive {} v{}, expected {} v{}",
+            header.format, header.version, FORMAT, FORMAT_VERSION
+        )));
+    }
+
+    let mut archive = Archive::default();
+    let mut end = None;
+    for (number, text) in lines {
+        if end.is_some() {
+            return Err(invalid(number, "records after the end of the archive"));
+        }
+        let mut value: Value = serde_json::from_str(text).map_err(|_| invalid(number, "malformed JSON"))?;
+        let kind = value["type"].as_str().unwrap_or_default().to_string();
+        let data = value["data"].take();
+
+        if kind == "end" {
+            end = Some(data);
+            continue;
+        }
+        let section = Section::from_name(&kind).ok_or_else(|| invalid(number, "unknown record type"))?;
+        if !data.is_object() || parse_id(&data["id"]).is_none() {
+            return Err(invalid(number, "record has no id"));
+        }
+        archive.records.entry(section).or_default().push(data);
+    }
+
+    let end = end.ok_or_else(|| AppError::BadRequest("Archive is truncated".to_string()))?;
+    for section in Section::ALL {
+        let expected = end[section.name()].as_u64().unwrap_or(0);
+        let found = archive.records.get(&section).map_or(0, |records| records.len() as u64);
+        if expected != found {
+            return Err(AppError::BadRequest(format!(
+                "Archive is truncated: expected {} {}, found {}",
+                expected,
+                section.name(),
+                found
+            )));
+        }
+    }
+
+    Ok(archive)
+}
+
+fn parse_id(value: &Value) -> Option<Uuid> {
+    value.as_str().and_then(|id| Uuid::parse_str(id).ok())
+}
+
+// New ids for every record, with references rewritten through the earlier sections' maps
+fn remap(
+    archive: &mut Archive,
+    id_maps: &mut HashMap<Section, HashMap<Uuid, Uuid>>,
+) -> Result<(), AppError> {
+    for section in Section::ALL {
+        let Some(records) = archive.records.get_mut(&section) else {
+            continue;
+        };
+
+        for record in records.iter_mut() {
+            let old_id = parse_id(&record["id"]).unwrap_or_default();
+            let new_id = *id_maps.entry(section).or_default().entry(old_id).or_insert_with(Uuid::new_v4);
+            record["id"] = json!(new_id);
+
+            for (column, target) in section.references() {
+                let Some(old) = parse_id(&record[*column]) else {
+                    continue; // nullable reference such as an unassigned task
+                };
+                let new = id_maps.get(target).and_then(|ids| ids.get(&old)).ok_or_else(|| {
+                    AppError::Validation(format!(
+                        "{} record {} references a missing {} record {}",
+                        section.name(),
+                        old_id,
+                        target.name(),
+                        old
+                    ))
+                })?;
+                record[*column] = json!(new);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Restores an archive into an instance without teams. Each section is written in
+// its own transaction; a dry run only validates and reports.
+pub async fn import(pool: &PgPool, body: &[u8], dry_run: bool) -> Result<ImportReport, AppError> {
+    let mut archive = parse_archive(body)?;
+
+    let has_teams: bool = sqlx::query("SELECT EXISTS(SELECT 1 FROM teams)")
+        .fetch_one(pool)
+        .await?
+        .get(0);
+    if has_teams {
+        return Err(AppError::Conflict("Imports need an instance without teams".to_string()));
+    }
+
+    let mut report = ImportReport { dry_run, ..Default::default() };
+    let mut id_maps: HashMap<Section, HashMap<Uuid, Uuid>> = HashMap::new();
+
+    // Users who already have an account keep it
+    let users = archive.records.remove(&Section::Users).unwrap_or_default();
+    let mut new_users = Vec::new();
+    for user in users {
+        let old_id = parse_id(&user["id"]).unwrap_or_default();
+        let email = user["email"].as_str().unwrap_or_default();
+        let existing = sqlx::query("SELECT id FROM users WHERE LOWER(email) = LOWER($1)")
+            .bind(email)
+            .fetch_optional(pool)
+            .await?;
+
+        match existing {
+            Some(row) => {
+                id_maps.entry(Section::Users).or_default().insert(old_id, row.get("id"));
+                report.matched_users += 1;
+            }
+            None => new_users.push(user),
+        }
+    }
+    archive.records.insert(Section::Users, new_users);
+
+    remap(&mut archive, &mut id_maps)?;
+
+    for section in Section::ALL {
+        let records = archive.records.remove(&section).unwrap_or_default();
+        report.created.insert(section.name(), records.len() as u64);
+        if dry_run || records.is_empty() {
+            continue;
+        }
+        insert_section(pool, section, records).await?;
+    }
+
+    Ok(report)
+}
+
+async fn insert_section(pool: &PgPool, section: Section, records: Vec<Value>) -> Result<(), AppError> {
+    let sql = format!(
+        "INSERT INTO {table} SELECT * FROM jsonb_populate_record(NULL::{table}, $1)",
+        table = section.table()
+    );
+    let mut tx = pool.begin().await?;
+    let mut ids = Vec::with_capacity(records.len());
+
+    for mut record in records {
+        let id = parse_id(&record["id"]).unwrap_or_default();
+        ids.push(id);
+
+        // Archives without password hashes restore accounts that need a password reset
+        if section == Section::Users && record["password_hash"].as_str().is_none() {
+            record["password_hash"] = json!(password::hash_password(&Uuid::new_v4().to_string())?);
+        }
+
+        sqlx::query(&sql).bind(&record).execute(&mut *tx).await?;
+
+        // The insert trigger stamps assignments with the current time
+        if section == Section::Tasks {
+            sqlx::query("UPDATE tasks SET assigned_at = ($2::jsonb ->> 'assigned_at')::timestamptz WHERE id = $1")
+                .bind(id)
+                .bind(&record)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    // New projects get a default board from a trigger; the archive has its own boards
+    if section == Section::Projects {
+        sqlx::query("DELETE FROM boards WHERE project_id = ANY($1)")
+            .bind(&ids)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(lines: &[Value]) -> Vec<u8> {
+        lines.iter().map(|line| format!("{}\n", line)).collect::<String>().into_bytes()
+    }
+
+    fn header() -> Value {
+        json!({ "type": "header", "data": {
+            "format": FORMAT,
+            "version": FORMAT_VERSION,
+            "exported_at": "2024-01-01T00:00:00Z",
+            "team_id": null,
+            "includes_password_hashes": false
+        }})
+    }
+
+    #[test]
+    fn test_sections_only_reference_earlier_sections() {
+        for (index, section) in Section::ALL.iter().enumerate() {
+            for (_, target) in section.references() {
+                assert!(Section::ALL[..index].contains(target), "{:?} references {:?}", section, target);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_requires_complete_archive() {
+        let user = json!({ "type": "users", "data": { "id": Uuid::new_v4(), "email": "a@example.com" } });
+
+        let complete = archive(&[header(), user.clone(), json!({ "type": "end", "data": { "users": 1 } })]);
+        assert_eq!(parse_archive(&complete).unwrap().records[&Section::Users].len(), 1);
+
+        let truncated = archive(&[header(), user.clone()]);
+        assert!(parse_archive(&truncated).is_err());
+
+        let short = archive(&[header(), user, json!({ "type": "end", "data": { "users": 2 } })]);
+        assert!(parse_archive(&short).is_err());
+
+        let mut wrong_version = header();
+        wrong_version["data"]["version"] = json!(FORMAT_VERSION + 1);
+        assert!(parse_archive(&archive(&[wrong_version, json!({ "type": "end", "data": {} })])).is_err());
+    }
+
+    #[test]
+    fn test_remap_rewrites_references() {
+        let (user, team, stranger) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut archive = Archive::default();
+        archive.records.insert(Section::Users, vec![json!({ "id": user })]);
+        archive.records.insert(Section::Teams, vec![json!({ "id": team, "created_by": user })]);
+
+        let mut id_maps = HashMap::new();
+        remap(&mut archive, &mut id_maps).unwrap();
+
+        let new_user = archive.records[&Section::Users][0]["id"].clone();
+        let new_team = &archive.records[&Section::Teams][0];
+        assert_ne!(new_user, json!(user));
+        assert_ne!(new_team["id"], json!(team));
+        assert_eq!(new_team["created_by"], new_user);
+
+        // References to records outside the archive are rejected
+        let mut archive = Archive::default();
+        archive.records.insert(Section::Teams, vec![json!({ "id": team, "created_by": stranger })]);
+        assert!(remap(&mut archive, &mut HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+// Demo data for local development: `cargo run --bin seed`
+//
+// Every record is keyed by a fixed UUID, so re-running the seed updates the
+// existing rows instead of creating duplicates. Refuses to run in production.
+
+use chrono::{Duration, Utc};
+use tracing::{info, Level};
+use uuid::Uuid;
+
+use simplecards::auth::password;
+use simplecards::config::Config;
+use simplecards::database::{
+    connection::Database,
+    models::{
+        CreateBoardRequest, CreateProjectRequest, CreateTaskCommentRequest, CreateTaskRequest,
+        CreateTeamRequest, CreateUserRequest, DueDate, ProjectRole, TaskPriority, TaskStatus, TeamRole, User,
+    },
+    queries::{BoardQueries, ProjectQueries, TaskCommentQueries, TaskQueries, TeamQueries, UserQueries},
+};
+
+const DEMO_PASSWORD: &str = "Password123!";
+const TASKS_PER_PROJECT: u128 = 70;
+
+// Fixed id ranges per entity type
+const USER_BASE: u128 = 0x5eed_0001_0000_0000_0000_0000_0000_0000;
+const TEAM_BASE: u128 = 0x5eed_0002_0000_0000_0000_0000_0000_0000;
+const PROJECT_BASE: u128 = 0x5eed_0003_0000_0000_0000_0000_0000_0000;
+const TASK_BASE: u128 = 0x5eed_0004_0000_0000_0000_0000_0000_0000;
+const COMMENT_BASE: u128 = 0x5eed_0005_0000_0000_0000_0000_0000_0000;
+
+const USERS: &[(&str, &str)] = &[
+    ("alice", "Alice Admin"),
+    ("bob", "Bob Builder"),
+    ("carol", "Carol Coder"),
+    ("dave", "Dave Designer"),
+    ("erin", "Erin Editor"),
+];
+
+const PROJECTS: &[(&str, &str, &str)] = &[
+    ("Website Relaunch", "New marketing site and blog", "#3B82F6"),
+    ("Mobile App", "iOS and Android clients", "#10B981"),
+    ("Infrastructure", "Servers, CI and monitoring", "#F59E0B"),
+];
+
+const TASK_TITLES: &[&str] = &[
+    "Draft copy for", "Review designs for", "Implement", "Write tests for",
+    "Fix bugs in", "Document", "Deploy", "Measure performance of",
+];
+
+const TASK_SUBJECTS: &[&str] = &[
+    "landing page", "login flow", "settings screen", "notification center",
+    "search", "billing", "onboarding", "dashboard", "API client", "release pipeline",
+];
+
+const TAGS: &[&str] = &["frontend", "backend", "design", "bug", "docs", "ops"];
+
+const COMMENTS: &[&str] = &[
+    "I can pick this up tomorrow.",
+    "Blocked on the API changes, see the linked task.",
+    "Looks good to me, merging.",
+    "Can we split this into two smaller tasks?",
+];
+
+fn seed_id(base: u128, index: u128) -> Uuid {
+    Uuid::from_u128(base + index)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_max_level(Level::INFO)
+        .with_target(false)
+        .compact()
+        .init();
+
+    dotenvy::dotenv().ok();
+
+    let config = Config::from_env()?;
+    if config.is_production() {
+        return Err("Refusing to seed demo data when APP_ENV=production".into());
+    }
+
+    let database = Database::new(&config.database).await?;
+    let pool = database.pool();
+
+    // Users
+    let password_hash = password::hash_password(DEMO_PASSWORD)?;
+    let mut users: Vec<User> = Vec::new();
+    for (index, (username, display_name)) in USERS.iter().enumerate() {
+        let request = CreateUserRequest {
+            email: format!("{}@demo.simplecards.dev", username),
+            username: username.to_string(),
+            display_name: display_name.to_string(),
+            password: DEMO_PASSWORD.to_string(),
+            signup_code: None,
+            invitation: None,
+        };
+        users.push(UserQueries::upsert_user(pool, seed_id(USER_BASE, index as u128), &request, &password_hash).await?);
+    }
+    let owner = &users[0];
+    UserQueries::grant_instance_admin(pool, owner.id).await?;
+    info!("Seeded {} users (password: {})", users.len(), DEMO_PASSWORD);
+
+    // Team
+    let team = TeamQueries::upsert_team(
+        pool,
+        seed_id(TEAM_BASE, 0),
+        &CreateTeamRequest {
+            name: "Demo Team".to_string(),
+            description: Some("Sample data created by the seed command".to_string()),
+        },
+        owner.id,
+    ).await?;
+
+    for (index, user) in users.iter().enumerate() {
+        let role = if index == 0 { TeamRole::Admin } else { TeamRole::Member };
+        match TeamQueries::get_user_team_role(pool, team.id, user.id).await? {
+            Some(_) => { TeamQueries::update_team_member_role(pool, team.id, user.id, role).await?; }
+            None => { TeamQueries::add_team_member(pool, team.id, user.id, role).await?; }
+        }
+    }
+    info!("Seeded team {}", team.name);
+
+    // Projects, boards, tasks and comments
+    let mut task_count = 0;
+    let mut comment_count = 0;
+    for (project_index, (name, description, color)) in PROJECTS.iter().enumerate() {
+        let project = ProjectQueries::upsert_project(
+            pool,
+            seed_id(PROJECT_BASE, project_index as u128),
+            &CreateProjectRequest {
+                name: name.to_string(),
+                description: Some(description.to_string()),
+                team_id: team.id,
+                color: Some(color.to_string()),
+                icon: None,
+                auto_add_members: false,
+                template_id: None,
+            },
+            owner.id,
+        ).await?;
+
+        for (index, user) in users.iter().enumerate() {
+            let role = match index {
+                0 => ProjectRole::Admin,
+                4 => ProjectRole::Guest,
+                _ => ProjectRole::Editor,
+            };
+            match ProjectQueries::get_user_project_role(pool, project.id, user.id).await? {
+                Some(_) => { ProjectQueries::update_project_member_role(pool, project.id, user.id, role).await?; }
+                None => { ProjectQueries::add_project_member(pool, project.id, user.id, role).await?; }
+            }
+        }
+
+        // The default board comes from the project trigger; add one custom board
+        let boards = BoardQueries::get_project_boards(pool, project.id).await?;
+        if !boards.iter().any(|board| board.name == "Sprint Board") {
+            BoardQueries::create_board(
+                pool,
+                project.id,
+                &CreateBoardRequest {
+                    name: "Sprint Board".to_string(),
+                    description: Some("Current sprint".to_string()),
+                    columns: Some(vec!["Backlog".to_string(), "Doing".to_string(), "Done".to_string()]),
+                    config: None,
+                    swimlane_config: None,
+                },
+                owner.id,
+            ).await?;
+        }
+
+        for index in 0..TASKS_PER_PROJECT {
+            let n = index as usize;
+            let task_index = project_index as u128 * TASKS_PER_PROJECT + index;
+            let status = match n % 4 {
+                0 => TaskStatus::Todo,
+                1 => TaskStatus::InProgress,
+                2 => TaskStatus::Review,
+                _ => TaskStatus::Done,
+            };
+            let priority = match n % 5 {
+                0 => TaskPriority::Low,
+                1 | 2 => TaskPriority::Medium,
+                3 => TaskPriority::High,
+                _ => TaskPriority::Critical,
+            };
+            let request = CreateTaskRequest {
+                title: format!("{} {}", TASK_TITLES[n % TASK_TITLES.len()], TASK_SUBJECTS[n % TASK_SUBJECTS.len()]),
+                description: Some(format!("Seeded task #{} for {}", index + 1, project.name)),
+                assigned_to: if n.is_multiple_of(6) { None } else { Some(users[n % users.len()].id) },
+                priority: Some(priority),
+                due_date: if n.is_multiple_of(3) { None } else { Some(DueDate::At(Utc::now() + Duration::days(n as i64 % 30 - 7))) },
+                tags: Some(vec![TAGS[n % TAGS.len()].to_string(), TAGS[(n / 2) % TAGS.len()].to_string()]),
+                cover_color: None,
+                cover_emoji: None,
+            };
+
+            let task = TaskQueries::upsert_task(
+                pool,
+                seed_id(TASK_BASE, task_index),
+                project.id,
+                &request,
+                status,
+                // Statuses rotate, so each column gets every fourth task
+                (index / 4) as i32 + 1,
+                owner.id,
+            ).await?;
+            task_count += 1;
+
+            // Comment threads on every fifth task
+            if n.is_multiple_of(5) {
+                for (comment_index, content) in COMMENTS.iter().enumerate() {
+                    TaskCommentQueries::upsert_comment(
+                        pool,
+                        seed_id(COMMENT_BASE, task_index * COMMENTS.len() as u128 + comment_index as u128),
+                        task.id,
+                        users[(n + comment_index) % users.len()].id,
+                        &CreateTaskCommentRequest { content: content.to_string() },
+                    ).await?;
+                    comment_count += 1;
+                }
+            }
+        }
+
+        info!("Seeded project {}", project.name);
+    }
+
+    info!("Seed complete: {} tasks, {} comments", task_count, comment_count);
+    Ok(())
+}
@@ -0,0 +1,277 @@
+// Instance administration from the shell, for when the web UI can't be used:
+// `cargo run --bin simplecards-admin -- <command> [--json] [--yes]`
+//
+// Commands go through the same query layer as the API. Commands that change
+// data refuse to run without --yes.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use simplecards::auth::password;
+use simplecards::config::Config;
+use simplecards::database::{
+    connection::Database,
+    models::User,
+    queries::{DashboardQueries, TeamQueries, UserQueries},
+};
+use simplecards::maintenance::{self, MaintenanceReport};
+use simplecards::utils::{errors::AppError, validation};
+
+const USAGE: &str = "\
+Usage: simplecards-admin <command> [--json] [--yes]
+
+Commands:
+  user reset-password <email>   Set a new random password and print it
+  user deactivate <email>       Deactivate the account
+  team list                     List all teams
+  maintenance check             Run the consistency checks
+  maintenance repair            Repair what the consistency checks find
+  stats                         Show instance-wide totals
+
+Options:
+  --json   Print machine-readable JSON
+  --yes    Confirm a command that changes data";
+
+const GENERATED_PASSWORD_LENGTH: usize = 20;
+const PASSWORD_CHARACTERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz23456789!@#%*-_";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    ResetPassword { email: String },
+    Deactivate { email: String },
+    ListTeams,
+    Check,
+    Repair,
+    Stats,
+}
+
+impl Command {
+    fn is_destructive(&self) -> bool {
+        matches!(self, Command::ResetPassword { .. } | Command::Deactivate { .. } | Command::Repair)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    command: Command,
+    json: bool,
+    yes: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut json = false;
+    let mut yes = false;
+    let mut words = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--yes" | "-y" => yes = true,
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            flag if flag.starts_with('-') => return Err(format!("Unknown option {}\n\n{}", flag, USAGE)),
+            _ => words.push(arg),
+        }
+    }
+
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let command = match words.as_slice() {
+        ["user", "reset-password", email] => Command::ResetPassword { email: email.to_string() },
+        ["user", "deactivate", email] => Command::Deactivate { email: email.to_string() },
+        ["team", "list"] => Command::ListTeams,
+        ["maintenance", "check"] => Command::Check,
+        ["maintenance", "repair"] => Command::Repair,
+        ["stats"] => Command::Stats,
+        _ => return Err(USAGE.to_string()),
+    };
+
+    Ok(Options { command, json, yes })
+}
+
+// Random, and always passes the password rules so the user can keep it for now
+fn generate_password() -> String {
+    loop {
+        let candidate: String = (0..GENERATED_PASSWORD_LENGTH)
+            .map(|_| PASSWORD_CHARACTERS[OsRng.next_u32() as usize % PASSWORD_CHARACTERS.len()] as char)
+            .collect();
+        if validation::validate_password(&candidate).is_ok() {
+            return candidate;
+        }
+    }
+}
+
+async fn find_user(pool: &PgPool, email: &str) -> Result<User, Box<dyn std::error::Error>> {
+    match UserQueries::get_user_by_email(pool, email).await {
+        Err(AppError::NotFound(_)) => Err(format!("No active user with email {}", email).into()),
+        result => Ok(result?),
+    }
+}
+
+fn print_json(value: &impl Serialize) -> Result<(), Box<dyn std::error::Error>> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn print_report(report: &MaintenanceReport) {
+    for check in &report.checks {
+        println!("{:?}: {} issue(s)", check.check, check.issues.len());
+        for issue in &check.issues {
+            match (issue.project_id, issue.subject_id) {
+                (Some(project_id), _) => println!("  project {}: {}", project_id, issue.detail),
+                (None, Some(team_id)) => println!("  team {}: {}", team_id, issue.detail),
+                (None, None) => println!("  {}", issue.detail),
+            }
+        }
+    }
+    match report.dry_run {
+        Some(_) => println!("Repaired {} issue(s)", report.issue_count),
+        None => println!("{} issue(s) found", report.issue_count),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    if options.command.is_destructive() && !options.yes {
+        eprintln!("This command changes data; run it again with --yes to confirm");
+        std::process::exit(2);
+    }
+
+    // Logs go to stderr so that --json output stays parseable
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .compact()
+        .init();
+
+    dotenvy::dotenv().ok();
+
+    if let Err(error) = run(options).await {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+async fn run(options: Options) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::from_env()?;
+    let database = Database::new(&config.database).await?;
+    let pool = database.pool();
+
+    match options.command {
+        Command::ResetPassword { email } => {
+            let user = find_user(pool, &email).await?;
+            let new_password = generate_password();
+            let password_hash = password::hash_password(&new_password)?;
+            UserQueries::update_password_hash(pool, user.id, &password_hash).await?;
+
+            if options.json {
+                print_json(&serde_json::json!({ "user_id": user.id, "email": user.email, "password": new_password }))?;
+            } else {
+                println!("New password for {}: {}", user.email, new_password);
+            }
+        }
+        Command::Deactivate { email } => {
+            let user = find_user(pool, &email).await?;
+            UserQueries::deactivate_user(pool, user.id).await?;
+
+            if options.json {
+                print_json(&serde_json::json!({ "user_id": user.id, "email": user.email, "is_active": false }))?;
+            } else {
+                println!("Deactivated {} ({})", user.email, user.id);
+            }
+        }
+        Command::ListTeams => {
+            let teams = TeamQueries::list_teams(pool).await?;
+
+            if options.json {
+                print_json(&teams)?;
+            } else {
+                for team in &teams {
+                    println!("{}  {}  ({} projects)", team.team.id, team.team.name, team.project_count);
+                }
+            }
+        }
+        Command::Check => {
+            let report = maintenance::check(pool, &config.attachments, None).await?;
+
+            if options.json {
+                print_json(&report)?;
+            } else {
+                print_report(&report);
+            }
+        }
+        Command::Repair => {
+            let report = maintenance::repair(pool, &config.attachments, None, false).await?;
+
+            if options.json {
+                print_json(&report)?;
+            } else {
+                print_report(&report);
+            }
+        }
+        Command::Stats => {
+            let stats = DashboardQueries::get_instance_stats(pool).await?;
+
+            if options.json {
+                print_json(&stats)?;
+            } else {
+                println!("Users:     {} ({} active, {} instance admins)", stats.users, stats.active_users, stats.instance_admins);
+                println!("Teams:     {}", stats.teams);
+                println!("Projects:  {} ({} archived)", stats.projects, stats.archived_projects);
+                println!("Tasks:     {} ({} open)", stats.tasks, stats.open_tasks);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parses_commands_and_flags() {
+        assert_eq!(
+            parse(&["user", "reset-password", "ops@example.com", "--yes"]),
+            Ok(Options {
+                command: Command::ResetPassword { email: "ops@example.com".to_string() },
+                json: false,
+                yes: true,
+            })
+        );
+        assert_eq!(
+            parse(&["--json", "team", "list"]),
+            Ok(Options { command: Command::ListTeams, json: true, yes: false })
+        );
+        assert!(parse(&["user", "deactivate"]).is_err());
+        assert!(parse(&["stats", "--force"]).is_err());
+        assert!(parse(&[]).is_err());
+    }
+
+    #[test]
+    fn test_only_commands_that_change_data_need_confirmation() {
+        assert!(Command::Repair.is_destructive());
+        assert!(Command::Deactivate { email: String::new() }.is_destructive());
+        assert!(!Command::Check.is_destructive());
+        assert!(!Command::Stats.is_destructive());
+    }
+
+    #[test]
+    fn test_generated_passwords_pass_validation() {
+        for _ in 0..20 {
+            let generated = generate_password();
+            assert_eq!(generated.len(), GENERATED_PASSWORD_LENGTH);
+            assert!(validation::validate_password(&generated).is_ok());
+        }
+    }
+}
@@ -0,0 +1,264 @@
+// Board setups as human-editable YAML, for sharing them between projects and
+// instances: name, columns, WIP limits and swimlanes, but no tasks. The format
+// mirrors the board JSON fields, so validation errors on import use the same
+// paths (e.g. config.wip_limits[1]). Unknown keys are rejected so typos don't
+// go unnoticed.
+
+use serde::{Deserialize, Serialize};
+
+use crate::database::models::{
+    Board, BoardConfig, ColumnWipLimit, CreateBoardRequest, SwimlaneConfig, SwimlaneGroupBy, SwimlaneLane, TaskStatus,
+    WipMode,
+};
+use crate::utils::errors::{AppError, FieldError};
+use crate::utils::i18n::Message;
+
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoardExport {
+    pub version: u32,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub config: ConfigExport,
+    #[serde(default)]
+    pub swimlane_config: SwimlaneConfigExport,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigExport {
+    #[serde(default)]
+    pub wip_mode: WipMode,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wip_limits: Vec<WipLimitExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WipLimitExport {
+    pub column_id: TaskStatus,
+    pub limit: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SwimlaneConfigExport {
+    #[serde(default)]
+    pub group_by: SwimlaneGroupBy,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lanes: Vec<LaneExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LaneExport {
+    pub key: Option<String>,
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+impl BoardExport {
+    // Assignee lanes are keyed by user ids that mean nothing elsewhere, so of
+    // those only the lane for unassigned tasks is kept
+    pub fn from_board(board: &Board) -> Self {
+        let lanes = board
+            .swimlane_config
+            .lanes
+            .iter()
+            .filter(|lane| board.swimlane_config.group_by != SwimlaneGroupBy::Assignee || lane.key.is_none())
+            .map(|lane| LaneExport { key: lane.key.clone(), collapsed: lane.collapsed })
+            .collect();
+
+        BoardExport {
+            version: FORMAT_VERSION,
+            name: board.name.clone(),
+            description: board.description.clone(),
+            columns: board.columns.clone(),
+            config: ConfigExport {
+                wip_mode: board.config.wip_mode,
+                wip_limits: board
+                    .config
+                    .wip_limits
+                    .iter()
+                    .map(|limit| WipLimitExport { column_id: limit.column_id, limit: limit.limit })
+                    .collect(),
+            },
+            swimlane_config: SwimlaneConfigExport { group_by: board.swimlane_config.group_by, lanes },
+        }
+    }
+
+    pub fn into_create_request(self) -> CreateBoardRequest {
+        CreateBoardRequest {
+            name: self.name,
+            description: self.description,
+            columns: Some(self.columns),
+            config: Some(BoardConfig {
+                wip_mode: self.config.wip_mode,
+                wip_limits: self
+                    .config
+                    .wip_limits
+                    .into_iter()
+                    .map(|limit| ColumnWipLimit { column_id: limit.column_id, limit: limit.limit })
+                    .collect(),
+            }),
+            swimlane_config: Some(SwimlaneConfig {
+                group_by: self.swimlane_config.group_by,
+                lanes: self
+                    .swimlane_config
+                    .lanes
+                    .into_iter()
+                    .map(|lane| SwimlaneLane { key: lane.key, collapsed: lane.collapsed })
+                    .collect(),
+            }),
+        }
+    }
+
+    pub fn to_yaml(&self) -> Result<String, AppError> {
+        serde_yaml::to_string(self).map_err(|e| AppError::InternalServer(format!("Failed to write board YAML: {}", e)))
+    }
+
+    // Syntax and schema errors carry the line and column they were found at
+    pub fn from_yaml(text: &str) -> Result<Self, AppError> {
+        let export: BoardExport = serde_yaml::from_str(text).map_err(|e| {
+            let mut reason = Message::new("invalid_board_yaml").with("error", e.to_string());
+            if let Some(location) = e.location() {
+                reason = reason.with("line", location.line()).with("column", location.column());
+            }
+            AppError::InvalidFields(vec![FieldError::new("yaml", reason)])
+        })?;
+
+        if export.version != FORMAT_VERSION {
+            return Err(AppError::InvalidFields(vec![FieldError::new(
+                "version",
+                Message::new("unsupported_board_version")
+                    .with("version", export.version)
+                    .with("expected", FORMAT_VERSION),
+            )]));
+        }
+        Ok(export)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::TaskPriority;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    // Small xorshift generator so the round-trip cases are reproducible
+    struct Cases(u64);
+
+    impl Cases {
+        fn step(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.step() % n as u64) as usize
+        }
+
+        fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+            items[self.below(items.len())]
+        }
+
+        // Includes text YAML would otherwise read as another type or as syntax
+        fn text(&mut self) -> String {
+            const PARTS: &[&str] = &[
+                "Bugs", "In Progress", "yes", "null", "~", "42", "3.0", "- item", "key: value", "#hash", "'quoted'",
+                "\"double\"", "a\nb", "  padded ", "Größe", "🐛", "[x]", "{y}", "&anchor", "*alias", "!tag", "%",
+            ];
+            (0..1 + self.below(3)).map(|_| self.pick(PARTS)).collect::<Vec<_>>().join(" ")
+        }
+
+        fn board(&mut self) -> Board {
+            let columns = (0..self.below(6)).map(|index| format!("{} {}", self.text(), index)).collect();
+            let mut wip_limits = Vec::new();
+            for column_id in TaskStatus::ALL {
+                if self.below(2) == 0 {
+                    wip_limits.push(ColumnWipLimit { column_id, limit: 1 + self.below(10) as i32 });
+                }
+            }
+            let group_by = self.pick(&[
+                SwimlaneGroupBy::None,
+                SwimlaneGroupBy::Priority,
+                SwimlaneGroupBy::Label,
+                SwimlaneGroupBy::Assignee,
+            ]);
+            let mut lanes = Vec::new();
+            for index in 0..self.below(4) {
+                let key = match group_by {
+                    SwimlaneGroupBy::Priority => Some(TaskPriority::ALL[index].name().to_string()),
+                    SwimlaneGroupBy::Label => Some(format!("{} {}", self.text(), index)),
+                    _ if index == 0 => None,
+                    _ => Some(Uuid::new_v4().to_string()),
+                };
+                lanes.push(SwimlaneLane { key, collapsed: self.below(2) == 0 });
+            }
+
+            Board {
+                id: Uuid::new_v4(),
+                name: self.text(),
+                description: (self.below(2) == 0).then(|| self.text()),
+                project_id: Uuid::new_v4(),
+                created_by: Uuid::new_v4(),
+                columns,
+                config: BoardConfig { wip_mode: self.pick(&[WipMode::Strict, WipMode::Advisory]), wip_limits },
+                swimlane_config: SwimlaneConfig { group_by, lanes },
+                is_default: false,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_export_round_trips_through_yaml() {
+        let mut cases = Cases(0x5eed_cafe_f00d_1234);
+        for _ in 0..500 {
+            let board = cases.board();
+            let export = BoardExport::from_board(&board);
+            let yaml = export.to_yaml().unwrap();
+            assert_eq!(BoardExport::from_yaml(&yaml).unwrap(), export, "{}", yaml);
+
+            // Importing gives the same setup, minus assignee lanes for specific users
+            let request = export.into_create_request();
+            assert_eq!(request.name, board.name);
+            assert_eq!(request.description, board.description);
+            assert_eq!(request.columns.as_ref(), Some(&board.columns));
+            assert_eq!(request.config.as_ref(), Some(&board.config));
+            let swimlanes = request.swimlane_config.unwrap();
+            assert_eq!(swimlanes.group_by, board.swimlane_config.group_by);
+            if board.swimlane_config.group_by == SwimlaneGroupBy::Assignee {
+                assert!(swimlanes.lanes.iter().all(|lane| lane.key.is_none()));
+            } else {
+                assert_eq!(swimlanes.lanes, board.swimlane_config.lanes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_yaml_errors_point_at_the_problem() {
+        let yaml = "version: 1\nname: Triage\ncolumns: [Todo]\nconfig:\n  wip_limits:\n    - column_id: Todo\n      limt: 3\n";
+        let Err(AppError::InvalidFields(errors)) = BoardExport::from_yaml(yaml) else {
+            panic!("expected a field error");
+        };
+        assert_eq!(errors[0].field, "yaml");
+        let params: Vec<&str> = errors[0].reason.params.iter().map(|(name, _)| *name).collect();
+        assert_eq!(params, vec!["error", "line", "column"]);
+        assert!(errors[0].reason.params[0].1.as_str().unwrap().contains("limt"));
+
+        let Err(AppError::InvalidFields(errors)) = BoardExport::from_yaml("version: 2\nname: Triage\ncolumns: []\n") else {
+            panic!("expected a field error");
+        };
+        assert_eq!(errors[0].field, "version");
+    }
+}
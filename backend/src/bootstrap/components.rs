@@ -0,0 +1,197 @@
+// The server's components, registered by `register` in the order main() has
+// always started them. Each names the components whose resources it uses.
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+use super::{Bootstrap, Component, Resources};
+use crate::audit_export::{self, AuditExportJob};
+use crate::auth::jwt::JwtService;
+use crate::config::{Config, PublicConfig};
+use crate::database::connection::Database;
+use crate::email::{self, digest::{self, DigestSchedulerJob}, EmailService, SendEmailJob};
+use crate::flags;
+use crate::integrations::slack::{self, SlackWebhookJob};
+use crate::jobs::{worker::JobWorker, JobRegistry};
+use crate::maintenance::{self, CounterReconcileJob};
+use crate::reports::{self, ReportSchedulerJob};
+use crate::retention::{self, RetentionSweepJob};
+use crate::scanning::{self, ScanAttachmentJob};
+use crate::staleness::{self, StalenessJob};
+use crate::trash::{self, TrashSweepJob};
+use crate::AppState;
+
+// Everything the server needs; the Config has to be provided
+pub fn register(bootstrap: &mut Bootstrap) {
+    bootstrap
+        .register(DatabaseComponent::default())
+        .register(JwtComponent)
+        .register(EmailComponent)
+        .register(AppStateComponent::default())
+        .register(JobWorkerComponent::default());
+}
+
+// Connects and applies the migrations
+#[derive(Default)]
+pub struct DatabaseComponent {
+    database: Option<Database>,
+}
+
+#[async_trait]
+impl Component for DatabaseComponent {
+    fn name(&self) -> &'static str {
+        "Database"
+    }
+
+    async fn init(&mut self, resources: &mut Resources) -> anyhow::Result<()> {
+        let config: Config = resources.get()?;
+        let database = Database::new(&config.database).await?;
+        resources.insert(database.clone());
+        self.database = Some(database);
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        if let Some(database) = self.database.take() {
+            database.pool().close().await;
+        }
+        Ok(())
+    }
+}
+
+pub struct JwtComponent;
+
+#[async_trait]
+impl Component for JwtComponent {
+    fn name(&self) -> &'static str {
+        "JwtService"
+    }
+
+    async fn init(&mut self, resources: &mut Resources) -> anyhow::Result<()> {
+        let config: Config = resources.get()?;
+        resources.insert(JwtService::new(&config.jwt)?);
+        Ok(())
+    }
+}
+
+// SMTP when SMTP_HOST is set, the log otherwise
+pub struct EmailComponent;
+
+#[async_trait]
+impl Component for EmailComponent {
+    fn name(&self) -> &'static str {
+        "EmailService"
+    }
+
+    async fn init(&mut self, resources: &mut Resources) -> anyhow::Result<()> {
+        let service: Arc<dyn EmailService> = email::email_service_from_env()?;
+        resources.insert(service);
+        Ok(())
+    }
+}
+
+// The state handlers share, including the WebSocket connections and the
+// feature flags, which are loaded here and then refreshed in the background
+#[derive(Default)]
+pub struct AppStateComponent {
+    flag_refresh: Option<JoinHandle<()>>,
+}
+
+#[async_trait]
+impl Component for AppStateComponent {
+    fn name(&self) -> &'static str {
+        "AppState"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["Database", "JwtService"]
+    }
+
+    async fn init(&mut self, resources: &mut Resources) -> anyhow::Result<()> {
+        // What GET /api/config tells clients about this instance
+        let config: Config = resources.get()?;
+        let public_config = PublicConfig::new(&config);
+        info!("Registration mode: {:?}", public_config.registration.mode);
+
+        let app_state = AppState::new(config, resources.get()?, resources.get()?, public_config);
+        app_state.flags.refresh().await?;
+        self.flag_refresh = Some(app_state.flags.spawn_refresh(flags::REFRESH_INTERVAL));
+        resources.insert(app_state);
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        if let Some(flag_refresh) = self.flag_refresh.take() {
+            flag_refresh.abort();
+        }
+        Ok(())
+    }
+}
+
+// Registers the job handlers, makes sure the recurring jobs are queued and
+// runs the worker
+#[derive(Default)]
+pub struct JobWorkerComponent {
+    worker: Option<(watch::Sender<bool>, JoinHandle<()>)>,
+}
+
+#[async_trait]
+impl Component for JobWorkerComponent {
+    fn name(&self) -> &'static str {
+        "JobWorker"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["AppState", "EmailService"]
+    }
+
+    async fn init(&mut self, resources: &mut Resources) -> anyhow::Result<()> {
+        let app_state: AppState = resources.get()?;
+        let database = app_state.database.clone();
+        let pool = database.pool();
+        let attachments = &app_state.config.attachments;
+
+        let mut registry = JobRegistry::new();
+        registry.register(email::SEND_EMAIL_JOB, SendEmailJob::new(database.clone(), resources.get()?));
+        registry.register(slack::SLACK_WEBHOOK_JOB, SlackWebhookJob::new(pool.clone())?);
+        registry.register(digest::DIGEST_SCHEDULER_JOB, DigestSchedulerJob::new(database.clone()));
+        registry.register(trash::TRASH_SWEEP_JOB, TrashSweepJob::new(database.clone()));
+        registry.register(maintenance::COUNTER_RECONCILE_JOB, CounterReconcileJob::new(database.clone(), attachments.clone()));
+        registry.register(reports::REPORT_SCHEDULER_JOB, ReportSchedulerJob::new(database.clone(), app_state.websocket.clone()));
+        registry.register(staleness::STALENESS_JOB, StalenessJob::new(database.clone(), app_state.websocket.clone()));
+        registry.register(retention::RETENTION_SWEEP_JOB, RetentionSweepJob::new(database.clone(), attachments.clone()));
+        registry.register(
+            scanning::SCAN_ATTACHMENT_JOB,
+            ScanAttachmentJob::new(database.clone(), app_state.websocket.clone(), attachments.clone(), scanning::scanner_from_env()),
+        );
+        registry.register(audit_export::AUDIT_EXPORT_JOB, AuditExportJob::new(database.clone(), app_state.websocket.clone(), attachments.clone()));
+
+        let now = chrono::Utc::now();
+        digest::schedule(pool, now).await?;
+        trash::schedule(pool, now).await?;
+        maintenance::schedule(pool, now).await?;
+        reports::schedule(pool, now).await?;
+        staleness::schedule(pool, now).await?;
+        retention::schedule(pool, now).await?;
+        if scanning::scanning_enabled() {
+            info!("Attachment virus scanning enabled");
+        }
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let worker = tokio::spawn(JobWorker::new(database.clone(), registry).run(shutdown_rx));
+        self.worker = Some((shutdown_tx, worker));
+        Ok(())
+    }
+
+    // Lets the worker finish the job it's running
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        if let Some((shutdown_tx, worker)) = self.worker.take() {
+            shutdown_tx.send(true).ok();
+            worker.await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+// Application startup and shutdown. Each part of the server (database, JWT,
+// email, job worker, ...) is a Component with an init and a shutdown hook.
+// Bootstrap starts them in dependency order, handing each the Resources the
+// earlier ones provided, and stops at the first failure after shutting down
+// what already started. Running::shutdown runs the hooks in reverse order
+// within a timeout.
+pub mod components;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+// How long the shutdown hooks get together, which mostly goes to letting the
+// job worker finish its current job
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[async_trait]
+pub trait Component: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Components that have to be initialized first
+    fn dependencies(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    async fn init(&mut self, resources: &mut Resources) -> anyhow::Result<()>;
+
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+// What components provide to the ones initialized after them, one value per type
+#[derive(Default)]
+pub struct Resources {
+    values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Resources {
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) {
+        self.values.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    pub fn get<T: Any + Clone>(&self) -> anyhow::Result<T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned()
+            .ok_or_else(|| anyhow!("{} is not available", type_name::<T>()))
+    }
+}
+
+#[derive(Default)]
+pub struct Bootstrap {
+    resources: Resources,
+    components: Vec<Box<dyn Component>>,
+}
+
+impl Bootstrap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A value that's there before any component starts, such as the config
+    pub fn provide<T: Any + Send + Sync>(&mut self, value: T) -> &mut Self {
+        self.resources.insert(value);
+        self
+    }
+
+    pub fn register<C: Component + 'static>(&mut self, component: C) -> &mut Self {
+        self.components.push(Box::new(component));
+        self
+    }
+
+    // Initializes every component. When one fails, the ones already started
+    // are shut down again and its error is returned.
+    pub async fn start(self) -> anyhow::Result<Running> {
+        let Bootstrap { mut resources, components } = self;
+        let mut running = Running { resources: Resources::default(), started: Vec::new() };
+
+        for mut component in startup_order(components)? {
+            let name = component.name();
+            if let Err(e) = component.init(&mut resources).await {
+                running.shutdown_started(None).await.ok();
+                return Err(e.context(format!("failed to initialize {}", name)));
+            }
+            info!("{} initialized", name);
+            running.started.push(component);
+        }
+
+        running.resources = resources;
+        Ok(running)
+    }
+}
+
+// Orders components so each comes after its dependencies, otherwise keeping
+// the order they were registered in
+fn startup_order(components: Vec<Box<dyn Component>>) -> anyhow::Result<Vec<Box<dyn Component>>> {
+    let positions: HashMap<&'static str, usize> =
+        components.iter().enumerate().map(|(i, component)| (component.name(), i)).collect();
+    for component in &components {
+        for dependency in component.dependencies() {
+            if !positions.contains_key(dependency) {
+                return Err(anyhow!("{} depends on {}, which isn't registered", component.name(), dependency));
+            }
+        }
+    }
+
+    // 0 = not visited, 1 = being visited, 2 = ordered
+    let mut state = vec![0u8; components.len()];
+    let mut order = Vec::with_capacity(components.len());
+    fn visit(
+        i: usize,
+        components: &[Box<dyn Component>],
+        positions: &HashMap<&'static str, usize>,
+        state: &mut [u8],
+        order: &mut Vec<usize>,
+    ) -> anyhow::Result<()> {
+        match state[i] {
+            2 => return Ok(()),
+            1 => return Err(anyhow!("dependency cycle involving {}", components[i].name())),
+            _ => {}
+        }
+        state[i] = 1;
+        for dependency in components[i].dependencies() {
+            visit(positions[dependency], components, positions, state, order)?;
+        }
+        state[i] = 2;
+        order.push(i);
+        Ok(())
+    }
+    for i in 0..components.len() {
+        visit(i, &components, &positions, &mut state, &mut order)?;
+    }
+
+    let mut slots: Vec<Option<Box<dyn Component>>> = components.into_iter().map(Some).collect();
+    Ok(order.into_iter().filter_map(|i| slots[i].take()).collect())
+}
+
+// The started components, in startup order
+pub struct Running {
+    resources: Resources,
+    started: Vec<Box<dyn Component>>,
+}
+
+impl Running {
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    // Runs the shutdown hooks in reverse startup order. A failing hook doesn't
+    // stop the others; once `timeout` is up the remaining ones are skipped.
+    pub async fn shutdown(mut self, timeout: Duration) -> anyhow::Result<()> {
+        self.shutdown_started(Some(Instant::now() + timeout)).await
+    }
+
+    async fn shutdown_started(&mut self, deadline: Option<Instant>) -> anyhow::Result<()> {
+        let mut failed = Vec::new();
+
+        while let Some(mut component) = self.started.pop() {
+            let name = component.name();
+            let result = match deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline, component.shutdown()).await,
+                None => Ok(component.shutdown().await),
+            };
+            match result {
+                Ok(Ok(())) => info!("{} shut down", name),
+                Ok(Err(e)) => {
+                    warn!("Shutting down {} failed: {:#}", name, e);
+                    failed.push(name);
+                }
+                Err(_) => {
+                    let skipped: Vec<&str> = self.started.drain(..).rev().map(|component| component.name()).collect();
+                    if skipped.is_empty() {
+                        return Err(anyhow!("shutdown timed out in {}", name));
+                    }
+                    return Err(anyhow!("shutdown timed out in {}, skipping {}", name, skipped.join(", ")));
+                }
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("failed to shut down {}", failed.join(", ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    struct Probe {
+        name: &'static str,
+        dependencies: &'static [&'static str],
+        fail_init: bool,
+        shutdown_delay: Duration,
+        log: Log,
+    }
+
+    fn probe(name: &'static str, dependencies: &'static [&'static str], log: &Log) -> Probe {
+        Probe { name, dependencies, fail_init: false, shutdown_delay: Duration::ZERO, log: log.clone() }
+    }
+
+    #[async_trait]
+    impl Component for Probe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn dependencies(&self) -> &'static [&'static str] {
+            self.dependencies
+        }
+
+        async fn init(&mut self, resources: &mut Resources) -> anyhow::Result<()> {
+            if self.fail_init {
+                return Err(anyhow!("connection refused"));
+            }
+            self.log.lock().unwrap().push(format!("init {}", self.name));
+            resources.insert(self.name.to_string());
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> anyhow::Result<()> {
+            tokio::time::sleep(self.shutdown_delay).await;
+            self.log.lock().unwrap().push(format!("shutdown {}", self.name));
+            Ok(())
+        }
+    }
+
+    fn entries(log: &Log) -> Vec<String> {
+        log.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_components_start_after_their_dependencies_and_stop_in_reverse() {
+        let log = Log::default();
+        let mut bootstrap = Bootstrap::new();
+        bootstrap
+            .provide(7u32)
+            .register(probe("Worker", &["State", "Email"], &log))
+            .register(probe("Email", &[], &log))
+            .register(probe("State", &["Database"], &log))
+            .register(probe("Database", &[], &log));
+
+        let running = bootstrap.start().await.unwrap();
+        assert_eq!(entries(&log), ["init Database", "init State", "init Email", "init Worker"]);
+        assert_eq!(running.resources().get::<u32>().unwrap(), 7);
+        assert_eq!(running.resources().get::<String>().unwrap(), "Worker");
+
+        running.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(
+            entries(&log)[4..],
+            ["shutdown Worker", "shutdown Email", "shutdown State", "shutdown Database"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failing_component_aborts_startup_and_stops_started_ones() {
+        let log = Log::default();
+        let mut bootstrap = Bootstrap::new();
+        bootstrap
+            .register(probe("Database", &[], &log))
+            .register(Probe { fail_init: true, ..probe("EmailService", &["Database"], &log) })
+            .register(probe("Worker", &["EmailService"], &log));
+
+        let error = bootstrap.start().await.err().unwrap();
+        assert_eq!(format!("{:#}", error), "failed to initialize EmailService: connection refused");
+        // The worker never started and the database was closed again
+        assert_eq!(entries(&log), ["init Database", "shutdown Database"]);
+    }
+
+    #[tokio::test]
+    async fn test_server_components_fail_without_config() {
+        let mut bootstrap = Bootstrap::new();
+        components::register(&mut bootstrap);
+        let error = bootstrap.start().await.err().unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "failed to initialize Database: simplecards::config::Config is not available"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_and_cyclic_dependencies_are_rejected() {
+        let log = Log::default();
+        let mut bootstrap = Bootstrap::new();
+        bootstrap.register(probe("Worker", &["Redis"], &log));
+        let error = bootstrap.start().await.err().unwrap();
+        assert_eq!(error.to_string(), "Worker depends on Redis, which isn't registered");
+
+        let mut bootstrap = Bootstrap::new();
+        bootstrap.register(probe("A", &["B"], &log)).register(probe("B", &["A"], &log));
+        let error = bootstrap.start().await.err().unwrap();
+        assert!(error.to_string().starts_with("dependency cycle"));
+        assert!(entries(&log).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_gives_up_after_timeout() {
+        let log = Log::default();
+        let mut bootstrap = Bootstrap::new();
+        bootstrap
+            .register(probe("Database", &[], &log))
+            .register(Probe { shutdown_delay: Duration::from_secs(60), ..probe("Worker", &["Database"], &log) });
+
+        let running = bootstrap.start().await.unwrap();
+        let error = running.shutdown(Duration::from_millis(20)).await.err().unwrap();
+        assert_eq!(error.to_string(), "shutdown timed out in Worker, skipping Database");
+        assert_eq!(entries(&log), ["init Database", "init Worker"]);
+    }
+}
@@ -0,0 +1,169 @@
+// Instance configuration the web client needs to know about, served without
+// authentication at GET /api/config. Assembled once at startup from the same
+// settings the backend enforces, so clients don't hardcode them. Only ever add
+// what an anonymous visitor may see: no secrets, keys, URLs of internal
+// services or anything derived from them beyond "is it configured".
+
+use serde::Serialize;
+use std::env;
+
+use super::Config;
+use crate::integrations::email_inbox;
+use crate::utils::{limits, validation};
+use crate::websocket;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicConfig {
+    pub api_version: &'static str,
+    pub registration: RegistrationConfig,
+    pub password_policy: PasswordPolicy,
+    pub limits: UploadLimits,
+    pub integrations: EnabledIntegrations,
+    pub features: FeatureFlags,
+    // Relative to the API host, e.g. wss://api.example.com/ws
+    pub websocket_path: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    Open,
+    // A signup code from an instance admin is required
+    InviteOnly,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RegistrationConfig {
+    pub mode: RegistrationMode,
+    // Empty allows every domain
+    pub allowed_domains: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    // At least one of special_characters
+    pub require_special: bool,
+    pub special_characters: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadLimits {
+    pub max_json_body_bytes: usize,
+    pub max_upload_bytes: usize,
+    pub max_websocket_message_bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnabledIntegrations {
+    pub slack: bool,
+    pub github: bool,
+    // Creating tasks by email needs a Mailgun signing key or forwarder secret
+    pub email_inbound: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureFlags {
+    // Emails are actually delivered (SMTP_HOST is set), not just logged
+    pub email_delivery: bool,
+    pub privacy_strict: bool,
+    pub hide_inaccessible_resources: bool,
+}
+
+impl PublicConfig {
+    pub fn new(config: &Config) -> Self {
+        let features = &config.features;
+
+        PublicConfig {
+            api_version: env!("CARGO_PKG_VERSION"),
+            registration: RegistrationConfig {
+                mode: if features.allow_public_registration { RegistrationMode::Open } else { RegistrationMode::InviteOnly },
+                allowed_domains: features.registration_allowed_domains.clone(),
+            },
+            password_policy: PasswordPolicy {
+                min_length: validation::PASSWORD_MIN_LENGTH,
+                max_length: validation::PASSWORD_MAX_LENGTH,
+                require_lowercase: true,
+                require_uppercase: true,
+                require_digit: true,
+                require_special: true,
+                special_characters: validation::PASSWORD_SPECIAL_CHARACTERS,
+            },
+            limits: UploadLimits {
+                max_json_body_bytes: limits::json_body_limit(),
+                max_upload_bytes: limits::upload_body_limit(),
+                max_websocket_message_bytes: websocket::limits::max_message_size(),
+            },
+            integrations: EnabledIntegrations {
+                slack: true,
+                github: true,
+                email_inbound: email_inbox::mailgun_signing_key().is_some() || email_inbox::forwarder_secret().is_some(),
+            },
+            features: FeatureFlags {
+                email_delivery: env::var("SMTP_HOST").is_ok_and(|host| !host.is_empty()),
+                privacy_strict: features.privacy_strict,
+                hide_inaccessible_resources: features.hide_inaccessible_resources,
+            },
+            websocket_path: "/ws",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    // Field names that would suggest a secret is being published
+    const SECRET_WORDS: &[&str] = &["secret", "token", "key", "hash", "credential", "database", "smtp", "jwt", "signing"];
+
+    fn field_names(value: &Value, names: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (name, value) in map {
+                    names.push(name.clone());
+                    field_names(value, names);
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|value| field_names(value, names)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_public_config_has_no_secret_fields() {
+        let server_config = Config::test_default();
+        let config = serde_json::to_value(PublicConfig::new(&server_config)).unwrap();
+
+        let mut names = Vec::new();
+        field_names(&config, &mut names);
+        for name in &names {
+            assert!(
+                !SECRET_WORDS.iter().any(|word| name.contains(word)),
+                "PublicConfig field `{}` looks secret-bearing",
+                name
+            );
+        }
+
+        // Whatever secrets this environment has, none of them leak through a value
+        let serialized = config.to_string();
+        let secrets = ["JWT_SECRET", "DATABASE_URL", "SMTP_PASSWORD", "MAILGUN_WEBHOOK_SIGNING_KEY", "INBOUND_EMAIL_SECRET"];
+        for secret in secrets.iter().filter_map(|name| env::var(name).ok()).filter(|value| value.len() >= 8) {
+            assert!(!serialized.contains(&secret));
+        }
+        assert!(!serialized.contains(&server_config.attachments.url_signing_key));
+    }
+
+    #[test]
+    fn test_password_policy_matches_validation() {
+        let policy = PublicConfig::new(&Config::test_default()).password_policy;
+        let shortest = format!("Aa1!{}", "a".repeat(policy.min_length - 4));
+
+        assert!(validation::validate_password(&shortest).is_ok());
+        assert!(validation::validate_password(&shortest[1..]).is_err());
+    }
+}
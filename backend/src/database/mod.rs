@@ -1,4 +1,5 @@
 // Database module - models and queries
 pub mod connection;
 pub mod models;
-pub mod queries;
\ No newline at end of file
+pub mod queries;
+pub mod timing;
\ No newline at end of file
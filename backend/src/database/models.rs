@@ -1,22 +1,55 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
+use crate::auth::authz::Scope;
+use crate::project_templates::ProjectSnapshot;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "team_role", rename_all = "lowercase")]
 pub enum TeamRole {
     Admin,
     Member,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
+impl TeamRole {
+    // Higher ranks hold more permissions (see auth::authz)
+    pub fn rank(&self) -> u8 {
+        match self {
+            TeamRole::Admin => 1,
+            TeamRole::Member => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, JsonSchema)]
 #[sqlx(type_name = "project_role", rename_all = "lowercase")]
 pub enum ProjectRole {
     Admin,
     Member,
     Editor,
     Guest,
+    Viewer,
+}
+
+impl ProjectRole {
+    // Higher ranks hold more permissions (see auth::authz)
+    pub fn rank(&self) -> u8 {
+        match self {
+            ProjectRole::Admin => 4,
+            ProjectRole::Editor => 3,
+            ProjectRole::Member => 2,
+            ProjectRole::Guest => 1,
+            ProjectRole::Viewer => 0,
+        }
+    }
+
+    // The roles of project members who aren't on the project's team
+    pub fn allowed_outside_team(&self) -> bool {
+        matches!(self, ProjectRole::Guest | ProjectRole::Viewer)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -39,6 +72,13 @@ pub struct CreateUserRequest {
     pub username: String,
     pub display_name: String,
     pub password: String,
+    // Required while public registration is closed
+    #[serde(default)]
+    pub signup_code: Option<String>,
+    // A project invitation token, which stands in for a signup code and is
+    // accepted once the account exists
+    #[serde(default)]
+    pub invitation: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -58,7 +98,86 @@ pub struct LoginResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateUserRequest {
     pub display_name: Option<String>,
-    pub avatar_url: Option<String>,
+    // null removes the avatar
+    #[serde(default, with = "crate::utils::double_option")]
+    pub avatar_url: Option<Option<String>>,
+}
+
+// Credentials of the account to merge into the signed-in one
+#[derive(Debug, Deserialize)]
+pub struct MergeAccountRequest {
+    pub email: String,
+    pub password: String,
+}
+
+// A team or project both accounts were members of with different roles
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleConflict<R> {
+    pub id: Uuid,
+    pub kept_role: R,
+    pub dropped_role: R,
+    // The surviving account took over the merged account's role
+    #[serde(skip)]
+    pub upgraded: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountMerge {
+    pub merged_user_id: Uuid,
+    pub team_memberships: u64,
+    pub project_memberships: u64,
+    pub assigned_tasks: u64,
+    pub comments: u64,
+    pub team_conflicts: Vec<RoleConflict<TeamRole>>,
+    pub project_conflicts: Vec<RoleConflict<ProjectRole>>,
+}
+
+// A personal API token as its owner sees it; the secret itself is only
+// returned once, when the token is created
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    // None for all of the user's projects
+    pub project_ids: Option<Vec<Uuid>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub project_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+    pub token: String,
+}
+
+// Where a token stands in its hourly request quota
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenQuota {
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiTokenWithQuota {
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+    pub quota: TokenQuota,
+}
+
+// Admin override of a user's per-token quota; null restores the default
+#[derive(Debug, Deserialize)]
+pub struct TokenRateLimitRequest {
+    pub requests_per_hour: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -86,6 +205,67 @@ pub struct TeamMember {
     pub joined_at: DateTime<Utc>,
 }
 
+// Which projects someone is added to when they join the team
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "auto_add_policy", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AutoAddPolicy {
+    #[default]
+    None,
+    All,
+    Selected,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamSettings {
+    pub team_id: Uuid,
+    pub auto_add_to_projects: AutoAddPolicy,
+    // Only set for the `selected` policy
+    pub project_ids: Vec<Uuid>,
+    // Role for members added by the policy or by `auto_add_members`
+    pub default_project_role: ProjectRole,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateTeamSettingsRequest {
+    pub auto_add_to_projects: AutoAddPolicy,
+    #[serde(default)]
+    pub project_ids: Vec<Uuid>,
+    // Defaults to Member
+    pub default_project_role: Option<ProjectRole>,
+}
+
+// How old a team's comments and attachments may get, and how long tasks may
+// stay done, before the retention sweep removes them, see retention.rs. Unset
+// keeps them for good.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    pub comment_max_age_years: Option<u16>,
+    pub attachment_max_age_months: Option<u16>,
+    pub closed_task_max_age_days: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq)]
+pub struct RetentionCounts {
+    pub comments: i64,
+    pub attachments: i64,
+    pub attachment_bytes: i64,
+    pub tasks: i64,
+}
+
+// What the next retention sweep would do to a team's content: hide it, bring
+// it back after the policy was relaxed, or delete it after the grace period
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RetentionPreview {
+    pub team_id: Uuid,
+    pub policy: RetentionPolicy,
+    pub grace_period_days: i64,
+    pub to_expire: RetentionCounts,
+    pub to_restore: RetentionCounts,
+    pub to_delete: RetentionCounts,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Project {
     pub id: Uuid,
@@ -94,17 +274,217 @@ pub struct Project {
     pub team_id: Uuid,
     pub created_by: Uuid,
     pub color: Option<String>,
+    pub contrast_text: Option<ContrastText>, // for text drawn on `color`
+    pub icon: Option<String>,
     pub is_active: bool,
+    pub archived_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+// `?status=` filter for project lists
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectStatusFilter {
+    #[default]
+    Active,
+    Archived,
+    All,
+}
+
+// Team and project details embed this many members; the rest are paged
+// through the members endpoints
+pub const EMBEDDED_MEMBERS: i64 = 10;
+
+// `?embed=members` on team and project details returns every member, for
+// clients from before the members endpoints. Deprecated.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetailsEmbed {
+    Members,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DetailsQuery {
+    pub embed: Option<DetailsEmbed>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateProjectRequest {
     pub name: String,
     pub description: Option<String>,
     pub team_id: Uuid,
     pub color: Option<String>,
+    pub icon: Option<String>,
+    // Adds every current team member with the team's default project role
+    #[serde(default)]
+    pub auto_add_members: bool,
+    // Starts from a published template's settings and boards
+    #[serde(default)]
+    pub template_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "contrast_text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ContrastText {
+    Light,
+    Dark,
+}
+
+// Statuses a task may move to from `from`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StatusTransition {
+    pub from: TaskStatus,
+    pub to: Vec<TaskStatus>,
+}
+
+// Statuses without a transition rule can move to any status
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProjectWorkflow {
+    #[serde(default)]
+    pub transitions: Vec<StatusTransition>,
+    // Every new task is checked for similar open tasks, as with check_duplicates
+    #[serde(default)]
+    pub check_duplicates: bool,
+}
+
+impl ProjectWorkflow {
+    pub fn allows(&self, from: TaskStatus, to: TaskStatus) -> bool {
+        if from == to {
+            return true;
+        }
+        self.transitions
+            .iter()
+            .find(|transition| transition.from == from)
+            .is_none_or(|transition| transition.to.contains(&to))
+    }
+}
+
+// One task of a project's onboarding checklist
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OnboardingTaskTemplate {
+    pub title: String,
+    pub description: Option<String>,
+    pub priority: Option<TaskPriority>,
+    pub tags: Option<Vec<String>>,
+    // All-day due date this many days after the member joins; none if unset
+    pub due_in_days: Option<u16>,
+}
+
+impl OnboardingTaskTemplate {
+    pub fn task_request(&self, assigned_to: Uuid, joined_on: NaiveDate) -> CreateTaskRequest {
+        CreateTaskRequest {
+            title: self.title.clone(),
+            description: self.description.clone(),
+            assigned_to: Some(assigned_to),
+            priority: self.priority,
+            due_date: self
+                .due_in_days
+                .map(|days| DueDate::AllDay(joined_on + chrono::Duration::days(i64::from(days)))),
+            tags: self.tags.clone(),
+            cover_color: None,
+            cover_emoji: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OnboardingTemplate {
+    #[serde(default)]
+    pub tasks: Vec<OnboardingTaskTemplate>,
+}
+
+// Open tasks that stay in a status longer than its threshold are marked stale
+// and their assignee is nudged once (see staleness.rs)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct StalenessThreshold {
+    pub status: TaskStatus,
+    pub days: u16,
+}
+
+// Statuses without a threshold never go stale
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct StalenessRules {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub thresholds: Vec<StalenessThreshold>,
+    // Also post a system comment on the task when it goes stale
+    #[serde(default)]
+    pub post_comment: bool,
+}
+
+// Who may use group mentions (@project, @assignees) in a project, see
+// mentions.rs. Individual mentions are open to everyone who can comment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MentionSettings {
+    // The lowest role allowed to use them
+    pub group_mention_role: ProjectRole,
+}
+
+impl Default for MentionSettings {
+    fn default() -> Self {
+        MentionSettings { group_mention_role: ProjectRole::Editor }
+    }
+}
+
+// A user reached by a mention. `group_token` is the group they were reached
+// through ("project" or "assignees"), None for mentions by username.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskMention {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub comment_id: Option<Uuid>, // None for mentions in the description
+    pub user: UserSummary,
+    pub mentioned_by: Option<Uuid>,
+    pub group_token: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// A task the staleness job has just marked stale
+#[derive(Debug, Clone)]
+pub struct StaleTask {
+    pub task_id: Uuid,
+    pub status: TaskStatus,
+    pub status_changed_at: DateTime<Utc>,
+}
+
+// An invitation to join one project as a guest or viewer, see
+// migration 050. The token is only returned when the invitation is created.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectInvitation {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub email: String,
+    pub role: ProjectRole,
+    pub invited_by: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateProjectInvitationRequest {
+    pub email: String,
+    #[serde(default = "guest_role")]
+    pub role: ProjectRole,
+}
+
+fn guest_role() -> ProjectRole {
+    ProjectRole::Guest
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedProjectInvitation {
+    #[serde(flatten)]
+    pub invitation: ProjectInvitation,
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptInvitationRequest {
+    pub token: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -117,7 +497,7 @@ pub struct ProjectMember {
 }
 
 // User summary for public display (no sensitive data)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct UserSummary {
     pub id: Uuid,
     pub username: String,
@@ -136,7 +516,7 @@ impl From<User> for UserSummary {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, JsonSchema)]
 #[sqlx(type_name = "task_status", rename_all = "lowercase")]
 pub enum TaskStatus {
     Todo,
@@ -145,7 +525,32 @@ pub enum TaskStatus {
     Done,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+impl TaskStatus {
+    pub const ALL: [TaskStatus; 4] = [TaskStatus::Todo, TaskStatus::InProgress, TaskStatus::Review, TaskStatus::Done];
+
+    // Same spelling as the JSON representation
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskStatus::Todo => "Todo",
+            TaskStatus::InProgress => "InProgress",
+            TaskStatus::Review => "Review",
+            TaskStatus::Done => "Done",
+        }
+    }
+
+    // The column a nudge moves a task to: its own for up and down, the
+    // neighbouring one for left and right. None past the first or last column.
+    pub fn adjacent(&self, direction: NudgeDirection) -> Option<TaskStatus> {
+        let index = TaskStatus::ALL.iter().position(|status| status == self)?;
+        match direction {
+            NudgeDirection::Up | NudgeDirection::Down => Some(*self),
+            NudgeDirection::Left => index.checked_sub(1).map(|index| TaskStatus::ALL[index]),
+            NudgeDirection::Right => TaskStatus::ALL.get(index + 1).copied(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, JsonSchema)]
 #[sqlx(type_name = "task_priority", rename_all = "lowercase")]
 pub enum TaskPriority {
     Low,
@@ -154,7 +559,85 @@ pub enum TaskPriority {
     Critical,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+impl TaskPriority {
+    pub const ALL: [TaskPriority; 4] = [TaskPriority::Low, TaskPriority::Medium, TaskPriority::High, TaskPriority::Critical];
+
+    // Same spelling as the JSON representation
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskPriority::Low => "Low",
+            TaskPriority::Medium => "Medium",
+            TaskPriority::High => "High",
+            TaskPriority::Critical => "Critical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct PriorityCounts {
+    pub critical: i64,
+    pub high: i64,
+    pub medium: i64,
+    pub low: i64,
+}
+
+// A member's open (not done) tasks in one project
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct TaskWorkload {
+    pub open_tasks: i64,
+    pub by_priority: PriorityCounts,
+    pub overdue: i64,
+}
+
+// Seconds a completed task spent in each status before its completion,
+// counting every visit
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct StatusDurations {
+    #[serde(rename = "Todo")]
+    pub todo: i64,
+    #[serde(rename = "InProgress")]
+    pub in_progress: i64,
+    #[serde(rename = "Review")]
+    pub review: i64,
+    #[serde(rename = "Done")]
+    pub done: i64,
+}
+
+// A task completed in the analytics window. Lead time runs from creation and
+// cycle time from the task first leaving Todo, both until it was last moved
+// to Done.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CycleTimeTask {
+    pub task_id: Uuid,
+    pub number: i32,
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+    pub lead_time_seconds: i64,
+    pub cycle_time_seconds: i64,
+    pub time_in_status: StatusDurations,
+}
+
+// In seconds; None without completed tasks
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct DurationPercentiles {
+    pub p50: Option<i64>,
+    pub p85: Option<i64>,
+    pub p95: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CycleTimeReport {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub completed: usize,
+    pub cycle_time: DurationPercentiles,
+    pub lead_time: DurationPercentiles,
+    pub tasks: Vec<CycleTimeTask>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, JsonSchema)]
 pub struct Task {
     pub id: Uuid,
     pub title: String,
@@ -165,34 +648,267 @@ pub struct Task {
     pub status: TaskStatus,
     pub priority: TaskPriority,
     pub due_date: Option<DateTime<Utc>>,
+    pub is_all_day: bool, // due_date is then UTC midnight of the day
     pub tags: Option<Vec<String>>,
+    pub cover_color: Option<String>, // "#RRGGBB"
+    pub cover_emoji: Option<String>,
     pub position: i32,
+    pub number: i32, // per-project sequence, referenced as e.g. "SC-142"
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl Task {
+    // Top-level fields selectable through `?fields=`
+    pub const FIELDS: &'static [&'static str] = &[
+        "id", "title", "description", "project_id", "created_by", "assigned_to",
+        "status", "priority", "due_date", "is_all_day", "tags", "cover_color", "cover_emoji", "position",
+        "number", "created_at", "updated_at",
+    ];
+}
+
+// A due date as clients send it: "2024-06-07" for the whole day, or an
+// RFC 3339 date-time with an offset. Date-times without an offset are
+// rejected since it isn't clear whose timezone they mean.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DueDate {
+    AllDay(NaiveDate),
+    At(DateTime<Utc>),
+}
+
+impl DueDate {
+    // What goes into tasks.due_date; all-day dates are stored as UTC midnight
+    pub fn instant(self) -> DateTime<Utc> {
+        match self {
+            DueDate::AllDay(date) => Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN)),
+            DueDate::At(at) => at,
+        }
+    }
+
+    pub fn is_all_day(self) -> bool {
+        matches!(self, DueDate::AllDay(_))
+    }
+}
+
+impl std::str::FromStr for DueDate {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            return Ok(DueDate::AllDay(date));
+        }
+        if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+            return Ok(DueDate::At(at.with_timezone(&Utc)));
+        }
+        if NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f").is_ok() {
+            return Err("due date has a time but no UTC offset; send a plain date or add the offset");
+        }
+        Err("due date must be a date (YYYY-MM-DD) or an RFC 3339 date-time")
+    }
+}
+
+impl Serialize for DueDate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            DueDate::AllDay(date) => serializer.collect_str(date),
+            DueDate::At(at) => serializer.serialize_str(&at.to_rfc3339()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DueDate {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// Filters shared by the task list and the task counts, so both agree
+#[derive(Debug, Clone, Default)]
+pub struct TaskListFilter {
+    pub status: Option<TaskStatus>,
+    pub priority: Option<TaskPriority>,
+    pub assigned_to: Option<Uuid>,
+    pub tag: Option<String>,
+    pub unread: Option<UnreadFilter>,
+}
+
+// Read state depends on the viewer, so it is checked against `task_reads`
+// rather than the task itself
+#[derive(Debug, Clone, Copy)]
+pub struct UnreadFilter {
+    pub user_id: Uuid,
+    pub unread: bool,
+}
+
+impl TaskListFilter {
+    // Everything but `unread`, see TaskReadState
+    pub fn matches(&self, task: &Task) -> bool {
+        self.status.is_none_or(|status| task.status == status)
+            && self.priority.is_none_or(|priority| task.priority == priority)
+            && self.assigned_to.is_none_or(|assigned_to| task.assigned_to == Some(assigned_to))
+            && self.tag.as_ref().is_none_or(|tag| {
+                task.tags.as_ref().map(|tags| tags.contains(tag)).unwrap_or(false)
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskGroupBy {
+    #[default]
+    Status,
+    Assignee,
+    Label,
+}
+
+// A status column's task ids in position order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct ColumnOrder {
+    pub status: TaskStatus,
+    pub task_ids: Vec<Uuid>,
+}
+
+// Which projects the task picker searches
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PickerScope {
+    #[default]
+    Project,
+    // Every project of the team the user is a member of
+    Team,
+}
+
+// Sort keys for the current user's assigned tasks
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskSort {
+    #[default]
+    DueDate,
+    Priority,
+    CreatedAt,
+    Project,
+    // The user's own order (PUT /api/tasks/my-order); unordered tasks follow by due date
+    Manual,
+}
+
+impl TaskSort {
+    // Soonest due and most urgent first unless the client asks otherwise
+    pub fn default_order(&self) -> SortOrder {
+        match self {
+            TaskSort::Priority => SortOrder::Desc,
+            TaskSort::DueDate | TaskSort::CreatedAt | TaskSort::Project | TaskSort::Manual => SortOrder::Asc,
+        }
+    }
+}
+
+// Task ids in the order the user wants them; may list only some of their tasks
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetTaskOrderRequest {
+    pub task_ids: Vec<Uuid>,
+}
+
+// `?due=` filter for the assigned task list, see due_dates.rs
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DueFilter {
+    Overdue,
+    // Due within the next week and not overdue yet
+    Upcoming,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+// `key` is the status name, assignee id or label; None for unassigned / unlabeled tasks
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskGroupCount {
+    pub key: Option<String>,
+    pub count: i64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateTaskRequest {
     pub title: String,
     pub description: Option<String>,
     pub assigned_to: Option<Uuid>,
     pub priority: Option<TaskPriority>,
-    pub due_date: Option<DateTime<Utc>>,
+    pub due_date: Option<DueDate>,
     pub tags: Option<Vec<String>>,
+    pub cover_color: Option<String>,
+    pub cover_emoji: Option<String>,
+}
+
+// Where a task created from a board column goes
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnPlacement {
+    Top,
+    #[default]
+    Bottom,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateProjectTaskRequest {
+    #[serde(flatten)]
+    pub task: CreateTaskRequest,
+    // Reject the task when similar open tasks exist (see duplicates.rs)
+    #[serde(default)]
+    pub check_duplicates: bool,
+    // Create it anyway, even where the project checks every new task
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateColumnTaskRequest {
+    #[serde(flatten)]
+    pub task: CreateTaskRequest,
+    #[serde(default)]
+    pub placement: ColumnPlacement,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateTaskRequest {
     pub title: Option<String>,
-    pub description: Option<String>,
-    pub assigned_to: Option<Uuid>,
+    // For these, null clears the field and leaving it out keeps it
+    #[serde(default, with = "crate::utils::double_option")]
+    pub description: Option<Option<String>>,
+    #[serde(default, with = "crate::utils::double_option")]
+    pub assigned_to: Option<Option<Uuid>>,
     pub status: Option<TaskStatus>,
     pub priority: Option<TaskPriority>,
-    pub due_date: Option<DateTime<Utc>>,
-    pub tags: Option<Vec<String>>,
+    #[serde(default, with = "crate::utils::double_option")]
+    pub due_date: Option<Option<DueDate>>,
+    #[serde(default, with = "crate::utils::double_option")]
+    pub tags: Option<Option<Vec<String>>>,
+    // An empty string removes the cover
+    pub cover_color: Option<String>,
+    pub cover_emoji: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+impl UpdateTaskRequest {
+    // Columns the client set to null
+    pub fn cleared_fields(&self) -> Vec<&'static str> {
+        use crate::utils::double_option::is_cleared;
+
+        [
+            ("description", is_cleared(&self.description)),
+            ("assigned_to", is_cleared(&self.assigned_to)),
+            ("due_date", is_cleared(&self.due_date)),
+            ("tags", is_cleared(&self.tags)),
+        ]
+        .into_iter()
+        .filter_map(|(field, cleared)| cleared.then_some(field))
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, JsonSchema)]
 pub struct Board {
     pub id: Uuid,
     pub name: String,
@@ -200,43 +916,1211 @@ pub struct Board {
     pub project_id: Uuid,
     pub created_by: Uuid,
     pub columns: Vec<String>, // JSON array of column names
+    pub config: BoardConfig,
+    pub swimlane_config: SwimlaneConfig,
     pub is_default: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateBoardRequest {
-    pub name: String,
-    pub description: Option<String>,
-    pub columns: Option<Vec<String>>,
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub enum WipMode {
+    // Moves into a full column are rejected
+    #[default]
+    Strict,
+    // Moves are allowed, clients only get the column status event
+    Advisory,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UpdateBoardRequest {
-    pub name: Option<String>,
-    pub description: Option<String>,
-    pub columns: Option<Vec<String>>,
+// Columns group tasks by status, so limits are keyed by the status a column shows
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct ColumnWipLimit {
+    pub column_id: TaskStatus,
+    pub limit: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct MoveTaskRequest {
-    pub task_id: Uuid,
-    pub status: TaskStatus,
-    pub position: i32,
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct BoardConfig {
+    #[serde(default)]
+    pub wip_mode: WipMode,
+    #[serde(default)]
+    pub wip_limits: Vec<ColumnWipLimit>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
-pub struct TaskComment {
-    pub id: Uuid,
-    pub task_id: Uuid,
-    pub user_id: Uuid,
-    pub content: String,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
+impl BoardConfig {
+    pub fn wip_limit(&self, column_id: TaskStatus) -> Option<i32> {
+        self.wip_limits
+            .iter()
+            .find(|limit| limit.column_id == column_id)
+            .map(|limit| limit.limit)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateTaskCommentRequest {
-    pub content: String,
-}
\ No newline at end of file
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SwimlaneGroupBy {
+    #[default]
+    None,
+    Assignee,
+    Priority,
+    Label,
+    Milestone,
+}
+
+impl SwimlaneGroupBy {
+    // Same spelling as the JSON representation
+    pub fn name(&self) -> &'static str {
+        match self {
+            SwimlaneGroupBy::None => "none",
+            SwimlaneGroupBy::Assignee => "assignee",
+            SwimlaneGroupBy::Priority => "priority",
+            SwimlaneGroupBy::Label => "label",
+            SwimlaneGroupBy::Milestone => "milestone",
+        }
+    }
+
+    // Tasks don't have milestones yet, so boards can't be grouped by them
+    pub fn is_supported(&self) -> bool {
+        !matches!(self, SwimlaneGroupBy::Milestone)
+    }
+}
+
+// `key` is the assignee id, priority name or label; None is the lane for tasks without one
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct SwimlaneLane {
+    pub key: Option<String>,
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+// Lanes listed here come first, in this order; lanes for other keys follow
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct SwimlaneConfig {
+    #[serde(default)]
+    pub group_by: SwimlaneGroupBy,
+    #[serde(default)]
+    pub lanes: Vec<SwimlaneLane>,
+}
+
+// What makes a board automation rule run (see automations.rs). Columns are
+// keyed by the status they show, like WIP limits.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AutomationTrigger {
+    MovedToColumn { column_id: TaskStatus },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AutomationAction {
+    // Takes turns between those of the users who are project members
+    AssignRoundRobin { user_ids: Vec<Uuid> },
+    AddLabel { label: String },
+    SetPriority { priority: TaskPriority },
+    PostSystemComment { content: String },
+}
+
+impl AutomationAction {
+    // Same spelling as the JSON `type`
+    pub fn name(&self) -> &'static str {
+        match self {
+            AutomationAction::AssignRoundRobin { .. } => "assign_round_robin",
+            AutomationAction::AddLabel { .. } => "add_label",
+            AutomationAction::SetPriority { .. } => "set_priority",
+            AutomationAction::PostSystemComment { .. } => "post_system_comment",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AutomationRule {
+    // Assigned on save; send it back to keep the rule's round-robin turn
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+    pub name: String,
+    pub trigger: AutomationTrigger,
+    pub actions: Vec<AutomationAction>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BoardAutomations {
+    #[serde(default)]
+    pub rules: Vec<AutomationRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateBoardRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub columns: Option<Vec<String>>,
+    pub config: Option<BoardConfig>,
+    #[serde(default)]
+    pub swimlane_config: Option<SwimlaneConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateBoardRequest {
+    pub name: Option<String>,
+    // null removes the description
+    #[serde(default, with = "crate::utils::double_option")]
+    pub description: Option<Option<String>>,
+    pub columns: Option<Vec<String>>,
+    pub config: Option<BoardConfig>,
+    pub swimlane_config: Option<SwimlaneConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoveTaskRequest {
+    pub task_id: Uuid,
+    pub status: TaskStatus,
+    pub position: i32,
+}
+
+// Keyboard moves: one step within the column or to the neighbouring column
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NudgeDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NudgeTaskRequest {
+    pub direction: NudgeDirection,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, JsonSchema)]
+pub struct TaskComment {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub user_id: Uuid,
+    pub content: String,
+    // Posted by SimpleCards (e.g. a scheduled report) on behalf of `user_id`
+    #[serde(default)]
+    pub is_system: bool,
+    // Pinned comments are listed first
+    #[serde(default)]
+    pub pinned: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateTaskCommentRequest {
+    pub content: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ConvertCommentRequest {
+    // Project member to assign the new task to
+    #[serde(default)]
+    pub assigned_to: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+#[sqlx(type_name = "job_status", rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Job {
+    pub id: Uuid,
+    pub job_type: String,
+    pub payload: serde_json::Value,
+    pub status: JobStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    // Set for ordered jobs, see jobs::enqueue_ordered
+    pub ordering_key: Option<String>,
+    pub sequence: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+#[sqlx(type_name = "email_status", rename_all = "lowercase")]
+pub enum EmailStatus {
+    Sent,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EmailLogEntry {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub recipient: String,
+    pub template: String,
+    pub subject: String,
+    pub status: EmailStatus,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateEmailPreferencesRequest {
+    pub opted_out: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ProjectIntegration {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub kind: String,
+    #[serde(skip_serializing)]
+    pub config: serde_json::Value, // may contain secrets such as webhook URLs
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+#[sqlx(type_name = "task_link_kind", rename_all = "snake_case")]
+pub enum TaskLinkKind {
+    Commit,
+    PullRequest,
+}
+
+// A commit or pull request that references a task
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TaskLink {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub kind: TaskLinkKind,
+    pub external_id: String,
+    pub url: String,
+    pub title: String,
+    pub author: Option<String>,
+    pub state: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// How relations are stored; a duplicate is always the source task
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+#[sqlx(type_name = "task_relation_kind", rename_all = "snake_case")]
+pub enum TaskRelationKind {
+    RelatesTo,
+    Duplicates,
+}
+
+// A relation as read from one of its tasks. IsDuplicatedBy is the inverse of
+// Duplicates and is never stored.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskRelationType {
+    RelatesTo,
+    Duplicates,
+    IsDuplicatedBy,
+}
+
+impl TaskRelationType {
+    // Stored kind, and whether the other task is the stored source
+    pub fn stored(&self) -> (TaskRelationKind, bool) {
+        match self {
+            TaskRelationType::RelatesTo => (TaskRelationKind::RelatesTo, false),
+            TaskRelationType::Duplicates => (TaskRelationKind::Duplicates, false),
+            TaskRelationType::IsDuplicatedBy => (TaskRelationKind::Duplicates, true),
+        }
+    }
+
+    pub fn seen_from(kind: TaskRelationKind, is_source: bool) -> Self {
+        match kind {
+            TaskRelationKind::RelatesTo => TaskRelationType::RelatesTo,
+            TaskRelationKind::Duplicates if is_source => TaskRelationType::Duplicates,
+            TaskRelationKind::Duplicates => TaskRelationType::IsDuplicatedBy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedTask {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub number: i32,
+    pub title: String,
+    pub status: TaskStatus,
+}
+
+impl From<&Task> for RelatedTask {
+    fn from(task: &Task) -> Self {
+        RelatedTask {
+            id: task.id,
+            project_id: task.project_id,
+            number: task.number,
+            title: task.title.clone(),
+            status: task.status,
+        }
+    }
+}
+
+// A comment with what a deep link needs to show it in its thread
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommentContext {
+    pub comment: TaskComment,
+    pub user: UserSummary,
+    pub task: RelatedTask,
+    pub project_id: Uuid,
+    // Position in the task's comment list, pinned comments first
+    pub offset: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskContextProject {
+    pub id: Uuid,
+    pub name: String,
+    pub team_id: Uuid,
+    pub is_active: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskContextBoard {
+    pub id: Uuid,
+    pub name: String,
+}
+
+// Where to open a task: its project's default board, in its status column
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskContext {
+    pub task: RelatedTask,
+    pub project: TaskContextProject,
+    // None once every board of the project is deleted
+    pub board: Option<TaskContextBoard>,
+    pub column_id: TaskStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserActivityKind {
+    TaskCreated,
+    // A status change to done
+    TaskCompleted,
+    StatusChanged,
+    Commented,
+}
+
+// Something a user did, for their profile's activity page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserActivity {
+    pub kind: UserActivityKind,
+    pub at: DateTime<Utc>,
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub task: RelatedTask,
+    pub comment_id: Option<Uuid>,
+    // Set for completions and status changes
+    pub from_status: Option<TaskStatus>,
+    pub to_status: Option<TaskStatus>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuickSwitchKind {
+    Team,
+    Project,
+    Task,
+}
+
+// Where a quick switcher entry lives: a project's team, a task's project
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuickSwitchContext {
+    pub id: Uuid,
+    pub name: String,
+}
+
+// An entry of the quick switcher; a task's color and icon are its cover
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuickSwitchItem {
+    #[serde(rename = "type")]
+    pub kind: QuickSwitchKind,
+    pub id: Uuid,
+    pub name: String,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub context: Option<QuickSwitchContext>,
+}
+
+// What quick_switch::rank ranks an item by
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuickSwitchCandidate {
+    pub item: QuickSwitchItem,
+    // pg_trgm word similarity of the query to the name
+    pub similarity: f32,
+    // Last opened: the project, any project of the team, the task
+    pub last_accessed_at: Option<DateTime<Utc>>,
+}
+
+// One `- [ ]` / `- [x]` item of a task description
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChecklistItem {
+    pub text: String,
+    pub done: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotProject {
+    pub id: Uuid,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotComment {
+    pub author: UserSummary,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// A self-contained rendering of a task for printing and archiving (see
+// task_snapshots.rs). Stored snapshots keep it as it was when taken.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskSnapshot {
+    pub task_id: Uuid,
+    pub number: i32,
+    pub title: String,
+    pub description: Option<String>,
+    pub description_html: String,
+    pub checklist: Vec<ChecklistItem>,
+    pub status: TaskStatus,
+    pub priority: TaskPriority,
+    pub due_date: Option<DateTime<Utc>>,
+    pub is_all_day: bool,
+    pub assignee: Option<UserSummary>,
+    pub labels: Vec<String>,
+    pub project: SnapshotProject,
+    // Only when asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comments: Option<Vec<SnapshotComment>>,
+    pub captured_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredTaskSnapshot {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub created_by: Option<UserSummary>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    // Left out of listings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<TaskSnapshot>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateTaskSnapshotRequest {
+    #[serde(default)]
+    pub include_comments: bool,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskRelation {
+    pub id: Uuid,
+    pub relation: TaskRelationType,
+    pub task: RelatedTask,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateTaskRelationRequest {
+    pub task_id: Uuid,
+    pub relation: TaskRelationType,
+    // Also moves the duplicate to Done
+    #[serde(default)]
+    pub close_duplicate: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+#[sqlx(type_name = "digest_frequency", rename_all = "lowercase")]
+pub enum DigestFrequency {
+    Off,
+    Daily,
+    Weekly,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DigestPreferences {
+    pub frequency: DigestFrequency,
+    pub timezone: String,
+    pub last_digest_sent_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateDigestPreferencesRequest {
+    pub frequency: Option<DigestFrequency>,
+    pub timezone: Option<String>,
+}
+
+// A user whose digest is due, claimed by the scheduler
+#[derive(Debug, Clone)]
+pub struct DueDigest {
+    pub user_id: Uuid,
+    pub email: String,
+    pub username: String,
+    pub display_name: String,
+    pub frequency: DigestFrequency,
+    pub previous_sent_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DigestTask {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub number: i32,
+    pub title: String,
+    pub status: TaskStatus,
+    pub due_date: Option<DateTime<Utc>>,
+}
+
+// An assigned task on the weekly agenda (see agenda.rs)
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AgendaTask {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub number: i32,
+    pub title: String,
+    pub status: TaskStatus,
+    pub priority: TaskPriority,
+    pub due_date: DateTime<Utc>,
+    pub is_all_day: bool,
+}
+
+// Open tasks due on one day, in the assignee's timezone
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DueDay {
+    pub day: NaiveDate,
+    pub tasks: Vec<DigestTask>,
+}
+
+// Maintained by triggers (migration 039) rather than counted per request
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct TeamCounters {
+    pub member_count: i64,
+    // Active projects only
+    pub project_count: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct ProjectCounters {
+    pub member_count: i64,
+    pub task_count: i64,
+    pub open_task_count: i64,
+}
+
+// Maintained alongside ProjectCounters (migration 048). Expired attachments
+// count until the retention sweep deletes them.
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq)]
+pub struct AttachmentStorage {
+    pub attachment_count: i64,
+    pub attachment_bytes: i64,
+}
+
+// A row of the instance storage report
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectStorage {
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub team_id: Uuid,
+    pub team_name: String,
+    #[serde(flatten)]
+    pub storage: AttachmentStorage,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TeamProjectCount {
+    #[serde(flatten)]
+    pub team: Team,
+    // Active projects only
+    pub project_count: i64,
+}
+
+// Instance-wide totals for operators; trashed projects and their tasks aren't counted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceStats {
+    pub users: i64,
+    pub active_users: i64,
+    pub instance_admins: i64,
+    pub teams: i64,
+    pub projects: i64,
+    pub archived_projects: i64,
+    pub tasks: i64,
+    pub open_tasks: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestMention {
+    pub comment_id: Uuid,
+    pub task_id: Uuid,
+    pub project_id: Uuid,
+    pub task_title: String,
+    pub project_name: String,
+    pub author: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "report_cadence", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ReportCadence {
+    Daily,
+    Weekly,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportSection {
+    Completed,
+    Created,
+    Overdue,
+    Members,
+}
+
+// Where a report goes; any combination of the three
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReportRecipients {
+    // Project members to email
+    #[serde(default)]
+    pub user_ids: Vec<Uuid>,
+    // Post to the project's Slack integration
+    #[serde(default)]
+    pub slack: bool,
+    // Task in the project to post the report on as a pinned comment
+    #[serde(default)]
+    pub comment_task_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectReport {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub name: String,
+    pub cadence: ReportCadence,
+    // ISO weekday (1 = Monday), only used by weekly reports
+    pub weekday: i16,
+    pub hour: i16,
+    pub timezone: String,
+    pub sections: Vec<ReportSection>,
+    pub recipients: ReportRecipients,
+    #[serde(skip_serializing)]
+    pub last_comment_id: Option<Uuid>,
+    pub last_sent_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateProjectReportRequest {
+    pub name: String,
+    pub cadence: ReportCadence,
+    pub weekday: Option<i16>,
+    pub hour: Option<i16>,
+    pub timezone: Option<String>,
+    pub sections: Vec<ReportSection>,
+    #[serde(default)]
+    pub recipients: ReportRecipients,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "template_visibility", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateVisibility {
+    // Members of the source project's team
+    #[default]
+    Team,
+    // Everyone, once an instance admin approved it
+    Instance,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectTemplate {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub visibility: TemplateVisibility,
+    pub team_id: Uuid,
+    pub source_project_id: Option<Uuid>,
+    pub document: ProjectSnapshot,
+    pub created_by: Option<Uuid>,
+    // None while an instance template waits for approval
+    pub approved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublishTemplateRequest {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub visibility: TemplateVisibility,
+}
+
+// A report the scheduler claimed, with the end of its previous period
+#[derive(Debug, Clone)]
+pub struct DueReport {
+    pub report: ProjectReport,
+    pub previous_sent_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReportTask {
+    pub id: Uuid,
+    pub number: i32,
+    pub title: String,
+    pub assigned_to: Option<Uuid>,
+    pub due_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectActivity {
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub tasks_created: i64,
+    pub tasks_completed: i64,
+    pub comments_added: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TaskAttachment {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub uploaded_by: Option<Uuid>,
+    pub filename: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    #[serde(skip_serializing)]
+    #[schemars(skip)]
+    pub storage_path: String,
+    // None for files stored before hashes were recorded (migration 041)
+    #[serde(skip_serializing)]
+    #[schemars(skip)]
+    pub content_sha256: Option<String>,
+    pub scan_status: AttachmentScanStatus,
+    pub scan_detail: Option<String>,
+    pub scanned_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, Eq, JsonSchema)]
+#[sqlx(type_name = "attachment_scan_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentScanStatus {
+    Pending,
+    Clean,
+    Infected,
+    // The scanner kept failing; the file is downloadable but unscanned
+    Failed,
+}
+
+impl AttachmentScanStatus {
+    pub fn blocks_download(self) -> bool {
+        matches!(self, AttachmentScanStatus::Pending | AttachmentScanStatus::Infected)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignupCode {
+    pub id: Uuid,
+    pub code: String,
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CreateSignupCodeRequest {
+    pub max_uses: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// Per-team quota overrides; `None` means the instance default applies
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TeamLimitOverrides {
+    pub max_projects: Option<i32>,
+    pub max_tasks_per_project: Option<i32>,
+    pub max_members: Option<i32>,
+    pub max_attachment_bytes_per_project: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectTaskCount {
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub tasks: i64,
+    pub attachment_bytes: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, JsonSchema)]
+#[sqlx(type_name = "notification_kind", rename_all = "snake_case")]
+pub enum NotificationKind {
+    TaskAssigned,
+    TaskUnassigned,
+    TaskCommented,
+    TaskStale,
+    TaskMentioned,
+    AuditExportReady,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, JsonSchema)]
+pub struct UserNotification {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: NotificationKind,
+    // None for team notifications
+    pub project_id: Option<Uuid>,
+    pub task_id: Option<Uuid>,
+    pub team_id: Option<Uuid>,
+    pub audit_export_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>, // the latest one when several events were counted
+    pub count: i32,
+    pub read_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// What a notification setting applies to. Reminders are stale task nudges;
+// digest settings decide which of a project's entries the digest email lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "notification_event_type", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEventType {
+    Mention,
+    Assignment,
+    Comment,
+    Reminder,
+    Digest,
+}
+
+impl NotificationEventType {
+    pub const ALL: [NotificationEventType; 5] = [
+        NotificationEventType::Mention,
+        NotificationEventType::Assignment,
+        NotificationEventType::Comment,
+        NotificationEventType::Reminder,
+        NotificationEventType::Digest,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "notification_level", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationLevel {
+    All,
+    // Only mentions get through, whatever the event type
+    MentionsOnly,
+    None,
+}
+
+impl NotificationLevel {
+    pub fn allows(&self, event_type: NotificationEventType) -> bool {
+        match self {
+            NotificationLevel::All => true,
+            NotificationLevel::MentionsOnly => event_type == NotificationEventType::Mention,
+            NotificationLevel::None => false,
+        }
+    }
+}
+
+// One event type's level; `inherited` when no setting of this scope overrides
+// the one it falls back to (the user's default, or `all` for defaults)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationSetting {
+    pub event_type: NotificationEventType,
+    pub level: NotificationLevel,
+    pub inherited: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskReadState {
+    pub task_id: Uuid,
+    pub unread: bool,
+    pub unread_comment_count: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+pub enum AuditAction {
+    ImpersonationStarted,
+    ImpersonatedRequest,
+    ContentQuarantined,
+    ContentRestored,
+    ContentPurged,
+    AttachmentQuarantined,
+    AccountsMerged,
+    RetentionApplied,
+    ContentApproved,
+    ContentRejected,
+    AuditExported,
+}
+
+// Tasks and comments one user created in a time range, as selected for moderation
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UserContent {
+    pub tasks: Vec<ModeratedTask>,
+    pub comments: Vec<ModeratedComment>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModeratedTask {
+    pub id: Uuid,
+    pub project_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModeratedComment {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub project_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareToken {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub token: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Task counts behind a project's status badge
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BadgeStats {
+    pub project_name: String,
+    pub open_tasks: i64,
+    pub done_tasks: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, sqlx::Type, PartialEq, JsonSchema)]
+#[sqlx(type_name = "announcement_severity", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AnnouncementSeverity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+// Instance-wide banner; active from starts_at until ends_at
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Announcement {
+    pub id: Uuid,
+    pub message: String,
+    pub severity: AnnouncementSeverity,
+    pub starts_at: DateTime<Utc>,
+    // None keeps it up until it's changed or deleted
+    pub ends_at: Option<DateTime<Utc>>,
+    pub dismissible: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateAnnouncementRequest {
+    pub message: String,
+    #[serde(default)]
+    pub severity: AnnouncementSeverity,
+    // Defaults to now
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    // Defaults to true
+    pub dismissible: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UpdateAnnouncementRequest {
+    pub message: Option<String>,
+    pub severity: Option<AnnouncementSeverity>,
+    pub starts_at: Option<DateTime<Utc>>,
+    // null makes it open-ended
+    #[serde(default, with = "crate::utils::double_option")]
+    pub ends_at: Option<Option<DateTime<Utc>>>,
+    pub dismissible: Option<bool>,
+}
+
+// A project or board in the trash
+#[derive(Debug, Clone, Serialize)]
+pub struct Trashed<T> {
+    #[serde(flatten)]
+    pub item: T,
+    pub deleted_at: DateTime<Utc>,
+    // When the trash sweep removes it for good
+    pub purge_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    pub action: AuditAction,
+    // Who did it, and on whose behalf when impersonating
+    pub actor_id: Option<Uuid>,
+    pub subject_id: Option<Uuid>,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub status: Option<i16>,
+    // Counts and such for system actions
+    pub details: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TeamFlagOverride {
+    pub team_id: Uuid,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureFlag {
+    pub name: String,
+    // Default for teams without an override
+    pub enabled: bool,
+    pub description: Option<String>,
+    pub overrides: Vec<TeamFlagOverride>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetFeatureFlagRequest {
+    pub enabled: bool,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetTeamFlagRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "moderation_status", rename_all = "lowercase")]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "held_content_type", rename_all = "lowercase")]
+#[serde(rename_all = "snake_case")]
+pub enum HeldContentType {
+    Task,
+    Comment,
+}
+
+// A task or comment held by content screening (see screening.rs)
+#[derive(Debug, Clone, Serialize)]
+pub struct ModerationItem {
+    pub id: Uuid,
+    pub content_type: HeldContentType,
+    pub task_id: Uuid,
+    pub comment_id: Option<Uuid>,
+    pub project_id: Uuid,
+    pub author: Option<UserSummary>,
+    // The task's title and description, or the comment
+    pub content: String,
+    pub score: f32,
+    pub reasons: Vec<String>,
+    pub language: Option<String>,
+    pub status: ModerationStatus,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationQueueQuery {
+    pub status: Option<ModerationStatus>,
+    pub limit: Option<i64>,
+}
+
+// What the author gets back for content held for moderation
+#[derive(Debug, Serialize)]
+pub struct PendingModeration<T> {
+    #[serde(flatten)]
+    pub content: T,
+    pub pending_moderation: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditExportFormat {
+    Csv,
+    Jsonl,
+}
+
+impl AuditExportFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuditExportFormat::Csv => "csv",
+            AuditExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, JsonSchema)]
+#[sqlx(type_name = "audit_export_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AuditExportStatus {
+    Pending,
+    Ready,
+    Failed,
+}
+
+// A team audit export written in the background (see audit_export.rs)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AuditExport {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub requested_by: Option<Uuid>,
+    pub format: AuditExportFormat,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub status: AuditExportStatus,
+    pub entry_count: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub storage_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditExportQuery {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub format: Option<AuditExportFormat>,
+}
+
+// One line of a team audit export
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditExportEntry {
+    #[serde(skip)]
+    pub id: Uuid,
+    pub at: DateTime<Utc>,
+    // task_created, status_changed, comment_added or an audit log action
+    pub action: String,
+    pub actor_id: Option<Uuid>,
+    pub actor: Option<String>,
+    // Only captured for audit log entries recorded during a request
+    pub ip_address: Option<String>,
+    pub entity_type: String,
+    pub entity_id: Option<Uuid>,
+    pub project_id: Option<Uuid>,
+    pub project_name: Option<String>,
+    pub summary: String,
+}
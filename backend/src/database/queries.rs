@@ -1,17 +1,44 @@
-use sqlx::{PgPool, Row};
+use sqlx::{postgres::PgRow, PgPool, Row};
 use uuid::Uuid;
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::{BTreeMap, HashMap};
 
 use crate::database::models::{
     User, CreateUserRequest, UpdateUserRequest,
     Team, CreateTeamRequest, TeamMember, TeamRole,
     Project, CreateProjectRequest, ProjectMember, ProjectRole, UserSummary,
-    Task, CreateTaskRequest, UpdateTaskRequest, TaskStatus, TaskPriority,
-    Board, CreateBoardRequest, UpdateBoardRequest, MoveTaskRequest,
-    TaskComment, CreateTaskCommentRequest
+    Task, CreateTaskRequest, UpdateTaskRequest, TaskStatus, ColumnPlacement, TaskPriority, PriorityCounts, TaskWorkload, DueDate, CycleTimeReport, CycleTimeTask, DurationPercentiles, StatusDurations,
+    TaskListFilter, TaskGroupBy, TaskGroupCount, TaskSort, SortOrder, ColumnOrder,
+    Board, CreateBoardRequest, UpdateBoardRequest, MoveTaskRequest, NudgeDirection,
+    TaskComment, CreateTaskCommentRequest,
+    Job, JobStatus,
+    EmailLogEntry, EmailStatus,
+    ProjectIntegration, TaskLink, TaskLinkKind, TaskRelation, TaskRelationType, RelatedTask,
+    DigestFrequency, DigestPreferences, DueDigest, DigestTask, AgendaTask, DigestMention, ProjectActivity, DueDay, TeamProjectCount, TeamCounters, ProjectCounters, AttachmentStorage, ProjectStorage, InstanceStats,
+    TeamLimitOverrides, ProjectTaskCount,
+    NotificationKind, UserNotification, TaskReadState, ProjectWorkflow, OnboardingTemplate, StalenessRules, StaleTask, MentionSettings, TaskMention, ProjectStatusFilter, SignupCode, TaskAttachment, AttachmentScanStatus,
+    AuditAction, AuditLogEntry, AccountMerge, RoleConflict, FeatureFlag, TeamFlagOverride, SetFeatureFlagRequest,
+    UserContent, ModeratedTask, ModeratedComment, Trashed, ShareToken, BadgeStats,
+    Announcement, AnnouncementSeverity, AutoAddPolicy, TeamSettings, RetentionPolicy, RetentionCounts,
+    ProjectReport, CreateProjectReportRequest, DueReport, ReportTask,
+    CommentContext, TaskContext, TaskContextProject, TaskContextBoard,
+    ProjectTemplate, PublishTemplateRequest, AutomationAction, BoardAutomations, ApiToken, ProjectInvitation,
+    UserActivity, UserActivityKind, QuickSwitchCandidate, QuickSwitchContext, QuickSwitchItem, QuickSwitchKind,
+    SnapshotComment, StoredTaskSnapshot, TaskSnapshot, HeldContentType, ModerationItem, ModerationStatus,
+    AuditExport, AuditExportEntry, AuditExportFormat, NotificationEventType, NotificationLevel,
 };
+use crate::auth::authz::Scope;
+use crate::automations::{self, BoardRule};
+use crate::database::timing::query_timer;
+use crate::project_templates::ProjectSnapshot;
+use crate::screening::Screening;
+use crate::positions;
+use crate::utils::colors;
+use crate::utils::double_option;
 use crate::utils::errors::AppError;
+use crate::utils::i18n::Message;
+use crate::utils::validation;
 
 pub struct UserQueries;
 
@@ -21,6 +48,7 @@ impl UserQueries {
         request: &CreateUserRequest,
         password_hash: &str,
     ) -> Result<User, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
             INSERT INTO users (email, username, display_name, password_hash)
@@ -51,6 +79,7 @@ impl UserQueries {
     }
 
     pub async fn get_user_by_id(pool: &PgPool, user_id: Uuid) -> Result<User, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             "SELECT id, email, username, password_hash, display_name, avatar_url, is_active, created_at, updated_at FROM users WHERE id = $1 AND is_active = true"
         )
@@ -74,6 +103,7 @@ impl UserQueries {
     }
 
     pub async fn get_user_by_email(pool: &PgPool, email: &str) -> Result<User, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             "SELECT id, email, username, password_hash, display_name, avatar_url, is_active, created_at, updated_at FROM users WHERE email = $1 AND is_active = true"
         )
@@ -97,6 +127,7 @@ impl UserQueries {
     }
 
     pub async fn get_user_by_username(pool: &PgPool, username: &str) -> Result<User, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             "SELECT id, email, username, password_hash, display_name, avatar_url, is_active, created_at, updated_at FROM users WHERE username = $1 AND is_active = true"
         )
@@ -124,12 +155,13 @@ impl UserQueries {
         user_id: Uuid,
         request: &UpdateUserRequest,
     ) -> Result<User, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
             UPDATE users 
             SET 
                 display_name = COALESCE($2, display_name),
-                avatar_url = COALESCE($3, avatar_url),
+                avatar_url = CASE WHEN $4 THEN NULL ELSE COALESCE($3, avatar_url) END,
                 updated_at = NOW()
             WHERE id = $1 AND is_active = true
             RETURNING id, email, username, password_hash, display_name, avatar_url, is_active, created_at, updated_at
@@ -137,7 +169,8 @@ impl UserQueries {
         )
         .bind(user_id)
         .bind(&request.display_name)
-        .bind(&request.avatar_url)
+        .bind(request.avatar_url.clone().flatten())
+        .bind(double_option::is_cleared(&request.avatar_url))
         .fetch_one(pool)
         .await?;
 
@@ -157,6 +190,7 @@ impl UserQueries {
     }
 
     pub async fn check_email_exists(pool: &PgPool, email: &str) -> Result<bool, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             "SELECT EXISTS(SELECT 1 FROM users WHERE email = $1)"
         )
@@ -167,18 +201,92 @@ impl UserQueries {
         Ok(row.get::<bool, _>("exists"))
     }
 
+    // Also true when an existing username merely looks the same, e.g. "adrnin"
+    // for "admin", so nobody can register a lookalike of someone else's name
     pub async fn check_username_exists(pool: &PgPool, username: &str) -> Result<bool, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            "SELECT EXISTS(SELECT 1 FROM users WHERE username = $1 OR {skeleton} = $2)",
+            skeleton = USERNAME_SKELETON_SQL,
+        ))
+        .bind(username)
+        .bind(validation::username_skeleton(username))
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.get::<bool, _>("exists"))
+    }
+
+    // Insert-or-update keyed by a fixed id, used by the seed command
+    pub async fn upsert_user(
+        pool: &PgPool,
+        user_id: Uuid,
+        request: &CreateUserRequest,
+        password_hash: &str,
+    ) -> Result<User, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
-            "SELECT EXISTS(SELECT 1 FROM users WHERE username = $1)"
+            r#"
+            INSERT INTO users (id, email, username, display_name, password_hash)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (id) DO UPDATE
+            SET email = EXCLUDED.email,
+                username = EXCLUDED.username,
+                display_name = EXCLUDED.display_name,
+                password_hash = EXCLUDED.password_hash,
+                is_active = true
+            RETURNING id, email, username, password_hash, display_name, avatar_url, is_active, created_at, updated_at
+            "#
         )
-        .bind(username)
+        .bind(user_id)
+        .bind(&request.email)
+        .bind(&request.username)
+        .bind(&request.display_name)
+        .bind(password_hash)
+        .fetch_one(pool)
+        .await?;
+
+        let user = User {
+            id: row.get("id"),
+            email: row.get("email"),
+            username: row.get("username"),
+            password_hash: row.get("password_hash"),
+            display_name: row.get("display_name"),
+            avatar_url: row.get("avatar_url"),
+            is_active: row.get("is_active"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        };
+
+        Ok(user)
+    }
+
+    pub async fn is_instance_admin(pool: &PgPool, user_id: Uuid) -> Result<bool, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            "SELECT EXISTS(SELECT 1 FROM instance_admins WHERE user_id = $1)"
+        )
+        .bind(user_id)
         .fetch_one(pool)
         .await?;
 
         Ok(row.get::<bool, _>("exists"))
     }
 
+    pub async fn grant_instance_admin(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query(
+            "INSERT INTO instance_admins (user_id) VALUES ($1) ON CONFLICT (user_id) DO NOTHING"
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn deactivate_user(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
         sqlx::query(
             "UPDATE users SET is_active = false, updated_at = NOW() WHERE id = $1"
         )
@@ -188,6 +296,204 @@ impl UserQueries {
 
         Ok(())
     }
+
+    pub async fn update_password_hash(pool: &PgPool, user_id: Uuid, password_hash: &str) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query(
+            "UPDATE users SET password_hash = $2, updated_at = NOW() WHERE id = $1 AND is_active = true"
+        )
+        .bind(user_id)
+        .bind(password_hash)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Moves memberships, tasks and comments of `merged_id` to `survivor_id`,
+    // deactivates `merged_id` and records the merge, all in one transaction.
+    // Where both were members of the same team or project the higher role is
+    // kept. Personal settings and instance admin rights stay with the
+    // deactivated account.
+    pub async fn merge_into(pool: &PgPool, survivor_id: Uuid, merged_id: Uuid) -> Result<AccountMerge, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        // Locks both accounts, so concurrent merges of either one wait
+        let locked = sqlx::query("SELECT id FROM users WHERE id IN ($1, $2) AND is_active = true FOR UPDATE")
+            .bind(survivor_id)
+            .bind(merged_id)
+            .fetch_all(&mut *tx)
+            .await?;
+        if locked.len() != 2 {
+            return Err(AppError::NotFound("User not found".to_string()));
+        }
+
+        let rows = sqlx::query(
+            r#"
+            SELECT s.team_id AS id, s.role AS survivor_role, m.role AS merged_role
+            FROM team_members s
+            JOIN team_members m ON m.team_id = s.team_id AND m.user_id = $2
+            WHERE s.user_id = $1 AND s.role <> m.role
+            ORDER BY s.team_id
+            "#
+        )
+        .bind(survivor_id)
+        .bind(merged_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let team_conflicts: Vec<RoleConflict<TeamRole>> = rows
+            .iter()
+            .map(|row| role_conflict(row.get("id"), row.get("survivor_role"), row.get("merged_role"), TeamRole::rank))
+            .collect();
+        sqlx::query(
+            r#"
+            UPDATE team_members s SET role = m.role
+            FROM team_members m
+            WHERE m.team_id = s.team_id AND m.user_id = $2 AND s.user_id = $1 AND s.team_id = ANY($3)
+            "#
+        )
+        .bind(survivor_id)
+        .bind(merged_id)
+        .bind(upgraded_ids(&team_conflicts))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            DELETE FROM team_members m
+            WHERE m.user_id = $2
+              AND EXISTS (SELECT 1 FROM team_members s WHERE s.team_id = m.team_id AND s.user_id = $1)
+            "#
+        )
+        .bind(survivor_id)
+        .bind(merged_id)
+        .execute(&mut *tx)
+        .await?;
+        let team_memberships = sqlx::query("UPDATE team_members SET user_id = $1 WHERE user_id = $2")
+            .bind(survivor_id)
+            .bind(merged_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        let rows = sqlx::query(
+            r#"
+            SELECT s.project_id AS id, s.role AS survivor_role, m.role AS merged_role
+            FROM project_members s
+            JOIN project_members m ON m.project_id = s.project_id AND m.user_id = $2
+            WHERE s.user_id = $1 AND s.role <> m.role
+            ORDER BY s.project_id
+            "#
+        )
+        .bind(survivor_id)
+        .bind(merged_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let project_conflicts: Vec<RoleConflict<ProjectRole>> = rows
+            .iter()
+            .map(|row| role_conflict(row.get("id"), row.get("survivor_role"), row.get("merged_role"), ProjectRole::rank))
+            .collect();
+        sqlx::query(
+            r#"
+            UPDATE project_members s SET role = m.role
+            FROM project_members m
+            WHERE m.project_id = s.project_id AND m.user_id = $2 AND s.user_id = $1 AND s.project_id = ANY($3)
+            "#
+        )
+        .bind(survivor_id)
+        .bind(merged_id)
+        .bind(upgraded_ids(&project_conflicts))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            DELETE FROM project_members m
+            WHERE m.user_id = $2
+              AND EXISTS (SELECT 1 FROM project_members s WHERE s.project_id = m.project_id AND s.user_id = $1)
+            "#
+        )
+        .bind(survivor_id)
+        .bind(merged_id)
+        .execute(&mut *tx)
+        .await?;
+        let project_memberships = sqlx::query("UPDATE project_members SET user_id = $1 WHERE user_id = $2")
+            .bind(survivor_id)
+            .bind(merged_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        let assigned_tasks = sqlx::query("UPDATE tasks SET assigned_to = $1 WHERE assigned_to = $2")
+            .bind(survivor_id)
+            .bind(merged_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        let comments = sqlx::query("UPDATE task_comments SET user_id = $1 WHERE user_id = $2")
+            .bind(survivor_id)
+            .bind(merged_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        for (table, column) in MERGED_AUTHORSHIP_COLUMNS {
+            sqlx::query(&format!("UPDATE {table} SET {column} = $1 WHERE {column} = $2"))
+                .bind(survivor_id)
+                .bind(merged_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query("UPDATE users SET is_active = false, updated_at = NOW() WHERE id = $1")
+            .bind(merged_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("INSERT INTO audit_log (action, actor_id, subject_id) VALUES ($1, $2, $3)")
+            .bind(AuditAction::AccountsMerged)
+            .bind(survivor_id)
+            .bind(merged_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(AccountMerge {
+            merged_user_id: merged_id,
+            team_memberships,
+            project_memberships,
+            assigned_tasks,
+            comments,
+            team_conflicts,
+            project_conflicts,
+        })
+    }
+}
+
+// Who created or received something, moved along in an account merge
+const MERGED_AUTHORSHIP_COLUMNS: [(&str, &str); 11] = [
+    ("teams", "created_by"),
+    ("projects", "created_by"),
+    ("boards", "created_by"),
+    ("tasks", "created_by"),
+    ("project_integrations", "created_by"),
+    ("task_attachments", "uploaded_by"),
+    ("task_relations", "created_by"),
+    ("notifications", "user_id"),
+    ("notifications", "actor_id"),
+    ("task_mentions", "mentioned_by"),
+    ("task_status_transitions", "changed_by"),
+];
+
+// The higher of the two roles wins
+fn role_conflict<R>(id: Uuid, survivor_role: R, merged_role: R, rank: fn(&R) -> u8) -> RoleConflict<R> {
+    if rank(&merged_role) > rank(&survivor_role) {
+        RoleConflict { id, kept_role: merged_role, dropped_role: survivor_role, upgraded: true }
+    } else {
+        RoleConflict { id, kept_role: survivor_role, dropped_role: merged_role, upgraded: false }
+    }
+}
+
+fn upgraded_ids<R>(conflicts: &[RoleConflict<R>]) -> Vec<Uuid> {
+    conflicts.iter().filter(|conflict| conflict.upgraded).map(|conflict| conflict.id).collect()
 }
 
 pub struct TeamQueries;
@@ -198,6 +504,7 @@ impl TeamQueries {
         request: &CreateTeamRequest,
         created_by: Uuid,
     ) -> Result<Team, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
             INSERT INTO teams (name, description, created_by)
@@ -227,6 +534,7 @@ impl TeamQueries {
     }
 
     pub async fn get_team_by_id(pool: &PgPool, team_id: Uuid) -> Result<Team, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             "SELECT id, name, description, created_by, created_at, updated_at FROM teams WHERE id = $1"
         )
@@ -246,7 +554,39 @@ impl TeamQueries {
         Ok(team)
     }
 
+    // Every team on the instance, for operators
+    pub async fn list_teams(pool: &PgPool) -> Result<Vec<TeamProjectCount>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT t.id, t.name, t.description, t.created_by, t.created_at, t.updated_at,
+                   COALESCE(c.project_count, 0) AS project_count
+            FROM teams t
+            LEFT JOIN team_counters c ON c.team_id = t.id
+            ORDER BY t.name
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| TeamProjectCount {
+                team: Team {
+                    id: row.get("id"),
+                    name: row.get("name"),
+                    description: row.get("description"),
+                    created_by: row.get("created_by"),
+                    created_at: row.get("created_at"),
+                    updated_at: row.get("updated_at"),
+                },
+                project_count: row.get("project_count"),
+            })
+            .collect())
+    }
+
     pub async fn get_user_teams(pool: &PgPool, user_id: Uuid) -> Result<Vec<Team>, AppError> {
+        let _timer = query_timer!();
         let rows = sqlx::query(
             r#"
             SELECT t.id, t.name, t.description, t.created_by, t.created_at, t.updated_at
@@ -277,6 +617,7 @@ impl TeamQueries {
         team_id: Uuid,
         request: &CreateTeamRequest,
     ) -> Result<Team, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
             UPDATE teams 
@@ -303,7 +644,45 @@ impl TeamQueries {
         Ok(team)
     }
 
+    // Insert-or-update keyed by a fixed id, used by the seed command.
+    // Unlike create_team this doesn't add the creator as a member.
+    pub async fn upsert_team(
+        pool: &PgPool,
+        team_id: Uuid,
+        request: &CreateTeamRequest,
+        created_by: Uuid,
+    ) -> Result<Team, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            INSERT INTO teams (id, name, description, created_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (id) DO UPDATE
+            SET name = EXCLUDED.name, description = EXCLUDED.description
+            RETURNING id, name, description, created_by, created_at, updated_at
+            "#
+        )
+        .bind(team_id)
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(created_by)
+        .fetch_one(pool)
+        .await?;
+
+        let team = Team {
+            id: row.get("id"),
+            name: row.get("name"),
+            description: row.get("description"),
+            created_by: row.get("created_by"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        };
+
+        Ok(team)
+    }
+
     pub async fn delete_team(pool: &PgPool, team_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
         sqlx::query("DELETE FROM teams WHERE id = $1")
             .bind(team_id)
             .execute(pool)
@@ -312,32 +691,18 @@ impl TeamQueries {
         Ok(())
     }
 
+    // Also adds the new member to projects per the team's join policy (see
+    // TeamSettingsQueries). Project memberships they already have are kept.
     pub async fn add_team_member(
         pool: &PgPool,
         team_id: Uuid,
         user_id: Uuid,
         role: TeamRole,
     ) -> Result<TeamMember, AppError> {
-        let row = sqlx::query(
-            r#"
-            INSERT INTO team_members (team_id, user_id, role)
-            VALUES ($1, $2, $3)
-            RETURNING id, team_id, user_id, role, joined_at
-            "#
-        )
-        .bind(team_id)
-        .bind(user_id)
-        .bind(&role)
-        .fetch_one(pool)
-        .await?;
-
-        let member = TeamMember {
-            id: row.get("id"),
-            team_id: row.get("team_id"),
-            user_id: row.get("user_id"),
-            role: row.get("role"),
-            joined_at: row.get("joined_at"),
-        };
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+        let member = insert_team_member(&mut tx, team_id, user_id, role).await?;
+        tx.commit().await?;
 
         Ok(member)
     }
@@ -347,6 +712,7 @@ impl TeamQueries {
         team_id: Uuid,
         user_id: Uuid,
     ) -> Result<(), AppError> {
+        let _timer = query_timer!();
         sqlx::query("DELETE FROM team_members WHERE team_id = $1 AND user_id = $2")
             .bind(team_id)
             .bind(user_id)
@@ -362,6 +728,7 @@ impl TeamQueries {
         user_id: Uuid,
         role: TeamRole,
     ) -> Result<TeamMember, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
             UPDATE team_members 
@@ -388,6 +755,7 @@ impl TeamQueries {
     }
 
     pub async fn get_team_members(pool: &PgPool, team_id: Uuid) -> Result<Vec<(TeamMember, UserSummary)>, AppError> {
+        let _timer = query_timer!();
         let rows = sqlx::query(
             r#"
             SELECT 
@@ -403,26 +771,56 @@ impl TeamQueries {
         .fetch_all(pool)
         .await?;
 
-        let members = rows.into_iter().map(|row| {
-            let member = TeamMember {
-                id: row.get("id"),
-                team_id: row.get("team_id"),
-                user_id: row.get("user_id"),
-                role: row.get("role"),
-                joined_at: row.get("joined_at"),
-            };
+        let members = rows.into_iter().map(team_member_from_row).collect();
 
-            let user = UserSummary {
-                id: member.user_id,
-                username: row.get("username"),
-                display_name: row.get("display_name"),
-                avatar_url: row.get("avatar_url"),
-            };
+        Ok(members)
+    }
 
-            (member, user)
-        }).collect();
+    // One page of a team's members, optionally only those with `role`, along
+    // with how many members match in total
+    pub async fn list_team_members(
+        pool: &PgPool,
+        team_id: Uuid,
+        role: Option<TeamRole>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<(TeamMember, UserSummary)>, i64), AppError> {
+        let _timer = query_timer!();
+        let total: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM team_members tm
+            INNER JOIN users u ON tm.user_id = u.id
+            WHERE tm.team_id = $1 AND u.is_active = true
+              AND ($2::team_role IS NULL OR tm.role = $2)
+            "#
+        )
+        .bind(team_id)
+        .bind(&role)
+        .fetch_one(pool)
+        .await?;
 
-        Ok(members)
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                tm.id, tm.team_id, tm.user_id, tm.role, tm.joined_at,
+                u.username, u.display_name, u.avatar_url
+            FROM team_members tm
+            INNER JOIN users u ON tm.user_id = u.id
+            WHERE tm.team_id = $1 AND u.is_active = true
+              AND ($2::team_role IS NULL OR tm.role = $2)
+            ORDER BY tm.role, u.display_name, tm.id
+            LIMIT $3 OFFSET $4
+            "#
+        )
+        .bind(team_id)
+        .bind(&role)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        Ok((rows.into_iter().map(team_member_from_row).collect(), total))
     }
 
     pub async fn get_user_team_role(
@@ -430,6 +828,7 @@ impl TeamQueries {
         team_id: Uuid,
         user_id: Uuid,
     ) -> Result<Option<TeamRole>, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             "SELECT role FROM team_members WHERE team_id = $1 AND user_id = $2"
         )
@@ -446,6 +845,7 @@ impl TeamQueries {
         team_id: Uuid,
         user_id: Uuid,
     ) -> Result<bool, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             "SELECT EXISTS(SELECT 1 FROM team_members WHERE team_id = $1 AND user_id = $2)"
         )
@@ -460,17 +860,61 @@ impl TeamQueries {
 
 pub struct ProjectQueries;
 
+// Expects the member columns plus the user's username, display_name and avatar_url
+fn team_member_from_row(row: PgRow) -> (TeamMember, UserSummary) {
+    let member = TeamMember {
+        id: row.get("id"),
+        team_id: row.get("team_id"),
+        user_id: row.get("user_id"),
+        role: row.get("role"),
+        joined_at: row.get("joined_at"),
+    };
+
+    let user = UserSummary {
+        id: member.user_id,
+        username: row.get("username"),
+        display_name: row.get("display_name"),
+        avatar_url: row.get("avatar_url"),
+    };
+
+    (member, user)
+}
+
+fn project_member_from_row(row: PgRow) -> (ProjectMember, UserSummary) {
+    let member = ProjectMember {
+        id: row.get("id"),
+        project_id: row.get("project_id"),
+        user_id: row.get("user_id"),
+        role: row.get("role"),
+        joined_at: row.get("joined_at"),
+    };
+
+    let user = UserSummary {
+        id: member.user_id,
+        username: row.get("username"),
+        display_name: row.get("display_name"),
+        avatar_url: row.get("avatar_url"),
+    };
+
+    (member, user)
+}
+
 impl ProjectQueries {
+    // With a template, its settings and boards are set up in the same transaction
     pub async fn create_project(
         pool: &PgPool,
         request: &CreateProjectRequest,
         created_by: Uuid,
+        template: Option<&ProjectSnapshot>,
     ) -> Result<Project, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
         let row = sqlx::query(
             r#"
-            INSERT INTO projects (name, description, team_id, created_by, color)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING id, name, description, team_id, created_by, color, is_active, created_at, updated_at
+            INSERT INTO projects (name, description, team_id, created_by, color, contrast_text, icon)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, name, description, team_id, created_by, color, contrast_text, icon, is_active, archived_at, created_at, updated_at
             "#
         )
         .bind(&request.name)
@@ -478,7 +922,9 @@ impl ProjectQueries {
         .bind(request.team_id)
         .bind(created_by)
         .bind(&request.color)
-        .fetch_one(pool)
+        .bind(request.color.as_deref().map(colors::contrast_text))
+        .bind(&request.icon)
+        .fetch_one(&mut *tx)
         .await?;
 
         let project = Project {
@@ -488,20 +934,99 @@ impl ProjectQueries {
             team_id: row.get("team_id"),
             created_by: row.get("created_by"),
             color: row.get("color"),
+            contrast_text: row.get("contrast_text"),
+            icon: row.get("icon"),
             is_active: row.get("is_active"),
+            archived_at: row.get("archived_at"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
         };
 
         // Add creator as admin
-        ProjectQueries::add_project_member(pool, project.id, created_by, ProjectRole::Admin).await?;
+        sqlx::query("INSERT INTO project_members (project_id, user_id, role) VALUES ($1, $2, $3)")
+            .bind(project.id)
+            .bind(created_by)
+            .bind(ProjectRole::Admin)
+            .execute(&mut *tx)
+            .await?;
 
+        if request.auto_add_members {
+            sqlx::query(
+                r#"
+                INSERT INTO project_members (project_id, user_id, role)
+                SELECT $1, tm.user_id, COALESCE(s.default_project_role, 'member')
+                FROM team_members tm
+                LEFT JOIN team_settings s ON s.team_id = tm.team_id
+                WHERE tm.team_id = $2
+                ON CONFLICT (project_id, user_id) DO NOTHING
+                "#
+            )
+            .bind(project.id)
+            .bind(project.team_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if let Some(template) = template {
+            sqlx::query("UPDATE projects SET workflow = $2, onboarding_template = $3, staleness = $4 WHERE id = $1")
+                .bind(project.id)
+                .bind(serde_json::to_value(&template.workflow)?)
+                .bind(serde_json::to_value(&template.onboarding_template)?)
+                .bind(serde_json::to_value(&template.staleness)?)
+                .execute(&mut *tx)
+                .await?;
+
+            // The first board takes the place of the default board the insert trigger created
+            for (index, board) in template.boards.iter().enumerate() {
+                let board = board.clone().into_create_request();
+                let columns = serde_json::to_value(board.columns.unwrap_or_default())?;
+                let config = serde_json::to_value(board.config.unwrap_or_default())?;
+                let swimlane_config = serde_json::to_value(board.swimlane_config.unwrap_or_default())?;
+
+                if index == 0 {
+                    sqlx::query(
+                        r#"
+                        UPDATE boards
+                        SET name = $2, description = $3, columns = $4, config = $5, swimlane_config = $6
+                        WHERE project_id = $1 AND is_default = true
+                        "#
+                    )
+                    .bind(project.id)
+                    .bind(&board.name)
+                    .bind(&board.description)
+                    .bind(columns)
+                    .bind(config)
+                    .bind(swimlane_config)
+                    .execute(&mut *tx)
+                    .await?;
+                } else {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO boards (project_id, name, description, columns, config, swimlane_config, created_by)
+                        VALUES ($1, $2, $3, $4, $5, $6, $7)
+                        "#
+                    )
+                    .bind(project.id)
+                    .bind(&board.name)
+                    .bind(&board.description)
+                    .bind(columns)
+                    .bind(config)
+                    .bind(swimlane_config)
+                    .bind(created_by)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+            }
+        }
+
+        tx.commit().await?;
         Ok(project)
     }
 
     pub async fn get_project_by_id(pool: &PgPool, project_id: Uuid) -> Result<Project, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
-            "SELECT id, name, description, team_id, created_by, color, is_active, created_at, updated_at FROM projects WHERE id = $1"
+            "SELECT id, name, description, team_id, created_by, color, contrast_text, icon, is_active, archived_at, created_at, updated_at FROM projects WHERE id = $1 AND deleted_at IS NULL"
         )
         .bind(project_id)
         .fetch_one(pool)
@@ -514,7 +1039,10 @@ impl ProjectQueries {
             team_id: row.get("team_id"),
             created_by: row.get("created_by"),
             color: row.get("color"),
+            contrast_text: row.get("contrast_text"),
+            icon: row.get("icon"),
             is_active: row.get("is_active"),
+            archived_at: row.get("archived_at"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
         };
@@ -522,12 +1050,24 @@ impl ProjectQueries {
         Ok(project)
     }
 
+    // Includes archived and trashed projects, unlike get_team_projects
+    pub async fn get_team_project_ids(pool: &PgPool, team_id: Uuid) -> Result<Vec<Uuid>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query("SELECT id FROM projects WHERE team_id = $1")
+            .bind(team_id)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| row.get("id")).collect())
+    }
+
     pub async fn get_team_projects(pool: &PgPool, team_id: Uuid) -> Result<Vec<Project>, AppError> {
+        let _timer = query_timer!();
         let rows = sqlx::query(
             r#"
-            SELECT id, name, description, team_id, created_by, color, is_active, created_at, updated_at
+            SELECT id, name, description, team_id, created_by, color, contrast_text, icon, is_active, archived_at, created_at, updated_at
             FROM projects 
-            WHERE team_id = $1 AND is_active = true
+            WHERE team_id = $1 AND is_active = true AND deleted_at IS NULL
             ORDER BY name
             "#
         )
@@ -542,7 +1082,10 @@ impl ProjectQueries {
             team_id: row.get("team_id"),
             created_by: row.get("created_by"),
             color: row.get("color"),
+            contrast_text: row.get("contrast_text"),
+            icon: row.get("icon"),
             is_active: row.get("is_active"),
+            archived_at: row.get("archived_at"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
         }).collect();
@@ -550,17 +1093,29 @@ impl ProjectQueries {
         Ok(projects)
     }
 
-    pub async fn get_user_projects(pool: &PgPool, user_id: Uuid) -> Result<Vec<Project>, AppError> {
+    pub async fn get_user_projects(
+        pool: &PgPool,
+        user_id: Uuid,
+        status: ProjectStatusFilter,
+    ) -> Result<Vec<Project>, AppError> {
+        let _timer = query_timer!();
+        let is_active = match status {
+            ProjectStatusFilter::Active => Some(true),
+            ProjectStatusFilter::Archived => Some(false),
+            ProjectStatusFilter::All => None,
+        };
+
         let rows = sqlx::query(
             r#"
-            SELECT p.id, p.name, p.description, p.team_id, p.created_by, p.color, p.is_active, p.created_at, p.updated_at
+            SELECT p.id, p.name, p.description, p.team_id, p.created_by, p.color, p.contrast_text, p.icon, p.is_active, p.archived_at, p.created_at, p.updated_at
             FROM projects p
             INNER JOIN project_members pm ON p.id = pm.project_id
-            WHERE pm.user_id = $1 AND p.is_active = true
+            WHERE pm.user_id = $1 AND ($2::bool IS NULL OR p.is_active = $2) AND p.deleted_at IS NULL
             ORDER BY p.name
             "#
         )
         .bind(user_id)
+        .bind(is_active)
         .fetch_all(pool)
         .await?;
 
@@ -571,7 +1126,10 @@ impl ProjectQueries {
             team_id: row.get("team_id"),
             created_by: row.get("created_by"),
             color: row.get("color"),
+            contrast_text: row.get("contrast_text"),
+            icon: row.get("icon"),
             is_active: row.get("is_active"),
+            archived_at: row.get("archived_at"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
         }).collect();
@@ -584,18 +1142,21 @@ impl ProjectQueries {
         project_id: Uuid,
         request: &CreateProjectRequest,
     ) -> Result<Project, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
             UPDATE projects 
-            SET name = $2, description = $3, color = $4, updated_at = NOW()
-            WHERE id = $1
-            RETURNING id, name, description, team_id, created_by, color, is_active, created_at, updated_at
+            SET name = $2, description = $3, color = $4, contrast_text = $5, icon = $6, updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING id, name, description, team_id, created_by, color, contrast_text, icon, is_active, archived_at, created_at, updated_at
             "#
         )
         .bind(project_id)
         .bind(&request.name)
         .bind(&request.description)
         .bind(&request.color)
+        .bind(request.color.as_deref().map(colors::contrast_text))
+        .bind(&request.icon)
         .fetch_one(pool)
         .await?;
 
@@ -606,7 +1167,10 @@ impl ProjectQueries {
             team_id: row.get("team_id"),
             created_by: row.get("created_by"),
             color: row.get("color"),
+            contrast_text: row.get("contrast_text"),
+            icon: row.get("icon"),
             is_active: row.get("is_active"),
+            archived_at: row.get("archived_at"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
         };
@@ -615,8 +1179,9 @@ impl ProjectQueries {
     }
 
     pub async fn archive_project(pool: &PgPool, project_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
         sqlx::query(
-            "UPDATE projects SET is_active = false, updated_at = NOW() WHERE id = $1"
+            "UPDATE projects SET is_active = false, archived_at = COALESCE(archived_at, NOW()), updated_at = NOW() WHERE id = $1"
         )
         .bind(project_id)
         .execute(pool)
@@ -626,8 +1191,9 @@ impl ProjectQueries {
     }
 
     pub async fn activate_project(pool: &PgPool, project_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
         sqlx::query(
-            "UPDATE projects SET is_active = true, updated_at = NOW() WHERE id = $1"
+            "UPDATE projects SET is_active = true, archived_at = NULL, updated_at = NOW() WHERE id = $1"
         )
         .bind(project_id)
         .execute(pool)
@@ -636,50 +1202,135 @@ impl ProjectQueries {
         Ok(())
     }
 
-    pub async fn delete_project(pool: &PgPool, project_id: Uuid) -> Result<(), AppError> {
-        sqlx::query("DELETE FROM projects WHERE id = $1")
-            .bind(project_id)
-            .execute(pool)
-            .await?;
-
-        Ok(())
-    }
-
-    pub async fn add_project_member(
+    // Insert-or-update keyed by a fixed id, used by the seed command.
+    // Unlike create_project this doesn't add the creator as a member.
+    pub async fn upsert_project(
         pool: &PgPool,
         project_id: Uuid,
-        user_id: Uuid,
-        role: ProjectRole,
-    ) -> Result<ProjectMember, AppError> {
+        request: &CreateProjectRequest,
+        created_by: Uuid,
+    ) -> Result<Project, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
-            INSERT INTO project_members (project_id, user_id, role)
-            VALUES ($1, $2, $3)
-            RETURNING id, project_id, user_id, role, joined_at
+            INSERT INTO projects (id, name, description, team_id, created_by, color, contrast_text, icon)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (id) DO UPDATE
+            SET name = EXCLUDED.name,
+                description = EXCLUDED.description,
+                color = EXCLUDED.color,
+                contrast_text = EXCLUDED.contrast_text,
+                icon = EXCLUDED.icon,
+                is_active = true,
+                archived_at = NULL
+            RETURNING id, name, description, team_id, created_by, color, contrast_text, icon, is_active, archived_at, created_at, updated_at
             "#
         )
         .bind(project_id)
-        .bind(user_id)
-        .bind(&role)
-        .fetch_one(pool)
-        .await?;
-
-        let member = ProjectMember {
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(request.team_id)
+        .bind(created_by)
+        .bind(&request.color)
+        .bind(request.color.as_deref().map(colors::contrast_text))
+        .bind(&request.icon)
+        .fetch_one(pool)
+        .await?;
+
+        let project = Project {
             id: row.get("id"),
-            project_id: row.get("project_id"),
-            user_id: row.get("user_id"),
-            role: row.get("role"),
-            joined_at: row.get("joined_at"),
+            name: row.get("name"),
+            description: row.get("description"),
+            team_id: row.get("team_id"),
+            created_by: row.get("created_by"),
+            color: row.get("color"),
+            contrast_text: row.get("contrast_text"),
+            icon: row.get("icon"),
+            is_active: row.get("is_active"),
+            archived_at: row.get("archived_at"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
         };
 
+        Ok(project)
+    }
+
+    // Moves the project and its boards to the trash as one batch. Boards already
+    // in the trash keep their own batch, so a restore leaves them there.
+    pub async fn delete_project(pool: &PgPool, project_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let batch_id = Uuid::new_v4();
+        let mut tx = pool.begin().await?;
+
+        let result = sqlx::query(
+            "UPDATE projects SET deleted_at = NOW(), delete_batch_id = $2 WHERE id = $1 AND deleted_at IS NULL"
+        )
+        .bind(project_id)
+        .bind(batch_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Project not found".to_string()));
+        }
+
+        sqlx::query(
+            "UPDATE boards SET deleted_at = NOW(), delete_batch_id = $2 WHERE project_id = $1 AND deleted_at IS NULL"
+        )
+        .bind(project_id)
+        .bind(batch_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn add_project_member(
+        pool: &PgPool,
+        project_id: Uuid,
+        user_id: Uuid,
+        role: ProjectRole,
+    ) -> Result<ProjectMember, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+        let member = insert_project_member(&mut tx, project_id, user_id, role).await?;
+        tx.commit().await?;
+
         Ok(member)
     }
 
+    // Adds the member and creates the template's tasks for them in one
+    // transaction. Due dates count from the day they joined (UTC).
+    pub async fn add_project_member_with_onboarding(
+        pool: &PgPool,
+        project_id: Uuid,
+        user_id: Uuid,
+        role: ProjectRole,
+        template: &OnboardingTemplate,
+        created_by: Uuid,
+    ) -> Result<(ProjectMember, Vec<Task>), AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+        let member = insert_project_member(&mut tx, project_id, user_id, role).await?;
+
+        let joined_on = member.joined_at.date_naive();
+        let mut tasks = Vec::with_capacity(template.tasks.len());
+        for item in &template.tasks {
+            let request = item.task_request(user_id, joined_on);
+            tasks.push(insert_task(&mut tx, project_id, &request, created_by, TaskStatus::Todo, ColumnPlacement::Bottom).await?);
+        }
+
+        tx.commit().await?;
+        Ok((member, tasks))
+    }
+
     pub async fn remove_project_member(
         pool: &PgPool,
         project_id: Uuid,
         user_id: Uuid,
     ) -> Result<(), AppError> {
+        let _timer = query_timer!();
         sqlx::query("DELETE FROM project_members WHERE project_id = $1 AND user_id = $2")
             .bind(project_id)
             .bind(user_id)
@@ -689,12 +1340,58 @@ impl ProjectQueries {
         Ok(())
     }
 
+    // Removes the user from the project and unassigns their tasks in it; the
+    // tasks themselves stay. The admin rows are locked so two admins can't both
+    // leave at once. Returns the ids of the unassigned tasks.
+    pub async fn leave_project(
+        pool: &PgPool,
+        project_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<Uuid>, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        let admins: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT user_id FROM project_members WHERE project_id = $1 AND role = 'admin' FOR UPDATE"
+        )
+        .bind(project_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if admins.contains(&user_id) && admins.len() <= 1 {
+            return Err(AppError::Validation("Cannot remove the last admin from project".to_string()));
+        }
+
+        let unassigned = sqlx::query_scalar(
+            "UPDATE tasks SET assigned_to = NULL WHERE project_id = $1 AND assigned_to = $2 RETURNING id"
+        )
+        .bind(project_id)
+        .bind(user_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let removed = sqlx::query("DELETE FROM project_members WHERE project_id = $1 AND user_id = $2")
+            .bind(project_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        if removed == 0 {
+            return Err(AppError::NotFound("Project not found".to_string()));
+        }
+
+        tx.commit().await?;
+        Ok(unassigned)
+    }
+
     pub async fn update_project_member_role(
         pool: &PgPool,
         project_id: Uuid,
         user_id: Uuid,
         role: ProjectRole,
     ) -> Result<ProjectMember, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
             UPDATE project_members 
@@ -721,6 +1418,7 @@ impl ProjectQueries {
     }
 
     pub async fn get_project_members(pool: &PgPool, project_id: Uuid) -> Result<Vec<(ProjectMember, UserSummary)>, AppError> {
+        let _timer = query_timer!();
         let rows = sqlx::query(
             r#"
             SELECT 
@@ -736,609 +1434,7137 @@ impl ProjectQueries {
         .fetch_all(pool)
         .await?;
 
-        let members = rows.into_iter().map(|row| {
-            let member = ProjectMember {
-                id: row.get("id"),
-                project_id: row.get("project_id"),
-                user_id: row.get("user_id"),
-                role: row.get("role"),
-                joined_at: row.get("joined_at"),
-            };
-
-            let user = UserSummary {
-                id: member.user_id,
-                username: row.get("username"),
-                display_name: row.get("display_name"),
-                avatar_url: row.get("avatar_url"),
-            };
-
-            (member, user)
-        }).collect();
+        let members = rows.into_iter().map(project_member_from_row).collect();
 
         Ok(members)
     }
 
-    pub async fn get_user_project_role(
+    // One page of a project's members, optionally only those with `role`,
+    // along with how many members match in total
+    pub async fn list_project_members(
         pool: &PgPool,
         project_id: Uuid,
-        user_id: Uuid,
-    ) -> Result<Option<ProjectRole>, AppError> {
-        let row = sqlx::query(
-            "SELECT role FROM project_members WHERE project_id = $1 AND user_id = $2"
+        role: Option<ProjectRole>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<(ProjectMember, UserSummary)>, i64), AppError> {
+        let _timer = query_timer!();
+        let total: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM project_members pm
+            INNER JOIN users u ON pm.user_id = u.id
+            WHERE pm.project_id = $1 AND u.is_active = true
+              AND ($2::project_role IS NULL OR pm.role = $2)
+            "#
         )
         .bind(project_id)
-        .bind(user_id)
-        .fetch_optional(pool)
+        .bind(&role)
+        .fetch_one(pool)
         .await?;
 
-        Ok(row.map(|r| r.get("role")))
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                pm.id, pm.project_id, pm.user_id, pm.role, pm.joined_at,
+                u.username, u.display_name, u.avatar_url
+            FROM project_members pm
+            INNER JOIN users u ON pm.user_id = u.id
+            WHERE pm.project_id = $1 AND u.is_active = true
+              AND ($2::project_role IS NULL OR pm.role = $2)
+            ORDER BY pm.role, u.display_name, pm.id
+            LIMIT $3 OFFSET $4
+            "#
+        )
+        .bind(project_id)
+        .bind(&role)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        Ok((rows.into_iter().map(project_member_from_row).collect(), total))
     }
 
-    pub async fn is_project_member(
+    // Every member's open tasks in the project, members without any included.
+    // `from` (inclusive) and `to` (exclusive) limit the tasks by when they're due.
+    pub async fn get_project_workload(
         pool: &PgPool,
         project_id: Uuid,
-        user_id: Uuid,
-    ) -> Result<bool, AppError> {
-        let row = sqlx::query(
-            "SELECT EXISTS(SELECT 1 FROM project_members WHERE project_id = $1 AND user_id = $2)"
-        )
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<(ProjectMember, UserSummary, TaskWorkload)>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT
+                pm.id, pm.project_id, pm.user_id, pm.role, pm.joined_at,
+                u.username, u.display_name, u.avatar_url,
+                COUNT(t.id) AS open_tasks,
+                COUNT(t.id) FILTER (WHERE t.priority = 'critical') AS critical,
+                COUNT(t.id) FILTER (WHERE t.priority = 'high') AS high,
+                COUNT(t.id) FILTER (WHERE t.priority = 'medium') AS medium,
+                COUNT(t.id) FILTER (WHERE t.priority = 'low') AS low,
+                COUNT(t.id) FILTER (WHERE {due_at} < NOW()) AS overdue
+            FROM project_members pm
+            INNER JOIN users u ON pm.user_id = u.id
+            LEFT JOIN digest_preferences tz ON tz.user_id = pm.user_id
+            LEFT JOIN tasks t ON t.project_id = pm.project_id AND t.assigned_to = pm.user_id
+                AND t.status <> 'done' AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+                AND ($2::timestamptz IS NULL OR {due_at} >= $2)
+                AND ($3::timestamptz IS NULL OR {due_at} < $3)
+            WHERE pm.project_id = $1 AND u.is_active = true
+            GROUP BY pm.id, u.id
+            ORDER BY open_tasks DESC, u.display_name, pm.id
+            "#,
+            due_at = DUE_AT_SQL,
+        ))
         .bind(project_id)
-        .bind(user_id)
-        .fetch_one(pool)
+        .bind(from)
+        .bind(to)
+        .fetch_all(pool)
         .await?;
 
-        Ok(row.get::<bool, _>("exists"))
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let workload = TaskWorkload {
+                    open_tasks: row.get("open_tasks"),
+                    by_priority: PriorityCounts {
+                        critical: row.get("critical"),
+                        high: row.get("high"),
+                        medium: row.get("medium"),
+                        low: row.get("low"),
+                    },
+                    overdue: row.get("overdue"),
+                };
+                let (member, user) = project_member_from_row(row);
+                (member, user, workload)
+            })
+            .collect())
     }
-}
-
-pub struct TaskQueries;
 
-impl TaskQueries {
-    pub async fn create_task(
+    // Members with their open tasks across all projects and their status
+    // changes and comments in this one since `since`, for assignment suggestions
+    pub async fn get_assignment_candidates(
         pool: &PgPool,
         project_id: Uuid,
-        request: &CreateTaskRequest,
-        created_by: Uuid,
-    ) -> Result<Task, AppError> {
-        // Get the next position for this project
-        let position_row = sqlx::query(
-            "SELECT COALESCE(MAX(position), 0) + 1 as next_position FROM tasks WHERE project_id = $1"
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(ProjectMember, UserSummary, i64, i64)>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                pm.id, pm.project_id, pm.user_id, pm.role, pm.joined_at,
+                u.username, u.display_name, u.avatar_url,
+                (
+                    SELECT COUNT(*) FROM tasks t
+                    JOIN projects p ON p.id = t.project_id AND p.deleted_at IS NULL
+                    WHERE t.assigned_to = pm.user_id AND t.status <> 'done' AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+                ) AS open_tasks,
+                (
+                    SELECT COUNT(*) FROM task_status_transitions tr
+                    JOIN tasks t ON t.id = tr.task_id
+                    WHERE tr.changed_by = pm.user_id AND t.project_id = pm.project_id AND tr.changed_at >= $2
+                ) + (
+                    SELECT COUNT(*) FROM task_comments c
+                    JOIN tasks t ON t.id = c.task_id
+                    WHERE c.user_id = pm.user_id AND t.project_id = pm.project_id AND c.created_at >= $2
+                      AND NOT c.is_system
+                ) AS recent_actions
+            FROM project_members pm
+            INNER JOIN users u ON pm.user_id = u.id
+            WHERE pm.project_id = $1 AND u.is_active = true
+            ORDER BY u.display_name, pm.id
+            "#
         )
         .bind(project_id)
-        .fetch_one(pool)
+        .bind(since)
+        .fetch_all(pool)
         .await?;
-        
-        let position: i32 = position_row.get("next_position");
-        let priority = request.priority.clone().unwrap_or(TaskPriority::Medium);
 
-        let row = sqlx::query(
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let open_tasks = row.get("open_tasks");
+                let recent_actions = row.get("recent_actions");
+                let (member, user) = project_member_from_row(row);
+                (member, user, open_tasks, recent_actions)
+            })
+            .collect())
+    }
+
+    // Which of `labels` each user has completed tasks with in the project,
+    // leaving out `task_id` itself
+    pub async fn get_completed_labels(
+        pool: &PgPool,
+        project_id: Uuid,
+        task_id: Uuid,
+        labels: &[String],
+    ) -> Result<HashMap<Uuid, Vec<String>>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
             r#"
-            INSERT INTO tasks (title, description, project_id, created_by, assigned_to, priority, due_date, tags, position)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-            RETURNING id, title, description, project_id, created_by, assigned_to, status, priority, due_date, tags, position, created_at, updated_at
+            SELECT DISTINCT t.assigned_to, l.label
+            FROM tasks t
+            -- Tasks created without tags store a JSON null
+            CROSS JOIN LATERAL jsonb_array_elements_text(
+                CASE WHEN jsonb_typeof(t.tags) = 'array' THEN t.tags ELSE '[]'::jsonb END
+            ) AS l(label)
+            WHERE t.project_id = $1 AND t.id <> $2 AND t.status = 'done'
+              AND t.assigned_to IS NOT NULL AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+              AND l.label = ANY($3)
             "#
         )
-        .bind(&request.title)
-        .bind(&request.description)
         .bind(project_id)
-        .bind(created_by)
-        .bind(&request.assigned_to)
-        .bind(&priority)
-        .bind(&request.due_date)
-        .bind(serde_json::to_value(&request.tags).unwrap_or(serde_json::Value::Array(vec![])))
-        .bind(position)
-        .fetch_one(pool)
+        .bind(task_id)
+        .bind(labels)
+        .fetch_all(pool)
         .await?;
 
-        Ok(Task {
-            id: row.get("id"),
-            title: row.get("title"),
-            description: row.get("description"),
-            project_id: row.get("project_id"),
-            created_by: row.get("created_by"),
-            assigned_to: row.get("assigned_to"),
-            status: row.get("status"),
-            priority: row.get("priority"),
-            due_date: row.get("due_date"),
-            tags: serde_json::from_value(row.get("tags")).unwrap_or(None),
-            position: row.get("position"),
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-        })
+        let mut completed: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for row in rows {
+            completed.entry(row.get("assigned_to")).or_default().push(row.get("label"));
+        }
+        Ok(completed)
     }
 
-    pub async fn get_project_tasks(
+    // Tasks of the project last moved to Done within [from, to) and still
+    // there, with the time they spent in each status and the cycle and lead
+    // time percentiles across them
+    pub async fn get_cycle_times(
         pool: &PgPool,
         project_id: Uuid,
-    ) -> Result<Vec<Task>, AppError> {
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<CycleTimeReport, AppError> {
+        let _timer = query_timer!();
         let rows = sqlx::query(
             r#"
-            SELECT id, title, description, project_id, created_by, assigned_to, status, priority, due_date, tags, position, created_at, updated_at
-            FROM tasks 
-            WHERE project_id = $1 
-            ORDER BY position ASC, created_at ASC
+            WITH completed AS (
+                SELECT t.id, t.number, t.title, t.created_at, MAX(tr.changed_at) AS completed_at
+                FROM tasks t
+                INNER JOIN task_status_transitions tr ON tr.task_id = t.id AND tr.to_status = 'done'
+                WHERE t.project_id = $1 AND t.status = 'done' AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+                GROUP BY t.id
+                HAVING MAX(tr.changed_at) >= $2 AND MAX(tr.changed_at) < $3
+            ),
+            -- The status each task was created in, then every status it moved to
+            entries AS (
+                SELECT c.id AS task_id, c.created_at AS entered_at, 0 AS step, first.from_status AS status
+                FROM completed c
+                CROSS JOIN LATERAL (
+                    SELECT from_status FROM task_status_transitions
+                    WHERE task_id = c.id
+                    ORDER BY changed_at
+                    LIMIT 1
+                ) first
+                UNION ALL
+                SELECT tr.task_id, tr.changed_at, 1, tr.to_status
+                FROM task_status_transitions tr
+                INNER JOIN completed c ON c.id = tr.task_id
+                WHERE tr.changed_at <= c.completed_at
+            ),
+            -- The last span, in Done since completion, has no end
+            spans AS (
+                SELECT task_id, status, entered_at,
+                    LEAD(entered_at) OVER (PARTITION BY task_id ORDER BY entered_at, step) AS left_at
+                FROM entries
+            ),
+            timed AS (
+                SELECT c.id, c.number, c.title, c.created_at, c.completed_at,
+                    MIN(s.entered_at) FILTER (WHERE s.status <> 'todo') AS started_at,
+                    COALESCE(ROUND(SUM(EXTRACT(EPOCH FROM s.left_at - s.entered_at)) FILTER (WHERE s.status = 'todo')), 0)::bigint AS todo_seconds,
+                    COALESCE(ROUND(SUM(EXTRACT(EPOCH FROM s.left_at - s.entered_at)) FILTER (WHERE s.status = 'inprogress')), 0)::bigint AS in_progress_seconds,
+                    COALESCE(ROUND(SUM(EXTRACT(EPOCH FROM s.left_at - s.entered_at)) FILTER (WHERE s.status = 'review')), 0)::bigint AS review_seconds,
+                    COALESCE(ROUND(SUM(EXTRACT(EPOCH FROM s.left_at - s.entered_at)) FILTER (WHERE s.status = 'done')), 0)::bigint AS done_seconds
+                FROM completed c
+                INNER JOIN spans s ON s.task_id = c.id
+                GROUP BY c.id, c.number, c.title, c.created_at, c.completed_at
+            ),
+            durations AS (
+                SELECT *,
+                    EXTRACT(EPOCH FROM completed_at - started_at)::float8 AS cycle_seconds,
+                    EXTRACT(EPOCH FROM completed_at - created_at)::float8 AS lead_seconds
+                FROM timed
+            ),
+            summary AS (
+                SELECT
+                    percentile_cont(ARRAY[0.5, 0.85, 0.95]) WITHIN GROUP (ORDER BY cycle_seconds) AS cycle_percentiles,
+                    percentile_cont(ARRAY[0.5, 0.85, 0.95]) WITHIN GROUP (ORDER BY lead_seconds) AS lead_percentiles
+                FROM durations
+            )
+            SELECT durations.*, summary.*
+            FROM durations CROSS JOIN summary
+            ORDER BY completed_at, number
             "#
         )
         .bind(project_id)
+        .bind(from)
+        .bind(to)
         .fetch_all(pool)
         .await?;
 
-        let tasks = rows.into_iter().map(|row| Task {
-            id: row.get("id"),
-            title: row.get("title"),
-            description: row.get("description"),
-            project_id: row.get("project_id"),
-            created_by: row.get("created_by"),
-            assigned_to: row.get("assigned_to"),
-            status: row.get("status"),
-            priority: row.get("priority"),
-            due_date: row.get("due_date"),
-            tags: serde_json::from_value(row.get("tags")).unwrap_or(None),
-            position: row.get("position"),
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-        }).collect();
+        let percentiles = |row: Option<&PgRow>, column: &str| {
+            let values: Vec<f64> = row.map(|row| row.get(column)).unwrap_or_default();
+            let value = |index: usize| values.get(index).map(|seconds| seconds.round() as i64);
+            DurationPercentiles { p50: value(0), p85: value(1), p95: value(2) }
+        };
 
-        Ok(tasks)
+        Ok(CycleTimeReport {
+            from,
+            to,
+            completed: rows.len(),
+            cycle_time: percentiles(rows.first(), "cycle_percentiles"),
+            lead_time: percentiles(rows.first(), "lead_percentiles"),
+            tasks: rows
+                .iter()
+                .map(|row| CycleTimeTask {
+                    task_id: row.get("id"),
+                    number: row.get("number"),
+                    title: row.get("title"),
+                    created_at: row.get("created_at"),
+                    started_at: row.get("started_at"),
+                    completed_at: row.get("completed_at"),
+                    lead_time_seconds: row.get::<f64, _>("lead_seconds").round() as i64,
+                    cycle_time_seconds: row.get::<f64, _>("cycle_seconds").round() as i64,
+                    time_in_status: StatusDurations {
+                        todo: row.get("todo_seconds"),
+                        in_progress: row.get("in_progress_seconds"),
+                        review: row.get("review_seconds"),
+                        done: row.get("done_seconds"),
+                    },
+                })
+                .collect(),
+        })
     }
 
-    pub async fn get_task_by_id(
+    pub async fn get_user_project_role(
         pool: &PgPool,
-        task_id: Uuid,
-    ) -> Result<Task, AppError> {
+        project_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<ProjectRole>, AppError> {
+        let _timer = query_timer!();
+        // Members of a trashed project keep their rows for a restore, but no access
         let row = sqlx::query(
             r#"
-            SELECT id, title, description, project_id, created_by, assigned_to, status, priority, due_date, tags, position, created_at, updated_at
-            FROM tasks 
-            WHERE id = $1
+            SELECT pm.role
+            FROM project_members pm
+            JOIN projects p ON p.id = pm.project_id AND p.deleted_at IS NULL
+            WHERE pm.project_id = $1 AND pm.user_id = $2
             "#
         )
-        .bind(task_id)
+        .bind(project_id)
+        .bind(user_id)
         .fetch_optional(pool)
         .await?;
 
-        match row {
-            Some(row) => Ok(Task {
-                id: row.get("id"),
-                title: row.get("title"),
-                description: row.get("description"),
-                project_id: row.get("project_id"),
-                created_by: row.get("created_by"),
-                assigned_to: row.get("assigned_to"),
-                status: row.get("status"),
-                priority: row.get("priority"),
-                due_date: row.get("due_date"),
-                tags: serde_json::from_value(row.get("tags")).unwrap_or(None),
-                position: row.get("position"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            }),
-            None => Err(AppError::NotFound("Task not found".to_string())),
-        }
+        Ok(row.map(|r| r.get("role")))
     }
 
-    pub async fn update_task(
+    pub async fn is_project_member(
         pool: &PgPool,
-        task_id: Uuid,
-        request: &UpdateTaskRequest,
-    ) -> Result<Task, AppError> {
+        project_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
-            UPDATE tasks 
-            SET title = COALESCE($2, title),
-                description = COALESCE($3, description),
-                assigned_to = COALESCE($4, assigned_to),
-                status = COALESCE($5, status),
-                priority = COALESCE($6, priority),
-                due_date = COALESCE($7, due_date),
-                tags = COALESCE($8, tags)
-            WHERE id = $1
-            RETURNING id, title, description, project_id, created_by, assigned_to, status, priority, due_date, tags, position, created_at, updated_at
+            SELECT EXISTS(
+                SELECT 1 FROM project_members pm
+                JOIN projects p ON p.id = pm.project_id AND p.deleted_at IS NULL
+                WHERE pm.project_id = $1 AND pm.user_id = $2
+            )
             "#
         )
-        .bind(task_id)
-        .bind(&request.title)
-        .bind(&request.description)
-        .bind(&request.assigned_to)
-        .bind(&request.status)
-        .bind(&request.priority)
-        .bind(&request.due_date)
-        .bind(request.tags.as_ref().map(|tags| serde_json::to_value(tags).unwrap_or(serde_json::Value::Null)))
-        .fetch_optional(pool)
+        .bind(project_id)
+        .bind(user_id)
+        .fetch_one(pool)
         .await?;
 
+        Ok(row.get::<bool, _>("exists"))
+    }
+
+    pub async fn is_project_active(pool: &PgPool, project_id: Uuid) -> Result<bool, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT is_active FROM projects WHERE id = $1 AND deleted_at IS NULL")
+            .bind(project_id)
+            .fetch_optional(pool)
+            .await?;
+
         match row {
-            Some(row) => Ok(Task {
-                id: row.get("id"),
-                title: row.get("title"),
-                description: row.get("description"),
-                project_id: row.get("project_id"),
-                created_by: row.get("created_by"),
-                assigned_to: row.get("assigned_to"),
-                status: row.get("status"),
-                priority: row.get("priority"),
-                due_date: row.get("due_date"),
-                tags: serde_json::from_value(row.get("tags")).unwrap_or(None),
-                position: row.get("position"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            }),
-            None => Err(AppError::NotFound("Task not found".to_string())),
+            Some(row) => Ok(row.get("is_active")),
+            None => Err(AppError::NotFound("Project not found".to_string())),
         }
     }
 
-    pub async fn delete_task(
+    pub async fn get_project_workflow(pool: &PgPool, project_id: Uuid) -> Result<ProjectWorkflow, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT workflow FROM projects WHERE id = $1")
+            .bind(project_id)
+            .fetch_optional(pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(serde_json::from_value(row.get("workflow")).unwrap_or_default()),
+            None => Err(AppError::NotFound("Project not found".to_string())),
+        }
+    }
+
+    pub async fn update_project_workflow(
         pool: &PgPool,
-        task_id: Uuid,
+        project_id: Uuid,
+        workflow: &ProjectWorkflow,
     ) -> Result<(), AppError> {
-        let result = sqlx::query("DELETE FROM tasks WHERE id = $1")
-            .bind(task_id)
+        let _timer = query_timer!();
+        sqlx::query("UPDATE projects SET workflow = $2, updated_at = NOW() WHERE id = $1")
+            .bind(project_id)
+            .bind(serde_json::to_value(workflow)?)
             .execute(pool)
             .await?;
 
-        if result.rows_affected() == 0 {
-            return Err(AppError::NotFound("Task not found".to_string()));
+        Ok(())
+    }
+
+    pub async fn get_onboarding_template(pool: &PgPool, project_id: Uuid) -> Result<OnboardingTemplate, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT onboarding_template FROM projects WHERE id = $1")
+            .bind(project_id)
+            .fetch_optional(pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(serde_json::from_value(row.get("onboarding_template")).unwrap_or_default()),
+            None => Err(AppError::NotFound("Project not found".to_string())),
         }
+    }
+
+    pub async fn update_onboarding_template(
+        pool: &PgPool,
+        project_id: Uuid,
+        template: &OnboardingTemplate,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query("UPDATE projects SET onboarding_template = $2, updated_at = NOW() WHERE id = $1")
+            .bind(project_id)
+            .bind(serde_json::to_value(template)?)
+            .execute(pool)
+            .await?;
 
         Ok(())
     }
 
-    pub async fn move_task(
+    pub async fn get_staleness_rules(pool: &PgPool, project_id: Uuid) -> Result<StalenessRules, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT staleness FROM projects WHERE id = $1")
+            .bind(project_id)
+            .fetch_optional(pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(serde_json::from_value(row.get("staleness")).unwrap_or_default()),
+            None => Err(AppError::NotFound("Project not found".to_string())),
+        }
+    }
+
+    pub async fn update_staleness_rules(
         pool: &PgPool,
-        task_id: Uuid,
-        new_status: TaskStatus,
-        new_position: i32,
-    ) -> Result<Task, AppError> {
-        let row = sqlx::query(
-            r#"
-            UPDATE tasks 
-            SET status = $2, position = $3
-            WHERE id = $1
-            RETURNING id, title, description, project_id, created_by, assigned_to, status, priority, due_date, tags, position, created_at, updated_at
-            "#
-        )
-        .bind(task_id)
-        .bind(&new_status)
-        .bind(new_position)
-        .fetch_optional(pool)
-        .await?;
+        project_id: Uuid,
+        rules: &StalenessRules,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query("UPDATE projects SET staleness = $2, updated_at = NOW() WHERE id = $1")
+            .bind(project_id)
+            .bind(serde_json::to_value(rules)?)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_mention_settings(pool: &PgPool, project_id: Uuid) -> Result<MentionSettings, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT mention_settings FROM projects WHERE id = $1")
+            .bind(project_id)
+            .fetch_optional(pool)
+            .await?;
 
         match row {
-            Some(row) => Ok(Task {
-                id: row.get("id"),
-                title: row.get("title"),
-                description: row.get("description"),
-                project_id: row.get("project_id"),
-                created_by: row.get("created_by"),
-                assigned_to: row.get("assigned_to"),
-                status: row.get("status"),
-                priority: row.get("priority"),
-                due_date: row.get("due_date"),
-                tags: serde_json::from_value(row.get("tags")).unwrap_or(None),
-                position: row.get("position"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            }),
-            None => Err(AppError::NotFound("Task not found".to_string())),
+            Some(row) => Ok(serde_json::from_value(row.get("mention_settings")).unwrap_or_default()),
+            None => Err(AppError::NotFound("Project not found".to_string())),
         }
     }
 
-    pub async fn get_user_assigned_tasks(
+    pub async fn update_mention_settings(
+        pool: &PgPool,
+        project_id: Uuid,
+        settings: &MentionSettings,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query("UPDATE projects SET mention_settings = $2, updated_at = NOW() WHERE id = $1")
+            .bind(project_id)
+            .bind(serde_json::to_value(settings)?)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Projects of the same team as `project_id` that the user is a member of,
+    // including `project_id` itself
+    pub async fn get_member_team_project_ids(
         pool: &PgPool,
+        project_id: Uuid,
         user_id: Uuid,
-    ) -> Result<Vec<Task>, AppError> {
-        let rows = sqlx::query(
+    ) -> Result<Vec<Uuid>, AppError> {
+        let _timer = query_timer!();
+        let ids = sqlx::query_scalar(
             r#"
-            SELECT id, title, description, project_id, created_by, assigned_to, status, priority, due_date, tags, position, created_at, updated_at
-            FROM tasks 
-            WHERE assigned_to = $1 
-            ORDER BY due_date ASC NULLS LAST, priority DESC, created_at ASC
+            SELECT p.id
+            FROM projects p
+            JOIN projects current ON current.team_id = p.team_id AND current.id = $1
+            JOIN project_members pm ON pm.project_id = p.id AND pm.user_id = $2
+            WHERE p.deleted_at IS NULL
             "#
         )
+        .bind(project_id)
         .bind(user_id)
         .fetch_all(pool)
         .await?;
 
-        let tasks = rows.into_iter().map(|row| Task {
-            id: row.get("id"),
-            title: row.get("title"),
-            description: row.get("description"),
-            project_id: row.get("project_id"),
-            created_by: row.get("created_by"),
-            assigned_to: row.get("assigned_to"),
-            status: row.get("status"),
-            priority: row.get("priority"),
-            due_date: row.get("due_date"),
-            tags: serde_json::from_value(row.get("tags")).unwrap_or(None),
-            position: row.get("position"),
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-        }).collect();
-
-        Ok(tasks)
+        Ok(ids)
     }
-}
 
-pub struct BoardQueries;
-
-impl BoardQueries {
-    pub async fn create_board(
+    // Newest change to the project, its tasks or its boards. Deletes touch
+    // projects.updated_at (see migration 003), so this also moves on removals.
+    pub async fn get_project_last_modified(
         pool: &PgPool,
         project_id: Uuid,
-        request: &CreateBoardRequest,
-        created_by: Uuid,
-    ) -> Result<Board, AppError> {
-        let columns = request.columns.clone()
-            .unwrap_or_else(|| vec!["Todo".to_string(), "In Progress".to_string(), "Review".to_string(), "Done".to_string()]);
-
+    ) -> Result<DateTime<Utc>, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
-            INSERT INTO boards (name, description, project_id, created_by, columns)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING id, name, description, project_id, created_by, columns, is_default, created_at, updated_at
+            SELECT GREATEST(
+                p.updated_at,
+                (SELECT MAX(t.updated_at) FROM tasks t WHERE t.project_id = p.id),
+                (SELECT MAX(b.updated_at) FROM boards b WHERE b.project_id = p.id)
+            ) AS last_modified
+            FROM projects p
+            WHERE p.id = $1
             "#
         )
-        .bind(&request.name)
-        .bind(&request.description)
         .bind(project_id)
-        .bind(created_by)
-        .bind(serde_json::to_value(&columns).unwrap())
-        .fetch_one(pool)
+        .fetch_optional(pool)
         .await?;
 
-        Ok(Board {
-            id: row.get("id"),
-            name: row.get("name"),
-            description: row.get("description"),
-            project_id: row.get("project_id"),
-            created_by: row.get("created_by"),
-            columns: serde_json::from_value(row.get("columns")).unwrap_or(vec![]),
-            is_default: row.get("is_default"),
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-        })
+        match row {
+            Some(row) => Ok(row.get("last_modified")),
+            None => Err(AppError::NotFound("Project not found".to_string())),
+        }
     }
+}
 
-    pub async fn get_project_boards(
+pub struct TaskQueries;
+
+impl TaskQueries {
+    pub async fn create_task(
         pool: &PgPool,
         project_id: Uuid,
-    ) -> Result<Vec<Board>, AppError> {
+        request: &CreateTaskRequest,
+        created_by: Uuid,
+    ) -> Result<Task, AppError> {
+        let _timer = query_timer!();
+        // New tasks go to the end of the Todo column
+        Self::create_task_in_column(pool, project_id, request, created_by, TaskStatus::Todo, ColumnPlacement::Bottom).await
+    }
+
+    // Creates the task at the top or bottom of a status column in one
+    // transaction. Placing it on top shifts the column down to keep it dense.
+    pub async fn create_task_in_column(
+        pool: &PgPool,
+        project_id: Uuid,
+        request: &CreateTaskRequest,
+        created_by: Uuid,
+        status: TaskStatus,
+        placement: ColumnPlacement,
+    ) -> Result<Task, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+        let task = insert_task(&mut tx, project_id, request, created_by, status, placement).await?;
+        tx.commit().await?;
+
+        Ok(task)
+    }
+
+    pub async fn get_project_tasks(
+        pool: &PgPool,
+        project_id: Uuid,
+        include_quarantined: bool,
+    ) -> Result<Vec<Task>, AppError> {
+        let _timer = query_timer!();
         let rows = sqlx::query(
             r#"
-            SELECT id, name, description, project_id, created_by, columns, is_default, created_at, updated_at
-            FROM boards 
-            WHERE project_id = $1 
-            ORDER BY is_default DESC, created_at ASC
+            SELECT id, title, description, project_id, created_by, assigned_to, status, priority, due_date, is_all_day, tags, cover_color, cover_emoji, position, number, created_at, updated_at
+            FROM tasks 
+            WHERE project_id = $1 AND expired_at IS NULL AND ($2 OR quarantined_at IS NULL)
+            ORDER BY position ASC, created_at ASC
             "#
         )
         .bind(project_id)
+        .bind(include_quarantined)
         .fetch_all(pool)
         .await?;
 
-        let boards = rows.into_iter().map(|row| Board {
+        let tasks = rows.into_iter().map(|row| Task {
             id: row.get("id"),
-            name: row.get("name"),
+            title: row.get("title"),
             description: row.get("description"),
             project_id: row.get("project_id"),
             created_by: row.get("created_by"),
-            columns: serde_json::from_value(row.get("columns")).unwrap_or(vec![]),
-            is_default: row.get("is_default"),
+            assigned_to: row.get("assigned_to"),
+            status: row.get("status"),
+            priority: row.get("priority"),
+            due_date: row.get("due_date"),
+            is_all_day: row.get("is_all_day"),
+            tags: row.get::<Option<serde_json::Value>, _>("tags").and_then(|tags| serde_json::from_value(tags).ok()),
+            cover_color: row.get("cover_color"),
+            cover_emoji: row.get("cover_emoji"),
+            position: row.get("position"),
+            number: row.get("number"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
         }).collect();
 
-        Ok(boards)
+        Ok(tasks)
     }
 
-    pub async fn get_board_by_id(
-        pool: &PgPool,
-        board_id: Uuid,
-    ) -> Result<Board, AppError> {
+    // The task's project and the board a link to it opens: the default board,
+    // or the oldest one left
+    pub async fn get_task_context(pool: &PgPool, task_id: Uuid) -> Result<TaskContext, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
-            SELECT id, name, description, project_id, created_by, columns, is_default, created_at, updated_at
-            FROM boards 
-            WHERE id = $1
+            SELECT t.id, t.project_id, t.number, t.title, t.status,
+                   p.name AS project_name, p.team_id, p.is_active,
+                   b.id AS board_id, b.name AS board_name
+            FROM tasks t
+            INNER JOIN projects p ON p.id = t.project_id AND p.deleted_at IS NULL
+            LEFT JOIN LATERAL (
+                SELECT id, name FROM boards
+                WHERE project_id = t.project_id AND deleted_at IS NULL
+                ORDER BY is_default DESC, created_at ASC
+                LIMIT 1
+            ) b ON true
+            WHERE t.id = $1 AND t.expired_at IS NULL
             "#
         )
-        .bind(board_id)
+        .bind(task_id)
         .fetch_optional(pool)
-        .await?;
+        .await?
+        .ok_or_else(|| AppError::NotFound("Task not found".to_string()))?;
 
-        match row {
-            Some(row) => Ok(Board {
+        let status: TaskStatus = row.get("status");
+        let board_id: Option<Uuid> = row.get("board_id");
+        Ok(TaskContext {
+            task: RelatedTask {
                 id: row.get("id"),
-                name: row.get("name"),
-                description: row.get("description"),
                 project_id: row.get("project_id"),
-                created_by: row.get("created_by"),
-                columns: serde_json::from_value(row.get("columns")).unwrap_or(vec![]),
-                is_default: row.get("is_default"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            }),
-            None => Err(AppError::NotFound("Board not found".to_string())),
-        }
+                number: row.get("number"),
+                title: row.get("title"),
+                status,
+            },
+            project: TaskContextProject {
+                id: row.get("project_id"),
+                name: row.get("project_name"),
+                team_id: row.get("team_id"),
+                is_active: row.get("is_active"),
+            },
+            board: board_id.map(|id| TaskContextBoard { id, name: row.get("board_name") }),
+            column_id: status,
+        })
     }
 
-    pub async fn update_board(
+    pub async fn get_task_by_id(
         pool: &PgPool,
-        board_id: Uuid,
-        request: &UpdateBoardRequest,
-    ) -> Result<Board, AppError> {
+        task_id: Uuid,
+    ) -> Result<Task, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
-            UPDATE boards 
-            SET name = COALESCE($2, name),
-                description = COALESCE($3, description),
-                columns = COALESCE($4, columns)
-            WHERE id = $1
-            RETURNING id, name, description, project_id, created_by, columns, is_default, created_at, updated_at
+            SELECT id, title, description, project_id, created_by, assigned_to, status, priority, due_date, is_all_day, tags, cover_color, cover_emoji, position, number, created_at, updated_at
+            FROM tasks 
+            WHERE id = $1 AND expired_at IS NULL
             "#
         )
-        .bind(board_id)
-        .bind(&request.name)
-        .bind(&request.description)
-        .bind(request.columns.as_ref().map(|cols| serde_json::to_value(cols).unwrap()))
+        .bind(task_id)
         .fetch_optional(pool)
         .await?;
 
         match row {
-            Some(row) => Ok(Board {
+            Some(row) => Ok(Task {
                 id: row.get("id"),
-                name: row.get("name"),
+                title: row.get("title"),
                 description: row.get("description"),
                 project_id: row.get("project_id"),
                 created_by: row.get("created_by"),
-                columns: serde_json::from_value(row.get("columns")).unwrap_or(vec![]),
-                is_default: row.get("is_default"),
+                assigned_to: row.get("assigned_to"),
+                status: row.get("status"),
+                priority: row.get("priority"),
+                due_date: row.get("due_date"),
+                is_all_day: row.get("is_all_day"),
+                tags: row.get::<Option<serde_json::Value>, _>("tags").and_then(|tags| serde_json::from_value(tags).ok()),
+                cover_color: row.get("cover_color"),
+                cover_emoji: row.get("cover_emoji"),
+                position: row.get("position"),
+                number: row.get("number"),
                 created_at: row.get("created_at"),
                 updated_at: row.get("updated_at"),
             }),
-            None => Err(AppError::NotFound("Board not found".to_string())),
+            None => Err(AppError::NotFound("Task not found".to_string())),
         }
     }
 
-    pub async fn delete_board(
+    pub async fn update_task(
         pool: &PgPool,
-        board_id: Uuid,
-    ) -> Result<(), AppError> {
-        // Check if this is the default board
-        let is_default_row = sqlx::query(
-            "SELECT is_default FROM boards WHERE id = $1"
+        task_id: Uuid,
+        request: &UpdateTaskRequest,
+        updated_by: Uuid,
+    ) -> Result<Task, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+        let previous: Option<TaskStatus> = sqlx::query_scalar("SELECT status FROM tasks WHERE id = $1 FOR UPDATE")
+            .bind(task_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(previous) = previous else {
+            return Err(AppError::NotFound("Task not found".to_string()));
+        };
+
+        let row = sqlx::query(
+            r#"
+            UPDATE tasks 
+            SET title = COALESCE($2, title),
+                description = CASE WHEN 'description' = ANY($11) THEN NULL ELSE COALESCE($3, description) END,
+                assigned_to = CASE WHEN 'assigned_to' = ANY($11) THEN NULL ELSE COALESCE($4, assigned_to) END,
+                status = COALESCE($5, status),
+                priority = COALESCE($6, priority),
+                due_date = CASE WHEN 'due_date' = ANY($11) THEN NULL ELSE COALESCE($7, due_date) END,
+                is_all_day = CASE WHEN 'due_date' = ANY($11) THEN false ELSE COALESCE($12, is_all_day) END,
+                tags = CASE WHEN 'tags' = ANY($11) THEN NULL ELSE COALESCE($8, tags) END,
+                -- An empty string removes the cover
+                cover_color = NULLIF(COALESCE($9, cover_color), ''),
+                cover_emoji = NULLIF(COALESCE($10, cover_emoji), ''),
+                -- A status change appends the task to its new column
+                position = CASE
+                    WHEN $5::task_status IS NOT NULL AND $5 <> status THEN (
+                        SELECT COALESCE(MAX(other.position), 0) + 1 FROM tasks other
+                        WHERE other.project_id = tasks.project_id AND other.status = $5
+                    )
+                    ELSE position
+                END
+            WHERE id = $1
+            RETURNING id, title, description, project_id, created_by, assigned_to, status, priority, due_date, is_all_day, tags, cover_color, cover_emoji, position, number, created_at, updated_at
+            "#
         )
-        .bind(board_id)
-        .fetch_optional(pool)
+        .bind(task_id)
+        .bind(&request.title)
+        .bind(request.description.clone().flatten())
+        .bind(request.assigned_to.flatten())
+        .bind(&request.status)
+        .bind(&request.priority)
+        .bind(request.due_date.flatten().map(DueDate::instant))
+        .bind(request.tags.as_ref().and_then(Option::as_ref).map(|tags| serde_json::to_value(tags).unwrap_or(serde_json::Value::Null)))
+        .bind(&request.cover_color)
+        .bind(&request.cover_emoji)
+        .bind(request.cleared_fields())
+        .bind(request.due_date.flatten().map(DueDate::is_all_day))
+        .fetch_optional(&mut *tx)
         .await?;
 
-        match is_default_row {
-            Some(row) => {
-                if row.get::<bool, _>("is_default") {
-                    return Err(AppError::Validation("Cannot delete the default board".to_string()));
-                }
-            }
-            None => return Err(AppError::NotFound("Board not found".to_string())),
-        }
+        let task = match row {
+            Some(row) => Task {
+                id: row.get("id"),
+                title: row.get("title"),
+                description: row.get("description"),
+                project_id: row.get("project_id"),
+                created_by: row.get("created_by"),
+                assigned_to: row.get("assigned_to"),
+                status: row.get("status"),
+                priority: row.get("priority"),
+                due_date: row.get("due_date"),
+                is_all_day: row.get("is_all_day"),
+                tags: row.get::<Option<serde_json::Value>, _>("tags").and_then(|tags| serde_json::from_value(tags).ok()),
+                cover_color: row.get("cover_color"),
+                cover_emoji: row.get("cover_emoji"),
+                position: row.get("position"),
+                number: row.get("number"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+            },
+            None => return Err(AppError::NotFound("Task not found".to_string())),
+        };
+        record_status_change(&mut tx, task_id, previous, task.status, Some(updated_by)).await?;
+        tx.commit().await?;
 
-        let result = sqlx::query("DELETE FROM boards WHERE id = $1")
-            .bind(board_id)
+        Ok(task)
+    }
+
+    pub async fn delete_task(
+        pool: &PgPool,
+        task_id: Uuid,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query("DELETE FROM tasks WHERE id = $1")
+            .bind(task_id)
             .execute(pool)
             .await?;
 
         if result.rows_affected() == 0 {
-            return Err(AppError::NotFound("Board not found".to_string()));
+            return Err(AppError::NotFound("Task not found".to_string()));
         }
 
         Ok(())
     }
-}
 
-pub struct TaskCommentQueries;
+    // Puts the task at `new_position` (1-based, clamped to the column) of the
+    // `new_status` column. Both affected columns are renumbered densely while
+    // their locks are held. `rules` run in the same transaction when the task
+    // changes columns; returns the task after them and the comments recording
+    // the rules that did something.
+    pub async fn move_task(
+        pool: &PgPool,
+        task_id: Uuid,
+        new_status: TaskStatus,
+        new_position: i32,
+        rules: &[BoardRule],
+        user_id: Uuid,
+    ) -> Result<(Task, Vec<TaskComment>), AppError> {
+        let _timer = query_timer!();
+        let current = Self::get_task_by_id(pool, task_id).await?;
 
-impl TaskCommentQueries {
-    pub async fn create_comment(
+        let mut tx = pool.begin().await?;
+        lock_task_move(&mut tx, &current, new_status).await?;
+        let task = reposition_task(&mut tx, &current, new_status, new_position, user_id).await?;
+        let (task, comments) = run_automations(&mut tx, task, rules, user_id).await?;
+        tx.commit().await?;
+
+        Ok((task, comments))
+    }
+
+    // Moves the task one step in `direction`, deciding where under the same
+    // locks as move_task. Returns the moved task and the new order of the
+    // affected columns, or None when the task is already at that edge.
+    pub async fn nudge_task(
         pool: &PgPool,
         task_id: Uuid,
+        direction: NudgeDirection,
+        rules: &[BoardRule],
         user_id: Uuid,
-        request: &CreateTaskCommentRequest,
-    ) -> Result<TaskComment, AppError> {
+    ) -> Result<Option<(Task, Vec<ColumnOrder>, Vec<TaskComment>)>, AppError> {
+        let _timer = query_timer!();
+        let current = Self::get_task_by_id(pool, task_id).await?;
+        let Some(new_status) = current.status.adjacent(direction) else {
+            return Ok(None);
+        };
+
+        let mut tx = pool.begin().await?;
+        lock_task_move(&mut tx, &current, new_status).await?;
+
+        let column = column_task_ids(&mut tx, current.project_id, current.status).await?;
+        let Some(new_position) = positions::nudged_position(&column, task_id, direction) else {
+            return Ok(None);
+        };
+        let task = reposition_task(&mut tx, &current, new_status, new_position, user_id).await?;
+        let (task, comments) = run_automations(&mut tx, task, rules, user_id).await?;
+
+        let mut columns = Vec::new();
+        for status in TaskStatus::ALL.into_iter().filter(|status| *status == current.status || *status == new_status) {
+            let task_ids = column_task_ids(&mut tx, current.project_id, status).await?;
+            columns.push(ColumnOrder { status, task_ids });
+        }
+        tx.commit().await?;
+
+        Ok(Some((task, columns, comments)))
+    }
+
+    // Largest gap between a column's highest position and its task count
+    pub async fn max_position_gap(
+        pool: &PgPool,
+        project_id: Uuid,
+    ) -> Result<i64, AppError> {
+        let _timer = query_timer!();
+        let gap: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT MAX(gap) FROM (
+                SELECT MAX(position)::bigint - COUNT(*) AS gap
+                FROM tasks
+                WHERE project_id = $1
+                GROUP BY status
+            ) columns
+            "#
+        )
+        .bind(project_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(gap.unwrap_or(0))
+    }
+
+    // Rewrites every column of the project to 1..n, keeping the current order.
+    // Returns how many tasks changed position and the resulting order.
+    pub async fn compact_positions(
+        pool: &PgPool,
+        project_id: Uuid,
+    ) -> Result<(u64, Vec<ColumnOrder>), AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+        lock_columns(&mut tx, project_id, &TaskStatus::ALL).await?;
+        set_reordering(&mut tx, true).await?;
+
+        let updated = sqlx::query(
+            r#"
+            UPDATE tasks t
+            SET position = r.position
+            FROM (
+                SELECT id, ROW_NUMBER() OVER (PARTITION BY status ORDER BY position, created_at, id)::int AS position
+                FROM tasks
+                WHERE project_id = $1
+            ) r
+            WHERE t.id = r.id AND t.position <> r.position
+            "#
+        )
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        let mut columns = Vec::new();
+        for status in TaskStatus::ALL {
+            let task_ids = column_task_ids(&mut tx, project_id, status).await?;
+            columns.push(ColumnOrder { status, task_ids });
+        }
+
+        // Task timestamps are left alone, so bump the project for ETags
+        if updated > 0 {
+            sqlx::query("UPDATE projects SET updated_at = NOW() WHERE id = $1")
+                .bind(project_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok((updated, columns))
+    }
+
+    // Insert-or-update keyed by a fixed id, used by the seed command
+    pub async fn upsert_task(
+        pool: &PgPool,
+        task_id: Uuid,
+        project_id: Uuid,
+        request: &CreateTaskRequest,
+        status: TaskStatus,
+        position: i32,
+        created_by: Uuid,
+    ) -> Result<Task, AppError> {
+        let _timer = query_timer!();
+        let priority = request.priority.unwrap_or(TaskPriority::Medium);
+
         let row = sqlx::query(
             r#"
-            INSERT INTO task_comments (task_id, user_id, content)
-            VALUES ($1, $2, $3)
-            RETURNING id, task_id, user_id, content, created_at, updated_at
+            INSERT INTO tasks (id, title, description, project_id, created_by, assigned_to, status, priority, due_date, tags, position, cover_color, cover_emoji, is_all_day)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, NULLIF($12, ''), NULLIF($13, ''), COALESCE($14, false))
+            ON CONFLICT (id) DO UPDATE
+            SET title = EXCLUDED.title,
+                description = EXCLUDED.description,
+                assigned_to = EXCLUDED.assigned_to,
+                status = EXCLUDED.status,
+                priority = EXCLUDED.priority,
+                due_date = EXCLUDED.due_date,
+                is_all_day = EXCLUDED.is_all_day,
+                tags = EXCLUDED.tags,
+                position = EXCLUDED.position,
+                cover_color = EXCLUDED.cover_color,
+                cover_emoji = EXCLUDED.cover_emoji
+            RETURNING id, title, description, project_id, created_by, assigned_to, status, priority, due_date, is_all_day, tags, cover_color, cover_emoji, position, number, created_at, updated_at
             "#
         )
         .bind(task_id)
-        .bind(user_id)
-        .bind(&request.content)
+        .bind(&request.title)
+        .bind(&request.description)
+        .bind(project_id)
+        .bind(created_by)
+        .bind(request.assigned_to)
+        .bind(status)
+        .bind(priority)
+        .bind(request.due_date.map(DueDate::instant))
+        .bind(serde_json::to_value(&request.tags).unwrap_or(serde_json::Value::Array(vec![])))
+        .bind(position)
+        .bind(&request.cover_color)
+        .bind(&request.cover_emoji)
+        .bind(request.due_date.map(DueDate::is_all_day))
         .fetch_one(pool)
         .await?;
 
-        Ok(TaskComment {
+        Ok(Task {
             id: row.get("id"),
-            task_id: row.get("task_id"),
-            user_id: row.get("user_id"),
-            content: row.get("content"),
+            title: row.get("title"),
+            description: row.get("description"),
+            project_id: row.get("project_id"),
+            created_by: row.get("created_by"),
+            assigned_to: row.get("assigned_to"),
+            status: row.get("status"),
+            priority: row.get("priority"),
+            due_date: row.get("due_date"),
+            is_all_day: row.get("is_all_day"),
+            tags: row.get::<Option<serde_json::Value>, _>("tags").and_then(|tags| serde_json::from_value(tags).ok()),
+            cover_color: row.get("cover_color"),
+            cover_emoji: row.get("cover_emoji"),
+            position: row.get("position"),
+            number: row.get("number"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
         })
     }
 
-    pub async fn get_task_comments(
+    // Sorted by the requested key, then by due date, priority and age. Every
+    // ORDER BY fragment comes from the match below, never from the request.
+    pub async fn get_user_assigned_tasks(
         pool: &PgPool,
-        task_id: Uuid,
-    ) -> Result<Vec<TaskComment>, AppError> {
-        let rows = sqlx::query(
+        user_id: Uuid,
+        sort: TaskSort,
+        order: SortOrder,
+    ) -> Result<Vec<Task>, AppError> {
+        let _timer = query_timer!();
+        let key = match sort {
+            TaskSort::DueDate => "t.due_date",
+            TaskSort::Priority => PRIORITY_RANK_SQL,
+            TaskSort::CreatedAt => "t.created_at",
+            TaskSort::Project => "p.name",
+            // Unordered tasks have no position and follow in the default order
+            TaskSort::Manual => "o.position",
+        };
+        let join = match sort {
+            TaskSort::Manual => "LEFT JOIN user_task_order o ON o.task_id = t.id AND o.user_id = $1",
+            _ => "",
+        };
+        let direction = match order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+
+        let sql = format!(
             r#"
-            SELECT id, task_id, user_id, content, created_at, updated_at
-            FROM task_comments 
-            WHERE task_id = $1 
-            ORDER BY created_at ASC
-            "#
-        )
-        .bind(task_id)
-        .fetch_all(pool)
-        .await?;
+            SELECT t.id, t.title, t.description, t.project_id, t.created_by, t.assigned_to, t.status, t.priority,
+                   t.due_date, t.is_all_day, t.tags, t.cover_color, t.cover_emoji, t.position, t.number, t.created_at, t.updated_at
+            FROM tasks t
+            JOIN projects p ON p.id = t.project_id
+            {join}
+            WHERE t.assigned_to = $1 AND t.quarantined_at IS NULL AND t.expired_at IS NULL AND p.deleted_at IS NULL
+            ORDER BY {key} {direction} NULLS LAST, t.due_date ASC NULLS LAST, {rank} DESC, t.created_at ASC, t.id
+            "#,
+            rank = PRIORITY_RANK_SQL,
+        );
+
+        let rows = sqlx::query(&sql)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
 
-        let comments = rows.into_iter().map(|row| TaskComment {
+        let tasks = rows.into_iter().map(|row| Task {
             id: row.get("id"),
-            task_id: row.get("task_id"),
-            user_id: row.get("user_id"),
-            content: row.get("content"),
+            title: row.get("title"),
+            description: row.get("description"),
+            project_id: row.get("project_id"),
+            created_by: row.get("created_by"),
+            assigned_to: row.get("assigned_to"),
+            status: row.get("status"),
+            priority: row.get("priority"),
+            due_date: row.get("due_date"),
+            is_all_day: row.get("is_all_day"),
+            tags: row.get::<Option<serde_json::Value>, _>("tags").and_then(|tags| serde_json::from_value(tags).ok()),
+            cover_color: row.get("cover_color"),
+            cover_emoji: row.get("cover_emoji"),
+            position: row.get("position"),
+            number: row.get("number"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
         }).collect();
 
-        Ok(comments)
+        Ok(tasks)
     }
 
-    pub async fn get_comment_by_id(
+    // The user's tasks with a due date stored within [from, to), in live
+    // projects, soonest first and the most urgent first among equals
+    pub async fn get_assigned_tasks_due_between(
         pool: &PgPool,
-        comment_id: Uuid,
-    ) -> Result<TaskComment, AppError> {
+        user_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<AgendaTask>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT t.id, t.project_id, p.name AS project_name, t.number, t.title, t.status, t.priority,
+                   t.due_date, t.is_all_day
+            FROM tasks t
+            JOIN projects p ON p.id = t.project_id
+            WHERE t.assigned_to = $1 AND t.due_date >= $2 AND t.due_date < $3
+              AND t.quarantined_at IS NULL AND t.expired_at IS NULL AND p.deleted_at IS NULL
+            ORDER BY t.due_date, {rank} DESC, t.number
+            "#,
+            rank = PRIORITY_RANK_SQL,
+        ))
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| AgendaTask {
+                id: row.get("id"),
+                project_id: row.get("project_id"),
+                project_name: row.get("project_name"),
+                number: row.get("number"),
+                title: row.get("title"),
+                status: row.get("status"),
+                priority: row.get("priority"),
+                due_date: row.get("due_date"),
+                is_all_day: row.get("is_all_day"),
+            })
+            .collect())
+    }
+
+    pub async fn get_task_by_number(
+        pool: &PgPool,
+        project_id: Uuid,
+        number: i32,
+    ) -> Result<Option<Task>, AppError> {
+        let _timer = query_timer!();
         let row = sqlx::query(
             r#"
-            SELECT id, task_id, user_id, content, created_at, updated_at
-            FROM task_comments 
-            WHERE id = $1
+            SELECT id, title, description, project_id, created_by, assigned_to, status, priority, due_date, is_all_day, tags, cover_color, cover_emoji, position, number, created_at, updated_at
+            FROM tasks
+            WHERE project_id = $1 AND number = $2 AND quarantined_at IS NULL AND expired_at IS NULL
             "#
         )
-        .bind(comment_id)
-        .fetch_one(pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => AppError::NotFound("Comment not found".to_string()),
-            _ => AppError::DatabaseError(e.to_string()),
-        })?;
+        .bind(project_id)
+        .bind(number)
+        .fetch_optional(pool)
+        .await?;
 
-        Ok(TaskComment {
+        Ok(row.map(|row| Task {
             id: row.get("id"),
-            task_id: row.get("task_id"),
-            user_id: row.get("user_id"),
-            content: row.get("content"),
+            title: row.get("title"),
+            description: row.get("description"),
+            project_id: row.get("project_id"),
+            created_by: row.get("created_by"),
+            assigned_to: row.get("assigned_to"),
+            status: row.get("status"),
+            priority: row.get("priority"),
+            due_date: row.get("due_date"),
+            is_all_day: row.get("is_all_day"),
+            tags: row.get::<Option<serde_json::Value>, _>("tags").and_then(|tags| serde_json::from_value(tags).ok()),
+            cover_color: row.get("cover_color"),
+            cover_emoji: row.get("cover_emoji"),
+            position: row.get("position"),
+            number: row.get("number"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
-        })
+        }))
     }
 
-    pub async fn delete_comment(
+    // Typeahead for linking tasks: the task numbered `number` first, then
+    // titles starting with `query`, then titles containing it, most recently
+    // updated first. An empty query lists recently updated tasks.
+    pub async fn pick_tasks(
         pool: &PgPool,
-        comment_id: Uuid,
-        user_id: Uuid,
-    ) -> Result<(), AppError> {
-        let result = sqlx::query(
-            "DELETE FROM task_comments WHERE id = $1 AND user_id = $2"
+        project_ids: &[Uuid],
+        exclude: &[Uuid],
+        query: &str,
+        number: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<RelatedTask>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, project_id, number, title, status
+            FROM tasks
+            WHERE project_id = ANY($1) AND NOT (id = ANY($2)) AND quarantined_at IS NULL AND expired_at IS NULL
+              AND (number = $4 OR title ILIKE '%' || $3 || '%' ESCAPE '\')
+            ORDER BY number = $4 DESC NULLS LAST, title ILIKE $3 || '%' ESCAPE '\' DESC, updated_at DESC
+            LIMIT $5
+            "#
         )
-        .bind(comment_id)
-        .bind(user_id)
-        .execute(pool)
+        .bind(project_ids)
+        .bind(exclude)
+        .bind(crate::utils::search::escape_like(query))
+        .bind(number)
+        .bind(limit)
+        .fetch_all(pool)
         .await?;
 
-        if result.rows_affected() == 0 {
-            return Err(AppError::NotFound("Comment not found or not authorized".to_string()));
-        }
+        Ok(rows
+            .into_iter()
+            .map(|row| RelatedTask {
+                id: row.get("id"),
+                project_id: row.get("project_id"),
+                number: row.get("number"),
+                title: row.get("title"),
+                status: row.get("status"),
+            })
+            .collect())
+    }
 
-        Ok(())
+    // Open tasks of the project whose titles are at least `threshold` similar to
+    // `title` (pg_trgm), most similar first. Setting the threshold for the %
+    // operator lets the trigram index on titles do the filtering.
+    pub async fn find_similar_open_tasks(
+        pool: &PgPool,
+        project_id: Uuid,
+        title: &str,
+        threshold: f32,
+        limit: i64,
+    ) -> Result<Vec<RelatedTask>, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("SELECT set_config('pg_trgm.similarity_threshold', $1, true)")
+            .bind(threshold.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT id, project_id, number, title, status
+            FROM tasks
+            WHERE project_id = $1 AND status <> 'done' AND quarantined_at IS NULL AND expired_at IS NULL
+              AND title % $2
+            ORDER BY similarity(title, $2) DESC, updated_at DESC
+            LIMIT $3
+            "#
+        )
+        .bind(project_id)
+        .bind(title)
+        .bind(limit)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| RelatedTask {
+                id: row.get("id"),
+                project_id: row.get("project_id"),
+                number: row.get("number"),
+                title: row.get("title"),
+                status: row.get("status"),
+            })
+            .collect())
     }
-}
\ No newline at end of file
+
+    // Status changes made by integrations, so without a user
+    pub async fn set_task_status(
+        pool: &PgPool,
+        task_id: Uuid,
+        status: TaskStatus,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+        let previous: Option<TaskStatus> = sqlx::query_scalar("SELECT status FROM tasks WHERE id = $1 FOR UPDATE")
+            .bind(task_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(previous) = previous else {
+            return Err(AppError::NotFound("Task not found".to_string()));
+        };
+
+        sqlx::query("UPDATE tasks SET status = $2 WHERE id = $1")
+            .bind(task_id)
+            .bind(status)
+            .execute(&mut *tx)
+            .await?;
+        record_status_change(&mut tx, task_id, previous, status, None).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Task counts for one grouping in a single GROUP BY query. Tasks with several
+    // labels count once per label when grouping by label.
+    pub async fn count_tasks_grouped(
+        pool: &PgPool,
+        project_id: Uuid,
+        filter: &TaskListFilter,
+        group_by: TaskGroupBy,
+    ) -> Result<Vec<TaskGroupCount>, AppError> {
+        let _timer = query_timer!();
+        let (key, join) = match group_by {
+            TaskGroupBy::Status => ("t.status", ""),
+            TaskGroupBy::Assignee => ("t.assigned_to", ""),
+            TaskGroupBy::Label => (
+                "l.label",
+                // Tasks created without tags store a JSON null
+                "LEFT JOIN LATERAL jsonb_array_elements_text(
+                    CASE WHEN jsonb_typeof(t.tags) = 'array' THEN t.tags ELSE '[]'::jsonb END
+                ) AS l(label) ON true",
+            ),
+        };
+
+        let sql = format!(
+            r#"
+            SELECT {key} AS key, COUNT(*) AS count
+            FROM tasks t
+            LEFT JOIN task_reads r ON r.task_id = t.id AND r.user_id = $6
+            {join}
+            WHERE t.project_id = $1 AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+              AND ($2::task_status IS NULL OR t.status = $2)
+              AND ($3::task_priority IS NULL OR t.priority = $3)
+              AND ($4::uuid IS NULL OR t.assigned_to = $4)
+              AND ($5::text IS NULL OR t.tags @> jsonb_build_array($5::text))
+              AND ($6::uuid IS NULL OR {unread} = $7)
+            GROUP BY {key}
+            ORDER BY {key} NULLS LAST
+            "#,
+            unread = UNREAD_SQL,
+        );
+
+        let rows = sqlx::query(&sql)
+            .bind(project_id)
+            .bind(filter.status)
+            .bind(filter.priority)
+            .bind(filter.assigned_to)
+            .bind(&filter.tag)
+            .bind(filter.unread.map(|unread| unread.user_id))
+            .bind(filter.unread.map(|unread| unread.unread))
+            .fetch_all(pool)
+            .await?;
+
+        let counts = rows.into_iter().map(|row| {
+            let key = match group_by {
+                TaskGroupBy::Status => Some(row.get::<TaskStatus, _>("key").name().to_string()),
+                TaskGroupBy::Assignee => row.get::<Option<Uuid>, _>("key").map(|id| id.to_string()),
+                TaskGroupBy::Label => row.get("key"),
+            };
+            TaskGroupCount { key, count: row.get("count") }
+        }).collect();
+
+        Ok(counts)
+    }
+
+    // Status counts in column order, including statuses without tasks
+    pub async fn count_tasks_by_status(
+        pool: &PgPool,
+        project_id: Uuid,
+        filter: &TaskListFilter,
+    ) -> Result<Vec<TaskGroupCount>, AppError> {
+        let _timer = query_timer!();
+        let counts = Self::count_tasks_grouped(pool, project_id, filter, TaskGroupBy::Status).await?;
+
+        Ok(TaskStatus::ALL.iter().map(|status| {
+            let key = Some(status.name().to_string());
+            let count = counts.iter().find(|c| c.key == key).map(|c| c.count).unwrap_or(0);
+            TaskGroupCount { key, count }
+        }).collect())
+    }
+
+    pub async fn count_tasks_with_status(
+        pool: &PgPool,
+        project_id: Uuid,
+        status: TaskStatus,
+    ) -> Result<i64, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM tasks WHERE project_id = $1 AND status = $2 AND quarantined_at IS NULL AND expired_at IS NULL")
+            .bind(project_id)
+            .bind(status)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+}
+
+// Adds the member along with the team's auto-add projects (see migration 035)
+async fn insert_team_member(
+    conn: &mut sqlx::PgConnection,
+    team_id: Uuid,
+    user_id: Uuid,
+    role: TeamRole,
+) -> Result<TeamMember, AppError> {
+    let row = sqlx::query(
+        r#"
+        INSERT INTO team_members (team_id, user_id, role)
+        VALUES ($1, $2, $3)
+        RETURNING id, team_id, user_id, role, joined_at
+        "#
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .bind(&role)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO project_members (project_id, user_id, role)
+        SELECT p.id, $2, s.default_project_role
+        FROM team_settings s
+        JOIN projects p ON p.team_id = s.team_id AND p.deleted_at IS NULL
+        WHERE s.team_id = $1
+          AND (s.auto_add_to_projects = 'all'
+               OR (s.auto_add_to_projects = 'selected' AND EXISTS (
+                   SELECT 1 FROM team_auto_add_projects a
+                   WHERE a.team_id = s.team_id AND a.project_id = p.id
+               )))
+        ON CONFLICT (project_id, user_id) DO NOTHING
+        "#
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
+
+    let member = TeamMember {
+        id: row.get("id"),
+        team_id: row.get("team_id"),
+        user_id: row.get("user_id"),
+        role: row.get("role"),
+        joined_at: row.get("joined_at"),
+    };
+
+    Ok(member)
+}
+
+async fn insert_project_member(
+    conn: &mut sqlx::PgConnection,
+    project_id: Uuid,
+    user_id: Uuid,
+    role: ProjectRole,
+) -> Result<ProjectMember, AppError> {
+    let row = sqlx::query(
+        r#"
+        INSERT INTO project_members (project_id, user_id, role)
+        VALUES ($1, $2, $3)
+        RETURNING id, project_id, user_id, role, joined_at
+        "#
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .bind(&role)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(ProjectMember {
+        id: row.get("id"),
+        project_id: row.get("project_id"),
+        user_id: row.get("user_id"),
+        role: row.get("role"),
+        joined_at: row.get("joined_at"),
+    })
+}
+
+// Inserts the task at the top or bottom of its status column, holding the
+// column lock until the caller's transaction ends
+async fn insert_task(
+    conn: &mut sqlx::PgConnection,
+    project_id: Uuid,
+    request: &CreateTaskRequest,
+    created_by: Uuid,
+    status: TaskStatus,
+    placement: ColumnPlacement,
+) -> Result<Task, AppError> {
+    lock_columns(&mut *conn, project_id, &[status]).await?;
+
+    let position: i32 = match placement {
+        ColumnPlacement::Top => {
+            set_reordering(&mut *conn, true).await?;
+            sqlx::query("UPDATE tasks SET position = position + 1 WHERE project_id = $1 AND status = $2")
+                .bind(project_id)
+                .bind(status)
+                .execute(&mut *conn)
+                .await?;
+            set_reordering(&mut *conn, false).await?;
+            1
+        }
+        ColumnPlacement::Bottom => {
+            sqlx::query_scalar("SELECT COALESCE(MAX(position), 0) + 1 FROM tasks WHERE project_id = $1 AND status = $2")
+                .bind(project_id)
+                .bind(status)
+                .fetch_one(&mut *conn)
+                .await?
+        }
+    };
+    let priority = request.priority.unwrap_or(TaskPriority::Medium);
+
+    let row = sqlx::query(
+        r#"
+        INSERT INTO tasks (title, description, project_id, created_by, assigned_to, priority, due_date, tags, position, cover_color, cover_emoji, is_all_day, status)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NULLIF($10, ''), NULLIF($11, ''), COALESCE($12, false), $13)
+        RETURNING id, title, description, project_id, created_by, assigned_to, status, priority, due_date, is_all_day, tags, cover_color, cover_emoji, position, number, created_at, updated_at
+        "#
+    )
+    .bind(&request.title)
+    .bind(&request.description)
+    .bind(project_id)
+    .bind(created_by)
+    .bind(request.assigned_to)
+    .bind(priority)
+    .bind(request.due_date.map(DueDate::instant))
+    .bind(serde_json::to_value(&request.tags).unwrap_or(serde_json::Value::Array(vec![])))
+    .bind(position)
+    .bind(&request.cover_color)
+    .bind(&request.cover_emoji)
+    .bind(request.due_date.map(DueDate::is_all_day))
+    .bind(status)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(Task {
+        id: row.get("id"),
+        title: row.get("title"),
+        description: row.get("description"),
+        project_id: row.get("project_id"),
+        created_by: row.get("created_by"),
+        assigned_to: row.get("assigned_to"),
+        status: row.get("status"),
+        priority: row.get("priority"),
+        due_date: row.get("due_date"),
+        is_all_day: row.get("is_all_day"),
+        tags: row.get::<Option<serde_json::Value>, _>("tags").and_then(|tags| serde_json::from_value(tags).ok()),
+        cover_color: row.get("cover_color"),
+        cover_emoji: row.get("cover_emoji"),
+        position: row.get("position"),
+        number: row.get("number"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
+
+// Serializes position changes per (project, status) column for the rest of the
+// transaction. Locks are taken in TaskStatus::ALL order to avoid deadlocks.
+async fn lock_columns(
+    conn: &mut sqlx::PgConnection,
+    project_id: Uuid,
+    statuses: &[TaskStatus],
+) -> Result<(), AppError> {
+    for status in TaskStatus::ALL.into_iter().filter(|status| statuses.contains(status)) {
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1::text || ':' || $2::text, 0))")
+            .bind(project_id)
+            .bind(status)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+// While on, task updates keep their updated_at (see migration 019)
+async fn set_reordering(
+    conn: &mut sqlx::PgConnection,
+    on: bool,
+) -> Result<(), AppError> {
+    sqlx::query("SELECT set_config('simplecards.reordering', $1, true)")
+        .bind(if on { "on" } else { "off" })
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+async fn column_task_ids(
+    conn: &mut sqlx::PgConnection,
+    project_id: Uuid,
+    status: TaskStatus,
+) -> Result<Vec<Uuid>, AppError> {
+    let ids = sqlx::query_scalar(
+        "SELECT id FROM tasks WHERE project_id = $1 AND status = $2 ORDER BY position, created_at, id"
+    )
+    .bind(project_id)
+    .bind(status)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(ids)
+}
+
+// Locks the columns a move touches and makes sure the task is still where
+// `current` says
+async fn lock_task_move(
+    conn: &mut sqlx::PgConnection,
+    current: &Task,
+    new_status: TaskStatus,
+) -> Result<(), AppError> {
+    lock_columns(&mut *conn, current.project_id, &[current.status, new_status]).await?;
+
+    let status: Option<TaskStatus> = sqlx::query_scalar("SELECT status FROM tasks WHERE id = $1 FOR UPDATE")
+        .bind(current.id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    match status {
+        None => Err(AppError::NotFound("Task not found".to_string())),
+        Some(status) if status != current.status => {
+            Err(AppError::Conflict("The task was moved by someone else, please retry".to_string()))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+// Puts the task at `new_position` of the `new_status` column and renumbers
+// both columns; the caller holds the locks from lock_task_move
+async fn reposition_task(
+    conn: &mut sqlx::PgConnection,
+    current: &Task,
+    new_status: TaskStatus,
+    new_position: i32,
+    moved_by: Uuid,
+) -> Result<Task, AppError> {
+    let task_id = current.id;
+    set_reordering(&mut *conn, true).await?;
+
+    let mut target = column_task_ids(&mut *conn, current.project_id, new_status).await?;
+    target.retain(|id| *id != task_id);
+    let index = usize::try_from(new_position.max(1) - 1).unwrap_or(0).min(target.len());
+    target.insert(index, task_id);
+    write_column_order(&mut *conn, &target).await?;
+
+    if current.status != new_status {
+        let mut source = column_task_ids(&mut *conn, current.project_id, current.status).await?;
+        source.retain(|id| *id != task_id);
+        write_column_order(&mut *conn, &source).await?;
+    }
+
+    // Only the moved task counts as updated
+    set_reordering(&mut *conn, false).await?;
+    let row = sqlx::query(
+        r#"
+        UPDATE tasks
+        SET status = $2
+        WHERE id = $1
+        RETURNING id, title, description, project_id, created_by, assigned_to, status, priority, due_date, is_all_day, tags, cover_color, cover_emoji, position, number, created_at, updated_at
+        "#
+    )
+    .bind(task_id)
+    .bind(new_status)
+    .fetch_one(&mut *conn)
+    .await?;
+    record_status_change(&mut *conn, task_id, current.status, new_status, Some(moved_by)).await?;
+
+    Ok(moved_task_from_row(&row))
+}
+
+// Adds a task_status_transitions row when the status actually changed
+async fn record_status_change(
+    conn: &mut sqlx::PgConnection,
+    task_id: Uuid,
+    from: TaskStatus,
+    to: TaskStatus,
+    changed_by: Option<Uuid>,
+) -> Result<(), AppError> {
+    if from == to {
+        return Ok(());
+    }
+    sqlx::query("INSERT INTO task_status_transitions (task_id, from_status, to_status, changed_by) VALUES ($1, $2, $3, $4)")
+        .bind(task_id)
+        .bind(from)
+        .bind(to)
+        .bind(changed_by)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+fn moved_task_from_row(row: &PgRow) -> Task {
+    Task {
+        id: row.get("id"),
+        title: row.get("title"),
+        description: row.get("description"),
+        project_id: row.get("project_id"),
+        created_by: row.get("created_by"),
+        assigned_to: row.get("assigned_to"),
+        status: row.get("status"),
+        priority: row.get("priority"),
+        due_date: row.get("due_date"),
+        is_all_day: row.get("is_all_day"),
+        tags: row.get::<Option<serde_json::Value>, _>("tags").and_then(|tags| serde_json::from_value(tags).ok()),
+        cover_color: row.get("cover_color"),
+        cover_emoji: row.get("cover_emoji"),
+        position: row.get("position"),
+        number: row.get("number"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+// Runs the board automation rules for a task that just moved into their
+// column, under the move's locks. Each rule that does something is recorded
+// in task_automation_runs and as a system comment by the user who moved it.
+async fn run_automations(
+    conn: &mut sqlx::PgConnection,
+    mut task: Task,
+    rules: &[BoardRule],
+    user_id: Uuid,
+) -> Result<(Task, Vec<TaskComment>), AppError> {
+    let mut comments = Vec::new();
+
+    for BoardRule { board_id, rule } in rules {
+        let rule_key = rule.id.to_string();
+        let first_run: bool = sqlx::query_scalar(
+            "SELECT NOT EXISTS (SELECT 1 FROM task_automation_runs WHERE task_id = $1 AND rule_id = $2)"
+        )
+        .bind(task.id)
+        .bind(rule.id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        let mut members = Vec::new();
+        let mut last_assignee = None;
+        if let Some(user_ids) = rule.actions.iter().find_map(|action| match action {
+            AutomationAction::AssignRoundRobin { user_ids } => Some(user_ids),
+            _ => None,
+        }) {
+            members = sqlx::query_scalar("SELECT user_id FROM project_members WHERE project_id = $1 AND user_id = ANY($2)")
+                .bind(task.project_id)
+                .bind(user_ids)
+                .fetch_all(&mut *conn)
+                .await?;
+            // Locks the board's turns until the move commits
+            let last: Option<String> = sqlx::query_scalar("SELECT automation_cursors ->> $2 FROM boards WHERE id = $1 FOR UPDATE")
+                .bind(board_id)
+                .bind(&rule_key)
+                .fetch_optional(&mut *conn)
+                .await?
+                .flatten();
+            last_assignee = last.and_then(|last| Uuid::parse_str(&last).ok());
+        }
+
+        let plan = automations::plan(rule, &task, &members, last_assignee, first_run);
+        if plan.is_empty() {
+            continue;
+        }
+
+        let row = sqlx::query(
+            r#"
+            UPDATE tasks
+            SET assigned_to = COALESCE($2, assigned_to),
+                priority = COALESCE($3, priority),
+                tags = CASE
+                    WHEN cardinality($4::text[]) = 0 THEN tags
+                    -- Tasks created without tags hold a JSON null
+                    WHEN jsonb_typeof(tags) = 'array' THEN tags || to_jsonb($4::text[])
+                    ELSE to_jsonb($4::text[])
+                END
+            WHERE id = $1
+            RETURNING id, title, description, project_id, created_by, assigned_to, status, priority, due_date, is_all_day, tags, cover_color, cover_emoji, position, number, created_at, updated_at
+            "#
+        )
+        .bind(task.id)
+        .bind(plan.assign_to)
+        .bind(plan.priority)
+        .bind(&plan.add_labels)
+        .fetch_one(&mut *conn)
+        .await?;
+        task = moved_task_from_row(&row);
+
+        let mut assignee: Option<String> = None;
+        if let Some(assign_to) = plan.assign_to {
+            sqlx::query("UPDATE boards SET automation_cursors = jsonb_set(automation_cursors, ARRAY[$2::text], to_jsonb($3::text)) WHERE id = $1")
+                .bind(board_id)
+                .bind(&rule_key)
+                .bind(assign_to.to_string())
+                .execute(&mut *conn)
+                .await?;
+            assignee = sqlx::query_scalar("SELECT display_name FROM users WHERE id = $1")
+                .bind(assign_to)
+                .fetch_optional(&mut *conn)
+                .await?;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO task_automation_runs (task_id, rule_id, board_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (task_id, rule_id) DO UPDATE SET run_count = task_automation_runs.run_count + 1, last_run_at = NOW()
+            "#
+        )
+        .bind(task.id)
+        .bind(rule.id)
+        .bind(board_id)
+        .execute(&mut *conn)
+        .await?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO task_comments (task_id, user_id, content, is_system)
+            VALUES ($1, $2, $3, true)
+            RETURNING {columns}
+            "#,
+            columns = COMMENT_COLUMNS_SQL,
+        ))
+        .bind(task.id)
+        .bind(user_id)
+        .bind(automations::history_comment(rule, &plan, assignee.as_deref()))
+        .fetch_one(&mut *conn)
+        .await?;
+        comments.push(comment_from_row(row));
+    }
+
+    Ok((task, comments))
+}
+
+// Positions 1..n in the order given; unchanged rows aren't written
+async fn write_column_order(
+    conn: &mut sqlx::PgConnection,
+    task_ids: &[Uuid],
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE tasks t
+        SET position = o.position::int
+        FROM UNNEST($1::uuid[]) WITH ORDINALITY AS o(id, position)
+        WHERE t.id = o.id AND t.position <> o.position
+        "#
+    )
+    .bind(task_ids)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// validation::username_skeleton of `username`, as indexed by migration 027
+const USERNAME_SKELETON_SQL: &str = "translate(replace(replace(lower(username), 'rn', 'm'), 'vv', 'w'), '01i', 'oll')";
+
+// When a task is due, for queries joining the assignee's digest_preferences as
+// `tz`. All-day dates last until local midnight (see due_dates.rs).
+const DUE_AT_SQL: &str = "CASE WHEN t.is_all_day \
+    THEN (t.due_date AT TIME ZONE 'UTC' + INTERVAL '1 day') AT TIME ZONE COALESCE(tz.timezone, 'UTC') \
+    ELSE t.due_date END";
+
+// Urgency of task `t`, so ordering doesn't depend on the enum's declaration order
+const PRIORITY_RANK_SQL: &str = "CASE t.priority WHEN 'critical' THEN 4 WHEN 'high' THEN 3 WHEN 'medium' THEN 2 ELSE 1 END";
+
+// Unread condition for task `t` joined with the viewer's `task_reads` row `r`.
+// The viewer's own comments don't make a task unread.
+const UNREAD_SQL: &str = r#"(
+    r.last_read_at IS NULL
+    OR t.updated_at > r.last_read_at
+    OR EXISTS (
+        SELECT 1 FROM task_comments c
+        WHERE c.task_id = t.id AND c.user_id <> r.user_id AND c.created_at > r.last_read_at
+    )
+)"#;
+
+pub struct BoardQueries;
+
+impl BoardQueries {
+    pub async fn create_board(
+        pool: &PgPool,
+        project_id: Uuid,
+        request: &CreateBoardRequest,
+        created_by: Uuid,
+    ) -> Result<Board, AppError> {
+        let _timer = query_timer!();
+        let columns = request.columns.clone()
+            .unwrap_or_else(|| vec!["Todo".to_string(), "In Progress".to_string(), "Review".to_string(), "Done".to_string()]);
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO boards (name, description, project_id, created_by, columns, config, swimlane_config)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, name, description, project_id, created_by, columns, config, swimlane_config, is_default, created_at, updated_at
+            "#
+        )
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(project_id)
+        .bind(created_by)
+        .bind(serde_json::to_value(&columns).unwrap())
+        .bind(serde_json::to_value(request.config.clone().unwrap_or_default()).unwrap())
+        .bind(serde_json::to_value(request.swimlane_config.clone().unwrap_or_default()).unwrap())
+        .fetch_one(pool)
+        .await?;
+
+        Ok(Board {
+            id: row.get("id"),
+            name: row.get("name"),
+            description: row.get("description"),
+            project_id: row.get("project_id"),
+            created_by: row.get("created_by"),
+            columns: serde_json::from_value(row.get("columns")).unwrap_or(vec![]),
+            config: serde_json::from_value(row.get("config")).unwrap_or_default(),
+            swimlane_config: serde_json::from_value(row.get("swimlane_config")).unwrap_or_default(),
+            is_default: row.get("is_default"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        })
+    }
+
+    pub async fn get_project_boards(
+        pool: &PgPool,
+        project_id: Uuid,
+    ) -> Result<Vec<Board>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, name, description, project_id, created_by, columns, config, swimlane_config, is_default, created_at, updated_at
+            FROM boards 
+            WHERE project_id = $1 AND deleted_at IS NULL
+            ORDER BY is_default DESC, created_at ASC
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        let boards = rows.into_iter().map(|row| Board {
+            id: row.get("id"),
+            name: row.get("name"),
+            description: row.get("description"),
+            project_id: row.get("project_id"),
+            created_by: row.get("created_by"),
+            columns: serde_json::from_value(row.get("columns")).unwrap_or(vec![]),
+            config: serde_json::from_value(row.get("config")).unwrap_or_default(),
+            swimlane_config: serde_json::from_value(row.get("swimlane_config")).unwrap_or_default(),
+            is_default: row.get("is_default"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }).collect();
+
+        Ok(boards)
+    }
+
+    pub async fn get_board_by_id(
+        pool: &PgPool,
+        board_id: Uuid,
+    ) -> Result<Board, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            SELECT id, name, description, project_id, created_by, columns, config, swimlane_config, is_default, created_at, updated_at
+            FROM boards 
+            WHERE id = $1 AND deleted_at IS NULL
+            "#
+        )
+        .bind(board_id)
+        .fetch_optional(pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Board {
+                id: row.get("id"),
+                name: row.get("name"),
+                description: row.get("description"),
+                project_id: row.get("project_id"),
+                created_by: row.get("created_by"),
+                columns: serde_json::from_value(row.get("columns")).unwrap_or(vec![]),
+                config: serde_json::from_value(row.get("config")).unwrap_or_default(),
+                swimlane_config: serde_json::from_value(row.get("swimlane_config")).unwrap_or_default(),
+                is_default: row.get("is_default"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+            }),
+            None => Err(AppError::NotFound("Board not found".to_string())),
+        }
+    }
+
+    pub async fn update_board(
+        pool: &PgPool,
+        board_id: Uuid,
+        request: &UpdateBoardRequest,
+    ) -> Result<Board, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            UPDATE boards 
+            SET name = COALESCE($2, name),
+                description = CASE WHEN $7 THEN NULL ELSE COALESCE($3, description) END,
+                columns = COALESCE($4, columns),
+                config = COALESCE($5, config),
+                swimlane_config = COALESCE($6, swimlane_config)
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING id, name, description, project_id, created_by, columns, config, swimlane_config, is_default, created_at, updated_at
+            "#
+        )
+        .bind(board_id)
+        .bind(&request.name)
+        .bind(request.description.clone().flatten())
+        .bind(request.columns.as_ref().map(|cols| serde_json::to_value(cols).unwrap()))
+        .bind(request.config.as_ref().map(|config| serde_json::to_value(config).unwrap()))
+        .bind(request.swimlane_config.as_ref().map(|config| serde_json::to_value(config).unwrap()))
+        .bind(double_option::is_cleared(&request.description))
+        .fetch_optional(pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(Board {
+                id: row.get("id"),
+                name: row.get("name"),
+                description: row.get("description"),
+                project_id: row.get("project_id"),
+                created_by: row.get("created_by"),
+                columns: serde_json::from_value(row.get("columns")).unwrap_or(vec![]),
+                config: serde_json::from_value(row.get("config")).unwrap_or_default(),
+                swimlane_config: serde_json::from_value(row.get("swimlane_config")).unwrap_or_default(),
+                is_default: row.get("is_default"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+            }),
+            None => Err(AppError::NotFound("Board not found".to_string())),
+        }
+    }
+
+    pub async fn delete_board(
+        pool: &PgPool,
+        board_id: Uuid,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        // Check if this is the default board
+        let is_default_row = sqlx::query(
+            "SELECT is_default FROM boards WHERE id = $1 AND deleted_at IS NULL"
+        )
+        .bind(board_id)
+        .fetch_optional(pool)
+        .await?;
+
+        match is_default_row {
+            Some(row) => {
+                if row.get::<bool, _>("is_default") {
+                    return Err(AppError::Validation("Cannot delete the default board".to_string()));
+                }
+            }
+            None => return Err(AppError::NotFound("Board not found".to_string())),
+        }
+
+        let result = sqlx::query(
+            "UPDATE boards SET deleted_at = NOW(), delete_batch_id = $2 WHERE id = $1 AND deleted_at IS NULL"
+        )
+        .bind(board_id)
+        .bind(Uuid::new_v4())
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Board not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_board_automations(pool: &PgPool, board_id: Uuid) -> Result<BoardAutomations, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT automations FROM boards WHERE id = $1 AND deleted_at IS NULL")
+            .bind(board_id)
+            .fetch_optional(pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(serde_json::from_value(row.get("automations")).unwrap_or_default()),
+            None => Err(AppError::NotFound("Board not found".to_string())),
+        }
+    }
+
+    pub async fn update_board_automations(
+        pool: &PgPool,
+        board_id: Uuid,
+        automations: &BoardAutomations,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query("UPDATE boards SET automations = $2, updated_at = NOW() WHERE id = $1")
+            .bind(board_id)
+            .bind(serde_json::to_value(automations)?)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // The automation rules of every board in the project, in board order
+    pub async fn get_project_automations(
+        pool: &PgPool,
+        project_id: Uuid,
+    ) -> Result<Vec<(Uuid, BoardAutomations)>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, automations
+            FROM boards
+            WHERE project_id = $1 AND deleted_at IS NULL AND automations <> '{}'
+            ORDER BY is_default DESC, created_at ASC
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("id"), serde_json::from_value(row.get("automations")).unwrap_or_default()))
+            .collect())
+    }
+}
+
+const COMMENT_COLUMNS_SQL: &str = "id, task_id, user_id, content, is_system, pinned, created_at, updated_at";
+
+fn comment_from_row(row: PgRow) -> TaskComment {
+    TaskComment {
+        id: row.get("id"),
+        task_id: row.get("task_id"),
+        user_id: row.get("user_id"),
+        content: row.get("content"),
+        is_system: row.get("is_system"),
+        pinned: row.get("pinned"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+pub struct TaskCommentQueries;
+
+impl TaskCommentQueries {
+    pub async fn create_comment(
+        pool: &PgPool,
+        task_id: Uuid,
+        user_id: Uuid,
+        request: &CreateTaskCommentRequest,
+    ) -> Result<TaskComment, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO task_comments (task_id, user_id, content)
+            VALUES ($1, $2, $3)
+            RETURNING {columns}
+            "#,
+            columns = COMMENT_COLUMNS_SQL,
+        ))
+        .bind(task_id)
+        .bind(user_id)
+        .bind(&request.content)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(comment_from_row(row))
+    }
+
+    pub async fn create_system_comment(
+        pool: &PgPool,
+        task_id: Uuid,
+        user_id: Uuid,
+        content: &str,
+    ) -> Result<TaskComment, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO task_comments (task_id, user_id, content, is_system)
+            VALUES ($1, $2, $3, true)
+            RETURNING {columns}
+            "#,
+            columns = COMMENT_COLUMNS_SQL,
+        ))
+        .bind(task_id)
+        .bind(user_id)
+        .bind(content)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(comment_from_row(row))
+    }
+
+    // Posts a pinned system comment and unpins `replaces`, the one it supersedes
+    pub async fn create_pinned_system_comment(
+        pool: &PgPool,
+        task_id: Uuid,
+        user_id: Uuid,
+        content: &str,
+        replaces: Option<Uuid>,
+    ) -> Result<TaskComment, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        if let Some(previous) = replaces {
+            sqlx::query("UPDATE task_comments SET pinned = false WHERE id = $1")
+                .bind(previous)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO task_comments (task_id, user_id, content, is_system, pinned)
+            VALUES ($1, $2, $3, true, true)
+            RETURNING {columns}
+            "#,
+            columns = COMMENT_COLUMNS_SQL,
+        ))
+        .bind(task_id)
+        .bind(user_id)
+        .bind(content)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(comment_from_row(row))
+    }
+
+    // Quarantined comments are only included for moderators, and the ones in
+    // `held` for their author. Without a limit the whole thread is returned.
+    pub async fn get_task_comments(
+        pool: &PgPool,
+        task_id: Uuid,
+        include_quarantined: bool,
+        held: &[Uuid],
+        limit: Option<i64>,
+        offset: i64,
+    ) -> Result<Vec<TaskComment>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {columns}
+            FROM task_comments 
+            WHERE task_id = $1 AND expired_at IS NULL AND ($2 OR quarantined_at IS NULL OR id = ANY($5))
+            ORDER BY pinned DESC, created_at ASC, id ASC
+            LIMIT $3 OFFSET $4
+            "#,
+            columns = COMMENT_COLUMNS_SQL,
+        ))
+        .bind(task_id)
+        .bind(include_quarantined)
+        .bind(limit)
+        .bind(offset)
+        .bind(held)
+        .fetch_all(pool)
+        .await?;
+
+        let comments = rows.into_iter().map(comment_from_row).collect();
+
+        Ok(comments)
+    }
+
+    // The comment with its author, task and position in the task's comment
+    // list (in get_task_comments order, quarantined comments left out)
+    pub async fn get_comment_context(pool: &PgPool, comment_id: Uuid) -> Result<CommentContext, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            SELECT
+                c.id, c.task_id, c.user_id, c.content, c.is_system, c.pinned, c.created_at, c.updated_at,
+                u.username, u.display_name, u.avatar_url,
+                t.project_id, t.number AS task_number, t.title AS task_title, t.status AS task_status,
+                (
+                    SELECT COUNT(*) FROM task_comments o
+                    WHERE o.task_id = c.task_id AND o.quarantined_at IS NULL AND o.expired_at IS NULL
+                      AND (o.pinned > c.pinned
+                           OR (o.pinned = c.pinned AND (o.created_at, o.id) < (c.created_at, c.id)))
+                ) AS comment_offset
+            FROM task_comments c
+            INNER JOIN users u ON u.id = c.user_id
+            INNER JOIN tasks t ON t.id = c.task_id AND t.expired_at IS NULL
+            INNER JOIN projects p ON p.id = t.project_id AND p.deleted_at IS NULL
+            WHERE c.id = $1 AND c.quarantined_at IS NULL AND c.expired_at IS NULL
+            "#
+        )
+        .bind(comment_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Comment not found".to_string()))?;
+
+        let user = UserSummary {
+            id: row.get("user_id"),
+            username: row.get("username"),
+            display_name: row.get("display_name"),
+            avatar_url: row.get("avatar_url"),
+        };
+        let project_id: Uuid = row.get("project_id");
+        let task = RelatedTask {
+            id: row.get("task_id"),
+            project_id,
+            number: row.get("task_number"),
+            title: row.get("task_title"),
+            status: row.get("task_status"),
+        };
+        let offset = row.get("comment_offset");
+
+        Ok(CommentContext {
+            comment: comment_from_row(row),
+            user,
+            task,
+            project_id,
+            offset,
+        })
+    }
+
+    // Comments of one task containing `query`, oldest first, with their authors
+    pub async fn search_task_comments(
+        pool: &PgPool,
+        task_id: Uuid,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<(TaskComment, UserSummary)>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                c.id, c.task_id, c.user_id, c.content, c.is_system, c.pinned, c.created_at, c.updated_at,
+                u.username, u.display_name, u.avatar_url
+            FROM task_comments c
+            INNER JOIN users u ON c.user_id = u.id
+            WHERE c.task_id = $1 AND c.quarantined_at IS NULL AND c.expired_at IS NULL AND c.content ILIKE '%' || $2 || '%' ESCAPE '\'
+            ORDER BY c.created_at ASC
+            LIMIT $3
+            "#
+        )
+        .bind(task_id)
+        .bind(crate::utils::search::escape_like(query))
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        let comments = rows.into_iter().map(|row| {
+            let user = UserSummary {
+                id: row.get("user_id"),
+                username: row.get("username"),
+                display_name: row.get("display_name"),
+                avatar_url: row.get("avatar_url"),
+            };
+            let comment = comment_from_row(row);
+
+            (comment, user)
+        }).collect();
+
+        Ok(comments)
+    }
+
+    pub async fn get_comment_by_id(
+        pool: &PgPool,
+        comment_id: Uuid,
+    ) -> Result<TaskComment, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT {columns}
+            FROM task_comments 
+            WHERE id = $1 AND expired_at IS NULL
+            "#,
+            columns = COMMENT_COLUMNS_SQL,
+        ))
+        .bind(comment_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::NotFound("Comment not found".to_string()),
+            _ => AppError::DatabaseError(e.to_string()),
+        })?;
+
+        Ok(comment_from_row(row))
+    }
+
+    // Insert-or-update keyed by a fixed id, used by the seed command
+    pub async fn upsert_comment(
+        pool: &PgPool,
+        comment_id: Uuid,
+        task_id: Uuid,
+        user_id: Uuid,
+        request: &CreateTaskCommentRequest,
+    ) -> Result<TaskComment, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO task_comments (id, task_id, user_id, content)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (id) DO UPDATE
+            SET content = EXCLUDED.content
+            RETURNING {columns}
+            "#,
+            columns = COMMENT_COLUMNS_SQL,
+        ))
+        .bind(comment_id)
+        .bind(task_id)
+        .bind(user_id)
+        .bind(&request.content)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(comment_from_row(row))
+    }
+
+    pub async fn delete_comment(
+        pool: &PgPool,
+        comment_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query(
+            "DELETE FROM task_comments WHERE id = $1 AND user_id = $2"
+        )
+        .bind(comment_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Comment not found or not authorized".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+pub struct JobQueries;
+
+fn job_from_row(row: PgRow) -> Job {
+    Job {
+        id: row.get("id"),
+        job_type: row.get("job_type"),
+        payload: row.get("payload"),
+        status: row.get("status"),
+        attempts: row.get("attempts"),
+        max_attempts: row.get("max_attempts"),
+        run_at: row.get("run_at"),
+        locked_at: row.get("locked_at"),
+        last_error: row.get("last_error"),
+        ordering_key: row.get("ordering_key"),
+        sequence: row.get("sequence"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+impl JobQueries {
+    pub async fn enqueue(
+        pool: &PgPool,
+        job_type: &str,
+        payload: serde_json::Value,
+        run_at: DateTime<Utc>,
+        max_attempts: i32,
+    ) -> Result<Job, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            INSERT INTO jobs (job_type, payload, run_at, max_attempts)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, job_type, payload, status, attempts, max_attempts, run_at, locked_at, last_error, ordering_key, sequence, created_at, updated_at
+            "#
+        )
+        .bind(job_type)
+        .bind(payload)
+        .bind(run_at)
+        .bind(max_attempts)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(job_from_row(row))
+    }
+
+    // Queues the job behind the others with the same ordering key, under the
+    // key's next sequence number
+    pub async fn enqueue_ordered(
+        pool: &PgPool,
+        job_type: &str,
+        ordering_key: &str,
+        payload: serde_json::Value,
+        max_attempts: i32,
+    ) -> Result<Job, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            WITH next AS (
+                INSERT INTO job_sequences (ordering_key, last_sequence)
+                VALUES ($2, 1)
+                ON CONFLICT (ordering_key) DO UPDATE SET last_sequence = job_sequences.last_sequence + 1
+                RETURNING last_sequence
+            )
+            INSERT INTO jobs (job_type, ordering_key, sequence, payload, max_attempts)
+            SELECT $1, $2, last_sequence, $3, $4 FROM next
+            RETURNING id, job_type, payload, status, attempts, max_attempts, run_at, locked_at, last_error, ordering_key, sequence, created_at, updated_at
+            "#
+        )
+        .bind(job_type)
+        .bind(ordering_key)
+        .bind(payload)
+        .bind(max_attempts)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(job_from_row(row))
+    }
+
+    // Claims the next due job. SKIP LOCKED lets several workers poll the same
+    // table; jobs stuck in "running" (worker crashed) are reclaimed after 15 minutes.
+    // An ordered job waits until every earlier job with its key has completed,
+    // so a job waiting for a retry or one that failed for good holds up the rest.
+    pub async fn claim_next(pool: &PgPool) -> Result<Option<Job>, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET status = 'running', attempts = attempts + 1, locked_at = NOW()
+            WHERE id = (
+                SELECT id FROM jobs
+                WHERE ((status = 'pending' AND run_at <= NOW())
+                   OR (status = 'running' AND locked_at < NOW() - INTERVAL '15 minutes'))
+                  AND NOT EXISTS (
+                      SELECT 1 FROM jobs earlier
+                      WHERE earlier.ordering_key = jobs.ordering_key
+                        AND earlier.sequence < jobs.sequence
+                        AND earlier.status <> 'completed'
+                  )
+                ORDER BY run_at ASC
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, job_type, payload, status, attempts, max_attempts, run_at, locked_at, last_error, ordering_key, sequence, created_at, updated_at
+            "#
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(job_from_row))
+    }
+
+    pub async fn has_pending_job(pool: &PgPool, job_type: &str) -> Result<bool, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            "SELECT EXISTS(SELECT 1 FROM jobs WHERE job_type = $1 AND status = 'pending') AS pending"
+        )
+        .bind(job_type)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.get("pending"))
+    }
+
+    pub async fn mark_completed(pool: &PgPool, job_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query(
+            "UPDATE jobs SET status = 'completed', locked_at = NULL, last_error = NULL WHERE id = $1"
+        )
+        .bind(job_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn schedule_retry(
+        pool: &PgPool,
+        job_id: Uuid,
+        run_at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query(
+            "UPDATE jobs SET status = 'pending', run_at = $2, locked_at = NULL, last_error = $3 WHERE id = $1"
+        )
+        .bind(job_id)
+        .bind(run_at)
+        .bind(error)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn mark_failed(pool: &PgPool, job_id: Uuid, error: &str) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query(
+            "UPDATE jobs SET status = 'failed', locked_at = NULL, last_error = $2 WHERE id = $1"
+        )
+        .bind(job_id)
+        .bind(error)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Queues the failed jobs of an ordering key again, with fresh attempts, so
+    // the key's jobs go on in order. Returns how many were queued.
+    pub async fn resume_ordered(pool: &PgPool, ordering_key: &str) -> Result<u64, AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs SET status = 'pending', attempts = 0, run_at = NOW(), locked_at = NULL
+            WHERE ordering_key = $1 AND status = 'failed'
+            "#
+        )
+        .bind(ordering_key)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list_jobs(
+        pool: &PgPool,
+        status: Option<JobStatus>,
+        job_type: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Job>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, job_type, payload, status, attempts, max_attempts, run_at, locked_at, last_error, ordering_key, sequence, created_at, updated_at
+            FROM jobs
+            WHERE ($1::job_status IS NULL OR status = $1)
+              AND ($2::varchar IS NULL OR job_type = $2)
+            ORDER BY created_at DESC
+            LIMIT $3
+            "#
+        )
+        .bind(status)
+        .bind(job_type)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(job_from_row).collect())
+    }
+}
+
+pub struct EmailQueries;
+
+impl EmailQueries {
+    pub async fn log_email(
+        pool: &PgPool,
+        user_id: Option<Uuid>,
+        recipient: &str,
+        template: &str,
+        subject: &str,
+        status: EmailStatus,
+        error: Option<&str>,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query(
+            r#"
+            INSERT INTO email_log (user_id, recipient, template, subject, status, error)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#
+        )
+        .bind(user_id)
+        .bind(recipient)
+        .bind(template)
+        .bind(subject)
+        .bind(status)
+        .bind(error)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_recent_emails(pool: &PgPool, limit: i64) -> Result<Vec<EmailLogEntry>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, user_id, recipient, template, subject, status, error, created_at
+            FROM email_log
+            ORDER BY created_at DESC
+            LIMIT $1
+            "#
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        let entries = rows.into_iter().map(|row| EmailLogEntry {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            recipient: row.get("recipient"),
+            template: row.get("template"),
+            subject: row.get("subject"),
+            status: row.get("status"),
+            error: row.get("error"),
+            created_at: row.get("created_at"),
+        }).collect();
+
+        Ok(entries)
+    }
+
+    pub async fn get_opted_out_templates(pool: &PgPool, user_id: Uuid) -> Result<Vec<String>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            "SELECT template FROM email_opt_outs WHERE user_id = $1 ORDER BY template"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.get("template")).collect())
+    }
+
+    pub async fn is_opted_out(pool: &PgPool, user_id: Uuid, template: &str) -> Result<bool, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            "SELECT EXISTS(SELECT 1 FROM email_opt_outs WHERE user_id = $1 AND template = $2)"
+        )
+        .bind(user_id)
+        .bind(template)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.get::<bool, _>("exists"))
+    }
+
+    pub async fn set_opted_out_templates(
+        pool: &PgPool,
+        user_id: Uuid,
+        templates: &[String],
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("DELETE FROM email_opt_outs WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for template in templates {
+            sqlx::query("INSERT INTO email_opt_outs (user_id, template) VALUES ($1, $2)")
+                .bind(user_id)
+                .bind(template)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+pub struct IntegrationQueries;
+
+fn integration_from_row(row: PgRow) -> ProjectIntegration {
+    ProjectIntegration {
+        id: row.get("id"),
+        project_id: row.get("project_id"),
+        kind: row.get("kind"),
+        config: row.get("config"),
+        events: serde_json::from_value(row.get("events")).unwrap_or_default(),
+        enabled: row.get("enabled"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+impl IntegrationQueries {
+    pub async fn upsert_integration(
+        pool: &PgPool,
+        project_id: Uuid,
+        kind: &str,
+        config: serde_json::Value,
+        events: &[String],
+        enabled: bool,
+        created_by: Uuid,
+    ) -> Result<ProjectIntegration, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            INSERT INTO project_integrations (project_id, kind, config, events, enabled, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (project_id, kind) DO UPDATE
+            SET config = EXCLUDED.config, events = EXCLUDED.events, enabled = EXCLUDED.enabled
+            RETURNING id, project_id, kind, config, events, enabled, created_by, created_at, updated_at
+            "#
+        )
+        .bind(project_id)
+        .bind(kind)
+        .bind(config)
+        .bind(serde_json::to_value(events).unwrap_or(serde_json::Value::Array(vec![])))
+        .bind(enabled)
+        .bind(created_by)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(integration_from_row(row))
+    }
+
+    pub async fn get_integration(
+        pool: &PgPool,
+        project_id: Uuid,
+        kind: &str,
+    ) -> Result<Option<ProjectIntegration>, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            SELECT id, project_id, kind, config, events, enabled, created_by, created_at, updated_at
+            FROM project_integrations
+            WHERE project_id = $1 AND kind = $2
+            "#
+        )
+        .bind(project_id)
+        .bind(kind)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(integration_from_row))
+    }
+
+    pub async fn set_integration_enabled(
+        pool: &PgPool,
+        project_id: Uuid,
+        kind: &str,
+        enabled: bool,
+    ) -> Result<ProjectIntegration, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            UPDATE project_integrations SET enabled = $3
+            WHERE project_id = $1 AND kind = $2
+            RETURNING id, project_id, kind, config, events, enabled, created_by, created_at, updated_at
+            "#
+        )
+        .bind(project_id)
+        .bind(kind)
+        .bind(enabled)
+        .fetch_optional(pool)
+        .await?;
+
+        row.map(integration_from_row)
+            .ok_or_else(|| AppError::NotFound("Integration not found".to_string()))
+    }
+
+    // Integrations of one kind whose config has `key` set to `value` (case-insensitive),
+    // leaving out those of trashed projects
+    pub async fn find_integrations_by_config(
+        pool: &PgPool,
+        kind: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<Vec<ProjectIntegration>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, project_id, kind, config, events, enabled, created_by, created_at, updated_at
+            FROM project_integrations i
+            WHERE kind = $1 AND LOWER(config->>$2) = LOWER($3)
+              AND NOT EXISTS (SELECT 1 FROM projects p WHERE p.id = i.project_id AND p.deleted_at IS NOT NULL)
+            "#
+        )
+        .bind(kind)
+        .bind(key)
+        .bind(value)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(integration_from_row).collect())
+    }
+
+    pub async fn delete_integration(pool: &PgPool, project_id: Uuid, kind: &str) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query("DELETE FROM project_integrations WHERE project_id = $1 AND kind = $2")
+            .bind(project_id)
+            .bind(kind)
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Integration not found".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+pub struct TaskLinkQueries;
+
+fn task_link_from_row(row: PgRow) -> TaskLink {
+    TaskLink {
+        id: row.get("id"),
+        task_id: row.get("task_id"),
+        kind: row.get("kind"),
+        external_id: row.get("external_id"),
+        url: row.get("url"),
+        title: row.get("title"),
+        author: row.get("author"),
+        state: row.get("state"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+impl TaskLinkQueries {
+    // Re-delivered webhooks update the existing link (e.g. a PR being merged)
+    #[allow(clippy::too_many_arguments)]
+    pub async fn upsert_link(
+        pool: &PgPool,
+        task_id: Uuid,
+        kind: TaskLinkKind,
+        external_id: &str,
+        url: &str,
+        title: &str,
+        author: Option<&str>,
+        state: Option<&str>,
+    ) -> Result<TaskLink, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            INSERT INTO task_links (task_id, kind, external_id, url, title, author, state)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (task_id, kind, external_id) DO UPDATE
+            SET url = EXCLUDED.url, title = EXCLUDED.title, author = EXCLUDED.author, state = EXCLUDED.state
+            RETURNING id, task_id, kind, external_id, url, title, author, state, created_at, updated_at
+            "#
+        )
+        .bind(task_id)
+        .bind(kind)
+        .bind(external_id)
+        .bind(url)
+        .bind(title)
+        .bind(author)
+        .bind(state)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(task_link_from_row(row))
+    }
+
+    pub async fn get_task_links(pool: &PgPool, task_id: Uuid) -> Result<Vec<TaskLink>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, task_id, kind, external_id, url, title, author, state, created_at, updated_at
+            FROM task_links
+            WHERE task_id = $1
+            ORDER BY created_at ASC
+            "#
+        )
+        .bind(task_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(task_link_from_row).collect())
+    }
+}
+
+pub struct TaskRelationQueries;
+
+impl TaskRelationQueries {
+    // Stores the relation once, flipped for "is duplicated by"
+    pub async fn create_relation(
+        pool: &PgPool,
+        task_id: Uuid,
+        related: &Task,
+        relation: TaskRelationType,
+        created_by: Uuid,
+    ) -> Result<TaskRelation, AppError> {
+        let _timer = query_timer!();
+        let (kind, related_is_source) = relation.stored();
+        let (source_task_id, target_task_id) = if related_is_source {
+            (related.id, task_id)
+        } else {
+            (task_id, related.id)
+        };
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO task_relations (source_task_id, target_task_id, kind, created_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT DO NOTHING
+            RETURNING id, created_at
+            "#
+        )
+        .bind(source_task_id)
+        .bind(target_task_id)
+        .bind(kind)
+        .bind(created_by)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::Conflict("The tasks are already related".to_string()))?;
+
+        Ok(TaskRelation {
+            id: row.get("id"),
+            relation,
+            task: related.into(),
+            created_by: Some(created_by),
+            created_at: row.get("created_at"),
+        })
+    }
+
+    // Relations of a task as seen from it. Related tasks in projects the viewer
+    // isn't a member of are left out.
+    pub async fn get_task_relations(
+        pool: &PgPool,
+        task_id: Uuid,
+        viewer_id: Uuid,
+    ) -> Result<Vec<TaskRelation>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT r.id, r.kind, r.source_task_id = $1 AS is_source, r.created_by, r.created_at,
+                   t.id AS task_id, t.project_id, t.number, t.title, t.status
+            FROM task_relations r
+            JOIN tasks t ON t.id = CASE WHEN r.source_task_id = $1 THEN r.target_task_id ELSE r.source_task_id END
+            JOIN projects p ON p.id = t.project_id AND p.deleted_at IS NULL
+            JOIN project_members pm ON pm.project_id = t.project_id AND pm.user_id = $2
+            WHERE (r.source_task_id = $1 OR r.target_task_id = $1) AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+            ORDER BY r.created_at ASC
+            "#
+        )
+        .bind(task_id)
+        .bind(viewer_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| TaskRelation {
+                id: row.get("id"),
+                relation: TaskRelationType::seen_from(row.get("kind"), row.get("is_source")),
+                task: RelatedTask {
+                    id: row.get("task_id"),
+                    project_id: row.get("project_id"),
+                    number: row.get("number"),
+                    title: row.get("title"),
+                    status: row.get("status"),
+                },
+                created_by: row.get("created_by"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+
+    // Removes the relation between the two tasks, whichever way round it was stored
+    pub async fn delete_relation(pool: &PgPool, task_id: Uuid, related_task_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query(
+            r#"
+            DELETE FROM task_relations
+            WHERE (source_task_id = $1 AND target_task_id = $2)
+               OR (source_task_id = $2 AND target_task_id = $1)
+            "#
+        )
+        .bind(task_id)
+        .bind(related_task_id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Task relation not found".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+pub struct DigestQueries;
+
+fn digest_task_from_row(row: PgRow) -> DigestTask {
+    DigestTask {
+        id: row.get("id"),
+        project_id: row.get("project_id"),
+        project_name: row.get("project_name"),
+        number: row.get("number"),
+        title: row.get("title"),
+        status: row.get("status"),
+        due_date: row.get("due_date"),
+    }
+}
+
+impl DigestQueries {
+    pub async fn get_preferences(pool: &PgPool, user_id: Uuid) -> Result<DigestPreferences, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            "SELECT frequency, timezone, last_digest_sent_at FROM digest_preferences WHERE user_id = $1"
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(match row {
+            Some(row) => DigestPreferences {
+                frequency: row.get("frequency"),
+                timezone: row.get("timezone"),
+                last_digest_sent_at: row.get("last_digest_sent_at"),
+            },
+            None => DigestPreferences {
+                frequency: DigestFrequency::Off,
+                timezone: "UTC".to_string(),
+                last_digest_sent_at: None,
+            },
+        })
+    }
+
+    pub async fn set_preferences(
+        pool: &PgPool,
+        user_id: Uuid,
+        frequency: DigestFrequency,
+        timezone: &str,
+    ) -> Result<DigestPreferences, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            INSERT INTO digest_preferences (user_id, frequency, timezone)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE
+            SET frequency = EXCLUDED.frequency, timezone = EXCLUDED.timezone
+            RETURNING frequency, timezone, last_digest_sent_at
+            "#
+        )
+        .bind(user_id)
+        .bind(frequency)
+        .bind(timezone)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(DigestPreferences {
+            frequency: row.get("frequency"),
+            timezone: row.get("timezone"),
+            last_digest_sent_at: row.get("last_digest_sent_at"),
+        })
+    }
+
+    // Claims every digest that is due: 8am local time each day (daily) or on
+    // Monday (weekly) and not yet sent for that period. The marker is updated in
+    // the same statement, so concurrent schedulers never claim a user twice.
+    pub async fn claim_due_digests(pool: &PgPool) -> Result<Vec<DueDigest>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            WITH due AS (
+                SELECT dp.user_id, dp.last_digest_sent_at AS previous_sent_at,
+                       (CASE dp.frequency
+                            WHEN 'daily' THEN date_trunc('day', NOW() AT TIME ZONE dp.timezone)
+                            ELSE date_trunc('week', NOW() AT TIME ZONE dp.timezone)
+                        END + INTERVAL '8 hours') AT TIME ZONE dp.timezone AS due_at
+                FROM digest_preferences dp
+                JOIN users u ON u.id = dp.user_id
+                WHERE dp.frequency <> 'off' AND u.is_active = true
+                FOR UPDATE OF dp SKIP LOCKED
+            )
+            UPDATE digest_preferences dp
+            SET last_digest_sent_at = NOW()
+            FROM due, users u
+            WHERE dp.user_id = due.user_id
+              AND u.id = dp.user_id
+              AND NOW() >= due.due_at
+              AND (due.previous_sent_at IS NULL OR due.previous_sent_at < due.due_at)
+            RETURNING dp.user_id, u.email, u.username, u.display_name, dp.frequency, due.previous_sent_at
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| DueDigest {
+            user_id: row.get("user_id"),
+            email: row.get("email"),
+            username: row.get("username"),
+            display_name: row.get("display_name"),
+            frequency: row.get("frequency"),
+            previous_sent_at: row.get("previous_sent_at"),
+        }).collect())
+    }
+
+    // Open tasks assigned to the user that are due before `until`
+    pub async fn get_tasks_due_soon(
+        pool: &PgPool,
+        user_id: Uuid,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<DigestTask>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT t.id, t.project_id, p.name AS project_name, t.number, t.title, t.status, t.due_date
+            FROM tasks t
+            JOIN projects p ON p.id = t.project_id
+            LEFT JOIN digest_preferences tz ON tz.user_id = t.assigned_to
+            WHERE t.assigned_to = $1 AND t.status <> 'done' AND t.due_date IS NOT NULL AND {due_at} <= $2
+              AND p.is_active = true AND p.deleted_at IS NULL AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+            ORDER BY {due_at} ASC
+            LIMIT 20
+            "#,
+            due_at = DUE_AT_SQL,
+        ))
+        .bind(user_id)
+        .bind(until)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(digest_task_from_row).collect())
+    }
+
+    // Tasks (re)assigned to the user by someone else since `since`
+    pub async fn get_newly_assigned_tasks(
+        pool: &PgPool,
+        user_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DigestTask>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT t.id, t.project_id, p.name AS project_name, t.number, t.title, t.status, t.due_date
+            FROM tasks t
+            JOIN projects p ON p.id = t.project_id
+            WHERE t.assigned_to = $1 AND t.assigned_at > $2 AND t.created_by <> $1 AND t.status <> 'done'
+              AND t.quarantined_at IS NULL AND t.expired_at IS NULL AND p.deleted_at IS NULL
+            ORDER BY t.assigned_at DESC
+            LIMIT 20
+            "#
+        )
+        .bind(user_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(digest_task_from_row).collect())
+    }
+
+    // Comments mentioning @username since `since` in the user's projects.
+    // There is no read tracking, so everything after the previous digest counts as unread.
+    pub async fn get_mentions(
+        pool: &PgPool,
+        user_id: Uuid,
+        username: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DigestMention>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT c.id AS comment_id, t.id AS task_id, t.project_id, t.title AS task_title, p.name AS project_name,
+                   u.display_name AS author, c.content, c.created_at
+            FROM task_comments c
+            JOIN tasks t ON t.id = c.task_id
+            JOIN projects p ON p.id = t.project_id
+            JOIN project_members pm ON pm.project_id = p.id AND pm.user_id = $1
+            JOIN users u ON u.id = c.user_id
+            WHERE c.created_at > $3 AND c.user_id <> $1 AND p.deleted_at IS NULL
+              AND c.quarantined_at IS NULL AND c.expired_at IS NULL AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+              AND c.content ~* ('(^|[^[:alnum:]_])@' || $2 || '($|[^[:alnum:]_])')
+            ORDER BY c.created_at DESC
+            LIMIT 20
+            "#
+        )
+        .bind(user_id)
+        .bind(username)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| DigestMention {
+            comment_id: row.get("comment_id"),
+            task_id: row.get("task_id"),
+            project_id: row.get("project_id"),
+            task_title: row.get("task_title"),
+            project_name: row.get("project_name"),
+            author: row.get("author"),
+            content: row.get("content"),
+            created_at: row.get("created_at"),
+        }).collect())
+    }
+
+    // Activity counts for every active project the user belongs to
+    pub async fn get_project_activity(
+        pool: &PgPool,
+        user_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ProjectActivity>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT p.id AS project_id, p.name AS project_name,
+                   COUNT(t.id) FILTER (WHERE t.created_at > $2) AS tasks_created,
+                   COUNT(t.id) FILTER (WHERE t.status = 'done' AND t.updated_at > $2) AS tasks_completed,
+                   COALESCE((
+                       SELECT COUNT(*) FROM task_comments c
+                       JOIN tasks ct ON ct.id = c.task_id
+                       WHERE ct.project_id = p.id AND c.created_at > $2
+                         AND c.quarantined_at IS NULL AND c.expired_at IS NULL AND ct.quarantined_at IS NULL AND ct.expired_at IS NULL
+                   ), 0) AS comments_added
+            FROM projects p
+            JOIN project_members pm ON pm.project_id = p.id AND pm.user_id = $1
+            LEFT JOIN tasks t ON t.project_id = p.id AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+            WHERE p.is_active = true AND p.deleted_at IS NULL
+            GROUP BY p.id, p.name
+            ORDER BY p.name ASC
+            "#
+        )
+        .bind(user_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| ProjectActivity {
+            project_id: row.get("project_id"),
+            project_name: row.get("project_name"),
+            tasks_created: row.get("tasks_created"),
+            tasks_completed: row.get("tasks_completed"),
+            comments_added: row.get("comments_added"),
+        }).collect())
+    }
+}
+
+const REPORT_COLUMNS_SQL: &str = "id, project_id, name, cadence, weekday, hour, timezone, sections, recipients, \
+    last_comment_id, last_sent_at, created_by, created_at";
+
+fn report_from_row(row: &PgRow) -> Result<ProjectReport, AppError> {
+    let sections: serde_json::Value = row.get("sections");
+    let recipients: serde_json::Value = row.get("recipients");
+    Ok(ProjectReport {
+        id: row.get("id"),
+        project_id: row.get("project_id"),
+        name: row.get("name"),
+        cadence: row.get("cadence"),
+        weekday: row.get("weekday"),
+        hour: row.get("hour"),
+        timezone: row.get("timezone"),
+        sections: serde_json::from_value(sections)?,
+        recipients: serde_json::from_value(recipients)?,
+        last_comment_id: row.get("last_comment_id"),
+        last_sent_at: row.get("last_sent_at"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+    })
+}
+
+fn report_task_from_row(row: PgRow) -> ReportTask {
+    ReportTask {
+        id: row.get("id"),
+        number: row.get("number"),
+        title: row.get("title"),
+        assigned_to: row.get("assigned_to"),
+        due_date: row.get("due_date"),
+    }
+}
+
+pub struct ReportQueries;
+
+impl ReportQueries {
+    pub async fn create_report(
+        pool: &PgPool,
+        project_id: Uuid,
+        request: &CreateProjectReportRequest,
+        created_by: Uuid,
+    ) -> Result<ProjectReport, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO project_reports (project_id, name, cadence, weekday, hour, timezone, sections, recipients, created_by)
+            VALUES ($1, $2, $3, COALESCE($4, 5), COALESCE($5, 16), COALESCE($6, 'UTC'), $7, $8, $9)
+            RETURNING {columns}
+            "#,
+            columns = REPORT_COLUMNS_SQL,
+        ))
+        .bind(project_id)
+        .bind(request.name.trim())
+        .bind(request.cadence)
+        .bind(request.weekday)
+        .bind(request.hour)
+        .bind(request.timezone.as_deref())
+        .bind(serde_json::to_value(&request.sections)?)
+        .bind(serde_json::to_value(&request.recipients)?)
+        .bind(created_by)
+        .fetch_one(pool)
+        .await?;
+
+        report_from_row(&row)
+    }
+
+    pub async fn get_project_reports(pool: &PgPool, project_id: Uuid) -> Result<Vec<ProjectReport>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            "SELECT {columns} FROM project_reports WHERE project_id = $1 ORDER BY created_at ASC",
+            columns = REPORT_COLUMNS_SQL,
+        ))
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        rows.iter().map(report_from_row).collect()
+    }
+
+    pub async fn get_report(pool: &PgPool, project_id: Uuid, report_id: Uuid) -> Result<ProjectReport, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            "SELECT {columns} FROM project_reports WHERE id = $1 AND project_id = $2",
+            columns = REPORT_COLUMNS_SQL,
+        ))
+        .bind(report_id)
+        .bind(project_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Report not found".to_string()))?;
+
+        report_from_row(&row)
+    }
+
+    pub async fn delete_report(pool: &PgPool, project_id: Uuid, report_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query("DELETE FROM project_reports WHERE id = $1 AND project_id = $2")
+            .bind(report_id)
+            .bind(project_id)
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Report not found".to_string()));
+        }
+        Ok(())
+    }
+
+    // Marks every report whose time has come as sent and returns them. A report
+    // is due at `hour` on each day (daily) or on `weekday` (weekly), in its
+    // timezone; one created after this period's time waits for the next.
+    pub async fn claim_due_reports(pool: &PgPool) -> Result<Vec<DueReport>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            WITH due AS (
+                SELECT r.id, r.last_sent_at AS previous_sent_at,
+                       (CASE r.cadence
+                            WHEN 'daily' THEN date_trunc('day', NOW() AT TIME ZONE r.timezone)
+                            ELSE date_trunc('week', NOW() AT TIME ZONE r.timezone) + (r.weekday - 1) * INTERVAL '1 day'
+                        END + r.hour * INTERVAL '1 hour') AT TIME ZONE r.timezone AS due_at
+                FROM project_reports r
+                JOIN projects p ON p.id = r.project_id
+                WHERE p.is_active = true AND p.deleted_at IS NULL
+                FOR UPDATE OF r SKIP LOCKED
+            )
+            UPDATE project_reports r
+            SET last_sent_at = NOW()
+            FROM due
+            WHERE r.id = due.id
+              AND NOW() >= due.due_at
+              AND COALESCE(due.previous_sent_at, r.created_at) < due.due_at
+            RETURNING r.*, due.previous_sent_at
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(DueReport {
+                    report: report_from_row(row)?,
+                    previous_sent_at: row.get("previous_sent_at"),
+                })
+            })
+            .collect()
+    }
+
+    pub async fn set_last_comment(pool: &PgPool, report_id: Uuid, comment_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query("UPDATE project_reports SET last_comment_id = $2 WHERE id = $1")
+            .bind(report_id)
+            .bind(comment_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    // Tasks moved to done since `since`, going by their last update like the digest does
+    pub async fn get_completed_tasks(pool: &PgPool, project_id: Uuid, since: DateTime<Utc>) -> Result<Vec<ReportTask>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT t.id, t.number, t.title, t.assigned_to, t.due_date
+            FROM tasks t
+            WHERE t.project_id = $1 AND t.status = 'done' AND t.updated_at > $2 AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+            ORDER BY t.updated_at ASC
+            "#
+        )
+        .bind(project_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(report_task_from_row).collect())
+    }
+
+    pub async fn get_created_tasks(pool: &PgPool, project_id: Uuid, since: DateTime<Utc>) -> Result<Vec<ReportTask>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT t.id, t.number, t.title, t.assigned_to, t.due_date
+            FROM tasks t
+            WHERE t.project_id = $1 AND t.created_at > $2 AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+            ORDER BY t.created_at ASC
+            "#
+        )
+        .bind(project_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(report_task_from_row).collect())
+    }
+
+    // Open tasks past their due time, in the assignee's timezone for all-day tasks
+    pub async fn get_overdue_tasks(pool: &PgPool, project_id: Uuid) -> Result<Vec<ReportTask>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT t.id, t.number, t.title, t.assigned_to, t.due_date
+            FROM tasks t
+            LEFT JOIN digest_preferences tz ON tz.user_id = t.assigned_to
+            WHERE t.project_id = $1 AND t.status <> 'done' AND t.due_date IS NOT NULL AND {due_at} < NOW()
+              AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+            ORDER BY {due_at} ASC
+            "#,
+            due_at = DUE_AT_SQL,
+        ))
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(report_task_from_row).collect())
+    }
+}
+
+// A missing counter row reads as zero; the maintenance counters check recreates it
+pub struct CounterQueries;
+
+impl CounterQueries {
+    pub async fn get_team_counters(pool: &PgPool, team_id: Uuid) -> Result<TeamCounters, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT member_count, project_count FROM team_counters WHERE team_id = $1")
+            .bind(team_id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(row
+            .map(|row| TeamCounters { member_count: row.get("member_count"), project_count: row.get("project_count") })
+            .unwrap_or_default())
+    }
+
+    pub async fn get_project_counters(pool: &PgPool, project_id: Uuid) -> Result<ProjectCounters, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT member_count, task_count, open_task_count FROM project_counters WHERE project_id = $1")
+            .bind(project_id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(row
+            .map(|row| ProjectCounters {
+                member_count: row.get("member_count"),
+                task_count: row.get("task_count"),
+                open_task_count: row.get("open_task_count"),
+            })
+            .unwrap_or_default())
+    }
+
+    pub async fn get_attachment_storage(pool: &PgPool, project_id: Uuid) -> Result<AttachmentStorage, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT attachment_count, attachment_bytes FROM project_counters WHERE project_id = $1")
+            .bind(project_id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(row
+            .map(|row| AttachmentStorage {
+                attachment_count: row.get("attachment_count"),
+                attachment_bytes: row.get("attachment_bytes"),
+            })
+            .unwrap_or_default())
+    }
+
+    // The projects using the most attachment storage, trashed ones included
+    // since their files are still on disk
+    pub async fn get_top_storage_projects(pool: &PgPool, limit: i64) -> Result<Vec<ProjectStorage>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT p.id AS project_id, p.name AS project_name, t.id AS team_id, t.name AS team_name,
+                   c.attachment_count, c.attachment_bytes
+            FROM project_counters c
+            JOIN projects p ON p.id = c.project_id
+            JOIN teams t ON t.id = p.team_id
+            WHERE c.attachment_count > 0
+            ORDER BY c.attachment_bytes DESC, p.id
+            LIMIT $1
+            "#
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ProjectStorage {
+                project_id: row.get("project_id"),
+                project_name: row.get("project_name"),
+                team_id: row.get("team_id"),
+                team_name: row.get("team_name"),
+                storage: AttachmentStorage {
+                    attachment_count: row.get("attachment_count"),
+                    attachment_bytes: row.get("attachment_bytes"),
+                },
+            })
+            .collect())
+    }
+}
+
+pub struct StalenessQueries;
+
+impl StalenessQueries {
+    // Active projects with staleness detection turned on, with their creator
+    pub async fn get_enabled_projects(pool: &PgPool) -> Result<Vec<(Uuid, Uuid, StalenessRules)>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, created_by, staleness
+            FROM projects
+            WHERE is_active = true AND deleted_at IS NULL
+              AND COALESCE((staleness->>'enabled')::boolean, false)
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let rules = serde_json::from_value(row.get("staleness")).unwrap_or_default();
+                (row.get("id"), row.get("created_by"), rules)
+            })
+            .collect())
+    }
+
+    // Marks the project's open tasks whose status last changed at or before
+    // that status' cutoff. Tasks that are already stale aren't returned again.
+    pub async fn mark_stale(
+        pool: &PgPool,
+        project_id: Uuid,
+        cutoffs: &[(TaskStatus, DateTime<Utc>)],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<StaleTask>, AppError> {
+        let _timer = query_timer!();
+        let (statuses, times) = cutoff_arrays(cutoffs);
+        let rows = sqlx::query(
+            r#"
+            UPDATE tasks t
+            SET stale_at = $4
+            FROM UNNEST($2::text[], $3::timestamptz[]) AS c(status, cutoff)
+            WHERE t.project_id = $1
+              AND t.stale_at IS NULL
+              AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+              AND t.status::text = c.status
+              AND t.status_changed_at <= c.cutoff
+            RETURNING t.id, t.status, t.status_changed_at
+            "#
+        )
+        .bind(project_id)
+        .bind(&statuses)
+        .bind(&times)
+        .bind(now)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| StaleTask {
+                task_id: row.get("id"),
+                status: row.get("status"),
+                status_changed_at: row.get("status_changed_at"),
+            })
+            .collect())
+    }
+
+    // Unmarks the project's tasks that no longer pass a cutoff, e.g. after a
+    // threshold was raised. Status changes unmark tasks on their own.
+    pub async fn clear_stale(
+        pool: &PgPool,
+        project_id: Uuid,
+        cutoffs: &[(TaskStatus, DateTime<Utc>)],
+    ) -> Result<u64, AppError> {
+        let _timer = query_timer!();
+        let (statuses, times) = cutoff_arrays(cutoffs);
+        let result = sqlx::query(
+            r#"
+            UPDATE tasks t
+            SET stale_at = NULL
+            WHERE t.project_id = $1
+              AND t.stale_at IS NOT NULL
+              AND NOT EXISTS (
+                  SELECT 1 FROM UNNEST($2::text[], $3::timestamptz[]) AS c(status, cutoff)
+                  WHERE t.status::text = c.status AND t.status_changed_at <= c.cutoff
+              )
+            "#
+        )
+        .bind(project_id)
+        .bind(&statuses)
+        .bind(&times)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Tasks stay stale only while their project has detection turned on
+    pub async fn clear_disabled(pool: &PgPool) -> Result<u64, AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query(
+            r#"
+            UPDATE tasks t
+            SET stale_at = NULL
+            FROM projects p
+            WHERE t.project_id = p.id
+              AND t.stale_at IS NOT NULL
+              AND NOT COALESCE((p.staleness->>'enabled')::boolean, false)
+            "#
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_stale_task_ids(pool: &PgPool, project_id: Uuid) -> Result<Vec<Uuid>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query("SELECT id FROM tasks WHERE project_id = $1 AND stale_at IS NOT NULL")
+            .bind(project_id)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| row.get("id")).collect())
+    }
+}
+
+// Statuses are compared as text, in their database spelling
+fn cutoff_arrays(cutoffs: &[(TaskStatus, DateTime<Utc>)]) -> (Vec<String>, Vec<DateTime<Utc>>) {
+    cutoffs
+        .iter()
+        .map(|(status, cutoff)| (status.name().to_lowercase(), *cutoff))
+        .unzip()
+}
+
+const TEMPLATE_COLUMNS_SQL: &str =
+    "t.id, t.name, t.description, t.visibility, t.team_id, t.source_project_id, t.document, t.created_by, t.approved_at, t.created_at";
+
+// Approved templates of the user's teams and approved instance templates
+const TEMPLATE_USABLE_SQL: &str = "t.approved_at IS NOT NULL AND (t.visibility = 'instance' \
+    OR EXISTS (SELECT 1 FROM team_members tm WHERE tm.team_id = t.team_id AND tm.user_id = $1))";
+
+fn template_from_row(row: &PgRow) -> Result<ProjectTemplate, AppError> {
+    Ok(ProjectTemplate {
+        id: row.get("id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        visibility: row.get("visibility"),
+        team_id: row.get("team_id"),
+        source_project_id: row.get("source_project_id"),
+        document: serde_json::from_value(row.get("document"))?,
+        created_by: row.get("created_by"),
+        approved_at: row.get("approved_at"),
+        created_at: row.get("created_at"),
+    })
+}
+
+pub struct TemplateQueries;
+
+impl TemplateQueries {
+    // Team templates are approved right away; instance ones wait for an admin
+    pub async fn publish_template(
+        pool: &PgPool,
+        team_id: Uuid,
+        source_project_id: Uuid,
+        request: &PublishTemplateRequest,
+        document: &ProjectSnapshot,
+        created_by: Uuid,
+    ) -> Result<ProjectTemplate, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO project_templates AS t (name, description, visibility, team_id, source_project_id, document, created_by, approved_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, CASE WHEN $3 = 'team'::template_visibility THEN NOW() END)
+            RETURNING {columns}
+            "#,
+            columns = TEMPLATE_COLUMNS_SQL,
+        ))
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(request.visibility)
+        .bind(team_id)
+        .bind(source_project_id)
+        .bind(serde_json::to_value(document)?)
+        .bind(created_by)
+        .fetch_one(pool)
+        .await?;
+
+        template_from_row(&row)
+    }
+
+    pub async fn get_template(pool: &PgPool, template_id: Uuid) -> Result<ProjectTemplate, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!("SELECT {} FROM project_templates t WHERE t.id = $1", TEMPLATE_COLUMNS_SQL))
+            .bind(template_id)
+            .fetch_optional(pool)
+            .await?;
+
+        match row {
+            Some(row) => template_from_row(&row),
+            None => Err(AppError::NotFound("Template not found".to_string())),
+        }
+    }
+
+    // What the user can start projects from, plus their own templates that
+    // are waiting for approval
+    pub async fn get_user_templates(pool: &PgPool, user_id: Uuid) -> Result<Vec<ProjectTemplate>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {columns}
+            FROM project_templates t
+            WHERE ({usable}) OR (t.approved_at IS NULL AND t.created_by = $1)
+            ORDER BY t.name, t.created_at
+            "#,
+            columns = TEMPLATE_COLUMNS_SQL,
+            usable = TEMPLATE_USABLE_SQL,
+        ))
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        rows.iter().map(template_from_row).collect()
+    }
+
+    // Not found unless the user may start a project from it
+    pub async fn get_usable_template(pool: &PgPool, template_id: Uuid, user_id: Uuid) -> Result<ProjectTemplate, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            "SELECT {columns} FROM project_templates t WHERE t.id = $2 AND {usable}",
+            columns = TEMPLATE_COLUMNS_SQL,
+            usable = TEMPLATE_USABLE_SQL,
+        ))
+        .bind(user_id)
+        .bind(template_id)
+        .fetch_optional(pool)
+        .await?;
+
+        match row {
+            Some(row) => template_from_row(&row),
+            None => Err(AppError::NotFound("Template not found".to_string())),
+        }
+    }
+
+    pub async fn get_pending_templates(pool: &PgPool) -> Result<Vec<ProjectTemplate>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM project_templates t WHERE t.approved_at IS NULL ORDER BY t.created_at",
+            TEMPLATE_COLUMNS_SQL
+        ))
+        .fetch_all(pool)
+        .await?;
+
+        rows.iter().map(template_from_row).collect()
+    }
+
+    // None if there's no such template waiting for approval
+    pub async fn approve_template(pool: &PgPool, template_id: Uuid, approved_by: Uuid) -> Result<Option<ProjectTemplate>, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE project_templates AS t
+            SET approved_at = NOW(), approved_by = $2
+            WHERE t.id = $1 AND t.approved_at IS NULL
+            RETURNING {columns}
+            "#,
+            columns = TEMPLATE_COLUMNS_SQL,
+        ))
+        .bind(template_id)
+        .bind(approved_by)
+        .fetch_optional(pool)
+        .await?;
+
+        row.as_ref().map(template_from_row).transpose()
+    }
+
+    pub async fn delete_template(pool: &PgPool, template_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query("DELETE FROM project_templates WHERE id = $1")
+            .bind(template_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+pub struct QuotaQueries;
+
+impl QuotaQueries {
+    // Teams the user created (i.e. owns)
+    pub async fn count_user_teams(pool: &PgPool, user_id: Uuid) -> Result<i64, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM teams WHERE created_by = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+
+    pub async fn count_team_projects(pool: &PgPool, team_id: Uuid) -> Result<i64, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM projects WHERE team_id = $1")
+            .bind(team_id)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+
+    pub async fn count_team_members(pool: &PgPool, team_id: Uuid) -> Result<i64, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM team_members WHERE team_id = $1")
+            .bind(team_id)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+
+    pub async fn count_project_tasks(pool: &PgPool, project_id: Uuid) -> Result<i64, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM tasks WHERE project_id = $1")
+            .bind(project_id)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+
+    pub async fn get_team_task_counts(pool: &PgPool, team_id: Uuid) -> Result<Vec<ProjectTaskCount>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT p.id AS project_id, p.name AS project_name, COUNT(t.id) AS tasks,
+                   COALESCE(MAX(c.attachment_bytes), 0)::BIGINT AS attachment_bytes
+            FROM projects p
+            LEFT JOIN tasks t ON t.project_id = p.id
+            LEFT JOIN project_counters c ON c.project_id = p.id
+            WHERE p.team_id = $1
+            GROUP BY p.id, p.name
+            ORDER BY tasks DESC, p.name ASC
+            "#
+        )
+        .bind(team_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| ProjectTaskCount {
+            project_id: row.get("project_id"),
+            project_name: row.get("project_name"),
+            tasks: row.get("tasks"),
+            attachment_bytes: row.get("attachment_bytes"),
+        }).collect())
+    }
+
+    pub async fn get_team_limits(pool: &PgPool, team_id: Uuid) -> Result<TeamLimitOverrides, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            SELECT max_projects, max_tasks_per_project, max_members, max_attachment_bytes_per_project
+            FROM team_limits
+            WHERE team_id = $1
+            "#
+        )
+        .bind(team_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|row| TeamLimitOverrides {
+            max_projects: row.get("max_projects"),
+            max_tasks_per_project: row.get("max_tasks_per_project"),
+            max_members: row.get("max_members"),
+            max_attachment_bytes_per_project: row.get("max_attachment_bytes_per_project"),
+        }).unwrap_or_default())
+    }
+
+    pub async fn set_team_limits(
+        pool: &PgPool,
+        team_id: Uuid,
+        limits: &TeamLimitOverrides,
+        updated_by: Uuid,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query(
+            r#"
+            INSERT INTO team_limits (team_id, max_projects, max_tasks_per_project, max_members, max_attachment_bytes_per_project, updated_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (team_id) DO UPDATE
+            SET max_projects = EXCLUDED.max_projects,
+                max_tasks_per_project = EXCLUDED.max_tasks_per_project,
+                max_members = EXCLUDED.max_members,
+                max_attachment_bytes_per_project = EXCLUDED.max_attachment_bytes_per_project,
+                updated_by = EXCLUDED.updated_by
+            "#
+        )
+        .bind(team_id)
+        .bind(limits.max_projects)
+        .bind(limits.max_tasks_per_project)
+        .bind(limits.max_members)
+        .bind(limits.max_attachment_bytes_per_project)
+        .bind(updated_by)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub struct NotificationQueries;
+
+fn notification_from_row(row: PgRow) -> UserNotification {
+    UserNotification {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        kind: row.get("kind"),
+        project_id: row.get("project_id"),
+        task_id: row.get("task_id"),
+        team_id: row.get("team_id"),
+        audit_export_id: row.get("audit_export_id"),
+        actor_id: row.get("actor_id"),
+        count: row.get("count"),
+        read_at: row.get("read_at"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+impl NotificationQueries {
+    pub async fn create_notification(
+        pool: &PgPool,
+        user_id: Uuid,
+        kind: NotificationKind,
+        project_id: Uuid,
+        task_id: Option<Uuid>,
+        actor_id: Option<Uuid>,
+    ) -> Result<UserNotification, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            INSERT INTO notifications (user_id, kind, project_id, task_id, actor_id)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, user_id, kind, project_id, task_id, team_id, audit_export_id, actor_id, count, read_at, created_at, updated_at
+            "#
+        )
+        .bind(user_id)
+        .bind(kind)
+        .bind(project_id)
+        .bind(task_id)
+        .bind(actor_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(notification_from_row(row))
+    }
+
+    // A finished team audit export, for the admin who asked for it
+    pub async fn create_export_notification(pool: &PgPool, export: &AuditExport, user_id: Uuid) -> Result<UserNotification, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            INSERT INTO notifications (user_id, kind, team_id, audit_export_id)
+            VALUES ($1, 'audit_export_ready', $2, $3)
+            RETURNING id, user_id, kind, project_id, task_id, team_id, audit_export_id, actor_id, count, read_at, created_at, updated_at
+            "#
+        )
+        .bind(user_id)
+        .bind(export.team_id)
+        .bind(export.id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(notification_from_row(row))
+    }
+
+    // The user's most recent `kind` notification about the task, read or not
+    pub async fn get_latest_task_notification(
+        pool: &PgPool,
+        user_id: Uuid,
+        kind: NotificationKind,
+        task_id: Uuid,
+    ) -> Result<Option<UserNotification>, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            SELECT id, user_id, kind, project_id, task_id, team_id, audit_export_id, actor_id, count, read_at, created_at, updated_at
+            FROM notifications
+            WHERE user_id = $1 AND kind = $2 AND task_id = $3
+            ORDER BY updated_at DESC
+            LIMIT 1
+            "#
+        )
+        .bind(user_id)
+        .bind(kind)
+        .bind(task_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(notification_from_row))
+    }
+
+    // Counts one more event into an unread notification. None if the user has
+    // read it in the meantime, in which case a new notification is due.
+    pub async fn bump_notification(
+        pool: &PgPool,
+        notification_id: Uuid,
+        actor_id: Uuid,
+    ) -> Result<Option<UserNotification>, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            UPDATE notifications
+            SET count = count + 1, actor_id = $2, updated_at = NOW()
+            WHERE id = $1 AND read_at IS NULL
+            RETURNING id, user_id, kind, project_id, task_id, team_id, audit_export_id, actor_id, count, read_at, created_at, updated_at
+            "#
+        )
+        .bind(notification_id)
+        .bind(actor_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(notification_from_row))
+    }
+
+    pub async fn mark_notifications_read(pool: &PgPool, user_id: Uuid) -> Result<u64, AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query("UPDATE notifications SET read_at = NOW() WHERE user_id = $1 AND read_at IS NULL")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_user_notifications(
+        pool: &PgPool,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<UserNotification>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, user_id, kind, project_id, task_id, team_id, audit_export_id, actor_id, count, read_at, created_at, updated_at
+            FROM notifications
+            WHERE user_id = $1
+            ORDER BY updated_at DESC
+            LIMIT $2
+            "#
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(notification_from_row).collect())
+    }
+}
+
+pub struct TaskMentionQueries;
+
+impl TaskMentionQueries {
+    // Records who a comment (or, without `comment_id`, the task's description)
+    // reached, as (user, group token) pairs. Returns the users actually
+    // recorded: description mentions are recorded once per task and user.
+    pub async fn create_mentions(
+        pool: &PgPool,
+        task_id: Uuid,
+        comment_id: Option<Uuid>,
+        mentioned_by: Uuid,
+        mentions: &[(Uuid, Option<&str>)],
+    ) -> Result<Vec<Uuid>, AppError> {
+        let _timer = query_timer!();
+        let user_ids: Vec<Uuid> = mentions.iter().map(|(user_id, _)| *user_id).collect();
+        let group_tokens: Vec<Option<&str>> = mentions.iter().map(|(_, token)| *token).collect();
+
+        let rows = sqlx::query(
+            r#"
+            INSERT INTO task_mentions (task_id, comment_id, user_id, mentioned_by, group_token)
+            SELECT $1, $2, m.user_id, $3, m.group_token
+            FROM UNNEST($4::uuid[], $5::text[]) AS m(user_id, group_token)
+            ON CONFLICT DO NOTHING
+            RETURNING user_id
+            "#
+        )
+        .bind(task_id)
+        .bind(comment_id)
+        .bind(mentioned_by)
+        .bind(&user_ids)
+        .bind(&group_tokens)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| row.get("user_id")).collect())
+    }
+
+    pub async fn get_task_mentions(pool: &PgPool, task_id: Uuid) -> Result<Vec<TaskMention>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT m.id, m.task_id, m.comment_id, m.mentioned_by, m.group_token, m.created_at,
+                   u.id AS user_id, u.username, u.display_name, u.avatar_url
+            FROM task_mentions m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.task_id = $1
+            ORDER BY m.created_at, u.username
+            "#
+        )
+        .bind(task_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| TaskMention {
+                id: row.get("id"),
+                task_id: row.get("task_id"),
+                comment_id: row.get("comment_id"),
+                user: UserSummary {
+                    id: row.get("user_id"),
+                    username: row.get("username"),
+                    display_name: row.get("display_name"),
+                    avatar_url: row.get("avatar_url"),
+                },
+                mentioned_by: row.get("mentioned_by"),
+                group_token: row.get("group_token"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+}
+
+pub struct TaskReadQueries;
+
+impl TaskReadQueries {
+    pub async fn mark_task_read(pool: &PgPool, user_id: Uuid, task_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query(
+            r#"
+            INSERT INTO task_reads (user_id, task_id, last_read_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (user_id, task_id) DO UPDATE SET last_read_at = NOW()
+            "#
+        )
+        .bind(user_id)
+        .bind(task_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Marks every task of the project read in one statement
+    pub async fn mark_project_read(pool: &PgPool, user_id: Uuid, project_id: Uuid) -> Result<u64, AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query(
+            r#"
+            INSERT INTO task_reads (user_id, task_id, last_read_at)
+            SELECT $1, id, NOW() FROM tasks WHERE project_id = $2
+            ON CONFLICT (user_id, task_id) DO UPDATE SET last_read_at = NOW()
+            "#
+        )
+        .bind(user_id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_read_states(
+        pool: &PgPool,
+        project_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<TaskReadState>, AppError> {
+        let _timer = query_timer!();
+        let sql = format!(
+            r#"
+            SELECT
+                t.id AS task_id,
+                {unread} AS unread,
+                (
+                    SELECT COUNT(*) FROM task_comments c
+                    WHERE c.task_id = t.id
+                      AND c.user_id <> $2
+                      AND c.quarantined_at IS NULL
+                      AND c.expired_at IS NULL
+                      AND (r.last_read_at IS NULL OR c.created_at > r.last_read_at)
+                ) AS unread_comment_count
+            FROM tasks t
+            LEFT JOIN task_reads r ON r.task_id = t.id AND r.user_id = $2
+            WHERE t.project_id = $1 AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+            "#,
+            unread = UNREAD_SQL,
+        );
+
+        let rows = sqlx::query(&sql)
+            .bind(project_id)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| TaskReadState {
+            task_id: row.get("task_id"),
+            unread: row.get("unread"),
+            unread_comment_count: row.get("unread_comment_count"),
+        }).collect())
+    }
+
+    // Changes when the user reads something or anyone comments, neither of
+    // which touches the project's last modified time. Used in list ETags.
+    pub async fn get_read_state_version(
+        pool: &PgPool,
+        project_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<DateTime<Utc>>, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            SELECT GREATEST(
+                (SELECT MAX(r.last_read_at) FROM task_reads r JOIN tasks t ON t.id = r.task_id
+                 WHERE t.project_id = $1 AND r.user_id = $2),
+                (SELECT MAX(c.created_at) FROM task_comments c JOIN tasks t ON t.id = c.task_id
+                 WHERE t.project_id = $1)
+            ) AS version
+            "#
+        )
+        .bind(project_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row.get("version"))
+    }
+}
+
+pub struct TaskOrderQueries;
+
+impl TaskOrderQueries {
+    // Replaces the user's ordering in one statement. Fails without changing
+    // anything unless every task is open and assigned to the user.
+    pub async fn set_order(pool: &PgPool, user_id: Uuid, task_ids: &[Uuid]) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            WITH removed AS (
+                DELETE FROM user_task_order
+                WHERE user_id = $1 AND task_id <> ALL($2)
+            )
+            INSERT INTO user_task_order (user_id, task_id, position)
+            SELECT $1, t.id, o.position::int
+            FROM UNNEST($2::uuid[]) WITH ORDINALITY AS o(id, position)
+            JOIN tasks t ON t.id = o.id AND t.assigned_to = $1 AND t.status <> 'done'
+            ON CONFLICT (user_id, task_id) DO UPDATE SET position = EXCLUDED.position
+            "#
+        )
+        .bind(user_id)
+        .bind(task_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() != task_ids.len() as u64 {
+            return Err(AppError::Invalid(Message::new("ordered_task_not_assigned")));
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Drops tasks the user has completed or no longer has assigned
+    pub async fn prune(pool: &PgPool, user_id: Uuid) -> Result<u64, AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query(
+            r#"
+            DELETE FROM user_task_order o
+            USING tasks t
+            WHERE o.user_id = $1 AND t.id = o.task_id
+              AND (t.assigned_to IS DISTINCT FROM $1 OR t.status = 'done')
+            "#
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+fn signup_code_from_row(row: PgRow) -> SignupCode {
+    SignupCode {
+        id: row.get("id"),
+        code: row.get("code"),
+        max_uses: row.get("max_uses"),
+        uses: row.get("uses"),
+        expires_at: row.get("expires_at"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+    }
+}
+
+pub struct SignupCodeQueries;
+
+impl SignupCodeQueries {
+    pub async fn create_signup_code(
+        pool: &PgPool,
+        code: &str,
+        max_uses: Option<i32>,
+        expires_at: Option<DateTime<Utc>>,
+        created_by: Uuid,
+    ) -> Result<SignupCode, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            INSERT INTO signup_codes (code, max_uses, expires_at, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, code, max_uses, uses, expires_at, created_by, created_at
+            "#
+        )
+        .bind(code)
+        .bind(max_uses)
+        .bind(expires_at)
+        .bind(created_by)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(signup_code_from_row(row))
+    }
+
+    // Counts a use of the code if it is still valid. Returns false for unknown,
+    // expired or used up codes; the check and the increment are one statement.
+    pub async fn redeem_signup_code(pool: &PgPool, code: &str) -> Result<bool, AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query(
+            r#"
+            UPDATE signup_codes
+            SET uses = uses + 1
+            WHERE code = $1
+              AND (expires_at IS NULL OR expires_at > NOW())
+              AND (max_uses IS NULL OR uses < max_uses)
+            "#
+        )
+        .bind(code)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+const ATTACHMENT_COLUMNS_SQL: &str =
+    "id, task_id, uploaded_by, filename, content_type, size_bytes, storage_path, content_sha256, scan_status, scan_detail, \
+     scanned_at, created_at";
+
+fn attachment_from_row(row: PgRow) -> TaskAttachment {
+    TaskAttachment {
+        id: row.get("id"),
+        task_id: row.get("task_id"),
+        uploaded_by: row.get("uploaded_by"),
+        filename: row.get("filename"),
+        content_type: row.get("content_type"),
+        size_bytes: row.get("size_bytes"),
+        storage_path: row.get("storage_path"),
+        content_sha256: row.get("content_sha256"),
+        scan_status: row.get("scan_status"),
+        scan_detail: row.get("scan_detail"),
+        scanned_at: row.get("scanned_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+pub struct AttachmentQueries;
+
+impl AttachmentQueries {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_attachment(
+        pool: &PgPool,
+        attachment_id: Uuid,
+        task_id: Uuid,
+        uploaded_by: Option<Uuid>,
+        filename: &str,
+        content_type: &str,
+        size_bytes: i64,
+        storage_path: &str,
+        content_sha256: &str,
+        scan_status: AttachmentScanStatus,
+    ) -> Result<TaskAttachment, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO task_attachments (id, task_id, uploaded_by, filename, content_type, size_bytes, storage_path, content_sha256, scan_status)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING {columns}
+            "#,
+            columns = ATTACHMENT_COLUMNS_SQL,
+        ))
+        .bind(attachment_id)
+        .bind(task_id)
+        .bind(uploaded_by)
+        .bind(filename)
+        .bind(content_type)
+        .bind(size_bytes)
+        .bind(storage_path)
+        .bind(content_sha256)
+        .bind(scan_status)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(attachment_from_row(row))
+    }
+
+    // Only pending attachments are updated, so a retried scan job can't
+    // overwrite an earlier result. None if the attachment was already scanned.
+    pub async fn record_scan_result(
+        pool: &PgPool,
+        attachment_id: Uuid,
+        scan_status: AttachmentScanStatus,
+        scan_detail: Option<&str>,
+        storage_path: &str,
+    ) -> Result<Option<TaskAttachment>, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE task_attachments
+            SET scan_status = $2, scan_detail = $3, storage_path = $4, scanned_at = NOW()
+            WHERE id = $1 AND scan_status = 'pending'
+            RETURNING {columns}
+            "#,
+            columns = ATTACHMENT_COLUMNS_SQL,
+        ))
+        .bind(attachment_id)
+        .bind(scan_status)
+        .bind(scan_detail)
+        .bind(storage_path)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(attachment_from_row))
+    }
+
+    pub async fn get_task_attachments(pool: &PgPool, task_id: Uuid) -> Result<Vec<TaskAttachment>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {columns}
+            FROM task_attachments
+            WHERE task_id = $1 AND expired_at IS NULL
+            ORDER BY created_at ASC
+            "#,
+            columns = ATTACHMENT_COLUMNS_SQL,
+        ))
+        .bind(task_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(attachment_from_row).collect())
+    }
+
+    pub async fn get_attachment(pool: &PgPool, attachment_id: Uuid) -> Result<TaskAttachment, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT {columns}
+            FROM task_attachments
+            WHERE id = $1 AND expired_at IS NULL
+            "#,
+            columns = ATTACHMENT_COLUMNS_SQL,
+        ))
+        .bind(attachment_id)
+        .fetch_optional(pool)
+        .await?;
+
+        row.map(attachment_from_row)
+            .ok_or_else(|| AppError::NotFound("Attachment not found".to_string()))
+    }
+}
+
+pub struct AuditQueries;
+
+impl AuditQueries {
+    pub async fn record(
+        pool: &PgPool,
+        action: AuditAction,
+        actor_id: Uuid,
+        subject_id: Uuid,
+        method: Option<&str>,
+        path: Option<&str>,
+    ) -> Result<Uuid, AppError> {
+        let _timer = query_timer!();
+        let id = sqlx::query_scalar(
+            r#"
+            INSERT INTO audit_log (action, actor_id, subject_id, method, path)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+            "#
+        )
+        .bind(action)
+        .bind(actor_id)
+        .bind(subject_id)
+        .bind(method)
+        .bind(path)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    // An action concerning a team, shown in its audit export. `ip_address` is
+    // the client's when a request caused it.
+    pub async fn record_team(
+        pool: &PgPool,
+        action: AuditAction,
+        actor_id: Option<Uuid>,
+        team_id: Uuid,
+        path: Option<&str>,
+        ip_address: Option<&str>,
+        details: Option<&serde_json::Value>,
+    ) -> Result<Uuid, AppError> {
+        let _timer = query_timer!();
+        let id = sqlx::query_scalar(
+            r#"
+            INSERT INTO audit_log (action, actor_id, team_id, path, ip_address, details)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id
+            "#
+        )
+        .bind(action)
+        .bind(actor_id)
+        .bind(team_id)
+        .bind(path)
+        .bind(ip_address)
+        .bind(details)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    // For actions the server takes on its own, without an acting user
+    pub async fn record_system(
+        pool: &PgPool,
+        action: AuditAction,
+        subject_id: Option<Uuid>,
+        path: Option<&str>,
+        details: Option<&serde_json::Value>,
+    ) -> Result<Uuid, AppError> {
+        let _timer = query_timer!();
+        let id = sqlx::query_scalar(
+            r#"
+            INSERT INTO audit_log (action, subject_id, path, details)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#
+        )
+        .bind(action)
+        .bind(subject_id)
+        .bind(path)
+        .bind(details)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    pub async fn set_status(pool: &PgPool, entry_id: Uuid, status: i16) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query("UPDATE audit_log SET status = $2 WHERE id = $1")
+            .bind(entry_id)
+            .bind(status)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_recent_entries(pool: &PgPool, limit: i64) -> Result<Vec<AuditLogEntry>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, action, actor_id, subject_id, method, path, status, details, created_at
+            FROM audit_log
+            ORDER BY created_at DESC
+            LIMIT $1
+            "#
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        let entries = rows.into_iter().map(|row| AuditLogEntry {
+            id: row.get("id"),
+            action: row.get("action"),
+            actor_id: row.get("actor_id"),
+            subject_id: row.get("subject_id"),
+            method: row.get("method"),
+            path: row.get("path"),
+            status: row.get("status"),
+            details: row.get("details"),
+            created_at: row.get("created_at"),
+        }).collect();
+
+        Ok(entries)
+    }
+}
+
+pub struct FeatureFlagQueries;
+
+impl FeatureFlagQueries {
+    // Every flag with its team overrides
+    pub async fn list_flags(pool: &PgPool) -> Result<Vec<FeatureFlag>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            "SELECT name, enabled, description, updated_at FROM feature_flags ORDER BY name"
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let override_rows = sqlx::query(
+            "SELECT flag_name, team_id, enabled FROM team_feature_flags ORDER BY flag_name, team_id"
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut overrides: HashMap<String, Vec<TeamFlagOverride>> = HashMap::new();
+        for row in override_rows {
+            overrides.entry(row.get("flag_name")).or_default().push(TeamFlagOverride {
+                team_id: row.get("team_id"),
+                enabled: row.get("enabled"),
+            });
+        }
+
+        Ok(rows.into_iter().map(|row| {
+            let name: String = row.get("name");
+            FeatureFlag {
+                overrides: overrides.remove(&name).unwrap_or_default(),
+                name,
+                enabled: row.get("enabled"),
+                description: row.get("description"),
+                updated_at: row.get("updated_at"),
+            }
+        }).collect())
+    }
+
+    // Creates the flag or changes its default; a missing description keeps the current one
+    pub async fn set_flag(
+        pool: &PgPool,
+        name: &str,
+        request: &SetFeatureFlagRequest,
+        updated_by: Uuid,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query(
+            r#"
+            INSERT INTO feature_flags (name, enabled, description, updated_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (name) DO UPDATE
+            SET enabled = EXCLUDED.enabled,
+                description = COALESCE(EXCLUDED.description, feature_flags.description),
+                updated_by = EXCLUDED.updated_by
+            "#
+        )
+        .bind(name)
+        .bind(request.enabled)
+        .bind(&request.description)
+        .bind(updated_by)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_team_override(
+        pool: &PgPool,
+        name: &str,
+        team_id: Uuid,
+        enabled: bool,
+        updated_by: Uuid,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query(
+            r#"
+            INSERT INTO team_feature_flags (flag_name, team_id, enabled, updated_by)
+            SELECT name, $2, $3, $4 FROM feature_flags WHERE name = $1
+            ON CONFLICT (flag_name, team_id) DO UPDATE
+            SET enabled = EXCLUDED.enabled,
+                updated_by = EXCLUDED.updated_by
+            "#
+        )
+        .bind(name)
+        .bind(team_id)
+        .bind(enabled)
+        .bind(updated_by)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Feature flag not found".to_string()));
+        }
+        Ok(())
+    }
+
+    // The team falls back to the flag's default
+    pub async fn remove_team_override(pool: &PgPool, name: &str, team_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query("DELETE FROM team_feature_flags WHERE flag_name = $1 AND team_id = $2")
+            .bind(name)
+            .bind(team_id)
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("No override for this team".to_string()));
+        }
+        Ok(())
+    }
+}
+
+// Tasks created by user $1 in [$2, $3), quarantined or not as $4 says
+const MODERATED_TASKS_SQL: &str = "t.created_by = $1 \
+    AND ($2::timestamptz IS NULL OR t.created_at >= $2) \
+    AND ($3::timestamptz IS NULL OR t.created_at < $3) \
+    AND (t.quarantined_at IS NOT NULL) = $4";
+
+// Comments written by user $1 in [$2, $3), quarantined or not as $4 says
+const MODERATED_COMMENTS_SQL: &str = "c.user_id = $1 \
+    AND ($2::timestamptz IS NULL OR c.created_at >= $2) \
+    AND ($3::timestamptz IS NULL OR c.created_at < $3) \
+    AND (c.quarantined_at IS NOT NULL) = $4";
+
+fn moderated_content(tasks: Vec<PgRow>, comments: Vec<PgRow>) -> UserContent {
+    UserContent {
+        tasks: tasks
+            .into_iter()
+            .map(|row| ModeratedTask { id: row.get("id"), project_id: row.get("project_id") })
+            .collect(),
+        comments: comments
+            .into_iter()
+            .map(|row| ModeratedComment { id: row.get("id"), task_id: row.get("task_id"), project_id: row.get("project_id") })
+            .collect(),
+    }
+}
+
+pub struct ModerationQueries;
+
+impl ModerationQueries {
+    // The user's content in the range that is (or isn't yet) quarantined
+    pub async fn find_content(
+        pool: &PgPool,
+        user_id: Uuid,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        quarantined: bool,
+    ) -> Result<UserContent, AppError> {
+        let _timer = query_timer!();
+        let tasks = sqlx::query(&format!(
+            "SELECT t.id, t.project_id FROM tasks t WHERE {tasks} ORDER BY t.created_at",
+            tasks = MODERATED_TASKS_SQL,
+        ))
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .bind(quarantined)
+        .fetch_all(pool)
+        .await?;
+
+        let comments = sqlx::query(&format!(
+            "SELECT c.id, c.task_id, t.project_id FROM task_comments c JOIN tasks t ON t.id = c.task_id \
+             WHERE {comments} ORDER BY c.created_at",
+            comments = MODERATED_COMMENTS_SQL,
+        ))
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .bind(quarantined)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(moderated_content(tasks, comments))
+    }
+
+    // Quarantines the user's content in the range, or restores it when
+    // `quarantined` is false, returning what changed
+    pub async fn set_quarantined(
+        pool: &PgPool,
+        user_id: Uuid,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        quarantined: bool,
+    ) -> Result<UserContent, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        let tasks = sqlx::query(&format!(
+            r#"
+            UPDATE tasks t
+            SET quarantined_at = CASE WHEN $5 THEN NOW() END
+            WHERE {tasks}
+            RETURNING t.id, t.project_id
+            "#,
+            tasks = MODERATED_TASKS_SQL,
+        ))
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .bind(!quarantined)
+        .bind(quarantined)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let comments = sqlx::query(&format!(
+            r#"
+            UPDATE task_comments c
+            SET quarantined_at = CASE WHEN $5 THEN NOW() END
+            FROM tasks t
+            WHERE t.id = c.task_id AND {comments}
+            RETURNING c.id, c.task_id, t.project_id
+            "#,
+            comments = MODERATED_COMMENTS_SQL,
+        ))
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .bind(!quarantined)
+        .bind(quarantined)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(moderated_content(tasks, comments))
+    }
+
+    // Deletes the user's quarantined content in the range for good. Comments
+    // by others on the deleted tasks go with them.
+    pub async fn purge_quarantined(
+        pool: &PgPool,
+        user_id: Uuid,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<UserContent, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        let comments = sqlx::query(&format!(
+            r#"
+            DELETE FROM task_comments c
+            USING tasks t
+            WHERE t.id = c.task_id AND {comments}
+            RETURNING c.id, c.task_id, t.project_id
+            "#,
+            comments = MODERATED_COMMENTS_SQL,
+        ))
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .bind(true)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let tasks = sqlx::query(&format!(
+            "DELETE FROM tasks t WHERE {tasks} RETURNING t.id, t.project_id",
+            tasks = MODERATED_TASKS_SQL,
+        ))
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .bind(true)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(moderated_content(tasks, comments))
+    }
+}
+
+// Per-user summaries for the home screen. Each is one indexed query so the
+// dashboard can run them side by side.
+pub struct DashboardQueries;
+
+impl DashboardQueries {
+    pub async fn get_user_teams_with_project_counts(pool: &PgPool, user_id: Uuid) -> Result<Vec<TeamProjectCount>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT t.id, t.name, t.description, t.created_by, t.created_at, t.updated_at,
+                   COALESCE(c.project_count, 0) AS project_count
+            FROM teams t
+            INNER JOIN team_members tm ON t.id = tm.team_id
+            LEFT JOIN team_counters c ON c.team_id = t.id
+            WHERE tm.user_id = $1
+            ORDER BY t.name
+            "#
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| TeamProjectCount {
+                team: Team {
+                    id: row.get("id"),
+                    name: row.get("name"),
+                    description: row.get("description"),
+                    created_by: row.get("created_by"),
+                    created_at: row.get("created_at"),
+                    updated_at: row.get("updated_at"),
+                },
+                project_count: row.get("project_count"),
+            })
+            .collect())
+    }
+
+    pub async fn get_instance_stats(pool: &PgPool) -> Result<InstanceStats, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM users) AS users,
+                (SELECT COUNT(*) FROM users WHERE is_active = true) AS active_users,
+                (SELECT COUNT(*) FROM instance_admins) AS instance_admins,
+                (SELECT COUNT(*) FROM teams) AS teams,
+                (SELECT COUNT(*) FROM projects WHERE deleted_at IS NULL) AS projects,
+                (SELECT COUNT(*) FROM projects WHERE deleted_at IS NULL AND is_active = false) AS archived_projects,
+                (SELECT COUNT(*) FROM tasks t JOIN projects p ON p.id = t.project_id WHERE p.deleted_at IS NULL) AS tasks,
+                (SELECT COUNT(*) FROM tasks t JOIN projects p ON p.id = t.project_id
+                 WHERE p.deleted_at IS NULL AND t.status <> 'done') AS open_tasks
+            "#
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(InstanceStats {
+            users: row.get("users"),
+            active_users: row.get("active_users"),
+            instance_admins: row.get("instance_admins"),
+            teams: row.get("teams"),
+            projects: row.get("projects"),
+            archived_projects: row.get("archived_projects"),
+            tasks: row.get("tasks"),
+            open_tasks: row.get("open_tasks"),
+        })
+    }
+
+    // Open tasks assigned to the user that fall due after `from` and no later
+    // than `until`, grouped by their day in the user's timezone
+    pub async fn get_tasks_due_by_day(
+        pool: &PgPool,
+        user_id: Uuid,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<DueDay>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT t.id, t.project_id, p.name AS project_name, t.number, t.title, t.status, t.due_date,
+                   CASE WHEN t.is_all_day THEN (t.due_date AT TIME ZONE 'UTC')::date
+                        ELSE (t.due_date AT TIME ZONE COALESCE(tz.timezone, 'UTC'))::date END AS due_day
+            FROM tasks t
+            JOIN projects p ON p.id = t.project_id
+            LEFT JOIN digest_preferences tz ON tz.user_id = t.assigned_to
+            WHERE t.assigned_to = $1 AND t.status <> 'done' AND t.due_date IS NOT NULL
+              AND {due_at} > $2 AND {due_at} <= $3
+              AND p.is_active = true AND p.deleted_at IS NULL AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+            ORDER BY {due_at} ASC, t.id
+            LIMIT 100
+            "#,
+            due_at = DUE_AT_SQL,
+        ))
+        .bind(user_id)
+        .bind(from)
+        .bind(until)
+        .fetch_all(pool)
+        .await?;
+
+        let mut days: Vec<DueDay> = Vec::new();
+        for row in rows {
+            let day: NaiveDate = row.get("due_day");
+            let task = digest_task_from_row(row);
+            match days.last_mut() {
+                Some(last) if last.day == day => last.tasks.push(task),
+                _ => days.push(DueDay { day, tasks: vec![task] }),
+            }
+        }
+        Ok(days)
+    }
+
+    // Open tasks assigned to the user that are past due at `now`
+    pub async fn count_overdue_tasks(pool: &PgPool, user_id: Uuid, now: DateTime<Utc>) -> Result<i64, AppError> {
+        let _timer = query_timer!();
+        let count = sqlx::query_scalar(&format!(
+            r#"
+            SELECT COUNT(*)
+            FROM tasks t
+            JOIN projects p ON p.id = t.project_id
+            LEFT JOIN digest_preferences tz ON tz.user_id = t.assigned_to
+            WHERE t.assigned_to = $1 AND t.status <> 'done' AND t.due_date IS NOT NULL AND {due_at} <= $2
+              AND p.is_active = true AND p.deleted_at IS NULL AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+            "#,
+            due_at = DUE_AT_SQL,
+        ))
+        .bind(user_id)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    pub async fn count_unread_notifications(pool: &PgPool, user_id: Uuid) -> Result<i64, AppError> {
+        let _timer = query_timer!();
+        let count = sqlx::query_scalar("SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND read_at IS NULL")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(count)
+    }
+}
+
+// What user $2 did in the projects they share with viewer $1. The intersect
+// keeps everything else out of the query itself rather than filtering later.
+const USER_ACTIVITY_SQL: &str = r#"
+    WITH shared AS (
+        SELECT project_id FROM project_members WHERE user_id = $1
+        INTERSECT
+        SELECT project_id FROM project_members WHERE user_id = $2
+    ),
+    activity AS (
+        SELECT t.id, 'task_created' AS kind, t.created_at AS at, t.id AS task_id, NULL::uuid AS comment_id,
+               NULL::task_status AS from_status, NULL::task_status AS to_status
+        FROM tasks t
+        WHERE t.created_by = $2 AND t.project_id IN (SELECT project_id FROM shared)
+        UNION ALL
+        SELECT tr.id, CASE WHEN tr.to_status = 'done' THEN 'task_completed' ELSE 'status_changed' END,
+               tr.changed_at, tr.task_id, NULL, tr.from_status, tr.to_status
+        FROM task_status_transitions tr
+        JOIN tasks t ON t.id = tr.task_id
+        WHERE tr.changed_by = $2 AND t.project_id IN (SELECT project_id FROM shared)
+        UNION ALL
+        SELECT c.id, 'commented', c.created_at, c.task_id, c.id, NULL, NULL
+        FROM task_comments c
+        JOIN tasks t ON t.id = c.task_id
+        WHERE c.user_id = $2 AND NOT c.is_system AND c.quarantined_at IS NULL AND c.expired_at IS NULL
+          AND t.project_id IN (SELECT project_id FROM shared)
+    )
+    SELECT {columns}
+    FROM activity a
+    JOIN tasks t ON t.id = a.task_id AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+    JOIN projects p ON p.id = t.project_id AND p.deleted_at IS NULL
+"#;
+
+fn user_activity_from_row(row: PgRow) -> UserActivity {
+    let kind = match row.get::<&str, _>("kind") {
+        "task_created" => UserActivityKind::TaskCreated,
+        "task_completed" => UserActivityKind::TaskCompleted,
+        "status_changed" => UserActivityKind::StatusChanged,
+        _ => UserActivityKind::Commented,
+    };
+
+    UserActivity {
+        kind,
+        at: row.get("at"),
+        project_id: row.get("project_id"),
+        project_name: row.get("project_name"),
+        task: RelatedTask {
+            id: row.get("task_id"),
+            project_id: row.get("project_id"),
+            number: row.get("number"),
+            title: row.get("title"),
+            status: row.get("status"),
+        },
+        comment_id: row.get("comment_id"),
+        from_status: row.get("from_status"),
+        to_status: row.get("to_status"),
+    }
+}
+
+pub struct UserActivityQueries;
+
+impl UserActivityQueries {
+    // Newest first, with the total for paging
+    pub async fn get_activity(
+        pool: &PgPool,
+        viewer_id: Uuid,
+        user_id: Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<UserActivity>, i64), AppError> {
+        let _timer = query_timer!();
+        let total: i64 = sqlx::query_scalar(&USER_ACTIVITY_SQL.replace("{columns}", "COUNT(*)"))
+            .bind(viewer_id)
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+
+        let rows = sqlx::query(&format!(
+            "{} ORDER BY a.at DESC, a.id DESC LIMIT $3 OFFSET $4",
+            USER_ACTIVITY_SQL.replace(
+                "{columns}",
+                "a.kind, a.at, a.task_id, a.comment_id, a.from_status, a.to_status, \
+                 t.project_id, t.number, t.title, t.status, p.name AS project_name",
+            ),
+        ))
+        .bind(viewer_id)
+        .bind(user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        Ok((rows.into_iter().map(user_activity_from_row).collect(), total))
+    }
+}
+
+// Candidates for the quick switcher, each kind matched on its name: ILIKE for
+// substrings, which the trigram indexes serve from three characters on, and
+// word similarity (<%) for typos. An empty query matches everything, so the
+// most recently opened places come back.
+const QUICK_SWITCH_SQL: &str = r#"
+    WITH my_projects AS (
+        SELECT p.id, p.name, p.color, p.icon, p.team_id
+        FROM projects p
+        JOIN project_members pm ON pm.project_id = p.id AND pm.user_id = $1
+        WHERE p.deleted_at IS NULL AND p.archived_at IS NULL
+    )
+    (
+        SELECT 'team' AS kind, t.id, t.name, NULL::text AS color, NULL::text AS icon,
+               NULL::uuid AS context_id, NULL::text AS context_name,
+               word_similarity($2, t.name) AS similarity,
+               (
+                   SELECT MAX(pa.last_accessed_at) FROM project_access pa
+                   JOIN projects p ON p.id = pa.project_id
+                   WHERE pa.user_id = $1 AND p.team_id = t.id
+               ) AS last_accessed_at
+        FROM teams t
+        JOIN team_members tm ON tm.team_id = t.id AND tm.user_id = $1
+        WHERE $2 = '' OR t.name ILIKE '%' || $3 || '%' ESCAPE '\' OR $2 <% t.name
+        ORDER BY last_accessed_at DESC NULLS LAST, similarity DESC
+        LIMIT $5
+    )
+    UNION ALL
+    (
+        SELECT 'project', p.id, p.name, p.color, p.icon, t.id, t.name,
+               word_similarity($2, p.name) AS similarity, pa.last_accessed_at
+        FROM my_projects p
+        JOIN teams t ON t.id = p.team_id
+        LEFT JOIN project_access pa ON pa.project_id = p.id AND pa.user_id = $1
+        WHERE $2 = '' OR p.name ILIKE '%' || $3 || '%' ESCAPE '\' OR $2 <% p.name
+        ORDER BY last_accessed_at DESC NULLS LAST, similarity DESC
+        LIMIT $5
+    )
+    UNION ALL
+    (
+        SELECT 'task', t.id, t.title, t.cover_color, t.cover_emoji, p.id, p.name,
+               word_similarity($2, t.title), tr.last_read_at
+        FROM task_reads tr
+        JOIN tasks t ON t.id = tr.task_id
+        JOIN my_projects p ON p.id = t.project_id
+        WHERE tr.user_id = $1 AND tr.last_read_at >= $4 AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+          AND ($2 = '' OR t.title ILIKE '%' || $3 || '%' ESCAPE '\' OR $2 <% t.title)
+        ORDER BY tr.last_read_at DESC
+        LIMIT $5
+    )
+"#;
+
+pub struct QuickSwitchQueries;
+
+impl QuickSwitchQueries {
+    // At most `limit` of each kind; tasks only when opened since `tasks_since`
+    pub async fn get_candidates(
+        pool: &PgPool,
+        user_id: Uuid,
+        query: &str,
+        tasks_since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<QuickSwitchCandidate>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(QUICK_SWITCH_SQL)
+            .bind(user_id)
+            .bind(query)
+            .bind(crate::utils::search::escape_like(query))
+            .bind(tasks_since)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let kind = match row.get::<&str, _>("kind") {
+                    "team" => QuickSwitchKind::Team,
+                    "project" => QuickSwitchKind::Project,
+                    _ => QuickSwitchKind::Task,
+                };
+                let context = match (row.get::<Option<Uuid>, _>("context_id"), row.get::<Option<String>, _>("context_name")) {
+                    (Some(id), Some(name)) => Some(QuickSwitchContext { id, name }),
+                    _ => None,
+                };
+                QuickSwitchCandidate {
+                    item: QuickSwitchItem {
+                        kind,
+                        id: row.get("id"),
+                        name: row.get("name"),
+                        color: row.get("color"),
+                        icon: row.get("icon"),
+                        context,
+                    },
+                    similarity: row.get("similarity"),
+                    last_accessed_at: row.get("last_accessed_at"),
+                }
+            })
+            .collect())
+    }
+
+    // Called whenever the user opens the project; writes at most once a minute
+    pub async fn record_project_access(pool: &PgPool, user_id: Uuid, project_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query(
+            r#"
+            INSERT INTO project_access (user_id, project_id, last_accessed_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (user_id, project_id) DO UPDATE SET last_accessed_at = NOW()
+            WHERE project_access.last_accessed_at < NOW() - INTERVAL '1 minute'
+            "#
+        )
+        .bind(user_id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+// Soft-deleted projects and boards. Trashed rows are excluded everywhere else;
+// `retention_days` only computes when the sweep will purge them.
+pub struct TrashQueries;
+
+fn project_from_row(row: &PgRow) -> Project {
+    Project {
+        id: row.get("id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        team_id: row.get("team_id"),
+        created_by: row.get("created_by"),
+        color: row.get("color"),
+        contrast_text: row.get("contrast_text"),
+        icon: row.get("icon"),
+        is_active: row.get("is_active"),
+        archived_at: row.get("archived_at"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn board_from_row(row: &PgRow) -> Board {
+    Board {
+        id: row.get("id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        project_id: row.get("project_id"),
+        created_by: row.get("created_by"),
+        columns: serde_json::from_value(row.get("columns")).unwrap_or(vec![]),
+        config: serde_json::from_value(row.get("config")).unwrap_or_default(),
+        swimlane_config: serde_json::from_value(row.get("swimlane_config")).unwrap_or_default(),
+        is_default: row.get("is_default"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn trashed_from_row<T>(row: PgRow, item: fn(&PgRow) -> T) -> Trashed<T> {
+    Trashed {
+        item: item(&row),
+        deleted_at: row.get("deleted_at"),
+        purge_at: row.get("purge_at"),
+    }
+}
+
+impl TrashQueries {
+    pub async fn get_trashed_projects(
+        pool: &PgPool,
+        team_id: Uuid,
+        retention_days: i32,
+    ) -> Result<Vec<Trashed<Project>>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, name, description, team_id, created_by, color, contrast_text, icon, is_active, archived_at, created_at, updated_at,
+                   deleted_at, deleted_at + make_interval(days => $2) AS purge_at
+            FROM projects
+            WHERE team_id = $1 AND deleted_at IS NOT NULL
+            ORDER BY deleted_at DESC
+            "#
+        )
+        .bind(team_id)
+        .bind(retention_days)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| trashed_from_row(row, project_from_row)).collect())
+    }
+
+    pub async fn get_trashed_boards(
+        pool: &PgPool,
+        project_id: Uuid,
+        retention_days: i32,
+    ) -> Result<Vec<Trashed<Board>>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, name, description, project_id, created_by, columns, config, swimlane_config, is_default, created_at, updated_at,
+                   deleted_at, deleted_at + make_interval(days => $2) AS purge_at
+            FROM boards
+            WHERE project_id = $1 AND deleted_at IS NOT NULL
+            ORDER BY deleted_at DESC
+            "#
+        )
+        .bind(project_id)
+        .bind(retention_days)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| trashed_from_row(row, board_from_row)).collect())
+    }
+
+    // Restores the project together with the boards trashed in the same batch
+    pub async fn restore_project(pool: &PgPool, team_id: Uuid, project_id: Uuid) -> Result<Project, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        let batch_id: Option<Uuid> = sqlx::query_scalar(
+            "SELECT delete_batch_id FROM projects WHERE id = $1 AND team_id = $2 AND deleted_at IS NOT NULL FOR UPDATE"
+        )
+        .bind(project_id)
+        .bind(team_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found in trash".to_string()))?;
+
+        let row = sqlx::query(
+            r#"
+            UPDATE projects
+            SET deleted_at = NULL, delete_batch_id = NULL, updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, name, description, team_id, created_by, color, contrast_text, icon, is_active, archived_at, created_at, updated_at
+            "#
+        )
+        .bind(project_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE boards SET deleted_at = NULL, delete_batch_id = NULL WHERE project_id = $1 AND delete_batch_id = $2"
+        )
+        .bind(project_id)
+        .bind(batch_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(project_from_row(&row))
+    }
+
+    pub async fn restore_board(pool: &PgPool, project_id: Uuid, board_id: Uuid) -> Result<Board, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            UPDATE boards
+            SET deleted_at = NULL, delete_batch_id = NULL
+            WHERE id = $1 AND project_id = $2 AND deleted_at IS NOT NULL
+            RETURNING id, name, description, project_id, created_by, columns, config, swimlane_config, is_default, created_at, updated_at
+            "#
+        )
+        .bind(board_id)
+        .bind(project_id)
+        .fetch_optional(pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(board_from_row(&row)),
+            None => Err(AppError::NotFound("Board not found in trash".to_string())),
+        }
+    }
+
+    // Deletes what has been in the trash since before `cutoff`, returning the
+    // number of projects and boards removed. Tasks go with their project.
+    pub async fn purge_trash(pool: &PgPool, cutoff: DateTime<Utc>) -> Result<(u64, u64), AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        let projects = sqlx::query("DELETE FROM projects WHERE deleted_at < $1")
+            .bind(cutoff)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        let boards = sqlx::query("DELETE FROM boards WHERE deleted_at < $1")
+            .bind(cutoff)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+        Ok((projects, boards))
+    }
+}
+
+pub struct ShareTokenQueries;
+
+fn share_token_from_row(row: PgRow) -> ShareToken {
+    ShareToken {
+        id: row.get("id"),
+        project_id: row.get("project_id"),
+        token: row.get("token"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+    }
+}
+
+impl ShareTokenQueries {
+    pub async fn create_token(pool: &PgPool, project_id: Uuid, token: &str, created_by: Uuid) -> Result<ShareToken, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            INSERT INTO project_share_tokens (project_id, token, created_by)
+            VALUES ($1, $2, $3)
+            RETURNING id, project_id, token, created_by, created_at
+            "#
+        )
+        .bind(project_id)
+        .bind(token)
+        .bind(created_by)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(share_token_from_row(row))
+    }
+
+    pub async fn get_project_tokens(pool: &PgPool, project_id: Uuid) -> Result<Vec<ShareToken>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, project_id, token, created_by, created_at
+            FROM project_share_tokens
+            WHERE project_id = $1
+            ORDER BY created_at ASC
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(share_token_from_row).collect())
+    }
+
+    // Revokes the token, returning it so cached badges can be dropped
+    pub async fn delete_token(pool: &PgPool, project_id: Uuid, token_id: Uuid) -> Result<ShareToken, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            DELETE FROM project_share_tokens
+            WHERE id = $1 AND project_id = $2
+            RETURNING id, project_id, token, created_by, created_at
+            "#
+        )
+        .bind(token_id)
+        .bind(project_id)
+        .fetch_optional(pool)
+        .await?;
+
+        row.map(share_token_from_row)
+            .ok_or_else(|| AppError::NotFound("Share token not found".to_string()))
+    }
+
+    // None for unknown or revoked tokens and for trashed projects
+    pub async fn get_badge_stats(pool: &PgPool, token: &str) -> Result<Option<BadgeStats>, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            SELECT p.name AS project_name,
+                   COUNT(t.id) FILTER (WHERE t.status <> 'done') AS open_tasks,
+                   COUNT(t.id) FILTER (WHERE t.status = 'done') AS done_tasks
+            FROM project_share_tokens s
+            JOIN projects p ON p.id = s.project_id AND p.deleted_at IS NULL
+            LEFT JOIN tasks t ON t.project_id = p.id AND t.quarantined_at IS NULL AND t.expired_at IS NULL
+            WHERE s.token = $1
+            GROUP BY p.id, p.name
+            "#
+        )
+        .bind(token)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|row| BadgeStats {
+            project_name: row.get("project_name"),
+            open_tasks: row.get("open_tasks"),
+            done_tasks: row.get("done_tasks"),
+        }))
+    }
+}
+
+pub struct ApiTokenQueries;
+
+const API_TOKEN_COLUMNS_SQL: &str = "id, name, scopes, project_ids, last_used_at, created_at";
+
+fn api_token_from_row(row: &PgRow) -> ApiToken {
+    let scopes: Vec<String> = row.get("scopes");
+    ApiToken {
+        id: row.get("id"),
+        name: row.get("name"),
+        scopes: scopes.iter().filter_map(|scope| Scope::parse(scope)).collect(),
+        project_ids: row.get("project_ids"),
+        last_used_at: row.get("last_used_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+impl ApiTokenQueries {
+    pub async fn create_token(
+        pool: &PgPool,
+        user_id: Uuid,
+        name: &str,
+        token_hash: &str,
+        scopes: &[Scope],
+        project_ids: Option<&[Uuid]>,
+    ) -> Result<ApiToken, AppError> {
+        let _timer = query_timer!();
+        let scopes: Vec<&str> = scopes.iter().map(Scope::as_str).collect();
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO api_tokens (user_id, name, token_hash, scopes, project_ids)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING {API_TOKEN_COLUMNS_SQL}
+            "#
+        ))
+        .bind(user_id)
+        .bind(name)
+        .bind(token_hash)
+        .bind(&scopes)
+        .bind(project_ids)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(api_token_from_row(&row))
+    }
+
+    pub async fn get_user_tokens(pool: &PgPool, user_id: Uuid) -> Result<Vec<ApiToken>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            "SELECT {API_TOKEN_COLUMNS_SQL} FROM api_tokens WHERE user_id = $1 ORDER BY created_at ASC"
+        ))
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.iter().map(api_token_from_row).collect())
+    }
+
+    pub async fn delete_token(pool: &PgPool, user_id: Uuid, token_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query("DELETE FROM api_tokens WHERE id = $1 AND user_id = $2")
+            .bind(token_id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Token not found".to_string()));
+        }
+        Ok(())
+    }
+
+    // The token, the username of its (active) user and the user's token rate
+    // limit override. Records the use at most once a minute, so busy widgets
+    // don't write on every request.
+    pub async fn authenticate(pool: &PgPool, token_hash: &str) -> Result<Option<(Uuid, String, ApiToken, Option<i32>)>, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            WITH token AS (
+                SELECT a.id, a.name, a.scopes, a.project_ids, a.last_used_at, a.created_at, a.user_id, u.username, u.api_token_rate_limit
+                FROM api_tokens a
+                JOIN users u ON u.id = a.user_id AND u.is_active = true
+                WHERE a.token_hash = $1
+            ), touched AS (
+                UPDATE api_tokens SET last_used_at = NOW()
+                WHERE id = (SELECT id FROM token)
+                  AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+            )
+            SELECT * FROM token
+            "#
+        )
+        .bind(token_hash)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|row| (row.get("user_id"), row.get("username"), api_token_from_row(&row), row.get("api_token_rate_limit"))))
+    }
+
+    // The admin override of the user's hourly quota per token; None for the default
+    pub async fn get_rate_limit(pool: &PgPool, user_id: Uuid) -> Result<Option<i32>, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT api_token_rate_limit FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        Ok(row.get("api_token_rate_limit"))
+    }
+
+    pub async fn set_rate_limit(pool: &PgPool, user_id: Uuid, requests_per_hour: Option<i32>) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query("UPDATE users SET api_token_rate_limit = $2 WHERE id = $1")
+            .bind(user_id)
+            .bind(requests_per_hour)
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("User not found".to_string()));
+        }
+        Ok(())
+    }
+}
+
+pub struct ProjectInvitationQueries;
+
+const INVITATION_COLUMNS_SQL: &str = "id, project_id, email, role, invited_by, expires_at, created_at";
+
+fn invitation_from_row(row: &PgRow) -> ProjectInvitation {
+    ProjectInvitation {
+        id: row.get("id"),
+        project_id: row.get("project_id"),
+        email: row.get("email"),
+        role: row.get("role"),
+        invited_by: row.get("invited_by"),
+        expires_at: row.get("expires_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+impl ProjectInvitationQueries {
+    // Replaces any pending invitation of the same email to the project
+    pub async fn create_invitation(
+        pool: &PgPool,
+        project_id: Uuid,
+        email: &str,
+        role: ProjectRole,
+        token_hash: &str,
+        invited_by: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<ProjectInvitation, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            "DELETE FROM project_invitations WHERE project_id = $1 AND LOWER(email) = LOWER($2) AND accepted_at IS NULL"
+        )
+        .bind(project_id)
+        .bind(email)
+        .execute(&mut *tx)
+        .await?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO project_invitations (project_id, email, role, token_hash, invited_by, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING {INVITATION_COLUMNS_SQL}
+            "#
+        ))
+        .bind(project_id)
+        .bind(email)
+        .bind(role)
+        .bind(token_hash)
+        .bind(invited_by)
+        .bind(expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(invitation_from_row(&row))
+    }
+
+    // Invitations that can still be accepted, newest first
+    pub async fn get_pending_invitations(pool: &PgPool, project_id: Uuid) -> Result<Vec<ProjectInvitation>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {INVITATION_COLUMNS_SQL} FROM project_invitations
+            WHERE project_id = $1 AND accepted_at IS NULL AND expires_at > NOW()
+            ORDER BY created_at DESC
+            "#
+        ))
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.iter().map(invitation_from_row).collect())
+    }
+
+    pub async fn delete_invitation(pool: &PgPool, project_id: Uuid, invitation_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query(
+            "DELETE FROM project_invitations WHERE id = $1 AND project_id = $2 AND accepted_at IS NULL"
+        )
+        .bind(invitation_id)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Invitation not found".to_string()));
+        }
+        Ok(())
+    }
+
+    // The invitation if it can still be accepted
+    pub async fn get_valid_invitation(pool: &PgPool, token_hash: &str) -> Result<Option<ProjectInvitation>, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT {INVITATION_COLUMNS_SQL} FROM project_invitations
+            WHERE token_hash = $1 AND accepted_at IS NULL AND expires_at > NOW()
+            "#
+        ))
+        .bind(token_hash)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.as_ref().map(invitation_from_row))
+    }
+
+    // Adds the user to the invitation's project and uses the invitation up.
+    // Existing members keep their role and the invitation, which the project
+    // admins can then revoke.
+    pub async fn accept_invitation(
+        pool: &PgPool,
+        token_hash: &str,
+        user_id: Uuid,
+    ) -> Result<(ProjectInvitation, ProjectMember), AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT {INVITATION_COLUMNS_SQL} FROM project_invitations
+            WHERE token_hash = $1 AND accepted_at IS NULL AND expires_at > NOW()
+            FOR UPDATE
+            "#
+        ))
+        .bind(token_hash)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::Validation("Invitation is invalid, expired or already used".to_string()))?;
+        let invitation = invitation_from_row(&row);
+
+        let is_member: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM project_members WHERE project_id = $1 AND user_id = $2)"
+        )
+        .bind(invitation.project_id)
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if is_member {
+            return Err(AppError::Conflict("User is already a project member".to_string()));
+        }
+
+        let member = insert_project_member(&mut tx, invitation.project_id, user_id, invitation.role.clone()).await?;
+        sqlx::query("UPDATE project_invitations SET accepted_by = $2, accepted_at = NOW() WHERE id = $1")
+            .bind(invitation.id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok((invitation, member))
+    }
+}
+
+pub struct AnnouncementQueries;
+
+const ANNOUNCEMENT_COLUMNS_SQL: &str =
+    "id, message, severity, starts_at, ends_at, dismissible, created_by, created_at, updated_at";
+
+fn announcement_from_row(row: PgRow) -> Announcement {
+    Announcement {
+        id: row.get("id"),
+        message: row.get("message"),
+        severity: row.get("severity"),
+        starts_at: row.get("starts_at"),
+        ends_at: row.get("ends_at"),
+        dismissible: row.get("dismissible"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+impl AnnouncementQueries {
+    // Every announcement, including scheduled and expired ones, latest start first
+    pub async fn list_announcements(pool: &PgPool) -> Result<Vec<Announcement>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM announcements ORDER BY starts_at DESC, created_at DESC",
+            ANNOUNCEMENT_COLUMNS_SQL
+        ))
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(announcement_from_row).collect())
+    }
+
+    pub async fn get_announcement(pool: &PgPool, announcement_id: Uuid) -> Result<Announcement, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!("SELECT {} FROM announcements WHERE id = $1", ANNOUNCEMENT_COLUMNS_SQL))
+            .bind(announcement_id)
+            .fetch_optional(pool)
+            .await?;
+
+        row.map(announcement_from_row)
+            .ok_or_else(|| AppError::NotFound("Announcement not found".to_string()))
+    }
+
+    pub async fn create_announcement(
+        pool: &PgPool,
+        message: &str,
+        severity: AnnouncementSeverity,
+        starts_at: DateTime<Utc>,
+        ends_at: Option<DateTime<Utc>>,
+        dismissible: bool,
+        created_by: Uuid,
+    ) -> Result<Announcement, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO announcements (message, severity, starts_at, ends_at, dismissible, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING {}
+            "#,
+            ANNOUNCEMENT_COLUMNS_SQL
+        ))
+        .bind(message)
+        .bind(severity)
+        .bind(starts_at)
+        .bind(ends_at)
+        .bind(dismissible)
+        .bind(created_by)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(announcement_from_row(row))
+    }
+
+    // Writes back every editable field of `announcement`
+    pub async fn update_announcement(pool: &PgPool, announcement: &Announcement) -> Result<Announcement, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE announcements
+            SET message = $2, severity = $3, starts_at = $4, ends_at = $5, dismissible = $6
+            WHERE id = $1
+            RETURNING {}
+            "#,
+            ANNOUNCEMENT_COLUMNS_SQL
+        ))
+        .bind(announcement.id)
+        .bind(&announcement.message)
+        .bind(announcement.severity)
+        .bind(announcement.starts_at)
+        .bind(announcement.ends_at)
+        .bind(announcement.dismissible)
+        .fetch_optional(pool)
+        .await?;
+
+        row.map(announcement_from_row)
+            .ok_or_else(|| AppError::NotFound("Announcement not found".to_string()))
+    }
+
+    pub async fn delete_announcement(pool: &PgPool, announcement_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let result = sqlx::query("DELETE FROM announcements WHERE id = $1")
+            .bind(announcement_id)
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Announcement not found".to_string()));
+        }
+        Ok(())
+    }
+
+    // Announcements running right now that the user hasn't dismissed
+    pub async fn get_active_announcements(pool: &PgPool, user_id: Uuid) -> Result<Vec<Announcement>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {}
+            FROM announcements a
+            WHERE a.starts_at <= NOW()
+              AND (a.ends_at IS NULL OR a.ends_at > NOW())
+              AND NOT EXISTS (
+                  SELECT 1 FROM announcement_dismissals d
+                  WHERE d.announcement_id = a.id AND d.user_id = $1
+              )
+            ORDER BY a.starts_at DESC, a.created_at DESC
+            "#,
+            ANNOUNCEMENT_COLUMNS_SQL
+        ))
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(announcement_from_row).collect())
+    }
+
+    // Dismissing twice is fine
+    pub async fn dismiss_announcement(pool: &PgPool, user_id: Uuid, announcement_id: Uuid) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query(
+            r#"
+            INSERT INTO announcement_dismissals (user_id, announcement_id)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id, announcement_id) DO NOTHING
+            "#
+        )
+        .bind(user_id)
+        .bind(announcement_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub struct TeamSettingsQueries;
+
+impl TeamSettingsQueries {
+    // Teams that never saved settings add new members to no projects
+    pub async fn get_settings(pool: &PgPool, team_id: Uuid) -> Result<TeamSettings, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            SELECT s.auto_add_to_projects, s.default_project_role,
+                   COALESCE(ARRAY_AGG(a.project_id) FILTER (WHERE a.project_id IS NOT NULL), '{}') AS project_ids
+            FROM team_settings s
+            LEFT JOIN team_auto_add_projects a ON a.team_id = s.team_id
+            WHERE s.team_id = $1
+            GROUP BY s.team_id
+            "#
+        )
+        .bind(team_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(match row {
+            Some(row) => TeamSettings {
+                team_id,
+                auto_add_to_projects: row.get("auto_add_to_projects"),
+                project_ids: row.get("project_ids"),
+                default_project_role: row.get("default_project_role"),
+            },
+            None => TeamSettings {
+                team_id,
+                auto_add_to_projects: AutoAddPolicy::None,
+                project_ids: Vec::new(),
+                default_project_role: ProjectRole::Member,
+            },
+        })
+    }
+
+    // Replaces the team's settings. `project_ids` must be projects of the team
+    // that aren't in the trash; they're only kept for the `selected` policy.
+    pub async fn update_settings(
+        pool: &PgPool,
+        team_id: Uuid,
+        auto_add_to_projects: AutoAddPolicy,
+        project_ids: &[Uuid],
+        default_project_role: ProjectRole,
+        updated_by: Uuid,
+    ) -> Result<TeamSettings, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO team_settings (team_id, auto_add_to_projects, default_project_role, updated_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (team_id) DO UPDATE
+            SET auto_add_to_projects = EXCLUDED.auto_add_to_projects,
+                default_project_role = EXCLUDED.default_project_role,
+                updated_by = EXCLUDED.updated_by
+            "#
+        )
+        .bind(team_id)
+        .bind(auto_add_to_projects)
+        .bind(&default_project_role)
+        .bind(updated_by)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM team_auto_add_projects WHERE team_id = $1")
+            .bind(team_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut selected: Vec<Uuid> = Vec::new();
+        if auto_add_to_projects == AutoAddPolicy::Selected {
+            selected = sqlx::query_scalar(
+                r#"
+                INSERT INTO team_auto_add_projects (team_id, project_id)
+                SELECT $1, p.id
+                FROM projects p
+                WHERE p.team_id = $1 AND p.id = ANY($2) AND p.deleted_at IS NULL
+                RETURNING project_id
+                "#
+            )
+            .bind(team_id)
+            .bind(project_ids)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            if let Some(missing) = project_ids.iter().find(|id| !selected.contains(id)) {
+                return Err(AppError::Validation(format!("Project {} is not a project of this team", missing)));
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(TeamSettings {
+            team_id,
+            auto_add_to_projects,
+            project_ids: selected,
+            default_project_role,
+        })
+    }
+}
+
+pub struct RetentionQueries;
+
+// A table under retention. Rows are `x`, their task `t` and project `p`; the
+// policy covers the rows matching `covered` once `aged` is before the table's
+// cutoff, except those `kept`. `files` are the storage paths that go with a
+// row when it is deleted.
+struct RetentionTable {
+    name: &'static str,
+    task_id: &'static str,
+    aged: &'static str,
+    covered: &'static str,
+    kept: &'static str,
+    bytes: &'static str,
+    files: &'static str,
+}
+
+// Closed tasks count from when they were closed, and stay while a task
+// related to them is still open
+const RETENTION_TABLES: [RetentionTable; 3] = [
+    RetentionTable {
+        name: "task_comments",
+        task_id: "x.task_id",
+        aged: "x.created_at",
+        covered: "true",
+        kept: "x.pinned",
+        bytes: "0",
+        files: "ARRAY[]::TEXT[]",
+    },
+    RetentionTable {
+        name: "task_attachments",
+        task_id: "x.task_id",
+        aged: "x.created_at",
+        covered: "true",
+        kept: "false",
+        bytes: "x.size_bytes",
+        files: "ARRAY[x.storage_path]",
+    },
+    RetentionTable {
+        name: "tasks",
+        task_id: "x.id",
+        aged: "x.status_changed_at",
+        covered: "x.status = 'done'",
+        kept: r#"EXISTS (
+            SELECT 1 FROM task_relations r
+            JOIN tasks o ON o.id = CASE WHEN r.source_task_id = x.id THEN r.target_task_id ELSE r.source_task_id END
+            WHERE (r.source_task_id = x.id OR r.target_task_id = x.id) AND o.status <> 'done'
+        )"#,
+        bytes: "0",
+        files: "ARRAY(SELECT a.storage_path FROM task_attachments a WHERE a.task_id = x.id)",
+    },
+];
+
+// With the table's cutoff as $2. Expired rows a relaxed policy no longer
+// covers are brought back; the rest are deleted once expired at or before $3.
+const RETENTION_EXPIRE_SQL: &str = "x.expired_at IS NULL AND {covered} AND NOT {kept} AND {aged} < $2";
+const RETENTION_RESTORE_SQL: &str =
+    "x.expired_at IS NOT NULL AND (NOT {covered} OR {kept} OR $2::timestamptz IS NULL OR {aged} >= $2)";
+const RETENTION_DELETE_SQL: &str = "x.expired_at IS NOT NULL AND x.expired_at <= $3 AND {covered} AND NOT {kept} AND {aged} < $2";
+
+fn retention_condition(condition: &str, table: &RetentionTable) -> String {
+    condition
+        .replace("{covered}", table.covered)
+        .replace("{kept}", table.kept)
+        .replace("{aged}", table.aged)
+}
+
+fn add_retention_counts(counts: &mut RetentionCounts, table: &RetentionTable, rows: i64, bytes: i64) {
+    match table.name {
+        "task_comments" => counts.comments += rows,
+        "task_attachments" => {
+            counts.attachments += rows;
+            counts.attachment_bytes += bytes;
+        }
+        _ => counts.tasks += rows,
+    }
+}
+
+impl RetentionQueries {
+    pub async fn get_policy(pool: &PgPool, team_id: Uuid) -> Result<RetentionPolicy, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query("SELECT retention FROM teams WHERE id = $1")
+            .bind(team_id)
+            .fetch_optional(pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(serde_json::from_value(row.get("retention")).unwrap_or_default()),
+            None => Err(AppError::NotFound("Team not found".to_string())),
+        }
+    }
+
+    pub async fn update_policy(pool: &PgPool, team_id: Uuid, policy: &RetentionPolicy) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query("UPDATE teams SET retention = $2, updated_at = NOW() WHERE id = $1")
+            .bind(team_id)
+            .bind(serde_json::to_value(policy)?)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Teams with a policy, and teams whose cleared policy leaves expired
+    // content to bring back
+    pub async fn get_teams(pool: &PgPool) -> Result<Vec<(Uuid, RetentionPolicy)>, AppError> {
+        let _timer = query_timer!();
+        let expired = RETENTION_TABLES
+            .iter()
+            .map(|table| {
+                format!(
+                    "EXISTS (SELECT 1 FROM {table} x JOIN tasks t ON t.id = {task_id} JOIN projects p ON p.id = t.project_id \
+                     WHERE p.team_id = tm.id AND x.expired_at IS NOT NULL)",
+                    table = table.name,
+                    task_id = table.task_id,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        let rows = sqlx::query(&format!("SELECT tm.id, tm.retention FROM teams tm WHERE tm.retention <> '{{}}' OR {}", expired))
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("id"), serde_json::from_value(row.get("retention")).unwrap_or_default()))
+            .collect())
+    }
+
+    // What expire, restore and delete_expired would do right now, in that order
+    pub async fn preview(
+        pool: &PgPool,
+        team_id: Uuid,
+        cutoffs: [Option<DateTime<Utc>>; 3],
+        delete_before: DateTime<Utc>,
+    ) -> Result<(RetentionCounts, RetentionCounts, RetentionCounts), AppError> {
+        let _timer = query_timer!();
+        let (mut to_expire, mut to_restore, mut to_delete) = Default::default();
+        for (table, cutoff) in RETENTION_TABLES.iter().zip(cutoffs) {
+            let row = sqlx::query(&format!(
+                r#"
+                SELECT
+                    COUNT(*) FILTER (WHERE {expire}) AS expire_rows,
+                    COALESCE(SUM({bytes}) FILTER (WHERE {expire}), 0)::BIGINT AS expire_bytes,
+                    COUNT(*) FILTER (WHERE {restore}) AS restore_rows,
+                    COALESCE(SUM({bytes}) FILTER (WHERE {restore}), 0)::BIGINT AS restore_bytes,
+                    COUNT(*) FILTER (WHERE {delete}) AS delete_rows,
+                    COALESCE(SUM({bytes}) FILTER (WHERE {delete}), 0)::BIGINT AS delete_bytes
+                FROM {name} x
+                JOIN tasks t ON t.id = {task_id}
+                JOIN projects p ON p.id = t.project_id
+                WHERE p.team_id = $1
+                "#,
+                expire = retention_condition(RETENTION_EXPIRE_SQL, table),
+                restore = retention_condition(RETENTION_RESTORE_SQL, table),
+                delete = retention_condition(RETENTION_DELETE_SQL, table),
+                bytes = table.bytes,
+                name = table.name,
+                task_id = table.task_id,
+            ))
+            .bind(team_id)
+            .bind(cutoff)
+            .bind(delete_before)
+            .fetch_one(pool)
+            .await?;
+
+            add_retention_counts(&mut to_expire, table, row.get("expire_rows"), row.get("expire_bytes"));
+            add_retention_counts(&mut to_restore, table, row.get("restore_rows"), row.get("restore_bytes"));
+            add_retention_counts(&mut to_delete, table, row.get("delete_rows"), row.get("delete_bytes"));
+        }
+
+        Ok((to_expire, to_restore, to_delete))
+    }
+
+    // Brings back expired content the team's current policy keeps
+    pub async fn restore(
+        pool: &PgPool,
+        team_id: Uuid,
+        cutoffs: [Option<DateTime<Utc>>; 3],
+    ) -> Result<RetentionCounts, AppError> {
+        let _timer = query_timer!();
+        let mut counts = RetentionCounts::default();
+        for (table, cutoff) in RETENTION_TABLES.iter().zip(cutoffs) {
+            let rows: Vec<i64> = sqlx::query_scalar(&format!(
+                r#"
+                UPDATE {name} x
+                SET expired_at = NULL
+                FROM tasks t JOIN projects p ON p.id = t.project_id
+                WHERE t.id = {task_id} AND p.team_id = $1 AND {restore}
+                RETURNING {bytes}::BIGINT
+                "#,
+                restore = retention_condition(RETENTION_RESTORE_SQL, table),
+                bytes = table.bytes,
+                name = table.name,
+                task_id = table.task_id,
+            ))
+            .bind(team_id)
+            .bind(cutoff)
+            .fetch_all(pool)
+            .await?;
+
+            add_retention_counts(&mut counts, table, rows.len() as i64, rows.iter().sum());
+        }
+
+        Ok(counts)
+    }
+
+    // Hides content created, or tasks closed, before the cutoffs, as of `now`
+    pub async fn expire(
+        pool: &PgPool,
+        team_id: Uuid,
+        cutoffs: [Option<DateTime<Utc>>; 3],
+        now: DateTime<Utc>,
+    ) -> Result<RetentionCounts, AppError> {
+        let _timer = query_timer!();
+        let mut counts = RetentionCounts::default();
+        for (table, cutoff) in RETENTION_TABLES.iter().zip(cutoffs) {
+            let rows: Vec<i64> = sqlx::query_scalar(&format!(
+                r#"
+                UPDATE {name} x
+                SET expired_at = $3
+                FROM tasks t JOIN projects p ON p.id = t.project_id
+                WHERE t.id = {task_id} AND p.team_id = $1 AND {expire}
+                RETURNING {bytes}::BIGINT
+                "#,
+                expire = retention_condition(RETENTION_EXPIRE_SQL, table),
+                bytes = table.bytes,
+                name = table.name,
+                task_id = table.task_id,
+            ))
+            .bind(team_id)
+            .bind(cutoff)
+            .bind(now)
+            .fetch_all(pool)
+            .await?;
+
+            add_retention_counts(&mut counts, table, rows.len() as i64, rows.iter().sum());
+        }
+
+        Ok(counts)
+    }
+
+    // Deletes content that expired at or before `delete_before`. Returns the
+    // storage paths of the deleted attachments, those of deleted tasks
+    // included, whose files are left to the caller.
+    pub async fn delete_expired(
+        pool: &PgPool,
+        team_id: Uuid,
+        cutoffs: [Option<DateTime<Utc>>; 3],
+        delete_before: DateTime<Utc>,
+    ) -> Result<(RetentionCounts, Vec<String>), AppError> {
+        let _timer = query_timer!();
+        let mut counts = RetentionCounts::default();
+        let mut storage_paths = Vec::new();
+        for (table, cutoff) in RETENTION_TABLES.iter().zip(cutoffs) {
+            let rows = sqlx::query(&format!(
+                r#"
+                DELETE FROM {name} x
+                USING tasks t JOIN projects p ON p.id = t.project_id
+                WHERE t.id = {task_id} AND p.team_id = $1 AND {delete}
+                RETURNING {bytes}::BIGINT AS bytes, {files} AS storage_paths
+                "#,
+                delete = retention_condition(RETENTION_DELETE_SQL, table),
+                bytes = table.bytes,
+                files = table.files,
+                name = table.name,
+                task_id = table.task_id,
+            ))
+            .bind(team_id)
+            .bind(cutoff)
+            .bind(delete_before)
+            .fetch_all(pool)
+            .await?;
+
+            let deleted_bytes = rows.iter().map(|row| row.get::<i64, _>("bytes")).sum();
+            add_retention_counts(&mut counts, table, rows.len() as i64, deleted_bytes);
+            storage_paths.extend(rows.iter().flat_map(|row| row.get::<Vec<String>, _>("storage_paths")));
+        }
+
+        Ok((counts, storage_paths))
+    }
+}
+
+// Accounts created by the CSV user import (see user_import.rs). Everything
+// runs on the caller's connection, inside the row's savepoint.
+pub struct UserImportQueries;
+
+impl UserImportQueries {
+    // Whether the email, or a username that is or looks the same, is taken
+    pub async fn account_exists(conn: &mut sqlx::PgConnection, email: &str, username: &str) -> Result<bool, AppError> {
+        let _timer = query_timer!();
+        let exists = sqlx::query_scalar(&format!(
+            "SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(email) = LOWER($1) OR username = $2 OR {skeleton} = $3)",
+            skeleton = USERNAME_SKELETON_SQL,
+        ))
+        .bind(email)
+        .bind(username)
+        .bind(validation::username_skeleton(username))
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(exists)
+    }
+
+    pub async fn create_account(
+        conn: &mut sqlx::PgConnection,
+        email: &str,
+        username: &str,
+        display_name: &str,
+        password_hash: &str,
+    ) -> Result<Uuid, AppError> {
+        let _timer = query_timer!();
+        let user_id = sqlx::query_scalar(
+            r#"
+            INSERT INTO users (email, username, display_name, password_hash)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#
+        )
+        .bind(email)
+        .bind(username)
+        .bind(display_name)
+        .bind(password_hash)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    pub async fn create_password_reset_token(
+        conn: &mut sqlx::PgConnection,
+        user_id: Uuid,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query("INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)")
+            .bind(user_id)
+            .bind(token_hash)
+            .bind(expires_at)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn count_team_members(conn: &mut sqlx::PgConnection, team_id: Uuid) -> Result<i64, AppError> {
+        let _timer = query_timer!();
+        let count = sqlx::query_scalar("SELECT COUNT(*) FROM team_members WHERE team_id = $1")
+            .bind(team_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        Ok(count)
+    }
+
+    pub async fn add_team_member(conn: &mut sqlx::PgConnection, team_id: Uuid, user_id: Uuid) -> Result<TeamMember, AppError> {
+        let _timer = query_timer!();
+        insert_team_member(conn, team_id, user_id, TeamRole::Member).await
+    }
+
+    // Teams a CSV row may name: by id, or by name ignoring case
+    pub async fn find_teams(pool: &PgPool, team: &str) -> Result<Vec<Uuid>, AppError> {
+        let _timer = query_timer!();
+        let ids = match team.parse::<Uuid>() {
+            Ok(team_id) => {
+                sqlx::query_scalar("SELECT id FROM teams WHERE id = $1")
+                    .bind(team_id)
+                    .fetch_all(pool)
+                    .await?
+            }
+            Err(_) => {
+                sqlx::query_scalar("SELECT id FROM teams WHERE LOWER(name) = LOWER($1) ORDER BY created_at LIMIT 2")
+                    .bind(team)
+                    .fetch_all(pool)
+                    .await?
+            }
+        };
+
+        Ok(ids)
+    }
+}
+
+pub struct PasswordResetQueries;
+
+impl PasswordResetQueries {
+    // Sets the password of the token's user and uses the token up. Returns the
+    // user, or None when the token is unknown, used or expired.
+    pub async fn reset_password(pool: &PgPool, token_hash: &str, password_hash: &str) -> Result<Option<Uuid>, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        let user_id: Option<Uuid> = sqlx::query_scalar(
+            r#"
+            UPDATE password_reset_tokens SET used_at = NOW()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+            RETURNING user_id
+            "#
+        )
+        .bind(token_hash)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+
+        sqlx::query("UPDATE users SET password_hash = $2, updated_at = NOW() WHERE id = $1 AND is_active = true")
+            .bind(user_id)
+            .bind(password_hash)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(Some(user_id))
+    }
+}
+
+// Stored task snapshots (see task_snapshots.rs). There are no updates: the
+// table rejects them.
+pub struct TaskSnapshotQueries;
+
+fn stored_snapshot_from_row(row: PgRow, with_data: bool) -> Result<StoredTaskSnapshot, AppError> {
+    let created_by = row.get::<Option<Uuid>, _>("created_by").map(|id| UserSummary {
+        id,
+        username: row.get("username"),
+        display_name: row.get("display_name"),
+        avatar_url: row.get("avatar_url"),
+    });
+    let snapshot = if with_data { Some(serde_json::from_value(row.get("data"))?) } else { None };
+
+    Ok(StoredTaskSnapshot {
+        id: row.get("id"),
+        task_id: row.get("task_id"),
+        created_by,
+        note: row.get("note"),
+        created_at: row.get("created_at"),
+        snapshot,
+    })
+}
+
+impl TaskSnapshotQueries {
+    // The comments a snapshot includes: the whole visible thread, oldest first
+    pub async fn get_comments(pool: &PgPool, task_id: Uuid) -> Result<Vec<SnapshotComment>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT c.user_id, c.content, c.created_at, u.username, u.display_name, u.avatar_url
+            FROM task_comments c
+            INNER JOIN users u ON c.user_id = u.id
+            WHERE c.task_id = $1 AND c.quarantined_at IS NULL AND c.expired_at IS NULL
+            ORDER BY c.created_at ASC, c.id ASC
+            "#
+        )
+        .bind(task_id)
+        .fetch_all(pool)
+        .await?;
+
+        let comments = rows.into_iter().map(|row| SnapshotComment {
+            author: UserSummary {
+                id: row.get("user_id"),
+                username: row.get("username"),
+                display_name: row.get("display_name"),
+                avatar_url: row.get("avatar_url"),
+            },
+            content: row.get("content"),
+            created_at: row.get("created_at"),
+        }).collect();
+
+        Ok(comments)
+    }
+
+    pub async fn create(
+        pool: &PgPool,
+        project_id: Uuid,
+        created_by: Uuid,
+        note: Option<&str>,
+        snapshot: &TaskSnapshot,
+    ) -> Result<StoredTaskSnapshot, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            WITH inserted AS (
+                INSERT INTO task_snapshots (task_id, project_id, created_by, note, data)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING *
+            )
+            SELECT s.*, u.username, u.display_name, u.avatar_url
+            FROM inserted s
+            LEFT JOIN users u ON u.id = s.created_by
+            "#
+        )
+        .bind(snapshot.task_id)
+        .bind(project_id)
+        .bind(created_by)
+        .bind(note)
+        .bind(serde_json::to_value(snapshot)?)
+        .fetch_one(pool)
+        .await?;
+
+        stored_snapshot_from_row(row, true)
+    }
+
+    // Newest first, without their data
+    pub async fn list(pool: &PgPool, task_id: Uuid) -> Result<Vec<StoredTaskSnapshot>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(
+            r#"
+            SELECT s.id, s.task_id, s.created_by, s.note, s.created_at, u.username, u.display_name, u.avatar_url
+            FROM task_snapshots s
+            LEFT JOIN users u ON u.id = s.created_by
+            WHERE s.task_id = $1
+            ORDER BY s.created_at DESC, s.id DESC
+            "#
+        )
+        .bind(task_id)
+        .fetch_all(pool)
+        .await?;
+
+        rows.into_iter().map(|row| stored_snapshot_from_row(row, false)).collect()
+    }
+
+    pub async fn get(pool: &PgPool, task_id: Uuid, snapshot_id: Uuid) -> Result<StoredTaskSnapshot, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(
+            r#"
+            SELECT s.*, u.username, u.display_name, u.avatar_url
+            FROM task_snapshots s
+            LEFT JOIN users u ON u.id = s.created_by
+            WHERE s.id = $1 AND s.task_id = $2
+            "#
+        )
+        .bind(snapshot_id)
+        .bind(task_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Snapshot not found".to_string()))?;
+
+        stored_snapshot_from_row(row, true)
+    }
+}
+
+// Tasks and comments held by content screening. Held rows are quarantined
+// while pending and after being rejected.
+pub struct ModerationQueueQueries;
+
+const MODERATION_ITEM_SQL: &str = r#"
+    SELECT
+        m.id, m.content_type, m.task_id, m.comment_id, m.project_id, m.author_id, m.score, m.reasons,
+        m.language, m.status, m.reviewed_by, m.reviewed_at, m.created_at,
+        u.username, u.display_name, u.avatar_url,
+        CASE m.content_type
+            WHEN 'comment' THEN c.content
+            ELSE t.title || COALESCE(E'\n\n' || NULLIF(t.description, ''), '')
+        END AS content
+    FROM moderation_queue m
+    INNER JOIN tasks t ON t.id = m.task_id
+    LEFT JOIN task_comments c ON c.id = m.comment_id
+    LEFT JOIN users u ON u.id = m.author_id
+"#;
+
+fn moderation_item_from_row(row: PgRow) -> ModerationItem {
+    let author = row.get::<Option<Uuid>, _>("author_id").map(|id| UserSummary {
+        id,
+        username: row.get("username"),
+        display_name: row.get("display_name"),
+        avatar_url: row.get("avatar_url"),
+    });
+
+    ModerationItem {
+        id: row.get("id"),
+        content_type: row.get("content_type"),
+        task_id: row.get("task_id"),
+        comment_id: row.get("comment_id"),
+        project_id: row.get("project_id"),
+        author,
+        content: row.get::<Option<String>, _>("content").unwrap_or_default(),
+        score: row.get("score"),
+        reasons: row.get("reasons"),
+        language: row.get("language"),
+        status: row.get("status"),
+        reviewed_by: row.get("reviewed_by"),
+        reviewed_at: row.get("reviewed_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+impl ModerationQueueQueries {
+    // Quarantines the new task or comment and queues it for review
+    pub async fn hold(
+        pool: &PgPool,
+        task_id: Uuid,
+        comment_id: Option<Uuid>,
+        project_id: Uuid,
+        author_id: Uuid,
+        screening: &Screening,
+    ) -> Result<Uuid, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        let content_type = match comment_id {
+            Some(comment_id) => {
+                sqlx::query("UPDATE task_comments SET quarantined_at = NOW() WHERE id = $1")
+                    .bind(comment_id)
+                    .execute(&mut *tx)
+                    .await?;
+                HeldContentType::Comment
+            }
+            None => {
+                sqlx::query("UPDATE tasks SET quarantined_at = NOW() WHERE id = $1")
+                    .bind(task_id)
+                    .execute(&mut *tx)
+                    .await?;
+                HeldContentType::Task
+            }
+        };
+
+        let id = sqlx::query_scalar(
+            r#"
+            INSERT INTO moderation_queue (content_type, task_id, comment_id, project_id, author_id, score, reasons, language)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id
+            "#
+        )
+        .bind(content_type)
+        .bind(task_id)
+        .bind(comment_id)
+        .bind(project_id)
+        .bind(author_id)
+        .bind(screening.score)
+        .bind(&screening.reasons)
+        .bind(screening.language)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn is_task_pending(pool: &PgPool, task_id: Uuid) -> Result<bool, AppError> {
+        let _timer = query_timer!();
+        let pending = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM moderation_queue WHERE task_id = $1 AND content_type = 'task' AND status = 'pending')",
+        )
+        .bind(task_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(pending)
+    }
+
+    // The author's comments on the task waiting for review
+    pub async fn pending_comment_ids(pool: &PgPool, task_id: Uuid, author_id: Uuid) -> Result<Vec<Uuid>, AppError> {
+        let _timer = query_timer!();
+        let ids = sqlx::query_scalar(
+            r#"
+            SELECT comment_id FROM moderation_queue
+            WHERE task_id = $1 AND author_id = $2 AND content_type = 'comment' AND status = 'pending'
+            "#
+        )
+        .bind(task_id)
+        .bind(author_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(ids)
+    }
+
+    // Oldest first, so the queue is worked through in order
+    pub async fn list(pool: &PgPool, status: ModerationStatus, limit: i64) -> Result<Vec<ModerationItem>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!("{} WHERE m.status = $1 ORDER BY m.created_at ASC, m.id ASC LIMIT $2", MODERATION_ITEM_SQL))
+            .bind(status)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rows.into_iter().map(moderation_item_from_row).collect())
+    }
+
+    pub async fn get(pool: &PgPool, item_id: Uuid) -> Result<ModerationItem, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!("{} WHERE m.id = $1", MODERATION_ITEM_SQL))
+            .bind(item_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Moderation item not found".to_string()))?;
+
+        Ok(moderation_item_from_row(row))
+    }
+
+    // Approves or rejects a pending item; approval lifts the quarantine.
+    // Returns false when the item was already reviewed.
+    pub async fn review(pool: &PgPool, item_id: Uuid, reviewer_id: Uuid, status: ModerationStatus) -> Result<bool, AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        let row = sqlx::query(
+            r#"
+            UPDATE moderation_queue SET status = $3, reviewed_by = $2, reviewed_at = NOW()
+            WHERE id = $1 AND status = 'pending'
+            RETURNING task_id, comment_id
+            "#
+        )
+        .bind(item_id)
+        .bind(reviewer_id)
+        .bind(status)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(row) = row else {
+            return Ok(false);
+        };
+
+        if status == ModerationStatus::Approved {
+            match row.get::<Option<Uuid>, _>("comment_id") {
+                Some(comment_id) => {
+                    sqlx::query("UPDATE task_comments SET quarantined_at = NULL WHERE id = $1")
+                        .bind(comment_id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                None => {
+                    sqlx::query("UPDATE tasks SET quarantined_at = NULL WHERE id = $1")
+                        .bind(row.get::<Uuid, _>("task_id"))
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+// Everything team $1 did in [$2, $3): tasks created, status changes and
+// comments in its projects (trashed ones included), and its audit entries
+const AUDIT_EXPORT_SQL: &str = r#"
+    WITH team_tasks AS (
+        SELECT t.id, t.number, t.title, t.created_by, t.created_at, p.id AS project_id, p.name AS project_name
+        FROM tasks t
+        JOIN projects p ON p.id = t.project_id
+        WHERE p.team_id = $1
+    ),
+    entries AS (
+        SELECT t.id, t.created_at AS at, 'task_created' AS action, t.created_by AS actor_id, NULL::varchar AS ip_address,
+               'task' AS entity_type, t.id AS entity_id, t.project_id, t.project_name,
+               format('Created task #%s %s', t.number, t.title) AS summary
+        FROM team_tasks t
+        WHERE t.created_at >= $2 AND t.created_at < $3
+        UNION ALL
+        SELECT tr.id, tr.changed_at, 'status_changed', tr.changed_by, NULL,
+               'task', t.id, t.project_id, t.project_name,
+               format('Moved task #%s %s from %s to %s', t.number, t.title,
+                      replace(initcap(tr.from_status::text), 'Inprogress', 'InProgress'),
+                      replace(initcap(tr.to_status::text), 'Inprogress', 'InProgress'))
+        FROM task_status_transitions tr
+        JOIN team_tasks t ON t.id = tr.task_id
+        WHERE tr.changed_at >= $2 AND tr.changed_at < $3
+        UNION ALL
+        SELECT c.id, c.created_at, 'comment_added', c.user_id, NULL,
+               'comment', c.id, t.project_id, t.project_name,
+               format('Commented on task #%s %s', t.number, t.title)
+        FROM task_comments c
+        JOIN team_tasks t ON t.id = c.task_id
+        WHERE NOT c.is_system AND c.created_at >= $2 AND c.created_at < $3
+        UNION ALL
+        SELECT a.id, a.created_at, a.action::text, a.actor_id, a.ip_address,
+               'team', a.team_id, NULL, NULL,
+               initcap(replace(a.action::text, '_', ' ')) || COALESCE(': ' || a.details::text, '')
+        FROM audit_log a
+        WHERE a.team_id = $1 AND a.created_at >= $2 AND a.created_at < $3
+    )
+"#;
+
+pub struct AuditExportQueries;
+
+fn audit_export_from_row(row: PgRow) -> AuditExport {
+    let format = match row.get::<&str, _>("format") {
+        "jsonl" => AuditExportFormat::Jsonl,
+        _ => AuditExportFormat::Csv,
+    };
+
+    AuditExport {
+        id: row.get("id"),
+        team_id: row.get("team_id"),
+        requested_by: row.get("requested_by"),
+        format,
+        from: row.get("from_at"),
+        to: row.get("to_at"),
+        status: row.get("status"),
+        entry_count: row.get("entry_count"),
+        error: row.get("error"),
+        created_at: row.get("created_at"),
+        completed_at: row.get("completed_at"),
+        storage_path: row.get("storage_path"),
+    }
+}
+
+const AUDIT_EXPORT_COLUMNS_SQL: &str = "id, team_id, requested_by, format, from_at, to_at, status, entry_count, \
+    storage_path, error, created_at, completed_at";
+
+impl AuditExportQueries {
+    pub async fn count_entries(pool: &PgPool, team_id: Uuid, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<i64, AppError> {
+        let _timer = query_timer!();
+        let count = sqlx::query_scalar(&format!("{} SELECT COUNT(*) FROM entries", AUDIT_EXPORT_SQL))
+            .bind(team_id)
+            .bind(from)
+            .bind(to)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    // Oldest first, the page after `after` (the previous page's last entry)
+    pub async fn get_entries(
+        pool: &PgPool,
+        team_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        after: Option<(DateTime<Utc>, Uuid)>,
+        limit: i64,
+    ) -> Result<Vec<AuditExportEntry>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query(&format!(
+            r#"
+            {}
+            SELECT e.*, u.username AS actor
+            FROM entries e
+            LEFT JOIN users u ON u.id = e.actor_id
+            WHERE $4::timestamptz IS NULL OR (e.at, e.id) > ($4, $5)
+            ORDER BY e.at ASC, e.id ASC
+            LIMIT $6
+            "#,
+            AUDIT_EXPORT_SQL,
+        ))
+        .bind(team_id)
+        .bind(from)
+        .bind(to)
+        .bind(after.map(|(at, _)| at))
+        .bind(after.map(|(_, id)| id))
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| AuditExportEntry {
+            id: row.get("id"),
+            at: row.get("at"),
+            action: row.get("action"),
+            actor_id: row.get("actor_id"),
+            actor: row.get("actor"),
+            ip_address: row.get("ip_address"),
+            entity_type: row.get("entity_type"),
+            entity_id: row.get("entity_id"),
+            project_id: row.get("project_id"),
+            project_name: row.get("project_name"),
+            summary: row.get("summary"),
+        }).collect())
+    }
+
+    pub async fn create_export(
+        pool: &PgPool,
+        team_id: Uuid,
+        requested_by: Uuid,
+        format: AuditExportFormat,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<AuditExport, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO audit_exports (team_id, requested_by, format, from_at, to_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING {}
+            "#,
+            AUDIT_EXPORT_COLUMNS_SQL,
+        ))
+        .bind(team_id)
+        .bind(requested_by)
+        .bind(format.name())
+        .bind(from)
+        .bind(to)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(audit_export_from_row(row))
+    }
+
+    pub async fn get_export(pool: &PgPool, export_id: Uuid) -> Result<AuditExport, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!("SELECT {} FROM audit_exports WHERE id = $1", AUDIT_EXPORT_COLUMNS_SQL))
+            .bind(export_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Export not found".to_string()))?;
+
+        Ok(audit_export_from_row(row))
+    }
+
+    pub async fn complete_export(pool: &PgPool, export_id: Uuid, entry_count: i64, storage_path: &str) -> Result<AuditExport, AppError> {
+        let _timer = query_timer!();
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE audit_exports
+            SET status = 'ready', entry_count = $2, storage_path = $3, completed_at = NOW()
+            WHERE id = $1
+            RETURNING {}
+            "#,
+            AUDIT_EXPORT_COLUMNS_SQL,
+        ))
+        .bind(export_id)
+        .bind(entry_count)
+        .bind(storage_path)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(audit_export_from_row(row))
+    }
+
+    pub async fn fail_export(pool: &PgPool, export_id: Uuid, error: &str) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        sqlx::query("UPDATE audit_exports SET status = 'failed', error = $2, completed_at = NOW() WHERE id = $1")
+            .bind(export_id)
+            .bind(error)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+pub struct NotificationSettingsQueries;
+
+impl NotificationSettingsQueries {
+    pub async fn get_defaults(pool: &PgPool, user_id: Uuid) -> Result<Vec<(NotificationEventType, NotificationLevel)>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query("SELECT event_type, level FROM notification_defaults WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| (row.get("event_type"), row.get("level"))).collect())
+    }
+
+    // The user's settings in all of their projects
+    pub async fn get_project_settings(
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> Result<Vec<(Uuid, NotificationEventType, NotificationLevel)>, AppError> {
+        let _timer = query_timer!();
+        let rows = sqlx::query("SELECT project_id, event_type, level FROM notification_settings WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| (row.get("project_id"), row.get("event_type"), row.get("level"))).collect())
+    }
+
+    // Sets the defaults of the given event types; None removes one
+    pub async fn set_defaults(
+        pool: &PgPool,
+        user_id: Uuid,
+        levels: &BTreeMap<NotificationEventType, Option<NotificationLevel>>,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        for (event_type, level) in levels {
+            match level {
+                Some(level) => {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO notification_defaults (user_id, event_type, level)
+                        VALUES ($1, $2, $3)
+                        ON CONFLICT (user_id, event_type) DO UPDATE SET level = EXCLUDED.level, updated_at = NOW()
+                        "#
+                    )
+                    .bind(user_id)
+                    .bind(event_type)
+                    .bind(level)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                None => {
+                    sqlx::query("DELETE FROM notification_defaults WHERE user_id = $1 AND event_type = $2")
+                        .bind(user_id)
+                        .bind(event_type)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Sets the project's levels of the given event types; None removes one
+    pub async fn set_project_settings(
+        pool: &PgPool,
+        user_id: Uuid,
+        project_id: Uuid,
+        levels: &BTreeMap<NotificationEventType, Option<NotificationLevel>>,
+    ) -> Result<(), AppError> {
+        let _timer = query_timer!();
+        let mut tx = pool.begin().await?;
+
+        for (event_type, level) in levels {
+            match level {
+                Some(level) => {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO notification_settings (user_id, project_id, event_type, level)
+                        VALUES ($1, $2, $3, $4)
+                        ON CONFLICT (user_id, project_id, event_type) DO UPDATE SET level = EXCLUDED.level, updated_at = NOW()
+                        "#
+                    )
+                    .bind(user_id)
+                    .bind(project_id)
+                    .bind(event_type)
+                    .bind(level)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                None => {
+                    sqlx::query("DELETE FROM notification_settings WHERE user_id = $1 AND project_id = $2 AND event_type = $3")
+                        .bind(user_id)
+                        .bind(project_id)
+                        .bind(event_type)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+// Query timing. Every *Queries function starts with `query_timer!()`, whose
+// guard records on drop how long the call took: in a histogram named after
+// the function (see GET /api/admin/metrics), in the current request's
+// database time for the X-Server-Timing header, and in the log when it took
+// longer than SLOW_QUERY_MS. Slow statements are also logged by sqlx itself,
+// with placeholders where the bound values go, so no user data ends up in the
+// log.
+//
+// Request timing lives in a task-local, so queries run on spawned tasks only
+// show up in the histograms.
+
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+const DEFAULT_SLOW_QUERY_MS: u64 = 250;
+const DEFAULT_SLOW_REQUEST_MS: u64 = 1000;
+
+// Upper bounds of the histogram buckets, in milliseconds
+const BUCKETS_MS: [u64; 14] = [1, 2, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000, u64::MAX];
+
+fn threshold_from_env(name: &str, default_ms: u64) -> Duration {
+    let ms = env::var(name)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(default_ms);
+    Duration::from_millis(ms)
+}
+
+// Query functions taking longer than this are logged (SLOW_QUERY_MS)
+pub fn slow_query_threshold() -> Duration {
+    static THRESHOLD: OnceLock<Duration> = OnceLock::new();
+    *THRESHOLD.get_or_init(|| threshold_from_env("SLOW_QUERY_MS", DEFAULT_SLOW_QUERY_MS))
+}
+
+// Requests taking longer than this are logged with their timing (SLOW_REQUEST_MS)
+pub fn slow_request_threshold() -> Duration {
+    static THRESHOLD: OnceLock<Duration> = OnceLock::new();
+    *THRESHOLD.get_or_init(|| threshold_from_env("SLOW_REQUEST_MS", DEFAULT_SLOW_REQUEST_MS))
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    counts: [u64; BUCKETS_MS.len()],
+    total: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    pub fn record(&mut self, elapsed: Duration) {
+        let ms = elapsed.as_millis();
+        let bucket = BUCKETS_MS.iter().position(|bound| ms < u128::from(*bound)).unwrap_or(BUCKETS_MS.len() - 1);
+        self.counts[bucket] += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    // Upper bound of the bucket the quantile falls in, capped at the slowest call
+    pub fn percentile_ms(&self, quantile: f64) -> f64 {
+        let rank = (quantile * self.count() as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return (BUCKETS_MS[bucket] as f64).min(millis(self.max));
+            }
+        }
+        0.0
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct QueryStats {
+    pub name: &'static str,
+    pub count: u64,
+    pub total_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl QueryStats {
+    fn new(name: &'static str, histogram: &Histogram) -> Self {
+        let count = histogram.count();
+        QueryStats {
+            name,
+            count,
+            total_ms: millis(histogram.total),
+            mean_ms: millis(histogram.total) / count.max(1) as f64,
+            p50_ms: histogram.percentile_ms(0.5),
+            p95_ms: histogram.percentile_ms(0.95),
+            p99_ms: histogram.percentile_ms(0.99),
+            max_ms: millis(histogram.max),
+        }
+    }
+}
+
+fn histograms() -> &'static Mutex<HashMap<&'static str, Histogram>> {
+    static HISTOGRAMS: OnceLock<Mutex<HashMap<&'static str, Histogram>>> = OnceLock::new();
+    HISTOGRAMS.get_or_init(Default::default)
+}
+
+// Every query function called since startup, slowest p95 first
+pub fn query_stats() -> Vec<QueryStats> {
+    let histograms = histograms().lock().unwrap();
+    let mut stats: Vec<QueryStats> = histograms.iter().map(|(name, histogram)| QueryStats::new(name, histogram)).collect();
+    stats.sort_by(|a, b| b.p95_ms.total_cmp(&a.p95_ms).then(a.name.cmp(b.name)));
+    stats
+}
+
+// Database time of the current request: how long at least one query function
+// was running, so nested and concurrent calls aren't counted twice
+#[derive(Debug, Default)]
+struct RequestTiming {
+    database: Duration,
+    queries: u64,
+    running: u32,
+    busy_since: Option<Instant>,
+}
+
+impl RequestTiming {
+    fn query_started(&mut self) {
+        self.queries += 1;
+        if self.running == 0 {
+            self.busy_since = Some(Instant::now());
+        }
+        self.running += 1;
+    }
+
+    fn query_finished(&mut self) {
+        self.running = self.running.saturating_sub(1);
+        if self.running == 0 {
+            if let Some(since) = self.busy_since.take() {
+                self.database += since.elapsed();
+            }
+        }
+    }
+}
+
+tokio::task_local! {
+    static REQUEST_TIMING: Arc<Mutex<RequestTiming>>;
+}
+
+// "simplecards::database::queries::UserQueries::get_user_by_id::{{closure}}::f"
+// becomes "UserQueries::get_user_by_id"
+pub fn function_name(path: &'static str) -> &'static str {
+    let path = path.trim_end_matches("::f").trim_end_matches("::{{closure}}");
+    match path.rmatch_indices("::").nth(1) {
+        Some((index, _)) => &path[index + 2..],
+        None => path,
+    }
+}
+
+// Times the query function it's started in, see query_timer!
+pub struct QueryTimer {
+    name: &'static str,
+    started: Instant,
+}
+
+impl QueryTimer {
+    pub fn start(name: &'static str) -> Self {
+        let _ = REQUEST_TIMING.try_with(|timing| timing.lock().unwrap().query_started());
+        QueryTimer { name, started: Instant::now() }
+    }
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        histograms().lock().unwrap().entry(self.name).or_default().record(elapsed);
+
+        let _ = REQUEST_TIMING.try_with(|timing| timing.lock().unwrap().query_finished());
+
+        if elapsed > slow_query_threshold() {
+            warn!("Slow query: {} took {} ms", self.name, elapsed.as_millis());
+        }
+    }
+}
+
+// Starts a QueryTimer named after the enclosing function
+macro_rules! query_timer {
+    () => {
+        $crate::database::timing::QueryTimer::start({
+            fn f() {}
+            $crate::database::timing::function_name(std::any::type_name_of_val(&f))
+        })
+    };
+}
+pub(crate) use query_timer;
+
+// `db` is the time spent waiting for query functions and `app` the rest
+pub fn server_timing_header(total: Duration, database: Duration, queries: u64) -> String {
+    format!(
+        "db;dur={:.1};desc=\"{} queries\", app;dur={:.1}",
+        millis(database),
+        queries,
+        millis(total.saturating_sub(database)),
+    )
+}
+
+// Adds X-Server-Timing to every response and logs slow requests
+pub async fn server_timing(req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_string();
+    let method = req.method().clone();
+    let timing = Arc::new(Mutex::new(RequestTiming::default()));
+    let started = Instant::now();
+
+    let mut response = REQUEST_TIMING.scope(timing.clone(), next.run(req)).await;
+
+    let total = started.elapsed();
+    let (database, queries) = {
+        let timing = timing.lock().unwrap();
+        (timing.database, timing.queries)
+    };
+    let header = server_timing_header(total, database, queries);
+    if total > slow_request_threshold() {
+        warn!("Slow request: {} {} took {} ms ({})", method, path, total.as_millis(), header);
+    }
+    if let Ok(value) = HeaderValue::from_str(&header) {
+        response.headers_mut().insert("x-server-timing", value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_name_from_type_name() {
+        assert_eq!(
+            function_name("simplecards::database::queries::UserQueries::get_user_by_id::{{closure}}::f"),
+            "UserQueries::get_user_by_id"
+        );
+        fn f() {}
+        assert_eq!(function_name(std::any::type_name_of_val(&f)), "tests::test_function_name_from_type_name");
+    }
+
+    #[test]
+    fn test_percentiles_use_bucket_bounds() {
+        let mut histogram = Histogram::default();
+        for _ in 0..94 {
+            histogram.record(Duration::from_micros(1500));
+        }
+        for _ in 0..6 {
+            histogram.record(Duration::from_millis(300));
+        }
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.percentile_ms(0.5), 2.0);
+        assert_eq!(histogram.percentile_ms(0.95), 300.0);
+
+        let stats = QueryStats::new("TaskQueries::list_tasks", &histogram);
+        assert_eq!(stats.p99_ms, 300.0);
+        assert_eq!(stats.max_ms, 300.0);
+        assert!((stats.mean_ms - 19.41).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_empty_histogram() {
+        let stats = QueryStats::new("UserQueries::get_user_by_id", &Histogram::default());
+        assert_eq!((stats.count, stats.mean_ms, stats.p95_ms), (0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_server_timing_header() {
+        assert_eq!(
+            server_timing_header(Duration::from_millis(40), Duration::from_micros(12_340), 3),
+            "db;dur=12.3;desc=\"3 queries\", app;dur=27.7"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_query_functions_count_once() {
+        let pause = Duration::from_millis(20);
+        let timing = Arc::new(Mutex::new(RequestTiming::default()));
+        let started = Instant::now();
+        REQUEST_TIMING
+            .scope(timing.clone(), async {
+                let outer = QueryTimer::start("TeamQueries::create_team");
+                tokio::time::sleep(pause).await;
+                let inner = QueryTimer::start("TeamQueries::add_team_member");
+                tokio::time::sleep(pause).await;
+                drop(outer);
+                drop(inner);
+                // Not database time
+                tokio::time::sleep(pause).await;
+                let _timer = QueryTimer::start("UserQueries::get_user_by_id");
+                tokio::time::sleep(pause).await;
+            })
+            .await;
+        let total = started.elapsed();
+
+        let timing = timing.lock().unwrap();
+        assert_eq!((timing.queries, timing.running), (3, 0));
+        assert!(timing.database >= pause * 3);
+        assert!(timing.database <= total - pause);
+        let names: Vec<&str> = query_stats().into_iter().map(|stats| stats.name).collect();
+        assert!(names.contains(&"TeamQueries::add_team_member"));
+    }
+}
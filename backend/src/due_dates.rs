@@ -0,0 +1,113 @@
+// When tasks are due. An all-day due date names a day rather than an instant,
+// so it lasts until midnight at the end of that day in the assignee's timezone
+// (their digest timezone, UTC if unset). DUE_AT_SQL in queries.rs is the same
+// rule for queries.
+
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::database::models::{DueFilter, Task, TaskStatus};
+
+// How far ahead `?due=upcoming` looks
+pub const UPCOMING_DAYS: i64 = 7;
+
+pub fn due_at(task: &Task, timezone: Tz) -> Option<DateTime<Utc>> {
+    let due_date = task.due_date?;
+    if !task.is_all_day {
+        return Some(due_date);
+    }
+
+    let end_of_day = due_date.date_naive().succ_opt()?.and_time(NaiveTime::MIN);
+    // On days where local midnight is skipped, the day ends at the first instant after it
+    let end = timezone
+        .from_local_datetime(&end_of_day)
+        .earliest()
+        .or_else(|| timezone.from_local_datetime(&(end_of_day + Duration::hours(1))).earliest())?;
+    Some(end.with_timezone(&Utc))
+}
+
+pub fn matches(filter: DueFilter, task: &Task, now: DateTime<Utc>, timezone: Tz) -> bool {
+    if task.status == TaskStatus::Done {
+        return false;
+    }
+    match (filter, due_at(task, timezone)) {
+        (_, None) => false,
+        (DueFilter::Overdue, Some(due_at)) => due_at <= now,
+        (DueFilter::Upcoming, Some(due_at)) => due_at > now && due_at <= now + Duration::days(UPCOMING_DAYS),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::{DueDate, TaskPriority};
+    use uuid::Uuid;
+
+    fn task(due: DueDate) -> Task {
+        Task {
+            id: Uuid::new_v4(),
+            title: "Ship it".to_string(),
+            description: None,
+            project_id: Uuid::new_v4(),
+            created_by: Uuid::new_v4(),
+            assigned_to: None,
+            status: TaskStatus::Todo,
+            priority: TaskPriority::Medium,
+            due_date: Some(due.instant()),
+            is_all_day: due.is_all_day(),
+            tags: None,
+            cover_color: None,
+            cover_emoji: None,
+            position: 1,
+            number: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_all_day_dates_end_at_local_midnight() {
+        let friday = task("2024-06-07".parse().unwrap());
+
+        assert_eq!(due_at(&friday, Tz::UTC), Some(utc("2024-06-08T00:00:00Z")));
+        assert_eq!(due_at(&friday, chrono_tz::Europe::Berlin), Some(utc("2024-06-07T22:00:00Z")));
+        // UTC+13: the day is over while it is still Friday morning in UTC
+        assert_eq!(due_at(&friday, chrono_tz::Pacific::Tongatapu), Some(utc("2024-06-07T11:00:00Z")));
+
+        let timed = task("2024-06-07T09:30:00+02:00".parse().unwrap());
+        assert_eq!(due_at(&timed, chrono_tz::Pacific::Tongatapu), Some(utc("2024-06-07T07:30:00Z")));
+    }
+
+    #[test]
+    fn test_overdue_depends_on_the_assignee_timezone() {
+        let friday = task("2024-06-07".parse().unwrap());
+        // 01:00 Friday in Berlin is not past Friday
+        let early_friday = utc("2024-06-06T23:00:00Z");
+        assert!(!matches(DueFilter::Overdue, &friday, early_friday, chrono_tz::Europe::Berlin));
+        assert!(matches(DueFilter::Upcoming, &friday, early_friday, chrono_tz::Europe::Berlin));
+
+        // Noon UTC on Friday is already Saturday in UTC+13
+        let friday_noon = utc("2024-06-07T12:00:00Z");
+        assert!(matches(DueFilter::Overdue, &friday, friday_noon, chrono_tz::Pacific::Tongatapu));
+        assert!(!matches(DueFilter::Overdue, &friday, friday_noon, Tz::UTC));
+
+        let mut done = friday.clone();
+        done.status = TaskStatus::Done;
+        assert!(!matches(DueFilter::Overdue, &done, friday_noon, chrono_tz::Pacific::Tongatapu));
+    }
+
+    #[test]
+    fn test_due_dates_need_a_day_or_an_offset() {
+        assert_eq!("2024-06-07".parse::<DueDate>().unwrap().instant(), utc("2024-06-07T00:00:00Z"));
+        assert!("2024-06-07".parse::<DueDate>().unwrap().is_all_day());
+        assert!(!"2024-06-07T10:00:00Z".parse::<DueDate>().unwrap().is_all_day());
+
+        assert!("2024-06-07T10:00:00".parse::<DueDate>().is_err());
+        assert!("07.06.2024".parse::<DueDate>().is_err());
+        assert!("2024-02-30".parse::<DueDate>().is_err());
+    }
+}
@@ -0,0 +1,82 @@
+// Duplicate detection for new tasks. When a request asks for it, or the
+// project's workflow turns it on for every task, open tasks of the project
+// whose titles are trigram-similar to the new title (pg_trgm, see migration
+// 030) are returned in a 409 instead of creating the task. `force` skips the
+// check either way.
+
+use sqlx::PgPool;
+use std::env;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+use crate::database::queries::{ProjectQueries, TaskQueries};
+use crate::utils::errors::AppError;
+
+// pg_trgm's own default for the % operator
+const DEFAULT_THRESHOLD: f32 = 0.3;
+const MAX_CANDIDATES: i64 = 5;
+
+// DUPLICATE_TITLE_THRESHOLD, a similarity in (0, 1]; anything else falls back
+// to the default
+fn parse_threshold(value: Option<&str>) -> f32 {
+    value
+        .and_then(|value| value.trim().parse::<f32>().ok())
+        .filter(|threshold| *threshold > 0.0 && *threshold <= 1.0)
+        .unwrap_or(DEFAULT_THRESHOLD)
+}
+
+pub fn threshold() -> f32 {
+    static THRESHOLD: OnceLock<f32> = OnceLock::new();
+    *THRESHOLD.get_or_init(|| parse_threshold(env::var("DUPLICATE_TITLE_THRESHOLD").ok().as_deref()))
+}
+
+// None when the project's setting decides
+fn requested_check(check_duplicates: bool, force: bool) -> Option<bool> {
+    match (force, check_duplicates) {
+        (true, _) => Some(false),
+        (false, true) => Some(true),
+        (false, false) => None,
+    }
+}
+
+// Fails with PossibleDuplicates if the check applies and finds similar open tasks
+pub async fn check(pool: &PgPool, project_id: Uuid, title: &str, check_duplicates: bool, force: bool) -> Result<(), AppError> {
+    let enabled = match requested_check(check_duplicates, force) {
+        Some(enabled) => enabled,
+        None => ProjectQueries::get_project_workflow(pool, project_id).await?.check_duplicates,
+    };
+    if !enabled {
+        return Ok(());
+    }
+
+    let candidates = TaskQueries::find_similar_open_tasks(pool, project_id, title, threshold(), MAX_CANDIDATES).await?;
+    if candidates.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::PossibleDuplicates(candidates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_threshold() {
+        assert_eq!(parse_threshold(None), DEFAULT_THRESHOLD);
+        assert_eq!(parse_threshold(Some("0.6")), 0.6);
+        assert_eq!(parse_threshold(Some(" 1 ")), 1.0);
+        assert_eq!(parse_threshold(Some("0")), DEFAULT_THRESHOLD);
+        assert_eq!(parse_threshold(Some("1.5")), DEFAULT_THRESHOLD);
+        assert_eq!(parse_threshold(Some("-0.2")), DEFAULT_THRESHOLD);
+        assert_eq!(parse_threshold(Some("high")), DEFAULT_THRESHOLD);
+    }
+
+    #[test]
+    fn test_force_skips_the_check() {
+        assert_eq!(requested_check(true, true), Some(false));
+        assert_eq!(requested_check(false, true), Some(false));
+        assert_eq!(requested_check(true, false), Some(true));
+        assert_eq!(requested_check(false, false), None);
+    }
+}
@@ -0,0 +1,355 @@
+// Daily / weekly digest emails. The scheduler job re-enqueues itself every
+// few minutes, claims the users whose digest is due and queues one email each.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::database::{
+    connection::Database,
+    models::{DigestFrequency, DigestMention, DigestTask, Job, NotificationEventType, NotificationLevel, ProjectActivity},
+    queries::{DigestQueries, JobQueries},
+};
+use crate::integrations::app_base_url;
+use crate::jobs::{self, JobHandler};
+use crate::notification_settings::{self, NotificationSettings};
+use crate::utils::errors::AppError;
+use super::{queue_email, templates::EmailTemplate};
+
+pub const DIGEST_SCHEDULER_JOB: &str = "digest_scheduler";
+const SCHEDULER_INTERVAL_MINUTES: i64 = 15;
+const EXCERPT_LENGTH: usize = 140;
+
+#[derive(Debug, Serialize)]
+pub struct Digest {
+    pub frequency: DigestFrequency,
+    pub since: DateTime<Utc>,
+    pub due_soon: Vec<DigestTask>,
+    pub newly_assigned: Vec<DigestTask>,
+    pub mentions: Vec<DigestMention>,
+    pub activity: Vec<ProjectActivity>,
+}
+
+fn period_length(frequency: DigestFrequency) -> Duration {
+    match frequency {
+        DigestFrequency::Weekly => Duration::days(7),
+        DigestFrequency::Daily | DigestFrequency::Off => Duration::days(1),
+    }
+}
+
+// Gathers the digest with one query per section, across all of the user's projects,
+// minus what their digest settings leave out. Without a previous digest the window
+// covers one period.
+pub async fn build_digest(
+    pool: &PgPool,
+    user_id: Uuid,
+    username: &str,
+    frequency: DigestFrequency,
+    previous_sent_at: Option<DateTime<Utc>>,
+) -> Result<Digest, AppError> {
+    let now = Utc::now();
+    let period = period_length(frequency);
+    let since = previous_sent_at.unwrap_or(now - period);
+
+    let mut digest = Digest {
+        frequency,
+        since,
+        due_soon: DigestQueries::get_tasks_due_soon(pool, user_id, now + period).await?,
+        newly_assigned: DigestQueries::get_newly_assigned_tasks(pool, user_id, since).await?,
+        mentions: DigestQueries::get_mentions(pool, user_id, username, since).await?,
+        activity: DigestQueries::get_project_activity(pool, user_id, since).await?,
+    };
+    digest.retain_allowed(&*notification_settings::load(pool, user_id).await?);
+    Ok(digest)
+}
+
+impl Digest {
+    // Drops the entries of projects whose digest level leaves them out:
+    // `mentions_only` keeps their mentions, `none` keeps nothing
+    pub fn retain_allowed(&mut self, settings: &NotificationSettings) {
+        let listed = |project_id| settings.allows(project_id, NotificationEventType::Digest);
+        self.due_soon.retain(|task| listed(task.project_id));
+        self.newly_assigned.retain(|task| listed(task.project_id));
+        self.activity.retain(|project| listed(project.project_id));
+        self.mentions.retain(|mention| {
+            settings.level(mention.project_id, NotificationEventType::Digest) != NotificationLevel::None
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.due_soon.is_empty()
+            && self.newly_assigned.is_empty()
+            && self.mentions.is_empty()
+            && self.activity.iter().all(|project| {
+                project.tasks_created == 0 && project.tasks_completed == 0 && project.comments_added == 0
+            })
+    }
+
+    pub fn period_name(&self) -> &'static str {
+        match self.frequency {
+            DigestFrequency::Weekly => "weekly",
+            DigestFrequency::Daily | DigestFrequency::Off => "daily",
+        }
+    }
+
+    // Plain text body of the digest, one block per non-empty section
+    pub fn render_summary(&self, base_url: &str) -> String {
+        let mut sections: Vec<String> = Vec::new();
+
+        if !self.due_soon.is_empty() {
+            let lines: Vec<String> = self
+                .due_soon
+                .iter()
+                .map(|task| {
+                    let due = task.due_date.map(|d| d.format("%a %b %-d").to_string()).unwrap_or_default();
+                    format!("- {} (due {}) {}", task_line(task), due, task_url(base_url, task.project_id, task.id))
+                })
+                .collect();
+            sections.push(format!("Due soon\n{}", lines.join("\n")));
+        }
+
+        if !self.newly_assigned.is_empty() {
+            let lines: Vec<String> = self
+                .newly_assigned
+                .iter()
+                .map(|task| format!("- {} {}", task_line(task), task_url(base_url, task.project_id, task.id)))
+                .collect();
+            sections.push(format!("Assigned to you\n{}", lines.join("\n")));
+        }
+
+        if !self.mentions.is_empty() {
+            let lines: Vec<String> = self
+                .mentions
+                .iter()
+                .map(|mention| {
+                    format!(
+                        "- {} on \"{}\" ({}): {}\n  {}",
+                        mention.author,
+                        mention.task_title,
+                        mention.project_name,
+                        excerpt(&mention.content),
+                        task_url(base_url, mention.project_id, mention.task_id)
+                    )
+                })
+                .collect();
+            sections.push(format!("Mentions\n{}", lines.join("\n")));
+        }
+
+        let active: Vec<String> = self
+            .activity
+            .iter()
+            .filter(|project| project.tasks_created + project.tasks_completed + project.comments_added > 0)
+            .map(|project| {
+                format!(
+                    "- {}: {} created, {} completed, {} comments",
+                    project.project_name, project.tasks_created, project.tasks_completed, project.comments_added
+                )
+            })
+            .collect();
+        if !active.is_empty() {
+            sections.push(format!("Project activity\n{}", active.join("\n")));
+        }
+
+        if sections.is_empty() {
+            return "Nothing new this time.".to_string();
+        }
+        sections.join("\n\n")
+    }
+
+    pub fn template_context(&self, name: &str, base_url: &str) -> serde_json::Value {
+        json!({
+            "name": name,
+            "period": self.period_name(),
+            "summary": self.render_summary(base_url),
+        })
+    }
+}
+
+fn task_line(task: &DigestTask) -> String {
+    format!("#{} {} [{}]", task.number, task.title, task.project_name)
+}
+
+fn task_url(base_url: &str, project_id: Uuid, task_id: Uuid) -> String {
+    format!("{}/projects/{}/tasks/{}", base_url, project_id, task_id)
+}
+
+fn excerpt(content: &str) -> String {
+    let flat = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= EXCERPT_LENGTH {
+        return flat;
+    }
+    let truncated: String = flat.chars().take(EXCERPT_LENGTH).collect();
+    format!("{}…", truncated.trim_end())
+}
+
+// Makes sure a scheduler run is queued at `run_at`, unless one is already pending
+pub async fn schedule(pool: &PgPool, run_at: DateTime<Utc>) -> Result<(), AppError> {
+    if !JobQueries::has_pending_job(pool, DIGEST_SCHEDULER_JOB).await? {
+        jobs::enqueue_at(pool, DIGEST_SCHEDULER_JOB, &json!({}), run_at).await?;
+    }
+    Ok(())
+}
+
+pub struct DigestSchedulerJob {
+    database: Database,
+}
+
+impl DigestSchedulerJob {
+    pub fn new(database: Database) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait]
+impl JobHandler for DigestSchedulerJob {
+    async fn run(&self, _job: &Job) -> anyhow::Result<()> {
+        let pool = self.database.pool();
+
+        // Queue the next run first so a failing run doesn't stop the schedule;
+        // retries of this job find it pending and don't queue another one
+        schedule(pool, Utc::now() + Duration::minutes(SCHEDULER_INTERVAL_MINUTES)).await?;
+
+        let due = DigestQueries::claim_due_digests(pool).await?;
+        let base_url = app_base_url();
+        let mut queued = 0;
+
+        for user in due {
+            let digest = build_digest(pool, user.user_id, &user.username, user.frequency, user.previous_sent_at).await?;
+            if digest.is_empty() {
+                continue;
+            }
+
+            queue_email(
+                pool,
+                &user.email,
+                Some(user.user_id),
+                EmailTemplate::DailyDigest,
+                digest.template_context(&user.display_name, &base_url),
+            ).await?;
+            queued += 1;
+        }
+
+        if queued > 0 {
+            info!("Queued {} digest emails", queued);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::TaskStatus;
+
+    const BASE_URL: &str = "https://cards.example.com";
+
+    fn task(number: i32, title: &str) -> DigestTask {
+        DigestTask {
+            id: Uuid::new_v4(),
+            project_id: Uuid::new_v4(),
+            project_name: "Website".to_string(),
+            number,
+            title: title.to_string(),
+            status: TaskStatus::Todo,
+            due_date: Some(Utc::now() + Duration::days(1)),
+        }
+    }
+
+    fn empty_digest() -> Digest {
+        Digest {
+            frequency: DigestFrequency::Daily,
+            since: Utc::now() - Duration::days(1),
+            due_soon: vec![],
+            newly_assigned: vec![],
+            mentions: vec![],
+            activity: vec![ProjectActivity {
+                project_id: Uuid::new_v4(),
+                project_name: "Quiet".to_string(),
+                tasks_created: 0,
+                tasks_completed: 0,
+                comments_added: 0,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_empty_digest() {
+        let digest = empty_digest();
+
+        assert!(digest.is_empty());
+        assert_eq!(digest.render_summary(BASE_URL), "Nothing new this time.");
+    }
+
+    #[test]
+    fn test_summary_lists_only_non_empty_sections() {
+        let mut digest = empty_digest();
+        digest.frequency = DigestFrequency::Weekly;
+        digest.due_soon.push(task(12, "Ship landing page"));
+        digest.mentions.push(DigestMention {
+            comment_id: Uuid::new_v4(),
+            task_id: Uuid::new_v4(),
+            project_id: Uuid::new_v4(),
+            task_title: "Fix login".to_string(),
+            project_name: "Website".to_string(),
+            author: "Bob".to_string(),
+            content: "@alice can you\n take a look?".to_string(),
+            created_at: Utc::now(),
+        });
+
+        let summary = digest.render_summary(BASE_URL);
+        assert!(!digest.is_empty());
+        assert!(summary.starts_with("Due soon\n- #12 Ship landing page [Website]"));
+        assert!(summary.contains("Mentions\n- Bob on \"Fix login\" (Website): @alice can you take a look?"));
+        assert!(!summary.contains("Assigned to you"));
+        assert!(!summary.contains("Project activity"));
+
+        let email = EmailTemplate::DailyDigest
+            .render(&digest.template_context("Alice", BASE_URL))
+            .unwrap();
+        assert_eq!(email.subject, "Your weekly SimpleCards digest");
+        assert!(email.body.contains(&summary));
+    }
+
+    #[test]
+    fn test_muted_projects_leave_the_digest() {
+        let mention = |project_id| DigestMention {
+            comment_id: Uuid::new_v4(),
+            task_id: Uuid::new_v4(),
+            project_id,
+            task_title: "Fix login".to_string(),
+            project_name: "Website".to_string(),
+            author: "Bob".to_string(),
+            content: "@alice".to_string(),
+            created_at: Utc::now(),
+        };
+        let (listed, quiet, muted) = (task(1, "Listed"), task(2, "Quiet"), task(3, "Muted"));
+        let mut digest = empty_digest();
+        digest.due_soon = vec![listed.clone(), quiet.clone(), muted.clone()];
+        digest.newly_assigned = vec![quiet.clone(), muted.clone()];
+        digest.mentions = vec![mention(listed.project_id), mention(quiet.project_id), mention(muted.project_id)];
+
+        let settings = NotificationSettings {
+            defaults: Default::default(),
+            projects: [
+                ((quiet.project_id, NotificationEventType::Digest), NotificationLevel::MentionsOnly),
+                ((muted.project_id, NotificationEventType::Digest), NotificationLevel::None),
+                // Other event types don't matter here
+                ((listed.project_id, NotificationEventType::Comment), NotificationLevel::None),
+            ]
+            .into(),
+        };
+        digest.retain_allowed(&settings);
+
+        let numbers: Vec<i32> = digest.due_soon.iter().map(|task| task.number).collect();
+        assert_eq!(numbers, [1]);
+        assert!(digest.newly_assigned.is_empty());
+        let projects: Vec<Uuid> = digest.mentions.iter().map(|mention| mention.project_id).collect();
+        assert_eq!(projects, [listed.project_id, quiet.project_id]);
+        // Activity of projects without settings stays
+        assert_eq!(digest.activity.len(), 1);
+    }
+}
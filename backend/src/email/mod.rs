@@ -0,0 +1,176 @@
+// Outbound email: templates, transports and the queued delivery job
+pub mod digest;
+pub mod templates;
+
+use async_trait::async_trait;
+use lettre::{
+    message::Mailbox,
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::env;
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::database::{
+    connection::Database,
+    models::{EmailStatus, Job},
+    queries::EmailQueries,
+};
+use crate::jobs::{self, JobHandler};
+use crate::utils::errors::AppError;
+use templates::EmailTemplate;
+
+pub const SEND_EMAIL_JOB: &str = "send_email";
+
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait EmailService: Send + Sync {
+    async fn send(&self, message: &EmailMessage) -> anyhow::Result<()>;
+}
+
+// Development transport: writes the message to the log instead of sending it
+pub struct LogEmailService;
+
+#[async_trait]
+impl EmailService for LogEmailService {
+    async fn send(&self, message: &EmailMessage) -> anyhow::Result<()> {
+        info!("Email to {}: {}\n{}", message.to, message.subject, message.body);
+        Ok(())
+    }
+}
+
+pub struct SmtpEmailService {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpEmailService {
+    pub fn from_env(host: &str) -> anyhow::Result<Self> {
+        let port = env::var("SMTP_PORT")
+            .ok()
+            .and_then(|port| port.parse::<u16>().ok())
+            .unwrap_or(587);
+        let from = env::var("EMAIL_FROM")
+            .unwrap_or_else(|_| "SimpleCards <no-reply@simplecards.local>".to_string())
+            .parse::<Mailbox>()?;
+
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?.port(port);
+        if let (Ok(username), Ok(password)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(SmtpEmailService {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl EmailService for SmtpEmailService {
+    async fn send(&self, message: &EmailMessage) -> anyhow::Result<()> {
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(message.to.parse()?)
+            .subject(&message.subject)
+            .body(message.body.clone())?;
+
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
+
+// SMTP when SMTP_HOST is configured, otherwise log-only delivery
+pub fn email_service_from_env() -> anyhow::Result<Arc<dyn EmailService>> {
+    match env::var("SMTP_HOST") {
+        Ok(host) if !host.is_empty() => Ok(Arc::new(SmtpEmailService::from_env(&host)?)),
+        _ => Ok(Arc::new(LogEmailService)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendEmailPayload {
+    pub to: String,
+    pub user_id: Option<Uuid>,
+    pub template: EmailTemplate,
+    pub context: serde_json::Value,
+}
+
+// Queue an email for delivery by the job worker, so SMTP problems never fail
+// the API request that triggered it
+pub async fn queue_email(
+    pool: &PgPool,
+    to: &str,
+    user_id: Option<Uuid>,
+    template: EmailTemplate,
+    context: serde_json::Value,
+) -> Result<(), AppError> {
+    let payload = SendEmailPayload {
+        to: to.to_string(),
+        user_id,
+        template,
+        context,
+    };
+
+    jobs::enqueue(pool, SEND_EMAIL_JOB, &payload).await?;
+    Ok(())
+}
+
+pub struct SendEmailJob {
+    database: Database,
+    service: Arc<dyn EmailService>,
+}
+
+impl SendEmailJob {
+    pub fn new(database: Database, service: Arc<dyn EmailService>) -> Self {
+        Self { database, service }
+    }
+}
+
+#[async_trait]
+impl JobHandler for SendEmailJob {
+    async fn run(&self, job: &Job) -> anyhow::Result<()> {
+        let payload: SendEmailPayload = serde_json::from_value(job.payload.clone())?;
+        let pool = self.database.pool();
+        let template = payload.template.name();
+
+        // Opt-outs are checked at delivery time so they apply to already queued mail
+        if let Some(user_id) = payload.user_id {
+            if !payload.template.is_transactional()
+                && EmailQueries::is_opted_out(pool, user_id, template).await?
+            {
+                EmailQueries::log_email(pool, Some(user_id), &payload.to, template, "", EmailStatus::Skipped, None).await?;
+                return Ok(());
+            }
+        }
+
+        let rendered = payload.template.render(&payload.context).map_err(anyhow::Error::msg)?;
+        let message = EmailMessage {
+            to: payload.to.clone(),
+            subject: rendered.subject,
+            body: rendered.body,
+        };
+
+        match self.service.send(&message).await {
+            Ok(()) => {
+                EmailQueries::log_email(pool, payload.user_id, &message.to, template, &message.subject, EmailStatus::Sent, None).await?;
+                Ok(())
+            }
+            Err(e) => {
+                let error = e.to_string();
+                EmailQueries::log_email(pool, payload.user_id, &message.to, template, &message.subject, EmailStatus::Failed, Some(&error)).await?;
+                Err(e)
+            }
+        }
+    }
+}
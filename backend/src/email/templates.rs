@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Email templates use `{{name}}` placeholders filled from a JSON object context
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTemplate {
+    Invite,
+    ProjectInvite,
+    PasswordReset,
+    Mention,
+    DailyDigest,
+    InboundTaskCreated,
+    ProjectReport,
+    AccountInvite,
+}
+
+pub struct RenderedEmail {
+    pub subject: String,
+    pub body: String,
+}
+
+impl EmailTemplate {
+    pub const ALL: &'static [EmailTemplate] = &[
+        EmailTemplate::Invite,
+        EmailTemplate::ProjectInvite,
+        EmailTemplate::PasswordReset,
+        EmailTemplate::Mention,
+        EmailTemplate::DailyDigest,
+        EmailTemplate::InboundTaskCreated,
+        EmailTemplate::ProjectReport,
+        EmailTemplate::AccountInvite,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmailTemplate::Invite => "invite",
+            EmailTemplate::ProjectInvite => "project_invite",
+            EmailTemplate::PasswordReset => "password_reset",
+            EmailTemplate::Mention => "mention",
+            EmailTemplate::DailyDigest => "daily_digest",
+            EmailTemplate::InboundTaskCreated => "inbound_task_created",
+            EmailTemplate::ProjectReport => "project_report",
+            EmailTemplate::AccountInvite => "account_invite",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|template| template.name() == name)
+    }
+
+    // Transactional mail is always delivered regardless of opt-outs
+    pub fn is_transactional(&self) -> bool {
+        matches!(self, EmailTemplate::PasswordReset | EmailTemplate::InboundTaskCreated)
+    }
+
+    fn source(&self) -> (&'static str, &'static str) {
+        match self {
+            EmailTemplate::Invite => (
+                "{{inviter}} invited you to {{team}} on SimpleCards",
+                "Hi {{name}},\n\n{{inviter}} invited you to join the team \"{{team}}\".\n\nAccept the invitation: {{link}}\n",
+            ),
+            EmailTemplate::ProjectInvite => (
+                "{{inviter}} invited you to {{project}} on SimpleCards",
+                "Hi,\n\n{{inviter}} invited you to the project \"{{project}}\" as a {{role}}.\n\nAccept the invitation within {{days}} days: {{link}}\n",
+            ),
+            EmailTemplate::PasswordReset => (
+                "Reset your SimpleCards password",
+                "Hi {{name}},\n\nSomeone requested a password reset for your account. If this was you, use the link below within the next hour:\n\n{{link}}\n\nIf you didn't request this you can ignore this email.\n",
+            ),
+            EmailTemplate::Mention => (
+                "{{author}} mentioned you in \"{{task}}\"",
+                "Hi {{name}},\n\n{{author}} mentioned you in {{place}} \"{{task}}\":\n\n{{excerpt}}\n\nView the task: {{link}}\n",
+            ),
+            EmailTemplate::DailyDigest => (
+                "Your {{period}} SimpleCards digest",
+                "Hi {{name}},\n\nHere is what happened since your last digest:\n\n{{summary}}\n",
+            ),
+            EmailTemplate::InboundTaskCreated => (
+                "Re: {{subject}}",
+                "Your email was added to {{project}} as task #{{number}} \"{{task}}\".\n\nView the task: {{link}}\n",
+            ),
+            EmailTemplate::ProjectReport => (
+                "{{report}}: {{project}}",
+                "Hi {{name}},\n\n{{summary}}\n\nView the project: {{link}}\n",
+            ),
+            EmailTemplate::AccountInvite => (
+                "{{inviter}} created a SimpleCards account for you",
+                "Hi {{name}},\n\n{{inviter}} created a SimpleCards account for you with the username {{username}}.\n\nChoose your password within {{days}} days: {{link}}\n",
+            ),
+        }
+    }
+
+    pub fn render(&self, context: &Value) -> Result<RenderedEmail, String> {
+        let (subject, body) = self.source();
+        Ok(RenderedEmail {
+            subject: render_str(subject, context)?,
+            body: render_str(body, context)?,
+        })
+    }
+}
+
+fn render_str(source: &str, context: &Value) -> Result<String, String> {
+    let mut output = String::with_capacity(source.len());
+    let mut rest = source;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "Unclosed placeholder in template".to_string())?;
+        let key = after[..end].trim();
+
+        match context.get(key) {
+            Some(Value::String(value)) => output.push_str(value),
+            Some(Value::Null) | None => return Err(format!("Missing template variable: {}", key)),
+            Some(value) => output.push_str(&value.to_string()),
+        }
+
+        rest = &after[end + 2..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_mention_template() {
+        let context = json!({
+            "name": "Alice",
+            "author": "Bob",
+            "place": "a comment on",
+            "task": "Fix login",
+            "excerpt": "@alice can you take a look?",
+            "link": "http://localhost:3000/tasks/1",
+        });
+
+        let email = EmailTemplate::Mention.render(&context).unwrap();
+        assert_eq!(email.subject, "Bob mentioned you in \"Fix login\"");
+        assert!(email.body.contains("Bob mentioned you in a comment on \"Fix login\""));
+        assert!(email.body.contains("@alice can you take a look?"));
+    }
+
+    #[test]
+    fn test_render_fails_on_missing_variable() {
+        let context = json!({ "name": "Alice" });
+        assert!(EmailTemplate::PasswordReset.render(&context).is_err());
+    }
+
+    #[test]
+    fn test_template_names_round_trip() {
+        for template in EmailTemplate::ALL {
+            assert_eq!(EmailTemplate::from_name(template.name()), Some(*template));
+        }
+        assert_eq!(EmailTemplate::from_name("unknown"), None);
+    }
+}
@@ -0,0 +1,175 @@
+// Feature flags, for shipping risky features dark and enabling them team by
+// team. A team override beats the flag's instance-wide default and flags that
+// don't exist are off. Handlers read an in-memory snapshot, reloaded every
+// REFRESH_INTERVAL and right after a flag is changed through this instance,
+// so other instances pick up changes within the interval.
+
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::database::{models::FeatureFlag, queries::FeatureFlagQueries};
+use crate::utils::errors::AppError;
+
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlagSnapshot {
+    defaults: HashMap<String, bool>,
+    overrides: HashMap<(String, Uuid), bool>,
+}
+
+impl FlagSnapshot {
+    pub fn from_flags(flags: &[FeatureFlag]) -> Self {
+        let mut snapshot = FlagSnapshot::default();
+        for flag in flags {
+            snapshot.defaults.insert(flag.name.clone(), flag.enabled);
+            for team in &flag.overrides {
+                snapshot.overrides.insert((flag.name.clone(), team.team_id), team.enabled);
+            }
+        }
+        snapshot
+    }
+
+    pub fn enabled(&self, name: &str, team_id: Option<Uuid>) -> bool {
+        team_id
+            .and_then(|team_id| self.overrides.get(&(name.to_string(), team_id)))
+            .or_else(|| self.defaults.get(name))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    // State of every flag for the team, or the defaults without one
+    pub fn evaluate(&self, team_id: Option<Uuid>) -> BTreeMap<String, bool> {
+        self.defaults
+            .keys()
+            .map(|name| (name.clone(), self.enabled(name, team_id)))
+            .collect()
+    }
+}
+
+#[derive(Clone)]
+pub struct Flags {
+    pool: PgPool,
+    snapshot: Arc<RwLock<FlagSnapshot>>,
+}
+
+impl Flags {
+    // Everything is off until the first refresh
+    pub fn new(pool: PgPool) -> Self {
+        Flags {
+            pool,
+            snapshot: Arc::new(RwLock::new(FlagSnapshot::default())),
+        }
+    }
+
+    pub fn enabled(&self, name: &str, team_id: Uuid) -> bool {
+        self.snapshot().enabled(name, Some(team_id))
+    }
+
+    // For handlers behind a flag; the feature looks absent to other teams
+    pub fn require(&self, name: &str, team_id: Uuid) -> Result<(), AppError> {
+        if self.enabled(name, team_id) {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(format!("Feature {} is not enabled for this team", name)))
+        }
+    }
+
+    pub fn evaluate(&self, team_id: Option<Uuid>) -> BTreeMap<String, bool> {
+        self.snapshot().evaluate(team_id)
+    }
+
+    fn snapshot(&self) -> FlagSnapshot {
+        self.snapshot.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    pub async fn refresh(&self) -> Result<(), AppError> {
+        let flags = FeatureFlagQueries::list_flags(&self.pool).await?;
+        let snapshot = FlagSnapshot::from_flags(&flags);
+        *self.snapshot.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = snapshot;
+        Ok(())
+    }
+
+    // Keeps the snapshot current for as long as the server runs
+    pub fn spawn_refresh(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let flags = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = flags.refresh().await {
+                    warn!("Refreshing feature flags failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+// Lowercase snake_case, e.g. "structured_columns"
+pub fn validate_flag_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        return Err(AppError::Validation("Flag names must be lowercase snake_case, at most 64 characters".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::TeamFlagOverride;
+    use chrono::Utc;
+
+    fn flag(name: &str, enabled: bool, overrides: Vec<(Uuid, bool)>) -> FeatureFlag {
+        FeatureFlag {
+            name: name.to_string(),
+            enabled,
+            description: None,
+            overrides: overrides.into_iter().map(|(team_id, enabled)| TeamFlagOverride { team_id, enabled }).collect(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_team_overrides_beat_defaults() {
+        let beta_team = Uuid::new_v4();
+        let cautious_team = Uuid::new_v4();
+        let other_team = Uuid::new_v4();
+        let snapshot = FlagSnapshot::from_flags(&[
+            flag("structured_columns", false, vec![(beta_team, true)]),
+            flag("new_positioning", true, vec![(cautious_team, false)]),
+        ]);
+
+        assert!(snapshot.enabled("structured_columns", Some(beta_team)));
+        assert!(!snapshot.enabled("structured_columns", Some(other_team)));
+        assert!(!snapshot.enabled("structured_columns", None));
+
+        assert!(!snapshot.enabled("new_positioning", Some(cautious_team)));
+        assert!(snapshot.enabled("new_positioning", Some(other_team)));
+
+        assert!(!snapshot.enabled("never_created", Some(beta_team)));
+        assert_eq!(
+            snapshot.evaluate(Some(beta_team)),
+            BTreeMap::from([("new_positioning".to_string(), true), ("structured_columns".to_string(), true)])
+        );
+    }
+
+    #[test]
+    fn test_flag_names() {
+        assert!(validate_flag_name("structured_columns").is_ok());
+        assert!(validate_flag_name("v2_positions").is_ok());
+
+        assert!(validate_flag_name("").is_err());
+        assert!(validate_flag_name("Structured").is_err());
+        assert!(validate_flag_name("2fa").is_err());
+        assert!(validate_flag_name("new-positioning").is_err());
+        assert!(validate_flag_name(&"a".repeat(65)).is_err());
+    }
+}
@@ -0,0 +1,393 @@
+// Inbound email: tasks created from mail sent to a project's inbox address,
+// `project-<token>@<INBOUND_EMAIL_DOMAIN>`.
+//
+// Mailgun posts incoming mail as multipart form data signed with the account's
+// webhook signing key (MAILGUN_WEBHOOK_SIGNING_KEY). SES has no such webhook,
+// so its mail comes from a forwarder (e.g. a Lambda on the receipt rule)
+// posting JSON signed like GitHub webhooks with INBOUND_EMAIL_SECRET.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::env;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::auth::authz;
+use crate::database::{
+    models::{CreateTaskRequest, ProjectIntegration, UserSummary},
+    queries::{IntegrationQueries, ProjectQueries, TaskQueries, TaskReadQueries, UserQueries},
+};
+use crate::email::{queue_email, templates::EmailTemplate};
+use crate::integrations::{app_base_url, slack::{self, blocks::Notification}};
+use crate::utils::errors::AppError;
+use crate::utils::validation;
+use crate::websocket::events::{TaskEventData, WebSocketEvent};
+
+pub const INTEGRATION_KIND: &str = "email";
+const ADDRESS_PREFIX: &str = "project-";
+// Mailgun signatures older than this are rejected to limit replays
+const MAILGUN_MAX_AGE_SECONDS: i64 = 5 * 60;
+const TITLE_MAX_CHARS: usize = 255;
+const DESCRIPTION_MAX_BYTES: usize = 2000;
+
+// What to do with mail from addresses that don't belong to a project member
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownSenderPolicy {
+    #[default]
+    Reject,
+    // Create the task as `inbox_user_id`
+    InboxUser,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailInboxConfig {
+    pub token: String,
+    #[serde(default)]
+    pub unknown_senders: UnknownSenderPolicy,
+    pub inbox_user_id: Option<Uuid>,
+}
+
+impl EmailInboxConfig {
+    pub fn from_integration(integration: &ProjectIntegration) -> Option<Self> {
+        serde_json::from_value(integration.config.clone()).ok()
+    }
+}
+
+pub fn inbound_domain() -> String {
+    env::var("INBOUND_EMAIL_DOMAIN")
+        .unwrap_or_else(|_| "inbox.simplecards.local".to_string())
+        .to_lowercase()
+}
+
+pub fn inbox_address(token: &str) -> String {
+    format!("{}{}@{}", ADDRESS_PREFIX, token, inbound_domain())
+}
+
+pub fn mailgun_signing_key() -> Option<String> {
+    env::var("MAILGUN_WEBHOOK_SIGNING_KEY").ok().filter(|key| !key.is_empty())
+}
+
+pub fn forwarder_secret() -> Option<String> {
+    env::var("INBOUND_EMAIL_SECRET").ok().filter(|secret| !secret.is_empty())
+}
+
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 12];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// Bare address from "Name <user@example.com>" or "user@example.com", lowercased
+pub fn bare_address(value: &str) -> String {
+    let value = value.trim();
+    let address = match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value,
+    };
+    address.trim().to_lowercase()
+}
+
+// Inbox token from the first recipient on our inbound domain
+pub fn token_from_recipients(recipients: &str, domain: &str) -> Option<String> {
+    recipients.split(',').find_map(|recipient| {
+        let address = bare_address(recipient);
+        let (local, recipient_domain) = address.rsplit_once('@')?;
+        if recipient_domain != domain {
+            return None;
+        }
+        let token = local.strip_prefix(ADDRESS_PREFIX)?;
+        (!token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric())).then(|| token.to_string())
+    })
+}
+
+// Mailgun signs `timestamp + token` with HMAC-SHA256
+pub fn verify_mailgun_signature(signing_key: &str, timestamp: &str, token: &str, signature: &str, now: i64) -> bool {
+    let Ok(sent_at) = timestamp.parse::<i64>() else {
+        return false;
+    };
+    if (now - sent_at).abs() > MAILGUN_MAX_AGE_SECONDS {
+        return false;
+    }
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(signing_key.as_bytes()) else {
+        return false;
+    };
+
+    mac.update(timestamp.as_bytes());
+    mac.update(token.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+#[derive(Debug, Clone)]
+pub struct InboundAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InboundEmail {
+    pub recipients: String,
+    pub sender: String,
+    pub subject: String,
+    pub body: String,
+    pub attachments: Vec<InboundAttachment>,
+}
+
+impl InboundEmail {
+    // Text fields of a Mailgun "forward" route post
+    pub fn from_mailgun(fields: &HashMap<String, String>, attachments: Vec<InboundAttachment>) -> Option<Self> {
+        let field = |name: &str| fields.get(name).cloned().unwrap_or_default();
+        let sender = fields.get("sender").or_else(|| fields.get("from"))?;
+
+        Some(InboundEmail {
+            recipients: field("recipient"),
+            sender: bare_address(sender),
+            subject: field("subject"),
+            body: fields
+                .get("stripped-text")
+                .filter(|text| !text.trim().is_empty())
+                .or_else(|| fields.get("body-plain"))
+                .cloned()
+                .unwrap_or_default(),
+            attachments,
+        })
+    }
+
+    // Forwarder payload: {recipient, from, subject, text, attachments: [{filename, content_type, content_base64}]}
+    pub fn from_forwarded_json(payload: &Value) -> Option<Self> {
+        let text = |key: &str| payload[key].as_str().unwrap_or_default().to_string();
+        let sender = payload["from"].as_str()?;
+
+        let attachments = payload["attachments"]
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| {
+                        Some(InboundAttachment {
+                            filename: item["filename"].as_str().unwrap_or("attachment").to_string(),
+                            content_type: item["content_type"].as_str().unwrap_or_default().to_string(),
+                            data: BASE64.decode(item["content_base64"].as_str()?).ok()?,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(InboundEmail {
+            recipients: text("recipient"),
+            sender: bare_address(sender),
+            subject: text("subject"),
+            body: text("text"),
+            attachments,
+        })
+    }
+
+    // Subject becomes the title and the body the description, both cut to the task limits
+    pub fn task_request(&self) -> CreateTaskRequest {
+        let subject: String = self.subject.split_whitespace().collect::<Vec<_>>().join(" ");
+        let title = if subject.chars().count() < 2 {
+            format!("Email from {}", self.sender)
+        } else {
+            subject
+        };
+        let title: String = title.chars().take(TITLE_MAX_CHARS).collect();
+
+        let body = self.body.trim();
+        let description = (!body.is_empty()).then(|| truncate_bytes(body, DESCRIPTION_MAX_BYTES).to_string());
+
+        CreateTaskRequest {
+            title,
+            description,
+            assigned_to: None,
+            priority: None,
+            due_date: None,
+            tags: None,
+            cover_color: None,
+            cover_emoji: None,
+        }
+    }
+}
+
+fn truncate_bytes(text: &str, max: usize) -> &str {
+    if text.len() <= max {
+        return text;
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+// Returned to the provider with a 200 either way, so rejected mail isn't retried
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum InboundOutcome {
+    Created { task_id: Uuid, project_id: Uuid, attachments: usize },
+    Rejected { reason: String },
+}
+
+fn rejected(reason: &str) -> InboundOutcome {
+    InboundOutcome::Rejected { reason: reason.to_string() }
+}
+
+// Finds the project by inbox token, picks the acting user and creates the task
+pub async fn process(app_state: &crate::AppState, email: InboundEmail) -> Result<InboundOutcome, AppError> {
+    let pool = app_state.database.pool();
+
+    let Some(token) = token_from_recipients(&email.recipients, &inbound_domain()) else {
+        return Ok(rejected("No project inbox address among the recipients"));
+    };
+    let integration = IntegrationQueries::find_integrations_by_config(pool, INTEGRATION_KIND, "token", &token)
+        .await?
+        .into_iter()
+        .find(|integration| integration.enabled);
+    let Some((integration, config)) = integration
+        .and_then(|integration| EmailInboxConfig::from_integration(&integration).map(|config| (integration, config)))
+    else {
+        return Ok(rejected("Unknown project inbox"));
+    };
+    let project_id = integration.project_id;
+
+    // Senders are matched to project members by email address
+    let member = match UserQueries::get_user_by_email(pool, &email.sender).await {
+        Ok(user) if ProjectQueries::is_project_member(pool, project_id, user.id).await? => Some(user),
+        _ => None,
+    };
+    let actor_id = match (&member, config.unknown_senders, config.inbox_user_id) {
+        (Some(user), _, _) => user.id,
+        (None, UnknownSenderPolicy::InboxUser, Some(inbox_user_id)) => inbox_user_id,
+        _ => return Ok(rejected("Sender is not a project member")),
+    };
+
+    if authz::require_project_role(pool, project_id, actor_id, authz::Permission::CreateTasks).await.is_err() {
+        return Ok(rejected("Project does not accept new tasks"));
+    }
+
+    let project = ProjectQueries::get_project_by_id(pool, project_id).await?;
+    crate::quotas::check_can_create_task(pool, project.team_id, project_id).await?;
+
+    let mut request = email.task_request();
+    validation::validate_create_task(&mut request)?;
+    let task = TaskQueries::create_task(pool, project_id, &request, actor_id).await?;
+    TaskReadQueries::mark_task_read(pool, actor_id, task.id).await?;
+
+    let mut attachments = 0;
+    for attachment in &email.attachments {
+        match crate::attachments::store_attachment(app_state, &task, Some(actor_id), &attachment.filename, &attachment.content_type, &attachment.data).await {
+            Ok(_) => attachments += 1,
+            Err(e) => warn!("Failed to attach {} to task {}: {}", attachment.filename, task.id, e),
+        }
+    }
+
+    let actor: UserSummary = UserQueries::get_user_by_id(pool, actor_id).await?.into();
+    slack::notify(pool, project_id, &actor, Notification::TaskCreated { task: &task }).await;
+    let event = WebSocketEvent::TaskCreated(TaskEventData {
+        task: task.clone(),
+        project_id,
+        user: actor,
+        column: None,
+        client_mutation_id: None,
+    });
+    app_state.websocket.broadcast_to_project(project_id, event, None).await;
+
+    // Reply to the sender with a link to the new task
+    let context = json!({
+        "subject": email.subject,
+        "project": project.name,
+        "number": task.number,
+        "task": task.title,
+        "link": format!("{}/projects/{}/tasks/{}", app_base_url(), project_id, task.id),
+    });
+    if let Err(e) = queue_email(pool, &email.sender, member.map(|user| user.id), EmailTemplate::InboundTaskCreated, context).await {
+        warn!("Failed to queue inbound email reply for task {}: {}", task.id, e);
+    }
+
+    Ok(InboundOutcome::Created { task_id: task.id, project_id, attachments })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mailgun_sign(key: &str, timestamp: &str, token: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).unwrap();
+        mac.update(timestamp.as_bytes());
+        mac.update(token.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn test_mailgun_signature() {
+        let signature = mailgun_sign("key-123", "1700000000", "abc");
+
+        assert!(verify_mailgun_signature("key-123", "1700000000", "abc", &signature, 1700000060));
+        assert!(!verify_mailgun_signature("other-key", "1700000000", "abc", &signature, 1700000060));
+        assert!(!verify_mailgun_signature("key-123", "1700000000", "abd", &signature, 1700000060));
+        // Too old
+        assert!(!verify_mailgun_signature("key-123", "1700000000", "abc", &signature, 1700003600));
+    }
+
+    #[test]
+    fn test_token_from_recipients() {
+        let domain = "inbox.example.com";
+
+        assert_eq!(
+            token_from_recipients("Support <Project-a1B2@Inbox.Example.com>", domain),
+            Some("a1b2".to_string())
+        );
+        assert_eq!(
+            token_from_recipients("team@example.com, project-ff00@inbox.example.com", domain),
+            Some("ff00".to_string())
+        );
+        assert_eq!(token_from_recipients("project-ff00@elsewhere.com", domain), None);
+        assert_eq!(token_from_recipients("project-@inbox.example.com", domain), None);
+        assert_eq!(token_from_recipients("project-x%y@inbox.example.com", domain), None);
+    }
+
+    #[test]
+    fn test_task_request_from_email() {
+        let email = InboundEmail {
+            recipients: String::new(),
+            sender: "customer@example.com".to_string(),
+            subject: "  Login   broken\n on mobile ".to_string(),
+            body: format!("{}é", "a".repeat(1999)),
+            attachments: vec![],
+        };
+
+        let request = email.task_request();
+        assert_eq!(request.title, "Login broken on mobile");
+        assert_eq!(request.description.as_deref().map(str::len), Some(1999));
+
+        let empty = InboundEmail { subject: String::new(), body: "  ".to_string(), ..email };
+        let request = empty.task_request();
+        assert_eq!(request.title, "Email from customer@example.com");
+        assert_eq!(request.description, None);
+    }
+
+    #[test]
+    fn test_forwarded_json() {
+        let payload = json!({
+            "recipient": "project-abc@inbox.simplecards.local",
+            "from": "Bob <BOB@example.com>",
+            "subject": "Invoice",
+            "text": "See attached",
+            "attachments": [{ "filename": "invoice.txt", "content_type": "text/plain", "content_base64": "aGVsbG8=" }]
+        });
+
+        let email = InboundEmail::from_forwarded_json(&payload).unwrap();
+        assert_eq!(email.sender, "bob@example.com");
+        assert_eq!(email.attachments.len(), 1);
+        assert_eq!(email.attachments[0].data, b"hello");
+    }
+}
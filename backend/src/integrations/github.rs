@@ -0,0 +1,314 @@
+// GitHub webhooks: link commits and pull requests to the tasks they reference
+// (e.g. "SC-142" in a commit message or PR title) and apply status rules.
+
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::database::{
+    models::{TaskLinkKind, TaskStatus},
+    queries::{TaskLinkQueries, TaskQueries},
+};
+use crate::utils::errors::AppError;
+
+pub const INTEGRATION_KIND: &str = "github";
+pub const DEFAULT_TASK_KEY: &str = "SC";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GithubConfig {
+    pub repo: String,
+    pub webhook_secret: String,
+    pub task_key: String,
+    #[serde(default)]
+    pub transitions: TransitionRules,
+}
+
+// Status a referenced task moves to when a pull request is opened or merged.
+// Tasks only ever move forward, so a Done task is not reopened by a new PR.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransitionRules {
+    pub pull_request_opened: Option<TaskStatus>,
+    pub pull_request_merged: Option<TaskStatus>,
+}
+
+impl Default for TransitionRules {
+    fn default() -> Self {
+        TransitionRules {
+            pull_request_opened: Some(TaskStatus::Review),
+            pull_request_merged: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct WebhookOutcome {
+    pub linked: usize,
+    pub transitioned: usize,
+}
+
+// Checks an `X-Hub-Signature-256: sha256=<hex>` header against the raw body.
+// The comparison is constant-time (Mac::verify_slice).
+pub fn verify_signature(secret: &str, body: &[u8], header: Option<&str>) -> bool {
+    let Some(signature) = header.and_then(|value| value.strip_prefix("sha256=")) else {
+        return false;
+    };
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+// Task numbers referenced as `<KEY>-<number>` (case-insensitive), deduplicated
+pub fn extract_task_numbers(text: &str, task_key: &str) -> Vec<i32> {
+    let pattern = format!(r"(?i)\b{}-(\d+)\b", regex::escape(task_key));
+    let Ok(regex) = Regex::new(&pattern) else {
+        return Vec::new();
+    };
+
+    let mut numbers: Vec<i32> = Vec::new();
+    for captures in regex.captures_iter(text) {
+        if let Ok(number) = captures[1].parse::<i32>() {
+            if !numbers.contains(&number) {
+                numbers.push(number);
+            }
+        }
+    }
+    numbers
+}
+
+fn status_rank(status: TaskStatus) -> u8 {
+    match status {
+        TaskStatus::Todo => 0,
+        TaskStatus::InProgress => 1,
+        TaskStatus::Review => 2,
+        TaskStatus::Done => 3,
+    }
+}
+
+pub fn should_transition(current: TaskStatus, target: TaskStatus) -> bool {
+    status_rank(target) > status_rank(current)
+}
+
+struct LinkedItem {
+    kind: TaskLinkKind,
+    external_id: String,
+    url: String,
+    title: String,
+    author: Option<String>,
+    state: Option<String>,
+    // Text searched for task references
+    text: String,
+    transition: Option<TaskStatus>,
+}
+
+fn str_field(value: &Value, pointer: &str) -> Option<String> {
+    value.pointer(pointer).and_then(Value::as_str).map(str::to_string)
+}
+
+fn parse_push(payload: &Value) -> Vec<LinkedItem> {
+    let Some(commits) = payload["commits"].as_array() else {
+        return Vec::new();
+    };
+
+    commits
+        .iter()
+        .filter_map(|commit| {
+            let sha = str_field(commit, "/id")?;
+            let message = str_field(commit, "/message").unwrap_or_default();
+            Some(LinkedItem {
+                kind: TaskLinkKind::Commit,
+                url: str_field(commit, "/url").unwrap_or_default(),
+                title: message.lines().next().unwrap_or_default().to_string(),
+                author: str_field(commit, "/author/username").or_else(|| str_field(commit, "/author/name")),
+                state: None,
+                external_id: sha,
+                text: message,
+                transition: None,
+            })
+        })
+        .collect()
+}
+
+fn parse_pull_request(payload: &Value, repo: &str, rules: &TransitionRules) -> Vec<LinkedItem> {
+    let pr = &payload["pull_request"];
+    let Some(number) = pr["number"].as_i64() else {
+        return Vec::new();
+    };
+
+    let action = payload["action"].as_str().unwrap_or_default();
+    let merged = pr["merged"].as_bool().unwrap_or(false);
+    let state = if merged {
+        "merged".to_string()
+    } else {
+        pr["state"].as_str().unwrap_or("open").to_string()
+    };
+    let transition = match action {
+        "opened" | "reopened" | "ready_for_review" => rules.pull_request_opened,
+        "closed" if merged => rules.pull_request_merged,
+        _ => None,
+    };
+
+    let title = str_field(pr, "/title").unwrap_or_default();
+    let text = [
+        title.clone(),
+        str_field(pr, "/body").unwrap_or_default(),
+        str_field(pr, "/head/ref").unwrap_or_default(),
+    ]
+    .join("\n");
+
+    vec![LinkedItem {
+        kind: TaskLinkKind::PullRequest,
+        external_id: format!("{}#{}", repo, number),
+        url: str_field(pr, "/html_url").unwrap_or_default(),
+        title,
+        author: str_field(pr, "/user/login"),
+        state: Some(state),
+        text,
+        transition,
+    }]
+}
+
+// Stores links for every task referenced by a push or pull_request event and
+// applies the configured status transitions. Unknown event types are ignored.
+pub async fn process_event(
+    pool: &PgPool,
+    project_id: Uuid,
+    config: &GithubConfig,
+    event: &str,
+    payload: &Value,
+) -> Result<WebhookOutcome, AppError> {
+    let items = match event {
+        "push" => parse_push(payload),
+        "pull_request" => parse_pull_request(payload, &config.repo, &config.transitions),
+        _ => Vec::new(),
+    };
+
+    let mut outcome = WebhookOutcome::default();
+    for item in items {
+        for number in extract_task_numbers(&item.text, &config.task_key) {
+            // References to tasks that don't exist in this project are skipped
+            let Some(task) = TaskQueries::get_task_by_number(pool, project_id, number).await? else {
+                continue;
+            };
+
+            TaskLinkQueries::upsert_link(
+                pool,
+                task.id,
+                item.kind,
+                &item.external_id,
+                &item.url,
+                &item.title,
+                item.author.as_deref(),
+                item.state.as_deref(),
+            ).await?;
+            outcome.linked += 1;
+
+            if let Some(target) = item.transition {
+                if should_transition(task.status, target) {
+                    TaskQueries::set_task_status(pool, task.id, target).await?;
+                    outcome.transitioned += 1;
+                }
+            }
+        }
+    }
+
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn test_signature_verification() {
+        let body = br#"{"zen": "Keep it logically awesome."}"#;
+        let header = sign("It's a Secret to Everybody", body);
+
+        assert!(verify_signature("It's a Secret to Everybody", body, Some(&header)));
+        assert!(!verify_signature("wrong secret", body, Some(&header)));
+        assert!(!verify_signature("It's a Secret to Everybody", b"tampered", Some(&header)));
+        assert!(!verify_signature("It's a Secret to Everybody", body, Some(header.trim_start_matches("sha256="))));
+        assert!(!verify_signature("It's a Secret to Everybody", body, Some("sha256=not-hex")));
+        assert!(!verify_signature("It's a Secret to Everybody", body, None));
+    }
+
+    #[test]
+    fn test_extract_task_numbers() {
+        assert_eq!(extract_task_numbers("SC-142: fix login", "SC"), vec![142]);
+        assert_eq!(extract_task_numbers("Fixes sc-7 and SC-12, see SC-7", "SC"), vec![7, 12]);
+        assert_eq!(extract_task_numbers("feature/SC-3-search", "SC"), vec![3]);
+        assert!(extract_task_numbers("DESC-42 and SC42", "SC").is_empty());
+    }
+
+    #[test]
+    fn test_transitions_only_move_forward() {
+        assert!(should_transition(TaskStatus::Todo, TaskStatus::Review));
+        assert!(should_transition(TaskStatus::Review, TaskStatus::Done));
+        assert!(!should_transition(TaskStatus::Done, TaskStatus::Review));
+        assert!(!should_transition(TaskStatus::Review, TaskStatus::Review));
+    }
+
+    #[test]
+    fn test_parse_pull_request_events() {
+        let rules = TransitionRules {
+            pull_request_opened: Some(TaskStatus::Review),
+            pull_request_merged: Some(TaskStatus::Done),
+        };
+        let payload = json!({
+            "action": "closed",
+            "pull_request": {
+                "number": 17,
+                "title": "Add search",
+                "body": "Closes SC-5",
+                "state": "closed",
+                "merged": true,
+                "html_url": "https://github.com/acme/cards/pull/17",
+                "user": { "login": "octocat" },
+                "head": { "ref": "feature/SC-6" }
+            }
+        });
+
+        let items = parse_pull_request(&payload, "acme/cards", &rules);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].external_id, "acme/cards#17");
+        assert_eq!(items[0].state.as_deref(), Some("merged"));
+        assert_eq!(items[0].transition, Some(TaskStatus::Done));
+        assert_eq!(extract_task_numbers(&items[0].text, "SC"), vec![5, 6]);
+    }
+
+    #[test]
+    fn test_parse_push_event() {
+        let payload = json!({
+            "commits": [
+                {
+                    "id": "a1b2c3",
+                    "message": "SC-1 wire up login\n\nAlso touches SC-2",
+                    "url": "https://github.com/acme/cards/commit/a1b2c3",
+                    "author": { "name": "Mona", "username": "mona" }
+                }
+            ]
+        });
+
+        let items = parse_push(&payload);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].title, "SC-1 wire up login");
+        assert_eq!(items[0].author.as_deref(), Some("mona"));
+        assert_eq!(extract_task_numbers(&items[0].text, "SC"), vec![1, 2]);
+    }
+}
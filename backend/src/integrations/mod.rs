@@ -0,0 +1,14 @@
+// Third party integrations configured per project (stored in `project_integrations`)
+pub mod email_inbox;
+pub mod github;
+pub mod slack;
+
+use std::env;
+
+// Base URL of the web client, used to build links back into the app
+pub fn app_base_url() -> String {
+    env::var("APP_BASE_URL")
+        .unwrap_or_else(|_| "http://localhost:3000".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
@@ -0,0 +1,351 @@
+use serde_json::{json, Value};
+
+use crate::database::models::{Project, ProjectRole, Task, TaskComment, UserSummary};
+
+// Block Kit payloads for Slack incoming webhooks. Each message carries a
+// plain `text` fallback (used in notifications) plus the richer `blocks`.
+
+const EXCERPT_LENGTH: usize = 200;
+const SECTION_TEXT_LIMIT: usize = 3000;
+
+pub enum Notification<'a> {
+    TaskCreated { task: &'a Task },
+    TaskCompleted { task: &'a Task },
+    CommentAdded { task: &'a Task, comment: &'a TaskComment },
+    MemberJoined { member: &'a UserSummary, role: &'a ProjectRole },
+    Test,
+}
+
+pub fn format_message(project: &Project, actor: &UserSummary, notification: &Notification, base_url: &str) -> Value {
+    let project_name = escape(&project.name);
+    let actor_name = escape(&actor.display_name);
+
+    let (fallback, headline, detail) = match notification {
+        Notification::TaskCreated { task } => (
+            format!("{} created \"{}\" in {}", actor.display_name, task.title, project.name),
+            format!(":new: *{}* created a task in *{}*", actor_name, project_name),
+            Some(task_link(base_url, task)),
+        ),
+        Notification::TaskCompleted { task } => (
+            format!("{} completed \"{}\" in {}", actor.display_name, task.title, project.name),
+            format!(":white_check_mark: *{}* completed a task in *{}*", actor_name, project_name),
+            Some(task_link(base_url, task)),
+        ),
+        Notification::CommentAdded { task, comment } => (
+            format!("{} commented on \"{}\" in {}", actor.display_name, task.title, project.name),
+            format!(":speech_balloon: *{}* commented on {}", actor_name, task_link(base_url, task)),
+            Some(format!(">{}", escape(&excerpt(&comment.content)).replace('\n', "\n>"))),
+        ),
+        Notification::MemberJoined { member, role } => (
+            format!("{} joined {}", member.display_name, project.name),
+            format!(
+                ":wave: *{}* joined *{}* as {}",
+                escape(&member.display_name),
+                project_name,
+                role_name(role)
+            ),
+            None,
+        ),
+        Notification::Test => (
+            format!("SimpleCards is connected to {}", project.name),
+            format!(":electric_plug: SimpleCards is now posting updates from *{}*", project_name),
+            None,
+        ),
+    };
+
+    let mut blocks = vec![section(&headline)];
+    if let Some(detail) = detail {
+        blocks.push(section(&detail));
+    }
+    blocks.push(json!({
+        "type": "context",
+        "elements": [
+            { "type": "mrkdwn", "text": format!("<{}/projects/{}|{}> · by {}", base_url, project.id, project_name, actor_name) }
+        ]
+    }));
+
+    json!({
+        "text": fallback,
+        "blocks": blocks,
+    })
+}
+
+// A scheduled report (see reports.rs). Each markdown paragraph becomes its own
+// section, which keeps them under Slack's per-section length limit.
+pub fn format_report(project: &Project, title: &str, markdown: &str, base_url: &str) -> Value {
+    let mut blocks: Vec<Value> = markdown
+        .split("\n\n")
+        .filter(|paragraph| !paragraph.trim().is_empty())
+        .map(|paragraph| section(&truncate(&markdown_to_mrkdwn(paragraph), SECTION_TEXT_LIMIT)))
+        .collect();
+    blocks.push(json!({
+        "type": "context",
+        "elements": [
+            { "type": "mrkdwn", "text": format!("<{}/projects/{}|{}>", base_url, project.id, escape(&project.name)) }
+        ]
+    }));
+
+    json!({
+        "text": format!("{}: {}", title, project.name),
+        "blocks": blocks,
+    })
+}
+
+// Just what reports.rs produces: headings, _emphasis_, [links](url) and
+// backslash escapes
+fn markdown_to_mrkdwn(markdown: &str) -> String {
+    markdown
+        .lines()
+        .map(|line| {
+            let heading = line.trim_start_matches('#');
+            if heading.len() < line.len() && heading.starts_with(' ') {
+                format!("*{}*", inline_mrkdwn(heading.trim()))
+            } else {
+                inline_mrkdwn(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn inline_mrkdwn(text: &str) -> String {
+    let mut output = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    output.push_str(&escape(&escaped.to_string()));
+                }
+            }
+            '[' => {
+                let mut label = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => label.extend(chars.next()),
+                        ']' => break,
+                        c => label.push(c),
+                    }
+                }
+                if chars.peek() == Some(&'(') {
+                    chars.next();
+                    let url: String = chars.by_ref().take_while(|c| *c != ')').collect();
+                    output.push_str(&format!("<{}|{}>", url, escape(&label)));
+                } else {
+                    output.push_str(&escape(&format!("[{}]", label)));
+                }
+            }
+            c => output.push_str(&escape(&c.to_string())),
+        }
+    }
+    output
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let truncated: String = text.chars().take(max - 1).collect();
+    format!("{}…", truncated)
+}
+
+fn section(text: &str) -> Value {
+    json!({
+        "type": "section",
+        "text": { "type": "mrkdwn", "text": text }
+    })
+}
+
+fn task_link(base_url: &str, task: &Task) -> String {
+    format!("<{}/projects/{}/tasks/{}|{}>", base_url, task.project_id, task.id, escape(&task.title))
+}
+
+fn role_name(role: &ProjectRole) -> &'static str {
+    match role {
+        ProjectRole::Admin => "admin",
+        ProjectRole::Member => "member",
+        ProjectRole::Editor => "editor",
+        ProjectRole::Guest => "guest",
+        ProjectRole::Viewer => "viewer",
+    }
+}
+
+fn excerpt(content: &str) -> String {
+    if content.chars().count() <= EXCERPT_LENGTH {
+        return content.to_string();
+    }
+    let truncated: String = content.chars().take(EXCERPT_LENGTH).collect();
+    format!("{}…", truncated.trim_end())
+}
+
+// Slack treats &, < and > as control characters in mrkdwn text
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::{TaskPriority, TaskStatus};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    const BASE_URL: &str = "https://cards.example.com";
+
+    fn project() -> Project {
+        Project {
+            id: Uuid::new_v4(),
+            name: "Website <Relaunch>".to_string(),
+            description: None,
+            team_id: Uuid::new_v4(),
+            created_by: Uuid::new_v4(),
+            color: None,
+            contrast_text: None,
+            icon: None,
+            is_active: true,
+            archived_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn user(name: &str) -> UserSummary {
+        UserSummary {
+            id: Uuid::new_v4(),
+            username: name.to_lowercase(),
+            display_name: name.to_string(),
+            avatar_url: None,
+        }
+    }
+
+    fn task(project: &Project) -> Task {
+        Task {
+            id: Uuid::new_v4(),
+            title: "Fix login & signup".to_string(),
+            description: None,
+            project_id: project.id,
+            created_by: Uuid::new_v4(),
+            assigned_to: None,
+            status: TaskStatus::Todo,
+            priority: TaskPriority::Medium,
+            due_date: None,
+            is_all_day: false,
+            tags: None,
+            cover_color: None,
+            cover_emoji: None,
+            position: 1,
+            number: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn block_texts(message: &Value) -> Vec<String> {
+        message["blocks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|block| match block["type"].as_str().unwrap() {
+                "section" => block["text"]["text"].as_str().unwrap().to_string(),
+                "context" => block["elements"][0]["text"].as_str().unwrap().to_string(),
+                other => panic!("unexpected block type {}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_task_created_message_structure() {
+        let project = project();
+        let task = task(&project);
+        let message = format_message(&project, &user("Alice"), &Notification::TaskCreated { task: &task }, BASE_URL);
+
+        assert_eq!(message["text"], "Alice created \"Fix login & signup\" in Website <Relaunch>");
+
+        let blocks = message["blocks"].as_array().unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0]["type"], "section");
+        assert_eq!(blocks[0]["text"]["type"], "mrkdwn");
+        assert_eq!(blocks[2]["type"], "context");
+
+        let texts = block_texts(&message);
+        assert!(texts[0].contains("*Alice* created a task"));
+        assert_eq!(
+            texts[1],
+            format!("<{}/projects/{}/tasks/{}|Fix login &amp; signup>", BASE_URL, project.id, task.id)
+        );
+    }
+
+    #[test]
+    fn test_completed_and_member_joined_messages() {
+        let project = project();
+        let task = task(&project);
+
+        let completed = format_message(&project, &user("Bob"), &Notification::TaskCompleted { task: &task }, BASE_URL);
+        assert!(block_texts(&completed)[0].starts_with(":white_check_mark: *Bob* completed"));
+
+        let carol = user("Carol");
+        let joined = format_message(
+            &project,
+            &user("Alice"),
+            &Notification::MemberJoined { member: &carol, role: &ProjectRole::Editor },
+            BASE_URL,
+        );
+        let texts = block_texts(&joined);
+        assert_eq!(texts.len(), 2);
+        assert!(texts[0].contains("*Carol* joined *Website &lt;Relaunch&gt;* as editor"));
+    }
+
+    #[test]
+    fn test_comment_excerpt_is_quoted_and_truncated() {
+        let project = project();
+        let task = task(&project);
+        let comment = TaskComment {
+            id: Uuid::new_v4(),
+            task_id: task.id,
+            user_id: Uuid::new_v4(),
+            content: format!("Looks good\n{}", "x".repeat(400)),
+            is_system: false,
+            pinned: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+
+        let message = format_message(
+            &project,
+            &user("Dave"),
+            &Notification::CommentAdded { task: &task, comment: &comment },
+            BASE_URL,
+        );
+        let quote = &block_texts(&message)[1];
+
+        assert!(quote.starts_with(">Looks good\n>x"));
+        assert!(quote.ends_with('…'));
+        assert!(quote.chars().count() < 210);
+    }
+
+    #[test]
+    fn test_context_links_back_to_project() {
+        let project = project();
+        let message = format_message(&project, &user("Erin"), &Notification::Test, BASE_URL);
+        let texts = block_texts(&message);
+
+        assert_eq!(
+            texts.last().unwrap(),
+            &format!("<{}/projects/{}|Website &lt;Relaunch&gt;> · by Erin", BASE_URL, project.id)
+        );
+    }
+
+    #[test]
+    fn test_report_markdown_becomes_mrkdwn() {
+        let project = project();
+        let markdown = "## Weekly: Website\n\n### Completed (1)\n- [#4 Fix \\[a\\] & b](https://x/t/1)\n\n### Per member\n- Alice\\_A: 1 completed";
+        let message = format_report(&project, "Weekly", markdown, BASE_URL);
+        let texts = block_texts(&message);
+
+        assert_eq!(message["text"], "Weekly: Website <Relaunch>");
+        assert_eq!(texts.len(), 4);
+        assert_eq!(texts[0], "*Weekly: Website*");
+        assert_eq!(texts[1], "*Completed (1)*\n- <https://x/t/1|#4 Fix [a] &amp; b>");
+        assert_eq!(texts[2], "*Per member*\n- Alice_A: 1 completed");
+    }
+}
@@ -0,0 +1,187 @@
+// Slack incoming-webhook notifications: config helpers and the delivery job.
+// A project's messages are delivered in the order they were queued (see
+// jobs::enqueue_ordered), so a retry never posts "completed" before "created".
+pub mod blocks;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::database::{
+    models::{Job, ProjectIntegration, UserSummary},
+    queries::{IntegrationQueries, JobQueries, ProjectQueries},
+};
+use crate::jobs::{self, JobHandler};
+use crate::utils::errors::AppError;
+use blocks::Notification;
+
+pub const INTEGRATION_KIND: &str = "slack";
+pub const SLACK_WEBHOOK_JOB: &str = "slack_webhook";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SlackEvent {
+    TaskCreated,
+    TaskCompleted,
+    CommentAdded,
+    MemberJoined,
+}
+
+impl SlackEvent {
+    pub const ALL: [SlackEvent; 4] = [
+        SlackEvent::TaskCreated,
+        SlackEvent::TaskCompleted,
+        SlackEvent::CommentAdded,
+        SlackEvent::MemberJoined,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SlackEvent::TaskCreated => "task_created",
+            SlackEvent::TaskCompleted => "task_completed",
+            SlackEvent::CommentAdded => "comment_added",
+            SlackEvent::MemberJoined => "member_joined",
+        }
+    }
+}
+
+impl Notification<'_> {
+    pub fn event(&self) -> Option<SlackEvent> {
+        match self {
+            Notification::TaskCreated { .. } => Some(SlackEvent::TaskCreated),
+            Notification::TaskCompleted { .. } => Some(SlackEvent::TaskCompleted),
+            Notification::CommentAdded { .. } => Some(SlackEvent::CommentAdded),
+            Notification::MemberJoined { .. } => Some(SlackEvent::MemberJoined),
+            Notification::Test => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlackConfig {
+    pub webhook_url: String,
+}
+
+impl SlackConfig {
+    pub fn from_integration(integration: &ProjectIntegration) -> Option<Self> {
+        serde_json::from_value(integration.config.clone()).ok()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlackWebhookPayload {
+    pub project_id: Uuid,
+    pub message: serde_json::Value,
+    // Test messages are delivered even while the integration is disabled
+    #[serde(default)]
+    pub test: bool,
+}
+
+// Queue a Slack message for the project if it has an enabled integration
+// subscribed to this event. Failures are logged and never fail the request.
+pub async fn notify(pool: &PgPool, project_id: Uuid, actor: &UserSummary, notification: Notification<'_>) {
+    if let Err(e) = try_notify(pool, project_id, actor, &notification).await {
+        warn!("Failed to queue Slack notification for project {}: {}", project_id, e);
+    }
+}
+
+async fn try_notify(
+    pool: &PgPool,
+    project_id: Uuid,
+    actor: &UserSummary,
+    notification: &Notification<'_>,
+) -> Result<(), AppError> {
+    let Some(integration) = IntegrationQueries::get_integration(pool, project_id, INTEGRATION_KIND).await? else {
+        return Ok(());
+    };
+
+    let subscribed = notification
+        .event()
+        .map(|event| integration.events.iter().any(|name| name == event.name()))
+        .unwrap_or(false);
+    if !integration.enabled || !subscribed {
+        return Ok(());
+    }
+
+    queue_message(pool, project_id, actor, notification, false).await?;
+    Ok(())
+}
+
+pub async fn queue_message(
+    pool: &PgPool,
+    project_id: Uuid,
+    actor: &UserSummary,
+    notification: &Notification<'_>,
+    test: bool,
+) -> Result<Job, AppError> {
+    let project = ProjectQueries::get_project_by_id(pool, project_id).await?;
+    let message = blocks::format_message(&project, actor, notification, &super::app_base_url());
+    let payload = SlackWebhookPayload { project_id, message, test };
+
+    // Test messages skip the queue, so they can check a fix while it's halted
+    if test {
+        jobs::enqueue(pool, SLACK_WEBHOOK_JOB, &payload).await
+    } else {
+        jobs::enqueue_ordered(pool, SLACK_WEBHOOK_JOB, &ordering_key(project_id), &payload).await
+    }
+}
+
+pub fn ordering_key(project_id: Uuid) -> String {
+    format!("{}:{}", SLACK_WEBHOOK_JOB, project_id)
+}
+
+// Messages that failed for good halt the project's queue; this queues them
+// again once the integration changes, e.g. gets a working webhook URL.
+// Without an integration they are dropped in order.
+pub async fn resume_deliveries(pool: &PgPool, project_id: Uuid) -> Result<(), AppError> {
+    let resumed = JobQueries::resume_ordered(pool, &ordering_key(project_id)).await?;
+    if resumed > 0 {
+        info!("Resumed {} halted Slack deliveries for project {}", resumed, project_id);
+    }
+    Ok(())
+}
+
+pub struct SlackWebhookJob {
+    pool: PgPool,
+    client: reqwest::Client,
+}
+
+impl SlackWebhookJob {
+    pub fn new(pool: PgPool) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+
+        Ok(Self { pool, client })
+    }
+}
+
+#[async_trait]
+impl JobHandler for SlackWebhookJob {
+    async fn run(&self, job: &Job) -> anyhow::Result<()> {
+        let payload: SlackWebhookPayload = serde_json::from_value(job.payload.clone())?;
+
+        // The webhook URL is looked up at delivery time, so removing or disabling
+        // the integration also stops messages that are already queued
+        let Some(integration) = IntegrationQueries::get_integration(&self.pool, payload.project_id, INTEGRATION_KIND).await? else {
+            return Ok(());
+        };
+        if !integration.enabled && !payload.test {
+            return Ok(());
+        }
+        let config = SlackConfig::from_integration(&integration)
+            .ok_or_else(|| anyhow::anyhow!("Slack integration for project {} has no webhook URL", payload.project_id))?;
+
+        let response = self.client.post(&config.webhook_url).json(&payload.message).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("Slack webhook responded with {}: {}", status, body);
+        }
+
+        Ok(())
+    }
+}
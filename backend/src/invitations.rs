@@ -0,0 +1,114 @@
+// Project invitations for people outside the project's team, such as
+// contractors. Project admins invite an email address as guest or viewer and
+// the invitee gets a link with the token. Whoever holds the token joins the
+// project with that role, either when registering (the token stands in for a
+// signup code) or with an existing account. Members outside the team are only
+// ever guests or viewers (see authz::check_outside_team_role), and team
+// endpoints stay closed to them since they hold no team role.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::auth::tokens;
+use crate::database::models::{Project, ProjectInvitation, ProjectMember, User, UserSummary};
+use crate::database::queries::{ProjectInvitationQueries, UserQueries};
+use crate::email::{queue_email, templates::EmailTemplate};
+use crate::integrations::{app_base_url, slack::{self, blocks::Notification}};
+use crate::utils::errors::AppError;
+use crate::websocket::events::{InvitationNotificationData, MemberAddedEventData, WebSocketEvent};
+
+pub const INVITATION_EXPIRY_DAYS: i64 = 7;
+
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn expires_at(now: DateTime<Utc>) -> DateTime<Utc> {
+    now + Duration::days(INVITATION_EXPIRY_DAYS)
+}
+
+// The pending invitation a token belongs to, if it can still be accepted
+pub async fn find(pool: &PgPool, token: &str) -> Result<Option<ProjectInvitation>, AppError> {
+    ProjectInvitationQueries::get_valid_invitation(pool, &tokens::hash_token(token.trim())).await
+}
+
+pub async fn send(
+    pool: &PgPool,
+    invitation: &ProjectInvitation,
+    project: &Project,
+    inviter: &User,
+    token: &str,
+) -> Result<(), AppError> {
+    queue_email(
+        pool,
+        &invitation.email,
+        None,
+        EmailTemplate::ProjectInvite,
+        json!({
+            "inviter": inviter.display_name,
+            "project": project.name,
+            "role": format!("{:?}", invitation.role).to_lowercase(),
+            "days": INVITATION_EXPIRY_DAYS,
+            "link": format!("{}/invitations/{}", app_base_url(), token),
+        }),
+    ).await
+}
+
+// Lets the invitee know right away when the address belongs to an account.
+// Failures are logged, the email is what counts.
+pub async fn notify_invitee(app_state: &crate::AppState, invitation: &ProjectInvitation, project: &Project, inviter: &User) {
+    let invitee = match UserQueries::get_user_by_email(app_state.database.pool(), &invitation.email).await {
+        Ok(invitee) => invitee,
+        Err(AppError::NotFound(_)) => return,
+        Err(e) => {
+            warn!("Failed to look up the invitee of invitation {}: {}", invitation.id, e);
+            return;
+        }
+    };
+
+    let event = WebSocketEvent::InvitedToProject(InvitationNotificationData {
+        invitation_id: invitation.id,
+        project_id: project.id,
+        project_name: project.name.clone(),
+        role: invitation.role.clone(),
+        expires_at: invitation.expires_at,
+        user: inviter.clone().into(),
+    });
+    app_state.websocket.send_to_user(invitee.id, event).await;
+}
+
+// Adds the user to the invitation's project and tells its members, as adding
+// them by hand would. Guests get no onboarding tasks, they couldn't work on them.
+pub async fn accept(app_state: &crate::AppState, token: &str, user_id: uuid::Uuid) -> Result<ProjectMember, AppError> {
+    let pool = app_state.database.pool();
+    let (invitation, member) =
+        ProjectInvitationQueries::accept_invitation(pool, &tokens::hash_token(token.trim()), user_id).await?;
+
+    let joined: UserSummary = UserQueries::get_user_by_id(pool, user_id).await?.into();
+    let inviter = match invitation.invited_by {
+        Some(invited_by) => UserQueries::get_user_by_id(pool, invited_by).await?.into(),
+        None => joined.clone(),
+    };
+    slack::notify(
+        pool,
+        invitation.project_id,
+        &inviter,
+        Notification::MemberJoined { member: &joined, role: &invitation.role },
+    ).await;
+
+    let event = WebSocketEvent::MemberAdded(MemberAddedEventData {
+        project_id: invitation.project_id,
+        member: joined,
+        role: invitation.role,
+        onboarding_tasks: Vec::new(),
+        user: inviter,
+    });
+    app_state.websocket.broadcast_to_project(invitation.project_id, event, None).await;
+
+    Ok(member)
+}
@@ -0,0 +1,72 @@
+// Background jobs: persisted in the `jobs` table, executed by the worker loop
+pub mod worker;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::database::{models::Job, queries::JobQueries};
+use crate::utils::errors::AppError;
+
+pub const DEFAULT_MAX_ATTEMPTS: i32 = 5;
+
+#[async_trait]
+pub trait JobHandler: Send + Sync {
+    // Returning an error schedules a retry with backoff until max_attempts is reached
+    async fn run(&self, job: &Job) -> anyhow::Result<()>;
+}
+
+// Maps job types to the handler that executes them
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    handlers: HashMap<String, Arc<dyn JobHandler>>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<H: JobHandler + 'static>(&mut self, job_type: &str, handler: H) {
+        self.handlers.insert(job_type.to_string(), Arc::new(handler));
+    }
+
+    pub fn get(&self, job_type: &str) -> Option<Arc<dyn JobHandler>> {
+        self.handlers.get(job_type).cloned()
+    }
+}
+
+// Queue a job to run as soon as a worker picks it up
+pub async fn enqueue<T: Serialize>(pool: &PgPool, job_type: &str, payload: &T) -> Result<Job, AppError> {
+    enqueue_at(pool, job_type, payload, Utc::now()).await
+}
+
+// Queue a job to run no earlier than `run_at`
+pub async fn enqueue_at<T: Serialize>(
+    pool: &PgPool,
+    job_type: &str,
+    payload: &T,
+    run_at: DateTime<Utc>,
+) -> Result<Job, AppError> {
+    let payload = serde_json::to_value(payload)?;
+    JobQueries::enqueue(pool, job_type, payload, run_at, DEFAULT_MAX_ATTEMPTS).await
+}
+
+// Queue a job that runs only after every job queued earlier with the same
+// ordering key has completed, e.g. the deliveries to one destination. A job
+// that fails for good halts the key until JobQueries::resume_ordered queues it
+// again; later jobs are never run past it. Jobs still run at least once rather
+// than exactly once: a worker that dies mid-job has it reclaimed, so the job's
+// sequence number is what tells a repeat apart.
+pub async fn enqueue_ordered<T: Serialize>(
+    pool: &PgPool,
+    job_type: &str,
+    ordering_key: &str,
+    payload: &T,
+) -> Result<Job, AppError> {
+    let payload = serde_json::to_value(payload)?;
+    JobQueries::enqueue_ordered(pool, job_type, ordering_key, payload, DEFAULT_MAX_ATTEMPTS).await
+}
@@ -0,0 +1,98 @@
+use chrono::{Duration, Utc};
+use std::time::Duration as StdDuration;
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn};
+
+use crate::database::{connection::Database, models::Job, queries::JobQueries};
+use super::JobRegistry;
+
+const POLL_INTERVAL: StdDuration = StdDuration::from_secs(2);
+const BASE_BACKOFF_SECONDS: i64 = 10;
+const MAX_BACKOFF_SECONDS: i64 = 60 * 60;
+
+pub struct JobWorker {
+    database: Database,
+    registry: JobRegistry,
+}
+
+impl JobWorker {
+    pub fn new(database: Database, registry: JobRegistry) -> Self {
+        Self { database, registry }
+    }
+
+    // Runs until `shutdown` flips to true. The flag is only checked between
+    // jobs, so a job that's already running is always allowed to finish.
+    pub async fn run(self, mut shutdown: watch::Receiver<bool>) {
+        info!("Job worker started");
+
+        while !*shutdown.borrow() {
+            match JobQueries::claim_next(self.database.pool()).await {
+                Ok(Some(job)) => self.execute(job).await,
+                Ok(None) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                        _ = shutdown.changed() => {}
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to claim job: {}", e);
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+        }
+
+        info!("Job worker stopped");
+    }
+
+    async fn execute(&self, job: Job) {
+        let pool = self.database.pool();
+
+        let Some(handler) = self.registry.get(&job.job_type) else {
+            warn!("No handler registered for job type {}", job.job_type);
+            let message = format!("No handler registered for job type {}", job.job_type);
+            if let Err(e) = JobQueries::mark_failed(pool, job.id, &message).await {
+                error!("Failed to record job failure {}: {}", job.id, e);
+            }
+            return;
+        };
+
+        debug!("Running job {} ({}), attempt {}", job.id, job.job_type, job.attempts);
+
+        let result = match handler.run(&job).await {
+            Ok(()) => JobQueries::mark_completed(pool, job.id).await,
+            Err(e) if job.attempts >= job.max_attempts => {
+                error!("Job {} ({}) failed permanently: {}", job.id, job.job_type, e);
+                JobQueries::mark_failed(pool, job.id, &e.to_string()).await
+            }
+            Err(e) => {
+                let run_at = Utc::now() + backoff(job.attempts);
+                warn!("Job {} ({}) failed, retrying at {}: {}", job.id, job.job_type, run_at, e);
+                JobQueries::schedule_retry(pool, job.id, run_at, &e.to_string()).await
+            }
+        };
+
+        if let Err(e) = result {
+            error!("Failed to update job {}: {}", job.id, e);
+        }
+    }
+}
+
+// Exponential backoff: 10s, 20s, 40s, ... capped at one hour
+fn backoff(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    let seconds = BASE_BACKOFF_SECONDS.saturating_mul(2_i64.pow(exponent));
+    Duration::seconds(seconds.min(MAX_BACKOFF_SECONDS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_exponentially_and_caps() {
+        assert_eq!(backoff(1), Duration::seconds(10));
+        assert_eq!(backoff(2), Duration::seconds(20));
+        assert_eq!(backoff(4), Duration::seconds(80));
+        assert_eq!(backoff(30), Duration::seconds(MAX_BACKOFF_SECONDS));
+    }
+}
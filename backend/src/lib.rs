@@ -0,0 +1,383 @@
+use axum::{
+    http::HeaderValue,
+    routing::{get, post, put, delete},
+    middleware,
+    Router,
+    Json,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use tower_http::{compression::CompressionLayer, cors::{AllowOrigin, Any, CorsLayer}};
+
+pub mod agenda;
+pub mod analytics;
+pub mod api;
+pub mod assignment;
+pub mod attachments;
+pub mod audit_export;
+pub mod auth;
+pub mod automations;
+pub mod backup;
+pub mod board_export;
+pub mod bootstrap;
+pub mod config;
+pub mod database;
+pub mod due_dates;
+pub mod duplicates;
+pub mod email;
+pub mod flags;
+pub mod integrations;
+pub mod invitations;
+pub mod jobs;
+pub mod maintenance;
+pub mod mentions;
+pub mod notification_settings;
+pub mod notifications;
+pub mod positions;
+pub mod project_templates;
+pub mod quick_switch;
+pub mod quotas;
+pub mod reports;
+pub mod retention;
+pub mod scanning;
+pub mod screening;
+pub mod sharing;
+pub mod staleness;
+pub mod swimlanes;
+pub mod task_snapshots;
+pub mod trash;
+pub mod user_import;
+pub mod utils;
+pub mod websocket;
+pub mod wip;
+
+use auth::jwt::JwtService;
+use config::{Config, CorsConfig, PublicConfig};
+use flags::Flags;
+use utils::rate_limit::Quotas;
+use database::connection::Database;
+use websocket::handler::{WebSocketState, websocket_handler};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub database: Database,
+    pub jwt_service: JwtService,
+    pub websocket: WebSocketState,
+    pub public_config: Arc<PublicConfig>,
+    pub flags: Flags,
+    pub screen: Arc<dyn screening::ContentScreen>,
+    pub quotas: Arc<Quotas>,
+}
+
+impl AppState {
+    pub fn new(config: Config, database: Database, jwt_service: JwtService, public_config: PublicConfig) -> Self {
+        let websocket = WebSocketState::new(jwt_service.clone(), database.clone());
+        let flags = Flags::new(database.pool().clone());
+        let quotas = Quotas::new(config.redis_url.as_deref());
+
+        AppState {
+            config: Arc::new(config),
+            database,
+            jwt_service,
+            websocket,
+            public_config: Arc::new(public_config),
+            flags,
+            screen: screening::screen_from_env(),
+            quotas: Arc::new(quotas),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct HealthResponse {
+    status: String,
+    version: String,
+}
+
+#[derive(Serialize)]
+struct ApiResponse {
+    message: String,
+}
+
+async fn health() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
+
+async fn root() -> Json<ApiResponse> {
+    Json(ApiResponse {
+        message: "SimpleCards API v0.1.0".to_string(),
+    })
+}
+
+// Any origin unless CORS_ORIGIN lists them
+fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    if config.allowed_origins.is_empty() {
+        return CorsLayer::permissive();
+    }
+    let origins = config
+        .allowed_origins
+        .iter()
+        .filter_map(|origin| origin.parse().ok())
+        .collect::<Vec<HeaderValue>>();
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers(Any)
+}
+
+// Builds the full HTTP + WebSocket router. Shared by main() and the integration tests.
+pub fn build_app(state: AppState) -> Router {
+    // Build protected routes (require authentication)
+    let protected_routes = Router::new()
+        // User routes
+        .route("/users/me", get(api::users::get_current_user))
+        .route("/users/me", post(api::users::update_current_user))
+        .route("/users/me/email-preferences", get(api::users::get_email_preferences))
+        .route("/users/me/email-preferences", put(api::users::update_email_preferences))
+        .route("/users/me/digest", get(api::users::get_digest_preferences))
+        .route("/users/me/digest", put(api::users::update_digest_preferences))
+        .route("/users/me/digest/preview", post(api::users::preview_digest))
+        .route("/users/me/notifications", get(api::users::get_notifications))
+        .route("/users/me/notification-settings", get(api::users::get_notification_settings))
+        .route("/users/me/notification-settings", put(api::users::update_notification_settings))
+        .route("/users/me/notifications/read", post(api::users::mark_notifications_read))
+        .route("/users/me/merge", post(api::users::merge_account))
+        .route("/users/me/agenda.pdf", get(api::users::get_agenda))
+        .route("/users/me/tokens", get(api::users::get_api_tokens))
+        .route("/users/me/tokens", post(api::users::create_api_token))
+        .route("/users/me/tokens/:token_id", delete(api::users::delete_api_token))
+        .route("/users/:user_id/activity", get(api::users::get_user_activity))
+        .route("/dashboard", get(api::dashboard::get_dashboard))
+        .route("/quick-switch", get(api::quick_switch::quick_switch))
+        
+        // Team routes
+        .route("/teams", post(api::teams::create_team))
+        .route("/teams", get(api::teams::get_user_teams))
+        .route("/teams/:team_id", get(api::teams::get_team_details))
+        .route("/teams/:team_id", put(api::teams::update_team))
+        .route("/teams/:team_id", delete(api::teams::delete_team))
+        .route("/teams/:team_id/members", get(api::teams::get_team_members))
+        .route("/teams/:team_id/members", post(api::teams::add_team_member))
+        .route("/teams/:team_id/members/:user_id", delete(api::teams::remove_team_member))
+        .route("/teams/:team_id/members/:user_id", put(api::teams::update_team_member_role))
+        .route("/teams/:team_id/usage", get(api::teams::get_team_usage))
+        .route("/teams/:team_id/settings", get(api::teams::get_team_settings))
+        .route("/teams/:team_id/settings", put(api::teams::update_team_settings))
+        .route("/teams/:team_id/retention", get(api::teams::get_retention_policy))
+        .route("/teams/:team_id/retention", put(api::teams::update_retention_policy))
+        .route("/teams/:team_id/retention/preview", get(api::teams::preview_retention))
+        .route("/teams/:team_id/export", get(api::teams::export_team))
+        .route("/teams/:team_id/audit/export", get(api::teams::export_audit_log))
+        .route("/teams/:team_id/audit/exports/:export_id", get(api::teams::get_audit_export))
+        .route("/teams/:team_id/audit/exports/:export_id/download", get(api::teams::download_audit_export))
+        .route("/teams/:team_id/trash", get(api::trash::get_team_trash))
+        .route("/teams/:team_id/trash/projects/:project_id/restore", post(api::trash::restore_project))
+        
+        // Project routes
+        .route("/teams/:team_id/projects", post(api::projects::create_project))
+        .route("/teams/:team_id/projects", get(api::projects::get_team_projects))
+        .route("/projects", get(api::projects::get_user_projects))
+        .route("/projects/:project_id", get(api::projects::get_project_details))
+        .route("/projects/:project_id", put(api::projects::update_project))
+        .route("/projects/:project_id", delete(api::projects::delete_project))
+        .route("/projects/:project_id/my-permissions", get(api::projects::get_my_permissions))
+        .route("/projects/:project_id/workflow", get(api::projects::get_project_workflow))
+        .route("/projects/:project_id/workflow", put(api::projects::update_project_workflow))
+        .route("/projects/:project_id/onboarding-template", get(api::projects::get_onboarding_template))
+        .route("/projects/:project_id/onboarding-template", put(api::projects::update_onboarding_template))
+        .route("/projects/:project_id/staleness", get(api::projects::get_staleness_rules))
+        .route("/projects/:project_id/staleness", put(api::projects::update_staleness_rules))
+        .route("/projects/:project_id/mention-settings", get(api::projects::get_mention_settings))
+        .route("/projects/:project_id/mention-settings", put(api::projects::update_mention_settings))
+        .route("/projects/:project_id/notification-settings", get(api::projects::get_notification_settings))
+        .route("/projects/:project_id/notification-settings", put(api::projects::update_notification_settings))
+        .route("/projects/:project_id/archive", post(api::projects::archive_project))
+        .route("/projects/:project_id/activate", post(api::projects::activate_project))
+        .route("/projects/:project_id/members", get(api::projects::get_project_members))
+        .route("/projects/:project_id/members", post(api::projects::add_project_member))
+        .route("/projects/:project_id/usage", get(api::projects::get_project_usage))
+        .route("/projects/:project_id/workload", get(api::projects::get_project_workload))
+        .route("/projects/:project_id/assignment-suggestions", get(api::projects::get_assignment_suggestions))
+        .route("/projects/:project_id/analytics/cycle-time", get(api::projects::get_cycle_time_analytics))
+        .route("/projects/:project_id/reports", get(api::reports::get_project_reports))
+        .route("/projects/:project_id/reports", post(api::reports::create_project_report))
+        .route("/projects/:project_id/reports/:report_id", delete(api::reports::delete_project_report))
+        .route("/projects/:project_id/reports/:report_id/preview", get(api::reports::preview_project_report))
+        .route("/projects/:project_id/publish-template", post(api::templates::publish_template))
+        .route("/projects/:project_id/leave", post(api::projects::leave_project))
+        .route("/projects/:project_id/members/:user_id", delete(api::projects::remove_project_member))
+        .route("/projects/:project_id/members/:user_id", put(api::projects::update_project_member_role))
+        .route("/projects/:project_id/invitations", get(api::invitations::get_project_invitations))
+        .route("/projects/:project_id/invitations", post(api::invitations::create_project_invitation))
+        .route("/projects/:project_id/invitations/:invitation_id", delete(api::invitations::delete_project_invitation))
+        .route("/invitations/accept", post(api::invitations::accept_invitation))
+
+        // Event routes
+        .route("/events/schemas", get(api::events::get_event_schemas))
+        .route("/projects/:project_id/events/test", post(api::events::send_test_event))
+
+        // Template routes
+        .route("/templates", get(api::templates::get_templates))
+        .route("/templates/:template_id", delete(api::templates::unpublish_template))
+
+        // Project integration routes
+        .route("/projects/:project_id/integrations/slack", get(api::integrations::get_slack_integration))
+        .route("/projects/:project_id/integrations/slack", post(api::integrations::configure_slack_integration))
+        .route("/projects/:project_id/integrations/slack", delete(api::integrations::delete_slack_integration))
+        .route("/projects/:project_id/integrations/slack/test", post(api::integrations::test_slack_integration))
+        .route("/projects/:project_id/integrations/github", get(api::integrations::get_github_integration))
+        .route("/projects/:project_id/integrations/github", post(api::integrations::configure_github_integration))
+        .route("/projects/:project_id/integrations/github", delete(api::integrations::delete_github_integration))
+        .route("/projects/:project_id/integrations/email", get(api::integrations::get_email_integration))
+        .route("/projects/:project_id/integrations/email", post(api::integrations::configure_email_integration))
+        .route("/projects/:project_id/integrations/email", delete(api::integrations::delete_email_integration))
+        
+        // Task routes
+        .route("/projects/:project_id/tasks", post(api::tasks::create_task))
+        .route("/projects/:project_id/tasks", get(api::tasks::get_project_tasks))
+        .route("/projects/:project_id/tasks/counts", get(api::tasks::get_task_counts))
+        .route("/projects/:project_id/tasks/picker", get(api::tasks::pick_tasks))
+        .route("/projects/:project_id/tasks/normalize-positions", post(api::tasks::normalize_task_positions))
+        .route("/projects/:project_id/read", post(api::tasks::mark_project_read))
+        .route("/tasks", get(api::tasks::get_user_assigned_tasks))
+        .route("/tasks/my-order", put(api::tasks::set_my_task_order))
+        .route("/tasks/:task_id", get(api::tasks::get_task_details))
+        .route("/tasks/:task_id", put(api::tasks::update_task))
+        .route("/tasks/:task_id", delete(api::tasks::delete_task))
+        .route("/tasks/:task_id/move", post(api::tasks::move_task))
+        .route("/tasks/:task_id/nudge", post(api::tasks::nudge_task))
+        .route("/tasks/:task_id/read", post(api::tasks::mark_task_read))
+        .route("/tasks/:task_id/context", get(api::tasks::get_task_context))
+        .route("/tasks/:task_id/snapshot", get(api::task_snapshots::get_task_snapshot))
+        .route("/tasks/:task_id/snapshots", get(api::task_snapshots::list_task_snapshots))
+        .route("/tasks/:task_id/snapshots", post(api::task_snapshots::create_task_snapshot))
+        .route("/tasks/:task_id/snapshots/:snapshot_id", get(api::task_snapshots::get_stored_task_snapshot))
+        .route("/tasks/:task_id/relations", post(api::tasks::create_task_relation))
+        .route("/tasks/:task_id/relations/:related_task_id", delete(api::tasks::delete_task_relation))
+        
+        // Board routes
+        .route("/projects/:project_id/boards", post(api::boards::create_board))
+        .route("/projects/:project_id/boards", get(api::boards::get_project_boards))
+        .route("/projects/:project_id/boards/import", post(api::boards::import_board))
+        .route("/projects/:project_id/trash/boards", get(api::trash::get_board_trash))
+        .route("/projects/:project_id/trash/boards/:board_id/restore", post(api::trash::restore_board))
+        .route("/projects/:project_id/share-tokens", get(api::sharing::get_share_tokens))
+        .route("/projects/:project_id/share-tokens", post(api::sharing::create_share_token))
+        .route("/projects/:project_id/share-tokens/:token_id", delete(api::sharing::revoke_share_token))
+        .route("/boards/:board_id", get(api::boards::get_board_details))
+        .route("/boards/:board_id", put(api::boards::update_board))
+        .route("/boards/:board_id", delete(api::boards::delete_board))
+        .route("/boards/:board_id/export", get(api::boards::export_board))
+        .route("/boards/:board_id/automations", get(api::boards::get_board_automations))
+        .route("/boards/:board_id/automations", put(api::boards::update_board_automations))
+        .route("/boards/:board_id/columns/:column_id/tasks", post(api::tasks::create_column_task))
+        
+        // Task comment routes
+        .route("/tasks/:task_id/comments", post(api::comments::create_task_comment))
+        .route("/tasks/:task_id/comments", get(api::comments::get_task_comments))
+        .route("/tasks/:task_id/mentions", get(api::comments::get_task_mentions))
+        .route("/tasks/:task_id/comments/search", get(api::comments::search_task_comments))
+        .route("/comments/:comment_id", delete(api::comments::delete_task_comment))
+        .route("/comments/:comment_id/context", get(api::comments::get_comment_context))
+        .route("/comments/:comment_id/convert-to-task", post(api::comments::convert_comment_to_task))
+
+        // Attachment routes
+        .route("/attachments/:attachment_id/download", get(api::attachments::download_attachment))
+
+        // Announcement routes
+        .route("/announcements/active", get(api::announcements::get_active_announcements))
+        .route("/announcements/:announcement_id/dismiss", post(api::announcements::dismiss_announcement))
+
+        // Instance admin routes
+        .route("/admin/jobs", get(api::admin::list_jobs))
+        .route("/admin/emails", get(api::admin::list_emails))
+        .route("/admin/storage", get(api::admin::get_storage_report))
+        .route("/admin/teams/:team_id/limits", put(api::admin::update_team_limits))
+        .route("/admin/signup-codes", post(api::admin::create_signup_code))
+        .route("/admin/announcements", get(api::admin::list_announcements))
+        .route("/admin/announcements", post(api::admin::create_announcement))
+        .route("/admin/announcements/:announcement_id", put(api::admin::update_announcement))
+        .route("/admin/announcements/:announcement_id", delete(api::admin::delete_announcement))
+        .route("/admin/templates", get(api::templates::get_pending_templates))
+        .route("/admin/templates/:template_id/approve", post(api::templates::approve_template))
+        .route("/admin/export", get(api::admin::export_instance))
+        .route("/admin/audit-log", get(api::admin::list_audit_log))
+        .route("/admin/metrics", get(api::admin::get_metrics))
+        .route("/admin/impersonate/:user_id", post(api::admin::impersonate_user))
+        .route("/admin/flags", get(api::admin::list_flags))
+        .route("/admin/flags/:name", put(api::admin::set_flag))
+        .route("/admin/flags/:name/teams/:team_id", put(api::admin::set_team_flag))
+        .route("/admin/flags/:name/teams/:team_id", delete(api::admin::remove_team_flag))
+        .route("/admin/maintenance/check", post(api::admin::run_consistency_checks))
+        .route("/admin/maintenance/repair", post(api::admin::repair_consistency))
+        .route("/admin/users/:user_id/content/quarantine", post(api::admin::quarantine_user_content))
+        .route("/admin/users/:user_id/content/restore", post(api::admin::restore_user_content))
+        .route("/admin/users/:user_id/content/purge", post(api::admin::purge_user_content))
+        .route("/admin/users/:user_id/token-rate-limit", put(api::admin::update_token_rate_limit))
+        .route("/admin/moderation", get(api::admin::list_held_content))
+        .route("/admin/moderation/:item_id/approve", post(api::admin::approve_held_content))
+        .route("/admin/moderation/:item_id/reject", post(api::admin::reject_held_content))
+        // Archives are much larger than regular JSON bodies
+        .merge(utils::limits::with_body_limit(
+            Router::new()
+                .route("/admin/import", post(api::admin::import_archive))
+                .route("/admin/users/import", post(api::admin::import_users)),
+            utils::limits::upload_body_limit(),
+        ))
+        
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::middleware::auth_middleware,
+        ));
+
+    // Build public routes
+    let public_routes = Router::new()
+        .route("/", get(root))
+        .route("/health", get(health))
+        .route("/ready", get(health))
+        .route("/auth/register", post(api::auth::register))
+        .route("/auth/availability", get(api::auth::check_availability))
+        .route("/auth/login", post(api::auth::login))
+        .route("/auth/refresh", post(api::auth::refresh_token))
+        .route("/auth/logout", post(api::auth::logout))
+        .route("/auth/password-reset", post(api::auth::reset_password))
+        .route("/meta/palette", get(api::meta::get_palette))
+        .route("/config", get(api::meta::get_config))
+        .route("/files/signed/:attachment_id", get(api::attachments::download_signed_attachment))
+        .route("/public/projects/:share_token/badge.svg", get(api::sharing::get_badge_svg))
+        .route("/public/projects/:share_token/badge.json", get(api::sharing::get_badge_json))
+        .route("/integrations/github/webhook", post(api::integrations::github_webhook))
+        // Inbound mail carries attachments, so it gets the upload limit
+        .merge(utils::limits::with_body_limit(
+            Router::new().route("/integrations/email/inbound", post(api::integrations::email_inbound)),
+            utils::limits::upload_body_limit(),
+        ));
+
+    // WebSocket routes
+    let ws_routes = Router::new()
+        .route("/ws", get(websocket_handler))
+        .with_state(state.websocket.clone());
+
+    let cors = cors_layer(&state.config.cors);
+
+    // Combine routes
+    let app = Router::new()
+        .nest("/api", protected_routes)
+        .merge(public_routes.clone())
+        .nest("/api", public_routes)
+        .merge(ws_routes)
+        .with_state(state);
+
+    // Cap request bodies before they are buffered; upload routes can opt into
+    // utils::limits::upload_body_limit() on their own router
+    utils::limits::with_body_limit(app, utils::limits::json_body_limit())
+        .layer(middleware::from_fn(utils::i18n::localize))
+        .layer(middleware::from_fn(database::timing::server_timing))
+        .layer(CompressionLayer::new())
+        .layer(cors)
+}
@@ -0,0 +1,623 @@
+// Consistency checks for data that manual database fixes can leave behind, and
+// repairs for the ones that can be fixed without a judgement call. Each check is
+// a plain query over the whole instance or one project (the attachment files
+// check also looks at UPLOAD_DIR); repairs re-run their check inside a
+// transaction and fix what it found. The counters and attachment files checks
+// also run on their own as a daily reconciliation job.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool, Row};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::config::AttachmentConfig;
+use crate::database::{connection::Database, models::Job, queries::JobQueries};
+use crate::jobs::{self, JobHandler};
+use crate::utils::errors::AppError;
+
+pub const COUNTER_RECONCILE_JOB: &str = "counter_reconcile";
+const RECONCILE_INTERVAL_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    // Members whose user is gone or no longer in the project's team
+    OrphanedProjectMembers,
+    // Tasks assigned to someone who isn't a project member
+    NonMemberAssignees,
+    // Columns where several tasks share a position
+    DuplicatePositions,
+    // Projects without exactly one default board
+    DefaultBoards,
+    // Team and project counters (migrations 039 and 048) that don't match their rows
+    Counters,
+    // Attachments whose stored file is missing or has a different size
+    AttachmentFiles,
+}
+
+impl Check {
+    pub const ALL: [Check; 6] = [
+        Check::OrphanedProjectMembers,
+        Check::NonMemberAssignees,
+        Check::DuplicatePositions,
+        Check::DefaultBoards,
+        Check::Counters,
+        Check::AttachmentFiles,
+    ];
+
+    // Removing a membership may be the wrong fix (the team membership may be
+    // what's missing), so orphaned members are only reported. A lost file
+    // usually means a restore from backup is due rather than deleting the row.
+    pub fn repairable(&self) -> bool {
+        !matches!(self, Check::OrphanedProjectMembers | Check::AttachmentFiles)
+    }
+
+    pub async fn run(&self, conn: &mut PgConnection, config: &AttachmentConfig, project_id: Option<Uuid>) -> Result<Vec<Issue>, AppError> {
+        match self {
+            Check::OrphanedProjectMembers => orphaned_project_members(conn, project_id).await,
+            Check::NonMemberAssignees => non_member_assignees(conn, project_id).await,
+            Check::DuplicatePositions => duplicate_positions(conn, project_id).await,
+            Check::DefaultBoards => default_boards(conn, project_id).await,
+            Check::Counters => counter_drift(conn, project_id).await,
+            Check::AttachmentFiles => attachment_files(conn, config, project_id).await,
+        }
+    }
+
+    async fn repair(&self, conn: &mut PgConnection, project_id: Option<Uuid>) -> Result<(), AppError> {
+        match self {
+            Check::OrphanedProjectMembers | Check::AttachmentFiles => Ok(()),
+            Check::NonMemberAssignees => unassign_non_members(conn, project_id).await,
+            Check::DuplicatePositions => renumber_positions(conn, project_id).await,
+            Check::DefaultBoards => fix_default_boards(conn, project_id).await,
+            Check::Counters => recount(conn, project_id).await,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Issue {
+    // None for team-level issues, whose subject is the team
+    pub project_id: Option<Uuid>,
+    // The member, task, board or team concerned; None when it's the project itself
+    pub subject_id: Option<Uuid>,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    pub check: Check,
+    pub repairable: bool,
+    pub issues: Vec<Issue>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MaintenanceReport {
+    // Only set by repairs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<bool>,
+    pub issue_count: usize,
+    pub checks: Vec<CheckReport>,
+}
+
+impl MaintenanceReport {
+    fn new(dry_run: Option<bool>, checks: Vec<CheckReport>) -> Self {
+        let issue_count = checks.iter().map(|check| check.issues.len()).sum();
+        MaintenanceReport { dry_run, issue_count, checks }
+    }
+}
+
+// Runs every check, optionally limited to one project
+pub async fn check(pool: &PgPool, config: &AttachmentConfig, project_id: Option<Uuid>) -> Result<MaintenanceReport, AppError> {
+    let mut conn = pool.acquire().await?;
+    let mut checks = Vec::new();
+    for check in Check::ALL {
+        let issues = check.run(&mut conn, config, project_id).await?;
+        checks.push(CheckReport { check, repairable: check.repairable(), issues });
+    }
+    Ok(MaintenanceReport::new(None, checks))
+}
+
+// Repairs what the repairable checks find, one transaction per check. The
+// report lists the issues that were fixed, or would be on a dry run.
+pub async fn repair(pool: &PgPool, config: &AttachmentConfig, project_id: Option<Uuid>, dry_run: bool) -> Result<MaintenanceReport, AppError> {
+    let mut checks = Vec::new();
+    for check in Check::ALL.into_iter().filter(Check::repairable) {
+        checks.push(repair_check(pool, config, check, project_id, dry_run).await?);
+    }
+    Ok(MaintenanceReport::new(Some(dry_run), checks))
+}
+
+async fn repair_check(
+    pool: &PgPool,
+    config: &AttachmentConfig,
+    check: Check,
+    project_id: Option<Uuid>,
+    dry_run: bool,
+) -> Result<CheckReport, AppError> {
+    let mut tx = pool.begin().await?;
+    let issues = check.run(&mut tx, config, project_id).await?;
+    if !dry_run && !issues.is_empty() {
+        check.repair(&mut tx, project_id).await?;
+        tx.commit().await?;
+        tracing::info!("Repaired {} {:?} issues", issues.len(), check);
+    }
+    Ok(CheckReport { check, repairable: true, issues })
+}
+
+// Guests and viewers may be outside the team, e.g. invited contractors
+pub async fn orphaned_project_members(conn: &mut PgConnection, project_id: Option<Uuid>) -> Result<Vec<Issue>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT pm.project_id, pm.user_id, u.id IS NULL AS user_missing
+        FROM project_members pm
+        JOIN projects p ON p.id = pm.project_id
+        LEFT JOIN users u ON u.id = pm.user_id
+        LEFT JOIN team_members tm ON tm.team_id = p.team_id AND tm.user_id = pm.user_id
+        WHERE (u.id IS NULL OR (tm.id IS NULL AND pm.role NOT IN ('guest', 'viewer')))
+          AND ($1::uuid IS NULL OR pm.project_id = $1)
+        ORDER BY pm.project_id, pm.user_id
+        "#
+    )
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let detail = if row.get("user_missing") {
+                "User no longer exists"
+            } else {
+                "User is not a member of the project's team"
+            };
+            Issue { project_id: row.get("project_id"), subject_id: row.get("user_id"), detail: detail.to_string() }
+        })
+        .collect())
+}
+
+pub async fn non_member_assignees(conn: &mut PgConnection, project_id: Option<Uuid>) -> Result<Vec<Issue>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT t.project_id, t.id, t.assigned_to
+        FROM tasks t
+        WHERE t.assigned_to IS NOT NULL
+          AND NOT EXISTS (
+              SELECT 1 FROM project_members pm WHERE pm.project_id = t.project_id AND pm.user_id = t.assigned_to
+          )
+          AND ($1::uuid IS NULL OR t.project_id = $1)
+        ORDER BY t.project_id, t.number
+        "#
+    )
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Issue {
+            project_id: row.get("project_id"),
+            subject_id: row.get("id"),
+            detail: format!("Assigned to {}, who is not a project member", row.get::<Uuid, _>("assigned_to")),
+        })
+        .collect())
+}
+
+pub async fn duplicate_positions(conn: &mut PgConnection, project_id: Option<Uuid>) -> Result<Vec<Issue>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT project_id, status::text AS status, position, COUNT(*) AS tasks
+        FROM tasks
+        WHERE $1::uuid IS NULL OR project_id = $1
+        GROUP BY project_id, status, position
+        HAVING COUNT(*) > 1
+        ORDER BY project_id, status, position
+        "#
+    )
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Issue {
+            project_id: row.get("project_id"),
+            subject_id: None,
+            detail: format!(
+                "{} tasks share position {} in column {}",
+                row.get::<i64, _>("tasks"),
+                row.get::<i32, _>("position"),
+                row.get::<String, _>("status"),
+            ),
+        })
+        .collect())
+}
+
+pub async fn default_boards(conn: &mut PgConnection, project_id: Option<Uuid>) -> Result<Vec<Issue>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT p.id AS project_id, COUNT(b.id) FILTER (WHERE b.is_default) AS defaults
+        FROM projects p
+        LEFT JOIN boards b ON b.project_id = p.id
+        WHERE $1::uuid IS NULL OR p.id = $1
+        GROUP BY p.id
+        HAVING COUNT(b.id) FILTER (WHERE b.is_default) <> 1
+        ORDER BY p.id
+        "#
+    )
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let detail = match row.get::<i64, _>("defaults") {
+                0 => "Project has no default board".to_string(),
+                count => format!("Project has {} default boards", count),
+            };
+            Issue { project_id: row.get("project_id"), subject_id: None, detail }
+        })
+        .collect())
+}
+
+async fn unassign_non_members(conn: &mut PgConnection, project_id: Option<Uuid>) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE tasks t
+        SET assigned_to = NULL
+        WHERE t.assigned_to IS NOT NULL
+          AND NOT EXISTS (
+              SELECT 1 FROM project_members pm WHERE pm.project_id = t.project_id AND pm.user_id = t.assigned_to
+          )
+          AND ($1::uuid IS NULL OR t.project_id = $1)
+        "#
+    )
+    .bind(project_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// Renumbers affected columns 1..n in their current order, under the same
+// column locks as moves and without touching updated_at (see migration 019)
+async fn renumber_positions(conn: &mut PgConnection, project_id: Option<Uuid>) -> Result<(), AppError> {
+    let columns = sqlx::query(
+        r#"
+        SELECT DISTINCT project_id, status
+        FROM tasks
+        WHERE $1::uuid IS NULL OR project_id = $1
+        GROUP BY project_id, status, position
+        HAVING COUNT(*) > 1
+        ORDER BY project_id, status
+        "#
+    )
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    sqlx::query("SELECT set_config('simplecards.reordering', 'on', true)").execute(&mut *conn).await?;
+    for column in columns {
+        let project_id: Uuid = column.get("project_id");
+        let status: crate::database::models::TaskStatus = column.get("status");
+
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1::text || ':' || $2::text, 0))")
+            .bind(project_id)
+            .bind(status)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(
+            r#"
+            UPDATE tasks t
+            SET position = o.position::int
+            FROM (
+                SELECT id, ROW_NUMBER() OVER (ORDER BY position, created_at, id) AS position
+                FROM tasks
+                WHERE project_id = $1 AND status = $2
+            ) o
+            WHERE t.id = o.id AND t.position <> o.position
+            "#
+        )
+        .bind(project_id)
+        .bind(status)
+        .execute(&mut *conn)
+        .await?;
+    }
+    sqlx::query("SELECT set_config('simplecards.reordering', 'off', true)").execute(&mut *conn).await?;
+    Ok(())
+}
+
+// Keeps the oldest default board (or the oldest board if none is default) and
+// recreates the default board of projects that have none at all
+async fn fix_default_boards(conn: &mut PgConnection, project_id: Option<Uuid>) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE boards b
+        SET is_default = (b.id = keep.id)
+        FROM (
+            SELECT DISTINCT ON (project_id) project_id, id
+            FROM boards
+            WHERE $1::uuid IS NULL OR project_id = $1
+            ORDER BY project_id, COALESCE(is_default, false) DESC, created_at, id
+        ) keep
+        WHERE b.project_id = keep.project_id
+          AND b.is_default IS DISTINCT FROM (b.id = keep.id)
+        "#
+    )
+    .bind(project_id)
+    .execute(&mut *conn)
+    .await?;
+
+    // Same board as the create_default_board trigger (migration 002)
+    sqlx::query(
+        r#"
+        INSERT INTO boards (name, description, project_id, created_by, is_default)
+        SELECT 'Default Board', 'Default Kanban board for project', p.id, p.created_by, true
+        FROM projects p
+        WHERE ($1::uuid IS NULL OR p.id = $1)
+          AND NOT EXISTS (SELECT 1 FROM boards b WHERE b.project_id = p.id)
+        "#
+    )
+    .bind(project_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// Stored and actual value of each counter that differs
+fn drift_detail(counters: &[(&str, Option<i64>, i64)]) -> String {
+    if counters.iter().all(|(_, stored, _)| stored.is_none()) {
+        return "Counters are missing".to_string();
+    }
+    let drifted: Vec<String> = counters
+        .iter()
+        .filter(|(_, stored, actual)| *stored != Some(*actual))
+        .map(|(name, stored, actual)| format!("{} is {} but should be {}", name, stored.unwrap_or(0), actual))
+        .collect();
+    drifted.join(", ")
+}
+
+const PROJECT_COUNTS_SQL: &str = r#"
+    SELECT p.id AS project_id,
+           (SELECT COUNT(*) FROM project_members pm WHERE pm.project_id = p.id) AS member_count,
+           (SELECT COUNT(*) FROM tasks t WHERE t.project_id = p.id AND t.quarantined_at IS NULL AND t.expired_at IS NULL) AS task_count,
+           (SELECT COUNT(*) FROM tasks t
+            WHERE t.project_id = p.id AND t.quarantined_at IS NULL AND t.expired_at IS NULL AND t.status <> 'done') AS open_task_count,
+           a.attachment_count, a.attachment_bytes
+    FROM projects p
+    CROSS JOIN LATERAL (
+        SELECT COUNT(x.id) AS attachment_count, COALESCE(SUM(x.size_bytes), 0)::BIGINT AS attachment_bytes
+        FROM task_attachments x JOIN tasks t ON t.id = x.task_id
+        WHERE t.project_id = p.id
+    ) a
+    WHERE $1::uuid IS NULL OR p.id = $1
+"#;
+
+// With a project, only that project's team is checked
+const TEAM_COUNTS_SQL: &str = r#"
+    SELECT t.id AS team_id,
+           (SELECT COUNT(*) FROM team_members tm WHERE tm.team_id = t.id) AS member_count,
+           (SELECT COUNT(*) FROM projects p
+            WHERE p.team_id = t.id AND p.is_active = true AND p.deleted_at IS NULL) AS project_count
+    FROM teams t
+    WHERE $1::uuid IS NULL OR t.id = (SELECT team_id FROM projects WHERE id = $1)
+"#;
+
+pub async fn counter_drift(conn: &mut PgConnection, project_id: Option<Uuid>) -> Result<Vec<Issue>, AppError> {
+    let teams = sqlx::query(&format!(
+        r#"
+        SELECT a.team_id, a.member_count, a.project_count,
+               c.member_count AS stored_member_count, c.project_count AS stored_project_count
+        FROM ({TEAM_COUNTS_SQL}) a
+        LEFT JOIN team_counters c ON c.team_id = a.team_id
+        WHERE (c.member_count, c.project_count) IS DISTINCT FROM (a.member_count, a.project_count)
+        ORDER BY a.team_id
+        "#
+    ))
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let projects = sqlx::query(&format!(
+        r#"
+        SELECT a.project_id, a.member_count, a.task_count, a.open_task_count, a.attachment_count, a.attachment_bytes,
+               c.member_count AS stored_member_count, c.task_count AS stored_task_count,
+               c.open_task_count AS stored_open_task_count, c.attachment_count AS stored_attachment_count,
+               c.attachment_bytes AS stored_attachment_bytes
+        FROM ({PROJECT_COUNTS_SQL}) a
+        LEFT JOIN project_counters c ON c.project_id = a.project_id
+        WHERE (c.member_count, c.task_count, c.open_task_count, c.attachment_count, c.attachment_bytes)
+              IS DISTINCT FROM (a.member_count, a.task_count, a.open_task_count, a.attachment_count, a.attachment_bytes)
+        ORDER BY a.project_id
+        "#
+    ))
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let team_issues = teams.into_iter().map(|row| Issue {
+        project_id: None,
+        subject_id: row.get("team_id"),
+        detail: format!(
+            "Team {}",
+            drift_detail(&[
+                ("member_count", row.get("stored_member_count"), row.get("member_count")),
+                ("project_count", row.get("stored_project_count"), row.get("project_count")),
+            ])
+            .to_lowercase()
+        ),
+    });
+    let project_issues = projects.into_iter().map(|row| Issue {
+        project_id: row.get("project_id"),
+        subject_id: None,
+        detail: drift_detail(&[
+            ("member_count", row.get("stored_member_count"), row.get("member_count")),
+            ("task_count", row.get("stored_task_count"), row.get("task_count")),
+            ("open_task_count", row.get("stored_open_task_count"), row.get("open_task_count")),
+            ("attachment_count", row.get("stored_attachment_count"), row.get("attachment_count")),
+            ("attachment_bytes", row.get("stored_attachment_bytes"), row.get("attachment_bytes")),
+        ]),
+    });
+    Ok(team_issues.chain(project_issues).collect())
+}
+
+pub async fn attachment_files(conn: &mut PgConnection, config: &AttachmentConfig, project_id: Option<Uuid>) -> Result<Vec<Issue>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT t.project_id, x.id, x.storage_path, x.size_bytes
+        FROM task_attachments x
+        JOIN tasks t ON t.id = x.task_id
+        WHERE $1::uuid IS NULL OR t.project_id = $1
+        ORDER BY t.project_id, x.created_at
+        "#
+    )
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut issues = Vec::new();
+    for row in rows {
+        let storage_path: String = row.get("storage_path");
+        let size_bytes: i64 = row.get("size_bytes");
+        let detail = match tokio::fs::metadata(config.upload_dir.join(&storage_path)).await {
+            Ok(metadata) if metadata.len() as i64 == size_bytes => continue,
+            Ok(metadata) => format!("File is {} bytes but the attachment records {}", metadata.len(), size_bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => format!("File {} is missing", storage_path),
+            Err(e) => format!("File {} can't be read: {}", storage_path, e),
+        };
+        issues.push(Issue { project_id: row.get("project_id"), subject_id: row.get("id"), detail });
+    }
+    Ok(issues)
+}
+
+// Locks the counter rows before counting, so increments from transactions
+// still in flight wait and then apply on top of the recount instead of being
+// overwritten by it
+async fn recount(conn: &mut PgConnection, project_id: Option<Uuid>) -> Result<(), AppError> {
+    sqlx::query(&format!(
+        r#"
+        SELECT c.team_id FROM team_counters c
+        WHERE c.team_id IN (SELECT team_id FROM ({TEAM_COUNTS_SQL}) a)
+        ORDER BY c.team_id
+        FOR UPDATE
+        "#
+    ))
+    .bind(project_id)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(&format!(
+        r#"
+        SELECT c.project_id FROM project_counters c
+        WHERE c.project_id IN (SELECT project_id FROM ({PROJECT_COUNTS_SQL}) a)
+        ORDER BY c.project_id
+        FOR UPDATE
+        "#
+    ))
+    .bind(project_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(&format!(
+        r#"
+        INSERT INTO team_counters (team_id, member_count, project_count)
+        SELECT a.team_id, a.member_count, a.project_count FROM ({TEAM_COUNTS_SQL}) a
+        ON CONFLICT (team_id) DO UPDATE
+        SET member_count = EXCLUDED.member_count, project_count = EXCLUDED.project_count
+        WHERE (team_counters.member_count, team_counters.project_count)
+              IS DISTINCT FROM (EXCLUDED.member_count, EXCLUDED.project_count)
+        "#
+    ))
+    .bind(project_id)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(&format!(
+        r#"
+        INSERT INTO project_counters (project_id, member_count, task_count, open_task_count, attachment_count, attachment_bytes)
+        SELECT a.project_id, a.member_count, a.task_count, a.open_task_count, a.attachment_count, a.attachment_bytes
+        FROM ({PROJECT_COUNTS_SQL}) a
+        ON CONFLICT (project_id) DO UPDATE
+        SET member_count = EXCLUDED.member_count,
+            task_count = EXCLUDED.task_count,
+            open_task_count = EXCLUDED.open_task_count,
+            attachment_count = EXCLUDED.attachment_count,
+            attachment_bytes = EXCLUDED.attachment_bytes
+        WHERE (project_counters.member_count, project_counters.task_count, project_counters.open_task_count,
+               project_counters.attachment_count, project_counters.attachment_bytes)
+              IS DISTINCT FROM (EXCLUDED.member_count, EXCLUDED.task_count, EXCLUDED.open_task_count,
+                                EXCLUDED.attachment_count, EXCLUDED.attachment_bytes)
+        "#
+    ))
+    .bind(project_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// Makes sure a reconciliation is queued, unless one is already pending
+pub async fn schedule(pool: &PgPool, run_at: DateTime<Utc>) -> Result<(), AppError> {
+    if !JobQueries::has_pending_job(pool, COUNTER_RECONCILE_JOB).await? {
+        jobs::enqueue_at(pool, COUNTER_RECONCILE_JOB, &json!({}), run_at).await?;
+    }
+    Ok(())
+}
+
+// Repairs counter drift across the instance and checks that every attachment
+// still has its file. The triggers should never drift, so anything this finds
+// is logged as a warning.
+pub struct CounterReconcileJob {
+    database: Database,
+    attachments: AttachmentConfig,
+}
+
+impl CounterReconcileJob {
+    pub fn new(database: Database, attachments: AttachmentConfig) -> Self {
+        Self { database, attachments }
+    }
+}
+
+#[async_trait]
+impl JobHandler for CounterReconcileJob {
+    async fn run(&self, _job: &Job) -> anyhow::Result<()> {
+        let pool = self.database.pool();
+
+        // Same as the trash sweep: queue the next run before doing the work
+        schedule(pool, Utc::now() + Duration::hours(RECONCILE_INTERVAL_HOURS)).await?;
+
+        let report = repair_check(pool, &self.attachments, Check::Counters, None, false).await?;
+        for issue in &report.issues {
+            let subject = issue.project_id.or(issue.subject_id).unwrap_or_default();
+            warn!("Repaired counter drift on {}: {}", subject, issue.detail);
+        }
+
+        let mut conn = pool.acquire().await?;
+        for issue in Check::AttachmentFiles.run(&mut conn, &self.attachments, None).await? {
+            warn!("Attachment {} in project {}: {}", issue.subject_id.unwrap_or_default(), issue.project_id.unwrap_or_default(), issue.detail);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orphaned_members_and_lost_files_are_left_to_admins() {
+        let repairable: Vec<Check> = Check::ALL.into_iter().filter(Check::repairable).collect();
+        assert_eq!(
+            repairable,
+            vec![Check::NonMemberAssignees, Check::DuplicatePositions, Check::DefaultBoards, Check::Counters]
+        );
+    }
+
+    #[test]
+    fn test_drift_detail_names_the_counters_that_differ() {
+        assert_eq!(
+            drift_detail(&[("member_count", Some(2), 2), ("task_count", Some(7), 5), ("open_task_count", Some(-1), 0)]),
+            "task_count is 7 but should be 5, open_task_count is -1 but should be 0"
+        );
+        assert_eq!(drift_detail(&[("member_count", None, 1), ("project_count", None, 0)]), "Counters are missing");
+    }
+}
@@ -0,0 +1,309 @@
+// @mentions in comments and task descriptions. Besides usernames, the group
+// tokens @project (every project member) and @assignees (the task's assignee)
+// are expanded here on the server. Group mentions are limited to the roles the
+// project's MentionSettings allow (Editor and up by default) and to a few per
+// hour and user, so they can't be used to flood a project with notifications.
+// Everyone a mention reaches gets a task_mentions row, which records the group
+// token if any, a TaskMentioned notification and, unless they opted out, an
+// email.
+
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::database::models::{NotificationEventType, NotificationKind, Task, UserSummary};
+use crate::database::queries::{NotificationQueries, ProjectQueries, TaskMentionQueries, UserQueries};
+use crate::email::{queue_email, templates::EmailTemplate};
+use crate::integrations::app_base_url;
+use crate::notification_settings;
+use crate::utils::errors::AppError;
+use crate::utils::rate_limit;
+use crate::websocket::events::{MentionNotificationData, WebSocketEvent};
+
+pub const GROUP_PROJECT: &str = "project";
+pub const GROUP_ASSIGNEES: &str = "assignees";
+const GROUPS: [&str; 2] = [GROUP_PROJECT, GROUP_ASSIGNEES];
+
+// How much of the text mention emails quote
+const EXCERPT_CHARS: usize = 200;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Mentions {
+    pub usernames: Vec<String>,
+    pub groups: Vec<&'static str>,
+}
+
+impl Mentions {
+    pub fn is_empty(&self) -> bool {
+        self.usernames.is_empty() && self.groups.is_empty()
+    }
+
+    // The mentions `previous` didn't have yet, so editing a description
+    // doesn't mention everyone again
+    pub fn added_since(self, previous: &Mentions) -> Mentions {
+        Mentions {
+            usernames: self.usernames.into_iter().filter(|name| !previous.usernames.contains(name)).collect(),
+            groups: self.groups.into_iter().filter(|group| !previous.groups.contains(group)).collect(),
+        }
+    }
+}
+
+// Like the digest's mention search, a mention is an @ at the start of the text
+// or after a character that can't be part of a username, so email addresses
+// don't count. Group tokens win over usernames of the same name.
+pub fn parse(text: &str) -> Mentions {
+    let mut mentions = Mentions::default();
+
+    for (at, _) in text.match_indices('@') {
+        if text[..at].chars().next_back().is_some_and(is_word_char) {
+            continue;
+        }
+        let rest = &text[at + 1..];
+        let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        // Followed by a letter no username can have
+        if rest[end..].chars().next().is_some_and(is_word_char) {
+            continue;
+        }
+
+        let token = &rest[..end];
+        if let Some(&group) = GROUPS.iter().find(|group| token.eq_ignore_ascii_case(group)) {
+            if !mentions.groups.contains(&group) {
+                mentions.groups.push(group);
+            }
+        } else if token.len() >= 3 && !mentions.usernames.iter().any(|name| name == token) {
+            mentions.usernames.push(token.to_string());
+        }
+    }
+
+    mentions
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Recipient {
+    pub user_id: Uuid,
+    pub group_token: Option<&'static str>,
+}
+
+// Everyone the mentions reach, once, without the author. Users mentioned by
+// name count as such even when a group reaches them too; otherwise the first
+// group that reaches them is recorded.
+pub fn recipients(named: &[Uuid], groups: &[(&'static str, Vec<Uuid>)], author_id: Uuid) -> Vec<Recipient> {
+    let mut recipients: Vec<Recipient> = Vec::new();
+    let reached = named
+        .iter()
+        .map(|user_id| (*user_id, None))
+        .chain(groups.iter().flat_map(|(group, user_ids)| user_ids.iter().map(move |user_id| (*user_id, Some(*group)))));
+
+    for (user_id, group_token) in reached {
+        if user_id != author_id && !recipients.iter().any(|recipient| recipient.user_id == user_id) {
+            recipients.push(Recipient { user_id, group_token });
+        }
+    }
+    recipients
+}
+
+pub fn excerpt(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+// Call before saving the text. Group mentions need the project's minimum role
+// and count against the author's hourly limit.
+pub async fn check(pool: &PgPool, project_id: Uuid, author_id: Uuid, mentions: &Mentions) -> Result<(), AppError> {
+    if mentions.groups.is_empty() {
+        return Ok(());
+    }
+
+    let settings = ProjectQueries::get_mention_settings(pool, project_id).await?;
+    let role = ProjectQueries::get_user_project_role(pool, project_id, author_id).await?;
+    if role.is_none_or(|role| role.rank() < settings.group_mention_role.rank()) {
+        return Err(AppError::Forbidden(format!(
+            "Mentioning @{} needs the {:?} role or higher",
+            mentions.groups[0], settings.group_mention_role
+        )));
+    }
+
+    rate_limit::group_mention_limiter().check(author_id)
+}
+
+// Call after saving the comment (or, without `comment_id`, the description)
+// the mentions were parsed from; failures are logged, not returned
+pub async fn notify(
+    app_state: &crate::AppState,
+    task: &Task,
+    comment_id: Option<Uuid>,
+    text: &str,
+    mentions: &Mentions,
+    author_id: Uuid,
+) {
+    if mentions.is_empty() {
+        return;
+    }
+    if let Err(e) = notify_mentioned(app_state, task, comment_id, text, mentions, author_id).await {
+        warn!("Failed to notify users mentioned on task {}: {}", task.id, e);
+    }
+}
+
+async fn notify_mentioned(
+    app_state: &crate::AppState,
+    task: &Task,
+    comment_id: Option<Uuid>,
+    text: &str,
+    mentions: &Mentions,
+    author_id: Uuid,
+) -> Result<(), AppError> {
+    let pool = app_state.database.pool();
+
+    // Only members can see the task, so nobody else is reached
+    let members: Vec<UserSummary> = ProjectQueries::get_project_members(pool, task.project_id)
+        .await?
+        .into_iter()
+        .map(|(_, user)| user)
+        .collect();
+    let named: Vec<Uuid> = members
+        .iter()
+        .filter(|member| mentions.usernames.contains(&member.username))
+        .map(|member| member.id)
+        .collect();
+    let groups: Vec<(&'static str, Vec<Uuid>)> = mentions
+        .groups
+        .iter()
+        .map(|&group| {
+            let user_ids = members
+                .iter()
+                .map(|member| member.id)
+                .filter(|user_id| group == GROUP_PROJECT || task.assigned_to == Some(*user_id))
+                .collect();
+            (group, user_ids)
+        })
+        .collect();
+
+    let recipients = recipients(&named, &groups, author_id);
+    if recipients.is_empty() {
+        return Ok(());
+    }
+
+    let rows: Vec<(Uuid, Option<&str>)> = recipients.iter().map(|recipient| (recipient.user_id, recipient.group_token)).collect();
+    let recorded = TaskMentionQueries::create_mentions(pool, task.id, comment_id, author_id, &rows).await?;
+
+    let author: UserSummary = UserQueries::get_user_by_id(pool, author_id).await?.into();
+    for recipient in recipients.iter().filter(|recipient| recorded.contains(&recipient.user_id)) {
+        if let Err(e) = notify_recipient(app_state, task, comment_id, text, recipient, &author).await {
+            warn!("Failed to notify user {} about a mention on task {}: {}", recipient.user_id, task.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn notify_recipient(
+    app_state: &crate::AppState,
+    task: &Task,
+    comment_id: Option<Uuid>,
+    text: &str,
+    recipient: &Recipient,
+    author: &UserSummary,
+) -> Result<(), AppError> {
+    let pool = app_state.database.pool();
+    // The mention is recorded either way
+    if !notification_settings::should_notify(pool, recipient.user_id, task.project_id, NotificationEventType::Mention).await? {
+        return Ok(());
+    }
+
+    let notification = NotificationQueries::create_notification(
+        pool,
+        recipient.user_id,
+        NotificationKind::TaskMentioned,
+        task.project_id,
+        Some(task.id),
+        Some(author.id),
+    ).await?;
+    let event = WebSocketEvent::MentionedYou(MentionNotificationData {
+        notification,
+        task: task.clone(),
+        comment_id,
+        group_token: recipient.group_token.map(str::to_string),
+        user: author.clone(),
+    });
+    app_state.websocket.send_to_user(recipient.user_id, event).await;
+
+    // Opt-outs are checked when the email is sent
+    let user = UserQueries::get_user_by_id(pool, recipient.user_id).await?;
+    let place = if comment_id.is_some() { "a comment on" } else { "the description of" };
+    queue_email(
+        pool,
+        &user.email,
+        Some(user.id),
+        EmailTemplate::Mention,
+        json!({
+            "name": user.display_name,
+            "author": author.display_name,
+            "place": place,
+            "task": task.title,
+            "excerpt": excerpt(text),
+            "link": format!("{}/projects/{}/tasks/{}", app_base_url(), task.project_id, task.id),
+        }),
+    ).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_usernames_and_groups() {
+        let mentions = parse("@alice and @bob_2, please check. cc @Project @alice (@assignees)");
+        assert_eq!(mentions.usernames, vec!["alice", "bob_2"]);
+        assert_eq!(mentions.groups, vec![GROUP_PROJECT, GROUP_ASSIGNEES]);
+    }
+
+    #[test]
+    fn test_parse_skips_what_is_no_mention() {
+        // Email addresses, lone @s, names too short or running into other letters
+        let mentions = parse("mail alice@example.com, @ @ab @bobé x@project");
+        assert_eq!(mentions, Mentions::default());
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn test_added_since() {
+        let previous = parse("@alice @project");
+        let added = parse("@alice @bob @project @assignees").added_since(&previous);
+        assert_eq!(added.usernames, vec!["bob"]);
+        assert_eq!(added.groups, vec![GROUP_ASSIGNEES]);
+    }
+
+    #[test]
+    fn test_recipients_are_deduplicated_without_the_author() {
+        let author = Uuid::new_v4();
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        let carol = Uuid::new_v4();
+
+        let groups = vec![(GROUP_PROJECT, vec![author, alice, bob, carol]), (GROUP_ASSIGNEES, vec![carol])];
+        assert_eq!(
+            recipients(&[bob, author], &groups, author),
+            vec![
+                Recipient { user_id: bob, group_token: None },
+                Recipient { user_id: alice, group_token: Some(GROUP_PROJECT) },
+                Recipient { user_id: carol, group_token: Some(GROUP_PROJECT) },
+            ]
+        );
+        assert!(recipients(&[author], &[], author).is_empty());
+    }
+
+    #[test]
+    fn test_excerpt_is_cut() {
+        assert_eq!(excerpt("  short  "), "short");
+        let long = "a".repeat(EXCERPT_CHARS + 1);
+        assert_eq!(excerpt(&long), format!("{}…", "a".repeat(EXCERPT_CHARS)));
+    }
+}
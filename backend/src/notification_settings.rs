@@ -0,0 +1,217 @@
+// Which notifications a user gets, per project and event type. A level set
+// for the project wins over the user's default for the event type, and users
+// get everything they set nothing for. `mentions_only` lets only mentions
+// through, so "mentions everywhere, the rest only in two projects" is a
+// `mentions_only` default for every event type plus `all` in those projects.
+//
+// Every notification writer asks should_notify. Settings are cached per user
+// for CACHE_TTL and dropped when the user changes them on this server; other
+// servers pick changes up once their copy expires.
+
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::database::{
+    models::{NotificationEventType, NotificationLevel, NotificationSetting},
+    queries::NotificationSettingsQueries,
+};
+use crate::utils::errors::AppError;
+
+pub const CACHE_TTL: Duration = Duration::from_secs(60);
+// Entries are pruned once this many users are cached
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NotificationSettings {
+    pub defaults: HashMap<NotificationEventType, NotificationLevel>,
+    pub projects: HashMap<(Uuid, NotificationEventType), NotificationLevel>,
+}
+
+impl NotificationSettings {
+    pub fn default_level(&self, event_type: NotificationEventType) -> NotificationLevel {
+        self.defaults.get(&event_type).copied().unwrap_or(NotificationLevel::All)
+    }
+
+    pub fn level(&self, project_id: Uuid, event_type: NotificationEventType) -> NotificationLevel {
+        self.projects
+            .get(&(project_id, event_type))
+            .copied()
+            .unwrap_or_else(|| self.default_level(event_type))
+    }
+
+    pub fn allows(&self, project_id: Uuid, event_type: NotificationEventType) -> bool {
+        self.level(project_id, event_type).allows(event_type)
+    }
+
+    pub fn default_settings(&self) -> Vec<NotificationSetting> {
+        NotificationEventType::ALL
+            .into_iter()
+            .map(|event_type| NotificationSetting {
+                event_type,
+                level: self.default_level(event_type),
+                inherited: !self.defaults.contains_key(&event_type),
+            })
+            .collect()
+    }
+
+    pub fn project_settings(&self, project_id: Uuid) -> Vec<NotificationSetting> {
+        NotificationEventType::ALL
+            .into_iter()
+            .map(|event_type| NotificationSetting {
+                event_type,
+                level: self.level(project_id, event_type),
+                inherited: !self.projects.contains_key(&(project_id, event_type)),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SettingsCache {
+    entries: Mutex<HashMap<Uuid, (Instant, Arc<NotificationSettings>)>>,
+}
+
+impl SettingsCache {
+    pub fn get(&self, user_id: Uuid) -> Option<Arc<NotificationSettings>> {
+        self.get_at(user_id, Instant::now())
+    }
+
+    fn get_at(&self, user_id: Uuid, now: Instant) -> Option<Arc<NotificationSettings>> {
+        let entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries
+            .get(&user_id)
+            .filter(|(cached_at, _)| now.duration_since(*cached_at) < CACHE_TTL)
+            .map(|(_, settings)| settings.clone())
+    }
+
+    pub fn insert(&self, user_id: Uuid, settings: Arc<NotificationSettings>) {
+        self.insert_at(user_id, settings, Instant::now())
+    }
+
+    fn insert_at(&self, user_id: Uuid, settings: Arc<NotificationSettings>, now: Instant) {
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if entries.len() >= PRUNE_THRESHOLD {
+            entries.retain(|_, (cached_at, _)| now.duration_since(*cached_at) < CACHE_TTL);
+        }
+        entries.insert(user_id, (now, settings));
+    }
+
+    pub fn forget(&self, user_id: Uuid) {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&user_id);
+    }
+}
+
+pub fn settings_cache() -> &'static SettingsCache {
+    static INSTANCE: OnceLock<SettingsCache> = OnceLock::new();
+    INSTANCE.get_or_init(SettingsCache::default)
+}
+
+pub async fn load(pool: &PgPool, user_id: Uuid) -> Result<Arc<NotificationSettings>, AppError> {
+    if let Some(settings) = settings_cache().get(user_id) {
+        return Ok(settings);
+    }
+
+    let settings = Arc::new(NotificationSettings {
+        defaults: NotificationSettingsQueries::get_defaults(pool, user_id).await?.into_iter().collect(),
+        projects: NotificationSettingsQueries::get_project_settings(pool, user_id)
+            .await?
+            .into_iter()
+            .map(|(project_id, event_type, level)| ((project_id, event_type), level))
+            .collect(),
+    });
+    settings_cache().insert(user_id, settings.clone());
+    Ok(settings)
+}
+
+// Whether the user hears about an `event_type` event in the project
+pub async fn should_notify(
+    pool: &PgPool,
+    user_id: Uuid,
+    project_id: Uuid,
+    event_type: NotificationEventType,
+) -> Result<bool, AppError> {
+    Ok(load(pool, user_id).await?.allows(project_id, event_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVELS: [NotificationLevel; 3] = [NotificationLevel::All, NotificationLevel::MentionsOnly, NotificationLevel::None];
+
+    #[test]
+    fn test_levels_by_event_type() {
+        for level in LEVELS {
+            for event_type in NotificationEventType::ALL {
+                let expected = match level {
+                    NotificationLevel::All => true,
+                    NotificationLevel::MentionsOnly => event_type == NotificationEventType::Mention,
+                    NotificationLevel::None => false,
+                };
+                assert_eq!(level.allows(event_type), expected, "{:?} {:?}", level, event_type);
+            }
+        }
+    }
+
+    #[test]
+    fn test_project_levels_override_defaults() {
+        let project = Uuid::new_v4();
+        let other = Uuid::new_v4();
+
+        // Nothing set: everything
+        let settings = NotificationSettings::default();
+        assert!(NotificationEventType::ALL.into_iter().all(|event_type| settings.allows(project, event_type)));
+
+        for default in LEVELS {
+            for project_level in LEVELS {
+                for event_type in NotificationEventType::ALL {
+                    let settings = NotificationSettings {
+                        defaults: NotificationEventType::ALL.into_iter().map(|event_type| (event_type, default)).collect(),
+                        projects: [((project, event_type), project_level)].into(),
+                    };
+                    assert_eq!(settings.allows(project, event_type), project_level.allows(event_type));
+                    assert_eq!(settings.allows(other, event_type), default.allows(event_type));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_settings_report_what_they_inherit() {
+        let project = Uuid::new_v4();
+        let settings = NotificationSettings {
+            defaults: [(NotificationEventType::Comment, NotificationLevel::MentionsOnly)].into(),
+            projects: [((project, NotificationEventType::Comment), NotificationLevel::All)].into(),
+        };
+
+        let defaults = settings.default_settings();
+        assert_eq!(defaults.len(), NotificationEventType::ALL.len());
+        let comment = defaults.iter().find(|setting| setting.event_type == NotificationEventType::Comment).unwrap();
+        assert_eq!((comment.level, comment.inherited), (NotificationLevel::MentionsOnly, false));
+
+        let in_project = settings.project_settings(project);
+        let comment = in_project.iter().find(|setting| setting.event_type == NotificationEventType::Comment).unwrap();
+        assert_eq!((comment.level, comment.inherited), (NotificationLevel::All, false));
+        let elsewhere = settings.project_settings(Uuid::new_v4());
+        let comment = elsewhere.iter().find(|setting| setting.event_type == NotificationEventType::Comment).unwrap();
+        assert_eq!((comment.level, comment.inherited), (NotificationLevel::MentionsOnly, true));
+    }
+
+    #[test]
+    fn test_cache_expires_and_forgets() {
+        let cache = SettingsCache::default();
+        let user_id = Uuid::new_v4();
+        let start = Instant::now();
+
+        cache.insert_at(user_id, Arc::new(NotificationSettings::default()), start);
+        assert!(cache.get_at(user_id, start + CACHE_TTL / 2).is_some());
+        assert!(cache.get_at(user_id, start + CACHE_TTL).is_none());
+
+        cache.insert_at(user_id, Arc::new(NotificationSettings::default()), start);
+        cache.forget(user_id);
+        assert!(cache.get_at(user_id, start).is_none());
+    }
+}
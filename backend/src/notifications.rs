@@ -0,0 +1,305 @@
+// Personal notifications. Each one is pushed to the user's WebSocket connection
+// and stored as a notification row, so users who are offline still see it.
+// Users who turned a kind off for the project (see notification_settings) get
+// neither.
+
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::database::{
+    models::{NotificationEventType, NotificationKind, Task, UserNotification, UserSummary},
+    queries::{NotificationQueries, ProjectQueries},
+};
+use crate::notification_settings;
+use crate::utils::errors::AppError;
+use crate::websocket::events::{CommentNotificationData, TaskEventData, WebSocketEvent};
+
+// Comments on a task are counted into the same unread notification as long as
+// each follows the previous one within this many seconds
+const COMMENT_BATCH_SECONDS: i64 = 60;
+// How long a comment notification push waits for further comments
+const COMMENT_PUSH_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[derive(Debug, Default, PartialEq)]
+pub struct AssignmentChange {
+    pub assigned: Option<Uuid>,
+    pub unassigned: Option<Uuid>,
+}
+
+// Who to tell about a change of assignee. Users are never notified about
+// their own actions, e.g. assigning a task to themselves.
+pub fn assignment_change(previous: Option<Uuid>, current: Option<Uuid>, actor_id: Uuid) -> AssignmentChange {
+    if previous == current {
+        return AssignmentChange::default();
+    }
+
+    AssignmentChange {
+        assigned: current.filter(|user_id| *user_id != actor_id),
+        unassigned: previous.filter(|user_id| *user_id != actor_id),
+    }
+}
+
+// Call after a task update with the assignee it had before. Shared by every
+// handler that can change assignees; failures are logged, not returned.
+pub async fn notify_assignment_change(
+    app_state: &crate::AppState,
+    previous_assignee: Option<Uuid>,
+    task: &Task,
+    actor: &UserSummary,
+) {
+    let change = assignment_change(previous_assignee, task.assigned_to, actor.id);
+
+    if let Some(user_id) = change.assigned {
+        let result = notify(app_state, user_id, NotificationKind::TaskAssigned, WebSocketEvent::TaskAssignedToYou, task, actor).await;
+        if let Err(e) = result {
+            warn!("Failed to notify user {} about task {}: {}", user_id, task.id, e);
+        }
+    }
+    if let Some(user_id) = change.unassigned {
+        let result = notify(app_state, user_id, NotificationKind::TaskUnassigned, WebSocketEvent::TaskUnassigned, task, actor).await;
+        if let Err(e) = result {
+            warn!("Failed to notify user {} about task {}: {}", user_id, task.id, e);
+        }
+    }
+}
+
+async fn notify(
+    app_state: &crate::AppState,
+    user_id: Uuid,
+    kind: NotificationKind,
+    event: fn(TaskEventData) -> WebSocketEvent,
+    task: &Task,
+    actor: &UserSummary,
+) -> Result<(), AppError> {
+    let pool = app_state.database.pool();
+    if !notification_settings::should_notify(pool, user_id, task.project_id, NotificationEventType::Assignment).await? {
+        return Ok(());
+    }
+
+    NotificationQueries::create_notification(
+        pool,
+        user_id,
+        kind,
+        task.project_id,
+        Some(task.id),
+        Some(actor.id),
+    ).await?;
+
+    let data = TaskEventData {
+        task: task.clone(),
+        project_id: task.project_id,
+        user: actor.clone(),
+        column: None,
+        client_mutation_id: None,
+    };
+    app_state.websocket.send_to_user(user_id, event(data)).await;
+
+    Ok(())
+}
+
+// Who hears about a new comment: the task's assignee and creator, except the
+// comment's author
+pub fn comment_recipients(assigned_to: Option<Uuid>, created_by: Uuid, author_id: Uuid) -> Vec<Uuid> {
+    let mut recipients: Vec<Uuid> = [assigned_to, Some(created_by)]
+        .into_iter()
+        .flatten()
+        .filter(|user_id| *user_id != author_id)
+        .collect();
+    recipients.dedup();
+    recipients
+}
+
+// Whether another `kind` event on the task at `now` is counted into `latest`,
+// the user's most recent notification of that kind about the task. Once it's
+// been read, the next event starts a new notification.
+pub fn collapses_into(latest: &UserNotification, kind: NotificationKind, task_id: Uuid, now: DateTime<Utc>) -> bool {
+    kind == NotificationKind::TaskCommented
+        && latest.kind == kind
+        && latest.task_id == Some(task_id)
+        && latest.read_at.is_none()
+        && now - latest.updated_at < Duration::seconds(COMMENT_BATCH_SECONDS)
+}
+
+// Call after a comment was created; failures are logged, not returned
+pub async fn notify_comment(app_state: &crate::AppState, task: &Task, author: &UserSummary) {
+    for user_id in comment_recipients(task.assigned_to, task.created_by, author.id) {
+        if let Err(e) = notify_commented(app_state, user_id, task, author).await {
+            warn!("Failed to notify user {} about comments on task {}: {}", user_id, task.id, e);
+        }
+    }
+}
+
+async fn notify_commented(
+    app_state: &crate::AppState,
+    user_id: Uuid,
+    task: &Task,
+    author: &UserSummary,
+) -> Result<(), AppError> {
+    let pool = app_state.database.pool();
+    // The creator may have left the project since
+    if ProjectQueries::get_user_project_role(pool, task.project_id, user_id).await?.is_none() {
+        return Ok(());
+    }
+    if !notification_settings::should_notify(pool, user_id, task.project_id, NotificationEventType::Comment).await? {
+        return Ok(());
+    }
+
+    let kind = NotificationKind::TaskCommented;
+    let latest = NotificationQueries::get_latest_task_notification(pool, user_id, kind, task.id).await?;
+    let bumped = match latest {
+        Some(latest) if collapses_into(&latest, kind, task.id, Utc::now()) => {
+            NotificationQueries::bump_notification(pool, latest.id, author.id).await?
+        }
+        _ => None,
+    };
+    let notification = match bumped {
+        Some(notification) => notification,
+        None => {
+            NotificationQueries::create_notification(pool, user_id, kind, task.project_id, Some(task.id), Some(author.id)).await?
+        }
+    };
+
+    let notification_id = notification.id;
+    let event = WebSocketEvent::TaskCommentedOn(CommentNotificationData {
+        notification,
+        task: task.clone(),
+        user: author.clone(),
+    });
+    push_later(app_state, user_id, notification_id, event);
+
+    Ok(())
+}
+
+// Latest event per notification that is waiting to be pushed
+fn pending_pushes() -> &'static Mutex<HashMap<Uuid, WebSocketEvent>> {
+    static PENDING: OnceLock<Mutex<HashMap<Uuid, WebSocketEvent>>> = OnceLock::new();
+    PENDING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Pushes the event after COMMENT_PUSH_DELAY. Events for the same notification
+// that arrive in the meantime replace it, so only the latest count is sent.
+fn push_later(app_state: &crate::AppState, user_id: Uuid, notification_id: Uuid, event: WebSocketEvent) {
+    let queued = pending_pushes()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(notification_id, event)
+        .is_some();
+    if queued {
+        return;
+    }
+
+    let websocket = app_state.websocket.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(COMMENT_PUSH_DELAY).await;
+        let event = pending_pushes()
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&notification_id);
+        if let Some(event) = event {
+            websocket.send_to_user(user_id, event).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assignment_change() {
+        let actor = Uuid::new_v4();
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+
+        assert_eq!(
+            assignment_change(None, Some(alice), actor),
+            AssignmentChange { assigned: Some(alice), unassigned: None }
+        );
+        assert_eq!(
+            assignment_change(Some(alice), Some(bob), actor),
+            AssignmentChange { assigned: Some(bob), unassigned: Some(alice) }
+        );
+        assert_eq!(
+            assignment_change(Some(alice), None, actor),
+            AssignmentChange { assigned: None, unassigned: Some(alice) }
+        );
+        assert_eq!(assignment_change(Some(alice), Some(alice), actor), AssignmentChange::default());
+    }
+
+    #[test]
+    fn test_own_actions_do_not_notify() {
+        let actor = Uuid::new_v4();
+        let alice = Uuid::new_v4();
+
+        assert_eq!(assignment_change(None, Some(actor), actor), AssignmentChange::default());
+        assert_eq!(
+            assignment_change(Some(actor), Some(alice), actor),
+            AssignmentChange { assigned: Some(alice), unassigned: None }
+        );
+    }
+
+    #[test]
+    fn test_comment_recipients() {
+        let author = Uuid::new_v4();
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+
+        assert_eq!(comment_recipients(Some(alice), bob, author), vec![alice, bob]);
+        assert_eq!(comment_recipients(Some(alice), alice, author), vec![alice]);
+        assert_eq!(comment_recipients(None, author, author), Vec::<Uuid>::new());
+        assert_eq!(comment_recipients(Some(author), bob, author), vec![bob]);
+    }
+
+    fn comment_notification(task_id: Uuid, updated_at: DateTime<Utc>) -> UserNotification {
+        UserNotification {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            kind: NotificationKind::TaskCommented,
+            project_id: Some(Uuid::new_v4()),
+            task_id: Some(task_id),
+            team_id: None,
+            audit_export_id: None,
+            actor_id: Some(Uuid::new_v4()),
+            count: 1,
+            read_at: None,
+            created_at: updated_at,
+            updated_at,
+        }
+    }
+
+    #[test]
+    fn test_comments_collapse_within_the_window() {
+        let task_id = Uuid::new_v4();
+        let now = Utc::now();
+        let latest = comment_notification(task_id, now - Duration::seconds(30));
+
+        assert!(collapses_into(&latest, NotificationKind::TaskCommented, task_id, now));
+        // Too late, or about another task
+        assert!(!collapses_into(&latest, NotificationKind::TaskCommented, task_id, now + Duration::seconds(31)));
+        assert!(!collapses_into(&latest, NotificationKind::TaskCommented, Uuid::new_v4(), now));
+    }
+
+    #[test]
+    fn test_reading_starts_a_new_notification() {
+        let task_id = Uuid::new_v4();
+        let now = Utc::now();
+        let mut latest = comment_notification(task_id, now - Duration::seconds(5));
+        latest.read_at = Some(now - Duration::seconds(1));
+
+        assert!(!collapses_into(&latest, NotificationKind::TaskCommented, task_id, now));
+    }
+
+    #[test]
+    fn test_only_comment_notifications_collapse() {
+        let task_id = Uuid::new_v4();
+        let now = Utc::now();
+        let mut latest = comment_notification(task_id, now);
+        latest.kind = NotificationKind::TaskAssigned;
+
+        assert!(!collapses_into(&latest, NotificationKind::TaskAssigned, task_id, now));
+        assert!(!collapses_into(&latest, NotificationKind::TaskCommented, task_id, now));
+    }
+}
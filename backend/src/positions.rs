@@ -0,0 +1,63 @@
+// Task positions are dense 1..n sequences per (project, status) column. Moves
+// keep both affected columns dense; deletes and status changes through task
+// updates leave gaps behind, which are compacted once they grow too large.
+// Boards don't have columns of their own yet, so all of a project's boards
+// share these positions.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::database::{models::{ColumnOrder, NudgeDirection}, queries::TaskQueries};
+use crate::utils::errors::AppError;
+
+// Unused positions a column may accumulate before it is compacted
+const GAP_THRESHOLD: i64 = 50;
+
+// Compacts the project's columns if any has more than GAP_THRESHOLD gaps.
+// Returns the new order when positions changed.
+pub async fn compact_if_fragmented(pool: &PgPool, project_id: Uuid) -> Result<Option<Vec<ColumnOrder>>, AppError> {
+    if TaskQueries::max_position_gap(pool, project_id).await? <= GAP_THRESHOLD {
+        return Ok(None);
+    }
+
+    let (updated, columns) = TaskQueries::compact_positions(pool, project_id).await?;
+    Ok((updated > 0).then_some(columns))
+}
+
+// Position a nudge moves the task to, given its column in order: one place up
+// or down, or the same place in the neighbouring column (clamped to its end by
+// the move). None when the task is already at the top or bottom.
+pub fn nudged_position(column: &[Uuid], task_id: Uuid, direction: NudgeDirection) -> Option<i32> {
+    let index = column.iter().position(|id| *id == task_id)?;
+    let position = match direction {
+        NudgeDirection::Up => index.checked_sub(1)?,
+        NudgeDirection::Down if index + 1 < column.len() => index + 1,
+        NudgeDirection::Down => return None,
+        NudgeDirection::Left | NudgeDirection::Right => index,
+    };
+    i32::try_from(position + 1).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nudged_position_within_column() {
+        let column: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+
+        assert_eq!(nudged_position(&column, column[1], NudgeDirection::Up), Some(1));
+        assert_eq!(nudged_position(&column, column[1], NudgeDirection::Down), Some(3));
+        assert_eq!(nudged_position(&column, column[0], NudgeDirection::Up), None);
+        assert_eq!(nudged_position(&column, column[2], NudgeDirection::Down), None);
+    }
+
+    #[test]
+    fn test_nudged_position_across_columns_keeps_the_place() {
+        let column: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+
+        assert_eq!(nudged_position(&column, column[2], NudgeDirection::Right), Some(3));
+        assert_eq!(nudged_position(&column, column[0], NudgeDirection::Left), Some(1));
+        assert_eq!(nudged_position(&column, Uuid::new_v4(), NudgeDirection::Left), None);
+    }
+}
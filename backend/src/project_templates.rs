@@ -0,0 +1,124 @@
+// Project templates. Publishing takes a snapshot of a project's structure:
+// its look, workflow, onboarding checklist, staleness rules and boards, but
+// no tasks or members. Boards use the board export format (board_export.rs),
+// so assignee swimlanes for specific users are left out, as they would be
+// meaningless in another team. New projects created with a `template_id`
+// start from the snapshot.
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::board_export::BoardExport;
+use crate::database::{
+    models::{CreateProjectRequest, OnboardingTemplate, ProjectWorkflow, StalenessRules},
+    queries::{BoardQueries, ProjectQueries},
+};
+use crate::utils::errors::AppError;
+
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectSnapshot {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub workflow: ProjectWorkflow,
+    #[serde(default)]
+    pub onboarding_template: OnboardingTemplate,
+    #[serde(default)]
+    pub staleness: StalenessRules,
+    // The default board comes first and replaces the new project's default board
+    pub boards: Vec<BoardExport>,
+}
+
+impl ProjectSnapshot {
+    // Fills in what the create request leaves unset
+    pub fn apply_defaults(&self, request: &mut CreateProjectRequest) {
+        if request.description.is_none() {
+            request.description = self.description.clone();
+        }
+        if request.color.is_none() {
+            request.color = self.color.clone();
+        }
+        if request.icon.is_none() {
+            request.icon = self.icon.clone();
+        }
+    }
+}
+
+pub async fn snapshot(pool: &PgPool, project_id: Uuid) -> Result<ProjectSnapshot, AppError> {
+    let project = ProjectQueries::get_project_by_id(pool, project_id).await?;
+    // Ordered default board first
+    let boards = BoardQueries::get_project_boards(pool, project_id).await?;
+
+    Ok(ProjectSnapshot {
+        version: FORMAT_VERSION,
+        description: project.description,
+        color: project.color,
+        icon: project.icon,
+        workflow: ProjectQueries::get_project_workflow(pool, project_id).await?,
+        onboarding_template: ProjectQueries::get_onboarding_template(pool, project_id).await?,
+        staleness: ProjectQueries::get_staleness_rules(pool, project_id).await?,
+        boards: boards.iter().map(BoardExport::from_board).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::TaskStatus;
+
+    fn request() -> CreateProjectRequest {
+        CreateProjectRequest {
+            name: "Launch".to_string(),
+            description: None,
+            team_id: Uuid::new_v4(),
+            color: Some("#3b82f6".to_string()),
+            icon: None,
+            auto_add_members: false,
+            template_id: None,
+        }
+    }
+
+    #[test]
+    fn test_request_values_win_over_the_template() {
+        let snapshot = ProjectSnapshot {
+            version: FORMAT_VERSION,
+            description: Some("From the template".to_string()),
+            color: Some("#ef4444".to_string()),
+            icon: Some("🚀".to_string()),
+            workflow: ProjectWorkflow::default(),
+            onboarding_template: OnboardingTemplate::default(),
+            staleness: StalenessRules::default(),
+            boards: Vec::new(),
+        };
+
+        let mut request = request();
+        snapshot.apply_defaults(&mut request);
+        assert_eq!(request.description.as_deref(), Some("From the template"));
+        assert_eq!(request.color.as_deref(), Some("#3b82f6"));
+        assert_eq!(request.icon.as_deref(), Some("🚀"));
+    }
+
+    #[test]
+    fn test_snapshot_documents_round_trip() {
+        let json = serde_json::json!({
+            "version": 1,
+            "workflow": { "transitions": [{ "from": "Todo", "to": ["InProgress"] }] },
+            "boards": [{ "version": 1, "name": "Main", "columns": ["Todo", "Done"] }]
+        });
+        let snapshot: ProjectSnapshot = serde_json::from_value(json).unwrap();
+        assert!(snapshot.description.is_none());
+        assert!(!snapshot.workflow.allows(TaskStatus::Todo, TaskStatus::Done));
+        assert_eq!(snapshot.boards[0].columns, vec!["Todo", "Done"]);
+
+        let again: ProjectSnapshot = serde_json::from_value(serde_json::to_value(&snapshot).unwrap()).unwrap();
+        assert_eq!(again, snapshot);
+    }
+}
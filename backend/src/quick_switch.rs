@@ -0,0 +1,169 @@
+// The cmd-K switcher: teams, projects and recently opened tasks the user can
+// get to, ranked by how well their name matches and how recently the user was
+// there. QuickSwitchQueries finds the candidates with the trigram indexes;
+// `rank` orders them here.
+//
+// The match scores from 0 to 1: the whole name 1, a prefix 0.9, the start of
+// a word 0.75, anywhere in the name 0.6, otherwise half the trigram
+// similarity, which catches typos. Recency halves every RECENCY_HALF_LIFE.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use std::cmp::Ordering;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::database::{
+    models::{QuickSwitchCandidate, QuickSwitchItem, QuickSwitchKind},
+    queries::QuickSwitchQueries,
+};
+
+pub const MAX_RESULTS: usize = 15;
+// Tasks opened longer ago than this aren't offered
+pub const RECENT_TASKS: Duration = Duration::days(30);
+const RECENCY_HALF_LIFE: Duration = Duration::days(3);
+
+const MATCH_WEIGHT: f64 = 0.7;
+const RECENCY_WEIGHT: f64 = 0.3;
+
+// Remembers that the user opened the project. Failing to doesn't fail the
+// page that opened it.
+pub async fn record_access(pool: &PgPool, user_id: Uuid, project_id: Uuid) {
+    if let Err(e) = QuickSwitchQueries::record_project_access(pool, user_id, project_id).await {
+        warn!("Failed to record access to project {} by user {}: {}", project_id, user_id, e);
+    }
+}
+
+pub fn match_score(name: &str, query: &str, similarity: f32) -> f64 {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return 1.0;
+    }
+    let name = name.to_lowercase();
+    let fuzzy = f64::from(similarity.clamp(0.0, 1.0)) * 0.5;
+
+    let tier: f64 = if name == query {
+        1.0
+    } else if name.starts_with(&query) {
+        0.9
+    } else if name
+        .match_indices(&query)
+        .any(|(index, _)| !name[..index].chars().next_back().is_some_and(char::is_alphanumeric))
+    {
+        0.75
+    } else if name.contains(&query) {
+        0.6
+    } else {
+        0.0
+    };
+    tier.max(fuzzy)
+}
+
+// 1 right after the visit, 0 for places the user never opened
+pub fn recency_score(last_accessed_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> f64 {
+    let Some(at) = last_accessed_at else {
+        return 0.0;
+    };
+    let age = (now - at).num_seconds().max(0) as f64;
+    0.5_f64.powf(age / RECENCY_HALF_LIFE.num_seconds() as f64)
+}
+
+pub fn score(candidate: &QuickSwitchCandidate, query: &str, now: DateTime<Utc>) -> f64 {
+    MATCH_WEIGHT * match_score(&candidate.item.name, query, candidate.similarity)
+        + RECENCY_WEIGHT * recency_score(candidate.last_accessed_at, now)
+}
+
+fn kind_order(kind: QuickSwitchKind) -> u8 {
+    match kind {
+        QuickSwitchKind::Project => 0,
+        QuickSwitchKind::Team => 1,
+        QuickSwitchKind::Task => 2,
+    }
+}
+
+// Best first, at most MAX_RESULTS. Ties go to projects, then teams, then by name.
+pub fn rank(candidates: Vec<QuickSwitchCandidate>, query: &str, now: DateTime<Utc>) -> Vec<QuickSwitchItem> {
+    let mut scored: Vec<(f64, QuickSwitchItem)> = candidates
+        .into_iter()
+        .map(|candidate| (score(&candidate, query, now), candidate.item))
+        .filter(|(score, _)| *score > 0.0)
+        .collect();
+
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .partial_cmp(a_score)
+            .unwrap_or(Ordering::Equal)
+            .then(kind_order(a.kind).cmp(&kind_order(b.kind)))
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    scored.truncate(MAX_RESULTS);
+    scored.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(kind: QuickSwitchKind, name: &str, last_accessed_at: Option<DateTime<Utc>>) -> QuickSwitchCandidate {
+        QuickSwitchCandidate {
+            item: QuickSwitchItem { kind, id: Uuid::new_v4(), name: name.to_string(), color: None, icon: None, context: None },
+            similarity: 0.0,
+            last_accessed_at,
+        }
+    }
+
+    fn names(items: &[QuickSwitchItem]) -> Vec<&str> {
+        items.iter().map(|item| item.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_match_tiers() {
+        assert_eq!(match_score("Website", "website", 1.0), 1.0);
+        assert_eq!(match_score("Website", "Web", 0.6), 0.9);
+        assert_eq!(match_score("Mobile website", "web", 0.6), 0.75);
+        assert_eq!(match_score("Cobweb", "web", 0.6), 0.6);
+        // A typo only matches through the trigrams
+        assert_eq!(match_score("Website", "wesbite", 0.5), 0.25);
+        assert_eq!(match_score("Website", "  ", 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_recency_halves() {
+        let now = Utc::now();
+        assert_eq!(recency_score(Some(now), now), 1.0);
+        assert_eq!(recency_score(Some(now - RECENCY_HALF_LIFE), now), 0.5);
+        assert_eq!(recency_score(None, now), 0.0);
+    }
+
+    #[test]
+    fn test_recent_place_outranks_better_match() {
+        let now = Utc::now();
+        let ranked = rank(
+            vec![
+                candidate(QuickSwitchKind::Project, "Web shop", None),
+                candidate(QuickSwitchKind::Project, "Mobile web", Some(now - Duration::hours(1))),
+                candidate(QuickSwitchKind::Task, "Cobweb cleanup", None),
+            ],
+            "web",
+            now,
+        );
+        assert_eq!(names(&ranked), ["Mobile web", "Web shop", "Cobweb cleanup"]);
+    }
+
+    #[test]
+    fn test_empty_query_lists_recent_places_first() {
+        let now = Utc::now();
+        let mut candidates = vec![
+            candidate(QuickSwitchKind::Team, "Platform", None),
+            candidate(QuickSwitchKind::Project, "Billing", None),
+            candidate(QuickSwitchKind::Task, "Fix login", Some(now - Duration::days(1))),
+        ];
+        candidates.extend((0..20).map(|i| candidate(QuickSwitchKind::Project, &format!("Old {:02}", i), None)));
+
+        let ranked = rank(candidates, "", now);
+        assert_eq!(ranked.len(), MAX_RESULTS);
+        // Unvisited ties: projects before teams, by name
+        assert_eq!(names(&ranked[..3]), ["Fix login", "Billing", "Old 00"]);
+        assert!(!names(&ranked).contains(&"Platform"));
+    }
+}
@@ -0,0 +1,191 @@
+// Instance-level usage limits. Defaults come from the environment and can be
+// overridden per team by an instance admin (`team_limits` table).
+
+use serde::Serialize;
+use sqlx::PgPool;
+use std::env;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+use crate::database::{models::TeamLimitOverrides, queries::QuotaQueries};
+use crate::utils::errors::AppError;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct Limits {
+    pub max_teams_per_user: i64,
+    pub max_projects_per_team: i64,
+    pub max_tasks_per_project: i64,
+    pub max_members_per_team: i64,
+    pub max_attachment_bytes_per_project: i64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_teams_per_user: 50,
+            max_projects_per_team: 500,
+            max_tasks_per_project: 10_000,
+            max_members_per_team: 1_000,
+            max_attachment_bytes_per_project: 1024 * 1024 * 1024, // 1GB
+        }
+    }
+}
+
+fn env_limit(name: &str, default: i64) -> i64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse::<i64>().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(default)
+}
+
+impl Limits {
+    pub fn from_env() -> Self {
+        let defaults = Limits::default();
+        Limits {
+            max_teams_per_user: env_limit("MAX_TEAMS_PER_USER", defaults.max_teams_per_user),
+            max_projects_per_team: env_limit("MAX_PROJECTS_PER_TEAM", defaults.max_projects_per_team),
+            max_tasks_per_project: env_limit("MAX_TASKS_PER_PROJECT", defaults.max_tasks_per_project),
+            max_members_per_team: env_limit("MAX_MEMBERS_PER_TEAM", defaults.max_members_per_team),
+            max_attachment_bytes_per_project: env_limit(
+                "MAX_ATTACHMENT_BYTES_PER_PROJECT",
+                defaults.max_attachment_bytes_per_project,
+            ),
+        }
+    }
+
+    // Instance defaults, read from the environment once
+    pub fn instance() -> Self {
+        static INSTANCE: OnceLock<Limits> = OnceLock::new();
+        *INSTANCE.get_or_init(Limits::from_env)
+    }
+
+    // Team overrides take precedence over the instance defaults
+    pub fn with_overrides(self, overrides: &TeamLimitOverrides) -> Self {
+        Limits {
+            max_teams_per_user: self.max_teams_per_user,
+            max_projects_per_team: overrides.max_projects.map(i64::from).unwrap_or(self.max_projects_per_team),
+            max_tasks_per_project: overrides.max_tasks_per_project.map(i64::from).unwrap_or(self.max_tasks_per_project),
+            max_members_per_team: overrides.max_members.map(i64::from).unwrap_or(self.max_members_per_team),
+            max_attachment_bytes_per_project: overrides
+                .max_attachment_bytes_per_project
+                .unwrap_or(self.max_attachment_bytes_per_project),
+        }
+    }
+}
+
+pub async fn team_limits(pool: &PgPool, team_id: Uuid) -> Result<Limits, AppError> {
+    let overrides = QuotaQueries::get_team_limits(pool, team_id).await?;
+    Ok(Limits::instance().with_overrides(&overrides))
+}
+
+fn ensure_below(current: i64, limit: i64, message: impl FnOnce(i64) -> String) -> Result<(), AppError> {
+    if current >= limit {
+        return Err(AppError::QuotaExceeded(message(limit)));
+    }
+    Ok(())
+}
+
+pub async fn check_can_create_team(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+    let count = QuotaQueries::count_user_teams(pool, user_id).await?;
+    ensure_below(count, Limits::instance().max_teams_per_user, |limit| {
+        format!("You have reached the limit of {} teams", limit)
+    })
+}
+
+pub async fn check_can_create_project(pool: &PgPool, team_id: Uuid) -> Result<(), AppError> {
+    let limits = team_limits(pool, team_id).await?;
+    let count = QuotaQueries::count_team_projects(pool, team_id).await?;
+    ensure_below(count, limits.max_projects_per_team, |limit| {
+        format!("Team has reached the limit of {} projects", limit)
+    })
+}
+
+pub async fn check_can_create_task(pool: &PgPool, team_id: Uuid, project_id: Uuid) -> Result<(), AppError> {
+    check_can_create_tasks(pool, team_id, project_id, 1).await
+}
+
+// For creating several tasks at once, e.g. a new member's onboarding checklist
+pub async fn check_can_create_tasks(pool: &PgPool, team_id: Uuid, project_id: Uuid, new_tasks: i64) -> Result<(), AppError> {
+    let limits = team_limits(pool, team_id).await?;
+    let count = QuotaQueries::count_project_tasks(pool, project_id).await?;
+    ensure_below(count + new_tasks - 1, limits.max_tasks_per_project, |limit| {
+        format!("Project has reached the limit of {} tasks", limit)
+    })
+}
+
+pub async fn check_can_add_team_member(pool: &PgPool, team_id: Uuid) -> Result<(), AppError> {
+    let limits = team_limits(pool, team_id).await?;
+    let count = QuotaQueries::count_team_members(pool, team_id).await?;
+    check_team_member_count(count, &limits)
+}
+
+// For callers that count the members themselves, e.g. inside a transaction
+pub fn check_team_member_count(count: i64, limits: &Limits) -> Result<(), AppError> {
+    ensure_below(count, limits.max_members_per_team, |limit| {
+        format!("Team has reached the limit of {} members", limit)
+    })
+}
+
+// For upload handlers: `used_bytes` is the project's current storage. An
+// upload that doesn't fit is a 413 that says how much is left.
+pub async fn check_attachment_storage(
+    pool: &PgPool,
+    team_id: Uuid,
+    used_bytes: i64,
+    upload_bytes: i64,
+) -> Result<(), AppError> {
+    let limits = team_limits(pool, team_id).await?;
+    ensure_storage_fits(used_bytes, upload_bytes, limits.max_attachment_bytes_per_project)
+}
+
+fn ensure_storage_fits(used_bytes: i64, upload_bytes: i64, limit: i64) -> Result<(), AppError> {
+    if used_bytes + upload_bytes > limit {
+        return Err(AppError::PayloadTooLarge(format!(
+            "Project attachments use {} of {} bytes; the {} byte upload doesn't fit",
+            used_bytes, limit, upload_bytes
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_replace_only_set_limits() {
+        let defaults = Limits::default();
+        let overrides = TeamLimitOverrides {
+            max_tasks_per_project: Some(100_000),
+            ..Default::default()
+        };
+
+        let limits = defaults.with_overrides(&overrides);
+        assert_eq!(limits.max_tasks_per_project, 100_000);
+        assert_eq!(limits.max_projects_per_team, defaults.max_projects_per_team);
+        assert_eq!(limits.max_members_per_team, defaults.max_members_per_team);
+    }
+
+    #[test]
+    fn test_limit_message_includes_limit() {
+        assert!(ensure_below(499, 500, |limit| format!("limit of {}", limit)).is_ok());
+
+        match ensure_below(500, 500, |limit| format!("limit of {}", limit)) {
+            Err(AppError::QuotaExceeded(message)) => assert_eq!(message, "limit of 500"),
+            other => panic!("expected quota error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_storage_error_reports_current_usage() {
+        assert!(ensure_storage_fits(900, 100, 1000).is_ok());
+
+        match ensure_storage_fits(900, 101, 1000) {
+            Err(AppError::PayloadTooLarge(message)) => {
+                assert_eq!(message, "Project attachments use 900 of 1000 bytes; the 101 byte upload doesn't fit")
+            }
+            other => panic!("expected payload error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,435 @@
+// Scheduled project reports. Like the digest, a scheduler job re-enqueues
+// itself every few minutes and claims the reports that are due. Each one is
+// gathered from the stats queries, rendered to markdown by `render_report`
+// (pure, so it's tested without a database) and delivered by email, to Slack
+// and/or as a pinned system comment that replaces the previous one.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::database::{
+    connection::Database,
+    models::{Job, ProjectReport, ReportCadence, ReportSection, ReportTask, UserSummary},
+    queries::{IntegrationQueries, JobQueries, ProjectQueries, ReportQueries, TaskCommentQueries, TaskQueries, UserQueries},
+};
+use crate::email::{queue_email, templates::EmailTemplate};
+use crate::integrations::{app_base_url, slack::{self, blocks, SlackWebhookPayload}};
+use crate::jobs::{self, JobHandler};
+use crate::utils::errors::AppError;
+use crate::websocket::events::{CommentEventData, WebSocketEvent};
+use crate::websocket::handler::WebSocketState;
+
+pub const REPORT_SCHEDULER_JOB: &str = "report_scheduler";
+const SCHEDULER_INTERVAL_MINUTES: i64 = 15;
+// Longer lists end with "…and N more"
+const MAX_LISTED_TASKS: usize = 20;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberCounts {
+    pub user: UserSummary,
+    // Completed during the period
+    pub completed: usize,
+    pub open_tasks: i64,
+    pub overdue: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportAggregates {
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub completed: Vec<ReportTask>,
+    pub created: Vec<ReportTask>,
+    pub overdue: Vec<ReportTask>,
+    pub members: Vec<MemberCounts>,
+}
+
+pub fn period_length(cadence: ReportCadence) -> Duration {
+    match cadence {
+        ReportCadence::Daily => Duration::days(1),
+        ReportCadence::Weekly => Duration::days(7),
+    }
+}
+
+// Open and overdue counts come from the workload query; completions are
+// counted from the period's completed tasks
+pub async fn gather(
+    pool: &PgPool,
+    project_id: Uuid,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<ReportAggregates, AppError> {
+    let project = ProjectQueries::get_project_by_id(pool, project_id).await?;
+    let completed = ReportQueries::get_completed_tasks(pool, project_id, since).await?;
+    let created = ReportQueries::get_created_tasks(pool, project_id, since).await?;
+    let overdue = ReportQueries::get_overdue_tasks(pool, project_id).await?;
+
+    let mut completed_by: HashMap<Uuid, usize> = HashMap::new();
+    for task in &completed {
+        if let Some(assignee) = task.assigned_to {
+            *completed_by.entry(assignee).or_default() += 1;
+        }
+    }
+
+    let members = ProjectQueries::get_project_workload(pool, project_id, None, None)
+        .await?
+        .into_iter()
+        .map(|(member, user, workload)| MemberCounts {
+            completed: completed_by.get(&member.user_id).copied().unwrap_or(0),
+            user,
+            open_tasks: workload.open_tasks,
+            overdue: workload.overdue,
+        })
+        .collect();
+
+    Ok(ReportAggregates {
+        project_id,
+        project_name: project.name,
+        since,
+        until,
+        completed,
+        created,
+        overdue,
+        members,
+    })
+}
+
+// Markdown body of the report, with the sections in the order they were configured
+pub fn render_report(report: &ProjectReport, aggregates: &ReportAggregates, base_url: &str) -> String {
+    let timezone: Tz = report.timezone.parse().unwrap_or(Tz::UTC);
+    let date = |at: DateTime<Utc>| at.with_timezone(&timezone).format("%a %b %-d").to_string();
+
+    let mut parts = vec![
+        format!("## {}: {}", escape_markdown(&report.name), escape_markdown(&aggregates.project_name)),
+        format!("_{} – {}_", date(aggregates.since), date(aggregates.until)),
+    ];
+
+    for section in &report.sections {
+        let part = match section {
+            ReportSection::Completed => task_list("Completed", &aggregates.completed, aggregates.project_id, base_url, |_| None),
+            ReportSection::Created => task_list("Newly created", &aggregates.created, aggregates.project_id, base_url, |_| None),
+            ReportSection::Overdue => task_list("Overdue", &aggregates.overdue, aggregates.project_id, base_url, |task| {
+                task.due_date.map(|due| format!("due {}", date(due)))
+            }),
+            ReportSection::Members => member_list(&aggregates.members),
+        };
+        parts.push(part);
+    }
+
+    parts.join("\n\n")
+}
+
+fn task_list(
+    heading: &str,
+    tasks: &[ReportTask],
+    project_id: Uuid,
+    base_url: &str,
+    detail: impl Fn(&ReportTask) -> Option<String>,
+) -> String {
+    let mut lines = vec![format!("### {} ({})", heading, tasks.len())];
+    if tasks.is_empty() {
+        lines.push("None".to_string());
+    }
+    for task in tasks.iter().take(MAX_LISTED_TASKS) {
+        let link = format!(
+            "[#{} {}]({}/projects/{}/tasks/{})",
+            task.number,
+            escape_markdown(&task.title),
+            base_url,
+            project_id,
+            task.id
+        );
+        match detail(task) {
+            Some(detail) => lines.push(format!("- {} ({})", link, detail)),
+            None => lines.push(format!("- {}", link)),
+        }
+    }
+    if tasks.len() > MAX_LISTED_TASKS {
+        lines.push(format!("- …and {} more", tasks.len() - MAX_LISTED_TASKS));
+    }
+    lines.join("\n")
+}
+
+// Bullets rather than a table so the Slack version reads the same
+fn member_list(members: &[MemberCounts]) -> String {
+    let mut lines = vec!["### Per member".to_string()];
+    if members.is_empty() {
+        lines.push("None".to_string());
+    }
+    for member in members {
+        lines.push(format!(
+            "- {}: {} completed, {} open, {} overdue",
+            escape_markdown(&member.user.display_name),
+            member.completed,
+            member.open_tasks,
+            member.overdue
+        ));
+    }
+    lines.join("\n")
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '[' | ']' | '`' | '#') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Makes sure a scheduler run is queued at `run_at`, unless one is already pending
+pub async fn schedule(pool: &PgPool, run_at: DateTime<Utc>) -> Result<(), AppError> {
+    if !JobQueries::has_pending_job(pool, REPORT_SCHEDULER_JOB).await? {
+        jobs::enqueue_at(pool, REPORT_SCHEDULER_JOB, &json!({}), run_at).await?;
+    }
+    Ok(())
+}
+
+pub struct ReportSchedulerJob {
+    database: Database,
+    websocket: WebSocketState,
+}
+
+impl ReportSchedulerJob {
+    pub fn new(database: Database, websocket: WebSocketState) -> Self {
+        Self { database, websocket }
+    }
+
+    async fn deliver(&self, report: &ProjectReport, markdown: &str, base_url: &str) -> Result<(), AppError> {
+        let pool = self.database.pool();
+        let project = ProjectQueries::get_project_by_id(pool, report.project_id).await?;
+        let recipients = &report.recipients;
+
+        // Recipients who have since left the project are skipped
+        if !recipients.user_ids.is_empty() {
+            let members: HashSet<Uuid> = ProjectQueries::get_project_members(pool, report.project_id)
+                .await?
+                .into_iter()
+                .map(|(member, _)| member.user_id)
+                .collect();
+            let link = format!("{}/projects/{}", base_url, project.id);
+
+            for user_id in recipients.user_ids.iter().filter(|id| members.contains(id)) {
+                let user = UserQueries::get_user_by_id(pool, *user_id).await?;
+                if !user.is_active {
+                    continue;
+                }
+                queue_email(
+                    pool,
+                    &user.email,
+                    Some(user.id),
+                    EmailTemplate::ProjectReport,
+                    json!({
+                        "name": user.display_name,
+                        "report": report.name,
+                        "project": project.name,
+                        "summary": markdown,
+                        "link": link,
+                    }),
+                ).await?;
+            }
+        }
+
+        if recipients.slack {
+            let integration = IntegrationQueries::get_integration(pool, project.id, slack::INTEGRATION_KIND).await?;
+            if integration.is_some_and(|integration| integration.enabled) {
+                let message = blocks::format_report(&project, &report.name, markdown, base_url);
+                let payload = SlackWebhookPayload { project_id: project.id, message, test: false };
+                jobs::enqueue_ordered(pool, slack::SLACK_WEBHOOK_JOB, &slack::ordering_key(project.id), &payload).await?;
+            }
+        }
+
+        if let Some(task_id) = recipients.comment_task_id {
+            self.post_comment(report, task_id, markdown).await?;
+        }
+
+        Ok(())
+    }
+
+    // Posted in the name of whoever set the report up, as long as they can still comment
+    async fn post_comment(&self, report: &ProjectReport, task_id: Uuid, markdown: &str) -> Result<(), AppError> {
+        let pool = self.database.pool();
+
+        let task = match TaskQueries::get_task_by_id(pool, task_id).await {
+            Ok(task) if task.project_id == report.project_id => task,
+            Ok(_) | Err(AppError::NotFound(_)) => {
+                warn!("Report {} can't be posted: task {} is not in the project", report.id, task_id);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        let author = match report.created_by {
+            Some(user_id) if ProjectQueries::is_project_member(pool, report.project_id, user_id).await? => {
+                UserQueries::get_user_by_id(pool, user_id).await?
+            }
+            _ => {
+                warn!("Report {} can't be posted: its creator is no longer a project member", report.id);
+                return Ok(());
+            }
+        };
+
+        let comment = TaskCommentQueries::create_pinned_system_comment(
+            pool,
+            task.id,
+            author.id,
+            markdown,
+            report.last_comment_id,
+        ).await?;
+        ReportQueries::set_last_comment(pool, report.id, comment.id).await?;
+
+        let event = WebSocketEvent::CommentCreated(CommentEventData {
+            comment,
+            task_id: task.id,
+            project_id: task.project_id,
+            user: author.into(),
+            client_mutation_id: None,
+        });
+        self.websocket.broadcast_to_project(task.project_id, event, None).await;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl JobHandler for ReportSchedulerJob {
+    async fn run(&self, _job: &Job) -> anyhow::Result<()> {
+        let pool = self.database.pool();
+
+        // Queue the next run first so a failing run doesn't stop the schedule
+        schedule(pool, Utc::now() + Duration::minutes(SCHEDULER_INTERVAL_MINUTES)).await?;
+
+        // Claimed reports count as sent, so a failing one is logged rather than
+        // failing the run and losing the others
+        let due = ReportQueries::claim_due_reports(pool).await?;
+        let base_url = app_base_url();
+        let now = Utc::now();
+        let mut delivered = 0;
+
+        for due_report in due {
+            let report = due_report.report;
+            let since = due_report.previous_sent_at.unwrap_or(now - period_length(report.cadence));
+
+            let result = match gather(pool, report.project_id, since, now).await {
+                Ok(aggregates) => {
+                    let markdown = render_report(&report, &aggregates, &base_url);
+                    self.deliver(&report, &markdown, &base_url).await
+                }
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => delivered += 1,
+                Err(e) => warn!("Failed to deliver report {}: {}", report.id, e),
+            }
+        }
+
+        if delivered > 0 {
+            info!("Delivered {} project reports", delivered);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::ReportRecipients;
+    use chrono::TimeZone;
+
+    const BASE_URL: &str = "https://cards.example.com";
+
+    fn report(sections: Vec<ReportSection>) -> ProjectReport {
+        ProjectReport {
+            id: Uuid::new_v4(),
+            project_id: Uuid::new_v4(),
+            name: "Weekly status".to_string(),
+            cadence: ReportCadence::Weekly,
+            weekday: 5,
+            hour: 16,
+            timezone: "Europe/Berlin".to_string(),
+            sections,
+            recipients: ReportRecipients::default(),
+            last_comment_id: None,
+            last_sent_at: None,
+            created_by: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn task(number: i32, title: &str, assigned_to: Option<Uuid>) -> ReportTask {
+        ReportTask {
+            id: Uuid::new_v4(),
+            number,
+            title: title.to_string(),
+            assigned_to,
+            due_date: Some(Utc.with_ymd_and_hms(2024, 6, 3, 22, 0, 0).unwrap()),
+        }
+    }
+
+    fn aggregates(project_id: Uuid) -> ReportAggregates {
+        let alice = UserSummary {
+            id: Uuid::new_v4(),
+            username: "alice".to_string(),
+            display_name: "Alice_A".to_string(),
+            avatar_url: None,
+        };
+        ReportAggregates {
+            project_id,
+            project_name: "Website".to_string(),
+            since: Utc.with_ymd_and_hms(2024, 6, 1, 14, 0, 0).unwrap(),
+            until: Utc.with_ymd_and_hms(2024, 6, 8, 14, 0, 0).unwrap(),
+            completed: vec![task(1, "Fix *login*", Some(alice.id))],
+            created: vec![],
+            overdue: vec![task(2, "Ship [beta]", Some(alice.id))],
+            members: vec![MemberCounts { user: alice, completed: 1, open_tasks: 3, overdue: 1 }],
+        }
+    }
+
+    #[test]
+    fn test_render_report_follows_configured_sections() {
+        let report = report(vec![ReportSection::Overdue, ReportSection::Created, ReportSection::Members]);
+        let aggregates = aggregates(report.project_id);
+        let markdown = render_report(&report, &aggregates, BASE_URL);
+        let overdue = &aggregates.overdue[0];
+
+        assert_eq!(
+            markdown,
+            format!(
+                "## Weekly status: Website\n\n\
+                 _Sat Jun 1 – Sat Jun 8_\n\n\
+                 ### Overdue (1)\n- [#2 Ship \\[beta\\]]({}/projects/{}/tasks/{}) (due Tue Jun 4)\n\n\
+                 ### Newly created (0)\nNone\n\n\
+                 ### Per member\n- Alice\\_A: 1 completed, 3 open, 1 overdue",
+                BASE_URL, report.project_id, overdue.id
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_report_caps_long_lists() {
+        let report = report(vec![ReportSection::Completed]);
+        let mut aggregates = aggregates(report.project_id);
+        aggregates.completed = (1..=25).map(|number| task(number, "Task", None)).collect();
+
+        let markdown = render_report(&report, &aggregates, BASE_URL);
+
+        assert!(markdown.contains("### Completed (25)"));
+        assert_eq!(markdown.matches("](").count(), MAX_LISTED_TASKS);
+        assert!(markdown.ends_with("- …and 5 more"));
+    }
+
+    #[test]
+    fn test_period_length() {
+        assert_eq!(period_length(ReportCadence::Daily), Duration::days(1));
+        assert_eq!(period_length(ReportCadence::Weekly), Duration::days(7));
+    }
+}
@@ -0,0 +1,183 @@
+// Data retention. Teams set how old their comments and attachments may get
+// and how long tasks may stay done (RetentionPolicy); an hourly sweep hides
+// older content by marking it expired and deletes it, files included, once it
+// has been expired for RETENTION_GRACE_DAYS (30 by default). Within the grace
+// period a more lenient policy brings it back. Pinned comments and closed tasks
+// related to an open task are never expired. Every sweep that changes a team's
+// content is written to the audit log with its counts.
+//
+// Like the staleness scan, the sweep takes the current time as a parameter so
+// policies can be tested without waiting for them.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Months, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use std::env;
+use std::sync::OnceLock;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::attachments;
+use crate::config::AttachmentConfig;
+use crate::database::{
+    connection::Database,
+    models::{AuditAction, Job, RetentionCounts, RetentionPolicy, RetentionPreview},
+    queries::{AuditQueries, JobQueries, RetentionQueries},
+};
+use crate::jobs::{self, JobHandler};
+use crate::utils::errors::AppError;
+
+pub const RETENTION_SWEEP_JOB: &str = "retention_sweep";
+const SWEEP_INTERVAL_HOURS: i64 = 1;
+const DEFAULT_GRACE_DAYS: i64 = 30;
+
+pub fn grace_days() -> i64 {
+    static DAYS: OnceLock<i64> = OnceLock::new();
+    *DAYS.get_or_init(|| {
+        env::var("RETENTION_GRACE_DAYS")
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .filter(|days: &i64| *days >= 0)
+            .unwrap_or(DEFAULT_GRACE_DAYS)
+    })
+}
+
+// Comments and attachments created before their cutoff are expired, and
+// tasks closed before theirs; unset ages have no cutoff
+pub fn cutoffs(policy: &RetentionPolicy, now: DateTime<Utc>) -> [Option<DateTime<Utc>>; 3] {
+    let before = |months: u32| now.checked_sub_months(Months::new(months));
+    [
+        policy.comment_max_age_years.and_then(|years| before(u32::from(years) * 12)),
+        policy.attachment_max_age_months.and_then(|months| before(u32::from(months))),
+        policy.closed_task_max_age_days.map(|days| now - Duration::days(i64::from(days))),
+    ]
+}
+
+pub async fn preview(pool: &PgPool, team_id: Uuid, now: DateTime<Utc>) -> Result<RetentionPreview, AppError> {
+    let policy = RetentionQueries::get_policy(pool, team_id).await?;
+    let delete_before = now - Duration::days(grace_days());
+    let (to_expire, to_restore, to_delete) =
+        RetentionQueries::preview(pool, team_id, cutoffs(&policy, now), delete_before).await?;
+
+    Ok(RetentionPreview {
+        team_id,
+        policy,
+        grace_period_days: grace_days(),
+        to_expire,
+        to_restore,
+        to_delete,
+    })
+}
+
+// Applies every team's policy as of `now`. Returns how many comments,
+// attachments and tasks were deleted for good.
+pub async fn sweep(pool: &PgPool, config: &AttachmentConfig, now: DateTime<Utc>) -> Result<RetentionCounts, AppError> {
+    let delete_before = now - Duration::days(grace_days());
+    let mut deleted = RetentionCounts::default();
+
+    for (team_id, policy) in RetentionQueries::get_teams(pool).await? {
+        let cutoffs = cutoffs(&policy, now);
+        let restored = RetentionQueries::restore(pool, team_id, cutoffs).await?;
+        let expired = RetentionQueries::expire(pool, team_id, cutoffs, now).await?;
+        let (team_deleted, storage_paths) = RetentionQueries::delete_expired(pool, team_id, cutoffs, delete_before).await?;
+
+        // The rows are gone either way; a file left behind only takes up space
+        for storage_path in &storage_paths {
+            if let Err(e) = attachments::remove_file(config, storage_path).await {
+                warn!("Failed to delete the file of an expired attachment: {}", e);
+            }
+        }
+
+        if [restored, expired, team_deleted] != [RetentionCounts::default(); 3] {
+            let details = json!({
+                "team_id": team_id,
+                "policy": policy,
+                "expired": expired,
+                "restored": restored,
+                "deleted": team_deleted,
+            });
+            let path = format!("/api/teams/{}/retention", team_id);
+            AuditQueries::record_team(pool, AuditAction::RetentionApplied, None, team_id, Some(&path), None, Some(&details)).await?;
+        }
+
+        deleted.comments += team_deleted.comments;
+        deleted.attachments += team_deleted.attachments;
+        deleted.attachment_bytes += team_deleted.attachment_bytes;
+        deleted.tasks += team_deleted.tasks;
+    }
+
+    Ok(deleted)
+}
+
+// Makes sure a sweep is queued, unless one is already pending
+pub async fn schedule(pool: &PgPool, run_at: DateTime<Utc>) -> Result<(), AppError> {
+    if !JobQueries::has_pending_job(pool, RETENTION_SWEEP_JOB).await? {
+        jobs::enqueue_at(pool, RETENTION_SWEEP_JOB, &json!({}), run_at).await?;
+    }
+    Ok(())
+}
+
+pub struct RetentionSweepJob {
+    database: Database,
+    attachments: AttachmentConfig,
+}
+
+impl RetentionSweepJob {
+    pub fn new(database: Database, attachments: AttachmentConfig) -> Self {
+        Self { database, attachments }
+    }
+}
+
+#[async_trait]
+impl JobHandler for RetentionSweepJob {
+    async fn run(&self, _job: &Job) -> anyhow::Result<()> {
+        let pool = self.database.pool();
+
+        // Queue the next run first so a failing run doesn't stop the schedule
+        schedule(pool, Utc::now() + Duration::hours(SWEEP_INTERVAL_HOURS)).await?;
+
+        let deleted = sweep(pool, &self.attachments, Utc::now()).await?;
+        if deleted != RetentionCounts::default() {
+            info!(
+                "Retention deleted {} comments, {} attachments ({} bytes) and {} tasks",
+                deleted.comments, deleted.attachments, deleted.attachment_bytes, deleted.tasks
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_cutoffs_count_back_calendar_months() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 31, 12, 0, 0).unwrap();
+        let policy = RetentionPolicy {
+            comment_max_age_years: Some(2),
+            attachment_max_age_months: Some(3),
+            closed_task_max_age_days: Some(45),
+        };
+        assert_eq!(
+            cutoffs(&policy, now),
+            [
+                Some(Utc.with_ymd_and_hms(2022, 5, 31, 12, 0, 0).unwrap()),
+                // February has no 31st
+                Some(Utc.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).unwrap()),
+                Some(Utc.with_ymd_and_hms(2024, 4, 16, 12, 0, 0).unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unset_ages_have_no_cutoff() {
+        let now = Utc::now();
+        assert_eq!(cutoffs(&RetentionPolicy::default(), now), [None, None, None]);
+        let policy = RetentionPolicy { attachment_max_age_months: Some(6), ..Default::default() };
+        assert!(cutoffs(&policy, now)[0].is_none());
+        assert!(cutoffs(&policy, now)[2].is_none());
+    }
+}
@@ -0,0 +1,224 @@
+// Virus scanning of attachments. When CLAMAV_ADDRESS is set, uploads are
+// stored as `pending` and a scan job hands the file to clamd; downloads are
+// refused until the scan comes back clean. Infected files are moved to the
+// quarantine directory (kept for review, never deleted) and recorded in the
+// audit log. Without a scanner uploads are stored as clean right away.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::attachments;
+use crate::config::AttachmentConfig;
+use crate::database::{
+    connection::Database,
+    models::{AttachmentScanStatus, AuditAction, Job},
+    queries::{AttachmentQueries, AuditQueries, TaskQueries},
+};
+use crate::jobs::{self, JobHandler};
+use crate::utils::errors::AppError;
+use crate::websocket::events::{AttachmentScanEventData, WebSocketEvent};
+use crate::websocket::handler::WebSocketState;
+
+pub const SCAN_ATTACHMENT_JOB: &str = "scan_attachment";
+const CLAMD_CHUNK_BYTES: usize = 64 * 1024;
+const CLAMD_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    Clean,
+    // Name of the matched signature
+    Infected(String),
+}
+
+#[async_trait]
+pub trait AttachmentScanner: Send + Sync {
+    // Errors are retried by the job worker; the last attempt marks the scan failed
+    async fn scan(&self, path: &Path) -> anyhow::Result<ScanVerdict>;
+}
+
+// Used when no scanner is configured: everything is clean
+pub struct NoopScanner;
+
+#[async_trait]
+impl AttachmentScanner for NoopScanner {
+    async fn scan(&self, _path: &Path) -> anyhow::Result<ScanVerdict> {
+        Ok(ScanVerdict::Clean)
+    }
+}
+
+// Streams the file to clamd over TCP with the INSTREAM command
+pub struct ClamAvScanner {
+    address: String,
+}
+
+impl ClamAvScanner {
+    pub fn new(address: &str) -> Self {
+        Self { address: address.to_string() }
+    }
+
+    async fn instream(&self, path: &Path) -> anyhow::Result<ScanVerdict> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut stream = TcpStream::connect(&self.address).await?;
+        stream.write_all(b"zINSTREAM\0").await?;
+
+        // Chunks are prefixed with their length; an empty chunk ends the stream
+        let mut buffer = vec![0; CLAMD_CHUNK_BYTES];
+        loop {
+            let read = file.read(&mut buffer).await?;
+            stream.write_all(&(read as u32).to_be_bytes()).await?;
+            if read == 0 {
+                break;
+            }
+            stream.write_all(&buffer[..read]).await?;
+        }
+
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).await?;
+        parse_clamd_reply(&String::from_utf8_lossy(&reply))
+    }
+}
+
+#[async_trait]
+impl AttachmentScanner for ClamAvScanner {
+    async fn scan(&self, path: &Path) -> anyhow::Result<ScanVerdict> {
+        tokio::time::timeout(CLAMD_TIMEOUT, self.instream(path))
+            .await
+            .map_err(|_| anyhow::anyhow!("clamd at {} did not answer within {:?}", self.address, CLAMD_TIMEOUT))?
+    }
+}
+
+// "stream: OK", "stream: <signature> FOUND" or "<message> ERROR"
+pub fn parse_clamd_reply(reply: &str) -> anyhow::Result<ScanVerdict> {
+    let reply = reply.trim_end_matches('\0').trim();
+    let result = reply.strip_prefix("stream:").map(str::trim).unwrap_or(reply);
+
+    if result == "OK" {
+        return Ok(ScanVerdict::Clean);
+    }
+    match result.strip_suffix(" FOUND") {
+        Some(signature) => Ok(ScanVerdict::Infected(signature.trim().to_string())),
+        None => anyhow::bail!("clamd: {}", result),
+    }
+}
+
+fn clamav_address() -> Option<String> {
+    env::var("CLAMAV_ADDRESS").ok().filter(|address| !address.trim().is_empty())
+}
+
+// Whether new uploads wait for a scan
+pub fn scanning_enabled() -> bool {
+    clamav_address().is_some()
+}
+
+pub fn scanner_from_env() -> Arc<dyn AttachmentScanner> {
+    match clamav_address() {
+        Some(address) => Arc::new(ClamAvScanner::new(address.trim())),
+        None => Arc::new(NoopScanner),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanAttachmentPayload {
+    pub attachment_id: Uuid,
+}
+
+pub async fn queue_scan(pool: &PgPool, attachment_id: Uuid) -> Result<Job, AppError> {
+    jobs::enqueue(pool, SCAN_ATTACHMENT_JOB, &ScanAttachmentPayload { attachment_id }).await
+}
+
+pub struct ScanAttachmentJob {
+    database: Database,
+    websocket: WebSocketState,
+    attachments: AttachmentConfig,
+    scanner: Arc<dyn AttachmentScanner>,
+}
+
+impl ScanAttachmentJob {
+    pub fn new(
+        database: Database,
+        websocket: WebSocketState,
+        attachments: AttachmentConfig,
+        scanner: Arc<dyn AttachmentScanner>,
+    ) -> Self {
+        Self { database, websocket, attachments, scanner }
+    }
+}
+
+#[async_trait]
+impl JobHandler for ScanAttachmentJob {
+    async fn run(&self, job: &Job) -> anyhow::Result<()> {
+        let payload: ScanAttachmentPayload = serde_json::from_value(job.payload.clone())?;
+        let pool = self.database.pool();
+
+        // Gone with its task, or already scanned by an earlier attempt
+        let attachment = match AttachmentQueries::get_attachment(pool, payload.attachment_id).await {
+            Err(AppError::NotFound(_)) => return Ok(()),
+            result => result?,
+        };
+        if attachment.scan_status != AttachmentScanStatus::Pending {
+            return Ok(());
+        }
+
+        let path = self.attachments.upload_dir.join(&attachment.storage_path);
+        let (status, detail, storage_path) = match self.scanner.scan(&path).await {
+            Ok(ScanVerdict::Clean) => (AttachmentScanStatus::Clean, None, attachment.storage_path.clone()),
+            Ok(ScanVerdict::Infected(signature)) => {
+                let quarantined = attachments::quarantine_file(&self.attachments, &attachment.storage_path).await?;
+                (AttachmentScanStatus::Infected, Some(signature), quarantined)
+            }
+            Err(e) if job.attempts < job.max_attempts => return Err(e),
+            Err(e) => (AttachmentScanStatus::Failed, Some(e.to_string()), attachment.storage_path.clone()),
+        };
+
+        let result = AttachmentQueries::record_scan_result(pool, attachment.id, status, detail.as_deref(), &storage_path).await;
+        if storage_path != attachment.storage_path && !matches!(result, Ok(Some(_))) {
+            // Put the file back where the attachment row still points
+            attachments::move_file(&self.attachments, &storage_path, &attachment.storage_path).await?;
+        }
+        let Some(scanned) = result? else {
+            return Ok(());
+        };
+
+        if status == AttachmentScanStatus::Infected {
+            warn!("Quarantined attachment {} ({})", scanned.id, detail.as_deref().unwrap_or_default());
+            let path = format!("/api/attachments/{}", scanned.id);
+            AuditQueries::record_system(pool, AuditAction::AttachmentQuarantined, scanned.uploaded_by, Some(&path), None).await?;
+        }
+
+        if let Some(uploader) = scanned.uploaded_by {
+            let task = TaskQueries::get_task_by_id(pool, scanned.task_id).await?;
+            let event = WebSocketEvent::AttachmentScanCompleted(AttachmentScanEventData {
+                attachment: scanned,
+                project_id: task.project_id,
+            });
+            self.websocket.send_to_user(uploader, event).await;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clamd_reply() {
+        assert_eq!(parse_clamd_reply("stream: OK\0").unwrap(), ScanVerdict::Clean);
+        assert_eq!(
+            parse_clamd_reply("stream: Win.Test.EICAR_HDB-1 FOUND\0").unwrap(),
+            ScanVerdict::Infected("Win.Test.EICAR_HDB-1".to_string())
+        );
+        assert!(parse_clamd_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+        assert!(parse_clamd_reply("").is_err());
+    }
+}
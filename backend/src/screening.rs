@@ -0,0 +1,271 @@
+// Content screening for public instances that get spam. With the
+// `content_screening` feature flag on for a team, new tasks and comments in
+// its projects go through a ContentScreen first; content scoring at least
+// CONTENT_SCREEN_THRESHOLD (default 0.5) is held in the moderation queue
+// instead of being published. Held rows are quarantined, shown only to their
+// author, until an instance admin approves them; approval publishes them with
+// their original timestamp and the usual events.
+//
+// The built-in HeuristicScreen scores from 0 to 1:
+// - links: how much of the text is links, counted fully from 3 links on
+// - repetition: share of repeated words, from 6 words on
+// - any link to a domain in CONTENT_SCREEN_BLOCKED_DOMAINS scores 1
+// It also guesses the language, which the queue shows the reviewer.
+
+use async_trait::async_trait;
+use regex::Regex;
+use std::collections::HashSet;
+use std::env;
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+pub const CONTENT_SCREENING_FLAG: &str = "content_screening";
+const DEFAULT_THRESHOLD: f32 = 0.5;
+
+const LINK_WEIGHT: f32 = 0.6;
+const REPETITION_WEIGHT: f32 = 0.4;
+const FULL_LINK_COUNT: usize = 3;
+const MIN_REPETITION_WORDS: usize = 6;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Screening {
+    pub score: f32,
+    // Why the content scored, for the reviewer
+    pub reasons: Vec<String>,
+    // ISO 639-1 guess
+    pub language: Option<&'static str>,
+}
+
+#[async_trait]
+pub trait ContentScreen: Send + Sync {
+    async fn screen(&self, text: &str) -> Screening;
+}
+
+// Publishes everything
+pub struct NoopScreen;
+
+#[async_trait]
+impl ContentScreen for NoopScreen {
+    async fn screen(&self, _text: &str) -> Screening {
+        Screening::default()
+    }
+}
+
+pub struct HeuristicScreen {
+    blocked_domains: Vec<String>,
+}
+
+impl HeuristicScreen {
+    pub fn new(blocked_domains: &[&str]) -> Self {
+        let blocked_domains = blocked_domains
+            .iter()
+            .map(|domain| domain.trim().trim_start_matches("www.").to_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .collect();
+        HeuristicScreen { blocked_domains }
+    }
+
+    fn is_blocked(&self, host: &str) -> bool {
+        self.blocked_domains
+            .iter()
+            .any(|domain| host == domain || host.strip_suffix(domain.as_str()).is_some_and(|rest| rest.ends_with('.')))
+    }
+
+    pub fn score(&self, text: &str) -> Screening {
+        let hosts = link_hosts(text);
+        let words: Vec<String> = text
+            .split_whitespace()
+            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+        let language = detect_language(text);
+
+        if let Some(host) = hosts.iter().find(|host| self.is_blocked(host)) {
+            return Screening { score: 1.0, reasons: vec![format!("links to blocked domain {}", host)], language };
+        }
+
+        let mut reasons = Vec::new();
+        let link_score = if hosts.is_empty() {
+            0.0
+        } else {
+            let density = hosts.len() as f32 / words.len().max(1) as f32;
+            (density * 2.0).min(1.0) * (hosts.len() as f32 / FULL_LINK_COUNT as f32).min(1.0)
+        };
+        if link_score > 0.0 {
+            reasons.push(format!("{} of {} words are links", hosts.len(), words.len()));
+        }
+
+        let repetition_score = if words.len() < MIN_REPETITION_WORDS {
+            0.0
+        } else {
+            let unique = words.iter().collect::<HashSet<_>>().len();
+            let repeated = 1.0 - unique as f32 / words.len() as f32;
+            ((repeated - 0.4) / 0.4).clamp(0.0, 1.0)
+        };
+        if repetition_score > 0.0 {
+            reasons.push("repeated text".to_string());
+        }
+
+        let score = LINK_WEIGHT * link_score + REPETITION_WEIGHT * repetition_score;
+        Screening { score: (score * 1000.0).round() / 1000.0, reasons, language }
+    }
+}
+
+#[async_trait]
+impl ContentScreen for HeuristicScreen {
+    async fn screen(&self, text: &str) -> Screening {
+        self.score(text)
+    }
+}
+
+// Hosts of the http(s) and www. links in the text, lowercased
+pub fn link_hosts(text: &str) -> Vec<String> {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let link = LINK.get_or_init(|| Regex::new(r"(?i)(?:\bhttps?://|\bwww\.)([a-z0-9.-]+)").unwrap());
+    link.captures_iter(text)
+        .map(|captures| captures[1].trim_end_matches('.').trim_start_matches("www.").to_lowercase())
+        .collect()
+}
+
+// Frequent short words of the Latin-script languages told apart
+const STOPWORDS: &[(&str, &[&str])] = &[
+    ("en", &["the", "and", "is", "are", "of", "to", "this", "that", "with", "for", "you", "it"]),
+    ("de", &["der", "die", "das", "und", "ist", "nicht", "mit", "ein", "eine", "ich", "zu", "auf"]),
+    ("fr", &["le", "la", "les", "et", "est", "une", "des", "pas", "pour", "dans", "avec", "je"]),
+    ("es", &["el", "los", "las", "y", "es", "una", "por", "para", "con", "que", "del", "pero"]),
+    ("it", &["il", "gli", "e", "che", "non", "della", "per", "con", "sono", "una", "questo", "di"]),
+    ("pt", &["o", "os", "as", "e", "que", "não", "uma", "para", "com", "do", "da", "em"]),
+    ("nl", &["de", "het", "een", "en", "is", "niet", "van", "met", "op", "dat", "ik", "voor"]),
+];
+
+// Scripts used by one language, or nearly so
+fn script_language(c: char) -> Option<&'static str> {
+    match c {
+        '\u{3040}'..='\u{30ff}' => Some("ja"),
+        '\u{ac00}'..='\u{d7af}' | '\u{1100}'..='\u{11ff}' => Some("ko"),
+        '\u{4e00}'..='\u{9fff}' => Some("zh"),
+        '\u{0400}'..='\u{04ff}' => Some("ru"),
+        '\u{0600}'..='\u{06ff}' => Some("ar"),
+        '\u{0590}'..='\u{05ff}' => Some("he"),
+        '\u{0370}'..='\u{03ff}' => Some("el"),
+        '\u{0e00}'..='\u{0e7f}' => Some("th"),
+        '\u{0900}'..='\u{097f}' => Some("hi"),
+        _ => None,
+    }
+}
+
+// A guess from the script, or for Latin script from common words. None when
+// there's too little to go on.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+    if letters == 0 {
+        return None;
+    }
+    let mut scripts: Vec<(&'static str, usize)> = Vec::new();
+    for language in text.chars().filter_map(script_language) {
+        match scripts.iter_mut().find(|(known, _)| *known == language) {
+            Some((_, count)) => *count += 1,
+            None => scripts.push((language, 1)),
+        }
+    }
+    // Japanese mixes kanji with kana
+    if scripts.iter().any(|(language, _)| *language == "ja") {
+        return Some("ja");
+    }
+    if let Some((language, count)) = scripts.into_iter().max_by_key(|(_, count)| *count) {
+        if count * 2 >= letters {
+            return Some(language);
+        }
+    }
+
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphabetic()).to_lowercase())
+        .collect();
+    let (language, hits) = STOPWORDS
+        .iter()
+        .map(|(language, stopwords)| (*language, words.iter().filter(|word| stopwords.contains(&word.as_str())).count()))
+        .max_by_key(|(_, hits)| *hits)?;
+    (hits >= 2).then_some(language)
+}
+
+pub fn threshold() -> f32 {
+    env::var("CONTENT_SCREEN_THRESHOLD")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|threshold: &f32| (0.0..=1.0).contains(threshold))
+        .unwrap_or(DEFAULT_THRESHOLD)
+}
+
+// CONTENT_SCREEN=off swaps the heuristics for NoopScreen
+pub fn screen_from_env() -> Arc<dyn ContentScreen> {
+    if env::var("CONTENT_SCREEN").is_ok_and(|value| value.trim().eq_ignore_ascii_case("off")) {
+        return Arc::new(NoopScreen);
+    }
+    let domains = env::var("CONTENT_SCREEN_BLOCKED_DOMAINS").unwrap_or_default();
+    Arc::new(HeuristicScreen::new(&domains.split(',').collect::<Vec<_>>()))
+}
+
+// What a new task is screened by
+pub fn task_text(title: &str, description: Option<&str>) -> String {
+    match description {
+        Some(description) if !description.trim().is_empty() => format!("{}\n\n{}", title, description),
+        _ => title.to_string(),
+    }
+}
+
+// The screening of new content in the team's projects when it should be held,
+// None when it can be published
+pub async fn check(app_state: &crate::AppState, team_id: Uuid, text: &str) -> Option<Screening> {
+    if !app_state.flags.enabled(CONTENT_SCREENING_FLAG, team_id) {
+        return None;
+    }
+    let screening = app_state.screen.screen(text).await;
+    (screening.score >= threshold()).then_some(screening)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text_and_single_links_pass() {
+        let screen = HeuristicScreen::new(&[]);
+        assert_eq!(screen.score("Looks good to me, merging after lunch").score, 0.0);
+        let single = screen.score("Fixed in https://github.com/acme/web/pull/12");
+        assert!(single.score < DEFAULT_THRESHOLD);
+        assert_eq!(single.reasons, ["1 of 3 words are links"]);
+    }
+
+    #[test]
+    fn test_link_spam_and_repetition_are_held() {
+        let screen = HeuristicScreen::new(&[]);
+        let links = screen.score("cheap https://a.example https://b.example www.c.example");
+        assert_eq!(links.score, 0.6);
+
+        let repeated = screen.score("buy now buy now buy now buy now buy now buy now https://a.example https://a.example https://a.example");
+        assert!(repeated.score >= DEFAULT_THRESHOLD);
+        assert!(repeated.reasons.contains(&"repeated text".to_string()));
+    }
+
+    #[test]
+    fn test_blocked_domains_and_subdomains() {
+        let screen = HeuristicScreen::new(&[" Spam.example ", ""]);
+        let blocked = screen.score("A long and perfectly normal comment, see http://shop.SPAM.example/deal");
+        assert_eq!(blocked.score, 1.0);
+        assert_eq!(blocked.reasons, ["links to blocked domain shop.spam.example"]);
+        assert!(screen.score("see https://notspam.example").score < 1.0);
+    }
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(detect_language("This is the fix for the login bug"), Some("en"));
+        assert_eq!(detect_language("Das ist nicht der richtige Button und die Farbe"), Some("de"));
+        assert_eq!(detect_language("Le bouton est cassé dans la page et les liens"), Some("fr"));
+        assert_eq!(detect_language("Кнопка не работает"), Some("ru"));
+        assert_eq!(detect_language("ログインできません"), Some("ja"));
+        assert_eq!(detect_language("登录失败"), Some("zh"));
+        assert_eq!(detect_language("ok"), None);
+        assert_eq!(detect_language("https://a.example 123"), None);
+    }
+}
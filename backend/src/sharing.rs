@@ -0,0 +1,237 @@
+// Public project status badges, unlocked by a project's share tokens. Badge
+// endpoints are unauthenticated, so counts are cached per token for a minute
+// and requests are rate limited per IP (see utils::rate_limit).
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::database::models::BadgeStats;
+
+pub const BADGE_CACHE_TTL: Duration = Duration::from_secs(60);
+// Entries are pruned once this many tokens are cached
+const PRUNE_THRESHOLD: usize = 10_000;
+
+const LABEL_COLOR: &str = "#555";
+const OPEN_COLOR: &str = "#007ec6";
+const DONE_COLOR: &str = "#4c1";
+const PARTLY_DONE_COLOR: &str = "#a4a61d";
+const BARELY_DONE_COLOR: &str = "#fe7d37";
+const EMPTY_COLOR: &str = "#9f9f9f";
+
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// What the SVG badge shows; the JSON variant always has both
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BadgeMetric {
+    // Tasks not done yet
+    #[default]
+    Open,
+    // Share of tasks done
+    Progress,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BadgeSummary {
+    pub project_name: String,
+    pub open_tasks: i64,
+    pub done_tasks: i64,
+    pub total_tasks: i64,
+    // None while the project has no tasks
+    pub percent_done: Option<i64>,
+}
+
+impl From<BadgeStats> for BadgeSummary {
+    fn from(stats: BadgeStats) -> Self {
+        let total_tasks = stats.open_tasks + stats.done_tasks;
+        BadgeSummary {
+            project_name: stats.project_name,
+            open_tasks: stats.open_tasks,
+            done_tasks: stats.done_tasks,
+            total_tasks,
+            percent_done: (total_tasks > 0).then(|| stats.done_tasks * 100 / total_tasks),
+        }
+    }
+}
+
+// Label, message and message color of the badge for `metric`
+pub fn badge_text(summary: &BadgeSummary, metric: BadgeMetric) -> (&'static str, String, &'static str) {
+    match (metric, summary.percent_done) {
+        (BadgeMetric::Open, _) => ("open tasks", summary.open_tasks.to_string(), OPEN_COLOR),
+        (BadgeMetric::Progress, None) => ("done", "no tasks".to_string(), EMPTY_COLOR),
+        (BadgeMetric::Progress, Some(percent)) => {
+            let color = match percent {
+                100.. => DONE_COLOR,
+                50.. => PARTLY_DONE_COLOR,
+                _ => BARELY_DONE_COLOR,
+            };
+            ("done", format!("{}%", percent), color)
+        }
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Rough width of 11px Verdana text plus padding; badges don't need exact metrics
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 7 + 10
+}
+
+// Flat two-part badge, "label | message", in the style of shields.io
+pub fn render_badge(label: &str, message: &str, color: &str) -> String {
+    let label_width = text_width(label);
+    let message_width = text_width(message);
+    format!(
+        concat!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">"##,
+            "<title>{label}: {message}</title>",
+            r##"<rect width="{label_width}" height="20" fill="{label_color}"/>"##,
+            r##"<rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/>"##,
+            r##"<g fill="#fff" text-anchor="middle" font-family="Verdana,DejaVu Sans,sans-serif" font-size="11">"##,
+            r##"<text x="{label_x}" y="14">{label}</text>"##,
+            r##"<text x="{message_x}" y="14">{message}</text>"##,
+            "</g></svg>",
+        ),
+        width = label_width + message_width,
+        label = escape_xml(label),
+        message = escape_xml(message),
+        label_width = label_width,
+        message_width = message_width,
+        label_color = LABEL_COLOR,
+        color = escape_xml(color),
+        label_x = label_width / 2,
+        message_x = label_width + message_width / 2,
+    )
+}
+
+// Badge counts per share token. Unknown tokens are cached too, so guessing
+// tokens doesn't reach the database more than once a minute per guess.
+#[derive(Debug, Default)]
+pub struct BadgeCache {
+    entries: Mutex<HashMap<String, (Instant, Option<BadgeStats>)>>,
+}
+
+impl BadgeCache {
+    // Outer None when the token isn't cached or its entry expired
+    pub fn get(&self, token: &str) -> Option<Option<BadgeStats>> {
+        self.get_at(token, Instant::now())
+    }
+
+    fn get_at(&self, token: &str, now: Instant) -> Option<Option<BadgeStats>> {
+        let entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries
+            .get(token)
+            .filter(|(cached_at, _)| now.duration_since(*cached_at) < BADGE_CACHE_TTL)
+            .map(|(_, stats)| stats.clone())
+    }
+
+    pub fn insert(&self, token: &str, stats: Option<BadgeStats>) {
+        self.insert_at(token, stats, Instant::now())
+    }
+
+    fn insert_at(&self, token: &str, stats: Option<BadgeStats>, now: Instant) {
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if entries.len() >= PRUNE_THRESHOLD {
+            entries.retain(|_, (cached_at, _)| now.duration_since(*cached_at) < BADGE_CACHE_TTL);
+        }
+        entries.insert(token.to_string(), (now, stats));
+    }
+
+    // Revoked tokens stop working right away rather than when their entry expires
+    pub fn forget(&self, token: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.remove(token);
+    }
+}
+
+pub fn badge_cache() -> &'static BadgeCache {
+    static INSTANCE: OnceLock<BadgeCache> = OnceLock::new();
+    INSTANCE.get_or_init(BadgeCache::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(open_tasks: i64, done_tasks: i64) -> BadgeSummary {
+        BadgeStats { project_name: "Website".to_string(), open_tasks, done_tasks }.into()
+    }
+
+    #[test]
+    fn test_render_badge_snapshots() {
+        assert_eq!(
+            render_badge("open tasks", "12", OPEN_COLOR),
+            concat!(
+                r##"<svg xmlns="http://www.w3.org/2000/svg" width="104" height="20" role="img" aria-label="open tasks: 12">"##,
+                "<title>open tasks: 12</title>",
+                r##"<rect width="80" height="20" fill="#555"/>"##,
+                r##"<rect x="80" width="24" height="20" fill="#007ec6"/>"##,
+                r##"<g fill="#fff" text-anchor="middle" font-family="Verdana,DejaVu Sans,sans-serif" font-size="11">"##,
+                r##"<text x="40" y="14">open tasks</text>"##,
+                r##"<text x="92" y="14">12</text>"##,
+                "</g></svg>",
+            )
+        );
+        assert_eq!(
+            render_badge("done", "42%", PARTLY_DONE_COLOR),
+            concat!(
+                r##"<svg xmlns="http://www.w3.org/2000/svg" width="69" height="20" role="img" aria-label="done: 42%">"##,
+                "<title>done: 42%</title>",
+                r##"<rect width="38" height="20" fill="#555"/>"##,
+                r##"<rect x="38" width="31" height="20" fill="#a4a61d"/>"##,
+                r##"<g fill="#fff" text-anchor="middle" font-family="Verdana,DejaVu Sans,sans-serif" font-size="11">"##,
+                r##"<text x="19" y="14">done</text>"##,
+                r##"<text x="53" y="14">42%</text>"##,
+                "</g></svg>",
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_badge_escapes_text() {
+        let svg = render_badge("<script>", "a & \"b\"", OPEN_COLOR);
+        assert!(svg.contains("<text x=\"33\" y=\"14\">&lt;script&gt;</text>"));
+        assert!(svg.contains("a &amp; &quot;b&quot;"));
+        assert!(!svg.contains("<script>"));
+    }
+
+    #[test]
+    fn test_badge_text() {
+        assert_eq!(badge_text(&summary(3, 1), BadgeMetric::Open), ("open tasks", "3".to_string(), OPEN_COLOR));
+        assert_eq!(badge_text(&summary(3, 1), BadgeMetric::Progress), ("done", "25%".to_string(), BARELY_DONE_COLOR));
+        assert_eq!(badge_text(&summary(1, 2), BadgeMetric::Progress), ("done", "66%".to_string(), PARTLY_DONE_COLOR));
+        assert_eq!(badge_text(&summary(0, 4), BadgeMetric::Progress), ("done", "100%".to_string(), DONE_COLOR));
+        assert_eq!(badge_text(&summary(0, 0), BadgeMetric::Progress), ("done", "no tasks".to_string(), EMPTY_COLOR));
+        assert_eq!(summary(0, 0).percent_done, None);
+    }
+
+    #[test]
+    fn test_badge_cache_expires_and_forgets() {
+        let cache = BadgeCache::default();
+        let stats = BadgeStats { project_name: "Website".to_string(), open_tasks: 2, done_tasks: 0 };
+        let start = Instant::now();
+
+        assert_eq!(cache.get_at("token", start), None);
+        cache.insert_at("token", Some(stats.clone()), start);
+        cache.insert_at("unknown", None, start);
+        assert_eq!(cache.get_at("token", start + Duration::from_secs(59)), Some(Some(stats)));
+        assert_eq!(cache.get_at("unknown", start), Some(None));
+        assert_eq!(cache.get_at("token", start + BADGE_CACHE_TTL), None);
+
+        cache.forget("unknown");
+        assert_eq!(cache.get_at("unknown", start), None);
+    }
+}
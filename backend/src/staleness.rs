@@ -0,0 +1,201 @@
+// Stale task detection. Projects set how many days a task may stay in each
+// status (StalenessRules); an hourly job marks open tasks that have been in
+// their status for longer and nudges their assignee, once per stale spell. A
+// status change ends the spell (see migration 040). The scan takes the current
+// time as a parameter so thresholds can be tested without waiting for them.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::database::{
+    connection::Database,
+    models::{Job, NotificationEventType, NotificationKind, StaleTask, StalenessRules, TaskStatus},
+    queries::{JobQueries, NotificationQueries, ProjectQueries, StalenessQueries, TaskCommentQueries, TaskQueries, UserQueries},
+};
+use crate::jobs::{self, JobHandler};
+use crate::notification_settings;
+use crate::utils::errors::AppError;
+use crate::websocket::events::{CommentEventData, StaleTaskNotificationData, WebSocketEvent};
+use crate::websocket::handler::WebSocketState;
+
+pub const STALENESS_JOB: &str = "staleness_scan";
+const SCAN_INTERVAL_HOURS: i64 = 1;
+
+// Tasks in a status whose last status change is at or before its cutoff are
+// stale. Done tasks never are, and disabled rules have no cutoffs at all.
+pub fn cutoffs(rules: &StalenessRules, now: DateTime<Utc>) -> Vec<(TaskStatus, DateTime<Utc>)> {
+    if !rules.enabled {
+        return Vec::new();
+    }
+    rules
+        .thresholds
+        .iter()
+        .filter(|threshold| threshold.status != TaskStatus::Done && threshold.days > 0)
+        .map(|threshold| (threshold.status, now - Duration::days(i64::from(threshold.days))))
+        .collect()
+}
+
+pub fn stale_comment(task: &StaleTask, now: DateTime<Utc>) -> String {
+    format!(
+        "This task has been in {} for {} days without a status change.",
+        task.status.name(),
+        (now - task.status_changed_at).num_days()
+    )
+}
+
+// Marks and unmarks tasks as of `now` and nudges the assignees of newly stale
+// ones. Returns how many tasks went stale per project, for projects with any.
+pub async fn scan(pool: &PgPool, websocket: &WebSocketState, now: DateTime<Utc>) -> Result<HashMap<Uuid, usize>, AppError> {
+    StalenessQueries::clear_disabled(pool).await?;
+
+    let mut marked = HashMap::new();
+    for (project_id, created_by, rules) in StalenessQueries::get_enabled_projects(pool).await? {
+        let cutoffs = cutoffs(&rules, now);
+        StalenessQueries::clear_stale(pool, project_id, &cutoffs).await?;
+        let stale = StalenessQueries::mark_stale(pool, project_id, &cutoffs, now).await?;
+        if !stale.is_empty() {
+            marked.insert(project_id, stale.len());
+        }
+
+        // The tasks are marked either way, so a failed nudge is only logged
+        for task in &stale {
+            if let Err(e) = nudge(pool, websocket, &rules, created_by, task, now).await {
+                warn!("Failed to nudge about stale task {}: {}", task.task_id, e);
+            }
+        }
+    }
+
+    Ok(marked)
+}
+
+async fn nudge(
+    pool: &PgPool,
+    websocket: &WebSocketState,
+    rules: &StalenessRules,
+    project_creator: Uuid,
+    stale: &StaleTask,
+    now: DateTime<Utc>,
+) -> Result<(), AppError> {
+    let task = TaskQueries::get_task_by_id(pool, stale.task_id).await?;
+
+    if let Some(user_id) = task.assigned_to {
+        if ProjectQueries::is_project_member(pool, task.project_id, user_id).await?
+            && notification_settings::should_notify(pool, user_id, task.project_id, NotificationEventType::Reminder).await?
+        {
+            let notification = NotificationQueries::create_notification(
+                pool,
+                user_id,
+                NotificationKind::TaskStale,
+                task.project_id,
+                Some(task.id),
+                None,
+            ).await?;
+            let event = WebSocketEvent::TaskWentStale(StaleTaskNotificationData { notification, task: task.clone() });
+            websocket.send_to_user(user_id, event).await;
+        }
+    }
+
+    // Posted in the name of the project's creator, as long as they're still a member
+    if rules.post_comment {
+        if !ProjectQueries::is_project_member(pool, task.project_id, project_creator).await? {
+            warn!("Stale task {} gets no comment: the project's creator is no longer a member", task.id);
+            return Ok(());
+        }
+        let author = UserQueries::get_user_by_id(pool, project_creator).await?;
+        let comment = TaskCommentQueries::create_system_comment(pool, task.id, author.id, &stale_comment(stale, now)).await?;
+        let event = WebSocketEvent::CommentCreated(CommentEventData {
+            comment,
+            task_id: task.id,
+            project_id: task.project_id,
+            user: author.into(),
+            client_mutation_id: None,
+        });
+        websocket.broadcast_to_project(task.project_id, event, None).await;
+    }
+
+    Ok(())
+}
+
+// Makes sure a scan is queued, unless one is already pending
+pub async fn schedule(pool: &PgPool, run_at: DateTime<Utc>) -> Result<(), AppError> {
+    if !JobQueries::has_pending_job(pool, STALENESS_JOB).await? {
+        jobs::enqueue_at(pool, STALENESS_JOB, &json!({}), run_at).await?;
+    }
+    Ok(())
+}
+
+pub struct StalenessJob {
+    database: Database,
+    websocket: WebSocketState,
+}
+
+impl StalenessJob {
+    pub fn new(database: Database, websocket: WebSocketState) -> Self {
+        Self { database, websocket }
+    }
+}
+
+#[async_trait]
+impl JobHandler for StalenessJob {
+    async fn run(&self, _job: &Job) -> anyhow::Result<()> {
+        let pool = self.database.pool();
+
+        // Queue the next run first so a failing run doesn't stop the schedule
+        schedule(pool, Utc::now() + Duration::hours(SCAN_INTERVAL_HOURS)).await?;
+
+        let marked: usize = scan(pool, &self.websocket, Utc::now()).await?.values().sum();
+        if marked > 0 {
+            info!("Marked {} tasks as stale", marked);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::StalenessThreshold;
+
+    fn rules(thresholds: &[(TaskStatus, u16)]) -> StalenessRules {
+        StalenessRules {
+            enabled: true,
+            thresholds: thresholds.iter().map(|&(status, days)| StalenessThreshold { status, days }).collect(),
+            post_comment: false,
+        }
+    }
+
+    #[test]
+    fn test_cutoffs_count_back_from_now() {
+        let now = Utc::now();
+        let cutoffs = cutoffs(&rules(&[(TaskStatus::Todo, 14), (TaskStatus::Review, 3)]), now);
+        assert_eq!(
+            cutoffs,
+            vec![(TaskStatus::Todo, now - Duration::days(14)), (TaskStatus::Review, now - Duration::days(3))]
+        );
+    }
+
+    #[test]
+    fn test_disabled_rules_and_done_tasks_have_no_cutoff() {
+        let now = Utc::now();
+        let disabled = StalenessRules { enabled: false, ..rules(&[(TaskStatus::Todo, 14)]) };
+        assert!(cutoffs(&disabled, now).is_empty());
+        assert!(cutoffs(&StalenessRules::default(), now).is_empty());
+        assert!(cutoffs(&rules(&[(TaskStatus::Done, 1), (TaskStatus::Todo, 0)]), now).is_empty());
+    }
+
+    #[test]
+    fn test_stale_comment_counts_whole_days() {
+        let now = Utc::now();
+        let task = StaleTask {
+            task_id: Uuid::new_v4(),
+            status: TaskStatus::InProgress,
+            status_changed_at: now - Duration::days(9) - Duration::hours(20),
+        };
+        assert_eq!(stale_comment(&task, now), "This task has been in InProgress for 9 days without a status change.");
+    }
+}
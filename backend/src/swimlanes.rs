@@ -0,0 +1,144 @@
+// Board swimlanes: rows that split every column by assignee, priority or label.
+// Configured lanes keep their order and collapsed state; tasks with other keys
+// get lanes after them, and the lane for tasks without a value comes last.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::database::models::{SwimlaneConfig, SwimlaneGroupBy, Task, TaskPriority};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Swimlane {
+    pub key: Option<String>,
+    pub collapsed: bool,
+    pub task_ids: Vec<Uuid>,
+}
+
+// Lanes a task belongs to; tasks with several labels show up in each label's lane
+fn lane_keys(group_by: SwimlaneGroupBy, task: &Task) -> Vec<Option<String>> {
+    match group_by {
+        SwimlaneGroupBy::Assignee => vec![task.assigned_to.map(|id| id.to_string())],
+        SwimlaneGroupBy::Priority => vec![Some(task.priority.name().to_string())],
+        SwimlaneGroupBy::Label => match task.tags.as_deref() {
+            Some(tags) if !tags.is_empty() => tags.iter().map(|tag| Some(tag.clone())).collect(),
+            _ => vec![None],
+        },
+        SwimlaneGroupBy::None | SwimlaneGroupBy::Milestone => vec![],
+    }
+}
+
+// Unconfigured lanes sort by priority rank or by key, with the empty lane last
+fn sort_rank(group_by: SwimlaneGroupBy, key: &Option<String>) -> (bool, usize, String) {
+    let rank = match (group_by, key) {
+        (SwimlaneGroupBy::Priority, Some(key)) => TaskPriority::ALL
+            .iter()
+            .position(|priority| priority.name() == key)
+            .unwrap_or(usize::MAX),
+        _ => 0,
+    };
+    (key.is_none(), rank, key.clone().unwrap_or_default())
+}
+
+// Empty when the board has no swimlanes
+pub fn group_tasks(config: &SwimlaneConfig, tasks: &[Task]) -> Vec<Swimlane> {
+    if matches!(config.group_by, SwimlaneGroupBy::None) || !config.group_by.is_supported() {
+        return Vec::new();
+    }
+
+    let mut lanes: Vec<Swimlane> = config
+        .lanes
+        .iter()
+        .map(|lane| Swimlane { key: lane.key.clone(), collapsed: lane.collapsed, task_ids: Vec::new() })
+        .collect();
+    let configured = lanes.len();
+
+    for task in tasks {
+        for key in lane_keys(config.group_by, task) {
+            match lanes.iter_mut().find(|lane| lane.key == key) {
+                Some(lane) => lane.task_ids.push(task.id),
+                None => lanes.push(Swimlane { key, collapsed: false, task_ids: vec![task.id] }),
+            }
+        }
+    }
+
+    lanes[configured..].sort_by_key(|lane| sort_rank(config.group_by, &lane.key));
+    lanes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::{SwimlaneLane, TaskStatus};
+    use chrono::Utc;
+
+    fn task(priority: TaskPriority, tags: Option<Vec<&str>>) -> Task {
+        Task {
+            id: Uuid::new_v4(),
+            title: "Task".to_string(),
+            description: None,
+            project_id: Uuid::nil(),
+            created_by: Uuid::nil(),
+            assigned_to: None,
+            status: TaskStatus::Todo,
+            priority,
+            due_date: None,
+            is_all_day: false,
+            tags: tags.map(|tags| tags.into_iter().map(str::to_string).collect()),
+            cover_color: None,
+            cover_emoji: None,
+            position: 0,
+            number: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn keys(lanes: &[Swimlane]) -> Vec<Option<&str>> {
+        lanes.iter().map(|lane| lane.key.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_no_grouping_has_no_lanes() {
+        let tasks = vec![task(TaskPriority::Low, None)];
+
+        assert!(group_tasks(&SwimlaneConfig::default(), &tasks).is_empty());
+    }
+
+    #[test]
+    fn test_configured_lanes_come_first() {
+        let tasks = vec![
+            task(TaskPriority::Low, None),
+            task(TaskPriority::Critical, None),
+            task(TaskPriority::Medium, None),
+        ];
+        let config = SwimlaneConfig {
+            group_by: SwimlaneGroupBy::Priority,
+            lanes: vec![
+                SwimlaneLane { key: Some("High".to_string()), collapsed: true },
+                SwimlaneLane { key: Some("Low".to_string()), collapsed: false },
+            ],
+        };
+
+        let lanes = group_tasks(&config, &tasks);
+        // Configured lanes are kept even when empty; the rest follow in priority order
+        assert_eq!(keys(&lanes), vec![Some("High"), Some("Low"), Some("Medium"), Some("Critical")]);
+        assert!(lanes[0].collapsed);
+        assert!(lanes[0].task_ids.is_empty());
+        assert_eq!(lanes[1].task_ids, vec![tasks[0].id]);
+    }
+
+    #[test]
+    fn test_label_lanes() {
+        let tasks = vec![
+            task(TaskPriority::Low, Some(vec!["qa", "api"])),
+            task(TaskPriority::Low, None),
+            task(TaskPriority::Low, Some(vec!["api"])),
+        ];
+        let config = SwimlaneConfig { group_by: SwimlaneGroupBy::Label, lanes: vec![] };
+
+        let lanes = group_tasks(&config, &tasks);
+        assert_eq!(keys(&lanes), vec![Some("api"), Some("qa"), None]);
+        assert_eq!(lanes[0].task_ids, vec![tasks[0].id, tasks[2].id]);
+        assert_eq!(lanes[2].task_ids, vec![tasks[1].id]);
+    }
+}
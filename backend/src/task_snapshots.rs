@@ -0,0 +1,267 @@
+// Task snapshots: a self-contained rendering of a task, as JSON or a printable
+// HTML page, with its description rendered from markdown, the checklist (the
+// description's `- [ ]` items), assignee, labels and optionally the comments.
+//
+// `capture` renders the task as it is now. Stored snapshots keep that JSON in
+// task_snapshots, so they still say what the task said when they were taken.
+
+use chrono::Utc;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use sqlx::PgPool;
+
+use crate::database::models::{
+    ChecklistItem, SnapshotProject, StoredTaskSnapshot, Task, TaskSnapshot, UserSummary,
+};
+use crate::database::queries::{ProjectQueries, TaskSnapshotQueries, UserQueries};
+use crate::utils::errors::AppError;
+use crate::utils::html::{escape_html, markdown_options, render_markdown};
+
+pub const MAX_NOTE_LENGTH: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    Html,
+    Json,
+}
+
+impl SnapshotFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "html" => Some(SnapshotFormat::Html),
+            "json" => Some(SnapshotFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+// The task list items of a markdown text, in order. Nested items are items of
+// their own.
+pub fn checklist(markdown: &str) -> Vec<ChecklistItem> {
+    let mut items = Vec::new();
+    let mut current: Option<ChecklistItem> = None;
+
+    for event in Parser::new_ext(markdown, markdown_options()) {
+        match event {
+            Event::TaskListMarker(done) => {
+                items.extend(current.take());
+                current = Some(ChecklistItem { text: String::new(), done });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(item) = current.as_mut() {
+                    item.text.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(item) = current.as_mut() {
+                    item.text.push(' ');
+                }
+            }
+            Event::Start(Tag::List(_)) | Event::End(TagEnd::Item) => items.extend(current.take()),
+            _ => {}
+        }
+    }
+    items.extend(current);
+
+    for item in &mut items {
+        item.text = item.text.trim().to_string();
+    }
+    items
+}
+
+pub async fn capture(pool: &PgPool, task: Task, include_comments: bool) -> Result<TaskSnapshot, AppError> {
+    let project = ProjectQueries::get_project_by_id(pool, task.project_id).await?;
+    let assignee = match task.assigned_to {
+        Some(user_id) => Some(UserSummary::from(UserQueries::get_user_by_id(pool, user_id).await?)),
+        None => None,
+    };
+    let comments = if include_comments {
+        Some(TaskSnapshotQueries::get_comments(pool, task.id).await?)
+    } else {
+        None
+    };
+
+    let description = task.description.unwrap_or_default();
+    Ok(TaskSnapshot {
+        task_id: task.id,
+        number: task.number,
+        title: task.title,
+        description_html: render_markdown(&description),
+        checklist: checklist(&description),
+        description: Some(description).filter(|description| !description.is_empty()),
+        status: task.status,
+        priority: task.priority,
+        due_date: task.due_date,
+        is_all_day: task.is_all_day,
+        assignee,
+        labels: task.tags.unwrap_or_default(),
+        project: SnapshotProject { id: project.id, name: project.name },
+        comments,
+        captured_at: Utc::now(),
+    })
+}
+
+pub fn validate_note(note: Option<String>) -> Result<Option<String>, AppError> {
+    let note = note.map(|note| note.trim().to_string()).filter(|note| !note.is_empty());
+    if note.as_ref().is_some_and(|note| note.chars().count() > MAX_NOTE_LENGTH) {
+        return Err(AppError::Validation(format!("note must be {} characters or less", MAX_NOTE_LENGTH)));
+    }
+    Ok(note)
+}
+
+fn due(snapshot: &TaskSnapshot) -> Option<String> {
+    let due_date = snapshot.due_date?;
+    Some(if snapshot.is_all_day {
+        due_date.format("%B %-d, %Y").to_string()
+    } else {
+        due_date.format("%B %-d, %Y %H:%M UTC").to_string()
+    })
+}
+
+// A printable page; `stored` adds who took the snapshot and why
+pub fn render_html(snapshot: &TaskSnapshot, stored: Option<&StoredTaskSnapshot>) -> String {
+    let mut details = vec![
+        ("Project", escape_html(&snapshot.project.name)),
+        ("Status", snapshot.status.name().to_string()),
+        ("Priority", snapshot.priority.name().to_string()),
+    ];
+    if let Some(assignee) = &snapshot.assignee {
+        details.push(("Assignee", escape_html(&assignee.display_name)));
+    }
+    if let Some(due) = due(snapshot) {
+        details.push(("Due", due));
+    }
+    if !snapshot.labels.is_empty() {
+        let labels: Vec<String> = snapshot.labels.iter().map(|label| escape_html(label)).collect();
+        details.push(("Labels", labels.join(", ")));
+    }
+    if !snapshot.checklist.is_empty() {
+        let done = snapshot.checklist.iter().filter(|item| item.done).count();
+        details.push(("Checklist", format!("{} of {} done", done, snapshot.checklist.len())));
+    }
+
+    let mut body = String::from("<dl>\n");
+    for (name, value) in details {
+        body.push_str(&format!("<dt>{}</dt><dd>{}</dd>\n", name, value));
+    }
+    body.push_str("</dl>\n");
+    body.push_str(&format!("<div class=\"description\">\n{}</div>\n", snapshot.description_html));
+
+    if let Some(comments) = &snapshot.comments {
+        body.push_str(&format!("<h2>Comments ({})</h2>\n", comments.len()));
+        for comment in comments {
+            body.push_str(&format!(
+                "<div class=\"comment\"><p class=\"meta\">{} &middot; {}</p>\n{}</div>\n",
+                escape_html(&comment.author.display_name),
+                comment.created_at.format("%B %-d, %Y %H:%M UTC"),
+                render_markdown(&comment.content)
+            ));
+        }
+    }
+
+    let mut footer = format!("Captured {}", snapshot.captured_at.format("%B %-d, %Y %H:%M UTC"));
+    if let Some(stored) = stored {
+        if let Some(author) = &stored.created_by {
+            footer.push_str(&format!(" by {}", escape_html(&author.display_name)));
+        }
+        if let Some(note) = &stored.note {
+            footer.push_str(&format!(": {}", escape_html(note)));
+        }
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>#{number} {title}</title>
+<style>
+body {{ font-family: Helvetica, Arial, sans-serif; margin: 2em; max-width: 50em; }}
+h1 {{ font-size: 1.5em; margin-bottom: 0.5em; }}
+h2 {{ font-size: 1.1em; page-break-after: avoid; }}
+dl {{ display: grid; grid-template-columns: max-content auto; gap: 0.2em 1em; }}
+dt {{ color: #555; }}
+dd {{ margin: 0; }}
+.comment {{ border-top: 1px solid #ddd; page-break-inside: avoid; }}
+.meta, footer {{ color: #555; font-size: 0.9em; }}
+</style>
+</head>
+<body>
+<h1><span class="number">#{number}</span> {title}</h1>
+{body}<footer>{footer}</footer>
+</body>
+</html>
+"#,
+        number = snapshot.number,
+        title = escape_html(&snapshot.title),
+        body = body,
+        footer = footer,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::{SnapshotComment, TaskPriority, TaskStatus};
+    use uuid::Uuid;
+
+    fn item(text: &str, done: bool) -> ChecklistItem {
+        ChecklistItem { text: text.to_string(), done }
+    }
+
+    #[test]
+    fn test_checklist_from_task_list_items() {
+        let markdown = "Steps:\n\n- [x] Write `tests`\n- [ ] Deploy\n  to staging\n  - [ ] Smoke test\n- plain item\n\n1. [X] Numbered";
+        assert_eq!(
+            checklist(markdown),
+            [
+                item("Write tests", true),
+                item("Deploy to staging", false),
+                item("Smoke test", false),
+                item("Numbered", true),
+            ]
+        );
+        assert!(checklist("Nothing to check").is_empty());
+    }
+
+    #[test]
+    fn test_note_length() {
+        assert_eq!(validate_note(Some("  ".to_string())).unwrap(), None);
+        assert_eq!(validate_note(Some(" v1.2 ".to_string())).unwrap().as_deref(), Some("v1.2"));
+        assert!(validate_note(Some("x".repeat(MAX_NOTE_LENGTH + 1))).is_err());
+    }
+
+    #[test]
+    fn test_html_escapes_task_content() {
+        let author = UserSummary {
+            id: Uuid::new_v4(),
+            username: "ada".to_string(),
+            display_name: "Ada <3".to_string(),
+            avatar_url: None,
+        };
+        let snapshot = TaskSnapshot {
+            task_id: Uuid::new_v4(),
+            number: 42,
+            title: "Fix <b>login</b>".to_string(),
+            description: Some("**Steps**".to_string()),
+            description_html: render_markdown("**Steps**"),
+            checklist: vec![item("One", true), item("Two", false)],
+            status: TaskStatus::InProgress,
+            priority: TaskPriority::High,
+            due_date: None,
+            is_all_day: false,
+            assignee: Some(author.clone()),
+            labels: vec!["bug".to_string()],
+            project: SnapshotProject { id: Uuid::new_v4(), name: "Web".to_string() },
+            comments: Some(vec![SnapshotComment { author, content: "<img src=x>".to_string(), created_at: Utc::now() }]),
+            captured_at: Utc::now(),
+        };
+
+        let html = render_html(&snapshot, None);
+        assert!(html.contains("<title>#42 Fix &lt;b&gt;login&lt;/b&gt;</title>"));
+        assert!(html.contains("<strong>Steps</strong>"));
+        assert!(html.contains("<dd>1 of 2 done</dd>"));
+        assert!(html.contains("Ada &lt;3"));
+        assert!(!html.contains("<img"));
+        assert!(html.contains("Comments (1)"));
+    }
+}
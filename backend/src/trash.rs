@@ -0,0 +1,70 @@
+// Deleted projects and boards go to the trash first (see migration 032). The
+// sweep job re-enqueues itself every hour and purges whatever has been in the
+// trash for longer than TRASH_RETENTION_DAYS (30 by default).
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use std::env;
+use std::sync::OnceLock;
+use tracing::info;
+
+use crate::database::{
+    connection::Database,
+    models::Job,
+    queries::{JobQueries, TrashQueries},
+};
+use crate::jobs::{self, JobHandler};
+use crate::utils::errors::AppError;
+
+pub const TRASH_SWEEP_JOB: &str = "trash_sweep";
+const SWEEP_INTERVAL_MINUTES: i64 = 60;
+const DEFAULT_RETENTION_DAYS: i32 = 30;
+
+pub fn retention_days() -> i32 {
+    static DAYS: OnceLock<i32> = OnceLock::new();
+    *DAYS.get_or_init(|| {
+        env::var("TRASH_RETENTION_DAYS")
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .filter(|days: &i32| *days >= 0)
+            .unwrap_or(DEFAULT_RETENTION_DAYS)
+    })
+}
+
+// Makes sure a sweep is queued, unless one is already pending
+pub async fn schedule(pool: &PgPool, run_at: DateTime<Utc>) -> Result<(), AppError> {
+    if !JobQueries::has_pending_job(pool, TRASH_SWEEP_JOB).await? {
+        jobs::enqueue_at(pool, TRASH_SWEEP_JOB, &json!({}), run_at).await?;
+    }
+    Ok(())
+}
+
+pub struct TrashSweepJob {
+    database: Database,
+}
+
+impl TrashSweepJob {
+    pub fn new(database: Database) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait]
+impl JobHandler for TrashSweepJob {
+    async fn run(&self, _job: &Job) -> anyhow::Result<()> {
+        let pool = self.database.pool();
+
+        // Same as the digest scheduler: queue the next run before doing the work
+        schedule(pool, Utc::now() + Duration::minutes(SWEEP_INTERVAL_MINUTES)).await?;
+
+        let cutoff = Utc::now() - Duration::days(i64::from(retention_days()));
+        let (projects, boards) = TrashQueries::purge_trash(pool, cutoff).await?;
+
+        if projects > 0 || boards > 0 {
+            info!("Purged {} projects and {} boards from the trash", projects, boards);
+        }
+        Ok(())
+    }
+}
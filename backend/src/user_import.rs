@@ -0,0 +1,463 @@
+// Bulk account creation for onboarding a whole organisation: an instance admin
+// uploads a CSV with the columns email, username, display_name and optionally
+// team (a team id or name), and every row becomes an account unless it's
+// invalid or the email or username is taken.
+//
+// Rows are validated like registrations and imported CHUNK_SIZE to a
+// transaction, each in its own savepoint, so a bad row is reported without
+// undoing the others. New accounts get a random password and a password reset
+// token, sent in an invitation email, to choose their own. Files with more than
+// MAX_ROWS rows are rejected before anything is written.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::{Connection, PgConnection, PgPool};
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::auth::{password, registration::RegistrationPolicy, tokens};
+use crate::database::models::User;
+use crate::database::queries::UserImportQueries;
+use crate::email::{queue_email, templates::EmailTemplate};
+use crate::integrations::app_base_url;
+use crate::quotas::{self, Limits};
+use crate::utils::errors::AppError;
+use crate::utils::i18n;
+use crate::utils::validation;
+
+pub const MAX_ROWS: usize = 500;
+const CHUNK_SIZE: usize = 50;
+// How long the emailed link to choose a password works
+pub const PASSWORD_SETUP_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportRow {
+    // Where the record starts in the file, header included
+    pub line: usize,
+    pub email: String,
+    pub username: String,
+    pub display_name: String,
+    pub team: Option<String>,
+}
+
+impl ImportRow {
+    // Normalizes the row the way registration does, then validates it
+    pub fn validate(&mut self, policy: &RegistrationPolicy) -> Result<(), AppError> {
+        self.email = self.email.trim().to_lowercase();
+        self.username = self.username.trim().to_string();
+        self.display_name = validation::normalize_name(self.display_name.trim());
+        validation::validate_email(&self.email)?;
+        validation::validate_username(&self.username)?;
+        validation::validate_display_name(&self.display_name)?;
+        policy.check_email_domain(&self.email)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    Created,
+    SkippedDuplicate,
+    Invalid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowReport {
+    pub line: usize,
+    pub email: String,
+    pub status: RowStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub created: usize,
+    pub skipped_duplicate: usize,
+    pub invalid: usize,
+    pub rows: Vec<RowReport>,
+}
+
+impl ImportReport {
+    fn push(&mut self, row: RowReport) {
+        match row.status {
+            RowStatus::Created => self.created += 1,
+            RowStatus::SkippedDuplicate => self.skipped_duplicate += 1,
+            RowStatus::Invalid => self.invalid += 1,
+        }
+        self.rows.push(row);
+    }
+}
+
+// RFC 4180 records, read one at a time: comma separated fields, quoted with
+// "" standing for a quote when they contain commas, quotes or line breaks.
+// Blank lines are skipped. Each record comes with the line it starts on.
+pub struct CsvRecords<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> CsvRecords<'a> {
+    pub fn new(text: &'a str) -> Self {
+        CsvRecords { chars: text.trim_start_matches('\u{feff}').chars().peekable(), line: 1 }
+    }
+}
+
+impl Iterator for CsvRecords<'_> {
+    type Item = Result<(usize, Vec<String>), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.chars.peek()?;
+            let start = self.line;
+            let mut fields = Vec::new();
+            let mut field = String::new();
+            let mut quoted = false;
+
+            loop {
+                match self.chars.next() {
+                    None if quoted => return Some(Err(format!("Line {}: a quoted field isn't closed", start))),
+                    None => break,
+                    Some('"') if quoted => {
+                        if self.chars.peek() == Some(&'"') {
+                            self.chars.next();
+                            field.push('"');
+                        } else {
+                            quoted = false;
+                        }
+                    }
+                    Some('"') if field.is_empty() => quoted = true,
+                    Some(c) if quoted => {
+                        if c == '\n' {
+                            self.line += 1;
+                        }
+                        field.push(c);
+                    }
+                    Some(',') => fields.push(std::mem::take(&mut field)),
+                    Some('\r') if self.chars.peek() == Some(&'\n') => {}
+                    Some('\n') => {
+                        self.line += 1;
+                        break;
+                    }
+                    Some(c) => field.push(c),
+                }
+            }
+            fields.push(field);
+
+            if fields.len() == 1 && fields[0].trim().is_empty() {
+                continue;
+            }
+            return Some(Ok((start, fields)));
+        }
+    }
+}
+
+// Where each column is, from the header row. Names are matched ignoring case;
+// other columns are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Columns {
+    email: usize,
+    username: usize,
+    display_name: usize,
+    team: Option<usize>,
+}
+
+impl Columns {
+    pub fn from_header(header: &[String]) -> Result<Self, String> {
+        let position = |name: &str| header.iter().position(|column| column.trim().eq_ignore_ascii_case(name));
+        let required = |name: &str| position(name).ok_or_else(|| format!("The CSV has no {} column", name));
+
+        Ok(Columns {
+            email: required("email")?,
+            username: required("username")?,
+            display_name: required("display_name")?,
+            team: position("team"),
+        })
+    }
+
+    pub fn row(&self, line: usize, fields: &[String]) -> ImportRow {
+        let field = |index: usize| fields.get(index).map(|value| value.trim().to_string()).unwrap_or_default();
+        ImportRow {
+            line,
+            email: field(self.email),
+            username: field(self.username),
+            display_name: field(self.display_name),
+            team: self.team.map(field).filter(|team| !team.is_empty()),
+        }
+    }
+}
+
+// The rows of a CSV file, or why it can't be imported at all
+pub fn parse(body: &[u8]) -> Result<Vec<ImportRow>, AppError> {
+    let text = std::str::from_utf8(body).map_err(|_| AppError::BadRequest("The CSV must be UTF-8".to_string()))?;
+    let mut records = CsvRecords::new(text);
+    let header = match records.next() {
+        Some(record) => record.map_err(AppError::BadRequest)?.1,
+        None => return Err(AppError::BadRequest("The CSV is empty".to_string())),
+    };
+    let columns = Columns::from_header(&header).map_err(AppError::BadRequest)?;
+
+    let mut rows = Vec::new();
+    for record in records {
+        let (line, fields) = record.map_err(AppError::BadRequest)?;
+        if rows.len() == MAX_ROWS {
+            return Err(AppError::BadRequest(format!("At most {} rows can be imported at once", MAX_ROWS)));
+        }
+        rows.push(columns.row(line, &fields));
+    }
+    Ok(rows)
+}
+
+// The text reported for a row that failed validation or a limit
+fn reason(error: AppError) -> Result<String, AppError> {
+    match error {
+        AppError::Invalid(message) => Ok(message.render(i18n::current_locale())),
+        AppError::Validation(message) | AppError::EmailDomainNotAllowed(message) | AppError::QuotaExceeded(message) => {
+            Ok(message)
+        }
+        other => Err(other),
+    }
+}
+
+fn is_unique_violation(error: &AppError) -> bool {
+    matches!(error, AppError::Database(sqlx::Error::Database(e)) if e.is_unique_violation())
+}
+
+type TeamLookup = HashMap<String, Result<(Uuid, Limits), String>>;
+
+// Every team the rows name, looked up once
+async fn resolve_teams(pool: &PgPool, rows: &[ImportRow]) -> Result<TeamLookup, AppError> {
+    let mut teams = TeamLookup::new();
+    for team in rows.iter().filter_map(|row| row.team.as_deref()) {
+        let key = team.to_lowercase();
+        if teams.contains_key(&key) {
+            continue;
+        }
+        let resolved = match UserImportQueries::find_teams(pool, team).await?[..] {
+            [team_id] => Ok((team_id, quotas::team_limits(pool, team_id).await?)),
+            [] => Err(format!("Team {} not found", team)),
+            _ => Err(format!("Several teams are named {}, use the team's id", team)),
+        };
+        teams.insert(key, resolved);
+    }
+    Ok(teams)
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+struct Created {
+    user_id: Uuid,
+    token: String,
+}
+
+// Creates the row's account in a savepoint of the chunk's transaction. None
+// when the email or username is taken.
+async fn create_account(
+    conn: &mut PgConnection,
+    row: &ImportRow,
+    team: Option<&(Uuid, Limits)>,
+) -> Result<Option<Created>, AppError> {
+    if UserImportQueries::account_exists(conn, &row.email, &row.username).await? {
+        return Ok(None);
+    }
+
+    // Nobody knows it; the person chooses theirs with the reset token
+    let password_hash = password::hash_password(&generate_token())
+        .map_err(|e| AppError::InternalServer(format!("Failed to hash password: {}", e)))?;
+    let user_id = UserImportQueries::create_account(conn, &row.email, &row.username, &row.display_name, &password_hash).await?;
+
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::days(PASSWORD_SETUP_DAYS);
+    UserImportQueries::create_password_reset_token(conn, user_id, &tokens::hash_token(&token), expires_at).await?;
+
+    if let Some((team_id, limits)) = team {
+        let members = UserImportQueries::count_team_members(conn, *team_id).await?;
+        quotas::check_team_member_count(members, limits)?;
+        UserImportQueries::add_team_member(conn, *team_id, user_id).await?;
+    }
+
+    Ok(Some(Created { user_id, token }))
+}
+
+async fn import_row(
+    conn: &mut PgConnection,
+    policy: &RegistrationPolicy,
+    mut row: ImportRow,
+    teams: &TeamLookup,
+) -> Result<(RowReport, Option<Created>), AppError> {
+    let mut report = RowReport {
+        line: row.line,
+        email: row.email.trim().to_string(),
+        status: RowStatus::Invalid,
+        user_id: None,
+        team_id: None,
+        reason: None,
+    };
+
+    if let Err(e) = row.validate(policy) {
+        report.reason = Some(reason(e)?);
+        return Ok((report, None));
+    }
+    report.email = row.email.clone();
+    let team = match row.team.as_deref().map(|team| &teams[&team.to_lowercase()]) {
+        Some(Ok(team)) => Some(team),
+        Some(Err(e)) => {
+            report.reason = Some(e.clone());
+            return Ok((report, None));
+        }
+        None => None,
+    };
+
+    let mut savepoint = conn.begin().await?;
+    let result = create_account(&mut savepoint, &row, team).await;
+    let created = match result {
+        Ok(Some(created)) => {
+            savepoint.commit().await?;
+            created
+        }
+        Ok(None) => {
+            savepoint.rollback().await?;
+            report.status = RowStatus::SkippedDuplicate;
+            return Ok((report, None));
+        }
+        // Registered or imported concurrently
+        Err(e) if is_unique_violation(&e) => {
+            savepoint.rollback().await?;
+            report.status = RowStatus::SkippedDuplicate;
+            return Ok((report, None));
+        }
+        Err(e) => {
+            savepoint.rollback().await?;
+            report.reason = Some(reason(e)?);
+            return Ok((report, None));
+        }
+    };
+
+    report.status = RowStatus::Created;
+    report.user_id = Some(created.user_id);
+    report.team_id = team.map(|(team_id, _)| *team_id);
+    Ok((report, Some(created)))
+}
+
+// Imports the rows and emails every new account its link to choose a
+// password. Emails go out once the chunk their row is in has been committed.
+pub async fn import(pool: &PgPool, policy: &RegistrationPolicy, rows: Vec<ImportRow>, inviter: &User) -> Result<ImportReport, AppError> {
+    let teams = resolve_teams(pool, &rows).await?;
+    let mut report = ImportReport::default();
+
+    let mut rows = rows.into_iter().peekable();
+    while rows.peek().is_some() {
+        let mut tx = pool.begin().await?;
+        let mut invites = Vec::new();
+        for row in rows.by_ref().take(CHUNK_SIZE) {
+            let (display_name, username) = (row.display_name.clone(), row.username.clone());
+            let (row_report, created) = import_row(&mut tx, policy, row, &teams).await?;
+            if let Some(created) = created {
+                invites.push((row_report.email.clone(), display_name, username, created));
+            }
+            report.push(row_report);
+        }
+        tx.commit().await?;
+
+        for (email, name, username, created) in invites {
+            let context = json!({
+                "name": name,
+                "inviter": inviter.display_name,
+                "username": username,
+                "days": PASSWORD_SETUP_DAYS,
+                "link": format!("{}/reset-password/{}", app_base_url(), created.token),
+            });
+            if let Err(e) = queue_email(pool, &email, Some(created.user_id), EmailTemplate::AccountInvite, context).await {
+                warn!("Failed to queue the invitation email of imported user {}: {}", created.user_id, e);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(text: &str) -> Vec<(usize, Vec<String>)> {
+        CsvRecords::new(text).collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn test_csv_records() {
+        let text = "\u{feff}email,username\r\nada@example.com,ada\r\n\r\n\"Lovelace, Ada\",\"say \"\"hi\"\"\nthere\"\nlast,row";
+        assert_eq!(
+            records(text),
+            [
+                (1, vec!["email".to_string(), "username".to_string()]),
+                (2, vec!["ada@example.com".to_string(), "ada".to_string()]),
+                (4, vec!["Lovelace, Ada".to_string(), "say \"hi\"\nthere".to_string()]),
+                (6, vec!["last".to_string(), "row".to_string()]),
+            ]
+        );
+        assert!(CsvRecords::new("a,\"open\nb").next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_columns_from_header() {
+        let header: Vec<String> = ["Display_Name", "team", "Email", "notes", "username"].map(String::from).to_vec();
+        let columns = Columns::from_header(&header).unwrap();
+        let row = columns.row(3, &["Ada".to_string(), " ".to_string(), " ada@example.com ".to_string()]);
+        assert_eq!(
+            row,
+            ImportRow {
+                line: 3,
+                email: "ada@example.com".to_string(),
+                username: String::new(),
+                display_name: "Ada".to_string(),
+                team: None,
+            }
+        );
+
+        let header: Vec<String> = ["email", "display_name"].map(String::from).to_vec();
+        assert_eq!(Columns::from_header(&header).unwrap_err(), "The CSV has no username column");
+    }
+
+    #[test]
+    fn test_row_cap() {
+        let mut csv = String::from("email,username,display_name\n");
+        for i in 0..MAX_ROWS {
+            csv.push_str(&format!("user{0}@example.com,user{0},User {0}\n", i));
+        }
+        assert_eq!(parse(csv.as_bytes()).unwrap().len(), MAX_ROWS);
+        csv.push_str("one@example.com,one,One\n");
+        assert!(parse(csv.as_bytes()).is_err());
+        assert!(parse(b"").is_err());
+    }
+
+    #[test]
+    fn test_rows_are_normalized_before_validation() {
+        let mut row = ImportRow {
+            line: 2,
+            email: " Ada@Example.COM ".to_string(),
+            username: "ada".to_string(),
+            display_name: "Ada".to_string(),
+            team: None,
+        };
+        let policy = RegistrationPolicy::default();
+        row.validate(&policy).unwrap();
+        assert_eq!(row.email, "ada@example.com");
+
+        row.email = "not an email".to_string();
+        assert!(reason(row.validate(&policy).unwrap_err()).is_ok());
+    }
+}
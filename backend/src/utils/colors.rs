@@ -0,0 +1,113 @@
+// Colors for projects and task covers. Clients may send a palette key
+// ("blue"), #RGB shorthand or #RRGGBB; colors are stored as uppercase #RRGGBB.
+// Projects also store which text color reads best on theirs, so every client
+// renders labels the same way.
+
+use serde::Serialize;
+
+use crate::database::models::ContrastText;
+
+// Served at GET /api/meta/palette
+pub const PALETTE: &[(&str, &str)] = &[
+    ("gray", "#6B7280"),
+    ("red", "#EF4444"),
+    ("orange", "#F97316"),
+    ("amber", "#F59E0B"),
+    ("yellow", "#EAB308"),
+    ("green", "#22C55E"),
+    ("teal", "#14B8A6"),
+    ("cyan", "#06B6D4"),
+    ("blue", "#3B82F6"),
+    ("indigo", "#6366F1"),
+    ("purple", "#A855F7"),
+    ("pink", "#EC4899"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaletteColor {
+    pub key: &'static str,
+    pub hex: &'static str,
+    pub contrast_text: ContrastText,
+}
+
+pub fn palette() -> Vec<PaletteColor> {
+    PALETTE
+        .iter()
+        .map(|&(key, hex)| PaletteColor { key, hex, contrast_text: contrast_text(hex) })
+        .collect()
+}
+
+// Uppercase #RRGGBB for a palette key (case-insensitive), #RGB or #RRGGBB
+pub fn normalize(color: &str) -> Option<String> {
+    let color = color.trim();
+    if let Some((_, hex)) = PALETTE.iter().find(|(key, _)| key.eq_ignore_ascii_case(color)) {
+        return Some(hex.to_string());
+    }
+
+    let digits = color.strip_prefix('#')?;
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits = match digits.len() {
+        3 => digits.chars().flat_map(|c| [c, c]).collect(),
+        6 => digits.to_string(),
+        _ => return None,
+    };
+    Some(format!("#{}", digits.to_ascii_uppercase()))
+}
+
+// WCAG relative luminance of a #RRGGBB color, 0.0 (black) to 1.0 (white)
+fn luminance(hex: &str) -> Option<f64> {
+    let channel = |range: std::ops::Range<usize>| {
+        let value = u8::from_str_radix(hex.get(range)?, 16).ok()? as f64 / 255.0;
+        Some(if value <= 0.03928 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) })
+    };
+    if hex.len() != 7 {
+        return None;
+    }
+    Some(0.2126 * channel(1..3)? + 0.7152 * channel(3..5)? + 0.0722 * channel(5..7)?)
+}
+
+// Dark text where it contrasts more than white text would. Expects a
+// normalized color; anything else gets light text.
+pub fn contrast_text(hex: &str) -> ContrastText {
+    match luminance(hex) {
+        Some(luminance) if (luminance + 0.05) / 0.05 >= 1.05 / (luminance + 0.05) => ContrastText::Dark,
+        _ => ContrastText::Light,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colors_are_normalized_to_six_digit_hex() {
+        assert_eq!(normalize("#fa0").as_deref(), Some("#FFAA00"));
+        assert_eq!(normalize("#3b82f6").as_deref(), Some("#3B82F6"));
+        assert_eq!(normalize(" Blue ").as_deref(), Some("#3B82F6"));
+
+        assert_eq!(normalize("fa0"), None);
+        assert_eq!(normalize("#fa00"), None);
+        assert_eq!(normalize("#ggg"), None);
+        assert_eq!(normalize("magenta"), None);
+    }
+
+    #[test]
+    fn test_contrast_text_follows_luminance() {
+        assert_eq!(contrast_text("#FFFFFF"), ContrastText::Dark);
+        assert_eq!(contrast_text("#EAB308"), ContrastText::Dark);
+        assert_eq!(contrast_text("#000000"), ContrastText::Light);
+        assert_eq!(contrast_text("#1E3A8A"), ContrastText::Light);
+        // The gray where both text colors contrast about equally
+        assert_eq!(contrast_text("#767676"), ContrastText::Dark);
+        assert_eq!(contrast_text("#757575"), ContrastText::Light);
+    }
+
+    #[test]
+    fn test_palette_is_normalized() {
+        for color in palette() {
+            assert_eq!(normalize(color.hex).as_deref(), Some(color.hex), "{}", color.key);
+        }
+    }
+}
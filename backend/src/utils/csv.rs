@@ -0,0 +1,30 @@
+// CSV for spreadsheets (cycle-time analytics, team audit exports)
+
+// Quotes fields with separators, quotes or line breaks. Values that a
+// spreadsheet would take for a formula get a leading apostrophe.
+pub fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field_escaping() {
+        assert_eq!(csv_field("Fix login"), "Fix login");
+        assert_eq!(csv_field("Fix login, signup"), "\"Fix login, signup\"");
+        assert_eq!(csv_field("The \"new\" editor"), "\"The \"\"new\"\" editor\"");
+        assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+        assert_eq!(csv_field("-1 day"), "'-1 day");
+    }
+}
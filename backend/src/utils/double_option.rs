@@ -0,0 +1,54 @@
+// Serde helper for update requests that need to tell a missing field from an
+// explicit null: absent is None (keep), null is Some(None) (clear) and a value
+// is Some(Some(value)). Use with #[serde(default, with = "crate::utils::double_option")].
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<T, S>(value: &Option<Option<T>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    match value {
+        Some(inner) => inner.serialize(serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    // Only called when the field is present, so null becomes Some(None)
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// Whether the client sent null for the field
+pub fn is_cleared<T>(value: &Option<Option<T>>) -> bool {
+    matches!(value, Some(None))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Patch {
+        #[serde(default, with = "crate::utils::double_option")]
+        note: Option<Option<String>>,
+    }
+
+    #[test]
+    fn test_absent_null_and_value_are_distinct() {
+        let absent: Patch = serde_json::from_str("{}").unwrap();
+        let null: Patch = serde_json::from_str(r#"{"note":null}"#).unwrap();
+        let value: Patch = serde_json::from_str(r#"{"note":"hi"}"#).unwrap();
+
+        assert_eq!(absent.note, None);
+        assert_eq!(null.note, Some(None));
+        assert_eq!(value.note, Some(Some("hi".to_string())));
+        assert!(super::is_cleared(&null.note));
+        assert!(!super::is_cleared(&absent.note));
+    }
+}
@@ -1,22 +1,67 @@
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 use serde_json::json;
 use std::fmt;
 
+use crate::database::models::RelatedTask;
+use crate::utils::i18n::{self, Locale, Message};
+
+// One violation in a structured validation error
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub reason: Message,
+}
+
+impl FieldError {
+    pub fn new(field: &str, reason: Message) -> Self {
+        FieldError { field: field.to_string(), reason }
+    }
+}
+
+// The message is rendered in the language of the request being handled
+impl Serialize for FieldError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("FieldError", 3)?;
+        state.serialize_field("field", &self.field)?;
+        state.serialize_field("message", &self.reason.render(i18n::current_locale()))?;
+        state.serialize_field("reason", &self.reason)?;
+        state.end()
+    }
+}
+
 #[derive(Debug)]
 pub enum AppError {
     Database(sqlx::Error),
     DatabaseError(String),
     Validation(String),
+    // A catalog code with parameters, localized in the response
+    Invalid(Message),
+    // Every violation found in a request body, reported together
+    InvalidFields(Vec<FieldError>),
     NotFound(String),
     Unauthorized(String),
     Forbidden(String),
+    // Registration needs an invitation, distinct from a disallowed email domain
+    RegistrationClosed(String),
+    EmailDomainNotAllowed(String),
     Conflict(String),
+    // Mutation of an archived project; a 409 with its own code so clients can offer to re-activate
+    ProjectArchived(String),
+    // A new task looks like these open ones; a 409 listing them so clients can offer to force it
+    PossibleDuplicates(Vec<RelatedTask>),
     InternalServer(String),
     BadRequest(String),
+    PayloadTooLarge(String),
+    QuotaExceeded(String),
+    // An attachment held back by the virus scan
+    Locked(String),
+    // Seconds until the client may retry, sent as Retry-After
+    RateLimited(u64),
 }
 
 impl fmt::Display for AppError {
@@ -25,18 +70,70 @@ impl fmt::Display for AppError {
             AppError::Database(err) => write!(f, "Database error: {}", err),
             AppError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
             AppError::Validation(msg) => write!(f, "Validation error: {}", msg),
+            AppError::Invalid(reason) => write!(f, "Validation error: {}", reason.render(Locale::En)),
+            AppError::InvalidFields(errors) => write!(f, "Validation error: {}", field_summary(errors, Locale::En)),
             AppError::NotFound(msg) => write!(f, "Not found: {}", msg),
             AppError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             AppError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+            AppError::RegistrationClosed(msg) => write!(f, "Registration closed: {}", msg),
+            AppError::EmailDomainNotAllowed(msg) => write!(f, "Email domain not allowed: {}", msg),
             AppError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+            AppError::ProjectArchived(msg) => write!(f, "Project archived: {}", msg),
+            AppError::PossibleDuplicates(tasks) => write!(f, "Possible duplicate of {} open tasks", tasks.len()),
             AppError::InternalServer(msg) => write!(f, "Internal server error: {}", msg),
             AppError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
+            AppError::PayloadTooLarge(msg) => write!(f, "Payload too large: {}", msg),
+            AppError::QuotaExceeded(msg) => write!(f, "Quota exceeded: {}", msg),
+            AppError::Locked(msg) => write!(f, "Locked: {}", msg),
+            AppError::RateLimited(retry_after) => write!(f, "Rate limited: retry in {} seconds", retry_after),
         }
     }
 }
 
+impl std::error::Error for AppError {}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let locale = i18n::current_locale();
+
+        if let AppError::InvalidFields(errors) = self {
+            let body = Json(json!({
+                "error": {
+                    "code": "VALIDATION_ERROR",
+                    "message": field_summary(&errors, locale),
+                    "fields": errors,
+                }
+            }));
+            return (StatusCode::BAD_REQUEST, body).into_response();
+        }
+
+        if let AppError::Invalid(reason) = self {
+            let body = Json(json!({
+                "error": {
+                    "code": "VALIDATION_ERROR",
+                    "message": reason.render(locale),
+                    "reason": reason,
+                }
+            }));
+            return (StatusCode::BAD_REQUEST, body).into_response();
+        }
+
+        if let AppError::PossibleDuplicates(tasks) = self {
+            let body = Json(json!({
+                "error": {
+                    "code": "POSSIBLE_DUPLICATE",
+                    "message": Message::new("possible_duplicate").render(locale),
+                    "tasks": tasks,
+                }
+            }));
+            return (StatusCode::CONFLICT, body).into_response();
+        }
+
+        let retry_after = match &self {
+            AppError::RateLimited(retry_after) => Some(*retry_after),
+            _ => None,
+        };
+
         let (status, error_code, message) = match self {
             AppError::Database(err) => {
                 tracing::error!("Database error: {}", err);
@@ -59,11 +156,41 @@ impl IntoResponse for AppError {
                 "VALIDATION_ERROR",
                 msg,
             ),
+            AppError::Invalid(reason) => (
+                StatusCode::BAD_REQUEST,
+                "VALIDATION_ERROR",
+                reason.render(locale),
+            ),
+            AppError::InvalidFields(errors) => (
+                StatusCode::BAD_REQUEST,
+                "VALIDATION_ERROR",
+                field_summary(&errors, locale),
+            ),
             AppError::BadRequest(msg) => (
                 StatusCode::BAD_REQUEST,
                 "BAD_REQUEST",
                 msg,
             ),
+            AppError::PayloadTooLarge(msg) => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "PAYLOAD_TOO_LARGE",
+                msg,
+            ),
+            AppError::QuotaExceeded(msg) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "QUOTA_EXCEEDED",
+                msg,
+            ),
+            AppError::Locked(msg) => (
+                StatusCode::LOCKED,
+                "LOCKED",
+                msg,
+            ),
+            AppError::RateLimited(retry_after) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "RATE_LIMITED",
+                Message::new("rate_limited").with("seconds", retry_after).render(locale),
+            ),
             AppError::NotFound(msg) => (
                 StatusCode::NOT_FOUND,
                 "NOT_FOUND",
@@ -79,11 +206,31 @@ impl IntoResponse for AppError {
                 "FORBIDDEN",
                 msg,
             ),
+            AppError::RegistrationClosed(msg) => (
+                StatusCode::FORBIDDEN,
+                "REGISTRATION_CLOSED",
+                msg,
+            ),
+            AppError::EmailDomainNotAllowed(msg) => (
+                StatusCode::FORBIDDEN,
+                "EMAIL_DOMAIN_NOT_ALLOWED",
+                msg,
+            ),
             AppError::Conflict(msg) => (
                 StatusCode::CONFLICT,
                 "CONFLICT",
                 msg,
             ),
+            AppError::ProjectArchived(msg) => (
+                StatusCode::CONFLICT,
+                "PROJECT_ARCHIVED",
+                msg,
+            ),
+            AppError::PossibleDuplicates(tasks) => (
+                StatusCode::CONFLICT,
+                "POSSIBLE_DUPLICATE",
+                format!("Possible duplicate of {} open tasks", tasks.len()),
+            ),
             AppError::InternalServer(msg) => {
                 tracing::error!("Internal server error: {}", msg);
                 (
@@ -94,17 +241,40 @@ impl IntoResponse for AppError {
             }
         };
 
-        let body = Json(json!({
-            "error": {
-                "code": error_code,
-                "message": message,
-            }
-        }));
+        // Free-form messages are English; other languages get the code's
+        // catalog message, with the original kept as detail
+        let body = if locale == Locale::En || error_code == "RATE_LIMITED" {
+            Json(json!({
+                "error": {
+                    "code": error_code,
+                    "message": message,
+                }
+            }))
+        } else {
+            Json(json!({
+                "error": {
+                    "code": error_code,
+                    "message": i18n::error_summary(locale, error_code),
+                    "detail": message,
+                }
+            }))
+        };
 
-        (status, body).into_response()
+        match retry_after {
+            Some(retry_after) => (status, [(header::RETRY_AFTER, retry_after.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
     }
 }
 
+fn field_summary(errors: &[FieldError], locale: Locale) -> String {
+    errors
+        .iter()
+        .map(|error| format!("{}: {}", error.field, error.reason.render(locale)))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 // Implement From traits for common error types
 impl From<sqlx::Error> for AppError {
     fn from(err: sqlx::Error) -> Self {
@@ -121,6 +291,12 @@ impl From<anyhow::Error> for AppError {
     }
 }
 
+impl From<serde_json::Error> for AppError {
+    fn from(err: serde_json::Error) -> Self {
+        AppError::InternalServer(format!("Failed to serialize response: {}", err))
+    }
+}
+
 impl From<uuid::Error> for AppError {
     fn from(err: uuid::Error) -> Self {
         AppError::Validation(format!("Invalid UUID: {}", err))
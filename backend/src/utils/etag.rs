@@ -0,0 +1,133 @@
+use axum::{
+    http::{
+        header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::utils::extractors::Json;
+
+// Weak validators for GET responses whose content is derived from a
+// "last modified" timestamp (e.g. the newest task/board in a project), and
+// strong ones for stored files. Handlers compute the ETag first and skip
+// loading the payload on a match.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ETag(String);
+
+impl ETag {
+    pub fn weak_from_timestamp(version: DateTime<Utc>) -> Self {
+        ETag(format!("W/\"{:x}\"", version.timestamp_micros()))
+    }
+
+    // For content that never changes under its tag, e.g. a file's hash
+    pub fn strong(tag: &str) -> Self {
+        ETag(format!("\"{}\"", tag))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    // Weak comparison as per RFC 7232: the W/ prefix is ignored on both sides
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        let Some(value) = headers.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) else {
+            return false;
+        };
+
+        let ours = opaque_tag(&self.0);
+        value
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || opaque_tag(candidate) == ours)
+    }
+
+    pub fn not_modified(&self) -> Response {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        self.apply(response.headers_mut());
+        response
+    }
+
+    pub fn json<T: Serialize>(&self, body: T) -> Response {
+        let mut response = Json(body).into_response();
+        self.apply(response.headers_mut());
+        response
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        if let Ok(value) = HeaderValue::from_str(&self.0) {
+            headers.insert(ETAG, value);
+        }
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("private, no-cache"));
+    }
+}
+
+fn opaque_tag(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn conditional_get(etag: &ETag, if_none_match: Option<&str>) -> StatusCode {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = if_none_match {
+            headers.insert(IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+        }
+
+        if etag.matches(&headers) {
+            etag.not_modified().status()
+        } else {
+            etag.json(serde_json::json!({ "tasks": [] })).status()
+        }
+    }
+
+    #[test]
+    fn test_conditional_get_sequence() {
+        let version = Utc::now();
+        let etag = ETag::weak_from_timestamp(version);
+
+        // First fetch has no validator
+        assert_eq!(conditional_get(&etag, None), StatusCode::OK);
+
+        // Re-fetch with the returned ETag is not modified
+        assert_eq!(conditional_get(&etag, Some(etag.as_str())), StatusCode::NOT_MODIFIED);
+
+        // After a mutation bumps the version the old ETag no longer matches
+        let changed = ETag::weak_from_timestamp(version + Duration::milliseconds(1));
+        assert_eq!(conditional_get(&changed, Some(etag.as_str())), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_if_none_match_lists_and_wildcard() {
+        let etag = ETag::weak_from_timestamp(Utc::now());
+
+        let list = format!("\"other\", {}", etag.as_str());
+        assert_eq!(conditional_get(&etag, Some(&list)), StatusCode::NOT_MODIFIED);
+        assert_eq!(conditional_get(&etag, Some("*")), StatusCode::NOT_MODIFIED);
+        assert_eq!(conditional_get(&etag, Some("W/\"stale\"")), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_strong_tags_compare_weakly() {
+        let etag = ETag::strong("abc123");
+        assert_eq!(etag.as_str(), "\"abc123\"");
+        assert_eq!(conditional_get(&etag, Some("\"abc123\"")), StatusCode::NOT_MODIFIED);
+        assert_eq!(conditional_get(&etag, Some("W/\"abc123\"")), StatusCode::NOT_MODIFIED);
+        assert_eq!(conditional_get(&etag, Some("\"abc124\"")), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_response_carries_cache_headers() {
+        let etag = ETag::weak_from_timestamp(Utc::now());
+        let response = etag.json(serde_json::json!({}));
+
+        assert_eq!(response.headers()[ETAG], etag.as_str());
+        assert_eq!(response.headers()[CACHE_CONTROL], "private, no-cache");
+    }
+}
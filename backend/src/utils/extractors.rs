@@ -0,0 +1,186 @@
+use axum::{
+    async_trait,
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts, Request,
+    },
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::utils::errors::AppError;
+use crate::utils::limits::BodyLimit;
+
+// Drop-in replacements for axum's Json/Path/Query extractors whose rejections
+// are rendered through AppError, so clients always get our error envelope.
+
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let limit = req.extensions().get::<BodyLimit>().copied();
+
+        match axum::Json::<T>::from_request(req, state).await {
+            Ok(axum::Json(value)) => Ok(Json(value)),
+            Err(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                Err(payload_too_large(limit))
+            }
+            Err(rejection) => Err(json_rejection_to_error(rejection)),
+        }
+    }
+}
+
+impl<T> IntoResponse for Json<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => Ok(Path(value)),
+            Err(rejection) => Err(path_rejection_to_error(rejection)),
+        }
+    }
+}
+
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Query::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Query(value)) => Ok(Query(value)),
+            Err(rejection) => Err(query_rejection_to_error(rejection)),
+        }
+    }
+}
+
+fn json_rejection_to_error(rejection: JsonRejection) -> AppError {
+    match rejection {
+        // The data error text carries the serde path of the failing field,
+        // e.g. "due_date: premature end of input"
+        JsonRejection::JsonDataError(err) => {
+            AppError::Validation(format!("Invalid request body: {}", err.body_text()))
+        }
+        JsonRejection::JsonSyntaxError(err) => {
+            AppError::Validation(format!("Malformed JSON: {}", err.body_text()))
+        }
+        JsonRejection::MissingJsonContentType(_) => AppError::Validation(
+            "Expected request with `Content-Type: application/json`".to_string(),
+        ),
+        other => AppError::BadRequest(other.body_text()),
+    }
+}
+
+fn payload_too_large(limit: Option<BodyLimit>) -> AppError {
+    match limit {
+        Some(BodyLimit(bytes)) => AppError::PayloadTooLarge(format!(
+            "Request body exceeds the limit of {} bytes",
+            bytes
+        )),
+        None => AppError::PayloadTooLarge("Request body is too large".to_string()),
+    }
+}
+
+fn path_rejection_to_error(rejection: PathRejection) -> AppError {
+    match rejection {
+        PathRejection::FailedToDeserializePathParams(err) => {
+            AppError::Validation(format!("Invalid path parameter: {}", err.body_text()))
+        }
+        other => AppError::InternalServer(other.body_text()),
+    }
+}
+
+fn query_rejection_to_error(rejection: QueryRejection) -> AppError {
+    AppError::Validation(format!("Invalid query string: {}", rejection.body_text()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::header::CONTENT_TYPE;
+
+    use crate::database::models::{CreateTaskRequest, CreateUserRequest, LoginRequest};
+
+    fn json_request(body: &'static str) -> Request {
+        Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn error_message(err: AppError) -> String {
+        match err {
+            AppError::Validation(msg) => msg,
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_rejects_malformed_json() {
+        let req = json_request(r#"{"email": "test@example.com", "username": "#);
+        let err = Json::<CreateUserRequest>::from_request(req, &()).await.err().unwrap();
+
+        assert!(error_message(err).starts_with("Malformed JSON"));
+    }
+
+    #[tokio::test]
+    async fn test_login_rejects_missing_field() {
+        let req = json_request(r#"{"email": "test@example.com"}"#);
+        let err = Json::<LoginRequest>::from_request(req, &()).await.err().unwrap();
+
+        assert!(error_message(err).contains("password"));
+    }
+
+    #[tokio::test]
+    async fn test_create_task_reports_failing_field_path() {
+        let req = json_request(r#"{"title": "Write docs", "due_date": 42}"#);
+        let err = Json::<CreateTaskRequest>::from_request(req, &()).await.err().unwrap();
+
+        assert!(error_message(err).contains("due_date"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_content_type() {
+        let req = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Body::from(r#"{"email": "test@example.com", "password": "secret"}"#))
+            .unwrap();
+        let err = Json::<LoginRequest>::from_request(req, &()).await.err().unwrap();
+
+        assert!(error_message(err).contains("application/json"));
+    }
+}
@@ -0,0 +1,127 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::utils::errors::AppError;
+
+// Sparse fieldsets (`?fields=id,title,status`): keeps only the requested
+// top-level keys of each object, so clients rendering large lists can skip
+// descriptions and other heavy fields.
+
+#[derive(Debug, Clone)]
+pub struct SparseFields(Vec<String>);
+
+impl SparseFields {
+    // Parses a comma separated field list, rejecting names not in `allowed`
+    pub fn parse(raw: Option<&str>, allowed: &[&str]) -> Result<Option<Self>, AppError> {
+        let Some(raw) = raw else {
+            return Ok(None);
+        };
+
+        let fields: Vec<String> = raw
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        if fields.is_empty() {
+            return Err(AppError::Validation("fields must list at least one field".to_string()));
+        }
+
+        let unknown: Vec<&str> = fields
+            .iter()
+            .map(String::as_str)
+            .filter(|field| !allowed.contains(field))
+            .collect();
+
+        if !unknown.is_empty() {
+            return Err(AppError::Validation(format!(
+                "Unknown fields: {}. Allowed fields: {}",
+                unknown.join(", "),
+                allowed.join(", ")
+            )));
+        }
+
+        Ok(Some(SparseFields(fields)))
+    }
+
+    pub fn includes(&self, field: &str) -> bool {
+        self.0.iter().any(|selected| selected == field)
+    }
+
+    // Projects an object, or every object of an array, onto the selected fields
+    pub fn apply(&self, value: Value) -> Value {
+        match value {
+            Value::Array(items) => Value::Array(items.into_iter().map(|item| self.apply(item)).collect()),
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .filter(|(key, _)| self.0.iter().any(|field| field == key))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+
+    pub fn project<T: Serialize>(fields: Option<&Self>, value: &T) -> Result<Value, AppError> {
+        let value = serde_json::to_value(value)?;
+        Ok(match fields {
+            Some(fields) => fields.apply(value),
+            None => value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::{Task, TaskPriority, TaskStatus};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    const ALLOWED: &[&str] = &["id", "title", "description", "status", "position"];
+
+    fn task() -> Task {
+        Task {
+            id: Uuid::new_v4(),
+            title: "Render the board".to_string(),
+            description: Some("A long description ".repeat(50)),
+            project_id: Uuid::new_v4(),
+            created_by: Uuid::new_v4(),
+            assigned_to: None,
+            status: TaskStatus::Todo,
+            priority: TaskPriority::Medium,
+            due_date: None,
+            is_all_day: false,
+            tags: Some(vec!["frontend".to_string()]),
+            cover_color: None,
+            cover_emoji: None,
+            position: 1,
+            number: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_filtered_payload_omits_descriptions() {
+        let tasks = vec![task(), task()];
+        let fields = SparseFields::parse(Some("id,title,status,position"), ALLOWED).unwrap();
+
+        let full = SparseFields::project(None, &tasks).unwrap();
+        let filtered = SparseFields::project(fields.as_ref(), &tasks).unwrap();
+
+        for item in filtered.as_array().unwrap() {
+            let item = item.as_object().unwrap();
+            assert!(!item.contains_key("description"));
+            assert_eq!(item.len(), 4);
+        }
+        assert!(filtered.to_string().len() < full.to_string().len() / 4);
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        assert!(SparseFields::parse(Some("id,password_hash"), ALLOWED).is_err());
+        assert!(SparseFields::parse(Some(" , "), ALLOWED).is_err());
+        assert!(SparseFields::parse(None, ALLOWED).unwrap().is_none());
+    }
+}
@@ -0,0 +1,73 @@
+// HTML for the printable pages (agendas, task snapshots). Markdown is rendered
+// with pulldown-cmark; raw HTML in it is shown as text and links or images
+// with schemes other than http(s) and mailto lose their target, so user
+// content can't run scripts in the page.
+
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag};
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+pub fn markdown_options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS
+}
+
+// Relative URLs and http(s) or mailto ones
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    match url.find([':', '/', '?', '#']) {
+        Some(index) if url[index..].starts_with(':') => matches!(&url[..index], "http" | "https" | "mailto"),
+        _ => true,
+    }
+}
+
+fn sanitize(url: CowStr<'_>) -> CowStr<'_> {
+    if is_safe_url(&url) {
+        url
+    } else {
+        CowStr::Borrowed("")
+    }
+}
+
+pub fn render_markdown(markdown: &str) -> String {
+    let events = Parser::new_ext(markdown, markdown_options()).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link { link_type, dest_url, title, id }) => {
+            Event::Start(Tag::Link { link_type, dest_url: sanitize(dest_url), title, id })
+        }
+        Event::Start(Tag::Image { link_type, dest_url, title, id }) => {
+            Event::Start(Tag::Image { link_type, dest_url: sanitize(dest_url), title, id })
+        }
+        event => event,
+    });
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_is_rendered() {
+        assert_eq!(render_markdown("**Ship** it\n\n- [x] tests"), "<p><strong>Ship</strong> it</p>\n<ul>\n<li><input disabled=\"\" type=\"checkbox\" checked=\"\"/>\ntests</li>\n</ul>\n");
+    }
+
+    #[test]
+    fn test_html_and_scripts_are_neutralized() {
+        let html = render_markdown("<script>alert(1)</script>\n\n[x](javascript:alert(1)) [ok](https://example.com) [rel](/tasks/1)");
+        assert!(!html.contains("<script"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains("<a href=\"\">x</a>"));
+        assert!(html.contains("<a href=\"https://example.com\">ok</a>"));
+        assert!(html.contains("<a href=\"/tasks/1\">rel</a>"));
+        assert!(!render_markdown("![i](JavaScript:alert(1))").contains("javascript"));
+    }
+}
@@ -0,0 +1,390 @@
+// Localized error messages. Validation failures are reported as a catalog code
+// plus parameters (e.g. {"code": "too_long", "field": "task_title", "max": 255})
+// and rendered in the language picked from the request's Accept-Language
+// header, falling back to English.
+
+use axum::{
+    extract::Request,
+    http::header::ACCEPT_LANGUAGE,
+    middleware::Next,
+    response::Response,
+};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+}
+
+impl Locale {
+    fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split(['-', '_']).next().unwrap_or("").trim().to_lowercase();
+        match primary.as_str() {
+            "en" => Some(Locale::En),
+            "de" => Some(Locale::De),
+            _ => None,
+        }
+    }
+
+    // Best supported language of an Accept-Language header, e.g. "de-CH, de;q=0.9, en;q=0.8"
+    pub fn from_accept_language(header: &str) -> Self {
+        let mut ranges: Vec<(&str, f32)> = header
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        // Stable, so equally weighted languages keep the client's order
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        ranges
+            .into_iter()
+            .find_map(|(tag, _)| Locale::from_tag(tag))
+            .unwrap_or_default()
+    }
+
+    fn catalog(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Locale::En => EN,
+            Locale::De => DE,
+        }
+    }
+
+    fn lookup(&self, key: &str) -> Option<&'static str> {
+        self.catalog()
+            .iter()
+            .find(|(entry, _)| *entry == key)
+            .or_else(|| EN.iter().find(|(entry, _)| *entry == key))
+            .map(|(_, text)| *text)
+    }
+}
+
+tokio::task_local! {
+    static LOCALE: Locale;
+}
+
+// Locale of the request being handled; English outside of a request
+pub fn current_locale() -> Locale {
+    LOCALE.try_with(|locale| *locale).unwrap_or_default()
+}
+
+// Makes the Accept-Language choice available to error responses built anywhere in the handler
+pub async fn localize(request: Request, next: Next) -> Response {
+    let locale = request
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(Locale::from_accept_language)
+        .unwrap_or_default();
+
+    LOCALE.scope(locale, next.run(request)).await
+}
+
+// A catalog code with its interpolation parameters. A "field" parameter names
+// a field label, which is translated too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub code: &'static str,
+    pub params: Vec<(&'static str, Value)>,
+}
+
+impl Message {
+    pub fn new(code: &'static str) -> Self {
+        Message { code, params: Vec::new() }
+    }
+
+    pub fn with(mut self, name: &'static str, value: impl Into<Value>) -> Self {
+        self.params.push((name, value.into()));
+        self
+    }
+
+    pub fn render(&self, locale: Locale) -> String {
+        let mut text = locale.lookup(self.code).unwrap_or(self.code).to_string();
+        for (name, value) in &self.params {
+            let value = match (*name, value) {
+                ("field", Value::String(field)) => locale
+                    .lookup(&format!("field.{}", field))
+                    .map(str::to_string)
+                    .unwrap_or_else(|| field.clone()),
+                (_, Value::String(value)) => value.clone(),
+                (_, value) => value.to_string(),
+            };
+            text = text.replace(&format!("{{{}}}", name), &value);
+        }
+        text
+    }
+}
+
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.params.len() + 1))?;
+        map.serialize_entry("code", self.code)?;
+        for (name, value) in &self.params {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+// Generic message for an error code (e.g. "NOT_FOUND"), used when the
+// detailed message isn't in the catalog
+pub fn error_summary(locale: Locale, error_code: &str) -> String {
+    locale
+        .lookup(&format!("error.{}", error_code))
+        .unwrap_or(error_code)
+        .to_string()
+}
+
+const EN: &[(&str, &str)] = &[
+    ("required", "{field} is required"),
+    ("too_short", "{field} must be at least {min} characters"),
+    ("too_long", "{field} must be {max} characters or less"),
+    ("invalid_email", "Invalid email format"),
+    ("username_characters", "Username can only contain letters a-z, numbers, and underscores"),
+    ("password_lowercase", "Password must contain at least one lowercase letter"),
+    ("password_uppercase", "Password must contain at least one uppercase letter"),
+    ("password_digit", "Password must contain at least one digit"),
+    ("password_special", "Password must contain at least one special character"),
+    ("invalid_color", "Color must be a hex color code (e.g., #FF0000 or #F00) or a palette color name"),
+    ("invalid_emoji", "Must be a single emoji"),
+    ("invalid_slack_webhook", "Webhook URL must be a Slack incoming webhook (https://hooks.slack.com/services/...)"),
+    ("invalid_github_repo", "Repository must be in the form owner/name"),
+    ("invalid_task_key", "Task key must be 2-10 uppercase letters"),
+    ("unknown_timezone", "Unknown timezone: {timezone}"),
+    ("wip_limit_too_low", "WIP limits must be at least 1"),
+    ("duplicate_wip_limit", "Duplicate WIP limit for column {column}"),
+    ("unsupported_swimlanes", "Boards can't be grouped by {group_by} yet"),
+    ("too_many_lanes", "A board can have at most {max} configured lanes"),
+    ("duplicate_lane", "Duplicate lane {lane}"),
+    ("duplicate_column", "A column named \"{column}\" already exists"),
+    ("invalid_lane", "\"{lane}\" is not a valid {group_by} lane"),
+    ("too_many_tags", "A task can have at most {max} tags"),
+    ("tag_too_long", "Tag \"{tag}\" must be {max} characters or less"),
+    ("too_many_ordered_tasks", "At most {max} tasks can be ordered"),
+    ("duplicate_ordered_task", "Task {task} is listed more than once"),
+    ("ordered_task_not_assigned", "Only open tasks assigned to you can be ordered"),
+    ("due_date_out_of_range", "Due date must be between 2000 and {years} years from now"),
+    ("invalid_date_range", "The start of the date range must be before its end"),
+    ("status_transition_not_allowed", "Tasks in {from} cannot be moved to {to}"),
+    ("duplicate_transition", "Duplicate transition rule for {status}"),
+    ("too_many_onboarding_tasks", "An onboarding template can have at most {max} tasks"),
+    ("onboarding_due_too_far", "Onboarding tasks can be due at most {max} days after joining"),
+    ("duplicate_staleness_threshold", "Duplicate staleness threshold for {status}"),
+    ("staleness_done_status", "Done tasks can't go stale"),
+    ("staleness_days_out_of_range", "Must be between {min} and {max} days"),
+    ("retention_years_out_of_range", "Must be between {min} and {max} years"),
+    ("retention_months_out_of_range", "Must be between {min} and {max} months"),
+    ("retention_days_out_of_range", "Must be between {min} and {max} days"),
+    ("token_scopes_required", "Choose at least one scope"),
+    ("token_projects_required", "List at least one project, or leave project_ids out for all of your projects"),
+    ("token_project_not_member", "Not a member of project {project}"),
+    ("invitation_role", "Only guests and viewers can be invited"),
+    ("report_sections_required", "Choose at least one report section"),
+    ("report_no_recipients", "A report needs at least one recipient: users, Slack or a task to comment on"),
+    ("report_recipient_not_member", "User {user} is not a member of this project"),
+    ("report_task_not_in_project", "The task to comment on must belong to this project"),
+    ("too_many_automation_rules", "A board can have at most {max} automation rules"),
+    ("duplicate_automation_rule", "Duplicate automation rule id"),
+    ("automation_actions_required", "An automation rule needs at least one action"),
+    ("too_many_automation_actions", "An automation rule can have at most {max} actions"),
+    ("duplicate_automation_action", "A rule can only have one {action} action"),
+    ("automation_rotation_required", "Choose at least one user to assign"),
+    ("automation_user_not_member", "User {user} is not a member of this project"),
+    ("invalid_weekday", "Weekday must be between 1 (Monday) and 7 (Sunday)"),
+    ("invalid_hour", "Hour must be between 0 and 23"),
+    ("invalid_week", "Week must be an ISO week such as 2024-W23"),
+    ("invalid_board_yaml", "Invalid board YAML: {error}"),
+    ("unsupported_board_version", "Unsupported board export version {version}, expected {expected}"),
+    ("unsupported_export_format", "Unsupported export format {format}"),
+    ("rate_limited", "Too many requests, retry in {seconds} seconds"),
+    ("possible_duplicate", "Similar open tasks already exist; send force: true to create it anyway"),
+    ("field.email", "Email"),
+    ("field.username", "Username"),
+    ("field.password", "Password"),
+    ("field.display_name", "Display name"),
+    ("field.team_name", "Team name"),
+    ("field.team_description", "Team description"),
+    ("field.project_name", "Project name"),
+    ("field.project_description", "Project description"),
+    ("field.task_title", "Task title"),
+    ("field.task_description", "Task description"),
+    ("field.board_name", "Board name"),
+    ("field.board_description", "Board description"),
+    ("field.report_name", "Report name"),
+    ("field.token_name", "Token name"),
+    ("field.template_name", "Template name"),
+    ("field.automation_name", "Rule name"),
+    ("field.label", "Label"),
+    ("field.template_description", "Template description"),
+    ("field.column_name", "Column name"),
+    ("field.comment", "Comment"),
+    ("field.announcement_message", "Announcement message"),
+    ("field.search_query", "Search query"),
+    ("field.webhook_url", "Webhook URL"),
+    ("error.VALIDATION_ERROR", "The request contains invalid values"),
+    ("error.BAD_REQUEST", "The request is invalid"),
+    ("error.UNAUTHORIZED", "Authentication required"),
+    ("error.FORBIDDEN", "You don't have permission to do this"),
+    ("error.NOT_FOUND", "The requested resource was not found"),
+    ("error.CONFLICT", "The request conflicts with the current state"),
+    ("error.PROJECT_ARCHIVED", "The project is archived"),
+    ("error.POSSIBLE_DUPLICATE", "Similar open tasks already exist"),
+    ("error.REGISTRATION_CLOSED", "Registration requires an invitation"),
+    ("error.EMAIL_DOMAIN_NOT_ALLOWED", "Registration isn't open for this email domain"),
+    ("error.PAYLOAD_TOO_LARGE", "The request is too large"),
+    ("error.QUOTA_EXCEEDED", "A plan limit was reached"),
+    ("error.LOCKED", "The file is held back by the virus scan"),
+    ("error.DATABASE_ERROR", "An internal error occurred"),
+    ("error.INTERNAL_ERROR", "An internal error occurred"),
+];
+
+const DE: &[(&str, &str)] = &[
+    ("required", "{field} ist erforderlich"),
+    ("too_short", "{field} muss mindestens {min} Zeichen lang sein"),
+    ("too_long", "{field} darf höchstens {max} Zeichen lang sein"),
+    ("invalid_email", "Ungültiges E-Mail-Format"),
+    ("username_characters", "Der Benutzername darf nur die Buchstaben a-z, Ziffern und Unterstriche enthalten"),
+    ("password_lowercase", "Das Passwort muss mindestens einen Kleinbuchstaben enthalten"),
+    ("password_uppercase", "Das Passwort muss mindestens einen Großbuchstaben enthalten"),
+    ("password_digit", "Das Passwort muss mindestens eine Ziffer enthalten"),
+    ("password_special", "Das Passwort muss mindestens ein Sonderzeichen enthalten"),
+    ("invalid_color", "Die Farbe muss ein Hex-Farbcode (z. B. #FF0000 oder #F00) oder der Name einer Palettenfarbe sein"),
+    ("invalid_emoji", "Muss ein einzelnes Emoji sein"),
+    ("invalid_slack_webhook", "Die Webhook-URL muss ein Slack Incoming Webhook sein (https://hooks.slack.com/services/...)"),
+    ("invalid_github_repo", "Das Repository muss die Form owner/name haben"),
+    ("invalid_task_key", "Der Aufgabenschlüssel muss aus 2-10 Großbuchstaben bestehen"),
+    ("unknown_timezone", "Unbekannte Zeitzone: {timezone}"),
+    ("wip_limit_too_low", "WIP-Limits müssen mindestens 1 sein"),
+    ("duplicate_wip_limit", "Doppeltes WIP-Limit für Spalte {column}"),
+    ("unsupported_swimlanes", "Boards können noch nicht nach {group_by} gruppiert werden"),
+    ("too_many_lanes", "Ein Board kann höchstens {max} konfigurierte Swimlanes haben"),
+    ("duplicate_lane", "Doppelte Swimlane {lane}"),
+    ("duplicate_column", "Es gibt bereits eine Spalte namens \"{column}\""),
+    ("invalid_lane", "\"{lane}\" ist keine gültige Swimlane für {group_by}"),
+    ("too_many_tags", "Eine Aufgabe kann höchstens {max} Tags haben"),
+    ("tag_too_long", "Der Tag \"{tag}\" darf höchstens {max} Zeichen lang sein"),
+    ("too_many_ordered_tasks", "Es können höchstens {max} Aufgaben sortiert werden"),
+    ("duplicate_ordered_task", "Die Aufgabe {task} ist mehrfach aufgeführt"),
+    ("ordered_task_not_assigned", "Nur eigene offene Aufgaben können sortiert werden"),
+    ("due_date_out_of_range", "Das Fälligkeitsdatum muss zwischen 2000 und {years} Jahren ab heute liegen"),
+    ("invalid_date_range", "Der Beginn des Zeitraums muss vor seinem Ende liegen"),
+    ("status_transition_not_allowed", "Aufgaben in {from} können nicht nach {to} verschoben werden"),
+    ("duplicate_transition", "Doppelte Übergangsregel für {status}"),
+    ("too_many_onboarding_tasks", "Eine Onboarding-Vorlage kann höchstens {max} Aufgaben haben"),
+    ("onboarding_due_too_far", "Onboarding-Aufgaben können höchstens {max} Tage nach dem Beitritt fällig sein"),
+    ("duplicate_staleness_threshold", "Doppelter Schwellenwert für {status}"),
+    ("staleness_done_status", "Erledigte Aufgaben können nicht veralten"),
+    ("staleness_days_out_of_range", "Muss zwischen {min} und {max} Tagen liegen"),
+    ("retention_years_out_of_range", "Muss zwischen {min} und {max} Jahren liegen"),
+    ("retention_months_out_of_range", "Muss zwischen {min} und {max} Monaten liegen"),
+    ("retention_days_out_of_range", "Muss zwischen {min} und {max} Tagen liegen"),
+    ("token_scopes_required", "Mindestens ein Scope muss gewählt werden"),
+    ("token_projects_required", "Mindestens ein Projekt angeben, oder project_ids für alle eigenen Projekte weglassen"),
+    ("token_project_not_member", "Kein Mitglied des Projekts {project}"),
+    ("invitation_role", "Eingeladen werden können nur Gäste und Betrachter"),
+    ("report_sections_required", "Mindestens ein Berichtsabschnitt muss gewählt werden"),
+    ("report_no_recipients", "Ein Bericht braucht mindestens einen Empfänger: Benutzer, Slack oder eine Aufgabe für den Kommentar"),
+    ("report_recipient_not_member", "Benutzer {user} ist kein Mitglied dieses Projekts"),
+    ("report_task_not_in_project", "Die Aufgabe für den Kommentar muss zu diesem Projekt gehören"),
+    ("too_many_automation_rules", "Ein Board kann höchstens {max} Automatisierungsregeln haben"),
+    ("duplicate_automation_rule", "Doppelte ID einer Automatisierungsregel"),
+    ("automation_actions_required", "Eine Automatisierungsregel braucht mindestens eine Aktion"),
+    ("too_many_automation_actions", "Eine Automatisierungsregel kann höchstens {max} Aktionen haben"),
+    ("duplicate_automation_action", "Eine Regel kann nur eine Aktion {action} haben"),
+    ("automation_rotation_required", "Wähle mindestens einen Benutzer für die Zuweisung"),
+    ("automation_user_not_member", "Benutzer {user} ist kein Mitglied dieses Projekts"),
+    ("invalid_weekday", "Der Wochentag muss zwischen 1 (Montag) und 7 (Sonntag) liegen"),
+    ("invalid_hour", "Die Stunde muss zwischen 0 und 23 liegen"),
+    ("invalid_week", "Die Woche muss eine ISO-Kalenderwoche wie 2024-W23 sein"),
+    ("invalid_board_yaml", "Ungültiges Board-YAML: {error}"),
+    ("unsupported_board_version", "Nicht unterstützte Board-Exportversion {version}, erwartet wird {expected}"),
+    ("unsupported_export_format", "Nicht unterstütztes Exportformat {format}"),
+    ("rate_limited", "Zu viele Anfragen, bitte in {seconds} Sekunden erneut versuchen"),
+    ("possible_duplicate", "Es gibt bereits ähnliche offene Aufgaben; mit force: true wird sie trotzdem angelegt"),
+    ("field.email", "E-Mail"),
+    ("field.username", "Benutzername"),
+    ("field.password", "Passwort"),
+    ("field.display_name", "Anzeigename"),
+    ("field.team_name", "Teamname"),
+    ("field.team_description", "Teambeschreibung"),
+    ("field.project_name", "Projektname"),
+    ("field.project_description", "Projektbeschreibung"),
+    ("field.task_title", "Aufgabentitel"),
+    ("field.task_description", "Aufgabenbeschreibung"),
+    ("field.board_name", "Boardname"),
+    ("field.board_description", "Boardbeschreibung"),
+    ("field.report_name", "Berichtsname"),
+    ("field.token_name", "Tokenname"),
+    ("field.template_name", "Vorlagenname"),
+    ("field.automation_name", "Regelname"),
+    ("field.label", "Label"),
+    ("field.template_description", "Vorlagenbeschreibung"),
+    ("field.column_name", "Spaltenname"),
+    ("field.comment", "Kommentar"),
+    ("field.announcement_message", "Ankündigungstext"),
+    ("field.search_query", "Suchanfrage"),
+    ("field.webhook_url", "Webhook-URL"),
+    ("error.VALIDATION_ERROR", "Die Anfrage enthält ungültige Werte"),
+    ("error.BAD_REQUEST", "Ungültige Anfrage"),
+    ("error.UNAUTHORIZED", "Anmeldung erforderlich"),
+    ("error.FORBIDDEN", "Dafür fehlt die Berechtigung"),
+    ("error.NOT_FOUND", "Die angeforderte Ressource wurde nicht gefunden"),
+    ("error.CONFLICT", "Die Anfrage steht im Konflikt mit dem aktuellen Zustand"),
+    ("error.PROJECT_ARCHIVED", "Das Projekt ist archiviert"),
+    ("error.POSSIBLE_DUPLICATE", "Es gibt bereits ähnliche offene Aufgaben"),
+    ("error.REGISTRATION_CLOSED", "Die Registrierung ist nur mit Einladung möglich"),
+    ("error.EMAIL_DOMAIN_NOT_ALLOWED", "Die Registrierung ist für diese E-Mail-Domain nicht freigegeben"),
+    ("error.PAYLOAD_TOO_LARGE", "Die Anfrage ist zu groß"),
+    ("error.QUOTA_EXCEEDED", "Ein Limit des Tarifs wurde erreicht"),
+    ("error.LOCKED", "Die Datei wird vom Virenscan zurückgehalten"),
+    ("error.DATABASE_ERROR", "Ein interner Fehler ist aufgetreten"),
+    ("error.INTERNAL_ERROR", "Ein interner Fehler ist aufgetreten"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_language_selection() {
+        assert_eq!(Locale::from_accept_language("de-DE,de;q=0.9,en;q=0.8"), Locale::De);
+        assert_eq!(Locale::from_accept_language("fr-CH, fr;q=0.9, de;q=0.7, en;q=0.8"), Locale::En);
+        assert_eq!(Locale::from_accept_language("en;q=0.5, de_AT"), Locale::De);
+        assert_eq!(Locale::from_accept_language("de;q=0, *"), Locale::En);
+        assert_eq!(Locale::from_accept_language(""), Locale::En);
+    }
+
+    #[test]
+    fn test_messages_interpolate_parameters() {
+        let message = Message::new("too_long").with("field", "task_title").with("max", 255);
+
+        assert_eq!(message.render(Locale::En), "Task title must be 255 characters or less");
+        assert_eq!(message.render(Locale::De), "Aufgabentitel darf höchstens 255 Zeichen lang sein");
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({ "code": "too_long", "field": "task_title", "max": 255 })
+        );
+
+        // Unknown codes render as themselves rather than failing
+        assert_eq!(Message::new("no_such_code").render(Locale::De), "no_such_code");
+    }
+
+    #[test]
+    fn test_catalogs_cover_the_same_keys() {
+        for (key, _) in EN {
+            assert!(DE.iter().any(|(entry, _)| entry == key), "German catalog is missing {}", key);
+        }
+        for (key, _) in DE {
+            assert!(EN.iter().any(|(entry, _)| entry == key), "English catalog is missing {}", key);
+        }
+    }
+}
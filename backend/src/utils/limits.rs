@@ -0,0 +1,85 @@
+use axum::{extract::DefaultBodyLimit, Extension, Router};
+use std::env;
+
+pub const DEFAULT_JSON_BODY_LIMIT: usize = 1024 * 1024; // 1MB
+pub const DEFAULT_UPLOAD_BODY_LIMIT: usize = 10 * 1024 * 1024; // 10MB
+
+// Body limit in effect for a request, read by the extractors so the 413
+// response can tell the client what the cap is
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimit(pub usize);
+
+pub fn json_body_limit() -> usize {
+    env::var("MAX_JSON_BODY_SIZE")
+        .ok()
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_JSON_BODY_LIMIT)
+}
+
+pub fn upload_body_limit() -> usize {
+    env::var("MAX_FILE_SIZE")
+        .ok()
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_UPLOAD_BODY_LIMIT)
+}
+
+// Caps how much of a request body the Json/Bytes extractors will buffer.
+// Routes nested further in can apply their own (larger) limit, the innermost wins.
+pub fn with_body_limit<S>(router: Router<S>, limit: usize) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router
+        .layer(DefaultBodyLimit::max(limit))
+        .layer(Extension(BodyLimit(limit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header::CONTENT_TYPE, Request, StatusCode},
+        routing::post,
+    };
+    use tower::ServiceExt;
+
+    use crate::utils::extractors::Json;
+
+    async fn echo(Json(value): Json<serde_json::Value>) -> Json<serde_json::Value> {
+        Json(value)
+    }
+
+    fn app(limit: usize) -> Router {
+        with_body_limit(Router::new().route("/echo", post(echo)), limit)
+    }
+
+    fn json_request(body: String) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/echo")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_body_within_limit_is_accepted() {
+        let response = app(64).oneshot(json_request(r#"{"title": "ok"}"#.to_string())).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_is_rejected_with_envelope() {
+        let body = format!(r#"{{"description": "{}"}}"#, "x".repeat(256));
+        let response = app(64).oneshot(json_request(body)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["error"]["code"], "PAYLOAD_TOO_LARGE");
+        assert!(body["error"]["message"].as_str().unwrap().contains("64 bytes"));
+    }
+}
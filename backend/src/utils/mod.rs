@@ -1,3 +1,15 @@
 // Utility functions
 pub mod validation;
-pub mod errors;
\ No newline at end of file
+pub mod colors;
+pub mod csv;
+pub mod errors;
+pub mod double_option;
+pub mod etag;
+pub mod extractors;
+pub mod fields;
+pub mod html;
+pub mod i18n;
+pub mod limits;
+pub mod pdf;
+pub mod rate_limit;
+pub mod search;
@@ -0,0 +1,198 @@
+// A minimal writer for text-only PDFs: A4 pages of single lines in Helvetica,
+// one of the standard fonts every viewer has, so no fonts are embedded. Text
+// is WinAnsi encoded; characters it can't represent become '?'. Enough for
+// printable lists such as the weekly agenda (see agenda.rs).
+
+pub const PAGE_WIDTH: f32 = 595.0;
+pub const PAGE_HEIGHT: f32 = 842.0;
+pub const MARGIN: f32 = 50.0;
+// Rough average Helvetica glyph width, as a fraction of the font size
+const AVERAGE_CHAR_WIDTH: f32 = 0.55;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TextDocument {
+    // Content streams of the finished pages
+    pages: Vec<String>,
+    current: String,
+    y: f32,
+}
+
+impl Default for TextDocument {
+    fn default() -> Self {
+        TextDocument { pages: Vec::new(), current: String::new(), y: PAGE_HEIGHT - MARGIN }
+    }
+}
+
+impl TextDocument {
+    // Adds a line below the previous one, on a new page once this one is full.
+    // Text too wide for the page is cut.
+    pub fn line(&mut self, font: Font, size: f32, indent: f32, text: &str) {
+        let leading = size * 1.4;
+        if self.y - leading < MARGIN {
+            self.pages.push(std::mem::take(&mut self.current));
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+        self.y -= leading;
+
+        let text = fit(text, size, PAGE_WIDTH - 2.0 * MARGIN - indent);
+        self.current.push_str(&format!(
+            "BT /{} {} Tf {} {} Td ({}) Tj ET\n",
+            font.resource(),
+            size,
+            MARGIN + indent,
+            self.y,
+            escape(&text)
+        ));
+    }
+
+    pub fn space(&mut self, points: f32) {
+        self.y -= points;
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        if !self.current.is_empty() || self.pages.is_empty() {
+            self.pages.push(self.current);
+        }
+
+        // Objects 1-4 are the catalog, the page tree and the fonts; each page
+        // is followed by its content stream
+        let page_ids: Vec<usize> = (0..self.pages.len()).map(|index| 5 + 2 * index).collect();
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                page_ids.iter().map(|id| format!("{} 0 R", id)).collect::<Vec<_>>().join(" "),
+                page_ids.len()
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+        ];
+        for (page_id, content) in page_ids.iter().zip(&self.pages) {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                page_id + 1
+            ));
+            objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content));
+        }
+
+        let mut output: Vec<u8> = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(output.len());
+            output.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).as_bytes());
+        }
+
+        let xref_offset = output.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            xref.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        xref.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref_offset
+        ));
+        output.extend_from_slice(xref.as_bytes());
+        output
+    }
+}
+
+// Cuts text that wouldn't fit `width` points, marking the cut
+fn fit(text: &str, size: f32, width: f32) -> String {
+    let max_chars = (width / (size * AVERAGE_CHAR_WIDTH)) as usize;
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max_chars.saturating_sub(3)).collect();
+    format!("{}...", cut.trim_end())
+}
+
+// A PDF string literal's contents in WinAnsi. Latin-1 characters have the
+// same codes there and are written as octal escapes to keep the file ASCII.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            '\u{a0}'..='\u{ff}' => escaped.push_str(&format!("\\{:03o}", c as u32)),
+            '\u{2013}' | '\u{2014}' => escaped.push('-'),
+            '\u{2022}' => escaped.push_str("\\225"),
+            '\u{2026}' => escaped.push_str("..."),
+            '\u{20ac}' => escaped.push_str("\\200"),
+            c if c.is_whitespace() => escaped.push(' '),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("Fix (login) \\ now"), "Fix \\(login\\) \\\\ now");
+        assert_eq!(escape("Café – 5€"), "Caf\\351 - 5\\200");
+        assert_eq!(escape("tab\there 🚀"), "tab here ?");
+    }
+
+    #[test]
+    fn test_long_lines_are_cut() {
+        assert_eq!(fit("Short", 10.0, 495.0), "Short");
+        let cut = fit(&"word ".repeat(100), 10.0, 495.0);
+        assert!(cut.ends_with("..."));
+        assert!(cut.chars().count() <= 90);
+    }
+
+    #[test]
+    fn test_xref_points_at_the_objects() {
+        let mut document = TextDocument::default();
+        for number in 0..80 {
+            document.line(Font::Regular, 10.0, 0.0, &format!("Line {}", number));
+        }
+        let pdf = document.finish();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        // 80 lines at 14 points don't fit on one page
+        assert!(text.contains("/Count 2"));
+
+        let startxref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        let xref = &pdf[startxref..];
+        assert!(xref.starts_with(b"xref\n0 9\n"));
+        let entries = String::from_utf8_lossy(xref);
+        for (number, entry) in entries.lines().skip(3).take(8).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", number + 1).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_empty_document_has_a_page() {
+        let pdf = TextDocument::default().finish();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/Count 1"));
+    }
+}
@@ -0,0 +1,500 @@
+// In-memory fixed-window rate limiting keyed by client IP, for the few public
+// endpoints that could otherwise be used to enumerate accounts or share tokens,
+// or by user for actions that notify many others at once and for API sessions
+// as a whole, or by API token. Counters live in the process, so each instance behind a load
+// balancer limits on its own.
+//
+// Session and token quotas are the exception: with REDIS_URL set, `Quotas`
+// keeps their counters in Redis so all instances share one window. Should
+// Redis be unreachable they fall back to the in-memory counters for a while.
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use std::collections::HashMap;
+use std::env;
+use std::hash::Hash;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::utils::errors::AppError;
+
+// Windows are pruned once this many clients are tracked
+const PRUNE_THRESHOLD: usize = 10_000;
+// How long a Redis round trip may take before the request is counted in memory
+const REDIS_TIMEOUT: Duration = Duration::from_secs(1);
+// How long to count in memory after Redis failed, before trying it again
+const REDIS_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+// Where a client stands in its current window, for the X-RateLimit headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset_after: Duration,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter<K = IpAddr> {
+    max_requests: u32,
+    window: Duration,
+    windows: Mutex<HashMap<K, (Instant, u32)>>,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        RateLimiter {
+            max_requests,
+            window,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Counts the request; RateLimited with the seconds until the window resets once over the limit
+    pub fn check(&self, key: K) -> Result<(), AppError> {
+        self.check_at(key, Instant::now())
+    }
+
+    pub fn max_requests(&self) -> u32 {
+        self.max_requests
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    // Counts the request like check, but reports the quota left either way:
+    // Err once the limit is reached
+    pub fn take(&self, key: K) -> Result<Quota, Quota> {
+        self.take_at(key, self.max_requests, Instant::now())
+    }
+
+    // take with a limit of its own for this key, e.g. an admin override
+    pub fn take_limited(&self, key: K, max_requests: u32) -> Result<Quota, Quota> {
+        self.take_at(key, max_requests, Instant::now())
+    }
+
+    // The quota left under `max_requests`, without counting a request
+    pub fn peek(&self, key: &K, max_requests: u32) -> Quota {
+        self.peek_at(key, max_requests, Instant::now())
+    }
+
+    fn check_at(&self, key: K, now: Instant) -> Result<(), AppError> {
+        self.take_at(key, self.max_requests, now)
+            .map(|_| ())
+            .map_err(|quota| AppError::RateLimited(quota.reset_after.as_secs().max(1)))
+    }
+
+    fn peek_at(&self, key: &K, max_requests: u32, now: Instant) -> Quota {
+        let windows = self.windows.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match windows.get(key) {
+            Some((started, count)) if now.duration_since(*started) < self.window => Quota {
+                limit: max_requests,
+                remaining: max_requests.saturating_sub(*count),
+                reset_after: self.window - now.duration_since(*started),
+            },
+            _ => Quota { limit: max_requests, remaining: max_requests, reset_after: self.window },
+        }
+    }
+
+    fn take_at(&self, key: K, max_requests: u32, now: Instant) -> Result<Quota, Quota> {
+        let mut windows = self.windows.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if windows.len() >= PRUNE_THRESHOLD {
+            windows.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+        }
+
+        let (started, count) = windows.entry(key).or_insert((now, 0));
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+
+        // The limit may have been lowered below what the window already used
+        let over_limit = *count >= max_requests;
+        if !over_limit {
+            *count += 1;
+        }
+        let quota = Quota {
+            limit: max_requests,
+            remaining: max_requests.saturating_sub(*count),
+            reset_after: self.window.saturating_sub(now.duration_since(*started)),
+        };
+        if over_limit {
+            Err(quota)
+        } else {
+            Ok(quota)
+        }
+    }
+}
+
+// GET /auth/availability: AVAILABILITY_RATE_LIMIT requests per minute and IP
+pub fn availability_limiter() -> &'static RateLimiter {
+    static INSTANCE: OnceLock<RateLimiter> = OnceLock::new();
+    INSTANCE.get_or_init(|| {
+        let max_requests = env::var("AVAILABILITY_RATE_LIMIT")
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(10);
+        RateLimiter::new(max_requests, Duration::from_secs(60))
+    })
+}
+
+// Public project badges: BADGE_RATE_LIMIT requests per minute and IP
+pub fn badge_limiter() -> &'static RateLimiter {
+    static INSTANCE: OnceLock<RateLimiter> = OnceLock::new();
+    INSTANCE.get_or_init(|| {
+        let max_requests = env::var("BADGE_RATE_LIMIT")
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(60);
+        RateLimiter::new(max_requests, Duration::from_secs(60))
+    })
+}
+
+// Comments and descriptions with group mentions (@project, @assignees):
+// GROUP_MENTION_RATE_LIMIT per hour and user
+pub fn group_mention_limiter() -> &'static RateLimiter<Uuid> {
+    static INSTANCE: OnceLock<RateLimiter<Uuid>> = OnceLock::new();
+    INSTANCE.get_or_init(|| {
+        let max_requests = env::var("GROUP_MENTION_RATE_LIMIT")
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(5);
+        RateLimiter::new(max_requests, Duration::from_secs(3600))
+    })
+}
+
+// Team audit exports: AUDIT_EXPORT_RATE_LIMIT per day and team
+pub fn audit_export_limiter() -> &'static RateLimiter<Uuid> {
+    static INSTANCE: OnceLock<RateLimiter<Uuid>> = OnceLock::new();
+    INSTANCE.get_or_init(|| {
+        let max_requests = env::var("AUDIT_EXPORT_RATE_LIMIT")
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(3);
+        RateLimiter::new(max_requests, Duration::from_secs(24 * 3600))
+    })
+}
+
+// Every authenticated request of an interactive session: SESSION_RATE_LIMIT
+// per hour and user. Generous, as the web client polls and loads boards in
+// bursts; it only stops runaway scripts using a session token.
+pub fn session_limiter() -> &'static RateLimiter<Uuid> {
+    static INSTANCE: OnceLock<RateLimiter<Uuid>> = OnceLock::new();
+    INSTANCE.get_or_init(|| {
+        let max_requests = env::var("SESSION_RATE_LIMIT")
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(10_000);
+        RateLimiter::new(max_requests, Duration::from_secs(3600))
+    })
+}
+
+// Requests made with an API token: API_TOKEN_RATE_LIMIT per hour and token,
+// unless an admin set another limit for the token's user. Tokens don't count
+// against their user's session bucket, nor one against another.
+pub fn token_limiter() -> &'static RateLimiter<Uuid> {
+    static INSTANCE: OnceLock<RateLimiter<Uuid>> = OnceLock::new();
+    INSTANCE.get_or_init(|| {
+        let max_requests = env::var("API_TOKEN_RATE_LIMIT")
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(1_000);
+        RateLimiter::new(max_requests, Duration::from_secs(3600))
+    })
+}
+
+// The hourly limit of each of a user's tokens, given the user's override
+pub fn token_max_requests(user_override: Option<i32>) -> u32 {
+    user_override
+        .and_then(|limit| u32::try_from(limit).ok())
+        .unwrap_or_else(|| token_limiter().max_requests())
+}
+
+// The session and token buckets, counted in Redis when it is configured
+pub struct Quotas {
+    shared: Option<SharedWindows>,
+}
+
+impl Quotas {
+    pub fn new(redis_url: Option<&str>) -> Self {
+        let shared = redis_url.and_then(|url| match redis::Client::open(url) {
+            Ok(client) => Some(SharedWindows { client, connection: tokio::sync::Mutex::new(None), paused_until: Mutex::new(None) }),
+            Err(e) => {
+                warn!("Invalid REDIS_URL, counting rate limits in memory: {}", e);
+                None
+            }
+        });
+        Quotas { shared }
+    }
+
+    pub async fn take_session(&self, user_id: Uuid) -> Result<Quota, Quota> {
+        let limiter = session_limiter();
+        self.take(limiter, "session", user_id, limiter.max_requests()).await
+    }
+
+    pub async fn take_token(&self, token_id: Uuid, max_requests: u32) -> Result<Quota, Quota> {
+        self.take(token_limiter(), "token", token_id, max_requests).await
+    }
+
+    pub async fn peek_token(&self, token_id: Uuid, max_requests: u32) -> Quota {
+        let limiter = token_limiter();
+        if let Some(shared) = self.shared.as_ref().filter(|shared| shared.available()) {
+            let window = WindowKey::new("token", token_id, limiter.window(), SystemTime::now());
+            match shared.count(&window).await {
+                Ok(count) => return window.quota(count, max_requests).unwrap_or_else(|quota| quota),
+                Err(e) => shared.pause(&e),
+            }
+        }
+        limiter.peek(&token_id, max_requests)
+    }
+
+    async fn take(&self, limiter: &RateLimiter<Uuid>, bucket: &str, key: Uuid, max_requests: u32) -> Result<Quota, Quota> {
+        if let Some(shared) = self.shared.as_ref().filter(|shared| shared.available()) {
+            let window = WindowKey::new(bucket, key, limiter.window(), SystemTime::now());
+            match shared.increment(&window).await {
+                Ok(count) => return window.quota(count, max_requests),
+                Err(e) => shared.pause(&e),
+            }
+        }
+        limiter.take_limited(key, max_requests)
+    }
+}
+
+// Fixed windows aligned to the clock, so every instance agrees on when one
+// starts. Requests refused over the limit are counted too, which changes
+// nothing: the window stays exhausted until it ends either way.
+struct SharedWindows {
+    client: redis::Client,
+    connection: tokio::sync::Mutex<Option<redis::aio::MultiplexedConnection>>,
+    paused_until: Mutex<Option<Instant>>,
+}
+
+impl SharedWindows {
+    fn available(&self) -> bool {
+        let paused_until = self.paused_until.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        paused_until.is_none_or(|until| Instant::now() >= until)
+    }
+
+    fn pause(&self, error: &redis::RedisError) {
+        warn!("Redis rate limit counters unavailable, counting in memory for {}s: {}", REDIS_RETRY_AFTER.as_secs(), error);
+        *self.paused_until.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Instant::now() + REDIS_RETRY_AFTER);
+    }
+
+    // INCR and EXPIRE together, so no counter outlives its window
+    async fn increment(&self, window: &WindowKey) -> redis::RedisResult<u32> {
+        let mut pipe = redis::pipe();
+        pipe.atomic().incr(&window.key, 1).expire(&window.key, window.ttl_seconds).ignore();
+        let (count,): (u32,) = self.query(&pipe).await?;
+        Ok(count)
+    }
+
+    async fn count(&self, window: &WindowKey) -> redis::RedisResult<u32> {
+        let mut pipe = redis::pipe();
+        pipe.get(&window.key);
+        let (count,): (Option<u32>,) = self.query(&pipe).await?;
+        Ok(count.unwrap_or(0))
+    }
+
+    // Connects on first use and again after a failure
+    async fn query<T: redis::FromRedisValue>(&self, pipe: &redis::Pipeline) -> redis::RedisResult<T> {
+        let attempt = async {
+            let mut connection = self.connection.lock().await;
+            let mut conn = match connection.as_ref() {
+                Some(conn) => conn.clone(),
+                None => connection.insert(self.client.get_multiplexed_tokio_connection().await?).clone(),
+            };
+            drop(connection);
+
+            let result = pipe.query_async(&mut conn).await;
+            if result.is_err() {
+                *self.connection.lock().await = None;
+            }
+            result
+        };
+        match tokio::time::timeout(REDIS_TIMEOUT, attempt).await {
+            Ok(result) => result,
+            Err(_) => {
+                *self.connection.lock().await = None;
+                Err(redis::RedisError::from((redis::ErrorKind::IoError, "Redis timed out")))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct WindowKey {
+    key: String,
+    ttl_seconds: i64,
+    reset_after: Duration,
+}
+
+impl WindowKey {
+    fn new(bucket: &str, id: Uuid, window: Duration, now: SystemTime) -> Self {
+        let window_seconds = window.as_secs().max(1);
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let started = now.as_secs() - now.as_secs() % window_seconds;
+        WindowKey {
+            key: format!("simplecards:rate_limit:{}:{}:{}", bucket, id, started),
+            ttl_seconds: window_seconds as i64,
+            reset_after: Duration::from_secs(started + window_seconds).saturating_sub(now),
+        }
+    }
+
+    // Err once `count` is past the limit
+    fn quota(&self, count: u32, max_requests: u32) -> Result<Quota, Quota> {
+        let quota = Quota {
+            limit: max_requests,
+            remaining: max_requests.saturating_sub(count),
+            reset_after: self.reset_after,
+        };
+        if count > max_requests {
+            Err(quota)
+        } else {
+            Ok(quota)
+        }
+    }
+}
+
+fn trust_proxy_headers() -> bool {
+    static TRUST: OnceLock<bool> = OnceLock::new();
+    *TRUST.get_or_init(|| {
+        env::var("TRUST_PROXY_HEADERS")
+            .map(|value| matches!(value.trim().to_lowercase().as_str(), "true" | "1" | "yes"))
+            .unwrap_or(false)
+    })
+}
+
+// The client's address: the first X-Forwarded-For entry behind a trusted proxy,
+// otherwise the peer address of the connection
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if trust_proxy_headers() {
+            let forwarded = parts
+                .headers
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .and_then(|value| value.trim().parse::<IpAddr>().ok());
+            if let Some(ip) = forwarded {
+                return Ok(ClientIp(ip));
+            }
+        }
+
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        Ok(ClientIp(peer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_per_ip_and_window() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let (alice, bob) = (IpAddr::from([10, 0, 0, 1]), IpAddr::from([10, 0, 0, 2]));
+        let start = Instant::now();
+
+        assert!(limiter.check_at(alice, start).is_ok());
+        assert!(limiter.check_at(alice, start).is_ok());
+        match limiter.check_at(alice, start + Duration::from_secs(15)) {
+            Err(AppError::RateLimited(retry_after)) => assert_eq!(retry_after, 45),
+            other => panic!("expected RateLimited, got {:?}", other),
+        }
+
+        // Other clients have their own window
+        assert!(limiter.check_at(bob, start).is_ok());
+
+        // The window resets
+        assert!(limiter.check_at(alice, start + Duration::from_secs(60)).is_ok());
+    }
+
+    #[test]
+    fn test_take_reports_the_quota_left() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(3600));
+        let user = Uuid::new_v4();
+        let start = Instant::now();
+
+        let quota = |remaining, reset_after| Quota { limit: 2, remaining, reset_after: Duration::from_secs(reset_after) };
+        assert_eq!(limiter.take_at(user, 2, start), Ok(quota(1, 3600)));
+        assert_eq!(limiter.take_at(user, 2, start + Duration::from_secs(600)), Ok(quota(0, 3000)));
+        // Exhausted: refused without counting, until the window resets
+        assert_eq!(limiter.take_at(user, 2, start + Duration::from_secs(1200)), Err(quota(0, 2400)));
+        assert_eq!(limiter.take_at(user, 2, start + Duration::from_secs(1800)), Err(quota(0, 1800)));
+        assert_eq!(limiter.take_at(user, 2, start + Duration::from_secs(3600)), Ok(quota(1, 3600)));
+    }
+
+    #[test]
+    fn test_limits_per_key_and_peek() {
+        let limiter = RateLimiter::new(100, Duration::from_secs(3600));
+        let token = Uuid::new_v4();
+        let start = Instant::now();
+
+        // Nothing used yet
+        assert_eq!(limiter.peek_at(&token, 3, start), Quota { limit: 3, remaining: 3, reset_after: Duration::from_secs(3600) });
+
+        assert!(limiter.take_at(token, 3, start).is_ok());
+        assert!(limiter.take_at(token, 3, start).is_ok());
+        let peeked = limiter.peek_at(&token, 3, start + Duration::from_secs(60));
+        assert_eq!(peeked, Quota { limit: 3, remaining: 1, reset_after: Duration::from_secs(3540) });
+        // Peeking doesn't count
+        assert_eq!(limiter.peek_at(&token, 3, start + Duration::from_secs(60)), peeked);
+
+        // Lowering the limit below what was used refuses further requests
+        assert_eq!(limiter.take_at(token, 1, start).unwrap_err().remaining, 0);
+        assert_eq!(limiter.peek_at(&token, 1, start).remaining, 0);
+    }
+
+    #[test]
+    fn test_shared_windows_follow_the_clock() {
+        let token = Uuid::new_v4();
+        let hour = Duration::from_secs(3600);
+        let at = |seconds| UNIX_EPOCH + Duration::from_secs(seconds);
+
+        let window = WindowKey::new("token", token, hour, at(7200 + 600));
+        assert_eq!(window.key, format!("simplecards:rate_limit:token:{}:7200", token));
+        assert_eq!(window.ttl_seconds, 3600);
+        assert_eq!(window.reset_after, Duration::from_secs(3000));
+
+        // Every instance lands in the same window until it ends
+        assert_eq!(WindowKey::new("token", token, hour, at(7200 + 3599)).key, window.key);
+        assert_ne!(WindowKey::new("token", token, hour, at(7200 + 3600)).key, window.key);
+        assert_ne!(WindowKey::new("session", token, hour, at(7200)).key, window.key);
+
+        // The count includes the request being taken
+        assert_eq!(window.quota(2, 2), Ok(Quota { limit: 2, remaining: 0, reset_after: Duration::from_secs(3000) }));
+        assert_eq!(window.quota(3, 2), Err(Quota { limit: 2, remaining: 0, reset_after: Duration::from_secs(3000) }));
+    }
+
+    #[tokio::test]
+    async fn test_quotas_count_in_memory_without_redis() {
+        // Nothing listens on port 1
+        let quotas = Quotas::new(Some("redis://127.0.0.1:1"));
+        let token = Uuid::new_v4();
+
+        assert_eq!(quotas.take_token(token, 2).await.unwrap().remaining, 1);
+        assert!(!quotas.shared.as_ref().unwrap().available());
+        assert_eq!(quotas.take_token(token, 2).await.unwrap().remaining, 0);
+        assert!(quotas.take_token(token, 2).await.is_err());
+        assert_eq!(quotas.peek_token(token, 2).await.remaining, 0);
+    }
+}
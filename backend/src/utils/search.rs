@@ -0,0 +1,133 @@
+use serde::Serialize;
+
+// Plain substring search helpers: the database matches with ILIKE, these build
+// the snippet shown for each match.
+
+const SNIPPET_CONTEXT: usize = 60;
+
+// Escapes LIKE wildcards so the query matches literally (`ESCAPE '\'`)
+pub fn escape_like(query: &str) -> String {
+    let mut escaped = String::with_capacity(query.len());
+    for c in query.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Task number in a picker query: "142", "#142" or "SC-142"
+pub fn task_number(query: &str) -> Option<i32> {
+    let digits = match query.rsplit_once('-') {
+        Some((key, digits)) if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric()) => digits,
+        Some(_) => return None,
+        None => query.strip_prefix('#').unwrap_or(query),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+// Excerpt around the first match. Highlights are [start, end) character
+// offsets into `text`, so clients can mark them without parsing markup.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Snippet {
+    pub text: String,
+    pub highlights: Vec<(usize, usize)>,
+}
+
+// Case-insensitive match positions, in characters
+fn match_ranges(content: &[char], needle: &[char]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    if needle.is_empty() || needle.len() > content.len() {
+        return ranges;
+    }
+
+    let mut i = 0;
+    while i + needle.len() <= content.len() {
+        let matched = content[i..i + needle.len()]
+            .iter()
+            .zip(needle)
+            .all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()));
+        if matched {
+            ranges.push((i, i + needle.len()));
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    ranges
+}
+
+pub fn snippet(content: &str, query: &str) -> Snippet {
+    let chars: Vec<char> = content.chars().collect();
+    let needle: Vec<char> = query.chars().collect();
+    let ranges = match_ranges(&chars, &needle);
+
+    let (first_start, first_end) = ranges.first().copied().unwrap_or((0, 0));
+    let start = first_start.saturating_sub(SNIPPET_CONTEXT);
+    let end = (first_end + SNIPPET_CONTEXT).min(chars.len());
+
+    let mut text = String::new();
+    let mut offset = 0;
+    if start > 0 {
+        text.push('…');
+        offset = 1;
+    }
+    text.extend(&chars[start..end]);
+    if end < chars.len() {
+        text.push('…');
+    }
+
+    let highlights = ranges
+        .into_iter()
+        .filter(|(from, to)| *from >= start && *to <= end)
+        .map(|(from, to)| (from - start + offset, to - start + offset))
+        .collect();
+
+    Snippet { text, highlights }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("100% done_now"), "100\\% done\\_now");
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+    }
+
+    #[test]
+    fn test_task_number() {
+        assert_eq!(task_number("142"), Some(142));
+        assert_eq!(task_number("#7"), Some(7));
+        assert_eq!(task_number("sc-12"), Some(12));
+        assert_eq!(task_number("fix-login"), None);
+        assert_eq!(task_number("-3"), None);
+        assert_eq!(task_number("#"), None);
+        assert_eq!(task_number("99999999999"), None);
+    }
+
+    #[test]
+    fn test_snippet_highlights_every_match_in_window() {
+        let snippet = snippet("We decided to use Postgres. postgres it is.", "POSTGRES");
+
+        assert_eq!(snippet.text, "We decided to use Postgres. postgres it is.");
+        assert_eq!(snippet.highlights, vec![(18, 26), (28, 36)]);
+    }
+
+    #[test]
+    fn test_snippet_trims_long_content() {
+        let content = format!("{}needle{}", "a".repeat(100), "b".repeat(100));
+        let snippet = snippet(&content, "needle");
+        let chars: Vec<char> = snippet.text.chars().collect();
+
+        assert!(snippet.text.starts_with('…') && snippet.text.ends_with('…'));
+        assert_eq!(snippet.highlights.len(), 1);
+        let (from, to) = snippet.highlights[0];
+        assert_eq!(chars[from..to].iter().collect::<String>(), "needle");
+    }
+}
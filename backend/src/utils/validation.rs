@@ -1,6 +1,63 @@
-use crate::utils::errors::AppError;
+use crate::database::models::{
+    AutomationAction, BoardAutomations, BoardConfig, CreateApiTokenRequest, CreateBoardRequest, CreateProjectInvitationRequest, CreateProjectReportRequest, CreateTaskRequest, OnboardingTemplate, ProjectWorkflow, PublishTemplateRequest, RetentionPolicy, StalenessRules,
+    SwimlaneConfig, SwimlaneGroupBy, TaskPriority, TaskStatus, UpdateTaskRequest,
+};
+use crate::utils::colors;
+use crate::utils::errors::{AppError, FieldError};
+use crate::utils::i18n::Message;
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use regex::Regex;
 use std::sync::OnceLock;
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+
+const MAX_TASK_TITLE_LENGTH: usize = 255;
+pub const MAX_TASK_TAGS: usize = 20;
+const MAX_TAG_LENGTH: usize = 50;
+const MAX_DUE_DATE_YEARS_AHEAD: i64 = 100;
+const MAX_SWIMLANES: usize = 100;
+const MAX_COLUMN_NAME_LENGTH: usize = 50;
+const MAX_ORDERED_TASKS: usize = 500;
+const MAX_ONBOARDING_TASKS: usize = 50;
+const MAX_ONBOARDING_DUE_DAYS: u16 = 365;
+const MAX_STALENESS_DAYS: u16 = 365;
+// Retention can't be set shorter than this, however eager compliance is
+const MIN_COMMENT_RETENTION_YEARS: u16 = 1;
+const MAX_COMMENT_RETENTION_YEARS: u16 = 100;
+const MIN_ATTACHMENT_RETENTION_MONTHS: u16 = 3;
+const MAX_ATTACHMENT_RETENTION_MONTHS: u16 = 1200;
+const MIN_CLOSED_TASK_RETENTION_DAYS: u16 = 30;
+const MAX_CLOSED_TASK_RETENTION_DAYS: u16 = 36500;
+const MAX_REPORT_NAME_LENGTH: usize = 100;
+const MAX_TOKEN_NAME_LENGTH: usize = 100;
+const MAX_TEMPLATE_NAME_LENGTH: usize = 100;
+const MAX_TEMPLATE_DESCRIPTION_LENGTH: usize = 1000;
+const MAX_AUTOMATION_RULES: usize = 20;
+const MAX_AUTOMATION_ACTIONS: usize = 10;
+const MAX_AUTOMATION_NAME_LENGTH: usize = 100;
+// Password rules, also published through GET /api/config
+pub const PASSWORD_MIN_LENGTH: usize = 8;
+pub const PASSWORD_MAX_LENGTH: usize = 128;
+pub const PASSWORD_SPECIAL_CHARACTERS: &str = "!@#$%^&*()_+-=[]{}|;:,.<>?";
+// Longest ZWJ sequences (e.g. families) are 7 code points
+const MAX_EMOJI_CHARS: usize = 8;
+
+fn invalid(code: &'static str) -> AppError {
+    AppError::Invalid(Message::new(code))
+}
+
+// `field` is a label key from the i18n catalog
+fn required(field: &'static str) -> AppError {
+    AppError::Invalid(Message::new("required").with("field", field))
+}
+
+fn too_short(field: &'static str, min: usize) -> AppError {
+    AppError::Invalid(Message::new("too_short").with("field", field).with("min", min))
+}
+
+fn too_long(field: &'static str, max: usize) -> AppError {
+    AppError::Invalid(Message::new("too_long").with("field", field).with("max", max))
+}
 
 // Email validation regex
 static EMAIL_REGEX: OnceLock<Regex> = OnceLock::new();
@@ -13,15 +70,15 @@ fn email_regex() -> &'static Regex {
 
 pub fn validate_email(email: &str) -> Result<(), AppError> {
     if email.is_empty() {
-        return Err(AppError::Validation("Email is required".to_string()));
+        return Err(required("email"));
     }
 
     if email.len() > 255 {
-        return Err(AppError::Validation("Email must be 255 characters or less".to_string()));
+        return Err(too_long("email", 255));
     }
 
     if !email_regex().is_match(email) {
-        return Err(AppError::Validation("Invalid email format".to_string()));
+        return Err(invalid("invalid_email"));
     }
 
     Ok(())
@@ -29,60 +86,82 @@ pub fn validate_email(email: &str) -> Result<(), AppError> {
 
 pub fn validate_username(username: &str) -> Result<(), AppError> {
     if username.is_empty() {
-        return Err(AppError::Validation("Username is required".to_string()));
+        return Err(required("username"));
     }
 
-    if username.len() < 3 {
-        return Err(AppError::Validation("Username must be at least 3 characters".to_string()));
+    if username.chars().count() < 3 {
+        return Err(too_short("username", 3));
     }
 
-    if username.len() > 50 {
-        return Err(AppError::Validation("Username must be 50 characters or less".to_string()));
+    if username.chars().count() > 50 {
+        return Err(too_long("username", 50));
     }
 
-    // Only allow alphanumeric characters and underscores
-    if !username.chars().all(|c| c.is_alphanumeric() || c == '_') {
-        return Err(AppError::Validation(
-            "Username can only contain letters, numbers, and underscores".to_string(),
-        ));
+    // ASCII only: Unicode letters and digits include lookalikes of other
+    // letters (e.g. mathematical or Cyrillic ones) that would allow impersonation
+    if !username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(invalid("username_characters"));
     }
 
     Ok(())
 }
 
+// What a username looks like at a glance: lowercase, with characters and pairs
+// that are easily mistaken for one another folded together. Must stay in sync
+// with USERNAME_SKELETON_SQL, which compares it against existing usernames.
+pub fn username_skeleton(username: &str) -> String {
+    username
+        .to_lowercase()
+        .replace("rn", "m")
+        .replace("vv", "w")
+        .chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' | 'i' => 'l',
+            c => c,
+        })
+        .collect()
+}
+
+// Names are stored in NFC so that visually identical names compare and sort
+// the same whichever form the client sent
+pub fn normalize_name(name: &str) -> String {
+    name.nfc().collect()
+}
+
 pub fn validate_password(password: &str) -> Result<(), AppError> {
     if password.is_empty() {
-        return Err(AppError::Validation("Password is required".to_string()));
+        return Err(required("password"));
     }
 
-    if password.len() < 8 {
-        return Err(AppError::Validation("Password must be at least 8 characters".to_string()));
+    if password.chars().count() < PASSWORD_MIN_LENGTH {
+        return Err(too_short("password", PASSWORD_MIN_LENGTH));
     }
 
-    if password.len() > 128 {
-        return Err(AppError::Validation("Password must be 128 characters or less".to_string()));
+    if password.chars().count() > PASSWORD_MAX_LENGTH {
+        return Err(too_long("password", PASSWORD_MAX_LENGTH));
     }
 
     // Check for at least one lowercase, uppercase, digit, and special character
     let has_lowercase = password.chars().any(|c| c.is_ascii_lowercase());
     let has_uppercase = password.chars().any(|c| c.is_ascii_uppercase());
     let has_digit = password.chars().any(|c| c.is_ascii_digit());
-    let has_special = password.chars().any(|c| "!@#$%^&*()_+-=[]{}|;:,.<>?".contains(c));
+    let has_special = password.chars().any(|c| PASSWORD_SPECIAL_CHARACTERS.contains(c));
 
     if !has_lowercase {
-        return Err(AppError::Validation("Password must contain at least one lowercase letter".to_string()));
+        return Err(invalid("password_lowercase"));
     }
 
     if !has_uppercase {
-        return Err(AppError::Validation("Password must contain at least one uppercase letter".to_string()));
+        return Err(invalid("password_uppercase"));
     }
 
     if !has_digit {
-        return Err(AppError::Validation("Password must contain at least one digit".to_string()));
+        return Err(invalid("password_digit"));
     }
 
     if !has_special {
-        return Err(AppError::Validation("Password must contain at least one special character".to_string()));
+        return Err(invalid("password_special"));
     }
 
     Ok(())
@@ -90,23 +169,30 @@ pub fn validate_password(password: &str) -> Result<(), AppError> {
 
 pub fn validate_display_name(display_name: &str) -> Result<(), AppError> {
     if display_name.is_empty() {
-        return Err(AppError::Validation("Display name is required".to_string()));
+        return Err(required("display_name"));
     }
 
-    if display_name.len() > 255 {
-        return Err(AppError::Validation("Display name must be 255 characters or less".to_string()));
+    if display_name.chars().count() > 255 {
+        return Err(too_long("display_name", 255));
     }
 
     Ok(())
 }
 
-pub fn validate_hex_color(color: &str) -> Result<(), AppError> {
-    if !color.starts_with('#') || color.len() != 7 {
-        return Err(AppError::Validation("Color must be a valid hex color code (e.g., #FF0000)".to_string()));
-    }
+// Accepts #RRGGBB, #RGB and palette keys; returns the color as it is stored
+pub fn normalize_color(color: &str) -> Result<String, AppError> {
+    colors::normalize(color).ok_or_else(|| invalid("invalid_color"))
+}
+
+// One emoji, possibly built from several code points (skin tones, ZWJ
+// sequences, keycaps); plain text is rejected.
+pub fn validate_emoji(emoji: &str) -> Result<(), AppError> {
+    let valid = emoji.chars().count() <= MAX_EMOJI_CHARS
+        && !emoji.is_ascii()
+        && !emoji.chars().any(|c| c.is_whitespace() || c.is_control() || c.is_alphabetic());
 
-    if !color[1..].chars().all(|c| c.is_ascii_hexdigit()) {
-        return Err(AppError::Validation("Color must be a valid hex color code".to_string()));
+    if !valid {
+        return Err(invalid("invalid_emoji"));
     }
 
     Ok(())
@@ -114,23 +200,23 @@ pub fn validate_hex_color(color: &str) -> Result<(), AppError> {
 
 pub fn validate_team_name(name: &str) -> Result<(), AppError> {
     if name.is_empty() {
-        return Err(AppError::Validation("Team name is required".to_string()));
+        return Err(required("team_name"));
     }
 
-    if name.len() < 2 {
-        return Err(AppError::Validation("Team name must be at least 2 characters".to_string()));
+    if name.chars().count() < 2 {
+        return Err(too_short("team_name", 2));
     }
 
-    if name.len() > 100 {
-        return Err(AppError::Validation("Team name must be 100 characters or less".to_string()));
+    if name.chars().count() > 100 {
+        return Err(too_long("team_name", 100));
     }
 
     Ok(())
 }
 
 pub fn validate_team_description(description: &str) -> Result<(), AppError> {
-    if description.len() > 500 {
-        return Err(AppError::Validation("Team description must be 500 characters or less".to_string()));
+    if description.chars().count() > 500 {
+        return Err(too_long("team_description", 500));
     }
 
     Ok(())
@@ -138,23 +224,23 @@ pub fn validate_team_description(description: &str) -> Result<(), AppError> {
 
 pub fn validate_project_name(name: &str) -> Result<(), AppError> {
     if name.is_empty() {
-        return Err(AppError::Validation("Project name is required".to_string()));
+        return Err(required("project_name"));
     }
 
-    if name.len() < 2 {
-        return Err(AppError::Validation("Project name must be at least 2 characters".to_string()));
+    if name.chars().count() < 2 {
+        return Err(too_short("project_name", 2));
     }
 
-    if name.len() > 100 {
-        return Err(AppError::Validation("Project name must be 100 characters or less".to_string()));
+    if name.chars().count() > 100 {
+        return Err(too_long("project_name", 100));
     }
 
     Ok(())
 }
 
 pub fn validate_project_description(description: &str) -> Result<(), AppError> {
-    if description.len() > 1000 {
-        return Err(AppError::Validation("Project description must be 1000 characters or less".to_string()));
+    if description.chars().count() > 1000 {
+        return Err(too_long("project_description", 1000));
     }
 
     Ok(())
@@ -162,23 +248,23 @@ pub fn validate_project_description(description: &str) -> Result<(), AppError> {
 
 pub fn validate_task_title(title: &str) -> Result<(), AppError> {
     if title.is_empty() {
-        return Err(AppError::Validation("Task title is required".to_string()));
+        return Err(required("task_title"));
     }
 
-    if title.len() < 2 {
-        return Err(AppError::Validation("Task title must be at least 2 characters".to_string()));
+    if title.chars().count() < 2 {
+        return Err(too_short("task_title", 2));
     }
 
-    if title.len() > 255 {
-        return Err(AppError::Validation("Task title must be 255 characters or less".to_string()));
+    if title.chars().count() > MAX_TASK_TITLE_LENGTH {
+        return Err(too_long("task_title", MAX_TASK_TITLE_LENGTH));
     }
 
     Ok(())
 }
 
 pub fn validate_task_description(description: &str) -> Result<(), AppError> {
-    if description.len() > 2000 {
-        return Err(AppError::Validation("Task description must be 2000 characters or less".to_string()));
+    if description.chars().count() > 2000 {
+        return Err(too_long("task_description", 2000));
     }
 
     Ok(())
@@ -186,23 +272,62 @@ pub fn validate_task_description(description: &str) -> Result<(), AppError> {
 
 pub fn validate_board_name(name: &str) -> Result<(), AppError> {
     if name.is_empty() {
-        return Err(AppError::Validation("Board name is required".to_string()));
+        return Err(required("board_name"));
     }
 
-    if name.len() < 2 {
-        return Err(AppError::Validation("Board name must be at least 2 characters".to_string()));
+    if name.chars().count() < 2 {
+        return Err(too_short("board_name", 2));
     }
 
-    if name.len() > 100 {
-        return Err(AppError::Validation("Board name must be 100 characters or less".to_string()));
+    if name.chars().count() > 100 {
+        return Err(too_long("board_name", 100));
     }
 
     Ok(())
 }
 
 pub fn validate_board_description(description: &str) -> Result<(), AppError> {
-    if description.len() > 500 {
-        return Err(AppError::Validation("Board description must be 500 characters or less".to_string()));
+    if description.chars().count() > 500 {
+        return Err(too_long("board_description", 500));
+    }
+
+    Ok(())
+}
+
+// Column names are labels for the status columns (tasks reference the status,
+// not the name), but two columns that differ only in case can't be told apart
+pub fn validate_board_columns(columns: &[String]) -> Result<(), AppError> {
+    for index in 0..columns.len() {
+        validate_board_column(columns, index)?;
+    }
+
+    Ok(())
+}
+
+// The column at `index`, compared with the ones before it
+fn validate_board_column(columns: &[String], index: usize) -> Result<(), AppError> {
+    let name = columns[index].trim();
+    if name.is_empty() {
+        return Err(required("column_name"));
+    }
+    if name.chars().count() > MAX_COLUMN_NAME_LENGTH {
+        return Err(too_long("column_name", MAX_COLUMN_NAME_LENGTH));
+    }
+    if columns[..index].iter().any(|other| other.trim().to_lowercase() == name.to_lowercase()) {
+        return Err(AppError::Invalid(Message::new("duplicate_column").with("column", name)));
+    }
+
+    Ok(())
+}
+
+pub fn validate_task_order(task_ids: &[Uuid]) -> Result<(), AppError> {
+    if task_ids.len() > MAX_ORDERED_TASKS {
+        return Err(AppError::Invalid(Message::new("too_many_ordered_tasks").with("max", MAX_ORDERED_TASKS)));
+    }
+    for (index, task_id) in task_ids.iter().enumerate() {
+        if task_ids[..index].contains(task_id) {
+            return Err(AppError::Invalid(Message::new("duplicate_ordered_task").with("task", task_id.to_string())));
+        }
     }
 
     Ok(())
@@ -210,19 +335,660 @@ pub fn validate_board_description(description: &str) -> Result<(), AppError> {
 
 pub fn validate_task_comment(content: &str) -> Result<(), AppError> {
     if content.is_empty() {
-        return Err(AppError::Validation("Comment content is required".to_string()));
+        return Err(required("comment"));
+    }
+
+    if content.chars().count() > 1000 {
+        return Err(too_long("comment", 1000));
+    }
+
+    Ok(())
+}
+
+pub fn validate_announcement_message(message: &str) -> Result<(), AppError> {
+    if message.trim().is_empty() {
+        return Err(required("announcement_message"));
     }
 
-    if content.len() > 1000 {
-        return Err(AppError::Validation("Comment must be 1000 characters or less".to_string()));
+    if message.chars().count() > 500 {
+        return Err(too_long("announcement_message", 500));
     }
 
     Ok(())
 }
 
+pub fn validate_search_query(query: &str) -> Result<(), AppError> {
+    if query.chars().count() < 2 {
+        return Err(too_short("search_query", 2));
+    }
+
+    if query.chars().count() > 200 {
+        return Err(too_long("search_query", 200));
+    }
+
+    Ok(())
+}
+
+// Typeahead queries may be empty or a single character (e.g. a task number)
+pub fn validate_picker_query(query: &str) -> Result<(), AppError> {
+    if query.chars().count() > 200 {
+        return Err(too_long("search_query", 200));
+    }
+
+    Ok(())
+}
+
+// Only Slack-hosted incoming webhooks are accepted, so the job worker never
+// posts to arbitrary (e.g. internal) hosts
+pub fn validate_slack_webhook_url(url: &str) -> Result<(), AppError> {
+    if url.len() > 500 {
+        return Err(too_long("webhook_url", 500));
+    }
+
+    let valid = url
+        .strip_prefix("https://hooks.slack.com/services/")
+        .map(|path| !path.is_empty() && path.chars().all(|c| c.is_ascii_alphanumeric() || c == '/'))
+        .unwrap_or(false);
+
+    if !valid {
+        return Err(invalid("invalid_slack_webhook"));
+    }
+
+    Ok(())
+}
+
+// GitHub repository in "owner/name" form
+pub fn validate_github_repo(repo: &str) -> Result<(), AppError> {
+    let valid = repo
+        .split_once('/')
+        .map(|(owner, name)| {
+            let part_ok = |part: &str| {
+                !part.is_empty()
+                    && part.len() <= 100
+                    && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+            };
+            part_ok(owner) && part_ok(name)
+        })
+        .unwrap_or(false);
+
+    if !valid {
+        return Err(invalid("invalid_github_repo"));
+    }
+
+    Ok(())
+}
+
+// Prefix used to reference tasks from commits, e.g. "SC" in "SC-142"
+pub fn validate_task_key(key: &str) -> Result<(), AppError> {
+    if key.len() < 2 || key.len() > 10 || !key.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(invalid("invalid_task_key"));
+    }
+
+    Ok(())
+}
+
+// IANA timezone name, e.g. "Europe/Berlin"
+pub fn validate_timezone(timezone: &str) -> Result<(), AppError> {
+    if timezone.parse::<chrono_tz::Tz>().is_err() {
+        return Err(AppError::Invalid(Message::new("unknown_timezone").with("timezone", timezone)));
+    }
+
+    Ok(())
+}
+
+// At most one positive WIP limit per column
+pub fn validate_board_config(config: &BoardConfig) -> Result<(), AppError> {
+    for index in 0..config.wip_limits.len() {
+        validate_wip_limit(config, index)?;
+    }
+
+    Ok(())
+}
+
+fn validate_wip_limit(config: &BoardConfig, index: usize) -> Result<(), AppError> {
+    let wip_limit = &config.wip_limits[index];
+    if wip_limit.limit < 1 {
+        return Err(invalid("wip_limit_too_low"));
+    }
+    if config.wip_limits[..index].iter().any(|other| other.column_id == wip_limit.column_id) {
+        return Err(AppError::Invalid(
+            Message::new("duplicate_wip_limit").with("column", format!("{:?}", wip_limit.column_id)),
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn validate_swimlane_config(config: &SwimlaneConfig) -> Result<(), AppError> {
+    validate_swimlane_group_by(config)?;
+    validate_swimlane_count(config)?;
+    for index in 0..config.lanes.len() {
+        validate_swimlane_lane(config, index)?;
+    }
+
+    Ok(())
+}
+
+fn validate_swimlane_group_by(config: &SwimlaneConfig) -> Result<(), AppError> {
+    if !config.group_by.is_supported() {
+        return Err(AppError::Invalid(Message::new("unsupported_swimlanes").with("group_by", config.group_by.name())));
+    }
+
+    Ok(())
+}
+
+fn validate_swimlane_count(config: &SwimlaneConfig) -> Result<(), AppError> {
+    if config.lanes.len() > MAX_SWIMLANES {
+        return Err(AppError::Invalid(Message::new("too_many_lanes").with("max", MAX_SWIMLANES)));
+    }
+
+    Ok(())
+}
+
+fn validate_swimlane_lane(config: &SwimlaneConfig, index: usize) -> Result<(), AppError> {
+    let lane = &config.lanes[index];
+    if config.lanes[..index].iter().any(|other| other.key == lane.key) {
+        return Err(AppError::Invalid(Message::new("duplicate_lane").with("lane", lane.key.clone())));
+    }
+    let Some(key) = lane.key.as_deref() else {
+        return Ok(());
+    };
+    let valid = match config.group_by {
+        SwimlaneGroupBy::Assignee => uuid::Uuid::parse_str(key).is_ok(),
+        SwimlaneGroupBy::Priority => TaskPriority::ALL.iter().any(|priority| priority.name() == key),
+        SwimlaneGroupBy::Label => !key.trim().is_empty() && key.chars().count() <= MAX_TAG_LENGTH,
+        SwimlaneGroupBy::None | SwimlaneGroupBy::Milestone => true,
+    };
+    if !valid {
+        return Err(AppError::Invalid(
+            Message::new("invalid_lane").with("lane", key).with("group_by", config.group_by.name()),
+        ));
+    }
+
+    Ok(())
+}
+
+// Title for a task made from free text such as a comment: its first non-blank
+// line, cut to fit with an ellipsis
+pub fn task_title_from_text(text: &str) -> String {
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default();
+    if line.chars().count() <= MAX_TASK_TITLE_LENGTH {
+        return line.to_string();
+    }
+    let truncated: String = line.chars().take(MAX_TASK_TITLE_LENGTH - 1).collect();
+    format!("{}…", truncated.trim_end())
+}
+
+// Trims tags, drops empty ones and removes case-insensitive duplicates,
+// keeping the first spelling
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+        if !normalized.iter().any(|existing| existing.to_lowercase() == tag.to_lowercase()) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+fn tag_errors(tags: &[String], errors: &mut Vec<FieldError>) {
+    if tags.len() > MAX_TASK_TAGS {
+        errors.push(FieldError::new("tags", Message::new("too_many_tags").with("max", MAX_TASK_TAGS)));
+    }
+    for tag in tags.iter().filter(|tag| tag.chars().count() > MAX_TAG_LENGTH) {
+        errors.push(FieldError::new(
+            "tags",
+            Message::new("tag_too_long").with("tag", tag.as_str()).with("max", MAX_TAG_LENGTH),
+        ));
+    }
+}
+
+fn due_date_errors(due_date: &DateTime<Utc>, errors: &mut Vec<FieldError>) {
+    let earliest = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+    let latest = Utc::now() + Duration::days(365 * MAX_DUE_DATE_YEARS_AHEAD);
+    if *due_date < earliest || *due_date > latest {
+        errors.push(FieldError::new(
+            "due_date",
+            Message::new("due_date_out_of_range").with("years", MAX_DUE_DATE_YEARS_AHEAD),
+        ));
+    }
+}
+
+// Empty strings are allowed; they mean "no cover"
+fn cover_errors(color: &mut Option<String>, emoji: Option<&str>, errors: &mut Vec<FieldError>) {
+    if let Some(color) = color.as_mut().filter(|color| !color.is_empty()) {
+        match normalize_color(color) {
+            Ok(normalized) => *color = normalized,
+            Err(error) => push_error(Err(error), "cover_color", errors),
+        }
+    }
+    if let Some(emoji) = emoji.filter(|emoji| !emoji.is_empty()) {
+        push_error(validate_emoji(emoji), "cover_emoji", errors);
+    }
+}
+
+fn push_error(result: Result<(), AppError>, field: &str, errors: &mut Vec<FieldError>) {
+    if let Err(AppError::Invalid(reason)) = result {
+        errors.push(FieldError::new(field, reason));
+    }
+}
+
+fn into_result(errors: Vec<FieldError>) -> Result<(), AppError> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::InvalidFields(errors))
+    }
+}
+
+// Normalizes the request's tags, then checks every field and reports all violations at once
+pub fn validate_create_task(request: &mut CreateTaskRequest) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+
+    push_error(validate_task_title(&request.title), "title", &mut errors);
+    if let Some(ref description) = request.description {
+        push_error(validate_task_description(description), "description", &mut errors);
+    }
+    if let Some(due_date) = request.due_date {
+        due_date_errors(&due_date.instant(), &mut errors);
+    }
+    if let Some(ref mut tags) = request.tags {
+        *tags = normalize_tags(tags);
+        tag_errors(tags, &mut errors);
+    }
+    cover_errors(&mut request.cover_color, request.cover_emoji.as_deref(), &mut errors);
+
+    into_result(errors)
+}
+
+// Like validate_create_task, plus the project's status transition rules
+pub fn validate_update_task(
+    request: &mut UpdateTaskRequest,
+    current_status: TaskStatus,
+    workflow: &ProjectWorkflow,
+) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+
+    if let Some(ref title) = request.title {
+        push_error(validate_task_title(title), "title", &mut errors);
+    }
+    if let Some(Some(ref description)) = request.description {
+        push_error(validate_task_description(description), "description", &mut errors);
+    }
+    if let Some(Some(due_date)) = request.due_date {
+        due_date_errors(&due_date.instant(), &mut errors);
+    }
+    if let Some(Some(ref mut tags)) = request.tags {
+        *tags = normalize_tags(tags);
+        tag_errors(tags, &mut errors);
+    }
+    cover_errors(&mut request.cover_color, request.cover_emoji.as_deref(), &mut errors);
+    if let Some(status) = request.status {
+        push_error(validate_status_transition(workflow, current_status, status), "status", &mut errors);
+    }
+
+    into_result(errors)
+}
+
+pub fn validate_status_transition(workflow: &ProjectWorkflow, from: TaskStatus, to: TaskStatus) -> Result<(), AppError> {
+    if !workflow.allows(from, to) {
+        return Err(AppError::Invalid(
+            Message::new("status_transition_not_allowed")
+                .with("from", format!("{:?}", from))
+                .with("to", format!("{:?}", to)),
+        ));
+    }
+
+    Ok(())
+}
+
+// Each status may have at most one transition rule
+pub fn validate_project_workflow(workflow: &ProjectWorkflow) -> Result<(), AppError> {
+    for (index, transition) in workflow.transitions.iter().enumerate() {
+        if workflow.transitions[..index].iter().any(|other| other.from == transition.from) {
+            return Err(AppError::Invalid(
+                Message::new("duplicate_transition").with("status", format!("{:?}", transition.from)),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Normalizes tags like validate_create_task; field errors are reported as
+// tasks[i].field so clients can point at the offending entry
+pub fn validate_onboarding_template(template: &mut OnboardingTemplate) -> Result<(), AppError> {
+    if template.tasks.len() > MAX_ONBOARDING_TASKS {
+        return Err(AppError::Invalid(Message::new("too_many_onboarding_tasks").with("max", MAX_ONBOARDING_TASKS)));
+    }
+
+    let mut errors = Vec::new();
+    for (index, task) in template.tasks.iter_mut().enumerate() {
+        let mut task_errors = Vec::new();
+        push_error(validate_task_title(&task.title), "title", &mut task_errors);
+        if let Some(ref description) = task.description {
+            push_error(validate_task_description(description), "description", &mut task_errors);
+        }
+        if let Some(ref mut tags) = task.tags {
+            *tags = normalize_tags(tags);
+            tag_errors(tags, &mut task_errors);
+        }
+        if task.due_in_days.is_some_and(|days| days > MAX_ONBOARDING_DUE_DAYS) {
+            task_errors.push(FieldError::new(
+                "due_in_days",
+                Message::new("onboarding_due_too_far").with("max", MAX_ONBOARDING_DUE_DAYS),
+            ));
+        }
+        errors.extend(task_errors.into_iter().map(|error| FieldError {
+            field: format!("tasks[{}].{}", index, error.field),
+            ..error
+        }));
+    }
+
+    into_result(errors)
+}
+
+// Done tasks never go stale, so they can't have a threshold
+pub fn validate_staleness_rules(rules: &StalenessRules) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+    for (index, threshold) in rules.thresholds.iter().enumerate() {
+        let field = format!("thresholds[{}]", index);
+        if threshold.status == TaskStatus::Done {
+            errors.push(FieldError::new(&format!("{}.status", field), Message::new("staleness_done_status")));
+        } else if rules.thresholds[..index].iter().any(|other| other.status == threshold.status) {
+            errors.push(FieldError::new(
+                &format!("{}.status", field),
+                Message::new("duplicate_staleness_threshold").with("status", threshold.status.name()),
+            ));
+        }
+        if threshold.days == 0 || threshold.days > MAX_STALENESS_DAYS {
+            errors.push(FieldError::new(
+                &format!("{}.days", field),
+                Message::new("staleness_days_out_of_range").with("min", 1).with("max", MAX_STALENESS_DAYS),
+            ));
+        }
+    }
+
+    into_result(errors)
+}
+
+pub fn validate_retention_policy(policy: &RetentionPolicy) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+    if let Some(years) = policy.comment_max_age_years {
+        if !(MIN_COMMENT_RETENTION_YEARS..=MAX_COMMENT_RETENTION_YEARS).contains(&years) {
+            errors.push(FieldError::new(
+                "comment_max_age_years",
+                Message::new("retention_years_out_of_range")
+                    .with("min", MIN_COMMENT_RETENTION_YEARS)
+                    .with("max", MAX_COMMENT_RETENTION_YEARS),
+            ));
+        }
+    }
+    if let Some(months) = policy.attachment_max_age_months {
+        if !(MIN_ATTACHMENT_RETENTION_MONTHS..=MAX_ATTACHMENT_RETENTION_MONTHS).contains(&months) {
+            errors.push(FieldError::new(
+                "attachment_max_age_months",
+                Message::new("retention_months_out_of_range")
+                    .with("min", MIN_ATTACHMENT_RETENTION_MONTHS)
+                    .with("max", MAX_ATTACHMENT_RETENTION_MONTHS),
+            ));
+        }
+    }
+    if let Some(days) = policy.closed_task_max_age_days {
+        if !(MIN_CLOSED_TASK_RETENTION_DAYS..=MAX_CLOSED_TASK_RETENTION_DAYS).contains(&days) {
+            errors.push(FieldError::new(
+                "closed_task_max_age_days",
+                Message::new("retention_days_out_of_range")
+                    .with("min", MIN_CLOSED_TASK_RETENTION_DAYS)
+                    .with("max", MAX_CLOSED_TASK_RETENTION_DAYS),
+            ));
+        }
+    }
+
+    into_result(errors)
+}
+
+// Trims names, labels and comments and drops repeated rotation users, then
+// reports every violation with the path of the offending entry. Whether the
+// rotation users are project members is up to the caller.
+pub fn validate_board_automations(automations: &mut BoardAutomations) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+    if automations.rules.len() > MAX_AUTOMATION_RULES {
+        errors.push(FieldError::new("rules", Message::new("too_many_automation_rules").with("max", MAX_AUTOMATION_RULES)));
+    }
+
+    for index in 0..automations.rules.len() {
+        let field = format!("rules[{}]", index);
+        if automations.rules[..index].iter().any(|other| other.id == automations.rules[index].id) {
+            errors.push(FieldError::new(&format!("{}.id", field), Message::new("duplicate_automation_rule")));
+        }
+
+        let rule = &mut automations.rules[index];
+        rule.name = rule.name.trim().to_string();
+        if rule.name.is_empty() {
+            push_error(Err(required("automation_name")), &format!("{}.name", field), &mut errors);
+        } else if rule.name.chars().count() > MAX_AUTOMATION_NAME_LENGTH {
+            push_error(Err(too_long("automation_name", MAX_AUTOMATION_NAME_LENGTH)), &format!("{}.name", field), &mut errors);
+        }
+
+        if rule.actions.is_empty() {
+            push_error(Err(invalid("automation_actions_required")), &format!("{}.actions", field), &mut errors);
+        } else if rule.actions.len() > MAX_AUTOMATION_ACTIONS {
+            errors.push(FieldError::new(
+                &format!("{}.actions", field),
+                Message::new("too_many_automation_actions").with("max", MAX_AUTOMATION_ACTIONS),
+            ));
+        }
+        for action_index in 0..rule.actions.len() {
+            let action_field = format!("{}.actions[{}]", field, action_index);
+            let (earlier, rest) = rule.actions.split_at_mut(action_index);
+            let action = &mut rest[0];
+            // Labels are the only action a rule can repeat
+            let repeated = !matches!(action, AutomationAction::AddLabel { .. })
+                && earlier.iter().any(|other| other.name() == action.name());
+            if repeated {
+                errors.push(FieldError::new(
+                    &action_field,
+                    Message::new("duplicate_automation_action").with("action", action.name()),
+                ));
+            }
+
+            match action {
+                AutomationAction::AssignRoundRobin { user_ids } => {
+                    let mut unique = Vec::new();
+                    for user_id in user_ids.iter() {
+                        if !unique.contains(user_id) {
+                            unique.push(*user_id);
+                        }
+                    }
+                    *user_ids = unique;
+                    if user_ids.is_empty() {
+                        push_error(Err(invalid("automation_rotation_required")), &format!("{}.user_ids", action_field), &mut errors);
+                    }
+                }
+                AutomationAction::AddLabel { label } => {
+                    *label = label.trim().to_string();
+                    if label.is_empty() {
+                        push_error(Err(required("label")), &format!("{}.label", action_field), &mut errors);
+                    } else if label.chars().count() > MAX_TAG_LENGTH {
+                        errors.push(FieldError::new(
+                            &format!("{}.label", action_field),
+                            Message::new("tag_too_long").with("tag", label.as_str()).with("max", MAX_TAG_LENGTH),
+                        ));
+                    }
+                }
+                AutomationAction::SetPriority { .. } => {}
+                AutomationAction::PostSystemComment { content } => {
+                    *content = content.trim().to_string();
+                    push_error(validate_task_comment(content), &format!("{}.content", action_field), &mut errors);
+                }
+            }
+        }
+    }
+
+    into_result(errors)
+}
+
+// Like the checks in create_board, but reports every violation with the path
+// of the offending entry (e.g. config.wip_limits[1]), for imported boards
+pub fn validate_board_fields(request: &CreateBoardRequest) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+
+    push_error(validate_board_name(&request.name), "name", &mut errors);
+    if let Some(ref description) = request.description {
+        push_error(validate_board_description(description), "description", &mut errors);
+    }
+    if let Some(ref columns) = request.columns {
+        for index in 0..columns.len() {
+            push_error(validate_board_column(columns, index), &format!("columns[{}]", index), &mut errors);
+        }
+    }
+    if let Some(ref config) = request.config {
+        for index in 0..config.wip_limits.len() {
+            push_error(validate_wip_limit(config, index), &format!("config.wip_limits[{}]", index), &mut errors);
+        }
+    }
+    if let Some(ref swimlane_config) = request.swimlane_config {
+        push_error(validate_swimlane_group_by(swimlane_config), "swimlane_config.group_by", &mut errors);
+        push_error(validate_swimlane_count(swimlane_config), "swimlane_config.lanes", &mut errors);
+        for index in 0..swimlane_config.lanes.len() {
+            push_error(
+                validate_swimlane_lane(swimlane_config, index),
+                &format!("swimlane_config.lanes[{}].key", index),
+                &mut errors,
+            );
+        }
+    }
+
+    into_result(errors)
+}
+
+// Drops repeated sections and recipients; membership of the recipients is
+// checked by the handler
+pub fn validate_publish_template(request: &mut PublishTemplateRequest) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+
+    request.name = normalize_name(request.name.trim());
+    if request.name.is_empty() {
+        push_error(Err(required("template_name")), "name", &mut errors);
+    } else if request.name.chars().count() > MAX_TEMPLATE_NAME_LENGTH {
+        push_error(Err(too_long("template_name", MAX_TEMPLATE_NAME_LENGTH)), "name", &mut errors);
+    }
+    if request.description.as_ref().is_some_and(|description| description.chars().count() > MAX_TEMPLATE_DESCRIPTION_LENGTH) {
+        push_error(
+            Err(too_long("template_description", MAX_TEMPLATE_DESCRIPTION_LENGTH)),
+            "description",
+            &mut errors,
+        );
+    }
+
+    into_result(errors)
+}
+
+pub fn validate_project_report(request: &mut CreateProjectReportRequest) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+
+    let name = request.name.trim();
+    if name.is_empty() {
+        push_error(Err(required("report_name")), "name", &mut errors);
+    } else if name.chars().count() > MAX_REPORT_NAME_LENGTH {
+        push_error(Err(too_long("report_name", MAX_REPORT_NAME_LENGTH)), "name", &mut errors);
+    }
+
+    let mut sections = Vec::new();
+    for section in &request.sections {
+        if !sections.contains(section) {
+            sections.push(*section);
+        }
+    }
+    request.sections = sections;
+    if request.sections.is_empty() {
+        push_error(Err(invalid("report_sections_required")), "sections", &mut errors);
+    }
+
+    if request.weekday.is_some_and(|weekday| !(1..=7).contains(&weekday)) {
+        push_error(Err(invalid("invalid_weekday")), "weekday", &mut errors);
+    }
+    if request.hour.is_some_and(|hour| !(0..=23).contains(&hour)) {
+        push_error(Err(invalid("invalid_hour")), "hour", &mut errors);
+    }
+    if let Some(ref timezone) = request.timezone {
+        push_error(validate_timezone(timezone), "timezone", &mut errors);
+    }
+
+    let recipients = &mut request.recipients;
+    let mut user_ids = Vec::new();
+    for user_id in &recipients.user_ids {
+        if !user_ids.contains(user_id) {
+            user_ids.push(*user_id);
+        }
+    }
+    recipients.user_ids = user_ids;
+    if recipients.user_ids.is_empty() && !recipients.slack && recipients.comment_task_id.is_none() {
+        push_error(Err(invalid("report_no_recipients")), "recipients", &mut errors);
+    }
+
+    into_result(errors)
+}
+
+// Trims the name and drops repeated scopes and projects. Whether the user is
+// a member of the projects is up to the caller.
+pub fn validate_api_token(request: &mut CreateApiTokenRequest) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+
+    request.name = request.name.trim().to_string();
+    if request.name.is_empty() {
+        push_error(Err(required("token_name")), "name", &mut errors);
+    } else if request.name.chars().count() > MAX_TOKEN_NAME_LENGTH {
+        push_error(Err(too_long("token_name", MAX_TOKEN_NAME_LENGTH)), "name", &mut errors);
+    }
+
+    let mut scopes = Vec::new();
+    for scope in &request.scopes {
+        if !scopes.contains(scope) {
+            scopes.push(*scope);
+        }
+    }
+    request.scopes = scopes;
+    if request.scopes.is_empty() {
+        push_error(Err(invalid("token_scopes_required")), "scopes", &mut errors);
+    }
+
+    if let Some(ref mut project_ids) = request.project_ids {
+        let mut unique = Vec::new();
+        for project_id in project_ids.iter() {
+            if !unique.contains(project_id) {
+                unique.push(*project_id);
+            }
+        }
+        *project_ids = unique;
+        if project_ids.is_empty() {
+            push_error(Err(invalid("token_projects_required")), "project_ids", &mut errors);
+        }
+    }
+
+    into_result(errors)
+}
+
+// Trims and lowercases the email. Only guests and viewers can be invited,
+// since invitees aren't on the project's team.
+pub fn validate_project_invitation(request: &mut CreateProjectInvitationRequest) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+
+    request.email = request.email.trim().to_lowercase();
+    push_error(validate_email(&request.email), "email", &mut errors);
+    if !request.role.allowed_outside_team() {
+        push_error(Err(invalid("invitation_role")), "role", &mut errors);
+    }
+
+    into_result(errors)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::models::DueDate;
+    use chrono::NaiveDate;
 
     #[test]
     fn test_email_validation() {
@@ -244,6 +1010,51 @@ mod tests {
         assert!(validate_username("us").is_err());
         assert!(validate_username("user@domain").is_err());
         assert!(validate_username("user-name").is_err());
+
+        // Mixed-script and non-ASCII lookalikes
+        assert!(validate_username("\u{1D586}\u{1D589}\u{1D592}\u{1D58E}\u{1D593}").is_err());
+        assert!(validate_username("\u{0430}dmin").is_err());
+        assert!(validate_username("jos\u{00E9}").is_err());
+        assert!(validate_username("user_\u{1F600}").is_err());
+    }
+
+    #[test]
+    fn test_username_skeletons_match_lookalikes() {
+        assert_eq!(username_skeleton("Admin"), username_skeleton("adrnin"));
+        assert_eq!(username_skeleton("bill"), username_skeleton("B1lI"));
+        assert_eq!(username_skeleton("oscar"), username_skeleton("0scar"));
+        assert_eq!(username_skeleton("vvalter"), "walter");
+        assert_ne!(username_skeleton("anna"), username_skeleton("anne"));
+    }
+
+    #[test]
+    fn test_lengths_count_characters() {
+        // 200 characters but 400 bytes
+        assert!(validate_display_name(&"\u{00E4}".repeat(200)).is_ok());
+        assert!(validate_display_name(&"\u{1F600}".repeat(256)).is_err());
+        assert!(validate_team_name(&"\u{00FC}".repeat(100)).is_ok());
+        assert!(validate_team_name("\u{1F680}").is_err());
+        assert!(validate_project_name(&"\u{00DF}".repeat(101)).is_err());
+        assert!(validate_task_title("\u{1F680}\u{1F680}").is_ok());
+    }
+
+    #[test]
+    fn test_names_are_normalized_to_nfc() {
+        // "e" + combining acute accent becomes a single "\u{00E9}"
+        assert_eq!(normalize_name("Rene\u{0301}"), "Ren\u{00E9}");
+        assert_eq!(normalize_name("Ren\u{00E9}"), "Ren\u{00E9}");
+        assert_eq!(normalize_name("Team \u{1F680}"), "Team \u{1F680}");
+    }
+
+    #[test]
+    fn test_errors_carry_codes_and_parameters() {
+        match validate_task_title(&"x".repeat(300)) {
+            Err(AppError::Invalid(reason)) => {
+                assert_eq!(reason, Message::new("too_long").with("field", "task_title").with("max", 255));
+            }
+            other => panic!("expected a coded error, got {:?}", other),
+        }
+        assert!(matches!(validate_email("nope"), Err(AppError::Invalid(reason)) if reason.code == "invalid_email"));
     }
 
     #[test]
@@ -259,13 +1070,401 @@ mod tests {
     }
 
     #[test]
-    fn test_hex_color_validation() {
-        assert!(validate_hex_color("#FF0000").is_ok());
-        assert!(validate_hex_color("#00ff00").is_ok());
-        assert!(validate_hex_color("#123ABC").is_ok());
-        
-        assert!(validate_hex_color("FF0000").is_err());
-        assert!(validate_hex_color("#FF00").is_err());
-        assert!(validate_hex_color("#GG0000").is_err());
+    fn test_color_normalization() {
+        assert_eq!(normalize_color("#FF0000").unwrap(), "#FF0000");
+        assert_eq!(normalize_color("#00ff00").unwrap(), "#00FF00");
+        assert_eq!(normalize_color("#f00").unwrap(), "#FF0000");
+        assert_eq!(normalize_color("teal").unwrap(), "#14B8A6");
+
+        assert!(normalize_color("FF0000").is_err());
+        assert!(normalize_color("#FF00").is_err());
+        assert!(normalize_color("#GG0000").is_err());
+    }
+
+    #[test]
+    fn test_emoji_validation() {
+        assert!(validate_emoji("🚀").is_ok());
+        assert!(validate_emoji("👍🏽").is_ok());
+        assert!(validate_emoji("1️⃣").is_ok());
+        assert!(validate_emoji("👩‍💻").is_ok());
+
+        assert!(validate_emoji("ok").is_err());
+        assert!(validate_emoji("🚀 🚀").is_err());
+        assert!(validate_emoji("äöü").is_err());
+        assert!(validate_emoji(&"🚀".repeat(9)).is_err());
+    }
+
+    #[test]
+    fn test_slack_webhook_url_validation() {
+        assert!(validate_slack_webhook_url("https://hooks.slack.com/services/T000/B000/XXXX").is_ok());
+
+        assert!(validate_slack_webhook_url("").is_err());
+        assert!(validate_slack_webhook_url("http://hooks.slack.com/services/T000/B000/XXXX").is_err());
+        assert!(validate_slack_webhook_url("https://hooks.slack.com.evil.example/services/T000").is_err());
+        assert!(validate_slack_webhook_url("https://hooks.slack.com/services/").is_err());
+        assert!(validate_slack_webhook_url("https://hooks.slack.com/services/T000?x=@internal").is_err());
+    }
+
+    #[test]
+    fn test_github_repo_validation() {
+        assert!(validate_github_repo("acme/cards").is_ok());
+        assert!(validate_github_repo("my-org/my_repo.rs").is_ok());
+
+        assert!(validate_github_repo("cards").is_err());
+        assert!(validate_github_repo("acme/").is_err());
+        assert!(validate_github_repo("https://github.com/acme/cards").is_err());
+    }
+
+    #[test]
+    fn test_timezone_validation() {
+        assert!(validate_timezone("UTC").is_ok());
+        assert!(validate_timezone("Europe/Berlin").is_ok());
+        assert!(validate_timezone("America/New_York").is_ok());
+
+        assert!(validate_timezone("").is_err());
+        assert!(validate_timezone("Mars/Olympus_Mons").is_err());
+    }
+
+    #[test]
+    fn test_board_config_validation() {
+        use crate::database::models::{ColumnWipLimit, WipMode};
+
+        let limit = |column_id, limit| ColumnWipLimit { column_id, limit };
+        let config = |wip_limits| BoardConfig { wip_mode: WipMode::Strict, wip_limits };
+
+        assert!(validate_board_config(&BoardConfig::default()).is_ok());
+        assert!(validate_board_config(&config(vec![limit(TaskStatus::InProgress, 3), limit(TaskStatus::Review, 2)])).is_ok());
+
+        assert!(validate_board_config(&config(vec![limit(TaskStatus::InProgress, 0)])).is_err());
+        assert!(validate_board_config(&config(vec![limit(TaskStatus::Review, 2), limit(TaskStatus::Review, 4)])).is_err());
+    }
+
+    #[test]
+    fn test_board_column_names_are_unique_ignoring_case() {
+        let columns = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        assert!(validate_board_columns(&columns(&["Todo", "Doing", "Done"])).is_ok());
+        assert!(validate_board_columns(&columns(&["Todo", "Done", "done "])).is_err());
+        assert!(validate_board_columns(&columns(&["Todo", "  "])).is_err());
+        assert!(validate_board_columns(&columns(&["Todo", &"x".repeat(51)])).is_err());
+    }
+
+    #[test]
+    fn test_task_order_lists_each_task_once() {
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+
+        assert!(validate_task_order(&[]).is_ok());
+        assert!(validate_task_order(&[first, second]).is_ok());
+        assert!(validate_task_order(&[first, second, first]).is_err());
+        assert!(validate_task_order(&vec![Uuid::nil(); MAX_ORDERED_TASKS + 1]).is_err());
+    }
+
+    #[test]
+    fn test_swimlane_config_validation() {
+        use crate::database::models::SwimlaneLane;
+
+        let config = |group_by, keys: &[Option<&str>]| SwimlaneConfig {
+            group_by,
+            lanes: keys.iter().map(|key| SwimlaneLane { key: key.map(str::to_string), collapsed: false }).collect(),
+        };
+
+        assert!(validate_swimlane_config(&SwimlaneConfig::default()).is_ok());
+        assert!(validate_swimlane_config(&config(SwimlaneGroupBy::Priority, &[Some("Critical"), None, Some("Low")])).is_ok());
+        assert!(validate_swimlane_config(&config(SwimlaneGroupBy::Label, &[Some("qa"), Some("roadmap")])).is_ok());
+
+        assert!(validate_swimlane_config(&config(SwimlaneGroupBy::Milestone, &[])).is_err());
+        assert!(validate_swimlane_config(&config(SwimlaneGroupBy::Priority, &[Some("Urgent")])).is_err());
+        assert!(validate_swimlane_config(&config(SwimlaneGroupBy::Assignee, &[Some("alice")])).is_err());
+        assert!(validate_swimlane_config(&config(SwimlaneGroupBy::Label, &[Some("qa"), Some("qa")])).is_err());
+    }
+
+    #[test]
+    fn test_tags_are_trimmed_and_deduped() {
+        let tags = vec![" Bug ".to_string(), "bug".to_string(), "".to_string(), "UI".to_string(), "ui ".to_string()];
+
+        assert_eq!(normalize_tags(&tags), vec!["Bug".to_string(), "UI".to_string()]);
+    }
+
+    #[test]
+    fn test_create_task_reports_every_violation() {
+        let mut request = CreateTaskRequest {
+            title: "x".to_string(),
+            description: None,
+            assigned_to: None,
+            priority: None,
+            due_date: Some(DueDate::AllDay(NaiveDate::from_ymd_opt(1970, 1, 1).unwrap())),
+            tags: Some((0..25).map(|i| format!("tag{}", i)).collect()),
+            cover_color: Some("reddish".to_string()),
+            cover_emoji: None,
+        };
+
+        match validate_create_task(&mut request) {
+            Err(AppError::InvalidFields(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+                assert_eq!(fields, vec!["title", "due_date", "tags", "cover_color"]);
+            }
+            other => panic!("expected field errors, got {:?}", other),
+        }
+
+        request.title = "Valid title".to_string();
+        request.due_date = Some(DueDate::At(Utc::now() + Duration::days(7)));
+        request.tags = Some(vec![" release ".to_string(), "Release".to_string()]);
+        request.cover_color = Some("#f80".to_string());
+        assert!(validate_create_task(&mut request).is_ok());
+        assert_eq!(request.tags, Some(vec!["release".to_string()]));
+        assert_eq!(request.cover_color.as_deref(), Some("#FF8800"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_status_transitions() {
+        use crate::database::models::StatusTransition;
+
+        let workflow = ProjectWorkflow {
+            transitions: vec![StatusTransition { from: TaskStatus::Done, to: vec![TaskStatus::Todo] }],
+            ..Default::default()
+        };
+
+        assert!(validate_status_transition(&ProjectWorkflow::default(), TaskStatus::Done, TaskStatus::Review).is_ok());
+        assert!(validate_status_transition(&workflow, TaskStatus::Done, TaskStatus::Todo).is_ok());
+        assert!(validate_status_transition(&workflow, TaskStatus::Done, TaskStatus::Done).is_ok());
+        assert!(validate_status_transition(&workflow, TaskStatus::Todo, TaskStatus::Done).is_ok());
+        assert!(validate_status_transition(&workflow, TaskStatus::Done, TaskStatus::InProgress).is_err());
+
+        let duplicate = ProjectWorkflow {
+            transitions: vec![workflow.transitions[0].clone(), workflow.transitions[0].clone()],
+            ..Default::default()
+        };
+        assert!(validate_project_workflow(&duplicate).is_err());
+    }
+
+    #[test]
+    fn test_onboarding_template_validation() {
+        use crate::database::models::OnboardingTaskTemplate;
+
+        let task = OnboardingTaskTemplate {
+            title: "Read the handbook".to_string(),
+            description: None,
+            priority: None,
+            tags: Some(vec![" docs ".to_string(), "Docs".to_string()]),
+            due_in_days: Some(7),
+        };
+        let mut template = OnboardingTemplate { tasks: vec![task.clone()] };
+        assert!(validate_onboarding_template(&mut template).is_ok());
+        assert_eq!(template.tasks[0].tags, Some(vec!["docs".to_string()]));
+
+        let mut template = OnboardingTemplate {
+            tasks: vec![
+                task.clone(),
+                OnboardingTaskTemplate { title: " ".to_string(), due_in_days: Some(400), ..task.clone() },
+            ],
+        };
+        match validate_onboarding_template(&mut template) {
+            Err(AppError::InvalidFields(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+                assert_eq!(fields, vec!["tasks[1].title", "tasks[1].due_in_days"]);
+            }
+            other => panic!("expected field errors, got {:?}", other),
+        }
+
+        let mut template = OnboardingTemplate { tasks: vec![task; MAX_ONBOARDING_TASKS + 1] };
+        assert!(validate_onboarding_template(&mut template).is_err());
+    }
+
+    #[test]
+    fn test_staleness_rules_validation() {
+        use crate::database::models::StalenessThreshold;
+
+        let threshold = |status, days| StalenessThreshold { status, days };
+        let rules = StalenessRules {
+            enabled: true,
+            thresholds: vec![threshold(TaskStatus::Todo, 14), threshold(TaskStatus::Review, MAX_STALENESS_DAYS)],
+            post_comment: false,
+        };
+        assert!(validate_staleness_rules(&rules).is_ok());
+        assert!(validate_staleness_rules(&StalenessRules::default()).is_ok());
+
+        let rules = StalenessRules {
+            thresholds: vec![
+                threshold(TaskStatus::Todo, 14),
+                threshold(TaskStatus::Todo, 7),
+                threshold(TaskStatus::Done, 3),
+                threshold(TaskStatus::InProgress, 0),
+                threshold(TaskStatus::Review, MAX_STALENESS_DAYS + 1),
+            ],
+            ..rules
+        };
+        match validate_staleness_rules(&rules) {
+            Err(AppError::InvalidFields(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+                assert_eq!(
+                    fields,
+                    vec!["thresholds[1].status", "thresholds[2].status", "thresholds[3].days", "thresholds[4].days"]
+                );
+            }
+            other => panic!("expected field errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_retention_policy_minimums() {
+        let policy = |years, months, days| RetentionPolicy {
+            comment_max_age_years: years,
+            attachment_max_age_months: months,
+            closed_task_max_age_days: days,
+        };
+        assert!(validate_retention_policy(&RetentionPolicy::default()).is_ok());
+        assert!(validate_retention_policy(&policy(Some(1), Some(3), Some(30))).is_ok());
+        assert!(validate_retention_policy(&policy(
+            Some(MAX_COMMENT_RETENTION_YEARS),
+            Some(MAX_ATTACHMENT_RETENTION_MONTHS),
+            Some(MAX_CLOSED_TASK_RETENTION_DAYS)
+        ))
+        .is_ok());
+
+        for (policy, field) in [
+            (policy(Some(0), None, None), "comment_max_age_years"),
+            (policy(Some(MAX_COMMENT_RETENTION_YEARS + 1), None, None), "comment_max_age_years"),
+            (policy(None, Some(2), None), "attachment_max_age_months"),
+            (policy(None, None, Some(7)), "closed_task_max_age_days"),
+        ] {
+            match validate_retention_policy(&policy) {
+                Err(AppError::InvalidFields(errors)) => assert_eq!(errors[0].field, field),
+                other => panic!("expected field errors, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_automations_validation() {
+        use crate::database::models::{AutomationRule, AutomationTrigger};
+
+        let rule = |name: &str, actions| AutomationRule {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            trigger: AutomationTrigger::MovedToColumn { column_id: TaskStatus::Review },
+            actions,
+        };
+        let alice = Uuid::new_v4();
+        let mut automations = BoardAutomations {
+            rules: vec![rule(
+                " QA handoff ",
+                vec![
+                    AutomationAction::AssignRoundRobin { user_ids: vec![alice, alice] },
+                    AutomationAction::AddLabel { label: " needs-review ".to_string() },
+                    AutomationAction::AddLabel { label: "qa".to_string() },
+                    AutomationAction::SetPriority { priority: TaskPriority::High },
+                ],
+            )],
+        };
+        assert!(validate_board_automations(&mut automations).is_ok());
+        assert_eq!(automations.rules[0].name, "QA handoff");
+        assert_eq!(automations.rules[0].actions[0], AutomationAction::AssignRoundRobin { user_ids: vec![alice] });
+        assert_eq!(automations.rules[0].actions[1], AutomationAction::AddLabel { label: "needs-review".to_string() });
+        assert!(validate_board_automations(&mut BoardAutomations::default()).is_ok());
+
+        let mut duplicate = automations.rules[0].clone();
+        duplicate.actions = vec![
+            AutomationAction::SetPriority { priority: TaskPriority::Low },
+            AutomationAction::SetPriority { priority: TaskPriority::High },
+            AutomationAction::AssignRoundRobin { user_ids: Vec::new() },
+            AutomationAction::PostSystemComment { content: "  ".to_string() },
+        ];
+        let mut automations = BoardAutomations { rules: vec![automations.rules[0].clone(), duplicate, rule(" ", Vec::new())] };
+        match validate_board_automations(&mut automations) {
+            Err(AppError::InvalidFields(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+                assert_eq!(
+                    fields,
+                    vec![
+                        "rules[1].id",
+                        "rules[1].actions[1]",
+                        "rules[1].actions[2].user_ids",
+                        "rules[1].actions[3].content",
+                        "rules[2].name",
+                        "rules[2].actions",
+                    ]
+                );
+            }
+            other => panic!("expected field errors, got {:?}", other),
+        }
+
+        let mut automations = BoardAutomations {
+            rules: (0..=MAX_AUTOMATION_RULES).map(|_| rule("Label", vec![AutomationAction::AddLabel { label: "x".to_string() }])).collect(),
+        };
+        assert!(validate_board_automations(&mut automations).is_err());
+    }
+
+    #[test]
+    fn test_publish_template_validation() {
+        use crate::database::models::TemplateVisibility;
+
+        let mut request = PublishTemplateRequest {
+            name: "  Sprint board ".to_string(),
+            description: None,
+            visibility: TemplateVisibility::Team,
+        };
+        assert!(validate_publish_template(&mut request).is_ok());
+        assert_eq!(request.name, "Sprint board");
+
+        let mut request = PublishTemplateRequest {
+            name: " ".to_string(),
+            description: Some("x".repeat(MAX_TEMPLATE_DESCRIPTION_LENGTH + 1)),
+            visibility: TemplateVisibility::Instance,
+        };
+        match validate_publish_template(&mut request) {
+            Err(AppError::InvalidFields(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+                assert_eq!(fields, vec!["name", "description"]);
+            }
+            other => panic!("expected field errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_project_report_validation() {
+        use crate::database::models::{ReportCadence, ReportRecipients, ReportSection};
+
+        let recipient = Uuid::new_v4();
+        let mut request = CreateProjectReportRequest {
+            name: "Friday status".to_string(),
+            cadence: ReportCadence::Weekly,
+            weekday: Some(5),
+            hour: Some(16),
+            timezone: Some("Europe/Berlin".to_string()),
+            sections: vec![ReportSection::Completed, ReportSection::Overdue, ReportSection::Completed],
+            recipients: ReportRecipients { user_ids: vec![recipient, recipient], ..Default::default() },
+        };
+        assert!(validate_project_report(&mut request).is_ok());
+        assert_eq!(request.sections, vec![ReportSection::Completed, ReportSection::Overdue]);
+        assert_eq!(request.recipients.user_ids, vec![recipient]);
+
+        let mut request = CreateProjectReportRequest {
+            name: " ".to_string(),
+            weekday: Some(0),
+            hour: Some(24),
+            timezone: Some("Mars/Olympus".to_string()),
+            sections: vec![],
+            recipients: ReportRecipients::default(),
+            ..request
+        };
+        match validate_project_report(&mut request) {
+            Err(AppError::InvalidFields(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+                assert_eq!(fields, vec!["name", "sections", "weekday", "hour", "timezone", "recipients"]);
+            }
+            other => panic!("expected field errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_task_title_from_text() {
+        assert_eq!(task_title_from_text("\n  Update the docs  \nwith the new flags"), "Update the docs");
+        assert_eq!(task_title_from_text("  \n "), "");
+
+        let title = task_title_from_text(&"a".repeat(300));
+        assert_eq!(title.chars().count(), MAX_TASK_TITLE_LENGTH);
+        assert!(title.ends_with('…'));
+        assert!(validate_task_title(&title).is_ok());
+    }
+}
@@ -1,56 +1,250 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
-use crate::database::models::{Task, TaskStatus, TaskPriority, Board, TaskComment, UserSummary};
+use crate::database::models::{AuditExport, Task, TaskStatus, TaskPriority, Board, TaskComment, UserSummary, ColumnOrder, UserNotification, Announcement, ProjectRole, TaskAttachment};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type", content = "data")]
 pub enum WebSocketEvent {
     // Authentication events
     Authenticate { token: String },
-    AuthenticationSuccess { user_id: Uuid },
+    // `connection_id` identifies this connection in X-Connection-Id, see
+    // websocket::origin
+    AuthenticationSuccess { user_id: Uuid, connection_id: Uuid },
     AuthenticationError { message: String },
 
     // Subscription events
     Subscribe { project_id: Uuid },
+    // Subscribes with a filter on task events, or replaces the filter of an
+    // existing subscription. A plain Subscribe clears it again.
+    SubscribeFiltered(SubscriptionFilter),
     Unsubscribe { project_id: Uuid },
     SubscriptionSuccess { project_id: Uuid },
     SubscriptionError { message: String },
 
-    // Task events
+    // Task events. Those caused by a request carry the request's
+    // client_mutation_id, if it sent one (see websocket::origin).
     TaskCreated(TaskEventData),
     TaskUpdated(TaskEventData),
-    TaskDeleted { task_id: Uuid, project_id: Uuid },
+    TaskDeleted {
+        task_id: Uuid,
+        project_id: Uuid,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_mutation_id: Option<String>,
+    },
     TaskMoved(TaskMoveEventData),
+    // Positions were compacted; clients should adopt this order
+    TasksReordered(TasksReorderedEventData),
+    // Sent only to the user the task was assigned to / taken from
+    TaskAssignedToYou(TaskEventData),
+    TaskUnassigned(TaskEventData),
+    // Sent only to the task's assignee and creator. A burst of comments is
+    // one push with the notification's final count.
+    TaskCommentedOn(CommentNotificationData),
+    // Sent only to the assignee, once, when the staleness job marks the task
+    TaskWentStale(StaleTaskNotificationData),
+    // Sent only to the users a comment or description mentions, see mentions.rs
+    MentionedYou(MentionNotificationData),
+    // Sent only to the account an invitation's email address belongs to. The
+    // token still only goes out by email.
+    InvitedToProject(InvitationNotificationData),
+    // Sent only to the team admin who asked for a team audit export
+    AuditExportReady(AuditExportNotificationData),
+
+    // From the client: pause or resume the notification events above on this
+    // connection, e.g. while presenting. Connections start unmuted.
+    MuteNotifications,
+    UnmuteNotifications,
+
+    // Project events. Terminal: subscribers are unsubscribed right after.
+    ProjectDeleted { project_id: Uuid },
+    ProjectArchived { project_id: Uuid },
+    // Carries the onboarding tasks created for the new member, which get no
+    // TaskCreated events of their own
+    MemberAdded(MemberAddedEventData),
+    // The user left or was removed; their subscription ends right after.
+    // Tasks they were assigned in the project are unassigned on leave.
+    MemberRemoved { project_id: Uuid, user_id: Uuid, unassigned_task_ids: Vec<Uuid> },
 
-    // Board events
+    // Board events, with the client_mutation_id like task events
     BoardCreated(BoardEventData),
     BoardUpdated(BoardEventData),
-    BoardDeleted { board_id: Uuid, project_id: Uuid },
+    BoardDeleted {
+        board_id: Uuid,
+        project_id: Uuid,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_mutation_id: Option<String>,
+    },
+    ColumnWipStatusChanged(ColumnWipEventData),
 
-    // Comment events
+    // Sent only to the uploader once the virus scan has a result
+    AttachmentScanCompleted(AttachmentScanEventData),
+
+    // Comment events, with the client_mutation_id like task events
     CommentCreated(CommentEventData),
-    CommentDeleted { comment_id: Uuid, task_id: Uuid, project_id: Uuid },
+    CommentDeleted {
+        comment_id: Uuid,
+        task_id: Uuid,
+        project_id: Uuid,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_mutation_id: Option<String>,
+    },
 
     // User presence events
     UserJoined(UserPresenceData),
     UserLeft(UserPresenceData),
     UserTyping(TypingEventData),
     UserStoppedTyping(TypingEventData),
+    // From the client: the board it is showing, or null when it shows none
+    ViewingBoard { board_id: Option<Uuid> },
+    // Someone started or stopped looking at a board. A switch between boards
+    // is a leave for the old one followed by a join for the new one.
+    BoardViewersChanged(BoardViewersEventData),
+
+    // Sent to every connection when an instance admin creates or changes an
+    // announcement, including ones scheduled for later
+    Announcement(Announcement),
 
     // Error events
     Error { message: String },
     Pong,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl WebSocketEvent {
+    // The `type` the event is sent with
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebSocketEvent::Authenticate { .. } => "Authenticate",
+            WebSocketEvent::AuthenticationSuccess { .. } => "AuthenticationSuccess",
+            WebSocketEvent::AuthenticationError { .. } => "AuthenticationError",
+            WebSocketEvent::Subscribe { .. } => "Subscribe",
+            WebSocketEvent::SubscribeFiltered(_) => "SubscribeFiltered",
+            WebSocketEvent::Unsubscribe { .. } => "Unsubscribe",
+            WebSocketEvent::SubscriptionSuccess { .. } => "SubscriptionSuccess",
+            WebSocketEvent::SubscriptionError { .. } => "SubscriptionError",
+            WebSocketEvent::TaskCreated(_) => "TaskCreated",
+            WebSocketEvent::TaskUpdated(_) => "TaskUpdated",
+            WebSocketEvent::TaskDeleted { .. } => "TaskDeleted",
+            WebSocketEvent::TaskMoved(_) => "TaskMoved",
+            WebSocketEvent::TasksReordered(_) => "TasksReordered",
+            WebSocketEvent::TaskAssignedToYou(_) => "TaskAssignedToYou",
+            WebSocketEvent::TaskUnassigned(_) => "TaskUnassigned",
+            WebSocketEvent::TaskCommentedOn(_) => "TaskCommentedOn",
+            WebSocketEvent::TaskWentStale(_) => "TaskWentStale",
+            WebSocketEvent::MentionedYou(_) => "MentionedYou",
+            WebSocketEvent::InvitedToProject(_) => "InvitedToProject",
+            WebSocketEvent::AuditExportReady(_) => "AuditExportReady",
+            WebSocketEvent::MuteNotifications => "MuteNotifications",
+            WebSocketEvent::UnmuteNotifications => "UnmuteNotifications",
+            WebSocketEvent::ProjectDeleted { .. } => "ProjectDeleted",
+            WebSocketEvent::ProjectArchived { .. } => "ProjectArchived",
+            WebSocketEvent::MemberAdded(_) => "MemberAdded",
+            WebSocketEvent::MemberRemoved { .. } => "MemberRemoved",
+            WebSocketEvent::BoardCreated(_) => "BoardCreated",
+            WebSocketEvent::BoardUpdated(_) => "BoardUpdated",
+            WebSocketEvent::BoardDeleted { .. } => "BoardDeleted",
+            WebSocketEvent::ColumnWipStatusChanged(_) => "ColumnWipStatusChanged",
+            WebSocketEvent::AttachmentScanCompleted(_) => "AttachmentScanCompleted",
+            WebSocketEvent::CommentCreated(_) => "CommentCreated",
+            WebSocketEvent::CommentDeleted { .. } => "CommentDeleted",
+            WebSocketEvent::UserJoined(_) => "UserJoined",
+            WebSocketEvent::UserLeft(_) => "UserLeft",
+            WebSocketEvent::UserTyping(_) => "UserTyping",
+            WebSocketEvent::UserStoppedTyping(_) => "UserStoppedTyping",
+            WebSocketEvent::ViewingBoard { .. } => "ViewingBoard",
+            WebSocketEvent::BoardViewersChanged(_) => "BoardViewersChanged",
+            WebSocketEvent::Announcement(_) => "Announcement",
+            WebSocketEvent::Error { .. } => "Error",
+            WebSocketEvent::Pong => "Pong",
+        }
+    }
+
+    // The user's own notifications. They reach every connection of the user,
+    // subscribed to a project or not, unless it muted them.
+    pub fn is_notification(&self) -> bool {
+        matches!(
+            self,
+            WebSocketEvent::TaskAssignedToYou(_)
+                | WebSocketEvent::TaskUnassigned(_)
+                | WebSocketEvent::TaskCommentedOn(_)
+                | WebSocketEvent::TaskWentStale(_)
+                | WebSocketEvent::MentionedYou(_)
+                | WebSocketEvent::InvitedToProject(_)
+                | WebSocketEvent::AuditExportReady(_)
+        )
+    }
+
+    // The task of events that carry one whole, which filters are applied to
+    pub fn task(&self) -> Option<&Task> {
+        match self {
+            WebSocketEvent::TaskCreated(data)
+            | WebSocketEvent::TaskUpdated(data)
+            | WebSocketEvent::TaskAssignedToYou(data)
+            | WebSocketEvent::TaskUnassigned(data) => Some(&data.task),
+            _ => None,
+        }
+    }
+}
+
+// Focus mode: only tasks matching every given condition are sent. Empty lists
+// and a missing assignee match any task.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SubscriptionFilter {
+    pub project_id: Uuid,
+    #[serde(default)]
+    pub assignee: Option<Uuid>,
+    // Any of these labels
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub priorities: Vec<TaskPriority>,
+}
+
+impl SubscriptionFilter {
+    pub fn matches(&self, task: &Task) -> bool {
+        let labels = task.tags.as_deref().unwrap_or_default();
+        self.assignee.is_none_or(|assignee| task.assigned_to == Some(assignee))
+            && (self.labels.is_empty() || self.labels.iter().any(|label| labels.contains(label)))
+            && (self.priorities.is_empty() || self.priorities.contains(&task.priority))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TaskEventData {
     pub task: Task,
     pub project_id: Uuid,
     pub user: UserSummary,
+    // Set when the task was created from a board column
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<BoardColumn>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_mutation_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AttachmentScanEventData {
+    pub attachment: TaskAttachment,
+    pub project_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MemberAddedEventData {
+    pub project_id: Uuid,
+    pub member: UserSummary,
+    pub role: ProjectRole,
+    pub onboarding_tasks: Vec<Task>,
+    // Who added them
+    pub user: UserSummary,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct BoardColumn {
+    pub board_id: Uuid,
+    pub column_id: TaskStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TaskMoveEventData {
     pub task_id: Uuid,
     pub from_status: TaskStatus,
@@ -58,31 +252,104 @@ pub struct TaskMoveEventData {
     pub position: i32,
     pub project_id: Uuid,
     pub user: UserSummary,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_mutation_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TasksReorderedEventData {
+    pub project_id: Uuid,
+    pub columns: Vec<ColumnOrder>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BoardEventData {
     pub board: Board,
     pub project_id: Uuid,
     pub user: UserSummary,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_mutation_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Sent when a column reaches its WIP limit or drops back below it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct ColumnWipEventData {
+    pub board_id: Uuid,
+    pub column_id: TaskStatus,
+    pub count: i64,
+    pub limit: i32,
+    pub project_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CommentEventData {
     pub comment: TaskComment,
     pub task_id: Uuid,
     pub project_id: Uuid,
     pub user: UserSummary,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_mutation_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CommentNotificationData {
+    pub notification: UserNotification,
+    pub task: Task,
+    pub user: UserSummary, // the latest commenter
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StaleTaskNotificationData {
+    pub notification: UserNotification,
+    pub task: Task,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MentionNotificationData {
+    pub notification: UserNotification,
+    pub task: Task,
+    pub comment_id: Option<Uuid>, // None for mentions in the description
+    pub group_token: Option<String>, // e.g. "project" for @project
+    pub user: UserSummary, // the author
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AuditExportNotificationData {
+    pub notification: UserNotification,
+    pub export: AuditExport,
+    pub download_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct InvitationNotificationData {
+    pub invitation_id: Uuid,
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub role: ProjectRole,
+    pub expires_at: DateTime<Utc>,
+    pub user: UserSummary, // who invited them
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct UserPresenceData {
     pub user: UserSummary,
     pub project_id: Uuid,
     pub timestamp: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BoardViewersEventData {
+    pub board_id: Uuid,
+    pub project_id: Uuid,
+    // Who arrived or left
+    pub user: UserSummary,
+    pub joined: bool,
+    // Everyone viewing the board after the change
+    pub viewers: Vec<Uuid>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TypingEventData {
     pub user: UserSummary,
     pub task_id: Uuid,
@@ -97,19 +364,36 @@ pub enum HeartbeatEvent {
     Pong,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewedBoard {
+    pub board_id: Uuid,
+    pub project_id: Uuid,
+}
+
 // Connection state
 #[derive(Debug, Clone)]
 pub struct ConnectionInfo {
+    pub connection_id: Uuid,
     pub user_id: Uuid,
     pub subscribed_projects: std::collections::HashSet<Uuid>,
+    // Focus filters of subscribed projects, see SubscriptionFilter
+    pub filters: std::collections::HashMap<Uuid, SubscriptionFilter>,
+    // Board the client is showing, if any
+    pub viewing: Option<ViewedBoard>,
+    // MuteNotifications was sent, see WebSocketEvent::is_notification
+    pub notifications_muted: bool,
     pub last_seen: DateTime<Utc>,
 }
 
 impl ConnectionInfo {
-    pub fn new(user_id: Uuid) -> Self {
+    pub fn new(connection_id: Uuid, user_id: Uuid) -> Self {
         Self {
+            connection_id,
             user_id,
             subscribed_projects: std::collections::HashSet::new(),
+            filters: std::collections::HashMap::new(),
+            viewing: None,
+            notifications_muted: false,
             last_seen: Utc::now(),
         }
     }
@@ -119,8 +403,17 @@ impl ConnectionInfo {
         self.last_seen = Utc::now();
     }
 
+    // Replaces the project's filter; None sends it everything again
+    pub fn set_filter(&mut self, project_id: Uuid, filter: Option<SubscriptionFilter>) {
+        match filter {
+            Some(filter) => self.filters.insert(project_id, filter),
+            None => self.filters.remove(&project_id),
+        };
+    }
+
     pub fn unsubscribe_from_project(&mut self, project_id: Uuid) {
         self.subscribed_projects.remove(&project_id);
+        self.filters.remove(&project_id);
         self.last_seen = Utc::now();
     }
 
@@ -128,6 +421,27 @@ impl ConnectionInfo {
         self.subscribed_projects.contains(&project_id)
     }
 
+    // Whether a project event goes to this connection: it must be subscribed,
+    // and a task event must pass the project's filter
+    pub fn wants(&self, project_id: Uuid, event: &WebSocketEvent) -> bool {
+        if !self.is_subscribed_to(project_id) {
+            return false;
+        }
+        match (self.filters.get(&project_id), event.task()) {
+            (Some(filter), Some(task)) => filter.matches(task),
+            _ => true,
+        }
+    }
+
+    // Whether an event sent to the connection's user goes out on it
+    pub fn wants_personal(&self, event: &WebSocketEvent) -> bool {
+        !(self.notifications_muted && event.is_notification())
+    }
+
+    pub fn is_viewing(&self, board_id: Uuid) -> bool {
+        self.viewing.is_some_and(|viewing| viewing.board_id == board_id)
+    }
+
     pub fn update_last_seen(&mut self) {
         self.last_seen = Utc::now();
     }
@@ -8,25 +8,27 @@ use futures_util::{sink::SinkExt, stream::StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{broadcast, RwLock, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, RwLock, Mutex};
 use uuid::Uuid;
 use tracing::{info, warn, error, debug};
 use chrono::Utc;
 
 use crate::auth::jwt::JwtService;
 use crate::database::{
-    models::UserSummary,
-    queries::{ProjectQueries, UserQueries}
+    models::{AuditAction, UserSummary},
+    queries::{AuditQueries, BoardQueries, ProjectQueries, UserQueries}
 };
 use crate::utils::errors::AppError;
-use super::events::{WebSocketEvent, ConnectionInfo, HeartbeatEvent};
+use super::events::{WebSocketEvent, ConnectionInfo, HeartbeatEvent, BoardViewersEventData, SubscriptionFilter, ViewedBoard};
+use super::limits::{self, WebSocketMetrics};
 
 #[derive(Debug, Deserialize)]
 pub struct WebSocketQuery {
     token: Option<String>,
 }
 
-// Global connection manager
+// Open connections by connection id. A user has one per tab or device.
 pub type ConnectionManager = Arc<RwLock<HashMap<Uuid, broadcast::Sender<WebSocketEvent>>>>;
 pub type UserConnectionsManager = Arc<RwLock<HashMap<Uuid, ConnectionInfo>>>;
 
@@ -36,6 +38,7 @@ pub struct WebSocketState {
     pub user_connections: UserConnectionsManager,
     pub jwt_service: JwtService,
     pub database: crate::database::connection::Database,
+    pub metrics: Arc<WebSocketMetrics>,
 }
 
 impl WebSocketState {
@@ -45,147 +48,402 @@ impl WebSocketState {
             user_connections: Arc::new(RwLock::new(HashMap::new())),
             jwt_service,
             database,
+            metrics: Arc::new(WebSocketMetrics::default()),
         }
     }
 
-    // Broadcast event to all users subscribed to a project
-    pub async fn broadcast_to_project(&self, project_id: Uuid, event: WebSocketEvent, exclude_user: Option<Uuid>) {
+    // Broadcast event to all connections subscribed to a project, except the
+    // one the change came from (see websocket::origin)
+    pub async fn broadcast_to_project(&self, project_id: Uuid, event: WebSocketEvent, exclude_connection: Option<Uuid>) {
         let user_connections = self.user_connections.read().await;
         let connections = self.connections.read().await;
 
-        for (user_id, conn_info) in user_connections.iter() {
-            if let Some(exclude) = exclude_user {
-                if *user_id == exclude {
-                    continue;
-                }
+        send_to_subscribers(&user_connections, &connections, project_id, &event, exclude_connection);
+    }
+
+    // Sends a final event to a deleted or archived project's subscribers and drops
+    // every subscription to it. Subscriptions are the only per-project state kept here.
+    pub async fn close_project(&self, project_id: Uuid, event: WebSocketEvent) {
+        self.broadcast_to_project(project_id, event, None).await;
+
+        let mut user_connections = self.user_connections.write().await;
+        for conn_info in user_connections.values_mut() {
+            conn_info.unsubscribe_from_project(project_id);
+            if conn_info.viewing.is_some_and(|viewing| viewing.project_id == project_id) {
+                conn_info.viewing = None;
             }
+        }
 
-            if conn_info.is_subscribed_to(project_id) {
-                if let Some(sender) = connections.get(user_id) {
-                    if let Err(e) = sender.send(event.clone()) {
-                        warn!("Failed to send message to user {}: {}", user_id, e);
-                    }
-                }
+        debug!("Closed WebSocket subscriptions for project {}", project_id);
+    }
+
+    // Announces that a member is gone (the member included) and drops the
+    // subscriptions of their connections to the project, if they had any.
+    pub async fn remove_member(&self, project_id: Uuid, user_id: Uuid, event: WebSocketEvent) {
+        self.broadcast_to_project(project_id, event, None).await;
+
+        for connection_id in self.connections_of(user_id).await {
+            self.stop_viewing_project(connection_id, project_id).await;
+
+            let subscribed = self
+                .user_connections
+                .read()
+                .await
+                .get(&connection_id)
+                .is_some_and(|conn_info| conn_info.is_subscribed_to(project_id));
+            if subscribed {
+                self.unsubscribe_from_project(connection_id, project_id).await;
             }
         }
     }
 
-    // Send event to specific user
-    pub async fn send_to_user(&self, user_id: Uuid, event: WebSocketEvent) {
+    // Send event to every connection, subscribed to a project or not
+    pub async fn broadcast_to_all(&self, event: WebSocketEvent) {
         let connections = self.connections.read().await;
-        if let Some(sender) = connections.get(&user_id) {
-            if let Err(e) = sender.send(event) {
-                warn!("Failed to send message to user {}: {}", user_id, e);
+        for (connection_id, sender) in connections.iter() {
+            if let Err(e) = sender.send(event.clone()) {
+                warn!("Failed to send message to connection {}: {}", connection_id, e);
             }
         }
     }
 
-    // Register new connection
-    pub async fn register_connection(&self, user_id: Uuid) -> broadcast::Receiver<WebSocketEvent> {
+    // Send event to every connection of a user. No subscription is needed;
+    // connections that muted notifications skip those.
+    pub async fn send_to_user(&self, user_id: Uuid, event: WebSocketEvent) {
+        let user_connections = self.user_connections.read().await;
+        let connections = self.connections.read().await;
+        for conn_info in user_connections
+            .values()
+            .filter(|conn_info| conn_info.user_id == user_id && conn_info.wants_personal(&event))
+        {
+            send_to(&connections, conn_info.connection_id, event.clone());
+        }
+    }
+
+    // Send event to one connection, e.g. the answer to something it sent
+    pub async fn send_to_connection(&self, connection_id: Uuid, event: WebSocketEvent) {
+        let connections = self.connections.read().await;
+        send_to(&connections, connection_id, event);
+    }
+
+    // The user a connection belongs to, while it's open
+    pub async fn connection_user(&self, connection_id: Uuid) -> Option<Uuid> {
+        let user_connections = self.user_connections.read().await;
+        user_connections.get(&connection_id).map(|conn_info| conn_info.user_id)
+    }
+
+    async fn connections_of(&self, user_id: Uuid) -> Vec<Uuid> {
+        let user_connections = self.user_connections.read().await;
+        user_connections
+            .values()
+            .filter(|conn_info| conn_info.user_id == user_id)
+            .map(|conn_info| conn_info.connection_id)
+            .collect()
+    }
+
+    // Register new connection, returning its id
+    pub async fn register_connection(&self, user_id: Uuid) -> (Uuid, broadcast::Receiver<WebSocketEvent>) {
+        let connection_id = Uuid::new_v4();
         let (tx, rx) = broadcast::channel(1000);
         
         {
             let mut connections = self.connections.write().await;
-            connections.insert(user_id, tx);
+            connections.insert(connection_id, tx);
         }
 
         {
             let mut user_connections = self.user_connections.write().await;
-            user_connections.insert(user_id, ConnectionInfo::new(user_id));
+            user_connections.insert(connection_id, ConnectionInfo::new(connection_id, user_id));
+        }
+
+        info!("User {} connected to WebSocket ({})", user_id, connection_id);
+        (connection_id, rx)
+    }
+
+    pub async fn set_notifications_muted(&self, connection_id: Uuid, muted: bool) {
+        let mut user_connections = self.user_connections.write().await;
+        if let Some(conn_info) = user_connections.get_mut(&connection_id) {
+            conn_info.notifications_muted = muted;
         }
+    }
+
+    // Users looking at the board right now
+    pub async fn board_viewers(&self, board_id: Uuid) -> Vec<Uuid> {
+        let user_connections = self.user_connections.read().await;
+        viewers_of(&user_connections, board_id)
+    }
+
+    // Sets or clears the board the connection is showing. The board must
+    // belong to a project the user is a member of.
+    pub async fn set_viewing_board(&self, connection_id: Uuid, board_id: Option<Uuid>) -> Result<(), AppError> {
+        let Some(user_id) = self.connection_user(connection_id).await else {
+            return Ok(());
+        };
+        let viewing = match board_id {
+            Some(board_id) => {
+                let board = BoardQueries::get_board_by_id(self.database.pool(), board_id).await?;
+                if !ProjectQueries::is_project_member(self.database.pool(), board.project_id, user_id).await? {
+                    return Err(AppError::Forbidden("Not a project member".to_string()));
+                }
+                Some(ViewedBoard { board_id, project_id: board.project_id })
+            }
+            None => None,
+        };
+
+        self.change_viewing(connection_id, user_id, |_| viewing).await;
+        Ok(())
+    }
+
+    // Clears the connection's viewed board if it belongs to the project
+    async fn stop_viewing_project(&self, connection_id: Uuid, project_id: Uuid) {
+        let Some(user_id) = self.connection_user(connection_id).await else {
+            return;
+        };
+        self.change_viewing(connection_id, user_id, |viewing| viewing.filter(|board| board.project_id != project_id))
+            .await;
+    }
+
+    // Leaving the old board and joining the new one happen under one write
+    // lock, and both announcements go out before it is released. Each carries
+    // the viewer list from that moment, so clients never see the user on two
+    // boards, nor an older list arriving after a newer one. A board the user
+    // has open in another tab as well isn't left or joined again.
+    async fn change_viewing(
+        &self,
+        connection_id: Uuid,
+        user_id: Uuid,
+        change: impl FnOnce(Option<ViewedBoard>) -> Option<ViewedBoard>,
+    ) {
+        let user = match UserQueries::get_user_by_id(self.database.pool(), user_id).await {
+            Ok(user) => UserSummary::from(user),
+            Err(e) => {
+                warn!("Failed to load user {} for board presence: {}", user_id, e);
+                return;
+            }
+        };
 
-        info!("User {} connected to WebSocket", user_id);
-        rx
+        let mut user_connections = self.user_connections.write().await;
+        let connections = self.connections.read().await;
+        let Some(conn_info) = user_connections.get_mut(&connection_id) else {
+            return;
+        };
+        let previous = conn_info.viewing;
+        let viewing = change(previous);
+        if previous == viewing {
+            return;
+        }
+        conn_info.viewing = viewing;
+
+        if let Some(board) = previous {
+            if viewing_connections(&user_connections, board.board_id, user_id) == 0 {
+                announce_viewers(&user_connections, &connections, board, &user, false);
+            }
+        }
+        if let Some(board) = viewing {
+            if viewing_connections(&user_connections, board.board_id, user_id) == 1 {
+                announce_viewers(&user_connections, &connections, board, &user, true);
+            }
+        }
     }
 
     // Unregister connection
-    pub async fn unregister_connection(&self, user_id: Uuid) {
+    pub async fn unregister_connection(&self, connection_id: Uuid) {
         {
             let mut connections = self.connections.write().await;
-            connections.remove(&user_id);
+            connections.remove(&connection_id);
         }
 
-        // Notify other users that this user left
+        // Notify other users that this user left, unless another of their
+        // connections is still there
         if let Some(conn_info) = {
             let mut user_connections = self.user_connections.write().await;
-            user_connections.remove(&user_id)
+            user_connections.remove(&connection_id)
         } {
+            let user_id = conn_info.user_id;
             // Get user info for presence notifications
             if let Ok(user) = UserQueries::get_user_by_id(self.database.pool(), user_id).await {
                 let user_summary: UserSummary = user.into();
+
+                if let Some(board) = conn_info.viewing {
+                    let user_connections = self.user_connections.read().await;
+                    let connections = self.connections.read().await;
+                    if viewing_connections(&user_connections, board.board_id, user_id) == 0 {
+                        announce_viewers(&user_connections, &connections, board, &user_summary, false);
+                    }
+                }
                 
                 // Notify all subscribed projects that user left
                 for project_id in conn_info.subscribed_projects {
+                    if self.subscribed_connections(user_id, project_id).await > 0 {
+                        continue;
+                    }
                     let presence_event = WebSocketEvent::UserLeft(super::events::UserPresenceData {
                         user: user_summary.clone(),
                         project_id,
                         timestamp: Utc::now(),
                     });
                     
-                    self.broadcast_to_project(project_id, presence_event, Some(user_id)).await;
+                    self.broadcast_to_project(project_id, presence_event, None).await;
                 }
             }
+            info!("User {} disconnected from WebSocket ({})", user_id, connection_id);
         }
+    }
 
-        info!("User {} disconnected from WebSocket", user_id);
+    // How many of the user's connections are subscribed to the project
+    async fn subscribed_connections(&self, user_id: Uuid, project_id: Uuid) -> usize {
+        let user_connections = self.user_connections.read().await;
+        user_connections
+            .values()
+            .filter(|conn_info| conn_info.user_id == user_id && conn_info.is_subscribed_to(project_id))
+            .count()
     }
 
-    // Subscribe user to project updates
-    pub async fn subscribe_to_project(&self, user_id: Uuid, project_id: Uuid) -> Result<(), AppError> {
+    // Subscribe a connection to project updates, with a focus filter or
+    // without. Subscribing again only changes the filter.
+    pub async fn subscribe_to_project(
+        &self,
+        connection_id: Uuid,
+        project_id: Uuid,
+        filter: Option<SubscriptionFilter>,
+    ) -> Result<(), AppError> {
+        let Some(user_id) = self.connection_user(connection_id).await else {
+            return Ok(());
+        };
         // Check if user has access to this project
         if !ProjectQueries::is_project_member(self.database.pool(), project_id, user_id).await? {
             return Err(AppError::Forbidden("Not a project member".to_string()));
         }
 
-        {
+        let resubscribed = {
             let mut user_connections = self.user_connections.write().await;
-            if let Some(conn_info) = user_connections.get_mut(&user_id) {
-                conn_info.subscribe_to_project(project_id);
+            match user_connections.get_mut(&connection_id) {
+                Some(conn_info) => {
+                    let subscribed = conn_info.is_subscribed_to(project_id);
+                    conn_info.subscribe_to_project(project_id);
+                    conn_info.set_filter(project_id, filter);
+                    subscribed
+                }
+                None => false,
             }
-        }
+        };
+        // Others only hear of the user's first connection
+        let joined = !resubscribed && self.subscribed_connections(user_id, project_id).await == 1;
 
         // Notify other users that this user joined
-        if let Ok(user) = UserQueries::get_user_by_id(self.database.pool(), user_id).await {
-            let user_summary: UserSummary = user.into();
-            let presence_event = WebSocketEvent::UserJoined(super::events::UserPresenceData {
-                user: user_summary,
-                project_id,
-                timestamp: Utc::now(),
-            });
-            
-            self.broadcast_to_project(project_id, presence_event, Some(user_id)).await;
+        if joined {
+            if let Ok(user) = UserQueries::get_user_by_id(self.database.pool(), user_id).await {
+                let user_summary: UserSummary = user.into();
+                let presence_event = WebSocketEvent::UserJoined(super::events::UserPresenceData {
+                    user: user_summary,
+                    project_id,
+                    timestamp: Utc::now(),
+                });
+                
+                self.broadcast_to_project(project_id, presence_event, Some(connection_id)).await;
+            }
         }
 
         // Send subscription success
-        self.send_to_user(user_id, WebSocketEvent::SubscriptionSuccess { project_id }).await;
+        self.send_to_connection(connection_id, WebSocketEvent::SubscriptionSuccess { project_id }).await;
         
-        debug!("User {} subscribed to project {}", user_id, project_id);
+        debug!("User {} subscribed to project {} ({})", user_id, project_id, connection_id);
         Ok(())
     }
 
-    // Unsubscribe user from project updates
-    pub async fn unsubscribe_from_project(&self, user_id: Uuid, project_id: Uuid) {
+    // Unsubscribe a connection from project updates
+    pub async fn unsubscribe_from_project(&self, connection_id: Uuid, project_id: Uuid) {
+        let Some(user_id) = self.connection_user(connection_id).await else {
+            return;
+        };
+        self.stop_viewing_project(connection_id, project_id).await;
+
         {
             let mut user_connections = self.user_connections.write().await;
-            if let Some(conn_info) = user_connections.get_mut(&user_id) {
+            if let Some(conn_info) = user_connections.get_mut(&connection_id) {
                 conn_info.unsubscribe_from_project(project_id);
             }
         }
 
         // Notify other users that this user left the project
-        if let Ok(user) = UserQueries::get_user_by_id(self.database.pool(), user_id).await {
-            let user_summary: UserSummary = user.into();
-            let presence_event = WebSocketEvent::UserLeft(super::events::UserPresenceData {
-                user: user_summary,
-                project_id,
-                timestamp: Utc::now(),
-            });
-            
-            self.broadcast_to_project(project_id, presence_event, Some(user_id)).await;
+        if self.subscribed_connections(user_id, project_id).await == 0 {
+            if let Ok(user) = UserQueries::get_user_by_id(self.database.pool(), user_id).await {
+                let user_summary: UserSummary = user.into();
+                let presence_event = WebSocketEvent::UserLeft(super::events::UserPresenceData {
+                    user: user_summary,
+                    project_id,
+                    timestamp: Utc::now(),
+                });
+                
+                self.broadcast_to_project(project_id, presence_event, Some(connection_id)).await;
+            }
+        }
+
+        debug!("User {} unsubscribed from project {} ({})", user_id, project_id, connection_id);
+    }
+}
+
+fn send_to(connections: &HashMap<Uuid, broadcast::Sender<WebSocketEvent>>, connection_id: Uuid, event: WebSocketEvent) {
+    if let Some(sender) = connections.get(&connection_id) {
+        if let Err(e) = sender.send(event) {
+            warn!("Failed to send message to connection {}: {}", connection_id, e);
+        }
+    }
+}
+
+fn send_to_subscribers(
+    user_connections: &HashMap<Uuid, ConnectionInfo>,
+    connections: &HashMap<Uuid, broadcast::Sender<WebSocketEvent>>,
+    project_id: Uuid,
+    event: &WebSocketEvent,
+    exclude_connection: Option<Uuid>,
+) {
+    for (connection_id, conn_info) in user_connections.iter() {
+        if exclude_connection == Some(*connection_id) {
+            continue;
+        }
+
+        if conn_info.wants(project_id, event) {
+            send_to(connections, *connection_id, event.clone());
         }
+    }
+}
 
-        debug!("User {} unsubscribed from project {}", user_id, project_id);
+// Each user once, however many of their connections show the board
+fn viewers_of(user_connections: &HashMap<Uuid, ConnectionInfo>, board_id: Uuid) -> Vec<Uuid> {
+    let mut viewers = Vec::new();
+    for conn_info in user_connections.values().filter(|conn_info| conn_info.is_viewing(board_id)) {
+        if !viewers.contains(&conn_info.user_id) {
+            viewers.push(conn_info.user_id);
+        }
     }
+    viewers
+}
+
+fn viewing_connections(user_connections: &HashMap<Uuid, ConnectionInfo>, board_id: Uuid, user_id: Uuid) -> usize {
+    user_connections
+        .values()
+        .filter(|conn_info| conn_info.user_id == user_id && conn_info.is_viewing(board_id))
+        .count()
+}
+
+// Tells the board's project, the user included, who is looking at it now
+fn announce_viewers(
+    user_connections: &HashMap<Uuid, ConnectionInfo>,
+    connections: &HashMap<Uuid, broadcast::Sender<WebSocketEvent>>,
+    board: ViewedBoard,
+    user: &UserSummary,
+    joined: bool,
+) {
+    let event = WebSocketEvent::BoardViewersChanged(BoardViewersEventData {
+        board_id: board.board_id,
+        project_id: board.project_id,
+        user: user.clone(),
+        joined,
+        viewers: viewers_of(user_connections, board.board_id),
+        timestamp: Utc::now(),
+    });
+    send_to_subscribers(user_connections, connections, board.project_id, &event, None);
 }
 
 // WebSocket upgrade handler
@@ -194,7 +452,9 @@ pub async fn websocket_handler(
     State(ws_state): State<WebSocketState>,
     Query(params): Query<WebSocketQuery>,
 ) -> Response {
-    ws.on_upgrade(move |socket| handle_socket(socket, ws_state, params.token))
+    ws.max_message_size(limits::transport_limit())
+        .max_frame_size(limits::transport_limit())
+        .on_upgrade(move |socket| handle_socket(socket, ws_state, params.token))
 }
 
 // Handle individual WebSocket connection
@@ -202,15 +462,12 @@ async fn handle_socket(socket: WebSocket, ws_state: WebSocketState, token: Optio
     let (mut sender, mut receiver) = socket.split();
     
     // Authentication
-    let user_id = match authenticate_connection(&token, &ws_state.jwt_service).await {
-        Ok(user_id) => {
-            let auth_success = WebSocketEvent::AuthenticationSuccess { user_id };
-            if let Err(e) = sender.send(Message::Text(serde_json::to_string(&auth_success).unwrap())).await {
-                error!("Failed to send auth success: {}", e);
-                return;
-            }
-            user_id
-        }
+    let authenticated = match authenticate_connection(&token, &ws_state.jwt_service).await {
+        Ok((user_id, Some(admin_id))) => audit_impersonated_connection(&ws_state, admin_id, user_id).await.map(|_| user_id),
+        other => other.map(|(user_id, _)| user_id),
+    };
+    let user_id = match authenticated {
+        Ok(user_id) => user_id,
         Err(e) => {
             let auth_error = WebSocketEvent::AuthenticationError { 
                 message: e.to_string() 
@@ -222,65 +479,110 @@ async fn handle_socket(socket: WebSocket, ws_state: WebSocketState, token: Optio
         }
     };
 
-    // Register connection
-    let mut event_rx = ws_state.register_connection(user_id).await;
+    // Register connection. Clients send its id with their requests so their
+    // own changes aren't echoed back to them (see websocket::origin).
+    let (connection_id, mut event_rx) = ws_state.register_connection(user_id).await;
+    let auth_success = WebSocketEvent::AuthenticationSuccess { user_id, connection_id };
+    if let Err(e) = sender.send(Message::Text(serde_json::to_string(&auth_success).unwrap())).await {
+        error!("Failed to send auth success: {}", e);
+        ws_state.unregister_connection(connection_id).await;
+        return;
+    }
     
-    // Spawn task to handle outgoing messages
-    let ws_state_clone = ws_state.clone();
-    let sender_task = tokio::spawn(async move {
-        while let Ok(event) = event_rx.recv().await {
-            let message = match serde_json::to_string(&event) {
-                Ok(msg) => Message::Text(msg),
-                Err(e) => {
-                    error!("Failed to serialize event: {}", e);
-                    continue;
+    // Spawn task to handle outgoing messages. Close frames for rejected
+    // client messages go through `close_tx` and end the task.
+    let (close_tx, mut close_rx) = mpsc::unbounded_channel::<Message>();
+    let mut sender_task = tokio::spawn(async move {
+        loop {
+            let message = tokio::select! {
+                Some(close) = close_rx.recv() => close,
+                event = event_rx.recv() => {
+                    let Ok(event) = event else {
+                        break;
+                    };
+                    match serde_json::to_string(&event) {
+                        Ok(msg) => Message::Text(msg),
+                        Err(e) => {
+                            error!("Failed to serialize event: {}", e);
+                            continue;
+                        }
+                    }
                 }
             };
-            
-            if sender.send(message).await.is_err() {
+
+            let closing = matches!(message, Message::Close(_));
+            if sender.send(message).await.is_err() || closing {
                 break;
             }
         }
     });
 
     // Handle incoming messages
-    let ws_state_clone2 = ws_state.clone();
+    let max_message_size = limits::max_message_size();
+    let mut closing = false;
     while let Some(msg) = receiver.next().await {
-        if let Ok(msg) = msg {
-            if handle_message(msg, user_id, &ws_state_clone2).await.is_err() {
-                break;
-            }
-        } else {
+        let Ok(msg) = msg else {
+            break;
+        };
+
+        if let Err(rejection) = limits::check_message(&msg, max_message_size) {
+            warn!("Rejected WebSocket message from user {}: {:?}", user_id, rejection);
+            ws_state.metrics.record_rejection(rejection);
+            closing = close_tx.send(rejection.close_frame()).is_ok();
+            break;
+        }
+
+        if handle_message(msg, connection_id, user_id, &ws_state).await.is_err() {
             break;
         }
     }
 
-    // Cleanup
+    // Cleanup, after letting a pending close frame go out
+    if closing {
+        let _ = tokio::time::timeout(Duration::from_secs(1), &mut sender_task).await;
+    }
     sender_task.abort();
-    ws_state.unregister_connection(user_id).await;
+    ws_state.unregister_connection(connection_id).await;
 }
 
 // Authenticate WebSocket connection
-async fn authenticate_connection(token: &Option<String>, jwt_service: &JwtService) -> Result<Uuid, AppError> {
+// Returns the user and, for impersonation tokens, the admin behind them
+async fn authenticate_connection(token: &Option<String>, jwt_service: &JwtService) -> Result<(Uuid, Option<Uuid>), AppError> {
     let token = token.as_ref().ok_or_else(|| AppError::Unauthorized("No token provided".to_string()))?;
     
     let claims = jwt_service.verify_token(token)
-        .map_err(|_| AppError::Unauthorized("Invalid token".to_string()))?;
+        .map_err(|e| AppError::Unauthorized(e.to_string()))?;
     
     let user_id = claims.sub.parse::<Uuid>()
         .map_err(|_| AppError::Unauthorized("Invalid user ID in token".to_string()))?;
-    
-    Ok(user_id)
+
+    let impersonated_by = claims.impersonator
+        .map(|admin_id| Uuid::parse_str(&admin_id))
+        .transpose()
+        .map_err(|_| AppError::Unauthorized("Invalid impersonator in token".to_string()))?;
+
+    Ok((user_id, impersonated_by))
+}
+
+// Impersonated sockets are audited like impersonated HTTP requests. A socket
+// whose entry can't be written is refused, so none goes unrecorded.
+async fn audit_impersonated_connection(ws_state: &WebSocketState, admin_id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+    let pool = ws_state.database.pool();
+    let entry_id = AuditQueries::record(pool, AuditAction::ImpersonatedRequest, admin_id, user_id, Some("GET"), Some("/ws")).await?;
+    if let Err(e) = AuditQueries::set_status(pool, entry_id, StatusCode::SWITCHING_PROTOCOLS.as_u16() as i16).await {
+        warn!("Recording the status of impersonated connection {} failed: {}", entry_id, e);
+    }
+    Ok(())
 }
 
 // Handle incoming WebSocket messages
-async fn handle_message(msg: Message, user_id: Uuid, ws_state: &WebSocketState) -> Result<(), AppError> {
+async fn handle_message(msg: Message, connection_id: Uuid, user_id: Uuid, ws_state: &WebSocketState) -> Result<(), AppError> {
     match msg {
         Message::Text(text) => {
             let event: WebSocketEvent = serde_json::from_str(&text)
                 .map_err(|e| AppError::BadRequest(format!("Invalid message format: {}", e)))?;
             
-            handle_event(event, user_id, ws_state).await
+            handle_event(event, connection_id, ws_state).await
         }
         Message::Close(_) => {
             debug!("WebSocket connection closed by user {}", user_id);
@@ -293,47 +595,60 @@ async fn handle_message(msg: Message, user_id: Uuid, ws_state: &WebSocketState)
         Message::Pong(_) => {
             // Update last seen
             let mut user_connections = ws_state.user_connections.write().await;
-            if let Some(conn_info) = user_connections.get_mut(&user_id) {
+            if let Some(conn_info) = user_connections.get_mut(&connection_id) {
                 conn_info.update_last_seen();
             }
             Ok(())
         }
-        _ => {
-            warn!("Unsupported message type from user {}", user_id);
-            Ok(())
-        }
+        // Rejected by limits::check_message before we get here
+        Message::Binary(_) => Err(AppError::BadRequest("Binary messages are not supported".to_string())),
     }
 }
 
 // Handle specific WebSocket events
-async fn handle_event(event: WebSocketEvent, user_id: Uuid, ws_state: &WebSocketState) -> Result<(), AppError> {
+async fn handle_event(event: WebSocketEvent, connection_id: Uuid, ws_state: &WebSocketState) -> Result<(), AppError> {
     match event {
         WebSocketEvent::Subscribe { project_id } => {
-            ws_state.subscribe_to_project(user_id, project_id).await?;
+            ws_state.subscribe_to_project(connection_id, project_id, None).await?;
+        }
+        WebSocketEvent::SubscribeFiltered(filter) => {
+            ws_state.subscribe_to_project(connection_id, filter.project_id, Some(filter)).await?;
         }
         WebSocketEvent::Unsubscribe { project_id } => {
-            ws_state.unsubscribe_from_project(user_id, project_id).await;
+            ws_state.unsubscribe_from_project(connection_id, project_id).await;
         }
         WebSocketEvent::UserTyping(typing_data) => {
-            // Broadcast typing indicator to other users in the project
+            // Broadcast typing indicator to the project's other connections
             ws_state.broadcast_to_project(
                 typing_data.project_id, 
                 WebSocketEvent::UserTyping(typing_data),
-                Some(user_id)
+                Some(connection_id)
             ).await;
         }
         WebSocketEvent::UserStoppedTyping(typing_data) => {
-            // Broadcast stop typing indicator to other users in the project
+            // Broadcast stop typing indicator to the project's other connections
             ws_state.broadcast_to_project(
                 typing_data.project_id, 
                 WebSocketEvent::UserStoppedTyping(typing_data),
-                Some(user_id)
+                Some(connection_id)
             ).await;
         }
+        WebSocketEvent::ViewingBoard { board_id } => {
+            // A board the user can't see is reported, not fatal to the connection
+            if let Err(e) = ws_state.set_viewing_board(connection_id, board_id).await {
+                ws_state.send_to_connection(connection_id, WebSocketEvent::Error { message: e.to_string() }).await;
+            }
+        }
+        WebSocketEvent::MuteNotifications => {
+            ws_state.set_notifications_muted(connection_id, true).await;
+        }
+        WebSocketEvent::UnmuteNotifications => {
+            ws_state.set_notifications_muted(connection_id, false).await;
+        }
         WebSocketEvent::Pong => {
             // Handle pong response to keep connection alive
             let mut user_connections = ws_state.user_connections.write().await;
-            if let Some(conn_info) = user_connections.get_mut(&user_id) {
+            if let Some(conn_info) = user_connections.get_mut(&connection_id) {
                 conn_info.update_last_seen();
             }
         }
@@ -0,0 +1,154 @@
+// Limits on what clients may send over the WebSocket. Oversized, binary and
+// deeply nested messages are rejected with a close frame before they reach the
+// JSON parser, and counted so abuse shows up in the metrics.
+
+use axum::extract::ws::{close_code, CloseFrame, Message};
+use std::borrow::Cow;
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+// Client events are flat; anything nested deeper than this isn't one of ours
+pub const MAX_JSON_DEPTH: usize = 16;
+
+// Largest text message accepted from a client (WS_MAX_MESSAGE_SIZE, in bytes)
+pub fn max_message_size() -> usize {
+    static SIZE: OnceLock<usize> = OnceLock::new();
+    *SIZE.get_or_init(|| {
+        env::var("WS_MAX_MESSAGE_SIZE")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|size| *size > 0)
+            .unwrap_or(DEFAULT_MAX_MESSAGE_SIZE)
+    })
+}
+
+// Hard cap handed to the transport. It is above max_message_size so that
+// moderately oversized messages still get a proper 1009 close from us;
+// beyond it the transport drops the connection by itself.
+pub fn transport_limit() -> usize {
+    max_message_size().saturating_mul(2)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameRejection {
+    TooLarge,
+    // No binary protocol (e.g. msgpack) can be negotiated, so binary frames are never valid
+    Binary,
+    TooDeep,
+}
+
+impl FrameRejection {
+    pub fn close_frame(&self) -> Message {
+        let (code, reason) = match self {
+            FrameRejection::TooLarge => (close_code::SIZE, "Message too large"),
+            FrameRejection::Binary => (close_code::UNSUPPORTED, "Binary messages are not supported"),
+            FrameRejection::TooDeep => (close_code::POLICY, "Message nested too deeply"),
+        };
+        Message::Close(Some(CloseFrame { code, reason: Cow::Borrowed(reason) }))
+    }
+}
+
+// Checks a client message before it is parsed
+pub fn check_message(message: &Message, max_size: usize) -> Result<(), FrameRejection> {
+    match message {
+        Message::Text(text) if text.len() > max_size => Err(FrameRejection::TooLarge),
+        Message::Text(text) if exceeds_depth(text, MAX_JSON_DEPTH) => Err(FrameRejection::TooDeep),
+        Message::Binary(_) => Err(FrameRejection::Binary),
+        _ => Ok(()),
+    }
+}
+
+// Whether arrays/objects in `json` nest deeper than `max_depth`. Brackets inside
+// strings don't count; the JSON doesn't have to be valid.
+fn exceeds_depth(json: &str, max_depth: usize) -> bool {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for byte in json.bytes() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return true;
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    false
+}
+
+// Rejected client messages since startup, by reason
+#[derive(Debug, Default)]
+pub struct WebSocketMetrics {
+    too_large: AtomicU64,
+    binary: AtomicU64,
+    too_deep: AtomicU64,
+}
+
+impl WebSocketMetrics {
+    fn counter(&self, rejection: FrameRejection) -> &AtomicU64 {
+        match rejection {
+            FrameRejection::TooLarge => &self.too_large,
+            FrameRejection::Binary => &self.binary,
+            FrameRejection::TooDeep => &self.too_deep,
+        }
+    }
+
+    pub fn record_rejection(&self, rejection: FrameRejection) {
+        self.counter(rejection).fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn rejections(&self, rejection: FrameRejection) -> u64 {
+        self.counter(rejection).load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_are_checked_before_parsing() {
+        let subscribe = r#"{"type":"Subscribe","data":{"project_id":"00000000-0000-0000-0000-000000000000"}}"#;
+        assert_eq!(check_message(&Message::Text(subscribe.to_string()), 1024), Ok(()));
+        assert_eq!(check_message(&Message::Ping(vec![1]), 1024), Ok(()));
+
+        assert_eq!(check_message(&Message::Text("x".repeat(1025)), 1024), Err(FrameRejection::TooLarge));
+        assert_eq!(check_message(&Message::Binary(vec![0x82]), 1024), Err(FrameRejection::Binary));
+        assert_eq!(check_message(&Message::Text("[".repeat(100)), 1024), Err(FrameRejection::TooDeep));
+    }
+
+    #[test]
+    fn test_json_depth_ignores_strings() {
+        assert!(!exceeds_depth(r#"{"a":{"b":[1,2]}}"#, 3));
+        assert!(exceeds_depth(r#"{"a":{"b":[[1]]}}"#, 3));
+        assert!(!exceeds_depth(r#"{"a":"[[[[{{{{\"[["}"#, 1));
+    }
+
+    #[test]
+    fn test_rejections_are_counted_per_reason() {
+        let metrics = WebSocketMetrics::default();
+        metrics.record_rejection(FrameRejection::TooLarge);
+        metrics.record_rejection(FrameRejection::TooLarge);
+        metrics.record_rejection(FrameRejection::Binary);
+
+        assert_eq!(metrics.rejections(FrameRejection::TooLarge), 2);
+        assert_eq!(metrics.rejections(FrameRejection::Binary), 1);
+        assert_eq!(metrics.rejections(FrameRejection::TooDeep), 0);
+    }
+}
@@ -1,3 +1,6 @@
 // WebSocket module for real-time features
 pub mod handler;
-pub mod events;
\ No newline at end of file
+pub mod events;
+pub mod limits;
+pub mod origin;
+pub mod schemas;
\ No newline at end of file
@@ -0,0 +1,124 @@
+// Lets clients reconcile optimistic updates with the WebSocket events of their
+// changes. Task, board and comment mutations accept a client_mutation_id, in
+// the X-Client-Mutation-Id header or as a field of the JSON body, and echo it
+// in the events they cause. It's only carried through the request, never
+// stored.
+//
+// X-Connection-Id names the WebSocket connection the request was made from
+// (the `connection_id` of AuthenticationSuccess). Only that connection is
+// left out of the broadcast; the user's other tabs get the events as well.
+
+use async_trait::async_trait;
+use axum::{extract::FromRequestParts, http::request::Parts};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::middleware::CurrentUser;
+use crate::utils::errors::AppError;
+
+pub const CONNECTION_ID_HEADER: &str = "x-connection-id";
+pub const CLIENT_MUTATION_ID_HEADER: &str = "x-client-mutation-id";
+
+const MAX_CLIENT_MUTATION_ID_LENGTH: usize = 128;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Origin {
+    // Always one of the current user's open connections
+    pub connection_id: Option<Uuid>,
+    pub client_mutation_id: Option<String>,
+}
+
+impl Origin {
+    // A client_mutation_id in the body takes precedence over the header
+    pub fn with_body(mut self, client_mutation_id: Option<String>) -> Result<Self, AppError> {
+        if let Some(id) = client_mutation_id {
+            self.client_mutation_id = Some(validate_client_mutation_id(id)?);
+        }
+        Ok(self)
+    }
+}
+
+// A request body with the optional client_mutation_id next to its own fields
+#[derive(Debug, Deserialize)]
+pub struct Mutation<T> {
+    #[serde(flatten)]
+    pub body: T,
+    #[serde(default)]
+    pub client_mutation_id: Option<String>,
+}
+
+pub fn validate_client_mutation_id(id: String) -> Result<String, AppError> {
+    if id.is_empty() || id.chars().count() > MAX_CLIENT_MUTATION_ID_LENGTH {
+        return Err(AppError::Validation(format!(
+            "client_mutation_id must be 1 to {} characters",
+            MAX_CLIENT_MUTATION_ID_LENGTH
+        )));
+    }
+    Ok(id)
+}
+
+fn header(parts: &Parts, name: &str) -> Result<Option<String>, AppError> {
+    match parts.headers.get(name) {
+        Some(value) => value
+            .to_str()
+            .map(|value| Some(value.trim().to_string()))
+            .map_err(|_| AppError::BadRequest(format!("Invalid {} header", name))),
+        None => Ok(None),
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<crate::AppState> for Origin {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &crate::AppState) -> Result<Self, Self::Rejection> {
+        let client_mutation_id = header(parts, CLIENT_MUTATION_ID_HEADER)?
+            .map(validate_client_mutation_id)
+            .transpose()?;
+        let connection_id = header(parts, CONNECTION_ID_HEADER)?
+            .map(|value| value.parse::<Uuid>())
+            .transpose()
+            .map_err(|_| AppError::BadRequest(format!("Invalid {} header", CONNECTION_ID_HEADER)))?;
+
+        // Anyone else's connection would miss the event otherwise
+        let user_id = parts.extensions.get::<CurrentUser>().map(CurrentUser::id);
+        let connection_id = match (connection_id, user_id) {
+            (Some(connection_id), Some(user_id)) if state.websocket.connection_user(connection_id).await == Some(user_id) => {
+                Some(connection_id)
+            }
+            _ => None,
+        };
+
+        Ok(Origin { connection_id, client_mutation_id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Rename {
+        name: String,
+    }
+
+    #[test]
+    fn test_mutation_id_from_body_or_header() {
+        let mutation: Mutation<Rename> = serde_json::from_str(r#"{"name":"Sprint","client_mutation_id":"m-1"}"#).unwrap();
+        assert_eq!(mutation.body.name, "Sprint");
+
+        let header = Origin { connection_id: None, client_mutation_id: Some("h-1".to_string()) };
+        assert_eq!(header.clone().with_body(mutation.client_mutation_id).unwrap().client_mutation_id.as_deref(), Some("m-1"));
+        assert_eq!(header.with_body(None).unwrap().client_mutation_id.as_deref(), Some("h-1"));
+
+        let plain: Mutation<Rename> = serde_json::from_str(r#"{"name":"Sprint"}"#).unwrap();
+        assert!(plain.client_mutation_id.is_none());
+    }
+
+    #[test]
+    fn test_client_mutation_id_length() {
+        assert!(validate_client_mutation_id(String::new()).is_err());
+        assert!(validate_client_mutation_id("x".repeat(MAX_CLIENT_MUTATION_ID_LENGTH)).is_ok());
+        assert!(validate_client_mutation_id("x".repeat(MAX_CLIENT_MUTATION_ID_LENGTH + 1)).is_err());
+    }
+}
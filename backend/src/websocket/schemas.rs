@@ -0,0 +1,403 @@
+// Machine-readable schemas for the WebSocket events, generated from the Rust
+// types, and a sample of every event. Integrators read the schemas from
+// GET /api/events/schemas and can have a sample sent to their own connections
+// (POST /api/projects/:project_id/events/test) instead of creating real tasks.
+
+use chrono::{Duration, Utc};
+use schemars::schema_for;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+use crate::database::models::{
+    Announcement, AnnouncementSeverity, AttachmentScanStatus, AuditExport, AuditExportFormat, AuditExportStatus, Board, BoardConfig, ColumnOrder, NotificationKind,
+    ProjectRole, SwimlaneConfig, Task, TaskAttachment, TaskComment, TaskPriority, TaskStatus, UserNotification,
+    UserSummary,
+};
+use crate::websocket::events::{
+    AttachmentScanEventData, AuditExportNotificationData, BoardColumn, BoardEventData, BoardViewersEventData, ColumnWipEventData,
+    CommentEventData, CommentNotificationData, InvitationNotificationData, MemberAddedEventData, MentionNotificationData, StaleTaskNotificationData, SubscriptionFilter, TaskEventData,
+    TaskMoveEventData, TasksReorderedEventData, TypingEventData, UserPresenceData, WebSocketEvent,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SentBy {
+    Server,
+    Client,
+    // Relayed by the server to the other clients
+    Both,
+}
+
+const CLIENT_EVENTS: [&str; 8] = [
+    "Authenticate",
+    "Subscribe",
+    "SubscribeFiltered",
+    "Unsubscribe",
+    "MuteNotifications",
+    "UnmuteNotifications",
+    "ViewingBoard",
+    "Pong",
+];
+const RELAYED_EVENTS: [&str; 2] = ["UserTyping", "UserStoppedTyping"];
+
+pub fn sent_by(event_type: &str) -> SentBy {
+    if CLIENT_EVENTS.contains(&event_type) {
+        SentBy::Client
+    } else if RELAYED_EVENTS.contains(&event_type) {
+        SentBy::Both
+    } else {
+        SentBy::Server
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventSchema {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub sent_by: SentBy,
+    // The whole message, `type` and `data`
+    pub schema: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaRegistry {
+    #[serde(rename = "$schema")]
+    pub meta_schema: Value,
+    pub events: Vec<EventSchema>,
+    // Shared types, referred to as "#/definitions/<name>"
+    pub definitions: Map<String, Value>,
+}
+
+// Generated once, the types don't change at runtime
+pub fn registry() -> &'static SchemaRegistry {
+    static REGISTRY: OnceLock<SchemaRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let root = serde_json::to_value(schema_for!(WebSocketEvent)).expect("schemas are valid JSON");
+        let variants = root.get("oneOf").or_else(|| root.get("anyOf")).and_then(Value::as_array);
+
+        let events = variants
+            .into_iter()
+            .flatten()
+            .map(|schema| {
+                let event_type = schema["properties"]["type"]["enum"][0].as_str().unwrap_or_default().to_string();
+                EventSchema { sent_by: sent_by(&event_type), event_type, schema: schema.clone() }
+            })
+            .collect();
+
+        SchemaRegistry {
+            meta_schema: root["$schema"].clone(),
+            events,
+            definitions: root["definitions"].as_object().cloned().unwrap_or_default(),
+        }
+    })
+}
+
+// One event of every type, in declaration order. Ids are made up except for
+// the project's and the user's, so clients can route them like real events.
+pub fn samples(project_id: Uuid, user: &UserSummary) -> Vec<WebSocketEvent> {
+    let now = Utc::now();
+    let task = Task {
+        id: Uuid::new_v4(),
+        title: "Sample task".to_string(),
+        description: Some("Sent as a test delivery".to_string()),
+        project_id,
+        created_by: user.id,
+        assigned_to: Some(user.id),
+        status: TaskStatus::Todo,
+        priority: TaskPriority::Medium,
+        due_date: Some(now + Duration::days(7)),
+        is_all_day: false,
+        tags: Some(vec!["sample".to_string()]),
+        cover_color: Some("#3B82F6".to_string()),
+        cover_emoji: None,
+        position: 0,
+        number: 1,
+        created_at: now,
+        updated_at: now,
+    };
+    let board = Board {
+        id: Uuid::new_v4(),
+        name: "Sample board".to_string(),
+        description: None,
+        project_id,
+        created_by: user.id,
+        columns: TaskStatus::ALL.iter().map(|status| status.name().to_string()).collect(),
+        config: BoardConfig::default(),
+        swimlane_config: SwimlaneConfig::default(),
+        is_default: false,
+        created_at: now,
+        updated_at: now,
+    };
+    let comment = TaskComment {
+        id: Uuid::new_v4(),
+        task_id: task.id,
+        user_id: user.id,
+        content: "Sample comment".to_string(),
+        is_system: false,
+        pinned: false,
+        created_at: now,
+        updated_at: now,
+    };
+    let notification = |kind, actor_id| UserNotification {
+        id: Uuid::new_v4(),
+        user_id: user.id,
+        kind,
+        project_id: Some(project_id),
+        task_id: Some(task.id),
+        team_id: None,
+        audit_export_id: None,
+        actor_id,
+        count: 1,
+        read_at: None,
+        created_at: now,
+        updated_at: now,
+    };
+    let task_data = || TaskEventData { task: task.clone(), project_id, user: user.clone(), column: None, client_mutation_id: None };
+    let board_data = || BoardEventData { board: board.clone(), project_id, user: user.clone(), client_mutation_id: None };
+    let client_mutation_id = || Some("client-mutation-id".to_string());
+    let presence = || UserPresenceData { user: user.clone(), project_id, timestamp: now };
+    let (team_id, export_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let typing = || TypingEventData { user: user.clone(), task_id: task.id, project_id, timestamp: now };
+
+    vec![
+        WebSocketEvent::Authenticate { token: "access token".to_string() },
+        WebSocketEvent::AuthenticationSuccess { user_id: user.id, connection_id: Uuid::new_v4() },
+        WebSocketEvent::AuthenticationError { message: "Invalid token".to_string() },
+        WebSocketEvent::Subscribe { project_id },
+        WebSocketEvent::SubscribeFiltered(SubscriptionFilter {
+            project_id,
+            assignee: Some(user.id),
+            labels: vec!["sample".to_string()],
+            priorities: vec![TaskPriority::High, TaskPriority::Critical],
+        }),
+        WebSocketEvent::Unsubscribe { project_id },
+        WebSocketEvent::SubscriptionSuccess { project_id },
+        WebSocketEvent::SubscriptionError { message: "Must be a project member".to_string() },
+        WebSocketEvent::TaskCreated(TaskEventData {
+            column: Some(BoardColumn { board_id: board.id, column_id: TaskStatus::Todo }),
+            ..task_data()
+        }),
+        WebSocketEvent::TaskUpdated(TaskEventData { client_mutation_id: client_mutation_id(), ..task_data() }),
+        WebSocketEvent::TaskDeleted { task_id: task.id, project_id, client_mutation_id: client_mutation_id() },
+        WebSocketEvent::TaskMoved(TaskMoveEventData {
+            task_id: task.id,
+            from_status: TaskStatus::Todo,
+            to_status: TaskStatus::InProgress,
+            position: 0,
+            project_id,
+            user: user.clone(),
+            client_mutation_id: client_mutation_id(),
+        }),
+        WebSocketEvent::TasksReordered(TasksReorderedEventData {
+            project_id,
+            columns: vec![ColumnOrder { status: TaskStatus::Todo, task_ids: vec![task.id] }],
+        }),
+        WebSocketEvent::TaskAssignedToYou(task_data()),
+        WebSocketEvent::TaskUnassigned(task_data()),
+        WebSocketEvent::TaskCommentedOn(CommentNotificationData {
+            notification: notification(NotificationKind::TaskCommented, Some(user.id)),
+            task: task.clone(),
+            user: user.clone(),
+        }),
+        WebSocketEvent::TaskWentStale(StaleTaskNotificationData {
+            notification: notification(NotificationKind::TaskStale, None),
+            task: task.clone(),
+        }),
+        WebSocketEvent::MentionedYou(MentionNotificationData {
+            notification: notification(NotificationKind::TaskMentioned, Some(user.id)),
+            task: task.clone(),
+            comment_id: Some(Uuid::new_v4()),
+            group_token: Some("project".to_string()),
+            user: user.clone(),
+        }),
+        WebSocketEvent::InvitedToProject(InvitationNotificationData {
+            invitation_id: Uuid::new_v4(),
+            project_id,
+            project_name: "Sample project".to_string(),
+            role: ProjectRole::Guest,
+            expires_at: now + Duration::days(7),
+            user: user.clone(),
+        }),
+        WebSocketEvent::AuditExportReady(AuditExportNotificationData {
+            notification: UserNotification {
+                project_id: None,
+                task_id: None,
+                team_id: Some(team_id),
+                audit_export_id: Some(export_id),
+                ..notification(NotificationKind::AuditExportReady, None)
+            },
+            export: AuditExport {
+                id: export_id,
+                team_id,
+                requested_by: Some(user.id),
+                format: AuditExportFormat::Csv,
+                from: now - Duration::days(90),
+                to: now,
+                status: AuditExportStatus::Ready,
+                entry_count: Some(25_000),
+                error: None,
+                created_at: now,
+                completed_at: Some(now),
+                storage_path: None,
+            },
+            download_url: format!("/api/teams/{}/audit/exports/{}/download", team_id, export_id),
+        }),
+        WebSocketEvent::MuteNotifications,
+        WebSocketEvent::UnmuteNotifications,
+        WebSocketEvent::ProjectDeleted { project_id },
+        WebSocketEvent::ProjectArchived { project_id },
+        WebSocketEvent::MemberAdded(MemberAddedEventData {
+            project_id,
+            member: user.clone(),
+            role: ProjectRole::Member,
+            onboarding_tasks: Vec::new(),
+            user: user.clone(),
+        }),
+        WebSocketEvent::MemberRemoved { project_id, user_id: user.id, unassigned_task_ids: vec![task.id] },
+        WebSocketEvent::BoardCreated(board_data()),
+        WebSocketEvent::BoardUpdated(BoardEventData { client_mutation_id: client_mutation_id(), ..board_data() }),
+        WebSocketEvent::BoardDeleted { board_id: board.id, project_id, client_mutation_id: client_mutation_id() },
+        WebSocketEvent::ColumnWipStatusChanged(ColumnWipEventData {
+            board_id: board.id,
+            column_id: TaskStatus::InProgress,
+            count: 3,
+            limit: 3,
+            project_id,
+        }),
+        WebSocketEvent::AttachmentScanCompleted(AttachmentScanEventData {
+            attachment: TaskAttachment {
+                id: Uuid::new_v4(),
+                task_id: task.id,
+                uploaded_by: Some(user.id),
+                filename: "sample.pdf".to_string(),
+                content_type: "application/pdf".to_string(),
+                size_bytes: 1024,
+                storage_path: String::new(),
+                content_sha256: None,
+                scan_status: AttachmentScanStatus::Clean,
+                scan_detail: None,
+                scanned_at: Some(now),
+                created_at: now,
+            },
+            project_id,
+        }),
+        WebSocketEvent::CommentCreated(CommentEventData {
+            comment: comment.clone(),
+            task_id: task.id,
+            project_id,
+            user: user.clone(),
+            client_mutation_id: client_mutation_id(),
+        }),
+        WebSocketEvent::CommentDeleted { comment_id: comment.id, task_id: task.id, project_id, client_mutation_id: client_mutation_id() },
+        WebSocketEvent::UserJoined(presence()),
+        WebSocketEvent::UserLeft(presence()),
+        WebSocketEvent::UserTyping(typing()),
+        WebSocketEvent::UserStoppedTyping(typing()),
+        WebSocketEvent::ViewingBoard { board_id: Some(board.id) },
+        WebSocketEvent::BoardViewersChanged(BoardViewersEventData {
+            board_id: board.id,
+            project_id,
+            user: user.clone(),
+            joined: true,
+            viewers: vec![user.id],
+            timestamp: now,
+        }),
+        WebSocketEvent::Announcement(Announcement {
+            id: Uuid::new_v4(),
+            message: "Sample announcement".to_string(),
+            severity: AnnouncementSeverity::Info,
+            starts_at: now,
+            ends_at: None,
+            dismissible: true,
+            created_by: None,
+            created_at: now,
+            updated_at: now,
+        }),
+        WebSocketEvent::Error { message: "Sample error".to_string() },
+        WebSocketEvent::Pong,
+    ]
+}
+
+pub fn sample(event_type: &str, project_id: Uuid, user: &UserSummary) -> Option<WebSocketEvent> {
+    samples(project_id, user).into_iter().find(|event| event.name() == event_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> UserSummary {
+        UserSummary {
+            id: Uuid::new_v4(),
+            username: "sample".to_string(),
+            display_name: "Sample".to_string(),
+            avatar_url: None,
+        }
+    }
+
+    fn resolve<'a>(schema: &'a Value, definitions: &'a Map<String, Value>) -> &'a Value {
+        match schema["$ref"].as_str().and_then(|path| path.strip_prefix("#/definitions/")) {
+            Some(name) => &definitions[name],
+            None => schema,
+        }
+    }
+
+    // Required properties are present, all the way down through objects
+    fn assert_has_required(value: &Value, schema: &Value, definitions: &Map<String, Value>, path: &str) {
+        let schema = resolve(schema, definitions);
+        let Some(object) = value.as_object() else { return };
+        for required in schema["required"].as_array().into_iter().flatten() {
+            let name = required.as_str().unwrap();
+            assert!(object.contains_key(name), "{} lacks {}", path, name);
+        }
+        for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+            if let Some(value) = object.get(name) {
+                assert_has_required(value, property, definitions, &format!("{}.{}", path, name));
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_event_has_a_schema() {
+        let registry = registry();
+        let samples = samples(Uuid::new_v4(), &user());
+        let names: Vec<&str> = samples.iter().map(WebSocketEvent::name).collect();
+        let schema_names: Vec<&str> = registry.events.iter().map(|event| event.event_type.as_str()).collect();
+
+        assert_eq!(schema_names, names);
+        let mut unique = names.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), names.len());
+        assert!(registry.definitions.contains_key("Task"));
+    }
+
+    #[test]
+    fn test_samples_match_their_schemas() {
+        let registry = registry();
+        for (event, schema) in samples(Uuid::new_v4(), &user()).iter().zip(&registry.events) {
+            let json = serde_json::to_value(event).unwrap();
+            assert_eq!(json["type"], schema.event_type);
+            assert_has_required(&json, &schema.schema, &registry.definitions, &schema.event_type);
+        }
+    }
+
+    #[test]
+    fn test_client_events_are_marked() {
+        let registry = registry();
+        let sent_by = |name: &str| registry.events.iter().find(|event| event.event_type == name).unwrap().sent_by;
+        for name in CLIENT_EVENTS {
+            assert_eq!(sent_by(name), SentBy::Client);
+        }
+        assert_eq!(sent_by("UserTyping"), SentBy::Both);
+        assert_eq!(sent_by("TaskCreated"), SentBy::Server);
+    }
+
+    #[test]
+    fn test_unknown_sample() {
+        assert!(sample("TaskCreated", Uuid::new_v4(), &user()).is_some());
+        assert!(sample("TaskExploded", Uuid::new_v4(), &user()).is_none());
+    }
+}
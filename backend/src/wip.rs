@@ -0,0 +1,217 @@
+// Column WIP limits. Board columns group tasks by status, so a limit applies to
+// the project's tasks with that status. Strict boards reject moves into a full
+// column; advisory boards allow them and only report the column status. New
+// tasks count as moves into their column.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::database::{
+    models::{Board, TaskStatus, WipMode},
+    queries::{BoardQueries, TaskQueries},
+};
+use crate::utils::errors::AppError;
+use crate::websocket::events::ColumnWipEventData;
+
+// Column counts taken before a task moves from one status to another, or is
+// created (no from_status)
+#[derive(Debug)]
+pub struct WipCheck {
+    boards: Vec<Board>,
+    from_status: Option<TaskStatus>,
+    to_status: TaskStatus,
+    from_count: i64,
+    to_count: i64,
+}
+
+fn has_limit(board: &Board, from_status: TaskStatus, to_status: TaskStatus) -> bool {
+    board.config.wip_limit(from_status).is_some() || board.config.wip_limit(to_status).is_some()
+}
+
+// Columns are "full" once they reach their limit
+fn crossed_limit(before: i64, after: i64, limit: i32) -> bool {
+    (before >= i64::from(limit)) != (after >= i64::from(limit))
+}
+
+// Counts the affected columns and rejects the move if it would take a column on
+// a strict board over its limit. Moves within a column are never limited.
+pub async fn check_move(
+    pool: &PgPool,
+    project_id: Uuid,
+    from_status: TaskStatus,
+    to_status: TaskStatus,
+) -> Result<WipCheck, AppError> {
+    let mut boards = Vec::new();
+    let mut from_count = 0;
+    let mut to_count = 0;
+
+    if from_status != to_status {
+        boards = BoardQueries::get_project_boards(pool, project_id).await?;
+        boards.retain(|board| has_limit(board, from_status, to_status));
+    }
+    if !boards.is_empty() {
+        from_count = TaskQueries::count_tasks_with_status(pool, project_id, from_status).await?;
+        to_count = TaskQueries::count_tasks_with_status(pool, project_id, to_status).await?;
+    }
+
+    let check = WipCheck { boards, from_status: Some(from_status), to_status, from_count, to_count };
+    check.ensure_allowed()?;
+    Ok(check)
+}
+
+// Counts the column and rejects the new task if it would take the column on a
+// strict board over its limit
+pub async fn check_create(pool: &PgPool, project_id: Uuid, status: TaskStatus) -> Result<WipCheck, AppError> {
+    let check = count_create(pool, project_id, status).await?;
+    check.ensure_allowed()?;
+    Ok(check)
+}
+
+// Like check_create without holding the task to the limits, for tasks that
+// are already in, like held ones being approved
+pub async fn count_create(pool: &PgPool, project_id: Uuid, status: TaskStatus) -> Result<WipCheck, AppError> {
+    let mut boards = BoardQueries::get_project_boards(pool, project_id).await?;
+    boards.retain(|board| board.config.wip_limit(status).is_some());
+
+    let mut to_count = 0;
+    if !boards.is_empty() {
+        to_count = TaskQueries::count_tasks_with_status(pool, project_id, status).await?;
+    }
+
+    Ok(WipCheck { boards, from_status: None, to_status: status, from_count: 0, to_count })
+}
+
+impl WipCheck {
+    fn ensure_allowed(&self) -> Result<(), AppError> {
+        for board in &self.boards {
+            if board.config.wip_mode != WipMode::Strict {
+                continue;
+            }
+            if let Some(limit) = board.config.wip_limit(self.to_status) {
+                if self.to_count + 1 > i64::from(limit) {
+                    return Err(AppError::Conflict(format!(
+                        "Column {:?} on board \"{}\" is at its WIP limit ({}/{})",
+                        self.to_status, board.name, self.to_count, limit
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Columns whose full / not full state changed with the move
+    pub fn changed_columns(&self) -> Vec<ColumnWipEventData> {
+        let mut events = Vec::new();
+        if self.from_status == Some(self.to_status) {
+            return events;
+        }
+
+        for board in &self.boards {
+            let from = self.from_status.map(|status| (status, self.from_count, self.from_count - 1));
+            let to = Some((self.to_status, self.to_count, self.to_count + 1));
+            for (column_id, before, after) in from.into_iter().chain(to) {
+                let Some(limit) = board.config.wip_limit(column_id) else {
+                    continue;
+                };
+                if crossed_limit(before, after, limit) {
+                    events.push(ColumnWipEventData {
+                        board_id: board.id,
+                        column_id,
+                        count: after,
+                        limit,
+                        project_id: board.project_id,
+                    });
+                }
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::{BoardConfig, ColumnWipLimit};
+    use chrono::Utc;
+
+    fn board(wip_mode: WipMode, wip_limits: Vec<ColumnWipLimit>) -> Board {
+        Board {
+            id: Uuid::new_v4(),
+            name: "Main".to_string(),
+            description: None,
+            project_id: Uuid::new_v4(),
+            created_by: Uuid::new_v4(),
+            columns: vec![],
+            config: BoardConfig { wip_mode, wip_limits },
+            swimlane_config: Default::default(),
+            is_default: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn in_progress_limit(limit: i32) -> Vec<ColumnWipLimit> {
+        vec![ColumnWipLimit { column_id: TaskStatus::InProgress, limit }]
+    }
+
+    fn check(board: Board, from_count: i64, to_count: i64) -> WipCheck {
+        WipCheck {
+            boards: vec![board],
+            from_status: Some(TaskStatus::Todo),
+            to_status: TaskStatus::InProgress,
+            from_count,
+            to_count,
+        }
+    }
+
+    #[test]
+    fn test_strict_board_blocks_moves_into_full_column() {
+        assert!(check(board(WipMode::Strict, in_progress_limit(3)), 5, 2).ensure_allowed().is_ok());
+
+        match check(board(WipMode::Strict, in_progress_limit(3)), 5, 3).ensure_allowed() {
+            Err(AppError::Conflict(message)) => assert!(message.ends_with("WIP limit (3/3)")),
+            other => panic!("expected conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_advisory_board_allows_moves_into_full_column() {
+        assert!(check(board(WipMode::Advisory, in_progress_limit(3)), 5, 3).ensure_allowed().is_ok());
+    }
+
+    #[test]
+    fn test_events_only_when_column_reaches_or_leaves_limit() {
+        // 2 -> 3 reaches the limit
+        let events = check(board(WipMode::Strict, in_progress_limit(3)), 5, 2).changed_columns();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].column_id, TaskStatus::InProgress);
+        assert_eq!((events[0].count, events[0].limit), (3, 3));
+
+        // 0 -> 1 stays below, 3 -> 4 stays full
+        assert!(check(board(WipMode::Strict, in_progress_limit(3)), 5, 0).changed_columns().is_empty());
+        assert!(check(board(WipMode::Advisory, in_progress_limit(3)), 5, 3).changed_columns().is_empty());
+
+        // Leaving a full column drops it below its limit
+        let limits = vec![ColumnWipLimit { column_id: TaskStatus::Todo, limit: 2 }];
+        let events = check(board(WipMode::Strict, limits), 2, 0).changed_columns();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].column_id, TaskStatus::Todo);
+        assert_eq!(events[0].count, 1);
+    }
+
+    #[test]
+    fn test_new_tasks_only_fill_their_column() {
+        let created = |to_count| WipCheck {
+            boards: vec![board(WipMode::Strict, in_progress_limit(3))],
+            from_status: None,
+            to_status: TaskStatus::InProgress,
+            from_count: 0,
+            to_count,
+        };
+
+        assert!(created(3).ensure_allowed().is_err());
+        let events = created(2).changed_columns();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].column_id, events[0].count), (TaskStatus::InProgress, 3));
+    }
+}
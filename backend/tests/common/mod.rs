@@ -0,0 +1,194 @@
+// Shared harness for the HTTP/WebSocket integration tests.
+// Requires the test database from DATABASE_TEST_URL (see .env.example).
+#![allow(dead_code)]
+
+use reqwest::{Client, Response};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use simplecards::{auth::jwt::JwtService, build_app, config::{Config, PublicConfig}, database::connection::Database, websocket::handler::WebSocketState, AppState};
+
+pub const TEST_PASSWORD: &str = "Password123!";
+
+pub struct TestApp {
+    pub address: SocketAddr,
+    pub client: Client,
+    pub database: Database,
+    pub config: Config,
+    // Shares connection state with the served app, for asserting on broadcasts
+    pub websocket: WebSocketState,
+}
+
+pub struct TestUser {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+impl TestApp {
+    // Binds the full app to an ephemeral port and serves it in the background
+    pub async fn spawn() -> Self {
+        dotenvy::dotenv().ok();
+
+        let config = Config::test_default();
+        let database = Database::new(&config.database).await.expect("Failed to connect to test database");
+        let jwt_service = JwtService::new(&config.jwt).expect("Failed to initialize JWT service");
+        let public_config = PublicConfig::new(&config);
+        let state = AppState::new(config.clone(), database.clone(), jwt_service, public_config);
+        state.flags.refresh().await.expect("Failed to load feature flags");
+        let websocket = state.websocket.clone();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind ephemeral port");
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let app = build_app(state).into_make_service_with_connect_info::<std::net::SocketAddr>();
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        TestApp {
+            address,
+            client: Client::new(),
+            database,
+            config,
+            websocket,
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.address, path)
+    }
+
+    pub fn ws_url(&self, token: &str) -> String {
+        format!("ws://{}/ws?token={}", self.address, token)
+    }
+
+    pub async fn get(&self, path: &str, token: &str) -> Response {
+        self.client
+            .get(self.url(path))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post(&self, path: &str, token: &str, body: Value) -> Response {
+        self.client
+            .post(self.url(path))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn put(&self, path: &str, token: &str, body: Value) -> Response {
+        self.client
+            .put(self.url(path))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn delete(&self, path: &str, token: &str) -> Response {
+        self.client
+            .delete(self.url(path))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_public(&self, path: &str, body: Value) -> Response {
+        self.client
+            .post(self.url(path))
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    // Registers a user with a unique username/email and returns its tokens
+    pub async fn register_user(&self, prefix: &str) -> TestUser {
+        let suffix = &Uuid::new_v4().simple().to_string()[..12];
+        let username = format!("{}_{}", prefix, suffix);
+        let email = format!("{}@example.com", username);
+
+        let response = self
+            .post_public(
+                "/api/auth/register",
+                json!({
+                    "email": email,
+                    "username": username,
+                    "display_name": prefix,
+                    "password": TEST_PASSWORD,
+                }),
+            )
+            .await;
+        assert_eq!(response.status(), 201, "registering {} failed", username);
+
+        let body: Value = response.json().await.unwrap();
+        TestUser {
+            id: body["user"]["id"].as_str().unwrap().parse().unwrap(),
+            username,
+            email,
+            access_token: body["access_token"].as_str().unwrap().to_string(),
+            refresh_token: body["refresh_token"].as_str().unwrap().to_string(),
+        }
+    }
+
+    pub async fn create_team(&self, owner: &TestUser, name: &str) -> Uuid {
+        let response = self
+            .post("/api/teams", &owner.access_token, json!({ "name": name }))
+            .await;
+        assert_eq!(response.status(), 201);
+
+        let body: Value = response.json().await.unwrap();
+        body["id"].as_str().unwrap().parse().unwrap()
+    }
+
+    pub async fn add_team_member(&self, admin: &TestUser, team_id: Uuid, user: &TestUser, role: &str) {
+        let response = self
+            .post(
+                &format!("/api/teams/{}/members", team_id),
+                &admin.access_token,
+                json!({ "user_id": user.id, "role": role }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+
+    pub async fn create_project(&self, owner: &TestUser, team_id: Uuid, name: &str) -> Uuid {
+        let response = self
+            .post(
+                &format!("/api/teams/{}/projects", team_id),
+                &owner.access_token,
+                json!({ "name": name, "team_id": team_id }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+
+        let body: Value = response.json().await.unwrap();
+        body["id"].as_str().unwrap().parse().unwrap()
+    }
+
+    pub async fn create_task(&self, user: &TestUser, project_id: Uuid, title: &str) -> Value {
+        let response = self
+            .post(
+                &format!("/api/projects/{}/tasks", project_id),
+                &user.access_token,
+                json!({ "title": title }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+
+        response.json().await.unwrap()
+    }
+}
@@ -0,0 +1,6448 @@
+mod common;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{protocol::frame::coding::CloseCode, Message},
+};
+
+use simplecards::websocket::events::WebSocketEvent;
+use simplecards::websocket::limits::{self, FrameRejection};
+use uuid::Uuid;
+
+use common::{TestApp, TestUser, TEST_PASSWORD};
+
+#[tokio::test]
+async fn test_register_login_and_refresh() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("alice").await;
+
+    let response = app
+        .post_public("/api/auth/login", json!({ "email": user.email, "password": TEST_PASSWORD }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["user"]["username"], user.username.as_str());
+    assert!(body["user"].get("password_hash").is_none());
+
+    let response = app
+        .post_public("/api/auth/login", json!({ "email": user.email, "password": "Wrong123!" }))
+        .await;
+    assert_eq!(response.status(), 401);
+
+    let response = app
+        .post_public("/api/auth/refresh", json!({ "refresh_token": user.refresh_token }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let access_token = body["access_token"].as_str().unwrap();
+
+    let response = app.get("/api/users/me", access_token).await;
+    assert_eq!(response.status(), 200);
+
+    // Refresh tokens are not accepted on API routes
+    let response = app.get("/api/users/me", &user.refresh_token).await;
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn test_duplicate_registration_conflicts() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("bob").await;
+
+    let response = app
+        .post_public(
+            "/api/auth/register",
+            json!({
+                "email": user.email,
+                "username": format!("{}_2", user.username),
+                "display_name": "Bob",
+                "password": TEST_PASSWORD,
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), 409);
+}
+
+#[tokio::test]
+async fn test_malformed_body_uses_error_envelope() {
+    let app = TestApp::spawn().await;
+
+    let response = app
+        .client
+        .post(app.url("/api/auth/login"))
+        .header("Content-Type", "application/json")
+        .body(r#"{"email": "#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "VALIDATION_ERROR");
+}
+
+#[tokio::test]
+async fn test_team_membership_permissions() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("admin").await;
+    let member = app.register_user("member").await;
+    let outsider = app.register_user("outsider").await;
+
+    let team_id = app.create_team(&admin, "Platform").await;
+    app.add_team_member(&admin, team_id, &member, "Member").await;
+
+    // Plain members cannot add people
+    let response = app
+        .post(
+            &format!("/api/teams/{}/members", team_id),
+            &member.access_token,
+            json!({ "user_id": outsider.id, "role": "Member" }),
+        )
+        .await;
+    assert_eq!(response.status(), 403);
+
+    // Non-members cannot see the team
+    let response = app.get(&format!("/api/teams/{}", team_id), &outsider.access_token).await;
+    assert_eq!(response.status(), 403);
+
+    let response = app.get(&format!("/api/teams/{}", team_id), &member.access_token).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["members"].as_array().unwrap().len(), 2);
+
+    // Adding the same user twice conflicts
+    let response = app
+        .post(
+            &format!("/api/teams/{}/members", team_id),
+            &admin.access_token,
+            json!({ "user_id": member.id, "role": "Member" }),
+        )
+        .await;
+    assert_eq!(response.status(), 409);
+}
+
+#[tokio::test]
+async fn test_project_and_task_crud() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("owner").await;
+    let outsider = app.register_user("outsider").await;
+
+    let team_id = app.create_team(&owner, "Delivery").await;
+    let project_id = app.create_project(&owner, team_id, "Website").await;
+
+    let response = app.get(&format!("/api/projects/{}", project_id), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+
+    let task = app.create_task(&owner, project_id, "Write landing page").await;
+    let task_id = task["id"].as_str().unwrap();
+    assert_eq!(task["status"], "Todo");
+
+    let response = app
+        .put(&format!("/api/tasks/{}", task_id), &owner.access_token, json!({ "title": "Ship landing page" }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["title"], "Ship landing page");
+
+    let response = app
+        .post(
+            &format!("/api/tasks/{}/move", task_id),
+            &owner.access_token,
+            json!({ "task_id": task_id, "status": "InProgress", "position": 1 }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+
+    let response = app.get(&format!("/api/projects/{}/tasks", project_id), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let tasks: Value = response.json().await.unwrap();
+    assert_eq!(tasks.as_array().unwrap().len(), 1);
+    assert_eq!(tasks[0]["status"], "InProgress");
+
+    // Outsiders can neither read nor create tasks, and can't tell the task exists
+    let response = app.get(&format!("/api/tasks/{}", task_id), &outsider.access_token).await;
+    assert_eq!(response.status(), 404);
+    let response = app
+        .post(&format!("/api/projects/{}/tasks", project_id), &outsider.access_token, json!({ "title": "Spam" }))
+        .await;
+    assert_eq!(response.status(), 403);
+
+    let response = app.delete(&format!("/api/tasks/{}", task_id), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let response = app.get(&format!("/api/tasks/{}", task_id), &owner.access_token).await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_bad_uuid_in_path_is_a_validation_error() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("pathy").await;
+
+    let response = app.get("/api/tasks/not-a-uuid", &user.access_token).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "VALIDATION_ERROR");
+}
+
+async fn next_event<S>(stream: &mut S) -> Value
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("timed out waiting for WebSocket event")
+            .expect("WebSocket closed")
+            .unwrap();
+
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_subscribe_receives_task_events() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("wsowner").await;
+    let team_id = app.create_team(&owner, "Realtime").await;
+    let project_id = app.create_project(&owner, team_id, "Live board").await;
+
+    let (mut socket, _) = connect_async(app.ws_url(&owner.access_token)).await.unwrap();
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "AuthenticationSuccess");
+    assert_eq!(event["data"]["user_id"], owner.id.to_string());
+
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": project_id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "SubscriptionSuccess");
+
+    app.create_task(&owner, project_id, "Realtime task").await;
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskCreated");
+    assert_eq!(event["data"]["task"]["title"], "Realtime task");
+}
+
+#[tokio::test]
+async fn test_websocket_focus_filter() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("wsfocus").await;
+    let member = app.register_user("wsfocusmember").await;
+    let team_id = app.create_team(&owner, "Focused").await;
+    let project_id = app.create_project(&owner, team_id, "Busy board").await;
+    app.add_team_member(&owner, team_id, &member, "Member").await;
+    let response = app
+        .post(&format!("/api/projects/{}/members", project_id), &owner.access_token, json!({ "user_id": member.id, "role": "Editor" }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let mine = app.create_task(&owner, project_id, "Mine").await;
+    let theirs = app.create_task(&owner, project_id, "Theirs").await;
+    let update = |task: &Value, body: Value| {
+        let (app, path, token) = (&app, format!("/api/tasks/{}", task["id"].as_str().unwrap()), member.access_token.clone());
+        async move { assert_eq!(app.put(&path, &token, body).await.status(), 200) }
+    };
+
+    let (mut socket, _) = connect_async(app.ws_url(&owner.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+    let subscribe = json!({ "type": "SubscribeFiltered", "data": { "project_id": project_id, "assignee": owner.id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+
+    // Only the task assigned to the owner comes through
+    update(&theirs, json!({ "assigned_to": member.id })).await;
+    update(&mine, json!({ "assigned_to": owner.id })).await;
+    assert_eq!(next_event(&mut socket).await["type"], "TaskAssignedToYou");
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskUpdated");
+    assert_eq!(event["data"]["task"]["title"], "Mine");
+
+    // Changing the filter doesn't resubscribe, and labels and priorities all have to match
+    let subscribe = json!({
+        "type": "SubscribeFiltered",
+        "data": { "project_id": project_id, "labels": ["bug", "ux"], "priorities": ["High", "Critical"] },
+    });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+    update(&mine, json!({ "tags": ["ux"], "priority": "Low" })).await;
+    update(&theirs, json!({ "tags": ["docs"], "priority": "High" })).await;
+    update(&theirs, json!({ "tags": ["bug"] })).await;
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskUpdated");
+    assert_eq!(event["data"]["task"]["title"], "Theirs");
+    assert_eq!(event["data"]["task"]["tags"], json!(["bug"]));
+
+    // Events without a whole task aren't filtered, and a plain Subscribe clears the filter
+    let task_id = mine["id"].as_str().unwrap().parse().unwrap();
+    app.websocket.broadcast_to_project(project_id, WebSocketEvent::TaskDeleted { task_id, project_id, client_mutation_id: None }, None).await;
+    assert_eq!(next_event(&mut socket).await["type"], "TaskDeleted");
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": project_id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+    update(&theirs, json!({ "priority": "Low" })).await;
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskUpdated");
+    assert_eq!(event["data"]["task"]["priority"], "Low");
+}
+
+#[tokio::test]
+async fn test_websocket_subscriptions_end_when_project_is_deleted() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("wsdelete").await;
+    let team_id = app.create_team(&owner, "Ephemeral").await;
+    let deleted_id = app.create_project(&owner, team_id, "Short lived").await;
+    let other_id = app.create_project(&owner, team_id, "Survivor").await;
+
+    let (mut socket, _) = connect_async(app.ws_url(&owner.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": deleted_id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+
+    let response = app.delete(&format!("/api/projects/{}", deleted_id), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "ProjectDeleted");
+    assert_eq!(event["data"]["project_id"], deleted_id.to_string());
+
+    // Later broadcasts for the deleted project are not delivered: the next event
+    // the client sees is the reply to its next subscription
+    app.websocket
+        .broadcast_to_project(deleted_id, WebSocketEvent::TaskDeleted { task_id: Uuid::new_v4(), project_id: deleted_id, client_mutation_id: None }, None)
+        .await;
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": other_id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "SubscriptionSuccess");
+    assert_eq!(event["data"]["project_id"], other_id.to_string());
+
+    // Deleting the team closes the projects it cascades to
+    let response = app.delete(&format!("/api/teams/{}", team_id), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "ProjectDeleted");
+    assert_eq!(event["data"]["project_id"], other_id.to_string());
+}
+
+async fn next_close_code<S>(stream: &mut S) -> CloseCode
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("timed out waiting for close frame")
+            .expect("WebSocket ended without a close frame")
+            .unwrap();
+
+        if let Message::Close(frame) = message {
+            return frame.expect("close frame without a code").code;
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_rejects_oversized_and_binary_messages() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("wsflood").await;
+
+    let (mut socket, _) = connect_async(app.ws_url(&user.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+    let too_large = app.websocket.metrics.rejections(FrameRejection::TooLarge);
+
+    let oversized = "x".repeat(limits::max_message_size() + 1);
+    socket.send(Message::Text(oversized)).await.unwrap();
+    assert_eq!(next_close_code(&mut socket).await, CloseCode::Size);
+    assert!(app.websocket.metrics.rejections(FrameRejection::TooLarge) > too_large);
+
+    let (mut socket, _) = connect_async(app.ws_url(&user.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+    socket.send(Message::Binary(vec![0x82, 0xa4])).await.unwrap();
+    assert_eq!(next_close_code(&mut socket).await, CloseCode::Unsupported);
+}
+
+#[tokio::test]
+async fn test_websocket_rejects_invalid_token() {
+    let app = TestApp::spawn().await;
+
+    let (mut socket, _) = connect_async(app.ws_url("garbage")).await.unwrap();
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "AuthenticationError");
+}
+
+#[tokio::test]
+async fn test_admin_jobs_requires_instance_admin() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("regular").await;
+
+    let response = app.get("/api/admin/jobs", &user.access_token).await;
+    assert_eq!(response.status(), 403);
+
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), user.id)
+        .await
+        .unwrap();
+
+    let response = app.get("/api/admin/jobs?limit=5", &user.access_token).await;
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_slack_integration_config_and_notifications() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("slackowner").await;
+    let team_id = app.create_team(&owner, "Chatty").await;
+    let project_id = app.create_project(&owner, team_id, "Announcements").await;
+    let path = format!("/api/projects/{}/integrations/slack", project_id);
+
+    let response = app.post(&path, &owner.access_token, json!({ "webhook_url": "http://example.com/hook" })).await;
+    assert_eq!(response.status(), 400);
+
+    let response = app
+        .post(
+            &path,
+            &owner.access_token,
+            json!({
+                "webhook_url": "https://hooks.slack.com/services/T000/B000/SECRET",
+                "events": ["task_completed"]
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["webhook_url"], "https://hooks.slack.com/services/T000/B000/****");
+    assert_eq!(body["events"], json!(["task_completed"]));
+    assert_eq!(body["enabled"], true);
+
+    // task_created is filtered out, so no delivery job is queued
+    app.create_task(&owner, project_id, "Quiet task").await;
+    let pending = simplecards::database::queries::JobQueries::list_jobs(app.database.pool(), None, Some("slack_webhook"), 50)
+        .await
+        .unwrap();
+    assert!(pending.iter().all(|job| job.payload["project_id"] != json!(project_id)));
+
+    // A project's messages queue up in order
+    for title in ["First", "Second"] {
+        let task = app.create_task(&owner, project_id, title).await;
+        let task_path = format!("/api/tasks/{}", task["id"].as_str().unwrap());
+        let response = app.put(&task_path, &owner.access_token, json!({ "status": "Done" })).await;
+        assert_eq!(response.status(), 200);
+    }
+    let slack_jobs = |jobs: Vec<simplecards::database::models::Job>| -> Vec<_> {
+        let mut jobs: Vec<_> = jobs.into_iter().filter(|job| job.payload["project_id"] == json!(project_id)).collect();
+        jobs.sort_by_key(|job| job.sequence);
+        jobs
+    };
+    let queued = slack_jobs(
+        simplecards::database::queries::JobQueries::list_jobs(app.database.pool(), None, Some("slack_webhook"), 50)
+            .await
+            .unwrap(),
+    );
+    let key = format!("slack_webhook:{}", project_id);
+    assert_eq!(queued.len(), 2);
+    assert!(queued.iter().all(|job| job.ordering_key.as_deref() == Some(key.as_str())));
+    assert_eq!(queued.iter().map(|job| job.sequence).collect::<Vec<_>>(), [Some(1), Some(2)]);
+
+    // One that failed for good halts the rest until the integration changes
+    sqlx::query("UPDATE jobs SET status = 'failed' WHERE id = $1")
+        .bind(queued[0].id)
+        .execute(app.database.pool())
+        .await
+        .unwrap();
+
+    // Disabling keeps the config; test messages can still be fired
+    let response = app.post(&path, &owner.access_token, json!({ "enabled": false })).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["enabled"], false);
+    let resumed = slack_jobs(
+        simplecards::database::queries::JobQueries::list_jobs(app.database.pool(), None, Some("slack_webhook"), 50)
+            .await
+            .unwrap(),
+    );
+    assert_eq!(resumed[0].status, simplecards::database::models::JobStatus::Pending);
+    assert_eq!(resumed[0].attempts, 0);
+
+    let response = app.post(&format!("{}/test", path), &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 202);
+    let body: Value = response.json().await.unwrap();
+    let test_job = simplecards::database::queries::JobQueries::list_jobs(app.database.pool(), None, Some("slack_webhook"), 50)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|job| json!(job.id) == body["job_id"])
+        .unwrap();
+    assert_eq!(test_job.ordering_key, None);
+
+    let response = app.delete(&path, &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let response = app.get(&path, &owner.access_token).await;
+    assert_eq!(response.status(), 404);
+}
+
+fn github_signature(secret: &str, body: &[u8]) -> String {
+    use hmac::{Hmac, Mac};
+
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[tokio::test]
+async fn test_github_webhook_links_and_transitions_tasks() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("ghowner").await;
+    let team_id = app.create_team(&owner, "Octo").await;
+    let project_id = app.create_project(&owner, team_id, "Cards").await;
+    let repo = format!("acme/cards-{}", project_id.simple());
+    let secret = "a-very-long-webhook-secret";
+
+    let response = app
+        .post(
+            &format!("/api/projects/{}/integrations/github", project_id),
+            &owner.access_token,
+            json!({ "repo": repo, "webhook_secret": secret }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["task_key"], "SC");
+    assert!(body.get("webhook_secret").is_none());
+
+    let task = app.create_task(&owner, project_id, "Build search").await;
+    let task_id = task["id"].as_str().unwrap();
+    let reference = format!("SC-{}", task["number"]);
+
+    let deliver = |event: &'static str, payload: Value, secret: &str| {
+        let body = serde_json::to_vec(&payload).unwrap();
+        let signature = github_signature(secret, &body);
+        let client = reqwest::Client::new();
+        let url = app.url("/integrations/github/webhook");
+        async move {
+            client
+                .post(url)
+                .header("X-GitHub-Event", event)
+                .header("X-Hub-Signature-256", signature)
+                .header("Content-Type", "application/json")
+                .body(body)
+                .send()
+                .await
+                .unwrap()
+        }
+    };
+
+    let push = json!({
+        "repository": { "full_name": repo },
+        "commits": [{
+            "id": "0123abcd",
+            "message": format!("{} add search index", reference),
+            "url": "https://github.com/acme/cards/commit/0123abcd",
+            "author": { "name": "Mona", "username": "mona" }
+        }]
+    });
+
+    // Wrong secret is rejected
+    let response = deliver("push", push.clone(), "not-the-secret-at-all").await;
+    assert_eq!(response.status(), 401);
+
+    let response = deliver("push", push, secret).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["linked"], 1);
+
+    let pull_request = json!({
+        "action": "opened",
+        "repository": { "full_name": repo },
+        "pull_request": {
+            "number": 7,
+            "title": format!("Search ({})", reference),
+            "body": null,
+            "state": "open",
+            "merged": false,
+            "html_url": "https://github.com/acme/cards/pull/7",
+            "user": { "login": "mona" },
+            "head": { "ref": "feature/search" }
+        }
+    });
+    let response = deliver("pull_request", pull_request, secret).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["transitioned"], 1);
+
+    let response = app.get(&format!("/api/tasks/{}", task_id), &owner.access_token).await;
+    let details: Value = response.json().await.unwrap();
+    assert_eq!(details["status"], "Review");
+    let links = details["links"].as_array().unwrap();
+    assert_eq!(links.len(), 2);
+    assert_eq!(links[0]["kind"], "Commit");
+    assert_eq!(links[1]["kind"], "PullRequest");
+    assert_eq!(links[1]["state"], "open");
+}
+
+#[tokio::test]
+async fn test_inbound_email_creates_tasks() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("inbox").await;
+    let team_id = app.create_team(&owner, "Support").await;
+    let project_id = app.create_project(&owner, team_id, "Helpdesk").await;
+    let secret = "inbound-forwarder-secret";
+    std::env::set_var("INBOUND_EMAIL_SECRET", secret);
+
+    let path = format!("/api/projects/{}/integrations/email", project_id);
+    let response = app.post(&path, &owner.access_token, json!({ "unknown_senders": "inbox_user" })).await;
+    assert_eq!(response.status(), 400);
+
+    let response = app.post(&path, &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 201);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["unknown_senders"], "reject");
+    let address = body["address"].as_str().unwrap().to_string();
+    assert!(address.starts_with("project-"));
+
+    let deliver = |from: &str, subject: &str| {
+        let payload = json!({
+            "recipient": format!("Helpdesk <{}>", address),
+            "from": from,
+            "subject": subject,
+            "text": "It crashes on start",
+            "attachments": [{ "filename": "log.txt", "content_type": "text/plain", "content_base64": "Y3Jhc2g=" }]
+        });
+        let body = serde_json::to_vec(&payload).unwrap();
+        let signature = github_signature(secret, &body);
+        let client = reqwest::Client::new();
+        let url = app.url("/integrations/email/inbound");
+        async move {
+            client
+                .post(url)
+                .header("X-Inbound-Signature", signature)
+                .header("Content-Type", "application/json")
+                .body(body)
+                .send()
+                .await
+                .unwrap()
+        }
+    };
+
+    let response = deliver("stranger@example.com", "Spam").await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "rejected");
+
+    let response = deliver(&format!("Owner <{}>", owner.email), "App crashes").await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "created");
+    assert_eq!(body["attachments"], 1);
+
+    let response = app.get(&format!("/api/tasks/{}", body["task_id"].as_str().unwrap()), &owner.access_token).await;
+    let task: Value = response.json().await.unwrap();
+    assert_eq!(task["title"], "App crashes");
+    assert_eq!(task["description"], "It crashes on start");
+}
+
+#[tokio::test]
+async fn test_digest_preferences_and_preview() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("digest").await;
+    let team_id = app.create_team(&user, "Digesters").await;
+    let project_id = app.create_project(&user, team_id, "Roadmap").await;
+
+    let response = app.get("/api/users/me/digest", &user.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["frequency"], "Off");
+
+    let response = app
+        .put("/api/users/me/digest", &user.access_token, json!({ "timezone": "Nowhere/Special" }))
+        .await;
+    assert_eq!(response.status(), 400);
+
+    let response = app
+        .put(
+            "/api/users/me/digest",
+            &user.access_token,
+            json!({ "frequency": "Weekly", "timezone": "Europe/Berlin" }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["frequency"], "Weekly");
+    assert_eq!(body["timezone"], "Europe/Berlin");
+
+    let due_date = (chrono::Utc::now() + chrono::Duration::days(2)).to_rfc3339();
+    let response = app
+        .post(
+            &format!("/api/projects/{}/tasks", project_id),
+            &user.access_token,
+            json!({ "title": "Prepare launch", "assigned_to": user.id, "due_date": due_date }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let response = app.post("/api/users/me/digest/preview", &user.access_token, json!({})).await;
+    assert_eq!(response.status(), 200);
+    let preview: Value = response.json().await.unwrap();
+    assert_eq!(preview["subject"], "Your weekly SimpleCards digest");
+    assert!(preview["body"].as_str().unwrap().contains("Prepare launch"));
+    assert_eq!(preview["digest"]["due_soon"].as_array().unwrap().len(), 1);
+
+    // Previewing doesn't count as sending
+    let response = app.get("/api/users/me/digest", &user.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert!(body["last_digest_sent_at"].is_null());
+}
+
+#[tokio::test]
+async fn test_team_limits_override_and_usage() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("quota").await;
+    let admin = app.register_user("quotaadmin").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id)
+        .await
+        .unwrap();
+
+    let team_id = app.create_team(&owner, "Limited").await;
+    app.create_project(&owner, team_id, "First").await;
+
+    let limits_path = format!("/api/admin/teams/{}/limits", team_id);
+    let response = app.put(&limits_path, &owner.access_token, json!({ "max_projects": 1 })).await;
+    assert_eq!(response.status(), 403);
+
+    let response = app.put(&limits_path, &admin.access_token, json!({ "max_projects": 0 })).await;
+    assert_eq!(response.status(), 400);
+
+    let response = app.put(&limits_path, &admin.access_token, json!({ "max_projects": 1 })).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["effective"]["max_projects_per_team"], 1);
+
+    let response = app
+        .post(&format!("/api/teams/{}/projects", team_id), &owner.access_token, json!({ "name": "Second", "team_id": team_id }))
+        .await;
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "QUOTA_EXCEEDED");
+    assert!(body["error"]["message"].as_str().unwrap().contains("1 projects"));
+
+    let response = app.get(&format!("/api/teams/{}/usage", team_id), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let usage: Value = response.json().await.unwrap();
+    assert_eq!(usage["projects"], 1);
+    assert_eq!(usage["members"], 1);
+    assert_eq!(usage["limits"]["max_projects_per_team"], 1);
+}
+
+#[tokio::test]
+async fn test_wip_limits_strict_and_advisory() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("wip").await;
+    let team_id = app.create_team(&owner, "Flow").await;
+    let project_id = app.create_project(&owner, team_id, "Kanban").await;
+
+    let response = app
+        .post(
+            &format!("/api/projects/{}/boards", project_id),
+            &owner.access_token,
+            json!({
+                "name": "Limited",
+                "config": { "wip_mode": "Strict", "wip_limits": [{ "column_id": "InProgress", "limit": 1 }] }
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let board: Value = response.json().await.unwrap();
+    let board_path = format!("/api/boards/{}", board["id"].as_str().unwrap());
+
+    let first = app.create_task(&owner, project_id, "First").await;
+    let second = app.create_task(&owner, project_id, "Second").await;
+    let move_to_in_progress = |task: &Value| {
+        let task_id = task["id"].as_str().unwrap().to_string();
+        (format!("/api/tasks/{}/move", task_id), json!({ "task_id": task_id, "status": "InProgress", "position": 0 }))
+    };
+
+    let (path, body) = move_to_in_progress(&first);
+    let response = app.post(&path, &owner.access_token, body).await;
+    assert_eq!(response.status(), 200);
+
+    let (path, body) = move_to_in_progress(&second);
+    let response = app.post(&path, &owner.access_token, body.clone()).await;
+    assert_eq!(response.status(), 409);
+    let error: Value = response.json().await.unwrap();
+    assert!(error["error"]["message"].as_str().unwrap().contains("(1/1)"));
+
+    let response = app
+        .put(&board_path, &owner.access_token, json!({ "config": { "wip_mode": "Advisory", "wip_limits": [{ "column_id": "InProgress", "limit": 1 }] } }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let updated: Value = response.json().await.unwrap();
+    assert_eq!(updated["config"]["wip_mode"], "Advisory");
+
+    let response = app.post(&path, &owner.access_token, body).await;
+    assert_eq!(response.status(), 200);
+
+    let response = app
+        .put(&board_path, &owner.access_token, json!({ "config": { "wip_limits": [{ "column_id": "Review", "limit": 0 }] } }))
+        .await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_board_swimlanes() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("lanes").await;
+    let team_id = app.create_team(&owner, "QA").await;
+    let project_id = app.create_project(&owner, team_id, "Releases").await;
+
+    let response = app
+        .post(&format!("/api/projects/{}/boards", project_id), &owner.access_token, json!({ "name": "QA" }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let board: Value = response.json().await.unwrap();
+    assert_eq!(board["swimlane_config"]["group_by"], "none");
+    let board_path = format!("/api/boards/{}", board["id"].as_str().unwrap());
+
+    let urgent = app.create_task(&owner, project_id, "Crash on login").await;
+    let response = app
+        .put(&format!("/api/tasks/{}", urgent["id"].as_str().unwrap()), &owner.access_token, json!({ "priority": "Critical" }))
+        .await;
+    assert_eq!(response.status(), 200);
+    app.create_task(&owner, project_id, "Typo").await;
+
+    // Milestones can't be grouped on yet
+    let response = app
+        .put(&board_path, &owner.access_token, json!({ "swimlane_config": { "group_by": "milestone" } }))
+        .await;
+    assert_eq!(response.status(), 400);
+
+    let swimlane_config = json!({
+        "group_by": "priority",
+        "lanes": [{ "key": "Critical", "collapsed": false }, { "key": "Low", "collapsed": true }]
+    });
+    let response = app.put(&board_path, &owner.access_token, json!({ "swimlane_config": swimlane_config })).await;
+    assert_eq!(response.status(), 200);
+
+    let response = app.get(&board_path, &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let details: Value = response.json().await.unwrap();
+    assert_eq!(details["swimlane_config"], swimlane_config);
+    let lanes = details["swimlanes"].as_array().unwrap();
+    let keys: Vec<&str> = lanes.iter().map(|lane| lane["key"].as_str().unwrap()).collect();
+    assert_eq!(keys, vec!["Critical", "Low", "Medium"]);
+    assert_eq!(lanes[0]["task_ids"], json!([urgent["id"]]));
+    assert_eq!(lanes[1]["collapsed"], true);
+}
+
+#[tokio::test]
+async fn test_team_export_archive() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("exporter").await;
+    let member = app.register_user("exported").await;
+    let team_id = app.create_team(&owner, "Backups").await;
+    app.add_team_member(&owner, team_id, &member, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Archive").await;
+    let task = app.create_task(&owner, project_id, "Keep me").await;
+    let response = app
+        .post(&format!("/api/tasks/{}/comments", task["id"].as_str().unwrap()), &owner.access_token, json!({ "content": "Saved" }))
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let path = format!("/api/teams/{}/export", team_id);
+    assert_eq!(app.get(&path, &member.access_token).await.status(), 403);
+
+    let response = app.get(&path, &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let archive = response.text().await.unwrap();
+    let lines: Vec<Value> = archive.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+
+    assert_eq!(lines[0]["type"], "header");
+    assert_eq!(lines[0]["data"]["version"], 1);
+    let end = lines.last().unwrap();
+    assert_eq!(end["type"], "end");
+    assert_eq!(end["data"]["users"], 2);
+    assert_eq!(end["data"]["teams"], 1);
+    assert_eq!(end["data"]["tasks"], 1);
+    assert_eq!(end["data"]["task_comments"], 1);
+    // Password hashes stay out of team exports
+    let user = lines.iter().find(|line| line["type"] == "users").unwrap();
+    assert!(user["data"].get("password_hash").is_none());
+
+    // Imports are for instance admins, and only into an instance without teams
+    let import = |token: &str| {
+        let (app, token, archive) = (&app, token.to_string(), archive.clone());
+        async move { post_ndjson(app, "/api/admin/import?dry_run=true", &token, archive).await }
+    };
+    assert_eq!(import(&owner.access_token).await.status(), 403);
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), owner.id)
+        .await
+        .unwrap();
+    let response = import(&owner.access_token).await;
+    assert_eq!(response.status(), 409);
+}
+
+async fn post_ndjson(app: &TestApp, path: &str, token: &str, body: String) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(app.url(path))
+        .bearer_auth(token)
+        .header("Content-Type", "application/x-ndjson")
+        .body(body)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_project_role_matrix() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("projadmin").await;
+    let viewer = app.register_user("viewer").await;
+    let guest = app.register_user("guest").await;
+    let member = app.register_user("projmember").await;
+    let editor = app.register_user("editor").await;
+
+    let team_id = app.create_team(&admin, "Readers").await;
+    let project_id = app.create_project(&admin, team_id, "Docs").await;
+    for (user, role) in [(&viewer, "Viewer"), (&guest, "Guest"), (&member, "Member"), (&editor, "Editor")] {
+        app.add_team_member(&admin, team_id, user, "Member").await;
+        let response = app
+            .post(
+                &format!("/api/projects/{}/members", project_id),
+                &admin.access_token,
+                json!({ "user_id": user.id, "role": role }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+
+    let task = app.create_task(&admin, project_id, "Proofread").await;
+    let task_id = task["id"].as_str().unwrap();
+    let comment = |user: &TestUser| {
+        let (app, path, token) = (&app, format!("/api/tasks/{}/comments", task_id), user.access_token.clone());
+        async move { app.post(&path, &token, json!({ "content": "Looks good" })).await }
+    };
+
+    // Viewers only read
+    let response = app.get(&format!("/api/projects/{}/tasks", project_id), &viewer.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(comment(&viewer).await.status(), 403);
+
+    // Guests can also comment, but not create tasks
+    assert_eq!(comment(&guest).await.status(), 201);
+    let response = app
+        .post(&format!("/api/projects/{}/tasks", project_id), &guest.access_token, json!({ "title": "Nope" }))
+        .await;
+    assert_eq!(response.status(), 403);
+
+    // Members edit their own tasks only
+    let own = app.create_task(&member, project_id, "Index").await;
+    let response = app
+        .put(&format!("/api/tasks/{}", own["id"].as_str().unwrap()), &member.access_token, json!({ "title": "Index pages" }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let response = app
+        .put(&format!("/api/tasks/{}", task_id), &member.access_token, json!({ "title": "Proofread docs" }))
+        .await;
+    assert_eq!(response.status(), 403);
+    let move_task = |user: &TestUser, id: &str| {
+        let (app, path, token) = (&app, format!("/api/tasks/{}/move", id), user.access_token.clone());
+        let body = json!({ "task_id": id, "status": "InProgress", "position": 0 });
+        async move { app.post(&path, &token, body).await }
+    };
+    assert_eq!(move_task(&member, own["id"].as_str().unwrap()).await.status(), 200);
+    assert_eq!(move_task(&member, task_id).await.status(), 403);
+    assert_eq!(move_task(&guest, task_id).await.status(), 403);
+
+    // Boards are edited by editors and admins only
+    let response = app
+        .post(&format!("/api/projects/{}/boards", project_id), &admin.access_token, json!({ "name": "Chapters" }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let board: Value = response.json().await.unwrap();
+    let board_path = format!("/api/boards/{}", board["id"].as_str().unwrap());
+    let response = app.put(&board_path, &member.access_token, json!({ "name": "Parts" })).await;
+    assert_eq!(response.status(), 403);
+    let response = app.put(&board_path, &editor.access_token, json!({ "name": "Parts" })).await;
+    assert_eq!(response.status(), 200);
+
+    // Editors edit any task but don't manage the project
+    let response = app
+        .put(&format!("/api/tasks/{}", task_id), &editor.access_token, json!({ "title": "Proofread docs" }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let response = app.post(&format!("/api/projects/{}/archive", project_id), &editor.access_token, json!({})).await;
+    assert_eq!(response.status(), 403);
+
+    let path = format!("/api/projects/{}/my-permissions", project_id);
+    let response = app.get(&path, &member.access_token).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["role"], "Member");
+    assert_eq!(body["permissions"], json!(["view_project", "comment", "create_tasks", "edit_own_tasks"]));
+
+    let response = app.get(&path, &viewer.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["permissions"], json!(["view_project"]));
+
+    let outsider = app.register_user("outsider").await;
+    assert_eq!(app.get(&path, &outsider.access_token).await.status(), 403);
+
+    // Any team member lists and creates projects in the team, whatever their project roles
+    let response = app.get(&format!("/api/teams/{}/projects", team_id), &viewer.access_token).await;
+    assert_eq!(response.status(), 200);
+    app.create_project(&viewer, team_id, "Viewer notes").await;
+}
+
+#[tokio::test]
+async fn test_leaving_a_project() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("leaveadmin").await;
+    let first = app.register_user("leavefirst").await;
+    let second = app.register_user("leavesecond").await;
+    let team_id = app.create_team(&admin, "Leavers").await;
+    let project_id = app.create_project(&admin, team_id, "Exit").await;
+    let other_id = app.create_project(&admin, team_id, "Elsewhere").await;
+    app.add_team_member(&admin, team_id, &first, "Member").await;
+    app.add_team_member(&admin, team_id, &second, "Member").await;
+    for (project, user) in [(project_id, &first), (project_id, &second), (other_id, &first)] {
+        let response = app
+            .post(
+                &format!("/api/projects/{}/members", project),
+                &admin.access_token,
+                json!({ "user_id": user.id, "role": "Editor" }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+
+    let path = format!("/api/projects/{}/members?role=Editor&per_page=1", project_id);
+    let response = app.get(&path, &first.access_token).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["total"], 2);
+    assert_eq!(body["members"].as_array().unwrap().len(), 1);
+    assert_eq!(body["members"][0]["role"], "Editor");
+
+    let response = app
+        .post(
+            &format!("/api/projects/{}/tasks", project_id),
+            &admin.access_token,
+            json!({ "title": "Hand over", "assigned_to": first.id }),
+        )
+        .await;
+    let task: Value = response.json().await.unwrap();
+    let task_id = task["id"].as_str().unwrap();
+
+    let (mut socket, _) = connect_async(app.ws_url(&first.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": project_id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+
+    let response = app.post(&format!("/api/projects/{}/leave", project_id), &first.access_token, json!({})).await;
+    assert_eq!(response.status(), 204);
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "MemberRemoved");
+    assert_eq!(event["data"]["user_id"], first.id.to_string());
+    assert_eq!(event["data"]["unassigned_task_ids"], json!([task_id]));
+
+    // The subscription is gone: the next event is the reply to another subscribe
+    app.websocket
+        .broadcast_to_project(project_id, WebSocketEvent::TaskDeleted { task_id: Uuid::new_v4(), project_id, client_mutation_id: None }, None)
+        .await;
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": other_id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "SubscriptionSuccess");
+    assert_eq!(event["data"]["project_id"], other_id.to_string());
+
+    let response = app.get(&format!("/api/tasks/{}", task_id), &admin.access_token).await;
+    let task: Value = response.json().await.unwrap();
+    assert_eq!(task["assigned_to"], Value::Null);
+
+    let response = app.get(&format!("/api/projects/{}/members", project_id), &admin.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["total"], 2);
+
+    // The last admin has to hand over first
+    let response = app.post(&format!("/api/projects/{}/leave", project_id), &admin.access_token, json!({})).await;
+    assert_eq!(response.status(), 400);
+    let response = app.post(&format!("/api/projects/{}/leave", project_id), &second.access_token, json!({})).await;
+    assert_eq!(response.status(), 204);
+}
+
+#[tokio::test]
+async fn test_inaccessible_resources_look_missing() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("hider").await;
+    let outsider = app.register_user("prober").await;
+    let team_id = app.create_team(&owner, "Private").await;
+    let project_id = app.create_project(&owner, team_id, "Secret").await;
+
+    let task = app.create_task(&owner, project_id, "Hidden").await;
+    let task_id = task["id"].as_str().unwrap().to_string();
+    let response = app
+        .post(&format!("/api/projects/{}/boards", project_id), &owner.access_token, json!({ "name": "Hidden" }))
+        .await;
+    let board: Value = response.json().await.unwrap();
+    let board_id = board["id"].as_str().unwrap().to_string();
+    let response = app
+        .post(&format!("/api/tasks/{}/comments", task_id), &owner.access_token, json!({ "content": "Hidden" }))
+        .await;
+    let comment: Value = response.json().await.unwrap();
+    let comment_id = comment["id"].as_str().unwrap().to_string();
+
+    let missing = Uuid::new_v4().to_string();
+    let probes = |id: &str| -> Vec<(&'static str, String)> {
+        vec![
+            ("GET", format!("/api/tasks/{}", id)),
+            ("PUT", format!("/api/tasks/{}", id)),
+            ("DELETE", format!("/api/tasks/{}", id)),
+            ("GET", format!("/api/tasks/{}/comments", id)),
+        ]
+    };
+    let mut cases: Vec<_> = probes(&task_id).into_iter().zip(probes(&missing)).collect();
+    cases.push((("GET", format!("/api/boards/{}", board_id)), ("GET", format!("/api/boards/{}", missing))));
+    cases.push((("DELETE", format!("/api/comments/{}", comment_id)), ("DELETE", format!("/api/comments/{}", missing))));
+
+    for ((method, existing_path), (_, missing_path)) in cases {
+        let mut results = Vec::new();
+        for path in [&existing_path, &missing_path] {
+            let response = match method {
+                "GET" => app.get(path, &outsider.access_token).await,
+                "PUT" => app.put(path, &outsider.access_token, json!({ "title": "Probe" })).await,
+                _ => app.delete(path, &outsider.access_token).await,
+            };
+            let status = response.status();
+            let body: Value = response.json().await.unwrap();
+            results.push((status, body));
+        }
+        assert_eq!(results[0].0, 404, "{} {}", method, existing_path);
+        assert_eq!(results[0], results[1], "{} {}", method, existing_path);
+    }
+
+    // Nothing was touched
+    let response = app.get(&format!("/api/tasks/{}", task_id), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_assigned_tasks_sort_by_priority() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("sorter").await;
+    let team_id = app.create_team(&owner, "Sorting").await;
+    let project_id = app.create_project(&owner, team_id, "Triage").await;
+
+    for priority in ["Medium", "Critical", "Low", "High"] {
+        let response = app
+            .post(
+                &format!("/api/projects/{}/tasks", project_id),
+                &owner.access_token,
+                json!({ "title": priority, "priority": priority, "assigned_to": owner.id }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+
+    let titles = |body: Value| -> Vec<String> {
+        body.as_array().unwrap().iter().map(|task| task["title"].as_str().unwrap().to_string()).collect()
+    };
+
+    let response = app.get("/api/tasks?sort=priority", &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(titles(response.json().await.unwrap()), vec!["Critical", "High", "Medium", "Low"]);
+
+    let response = app.get("/api/tasks?sort=priority&order=asc", &owner.access_token).await;
+    assert_eq!(titles(response.json().await.unwrap()), vec!["Low", "Medium", "High", "Critical"]);
+
+    // Without due dates the default order falls back to priority
+    let response = app.get("/api/tasks", &owner.access_token).await;
+    assert_eq!(titles(response.json().await.unwrap()), vec!["Critical", "High", "Medium", "Low"]);
+
+    let response = app.get("/api/tasks?sort=title", &owner.access_token).await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_project_colors_are_normalized() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("painter").await;
+    let team_id = app.create_team(&owner, "Palette").await;
+
+    let response = app.client.get(app.url("/api/meta/palette")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let palette: Value = response.json().await.unwrap();
+    assert!(palette.as_array().unwrap().contains(&json!({ "key": "blue", "hex": "#3B82F6", "contrast_text": "dark" })));
+
+    let response = app
+        .post(
+            &format!("/api/teams/{}/projects", team_id),
+            &owner.access_token,
+            json!({ "name": "Night", "team_id": team_id, "color": "#123", "icon": "🌙" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let project: Value = response.json().await.unwrap();
+    assert_eq!(project["color"], "#112233");
+    assert_eq!(project["contrast_text"], "light");
+    assert_eq!(project["icon"], "🌙");
+
+    let path = format!("/api/projects/{}", project["id"].as_str().unwrap());
+    let response = app.put(&path, &owner.access_token, json!({ "name": "Day", "team_id": team_id, "color": "Yellow" })).await;
+    assert_eq!(response.status(), 200);
+    let project: Value = response.json().await.unwrap();
+    assert_eq!(project["color"], "#EAB308");
+    assert_eq!(project["contrast_text"], "dark");
+
+    let response = app.put(&path, &owner.access_token, json!({ "name": "Day", "team_id": team_id, "color": "#12" })).await;
+    assert_eq!(response.status(), 400);
+    let response = app.put(&path, &owner.access_token, json!({ "name": "Day", "team_id": team_id, "icon": "sun" })).await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_null_clears_and_absent_keeps_fields() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("clearer").await;
+    let team_id = app.create_team(&owner, "Cleanup").await;
+    let project_id = app.create_project(&owner, team_id, "Tidy").await;
+
+    let response = app
+        .post(
+            &format!("/api/projects/{}/tasks", project_id),
+            &owner.access_token,
+            json!({
+                "title": "Sweep",
+                "description": "Every room",
+                "assigned_to": owner.id,
+                "due_date": "2030-01-01T00:00:00Z",
+                "tags": ["chores"],
+            }),
+        )
+        .await;
+    let task: Value = response.json().await.unwrap();
+    let task_path = format!("/api/tasks/{}", task["id"].as_str().unwrap());
+
+    // Each field on its own: leaving it out keeps it, null clears it
+    for field in ["description", "assigned_to", "due_date", "tags"] {
+        let response = app.put(&task_path, &owner.access_token, json!({ "title": "Sweep up" })).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body[field], task[field], "{} was not kept", field);
+
+        let response = app.put(&task_path, &owner.access_token, json!({ field: null })).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body[field], Value::Null, "{} was not cleared", field);
+        assert_eq!(body["title"], "Sweep up");
+    }
+
+    // Cleared tags are stored as NULL and read back as no tags
+    let response = app.get(&task_path, &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["tags"], Value::Null);
+    let response = app.get(&format!("/api/projects/{}/tasks", project_id), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let tasks: Vec<Value> = response.json().await.unwrap();
+    assert_eq!(tasks[0]["tags"], Value::Null);
+
+    let response = app
+        .post("/api/users/me", &owner.access_token, json!({ "avatar_url": "https://example.com/me.png" }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let response = app.post("/api/users/me", &owner.access_token, json!({ "display_name": "Clearer" })).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["avatar_url"], "https://example.com/me.png");
+    let response = app.post("/api/users/me", &owner.access_token, json!({ "avatar_url": null })).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["avatar_url"], Value::Null);
+    assert_eq!(body["display_name"], "Clearer");
+
+    let response = app
+        .post(
+            &format!("/api/projects/{}/boards", project_id),
+            &owner.access_token,
+            json!({ "name": "Rooms", "description": "One column per room" }),
+        )
+        .await;
+    let board: Value = response.json().await.unwrap();
+    let board_path = format!("/api/boards/{}", board["id"].as_str().unwrap());
+    let response = app.put(&board_path, &owner.access_token, json!({ "name": "Floors" })).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["description"], "One column per room");
+    let response = app.put(&board_path, &owner.access_token, json!({ "description": null })).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["description"], Value::Null);
+    assert_eq!(body["name"], "Floors");
+}
+
+#[tokio::test]
+async fn test_task_cover_color_and_emoji() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("decorator").await;
+    let team_id = app.create_team(&owner, "Covers").await;
+    let project_id = app.create_project(&owner, team_id, "Mood board").await;
+
+    let response = app
+        .post(
+            &format!("/api/projects/{}/tasks", project_id),
+            &owner.access_token,
+            json!({ "title": "Launch", "cover_color": "#FF8800", "cover_emoji": "🚀" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let task: Value = response.json().await.unwrap();
+    let task_id = task["id"].as_str().unwrap();
+    assert_eq!(task["cover_color"], "#FF8800");
+    assert_eq!(task["cover_emoji"], "🚀");
+
+    let response = app.get(&format!("/api/projects/{}/tasks", project_id), &owner.access_token).await;
+    let tasks: Value = response.json().await.unwrap();
+    assert_eq!(tasks[0]["cover_emoji"], "🚀");
+
+    let response = app
+        .put(
+            &format!("/api/tasks/{}", task_id),
+            &owner.access_token,
+            json!({ "cover_color": "sunset", "cover_emoji": "launch" }),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    let fields: Vec<&str> = body["error"]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, vec!["cover_color", "cover_emoji"]);
+
+    // Leaving the color out keeps it, an empty string clears the emoji
+    let response = app
+        .put(&format!("/api/tasks/{}", task_id), &owner.access_token, json!({ "cover_emoji": "" }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let task: Value = response.json().await.unwrap();
+    assert_eq!(task["cover_color"], "#FF8800");
+    assert_eq!(task["cover_emoji"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_task_positions_stay_dense_per_column() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("positioner").await;
+    let member = app.register_user("shuffler").await;
+    let team_id = app.create_team(&owner, "Ordering").await;
+    app.add_team_member(&owner, team_id, &member, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Columns").await;
+
+    let mut ids = Vec::new();
+    for title in ["First", "Second", "Third", "Fourth"] {
+        let task = app.create_task(&owner, project_id, title).await;
+        assert_eq!(task["position"], ids.len() + 1);
+        ids.push(task["id"].as_str().unwrap().to_string());
+    }
+
+    let column = |status: &'static str| {
+        let path = format!("/api/projects/{}/tasks", project_id);
+        let token = owner.access_token.clone();
+        let app = &app;
+        async move {
+            let response = app.get(&path, &token).await;
+            let tasks: Value = response.json().await.unwrap();
+            let mut tasks: Vec<(i64, String)> = tasks
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|task| task["status"] == status)
+                .map(|task| (task["position"].as_i64().unwrap(), task["id"].as_str().unwrap().to_string()))
+                .collect();
+            tasks.sort();
+            tasks
+        }
+    };
+    let move_task = |id: &str, status: &str, position: i32| {
+        let (app, path, token) = (&app, format!("/api/tasks/{}/move", id), owner.access_token.clone());
+        let body = json!({ "task_id": id, "status": status, "position": position });
+        async move { app.post(&path, &token, body).await }
+    };
+
+    // Moving within a column shifts its neighbours
+    let response = move_task(&ids[3], "Todo", 1).await;
+    assert_eq!(response.status(), 200);
+    let expected: Vec<(i64, String)> = [3, 0, 1, 2].iter().enumerate().map(|(i, &t)| (i as i64 + 1, ids[t].clone())).collect();
+    assert_eq!(column("Todo").await, expected);
+
+    // Positions past the end are clamped, and the old column closes its gap
+    let response = move_task(&ids[0], "InProgress", 99).await;
+    let moved: Value = response.json().await.unwrap();
+    assert_eq!(moved["position"], 1);
+    let todo = column("Todo").await;
+    assert_eq!(todo.iter().map(|(position, _)| *position).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+    // Deletes leave a gap until positions are normalized
+    let response = app.delete(&format!("/api/tasks/{}", todo[1].1), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let before = app.get(&format!("/api/tasks/{}", todo[2].1), &owner.access_token).await;
+    let before: Value = before.json().await.unwrap();
+
+    let path = format!("/api/projects/{}/tasks/normalize-positions", project_id);
+    assert_eq!(app.post(&path, &member.access_token, json!({})).await.status(), 403);
+    let response = app.post(&path, &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["updated"], 1);
+    assert_eq!(body["columns"][0], json!({ "status": "Todo", "task_ids": [todo[0].1, todo[2].1] }));
+    assert_eq!(column("Todo").await, vec![(1, todo[0].1.clone()), (2, todo[2].1.clone())]);
+
+    // Renumbering doesn't count as an edit
+    let after = app.get(&format!("/api/tasks/{}", todo[2].1), &owner.access_token).await;
+    let after: Value = after.json().await.unwrap();
+    assert_eq!(after["updated_at"], before["updated_at"]);
+}
+
+#[tokio::test]
+async fn test_task_counts_follow_list_filters() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("counter").await;
+    let team_id = app.create_team(&owner, "Counting").await;
+    let project_id = app.create_project(&owner, team_id, "Headers").await;
+
+    for (title, tags) in [("One", json!(["bug"])), ("Two", json!(["bug", "ui"])), ("Three", json!([]))] {
+        let response = app
+            .post(&format!("/api/projects/{}/tasks", project_id), &owner.access_token, json!({ "title": title, "tags": tags }))
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+
+    let counts_path = format!("/api/projects/{}/tasks/counts", project_id);
+    let response = app.get(&counts_path, &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["group_by"], "status");
+    assert_eq!(
+        body["counts"],
+        json!([
+            { "key": "Todo", "count": 3 },
+            { "key": "InProgress", "count": 0 },
+            { "key": "Review", "count": 0 },
+            { "key": "Done", "count": 0 }
+        ])
+    );
+
+    let response = app.get(&format!("{}?tag=bug", counts_path), &owner.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["counts"][0]["count"], 2);
+
+    let response = app.get(&format!("{}?group_by=label", counts_path), &owner.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["counts"],
+        json!([
+            { "key": "bug", "count": 2 },
+            { "key": "ui", "count": 1 },
+            { "key": null, "count": 1 }
+        ])
+    );
+
+    let response = app.get(&format!("{}?group_by=assignee", counts_path), &owner.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["counts"], json!([{ "key": null, "count": 3 }]));
+
+    let response = app.get(&format!("{}?group_by=epic", counts_path), &owner.access_token).await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_assignment_notifications() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("assigner").await;
+    let assignee = app.register_user("assignee").await;
+    let team_id = app.create_team(&owner, "Assignments").await;
+    app.add_team_member(&owner, team_id, &assignee, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Handover").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", project_id),
+            &owner.access_token,
+            json!({ "user_id": assignee.id, "role": "Member" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let (mut socket, _) = connect_async(app.ws_url(&assignee.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+
+    let task = app.create_task(&owner, project_id, "Review contract").await;
+    let task_path = format!("/api/tasks/{}", task["id"].as_str().unwrap());
+    let response = app.put(&task_path, &owner.access_token, json!({ "assigned_to": assignee.id })).await;
+    assert_eq!(response.status(), 200);
+
+    // Delivered without a project subscription
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskAssignedToYou");
+    assert_eq!(event["data"]["task"]["title"], "Review contract");
+    assert_eq!(event["data"]["user"]["id"], owner.id.to_string());
+
+    // Taking the task back notifies the previous assignee; the owner assigned
+    // it to themselves, so they get nothing
+    let response = app.put(&task_path, &owner.access_token, json!({ "assigned_to": owner.id })).await;
+    assert_eq!(response.status(), 200);
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskUnassigned");
+
+    let response = app.get("/api/users/me/notifications", &assignee.access_token).await;
+    assert_eq!(response.status(), 200);
+    let notifications: Value = response.json().await.unwrap();
+    let kinds: Vec<&str> = notifications.as_array().unwrap().iter().map(|n| n["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, vec!["TaskUnassigned", "TaskAssigned"]);
+
+    let response = app.get("/api/users/me/notifications", &owner.access_token).await;
+    let notifications: Value = response.json().await.unwrap();
+    assert!(notifications.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_task_read_tracking() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("writer").await;
+    let reader = app.register_user("reader").await;
+    let team_id = app.create_team(&owner, "Reading").await;
+    app.add_team_member(&owner, team_id, &reader, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Inbox").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", project_id),
+            &owner.access_token,
+            json!({ "user_id": reader.id, "role": "Member" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let first = app.create_task(&owner, project_id, "First").await;
+    app.create_task(&owner, project_id, "Second").await;
+    let first_id = first["id"].as_str().unwrap();
+
+    let tasks_path = format!("/api/projects/{}/tasks", project_id);
+
+    // Creators have read their own tasks, everyone else hasn't
+    let response = app.get(&format!("{}?unread=true", tasks_path), &owner.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 0);
+    let response = app.get(&format!("{}?unread=true", tasks_path), &reader.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 2);
+
+    let response = app.post(&format!("/api/tasks/{}/read", first_id), &reader.access_token, json!({})).await;
+    assert_eq!(response.status(), 204);
+    let response = app.get(&format!("{}?unread=true&fields=id,unread", tasks_path), &reader.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_ne!(body[0]["id"], first["id"]);
+    assert_eq!(body[0]["unread"], true);
+
+    // Comments from others make a read task unread again
+    let response = app
+        .post(&format!("/api/tasks/{}/comments", first_id), &owner.access_token, json!({ "content": "Updated the spec" }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let response = app.get(&format!("{}?fields=id,unread,unread_comment_count", tasks_path), &reader.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    let entry = body.as_array().unwrap().iter().find(|task| task["id"] == first["id"]).unwrap();
+    assert_eq!(entry["unread"], true);
+    assert_eq!(entry["unread_comment_count"], 1);
+
+    let response = app.post(&format!("/api/projects/{}/read", project_id), &reader.access_token, json!({})).await;
+    assert_eq!(response.status(), 204);
+    let response = app.get(&format!("{}?unread=true", tasks_path), &reader.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 0);
+
+    let response = app.get(&format!("{}/counts?unread=false", tasks_path), &reader.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["counts"][0]["count"], 2);
+}
+
+#[tokio::test]
+async fn test_task_validation_reports_every_field() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("validator").await;
+    let team_id = app.create_team(&owner, "Validation").await;
+    let project_id = app.create_project(&owner, team_id, "Rules").await;
+    let tasks_path = format!("/api/projects/{}/tasks", project_id);
+
+    let tags: Vec<String> = (0..21).map(|i| format!("tag{}", i)).collect();
+    let response = app
+        .post(&tasks_path, &owner.access_token, json!({ "title": "x", "due_date": "1970-01-01T00:00:00Z", "tags": tags }))
+        .await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "VALIDATION_ERROR");
+    let fields: Vec<&str> = body["error"]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, vec!["title", "due_date", "tags"]);
+
+    let response = app
+        .post(&tasks_path, &owner.access_token, json!({ "title": "Tagged", "tags": [" Bug", "bug ", "UI"] }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let task: Value = response.json().await.unwrap();
+    assert_eq!(task["tags"], json!(["Bug", "UI"]));
+    let task_id = task["id"].as_str().unwrap();
+
+    // Done tasks can only be re-opened to Todo
+    let workflow = json!({ "transitions": [{ "from": "Done", "to": ["Todo"] }] });
+    let response = app.put(&format!("/api/projects/{}/workflow", project_id), &owner.access_token, workflow).await;
+    assert_eq!(response.status(), 200);
+
+    let task_path = format!("/api/tasks/{}", task_id);
+    let response = app.put(&task_path, &owner.access_token, json!({ "status": "Done" })).await;
+    assert_eq!(response.status(), 200);
+    let response = app.put(&task_path, &owner.access_token, json!({ "status": "InProgress" })).await;
+    assert_eq!(response.status(), 400);
+    let response = app
+        .post(&format!("{}/move", task_path), &owner.access_token, json!({ "task_id": task_id, "status": "Review", "position": 0 }))
+        .await;
+    assert_eq!(response.status(), 400);
+    let response = app.put(&task_path, &owner.access_token, json!({ "status": "Todo" })).await;
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_websocket_events_echo_client_mutation_ids() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("wstabs").await;
+    let colleague = app.register_user("wscolleague").await;
+    let team_id = app.create_team(&owner, "Tabs").await;
+    let project_id = app.create_project(&owner, team_id, "Optimistic").await;
+    app.add_team_member(&owner, team_id, &colleague, "Member").await;
+    let response = app
+        .post(&format!("/api/projects/{}/members", project_id), &owner.access_token, json!({ "user_id": colleague.id, "role": "Editor" }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let task = app.create_task(&owner, project_id, "Shared").await;
+    let task_path = format!("/api/tasks/{}", task["id"].as_str().unwrap());
+
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": project_id } }).to_string();
+    let mut sockets = Vec::new();
+    let mut connection_ids = Vec::new();
+    for token in [&colleague.access_token, &owner.access_token, &owner.access_token] {
+        let (mut socket, _) = connect_async(app.ws_url(token)).await.unwrap();
+        let event = next_event(&mut socket).await;
+        assert_eq!(event["type"], "AuthenticationSuccess");
+        connection_ids.push(event["data"]["connection_id"].as_str().unwrap().to_string());
+        socket.send(Message::Text(subscribe.clone())).await.unwrap();
+        assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+        sockets.push(socket);
+    }
+    let [colleague_socket, first_tab, second_tab] = &mut sockets[..] else { unreachable!() };
+    // The owner's second tab doesn't join again
+    assert_eq!(next_event(colleague_socket).await["type"], "UserJoined");
+
+    let send = |method: reqwest::Method, path: &str, token: &str, headers: Vec<(&'static str, String)>, body: Value| {
+        let mut request = app.client.request(method, app.url(path)).bearer_auth(token).json(&body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        request.send()
+    };
+    let from_first_tab = |mutation_id: &str| {
+        vec![("X-Connection-Id", connection_ids[1].clone()), ("X-Client-Mutation-Id", mutation_id.to_string())]
+    };
+
+    // Everyone but the tab the change came from hears of it
+    let response = send(reqwest::Method::PUT, &task_path, &owner.access_token, from_first_tab("m-1"), json!({ "title": "Renamed" }))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    for socket in [&mut *second_tab, &mut *colleague_socket] {
+        let event = next_event(socket).await;
+        assert_eq!(event["type"], "TaskUpdated");
+        assert_eq!(event["data"]["client_mutation_id"], "m-1");
+    }
+
+    // The body's id wins over the header's
+    let comments_path = format!("{}/comments", task_path);
+    let body = json!({ "content": "On it", "client_mutation_id": "m-2" });
+    let response = send(reqwest::Method::POST, &comments_path, &owner.access_token, from_first_tab("h-2"), body).await.unwrap();
+    assert_eq!(response.status(), 201);
+    let event = next_event(second_tab).await;
+    assert_eq!(event["type"], "CommentCreated");
+    assert_eq!(event["data"]["client_mutation_id"], "m-2");
+    assert_eq!(next_event(colleague_socket).await["data"]["client_mutation_id"], "m-2");
+
+    // Another user's connection id is ignored, so the first tab gets this one
+    let headers = vec![("X-Connection-Id", connection_ids[1].clone())];
+    let response = send(reqwest::Method::PUT, &task_path, &colleague.access_token, headers, json!({ "priority": "High" })).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let event = next_event(first_tab).await;
+    assert_eq!(event["type"], "TaskUpdated");
+    assert_eq!(event["data"]["task"]["priority"], "High");
+    assert!(event["data"].get("client_mutation_id").is_none());
+
+    let headers = vec![("X-Client-Mutation-Id", "x".repeat(200))];
+    let response = send(reqwest::Method::DELETE, &task_path, &owner.access_token, headers, json!({})).await.unwrap();
+    assert_eq!(response.status(), 400);
+    let headers = vec![("X-Connection-Id", "not-a-connection".to_string())];
+    let response = send(reqwest::Method::DELETE, &task_path, &owner.access_token, headers, json!({})).await.unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_websocket_notifications_without_subscriptions() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("notifyowner").await;
+    let assignee = app.register_user("notifyassignee").await;
+    let invitee = app.register_user("notifyinvitee").await;
+    let team_id = app.create_team(&owner, "Notify").await;
+    let project_id = app.create_project(&owner, team_id, "Badges").await;
+    app.add_team_member(&owner, team_id, &assignee, "Member").await;
+    let response = app
+        .post(&format!("/api/projects/{}/members", project_id), &owner.access_token, json!({ "user_id": assignee.id, "role": "Editor" }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let tasks_path = format!("/api/projects/{}/tasks", project_id);
+
+    // Never subscribes to anything
+    let (mut socket, _) = connect_async(app.ws_url(&assignee.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+
+    let response = app.post(&tasks_path, &owner.access_token, json!({ "title": "Yours", "assigned_to": assignee.id })).await;
+    assert_eq!(response.status(), 201);
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskAssignedToYou");
+    assert_eq!(event["data"]["task"]["title"], "Yours");
+
+    // Muted connections still get project events, only notifications pause.
+    // The subscription's answer shows the mute was handled.
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": project_id } }).to_string();
+    socket.send(Message::Text(json!({ "type": "MuteNotifications" }).to_string())).await.unwrap();
+    socket.send(Message::Text(subscribe.clone())).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+    let response = app.post(&tasks_path, &owner.access_token, json!({ "title": "Quietly", "assigned_to": assignee.id })).await;
+    assert_eq!(response.status(), 201);
+    assert_eq!(next_event(&mut socket).await["type"], "TaskCreated");
+
+    socket.send(Message::Text(json!({ "type": "UnmuteNotifications" }).to_string())).await.unwrap();
+    socket.send(Message::Text(subscribe)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+    let response = app.post(&tasks_path, &owner.access_token, json!({ "title": "Loudly", "assigned_to": assignee.id })).await;
+    assert_eq!(response.status(), 201);
+    let mut types = vec![next_event(&mut socket).await, next_event(&mut socket).await]
+        .into_iter()
+        .map(|event| (event["type"].as_str().unwrap().to_string(), event["data"]["task"]["title"].clone()))
+        .collect::<Vec<_>>();
+    types.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(types, [("TaskAssignedToYou".to_string(), json!("Loudly")), ("TaskCreated".to_string(), json!("Loudly"))]);
+
+    // Invitations reach accounts with the invited address, without the token
+    let (mut invitee_socket, _) = connect_async(app.ws_url(&invitee.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut invitee_socket).await["type"], "AuthenticationSuccess");
+    let response = app
+        .post(&format!("/api/projects/{}/invitations", project_id), &owner.access_token, json!({ "email": invitee.email }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let event = next_event(&mut invitee_socket).await;
+    assert_eq!(event["type"], "InvitedToProject");
+    assert_eq!(event["data"]["project_name"], "Badges");
+    assert_eq!(event["data"]["role"], "Guest");
+    assert_eq!(event["data"]["user"]["id"], json!(owner.id));
+    assert!(event["data"].get("token").is_none());
+}
+
+#[tokio::test]
+async fn test_error_messages_follow_accept_language() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("polyglot").await;
+    let team_id = app.create_team(&owner, "Languages").await;
+    let project_id = app.create_project(&owner, team_id, "Babel").await;
+
+    let create_task = |language: &'static str| {
+        app.client
+            .post(app.url(&format!("/api/projects/{}/tasks", project_id)))
+            .bearer_auth(&owner.access_token)
+            .header("Accept-Language", language)
+            .json(&json!({ "title": "x" }))
+            .send()
+    };
+
+    let mut bodies = Vec::new();
+    for language in ["en-US,en;q=0.9", "de-DE,de;q=0.9,en;q=0.8"] {
+        let response = create_task(language).await.unwrap();
+        assert_eq!(response.status(), 400);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "VALIDATION_ERROR");
+        assert_eq!(body["error"]["fields"][0]["reason"], json!({ "code": "too_short", "field": "task_title", "min": 2 }));
+        bodies.push(body);
+    }
+
+    assert_eq!(bodies[0]["error"]["fields"][0]["message"], "Task title must be at least 2 characters");
+    assert_eq!(bodies[1]["error"]["fields"][0]["message"], "Aufgabentitel muss mindestens 2 Zeichen lang sein");
+
+    // Unsupported languages fall back to English
+    let response = create_task("fr-FR").await.unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["message"], bodies[0]["error"]["message"]);
+}
+
+#[tokio::test]
+async fn test_archived_projects_are_read_only() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("archivist").await;
+    let team_id = app.create_team(&owner, "Archive").await;
+    let project_id = app.create_project(&owner, team_id, "Frozen").await;
+    let task = app.create_task(&owner, project_id, "Old work").await;
+    let task_path = format!("/api/tasks/{}", task["id"].as_str().unwrap());
+
+    let response = app.post(&format!("/api/projects/{}/archive", project_id), &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 204);
+
+    let response = app.put(&task_path, &owner.access_token, json!({ "title": "New title" })).await;
+    assert_eq!(response.status(), 409);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "PROJECT_ARCHIVED");
+    let response = app
+        .post(&format!("/api/projects/{}/tasks", project_id), &owner.access_token, json!({ "title": "Another" }))
+        .await;
+    assert_eq!(response.status(), 409);
+    let response = app.post(&format!("{}/comments", task_path), &owner.access_token, json!({ "content": "Hello" })).await;
+    assert_eq!(response.status(), 409);
+
+    // Reads still work and archived projects can be found again
+    let response = app.get(&task_path, &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let response = app.get(&format!("/api/projects/{}", project_id), &owner.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["is_active"], false);
+    assert!(body["archived_at"].is_string());
+
+    let response = app.get("/api/projects", &owner.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert!(body.as_array().unwrap().iter().all(|project| project["id"] != project_id.to_string()));
+    let response = app.get("/api/projects?status=archived", &owner.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body[0]["id"], project_id.to_string());
+
+    let response = app.post(&format!("/api/projects/{}/activate", project_id), &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 204);
+    let response = app.put(&task_path, &owner.access_token, json!({ "title": "New title" })).await;
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_search_task_comments() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("searcher").await;
+    let outsider = app.register_user("outsider").await;
+    let team_id = app.create_team(&owner, "Search").await;
+    let project_id = app.create_project(&owner, team_id, "Decisions").await;
+    let task = app.create_task(&owner, project_id, "Pick a database").await;
+    let comments_path = format!("/api/tasks/{}/comments", task["id"].as_str().unwrap());
+
+    for content in ["Should we use MySQL?", "We decided on Postgres.", "Coverage is 100% done"] {
+        let response = app.post(&comments_path, &owner.access_token, json!({ "content": content })).await;
+        assert_eq!(response.status(), 201);
+    }
+
+    let response = app.get(&format!("{}/search?q=postgres", comments_path), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let results = body.as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["user"]["id"], owner.id.to_string());
+    assert_eq!(results[0]["snippet"]["text"], "We decided on Postgres.");
+    assert_eq!(results[0]["snippet"]["highlights"], json!([[14, 22]]));
+
+    // LIKE wildcards match literally
+    let response = app.get(&format!("{}/search?q=0%25%20d", comments_path), &owner.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    let response = app.get(&format!("{}/search?q=%25%25", comments_path), &owner.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 0);
+
+    let response = app.get(&format!("{}/search?q=postgres", comments_path), &outsider.access_token).await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_availability_check() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("taken").await;
+    let client = reqwest::Client::new();
+    let check = |query: String| client.get(app.url(&format!("/api/auth/availability?{}", query))).send();
+
+    let response = check(format!("username={}", user.username)).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["available"], false);
+    assert!(body["errors"].as_array().unwrap().is_empty());
+
+    let body: Value = check(format!("email={}", user.email)).await.unwrap().json().await.unwrap();
+    assert_eq!(body["available"], false);
+
+    let body: Value = check(format!("username=free_{}", &Uuid::new_v4().simple().to_string()[..8]))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["available"], true);
+
+    // Format problems come back inline
+    let body: Value = check("username=no!".to_string()).await.unwrap().json().await.unwrap();
+    assert_eq!(body["available"], false);
+    assert_eq!(body["errors"][0]["field"], "username");
+
+    // Limited per IP
+    let mut limited = None;
+    for _ in 0..20 {
+        let response = check("username=someone".to_string()).await.unwrap();
+        if response.status() == 429 {
+            limited = Some(response);
+            break;
+        }
+    }
+    let response = limited.expect("availability checks are never rate limited");
+    assert!(response.headers().contains_key("retry-after"));
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "RATE_LIMITED");
+}
+
+#[tokio::test]
+async fn test_signup_codes() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("codeadmin").await;
+    let user = app.register_user("nocodes").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id)
+        .await
+        .unwrap();
+
+    let response = app.post("/api/admin/signup-codes", &user.access_token, json!({})).await;
+    assert_eq!(response.status(), 403);
+    let response = app.post("/api/admin/signup-codes", &admin.access_token, json!({ "max_uses": 0 })).await;
+    assert_eq!(response.status(), 400);
+
+    let response = app.post("/api/admin/signup-codes", &admin.access_token, json!({ "max_uses": 1 })).await;
+    assert_eq!(response.status(), 201);
+    let body: Value = response.json().await.unwrap();
+    let code = body["code"].as_str().unwrap().to_string();
+
+    let register = |name: &str| {
+        let suffix = &Uuid::new_v4().simple().to_string()[..12];
+        json!({
+            "email": format!("{}_{}@example.com", name, suffix),
+            "username": format!("{}_{}", name, suffix),
+            "display_name": name,
+            "password": TEST_PASSWORD,
+            "signup_code": code,
+        })
+    };
+
+    let response = app.post_public("/api/auth/register", register("invited")).await;
+    assert_eq!(response.status(), 201);
+
+    // Used up
+    let response = app.post_public("/api/auth/register", register("latecomer")).await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_all_day_due_dates_follow_the_user_timezone() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("islander").await;
+    let team_id = app.create_team(&user, "Tonga").await;
+    let project_id = app.create_project(&user, team_id, "Harbour").await;
+
+    let response = app
+        .put("/api/users/me/digest", &user.access_token, json!({ "timezone": "Pacific/Tongatapu" }))
+        .await;
+    assert_eq!(response.status(), 200);
+
+    // UTC+13, so its calendar day is usually not the UTC one
+    let today = chrono::Utc::now().with_timezone(&chrono_tz::Pacific::Tongatapu).date_naive();
+    let yesterday = today.pred_opt().unwrap();
+
+    let tasks_path = format!("/api/projects/{}/tasks", project_id);
+    let response = app
+        .post(
+            &tasks_path,
+            &user.access_token,
+            json!({ "title": "Due today", "assigned_to": user.id, "due_date": today.to_string() }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let task: Value = response.json().await.unwrap();
+    assert_eq!(task["is_all_day"], true);
+    assert_eq!(task["due_date"], format!("{}T00:00:00Z", today));
+
+    let response = app
+        .post(
+            &tasks_path,
+            &user.access_token,
+            json!({ "title": "Due yesterday", "assigned_to": user.id, "due_date": yesterday.to_string() }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let response = app.get("/api/tasks?due=overdue", &user.access_token).await;
+    let tasks: Value = response.json().await.unwrap();
+    let titles: Vec<&str> = tasks.as_array().unwrap().iter().map(|task| task["title"].as_str().unwrap()).collect();
+    assert_eq!(titles, vec!["Due yesterday"]);
+
+    let response = app.get("/api/tasks?due=upcoming", &user.access_token).await;
+    let tasks: Value = response.json().await.unwrap();
+    let titles: Vec<&str> = tasks.as_array().unwrap().iter().map(|task| task["title"].as_str().unwrap()).collect();
+    assert_eq!(titles, vec!["Due today"]);
+
+    // A time without an offset could be in any timezone
+    let response = app
+        .post(&tasks_path, &user.access_token, json!({ "title": "Ambiguous", "due_date": "2030-06-07T10:00:00" }))
+        .await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_admin_impersonation_is_audited_and_limited() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("support").await;
+    let user = app.register_user("customer").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id)
+        .await
+        .unwrap();
+    let team_id = app.create_team(&user, "Customer team").await;
+    let project_id = app.create_project(&user, team_id, "Customer project").await;
+    let task = app.create_task(&user, project_id, "Keep me").await;
+    let task_id = task["id"].as_str().unwrap();
+
+    let impersonate_path = format!("/api/admin/impersonate/{}", user.id);
+    let response = app.post(&impersonate_path, &user.access_token, json!({})).await;
+    assert_eq!(response.status(), 403);
+
+    let response = app.post(&impersonate_path, &admin.access_token, json!({})).await;
+    assert_eq!(response.status(), 201);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["user"]["id"], user.id.to_string());
+    let token = body["access_token"].as_str().unwrap().to_string();
+
+    let response = app.get("/api/users/me", &token).await;
+    assert_eq!(response.status(), 200);
+    let me: Value = response.json().await.unwrap();
+    assert_eq!(me["id"], user.id.to_string());
+    assert_eq!(me["impersonated_by"]["id"], admin.id.to_string());
+
+    let response = app.get("/api/users/me", &user.access_token).await;
+    let me: Value = response.json().await.unwrap();
+    assert!(me["impersonated_by"].is_null());
+
+    // Looking around works, destroying things and admin endpoints don't
+    let response = app.get(&format!("/api/projects/{}/tasks", project_id), &token).await;
+    assert_eq!(response.status(), 200);
+    let response = app.delete(&format!("/api/tasks/{}", task_id), &token).await;
+    assert_eq!(response.status(), 403);
+    let response = app.get(&format!("/api/tasks/{}", task_id), &user.access_token).await;
+    assert_eq!(response.status(), 200);
+    let response = app.get("/api/admin/audit-log", &token).await;
+    assert_eq!(response.status(), 403);
+
+    // So is a socket opened with the token
+    let (mut socket, _) = connect_async(app.ws_url(&token)).await.unwrap();
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "AuthenticationSuccess");
+    assert_eq!(event["data"]["user_id"], user.id.to_string());
+    socket.close(None).await.unwrap();
+
+    let response = app.get("/api/admin/audit-log?limit=500", &admin.access_token).await;
+    assert_eq!(response.status(), 200);
+    let entries: Value = response.json().await.unwrap();
+    let entries: Vec<&Value> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry["subject_id"] == user.id.to_string())
+        .collect();
+    assert!(entries.iter().all(|entry| entry["actor_id"] == admin.id.to_string()));
+    assert!(entries.iter().any(|entry| entry["action"] == "ImpersonationStarted"));
+    let requests: Vec<(&str, &str, i64)> = entries
+        .iter()
+        .filter(|entry| entry["action"] == "ImpersonatedRequest")
+        .map(|entry| (entry["method"].as_str().unwrap(), entry["path"].as_str().unwrap(), entry["status"].as_i64().unwrap()))
+        .collect();
+    assert_eq!(requests.len(), 5);
+    assert!(requests.contains(&("DELETE", format!("/api/tasks/{}", task_id).as_str(), 403)));
+    assert!(requests.contains(&("GET", "/api/users/me", 200)));
+    assert!(requests.contains(&("GET", "/ws", 101)));
+}
+
+#[tokio::test]
+async fn test_renaming_board_columns_keeps_every_task() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("renamer").await;
+    let team_id = app.create_team(&owner, "Columns").await;
+    let project_id = app.create_project(&owner, team_id, "Rename").await;
+
+    let response = app
+        .post(&format!("/api/projects/{}/boards", project_id), &owner.access_token, json!({ "name": "Main" }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let board: Value = response.json().await.unwrap();
+    let board_path = format!("/api/boards/{}", board["id"].as_str().unwrap());
+
+    for n in 0..50 {
+        let task = app.create_task(&owner, project_id, &format!("Task {}", n)).await;
+        if n % 2 == 0 {
+            let response = app
+                .put(&format!("/api/tasks/{}", task["id"].as_str().unwrap()), &owner.access_token, json!({ "status": "InProgress" }))
+                .await;
+            assert_eq!(response.status(), 200);
+        }
+    }
+
+    // Collides with "Todo" once case is ignored
+    let response = app
+        .put(&board_path, &owner.access_token, json!({ "columns": ["Todo", "todo", "Review", "Done"] }))
+        .await;
+    assert_eq!(response.status(), 400);
+
+    let response = app
+        .put(&board_path, &owner.access_token, json!({ "columns": ["Todo", "Doing", "Review", "Done"] }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let board: Value = response.json().await.unwrap();
+    assert_eq!(board["columns"], json!(["Todo", "Doing", "Review", "Done"]));
+
+    // Tasks belong to status columns, so none were left without one
+    let response = app.get(&board_path, &owner.access_token).await;
+    let details: Value = response.json().await.unwrap();
+    let tasks = details["tasks"].as_array().unwrap();
+    assert_eq!(tasks.len(), 50);
+    assert_eq!(tasks.iter().filter(|task| task["status"] == "InProgress").count(), 25);
+    let counted: i64 = details["task_counts"].as_array().unwrap().iter().map(|count| count["count"].as_i64().unwrap()).sum();
+    assert_eq!(counted, 50);
+}
+
+#[tokio::test]
+async fn test_public_instance_config() {
+    let app = TestApp::spawn().await;
+
+    let response = app.client.get(app.url("/api/config")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["cache-control"], "public, max-age=300");
+    let config: Value = response.json().await.unwrap();
+    assert_eq!(config["api_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(config["password_policy"]["min_length"], 8);
+    assert_eq!(config["websocket_path"], "/ws");
+    assert!(config["limits"]["max_upload_bytes"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_feature_flag_team_overrides() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("flagger").await;
+    let owner = app.register_user("flagged").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id)
+        .await
+        .unwrap();
+    let beta_team = app.create_team(&owner, "Beta").await;
+    let other_team = app.create_team(&owner, "Stable").await;
+    let flag = format!("structured_columns_{}", &Uuid::new_v4().simple().to_string()[..8]);
+    let flag_path = format!("/api/admin/flags/{}", flag);
+
+    let response = app.put(&flag_path, &owner.access_token, json!({ "enabled": false })).await;
+    assert_eq!(response.status(), 403);
+    let response = app.put("/api/admin/flags/Not-A-Flag", &admin.access_token, json!({ "enabled": false })).await;
+    assert_eq!(response.status(), 400);
+
+    let response = app
+        .put(&flag_path, &admin.access_token, json!({ "enabled": false, "description": "Columns as records" }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let response = app
+        .put(&format!("{}/teams/{}", flag_path, beta_team), &admin.access_token, json!({ "enabled": true }))
+        .await;
+    assert_eq!(response.status(), 200);
+
+    async fn config_flag(app: &TestApp, flag: &str, team: Option<Uuid>) -> Value {
+        let path = match team {
+            Some(team_id) => format!("/api/config?team_id={}", team_id),
+            None => "/api/config".to_string(),
+        };
+        let config: Value = app.client.get(app.url(&path)).send().await.unwrap().json().await.unwrap();
+        config["flags"][flag].clone()
+    }
+    assert_eq!(config_flag(&app, &flag, Some(beta_team)).await, true);
+    assert_eq!(config_flag(&app, &flag, Some(other_team)).await, false);
+    assert_eq!(config_flag(&app, &flag, None).await, false);
+
+    // Turning the default on doesn't override the team's explicit choice
+    let response = app.put(&flag_path, &admin.access_token, json!({ "enabled": true })).await;
+    assert_eq!(response.status(), 200);
+    let response = app
+        .put(&format!("{}/teams/{}", flag_path, beta_team), &admin.access_token, json!({ "enabled": false }))
+        .await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(config_flag(&app, &flag, Some(beta_team)).await, false);
+    assert_eq!(config_flag(&app, &flag, Some(other_team)).await, true);
+
+    let response = app.delete(&format!("{}/teams/{}", flag_path, beta_team), &admin.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(config_flag(&app, &flag, Some(beta_team)).await, true);
+
+    let response = app.get("/api/admin/flags", &admin.access_token).await;
+    let flags: Value = response.json().await.unwrap();
+    let listed = flags.as_array().unwrap().iter().find(|listed| listed["name"] == flag.as_str()).unwrap();
+    assert_eq!(listed["description"], "Columns as records");
+    assert_eq!(listed["overrides"], json!([]));
+}
+
+#[tokio::test]
+async fn test_feature_flag_cache_refresh() {
+    use simplecards::database::{models::SetFeatureFlagRequest, queries::FeatureFlagQueries};
+    use simplecards::flags::Flags;
+
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("cacher").await;
+    let team_id = app.create_team(&admin, "Cached").await;
+    let flag = format!("cached_flag_{}", &Uuid::new_v4().simple().to_string()[..8]);
+    let set = |enabled| SetFeatureFlagRequest { enabled, description: None };
+
+    let flags = Flags::new(app.database.pool().clone());
+    FeatureFlagQueries::set_flag(app.database.pool(), &flag, &set(true), admin.id).await.unwrap();
+
+    // Reads come from the snapshot until it is refreshed
+    assert!(!flags.enabled(&flag, team_id));
+    flags.refresh().await.unwrap();
+    assert!(flags.enabled(&flag, team_id));
+
+    let refresher = flags.spawn_refresh(Duration::from_millis(50));
+    FeatureFlagQueries::set_flag(app.database.pool(), &flag, &set(false), admin.id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!flags.enabled(&flag, team_id));
+    refresher.abort();
+}
+
+#[tokio::test]
+async fn test_task_relations_across_projects() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("relater").await;
+    let team_id = app.create_team(&owner, "Related").await;
+    let web = app.create_project(&owner, team_id, "Web").await;
+    let api = app.create_project(&owner, team_id, "API").await;
+    let duplicate = app.create_task(&owner, web, "Login broken").await;
+    let original = app.create_task(&owner, api, "Login fails with 500").await;
+    let duplicate_id = duplicate["id"].as_str().unwrap().to_string();
+    let original_id = original["id"].as_str().unwrap().to_string();
+
+    let relations_path = format!("/api/tasks/{}/relations", duplicate_id);
+    let response = app
+        .post(&relations_path, &owner.access_token, json!({ "task_id": original_id, "relation": "duplicates", "close_duplicate": true }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let relation: Value = response.json().await.unwrap();
+    assert_eq!(relation["relation"], "duplicates");
+    assert_eq!(relation["task"]["id"], original_id.as_str());
+    assert_eq!(relation["closed_task"]["id"], duplicate_id.as_str());
+    assert_eq!(relation["closed_task"]["status"], "Done");
+
+    // Stored once, read from either side
+    let details: Value = app.get(&format!("/api/tasks/{}", original_id), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(details["relations"][0]["relation"], "is_duplicated_by");
+    assert_eq!(details["relations"][0]["task"]["id"], duplicate_id.as_str());
+    assert_eq!(details["relations"][0]["task"]["status"], "Done");
+    let details: Value = app.get(&format!("/api/tasks/{}", duplicate_id), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(details["relations"].as_array().unwrap().len(), 1);
+    assert_eq!(details["relations"][0]["relation"], "duplicates");
+
+    // One relation per pair, whichever way round
+    let response = app
+        .post(&format!("/api/tasks/{}/relations", original_id), &owner.access_token, json!({ "task_id": duplicate_id, "relation": "relates_to" }))
+        .await;
+    assert_eq!(response.status(), 409);
+
+    let response = app
+        .post(&relations_path, &owner.access_token, json!({ "task_id": duplicate_id, "relation": "relates_to" }))
+        .await;
+    assert_eq!(response.status(), 400);
+
+    // Tasks in projects the caller isn't a member of can't be linked
+    let outsider = app.register_user("unrelated").await;
+    let outsider_team = app.create_team(&outsider, "Elsewhere").await;
+    let outsider_project = app.create_project(&outsider, outsider_team, "Own").await;
+    let own_task = app.create_task(&outsider, outsider_project, "Mine").await;
+    let response = app
+        .post(
+            &format!("/api/tasks/{}/relations", own_task["id"].as_str().unwrap()),
+            &outsider.access_token,
+            json!({ "task_id": original_id, "relation": "relates_to" }),
+        )
+        .await;
+    assert_eq!(response.status(), 404);
+
+    let third = app.create_task(&owner, web, "Session expiry").await;
+    let response = app
+        .post(&relations_path, &owner.access_token, json!({ "task_id": third["id"], "relation": "relates_to", "close_duplicate": true }))
+        .await;
+    assert_eq!(response.status(), 400);
+
+    let response = app.delete(&format!("/api/tasks/{}/relations/{}", original_id, duplicate_id), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let details: Value = app.get(&format!("/api/tasks/{}", duplicate_id), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(details["relations"], json!([]));
+    let response = app.delete(&format!("/api/tasks/{}/relations/{}", original_id, duplicate_id), &owner.access_token).await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_manual_order_of_assigned_tasks() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("planner").await;
+    let team_id = app.create_team(&owner, "Planning").await;
+    let project_id = app.create_project(&owner, team_id, "Day").await;
+
+    let mut ids = std::collections::HashMap::new();
+    for priority in ["Critical", "High", "Medium", "Low"] {
+        let response = app
+            .post(
+                &format!("/api/projects/{}/tasks", project_id),
+                &owner.access_token,
+                json!({ "title": priority, "priority": priority, "assigned_to": owner.id }),
+            )
+            .await;
+        let task: Value = response.json().await.unwrap();
+        ids.insert(priority, task["id"].as_str().unwrap().to_string());
+    }
+    let titles = |body: Value| -> Vec<String> {
+        body.as_array().unwrap().iter().map(|task| task["title"].as_str().unwrap().to_string()).collect()
+    };
+
+    // Listed tasks come first, the rest keep the default order after them
+    let response = app.put("/api/tasks/my-order", &owner.access_token, json!({ "task_ids": [ids["Low"], ids["Medium"]] })).await;
+    assert_eq!(response.status(), 204);
+    let response = app.get("/api/tasks?sort=manual", &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(titles(response.json().await.unwrap()), vec!["Low", "Medium", "Critical", "High"]);
+
+    // Completed tasks drop out of the order the next time it is read
+    let response = app.put(&format!("/api/tasks/{}", ids["Low"]), &owner.access_token, json!({ "status": "Done" })).await;
+    assert_eq!(response.status(), 200);
+    let response = app.get("/api/tasks?sort=manual", &owner.access_token).await;
+    assert_eq!(titles(response.json().await.unwrap()), vec!["Medium", "Critical", "High", "Low"]);
+    let ordered: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_task_order WHERE user_id = $1")
+        .bind(owner.id)
+        .fetch_one(app.database.pool())
+        .await
+        .unwrap();
+    assert_eq!(ordered, 1);
+
+    // A rejected list leaves the order as it was
+    let unassigned = app.create_task(&owner, project_id, "Someone else's").await;
+    for task_ids in [
+        json!([ids["High"], unassigned["id"]]),
+        json!([ids["High"], ids["Low"]]),
+        json!([ids["High"], ids["High"]]),
+    ] {
+        let response = app.put("/api/tasks/my-order", &owner.access_token, json!({ "task_ids": task_ids })).await;
+        assert_eq!(response.status(), 400);
+    }
+    let response = app.get("/api/tasks?sort=manual", &owner.access_token).await;
+    assert_eq!(titles(response.json().await.unwrap()), vec!["Medium", "Critical", "High", "Low"]);
+
+    let response = app.put("/api/tasks/my-order", &owner.access_token, json!({ "task_ids": [] })).await;
+    assert_eq!(response.status(), 204);
+    let response = app.get("/api/tasks?sort=manual", &owner.access_token).await;
+    assert_eq!(titles(response.json().await.unwrap()), vec!["Critical", "High", "Medium", "Low"]);
+}
+
+#[tokio::test]
+async fn test_quick_add_task_to_board_column() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("quickadd").await;
+    let team_id = app.create_team(&owner, "Quick").await;
+    let project_id = app.create_project(&owner, team_id, "Inbox").await;
+
+    let response = app
+        .post(
+            &format!("/api/projects/{}/boards", project_id),
+            &owner.access_token,
+            json!({
+                "name": "Limited",
+                "config": { "wip_mode": "Strict", "wip_limits": [{ "column_id": "InProgress", "limit": 2 }] }
+            }),
+        )
+        .await;
+    let board: Value = response.json().await.unwrap();
+    let board_id = board["id"].as_str().unwrap().to_string();
+    let column_path = format!("/api/boards/{}/columns/InProgress/tasks", board_id);
+
+    let (mut socket, _) = connect_async(app.ws_url(&owner.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": project_id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+
+    let response = app.post(&column_path, &owner.access_token, json!({ "title": "Bottom" })).await;
+    assert_eq!(response.status(), 201);
+    let bottom: Value = response.json().await.unwrap();
+    assert_eq!(bottom["status"], "InProgress");
+    assert_eq!(bottom["position"], 1);
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskCreated");
+    assert_eq!(event["data"]["column"], json!({ "board_id": board_id, "column_id": "InProgress" }));
+
+    let response = app.post(&column_path, &owner.access_token, json!({ "title": "Top", "placement": "top" })).await;
+    assert_eq!(response.status(), 201);
+    let top: Value = response.json().await.unwrap();
+    assert_eq!(top["position"], 1);
+
+    // One creation event, then the column reaching its limit; no move
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskCreated");
+    assert_eq!(event["data"]["task"]["position"], 1);
+    assert_eq!(next_event(&mut socket).await["type"], "ColumnWipStatusChanged");
+
+    let details: Value = app
+        .get(&format!("/api/tasks/{}", bottom["id"].as_str().unwrap()), &owner.access_token)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(details["position"], 2);
+
+    let response = app.post(&column_path, &owner.access_token, json!({ "title": "Over the limit" })).await;
+    assert_eq!(response.status(), 409);
+
+    let response = app
+        .post(&format!("/api/boards/{}/columns/Backlog/tasks", board_id), &owner.access_token, json!({ "title": "Nowhere" }))
+        .await;
+    assert_eq!(response.status(), 400);
+}
+
+// A project with a second member who is assigned one of its tasks
+async fn maintenance_fixture(app: &TestApp, prefix: &str) -> (TestUser, TestUser, Uuid, Uuid, Uuid) {
+    let owner = app.register_user(prefix).await;
+    let member = app.register_user(&format!("{}m", prefix)).await;
+    let team_id = app.create_team(&owner, "Maintained").await;
+    let project_id = app.create_project(&owner, team_id, "Fixture").await;
+    app.add_team_member(&owner, team_id, &member, "Member").await;
+    let response = app
+        .post(&format!("/api/projects/{}/members", project_id), &owner.access_token, json!({ "user_id": member.id, "role": "Member" }))
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let response = app
+        .post(
+            &format!("/api/projects/{}/tasks", project_id),
+            &owner.access_token,
+            json!({ "title": "Assigned", "assigned_to": member.id }),
+        )
+        .await;
+    let task: Value = response.json().await.unwrap();
+    let task_id = task["id"].as_str().unwrap().parse().unwrap();
+    (owner, member, team_id, project_id, task_id)
+}
+
+#[tokio::test]
+async fn test_maintenance_finds_orphaned_project_members() {
+    use simplecards::maintenance;
+
+    let app = TestApp::spawn().await;
+    let (_, member, team_id, project_id, _) = maintenance_fixture(&app, "orphans").await;
+    let mut conn = app.database.pool().acquire().await.unwrap();
+    assert!(maintenance::orphaned_project_members(&mut conn, Some(project_id)).await.unwrap().is_empty());
+
+    // Removed from the team behind the API's back, which keeps the project membership
+    sqlx::query("DELETE FROM team_members WHERE team_id = $1 AND user_id = $2")
+        .bind(team_id)
+        .bind(member.id)
+        .execute(app.database.pool())
+        .await
+        .unwrap();
+
+    let issues = maintenance::orphaned_project_members(&mut conn, Some(project_id)).await.unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].subject_id, Some(member.id));
+    assert_eq!(issues[0].detail, "User is not a member of the project's team");
+
+    // Guests and viewers don't need to be on the team
+    sqlx::query("UPDATE project_members SET role = 'guest' WHERE project_id = $1 AND user_id = $2")
+        .bind(project_id)
+        .bind(member.id)
+        .execute(app.database.pool())
+        .await
+        .unwrap();
+    assert!(maintenance::orphaned_project_members(&mut conn, Some(project_id)).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_maintenance_finds_non_member_assignees() {
+    use simplecards::maintenance;
+
+    let app = TestApp::spawn().await;
+    let (_, member, _, project_id, task_id) = maintenance_fixture(&app, "assignees").await;
+    let mut conn = app.database.pool().acquire().await.unwrap();
+    assert!(maintenance::non_member_assignees(&mut conn, Some(project_id)).await.unwrap().is_empty());
+
+    // Leaving through the API would have unassigned the task
+    sqlx::query("DELETE FROM project_members WHERE project_id = $1 AND user_id = $2")
+        .bind(project_id)
+        .bind(member.id)
+        .execute(app.database.pool())
+        .await
+        .unwrap();
+
+    let issues = maintenance::non_member_assignees(&mut conn, Some(project_id)).await.unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].subject_id, Some(task_id));
+}
+
+#[tokio::test]
+async fn test_maintenance_finds_duplicate_positions() {
+    use simplecards::maintenance;
+
+    let app = TestApp::spawn().await;
+    let (owner, _, _, project_id, _) = maintenance_fixture(&app, "positions").await;
+    app.create_task(&owner, project_id, "Second").await;
+    app.create_task(&owner, project_id, "Third").await;
+    let mut conn = app.database.pool().acquire().await.unwrap();
+    assert!(maintenance::duplicate_positions(&mut conn, Some(project_id)).await.unwrap().is_empty());
+
+    sqlx::query("UPDATE tasks SET position = 1 WHERE project_id = $1")
+        .bind(project_id)
+        .execute(app.database.pool())
+        .await
+        .unwrap();
+
+    let issues = maintenance::duplicate_positions(&mut conn, Some(project_id)).await.unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].detail, "3 tasks share position 1 in column todo");
+}
+
+#[tokio::test]
+async fn test_maintenance_finds_default_board_violations() {
+    use simplecards::maintenance;
+
+    let app = TestApp::spawn().await;
+    let (owner, _, team_id, project_id, _) = maintenance_fixture(&app, "defaults").await;
+    let other_project = app.create_project(&owner, team_id, "Two defaults").await;
+    let mut conn = app.database.pool().acquire().await.unwrap();
+    assert!(maintenance::default_boards(&mut conn, Some(project_id)).await.unwrap().is_empty());
+
+    sqlx::query("UPDATE boards SET is_default = false WHERE project_id = $1")
+        .bind(project_id)
+        .execute(app.database.pool())
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO boards (name, project_id, created_by, is_default) VALUES ('Extra', $1, $2, true)")
+        .bind(other_project)
+        .bind(owner.id)
+        .execute(app.database.pool())
+        .await
+        .unwrap();
+
+    let issues = maintenance::default_boards(&mut conn, Some(project_id)).await.unwrap();
+    assert_eq!(issues[0].detail, "Project has no default board");
+    let issues = maintenance::default_boards(&mut conn, Some(other_project)).await.unwrap();
+    assert_eq!(issues[0].detail, "Project has 2 default boards");
+}
+
+#[tokio::test]
+async fn test_maintenance_repair_with_dry_run() {
+    let app = TestApp::spawn().await;
+    let (owner, member, _, project_id, task_id) = maintenance_fixture(&app, "repairs").await;
+    let admin = app.register_user("maintainer").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id).await.unwrap();
+    app.create_task(&owner, project_id, "Second").await;
+
+    let corrupt = [
+        "DELETE FROM project_members WHERE project_id = $1 AND user_id = $2",
+        "UPDATE tasks SET position = 1 WHERE project_id = $1 AND $2::uuid IS NOT NULL",
+        "UPDATE boards SET is_default = false WHERE project_id = $1 AND $2::uuid IS NOT NULL",
+    ];
+    for sql in corrupt {
+        sqlx::query(sql).bind(project_id).bind(member.id).execute(app.database.pool()).await.unwrap();
+    }
+
+    let response = app.post(&format!("/api/admin/maintenance/check?project_id={}", project_id), &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 403);
+
+    let check_path = format!("/api/admin/maintenance/check?project_id={}", project_id);
+    let report: Value = app.post(&check_path, &admin.access_token, json!({})).await.json().await.unwrap();
+    assert_eq!(report["issue_count"], 3);
+    let checks = report["checks"].as_array().unwrap();
+    assert_eq!(checks.len(), 6);
+    assert_eq!(checks[0]["check"], "orphaned_project_members");
+    assert_eq!(checks[1]["issues"][0]["subject_id"], task_id.to_string());
+
+    // A dry run reports the same issues and changes nothing
+    let response = app
+        .post(&format!("/api/admin/maintenance/repair?project_id={}&dry_run=true", project_id), &admin.access_token, json!({}))
+        .await;
+    assert_eq!(response.status(), 200);
+    let repair: Value = response.json().await.unwrap();
+    assert_eq!(repair["dry_run"], true);
+    assert_eq!(repair["issue_count"], 3);
+    let report: Value = app.post(&check_path, &admin.access_token, json!({})).await.json().await.unwrap();
+    assert_eq!(report["issue_count"], 3);
+
+    let repair: Value = app
+        .post(&format!("/api/admin/maintenance/repair?project_id={}", project_id), &admin.access_token, json!({}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(repair["dry_run"], false);
+    assert_eq!(repair["issue_count"], 3);
+    let report: Value = app.post(&check_path, &admin.access_token, json!({})).await.json().await.unwrap();
+    assert_eq!(report["issue_count"], 0);
+
+    let task: Value = app.get(&format!("/api/tasks/{}", task_id), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(task["assigned_to"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_project_workload() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("workowner").await;
+    let member = app.register_user("workmember").await;
+    let idle = app.register_user("workidle").await;
+
+    let team_id = app.create_team(&owner, "Capacity").await;
+    let project_id = app.create_project(&owner, team_id, "Roadmap").await;
+    for user in [&member, &idle] {
+        app.add_team_member(&owner, team_id, user, "Member").await;
+        let response = app
+            .post(
+                &format!("/api/projects/{}/members", project_id),
+                &owner.access_token,
+                json!({ "user_id": user.id, "role": "Member" }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+
+    let tasks_path = format!("/api/projects/{}/tasks", project_id);
+    for (title, priority, due_date) in [
+        ("Overdue", "High", Some("2024-01-01T00:00:00Z")),
+        ("Next year", "Medium", Some("2030-01-01T00:00:00Z")),
+        ("Someday", "Medium", None),
+        ("Shipped", "Critical", None),
+    ] {
+        let response = app
+            .post(
+                &tasks_path,
+                &owner.access_token,
+                json!({ "title": title, "priority": priority, "due_date": due_date, "assigned_to": member.id }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+        if title == "Shipped" {
+            let task: Value = response.json().await.unwrap();
+            let response = app.put(&format!("/api/tasks/{}", task["id"].as_str().unwrap()), &owner.access_token, json!({ "status": "Done" })).await;
+            assert_eq!(response.status(), 200);
+        }
+    }
+
+    let workload_path = format!("/api/projects/{}/workload", project_id);
+    assert_eq!(app.get(&workload_path, &member.access_token).await.status(), 403);
+
+    let response = app.get(&workload_path, &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let workload: Vec<Value> = response.json().await.unwrap();
+    assert_eq!(workload.len(), 3);
+    assert_eq!(workload[0]["user"]["id"], json!(member.id));
+    assert_eq!(workload[0]["open_tasks"], 3);
+    assert_eq!(workload[0]["by_priority"], json!({ "critical": 0, "high": 1, "medium": 2, "low": 0 }));
+    assert_eq!(workload[0]["overdue"], 1);
+    let idle_workload = workload.iter().find(|entry| entry["user"]["id"] == json!(idle.id)).unwrap();
+    assert_eq!(idle_workload["open_tasks"], 0);
+    assert_eq!(idle_workload["overdue"], 0);
+
+    // Only tasks due in the range count
+    let response = app
+        .get(&format!("{}?from=2029-01-01T00:00:00Z&to=2031-01-01T00:00:00Z", workload_path), &owner.access_token)
+        .await;
+    let workload: Vec<Value> = response.json().await.unwrap();
+    assert_eq!(workload[0]["open_tasks"], 1);
+    assert_eq!(workload[0]["overdue"], 0);
+
+    let response = app
+        .get(&format!("{}?from=2031-01-01T00:00:00Z&to=2029-01-01T00:00:00Z", workload_path), &owner.access_token)
+        .await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_lookalike_usernames_and_name_normalization() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("bill").await;
+
+    let register = |username: String, email: &str, display_name: &str| {
+        app.post_public(
+            "/api/auth/register",
+            json!({
+                "email": email,
+                "username": username,
+                "display_name": display_name,
+                "password": TEST_PASSWORD,
+            }),
+        )
+    };
+
+    let lookalike = user.username.replacen("bill", "B1lI", 1);
+    let response = register(lookalike.clone(), &format!("{}@example.com", lookalike), "Bill").await;
+    assert_eq!(response.status(), 409);
+
+    let response = register("\u{0430}dmin_user".to_string(), "cyrillic@example.com", "Admin").await;
+    assert_eq!(response.status(), 400);
+
+    // Decomposed "é" is stored composed
+    let username = format!("rene_{}", &Uuid::new_v4().simple().to_string()[..12]);
+    let response = register(username.clone(), &format!("{}@example.com", username), "Rene\u{0301}").await;
+    assert_eq!(response.status(), 201);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["user"]["display_name"], "Ren\u{00E9}");
+
+    let response = app.post("/api/teams", &user.access_token, json!({ "name": "Cafe\u{0301}" })).await;
+    assert_eq!(response.status(), 201);
+    let team: Value = response.json().await.unwrap();
+    assert_eq!(team["name"], "Caf\u{00E9}");
+}
+
+#[tokio::test]
+async fn test_comment_notifications_are_batched() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("chatty").await;
+    let assignee = app.register_user("listener").await;
+    let team_id = app.create_team(&owner, "Updates").await;
+    app.add_team_member(&owner, team_id, &assignee, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Launch").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", project_id),
+            &owner.access_token,
+            json!({ "user_id": assignee.id, "role": "Member" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let task = app.create_task(&owner, project_id, "Write release notes").await;
+    let task_id = task["id"].as_str().unwrap();
+    let response = app.put(&format!("/api/tasks/{}", task_id), &owner.access_token, json!({ "assigned_to": assignee.id })).await;
+    assert_eq!(response.status(), 200);
+
+    let (mut socket, _) = connect_async(app.ws_url(&assignee.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+
+    let comments_path = format!("/api/tasks/{}/comments", task_id);
+    for update in ["Draft is up", "Added the changelog", "Ready for review"] {
+        let response = app.post(&comments_path, &owner.access_token, json!({ "content": update })).await;
+        assert_eq!(response.status(), 201);
+    }
+
+    // One push with the final count
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskCommentedOn");
+    assert_eq!(event["data"]["notification"]["count"], 3);
+
+    let comment_notifications = |notifications: Value| -> Vec<Value> {
+        notifications
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|n| n["kind"] == "TaskCommented")
+            .cloned()
+            .collect()
+    };
+    let notifications: Value = app.get("/api/users/me/notifications", &assignee.access_token).await.json().await.unwrap();
+    let batched = comment_notifications(notifications);
+    assert_eq!(batched.len(), 1);
+    assert_eq!(batched[0]["count"], 3);
+    assert_eq!(batched[0]["actor_id"], json!(owner.id));
+
+    // Reading in between starts a new notification
+    let response = app.post("/api/users/me/notifications/read", &assignee.access_token, json!({})).await;
+    assert_eq!(response.status(), 204);
+    let response = app.post(&comments_path, &owner.access_token, json!({ "content": "One more thing" })).await;
+    assert_eq!(response.status(), 201);
+
+    let notifications: Value = app.get("/api/users/me/notifications", &assignee.access_token).await.json().await.unwrap();
+    let batched = comment_notifications(notifications);
+    assert_eq!(batched.len(), 2);
+    assert_eq!(batched[0]["count"], 1);
+    assert_eq!(batched[0]["read_at"], Value::Null);
+    assert_eq!(batched[1]["count"], 3);
+
+    // Authors aren't notified about their own comments
+    let notifications: Value = app.get("/api/users/me/notifications", &owner.access_token).await.json().await.unwrap();
+    assert!(comment_notifications(notifications).is_empty());
+}
+
+#[tokio::test]
+async fn test_quarantine_and_purge_user_content() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("moderator").await;
+    let spammer = app.register_user("spammer").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), owner.id).await.unwrap();
+    let team_id = app.create_team(&owner, "Community").await;
+    app.add_team_member(&owner, team_id, &spammer, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Forum").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", project_id),
+            &owner.access_token,
+            json!({ "user_id": spammer.id, "role": "Member" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let task = app.create_task(&owner, project_id, "Roadmap").await;
+    let task_id = task["id"].as_str().unwrap();
+    let spam_task = app.create_task(&spammer, project_id, "Cheap watches").await;
+    let comments_path = format!("/api/tasks/{}/comments", task_id);
+    let response = app.post(&comments_path, &spammer.access_token, json!({ "content": "Buy now" })).await;
+    assert_eq!(response.status(), 201);
+
+    let tasks_path = format!("/api/projects/{}/tasks", project_id);
+    let content_path = |action: &str| format!("/api/admin/users/{}/content/{}", spammer.id, action);
+    let task_titles = |tasks: Value| -> Vec<String> {
+        tasks.as_array().unwrap().iter().map(|t| t["title"].as_str().unwrap().to_string()).collect()
+    };
+
+    let response = app.post(&content_path("quarantine"), &spammer.access_token, json!({})).await;
+    assert_eq!(response.status(), 403);
+
+    // A dry run counts the content and hides nothing
+    let report: Value = app
+        .post(&format!("{}?dry_run=true", content_path("quarantine")), &owner.access_token, json!({}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["task_count"], 1);
+    assert_eq!(report["comment_count"], 1);
+    let tasks: Value = app.get(&tasks_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(task_titles(tasks).len(), 2);
+
+    let (mut socket, _) = connect_async(app.ws_url(&owner.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": project_id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+
+    let report: Value = app.post(&content_path("quarantine"), &owner.access_token, json!({})).await.json().await.unwrap();
+    assert_eq!(report["dry_run"], false);
+    assert_eq!(report["tasks"][0]["id"], spam_task["id"]);
+
+    // Open boards are told to drop both
+    let mut deleted = Vec::new();
+    while deleted.len() < 2 {
+        let event = next_event(&mut socket).await;
+        if matches!(event["type"].as_str(), Some("TaskDeleted" | "CommentDeleted")) {
+            deleted.push(event["type"].as_str().unwrap().to_string());
+        }
+    }
+    deleted.sort();
+    assert_eq!(deleted, vec!["CommentDeleted", "TaskDeleted"]);
+
+    let tasks: Value = app.get(&tasks_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(task_titles(tasks), vec!["Roadmap"]);
+    let comments: Value = app.get(&comments_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(comments.as_array().unwrap().len(), 0);
+
+    // Only instance admins can look at quarantined content
+    let response = app.get(&format!("{}?include_quarantined=true", tasks_path), &spammer.access_token).await;
+    assert_eq!(response.status(), 403);
+    let tasks: Value = app
+        .get(&format!("{}?include_quarantined=true", tasks_path), &owner.access_token)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(task_titles(tasks).len(), 2);
+    let comments: Value = app
+        .get(&format!("{}?include_quarantined=true", comments_path), &owner.access_token)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(comments.as_array().unwrap().len(), 1);
+
+    let report: Value = app.post(&content_path("restore"), &owner.access_token, json!({})).await.json().await.unwrap();
+    assert_eq!(report["task_count"], 1);
+    assert_eq!(report["comment_count"], 1);
+    let tasks: Value = app.get(&tasks_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(task_titles(tasks).len(), 2);
+
+    // Purging leaves content that isn't quarantined alone
+    let report: Value = app.post(&content_path("purge"), &owner.access_token, json!({})).await.json().await.unwrap();
+    assert_eq!(report["task_count"], 0);
+    assert_eq!(report["comment_count"], 0);
+
+    let response = app.post(&content_path("quarantine"), &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 200);
+    let report: Value = app.post(&content_path("purge"), &owner.access_token, json!({})).await.json().await.unwrap();
+    assert_eq!(report["task_count"], 1);
+    assert_eq!(report["comment_count"], 1);
+    let tasks: Value = app
+        .get(&format!("{}?include_quarantined=true", tasks_path), &owner.access_token)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(task_titles(tasks), vec!["Roadmap"]);
+
+    let response = app
+        .post(
+            &format!("{}?from=2024-01-02T00:00:00Z&to=2024-01-01T00:00:00Z", content_path("quarantine")),
+            &owner.access_token,
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+}
+
+async fn picker_titles(app: &TestApp, user: &TestUser, path: String) -> Vec<String> {
+    let response = app.get(&path, &user.access_token).await;
+    assert_eq!(response.status(), 200);
+    let tasks: Value = response.json().await.unwrap();
+    tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["title"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_task_picker() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("picker").await;
+    let outsider = app.register_user("notpicker").await;
+    let team_id = app.create_team(&owner, "Linking").await;
+    let project_id = app.create_project(&owner, team_id, "App").await;
+    let other_project_id = app.create_project(&owner, team_id, "Docs").await;
+
+    let login = app.create_task(&owner, project_id, "Login page").await;
+    let fix = app.create_task(&owner, project_id, "Fix login redirect").await;
+    app.create_task(&owner, project_id, "Logout").await;
+    let docs = app.create_task(&owner, other_project_id, "Document login flow").await;
+
+    let picker_path = format!("/api/projects/{}/tasks/picker", project_id);
+    let pick = |query: String| picker_titles(&app, &owner, format!("{}?{}", picker_path, query));
+
+    // Title prefix matches come before other substring matches
+    assert_eq!(pick("q=LOGIN".to_string()).await, vec!["Login page", "Fix login redirect"]);
+    assert_eq!(pick(format!("q=login&exclude={}", login["id"].as_str().unwrap())).await, vec!["Fix login redirect"]);
+
+    // Task numbers, with or without a key
+    let number = fix["number"].as_i64().unwrap();
+    assert_eq!(pick(format!("q=%23{}", number)).await, vec!["Fix login redirect"]);
+    assert_eq!(pick(format!("q=SC-{}", number)).await, vec!["Fix login redirect"]);
+
+    // Team scope searches the other projects too
+    let titles = pick("q=login&scope=team".to_string()).await;
+    assert_eq!(titles.len(), 3);
+    assert!(titles.contains(&docs["title"].as_str().unwrap().to_string()));
+
+    assert_eq!(pick("limit=2".to_string()).await.len(), 2);
+
+    let response = app.get(&picker_path, &outsider.access_token).await;
+    assert_eq!(response.status(), 403);
+}
+
+#[tokio::test]
+async fn test_nudge_task() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("nudger").await;
+    let member = app.register_user("watcher").await;
+    let team_id = app.create_team(&owner, "Keyboard").await;
+    app.add_team_member(&owner, team_id, &member, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Shortcuts").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", project_id),
+            &owner.access_token,
+            json!({ "user_id": member.id, "role": "Member" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let mut ids = Vec::new();
+    for title in ["First", "Second", "Third"] {
+        let task = app.create_task(&owner, project_id, title).await;
+        ids.push(task["id"].as_str().unwrap().to_string());
+    }
+
+    let nudge = |id: &str, direction: &str| {
+        let (app, path, token) = (&app, format!("/api/tasks/{}/nudge", id), owner.access_token.clone());
+        let body = json!({ "direction": direction });
+        async move { app.post(&path, &token, body).await }
+    };
+
+    // Top of the column + up and first column + left change nothing
+    for (id, direction) in [(&ids[0], "up"), (&ids[2], "down"), (&ids[0], "left")] {
+        let response = nudge(id, direction).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["moved"], false);
+        assert_eq!(body["columns"], json!([]));
+    }
+
+    let (mut socket, _) = connect_async(app.ws_url(&member.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": project_id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+
+    let body: Value = nudge(&ids[2], "up").await.json().await.unwrap();
+    assert_eq!(body["moved"], true);
+    assert_eq!(body["task"]["position"], 2);
+    assert_eq!(body["columns"], json!([{ "status": "Todo", "task_ids": [ids[0], ids[2], ids[1]] }]));
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskMoved");
+    assert_eq!(event["data"]["task_id"], ids[2]);
+    assert_eq!(event["data"]["position"], 2);
+
+    // Right keeps the place, clamped to the shorter column
+    let body: Value = nudge(&ids[1], "right").await.json().await.unwrap();
+    assert_eq!(body["task"]["status"], "InProgress");
+    assert_eq!(body["task"]["position"], 1);
+    assert_eq!(
+        body["columns"],
+        json!([
+            { "status": "Todo", "task_ids": [ids[0], ids[2]] },
+            { "status": "InProgress", "task_ids": [ids[1]] },
+        ])
+    );
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "TaskMoved");
+    assert_eq!(event["data"]["to_status"], "InProgress");
+
+    // Last column + right changes nothing
+    for _ in 0..2 {
+        let body: Value = nudge(&ids[1], "right").await.json().await.unwrap();
+        assert_eq!(body["moved"], true);
+    }
+    let body: Value = nudge(&ids[1], "right").await.json().await.unwrap();
+    assert_eq!(body["moved"], false);
+    assert_eq!(body["task"]["status"], "Done");
+
+    let response = nudge(&ids[0], "sideways").await;
+    assert!(response.status().is_client_error());
+}
+
+#[tokio::test]
+async fn test_dashboard_sections() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("homescreen").await;
+    let assignee = app.register_user("busy").await;
+    let team_id = app.create_team(&owner, "Home").await;
+    app.add_team_member(&owner, team_id, &assignee, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Landing").await;
+    app.create_project(&owner, team_id, "Blog").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", project_id),
+            &owner.access_token,
+            json!({ "user_id": assignee.id, "role": "Member" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let now = chrono::Utc::now();
+    let due_dates = [
+        ("Overdue", now - chrono::Duration::days(2)),
+        ("Soon", now + chrono::Duration::days(2)),
+        ("Later", now + chrono::Duration::days(30)),
+    ];
+    for (title, due) in due_dates {
+        let task = app.create_task(&owner, project_id, title).await;
+        let response = app
+            .put(
+                &format!("/api/tasks/{}", task["id"].as_str().unwrap()),
+                &owner.access_token,
+                json!({ "assigned_to": assignee.id, "due_date": due.to_rfc3339() }),
+            )
+            .await;
+        assert_eq!(response.status(), 200);
+    }
+
+    let response = app.get("/api/dashboard", &assignee.access_token).await;
+    assert_eq!(response.status(), 200);
+    let dashboard: Value = response.json().await.unwrap();
+    assert_eq!(dashboard["teams"][0]["name"], "Home");
+    assert_eq!(dashboard["teams"][0]["project_count"], 2);
+    assert_eq!(dashboard["overdue_count"], 1);
+    let due = dashboard["due_this_week"].as_array().unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0]["tasks"][0]["title"], "Soon");
+    assert_eq!(due[0]["tasks"][0]["project_name"], "Landing");
+    assert_eq!(dashboard["unread_notification_count"], 3);
+
+    // Only the requested sections are loaded
+    let dashboard: Value = app
+        .get("/api/dashboard?sections=overdue_count,unread_notification_count", &assignee.access_token)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let keys: Vec<&String> = dashboard.as_object().unwrap().keys().collect();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(dashboard["overdue_count"], 1);
+
+    let response = app.get("/api/dashboard?sections=teams,starred", &assignee.access_token).await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_trash_restores_project_with_its_boards() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("binowner").await;
+    let member = app.register_user("binmember").await;
+    let team_id = app.create_team(&owner, "Recycling").await;
+    app.add_team_member(&owner, team_id, &member, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Compost").await;
+    let task = app.create_task(&owner, project_id, "Keep me").await;
+    let task_path = format!("/api/tasks/{}", task["id"].as_str().unwrap());
+
+    let mut board_ids = Vec::new();
+    for name in ["QA", "Ops"] {
+        let response = app
+            .post(&format!("/api/projects/{}/boards", project_id), &owner.access_token, json!({ "name": name }))
+            .await;
+        assert_eq!(response.status(), 201);
+        let board: Value = response.json().await.unwrap();
+        board_ids.push(board["id"].as_str().unwrap().to_string());
+    }
+    let boards_path = format!("/api/projects/{}/boards", project_id);
+    let board_trash_path = format!("/api/projects/{}/trash/boards", project_id);
+
+    // A board deleted on its own goes to the project's trash
+    let response = app.delete(&format!("/api/boards/{}", board_ids[0]), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let response = app.get(&format!("/api/boards/{}", board_ids[0]), &owner.access_token).await;
+    assert_eq!(response.status(), 404);
+    let boards: Value = app.get(&boards_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(boards.as_array().unwrap().len(), 2);
+    let trashed: Value = app.get(&board_trash_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(trashed.as_array().unwrap().len(), 1);
+    assert_eq!(trashed[0]["name"], "QA");
+    assert!(trashed[0]["purge_at"].is_string());
+
+    // Deleting the project hides it and everything in it
+    let response = app.delete(&format!("/api/projects/{}", project_id), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let projects: Value = app.get(&format!("/api/teams/{}/projects", team_id), &owner.access_token).await.json().await.unwrap();
+    assert!(projects.as_array().unwrap().is_empty());
+    let response = app.get(&format!("/api/projects/{}", project_id), &owner.access_token).await;
+    assert_eq!(response.status(), 403);
+    let response = app.get(&task_path, &owner.access_token).await;
+    assert_eq!(response.status(), 404);
+
+    let trash_path = format!("/api/teams/{}/trash", team_id);
+    let trashed: Value = app.get(&trash_path, &member.access_token).await.json().await.unwrap();
+    assert_eq!(trashed.as_array().unwrap().len(), 1);
+    assert_eq!(trashed[0]["id"], project_id.to_string());
+    assert!(trashed[0]["deleted_at"].is_string());
+
+    // Only team admins restore projects
+    let restore_path = format!("/api/teams/{}/trash/projects/{}/restore", team_id, project_id);
+    let response = app.post(&restore_path, &member.access_token, json!({})).await;
+    assert_eq!(response.status(), 403);
+    let response = app.post(&restore_path, &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 200);
+    let restored: Value = response.json().await.unwrap();
+    assert_eq!(restored["name"], "Compost");
+    let response = app.post(&restore_path, &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 404);
+
+    // The boards deleted with the project are back; the one trashed before isn't
+    let response = app.get(&task_path, &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let boards: Value = app.get(&boards_path, &owner.access_token).await.json().await.unwrap();
+    let names: Vec<&str> = boards.as_array().unwrap().iter().map(|board| board["name"].as_str().unwrap()).collect();
+    assert_eq!(names.len(), 2);
+    assert!(names.contains(&"Ops"));
+    let trashed: Value = app.get(&board_trash_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(trashed.as_array().unwrap().len(), 1);
+    assert!(app.get(&trash_path, &owner.access_token).await.json::<Value>().await.unwrap().as_array().unwrap().is_empty());
+
+    let response = app
+        .post(&format!("{}/{}/restore", board_trash_path, board_ids[0]), &owner.access_token, json!({}))
+        .await;
+    assert_eq!(response.status(), 200);
+    let boards: Value = app.get(&boards_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(boards.as_array().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_attachment_downloads_use_signed_urls() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("signer").await;
+    let outsider = app.register_user("peeker").await;
+    let team_id = app.create_team(&owner, "Files").await;
+    let project_id = app.create_project(&owner, team_id, "Archive").await;
+    let task = app.create_task(&owner, project_id, "Has a file").await;
+    let task_id: Uuid = task["id"].as_str().unwrap().parse().unwrap();
+
+    let attachment_id = Uuid::new_v4();
+    let storage_path = format!("{}/{}", task_id, attachment_id);
+    let path = app.config.attachments.upload_dir.join(&storage_path);
+    tokio::fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+    tokio::fs::write(&path, b"hello there").await.unwrap();
+    simplecards::database::queries::AttachmentQueries::create_attachment(
+        app.database.pool(),
+        attachment_id,
+        task_id,
+        Some(owner.id),
+        "notes.txt",
+        "text/plain",
+        11,
+        &storage_path,
+        &simplecards::attachments::content_hash(b"hello there"),
+        simplecards::database::models::AttachmentScanStatus::Clean,
+    )
+    .await
+    .unwrap();
+
+    let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+    let download = |token: &str| {
+        client
+            .get(app.url(&format!("/api/attachments/{}/download", attachment_id)))
+            .bearer_auth(token)
+            .send()
+    };
+
+    let response = download(&outsider.access_token).await.unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Members are redirected to a short-lived signed URL that needs no token
+    let response = download(&owner.access_token).await.unwrap();
+    assert_eq!(response.status(), 302);
+    let location = response.headers()["location"].to_str().unwrap().to_string();
+    assert!(location.starts_with(&format!("/files/signed/{}?", attachment_id)));
+
+    let response = client.get(app.url(&location)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/plain");
+    assert!(response.headers()["content-disposition"].to_str().unwrap().contains("notes.txt"));
+    assert_eq!(response.text().await.unwrap(), "hello there");
+
+    let response = client.get(app.url(&location)).header("Range", "bytes=0-4").send().await.unwrap();
+    assert_eq!(response.status(), 206);
+    assert_eq!(response.text().await.unwrap(), "hello");
+
+    // The content hash is a strong ETag, and the file may be cached for good
+    let response = client.get(app.url(&location)).send().await.unwrap();
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(etag, format!("\"{}\"", simplecards::attachments::content_hash(b"hello there")));
+    assert_eq!(response.headers()["cache-control"], "private, max-age=31536000, immutable");
+    let last_modified = response.headers()["last-modified"].to_str().unwrap().to_string();
+
+    let response = client.get(app.url(&location)).header("If-None-Match", &etag).send().await.unwrap();
+    assert_eq!(response.status(), 304);
+    assert_eq!(response.headers()["etag"], etag.as_str());
+    assert!(response.text().await.unwrap().is_empty());
+    let response = client.get(app.url(&location)).header("If-None-Match", "\"other\"").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.get(app.url(&location)).header("If-Modified-Since", &last_modified).send().await.unwrap();
+    assert_eq!(response.status(), 304);
+
+    let response = client.get(app.url(&location)).header("Range", "bytes=6-").send().await.unwrap();
+    assert_eq!(response.status(), 206);
+    assert_eq!(response.headers()["content-range"], "bytes 6-10/11");
+    assert_eq!(response.headers()["etag"], etag.as_str());
+    assert_eq!(response.text().await.unwrap(), "there");
+
+    // A tampered signature or one that expired is rejected
+    let tampered = format!("{}0", location);
+    let response = client.get(app.url(&tampered)).send().await.unwrap();
+    assert_eq!(response.status(), 403);
+
+    let expired = simplecards::attachments::signed_download_url(&app.config.attachments, attachment_id, chrono::Utc::now().timestamp() - 1);
+    let response = client.get(app.url(&expired)).send().await.unwrap();
+    assert_eq!(response.status(), 403);
+
+    let other = Uuid::new_v4();
+    let response = client.get(app.url(&location.replace(&attachment_id.to_string(), &other.to_string()))).send().await.unwrap();
+    assert_eq!(response.status(), 403);
+}
+
+struct InfectedScanner;
+
+#[async_trait::async_trait]
+impl simplecards::scanning::AttachmentScanner for InfectedScanner {
+    async fn scan(&self, _path: &std::path::Path) -> anyhow::Result<simplecards::scanning::ScanVerdict> {
+        Ok(simplecards::scanning::ScanVerdict::Infected("Eicar-Test-Signature".to_string()))
+    }
+}
+
+#[tokio::test]
+async fn test_attachment_scanning_blocks_downloads() {
+    use simplecards::database::{models::AttachmentScanStatus, queries::AttachmentQueries};
+    use simplecards::jobs::JobHandler;
+    use simplecards::scanning::{self, ScanAttachmentJob};
+
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("scanned").await;
+    let admin = app.register_user("scanadmin").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id)
+        .await
+        .unwrap();
+    let team_id = app.create_team(&owner, "Scanning").await;
+    app.add_team_member(&owner, team_id, &admin, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Uploads").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", project_id),
+            &owner.access_token,
+            json!({ "user_id": admin.id, "role": "Viewer" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let task = app.create_task(&owner, project_id, "Has a suspicious file").await;
+    let task_id: Uuid = task["id"].as_str().unwrap().parse().unwrap();
+
+    let attachment_id = Uuid::new_v4();
+    let storage_path = format!("{}/{}", task_id, attachment_id);
+    let path = app.config.attachments.upload_dir.join(&storage_path);
+    tokio::fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+    tokio::fs::write(&path, b"not really a virus").await.unwrap();
+    AttachmentQueries::create_attachment(
+        app.database.pool(),
+        attachment_id,
+        task_id,
+        Some(owner.id),
+        "invoice.exe",
+        "application/octet-stream",
+        18,
+        &storage_path,
+        &simplecards::attachments::content_hash(b"not really a virus"),
+        AttachmentScanStatus::Pending,
+    )
+    .await
+    .unwrap();
+
+    let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+    let download = |token: &str, query: &str| {
+        client
+            .get(app.url(&format!("/api/attachments/{}/download{}", attachment_id, query)))
+            .bearer_auth(token)
+            .send()
+    };
+
+    // Locked until the scan has a result
+    let response = download(&owner.access_token, "").await.unwrap();
+    assert_eq!(response.status(), 423);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "LOCKED");
+
+    let (mut ws, _) = connect_async(app.ws_url(&owner.access_token)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let job = scanning::queue_scan(app.database.pool(), attachment_id).await.unwrap();
+    let handler = ScanAttachmentJob::new(app.database.clone(), app.websocket.clone(), app.config.attachments.clone(), std::sync::Arc::new(InfectedScanner));
+    handler.run(&job).await.unwrap();
+
+    // The file is moved to the quarantine directory, not deleted
+    let attachment = AttachmentQueries::get_attachment(app.database.pool(), attachment_id).await.unwrap();
+    assert_eq!(attachment.scan_status, AttachmentScanStatus::Infected);
+    assert_eq!(attachment.scan_detail.as_deref(), Some("Eicar-Test-Signature"));
+    assert_eq!(attachment.storage_path, format!("quarantine/{}", storage_path));
+    assert!(!path.exists());
+    assert!(app.config.attachments.upload_dir.join(&attachment.storage_path).exists());
+
+    // The uploader hears about it
+    let event = loop {
+        let message = tokio::time::timeout(Duration::from_secs(2), ws.next()).await.unwrap().unwrap().unwrap();
+        if let Message::Text(text) = message {
+            let event: Value = serde_json::from_str(&text).unwrap();
+            if event["type"] == "AttachmentScanCompleted" {
+                break event;
+            }
+        }
+    };
+    assert_eq!(event["data"]["attachment"]["id"], attachment_id.to_string());
+    assert_eq!(event["data"]["attachment"]["scan_status"], "infected");
+    assert_eq!(event["data"]["project_id"], project_id.to_string());
+
+    // Running the job again is a no-op
+    handler.run(&job).await.unwrap();
+
+    let response = download(&owner.access_token, "").await.unwrap();
+    assert_eq!(response.status(), 423);
+    let response = download(&owner.access_token, "?override_scan=true").await.unwrap();
+    assert_eq!(response.status(), 403);
+
+    // Instance admins can still fetch the quarantined file
+    let response = download(&admin.access_token, "?override_scan=true").await.unwrap();
+    assert_eq!(response.status(), 302);
+    let location = response.headers()["location"].to_str().unwrap().to_string();
+    let response = client.get(app.url(&location)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "not really a virus");
+
+    let response = app.get("/api/admin/audit-log?limit=500", &admin.access_token).await;
+    let entries: Value = response.json().await.unwrap();
+    let path = format!("/api/attachments/{}", attachment_id);
+    assert!(entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|entry| entry["action"] == "AttachmentQuarantined" && entry["path"] == path.as_str()));
+}
+
+#[tokio::test]
+async fn test_share_token_badges() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("badger").await;
+    let team_id = app.create_team(&owner, "Badges").await;
+    let project_id = app.create_project(&owner, team_id, "Website").await;
+    app.create_task(&owner, project_id, "Still open").await;
+    let done = app.create_task(&owner, project_id, "Shipped").await;
+    let done_id = done["id"].as_str().unwrap();
+
+    let response = app
+        .post(
+            &format!("/api/tasks/{}/move", done_id),
+            &owner.access_token,
+            json!({ "task_id": done_id, "status": "Done", "position": 0 }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+
+    let response = app.post(&format!("/api/projects/{}/share-tokens", project_id), &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 201);
+    let share: Value = response.json().await.unwrap();
+    let token = share["token"].as_str().unwrap().to_string();
+    let token_id = share["id"].as_str().unwrap().to_string();
+
+    // Badges need no auth
+    let response = app.client.get(app.url(&format!("/public/projects/{}/badge.svg", token))).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/svg+xml");
+    assert_eq!(response.headers()["cache-control"], "public, max-age=60");
+    let svg = response.text().await.unwrap();
+    assert!(svg.contains("open tasks: 1"));
+
+    let response = app
+        .client
+        .get(app.url(&format!("/api/public/projects/{}/badge.svg?metric=progress", token)))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.text().await.unwrap().contains("done: 50%"));
+
+    let response = app.client.get(app.url(&format!("/public/projects/{}/badge.json", token))).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let badge: Value = response.json().await.unwrap();
+    assert_eq!(badge["project_name"], "Website");
+    assert_eq!(badge["open_tasks"], 1);
+    assert_eq!(badge["done_tasks"], 1);
+    assert_eq!(badge["percent_done"], 50);
+
+    let response = app.client.get(app.url("/public/projects/not-a-token/badge.json")).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Revoking takes effect right away, even though counts are cached
+    let response = app.delete(&format!("/api/projects/{}/share-tokens/{}", project_id, token_id), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let response = app.client.get(app.url(&format!("/public/projects/{}/badge.svg", token))).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = app.get(&format!("/api/projects/{}/share-tokens", project_id), &owner.access_token).await;
+    let tokens: Value = response.json().await.unwrap();
+    assert_eq!(tokens, json!([]));
+}
+
+#[tokio::test]
+async fn test_announcements_broadcast_and_dismiss() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("announcer").await;
+    let user = app.register_user("reader").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id)
+        .await
+        .unwrap();
+
+    let response = app.post("/api/admin/announcements", &user.access_token, json!({ "message": "Nope" })).await;
+    assert_eq!(response.status(), 403);
+
+    let response = app
+        .post(
+            "/api/admin/announcements",
+            &admin.access_token,
+            json!({ "message": "Ends before it starts", "ends_at": "2000-01-01T00:00:00Z" }),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+
+    // Every connection hears about it, without subscribing to anything
+    let (mut socket, _) = connect_async(app.ws_url(&user.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+
+    let response = app
+        .post(
+            "/api/admin/announcements",
+            &admin.access_token,
+            json!({ "message": "Maintenance at 22:00 UTC", "severity": "warning" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let announcement: Value = response.json().await.unwrap();
+    let announcement_id = announcement["id"].as_str().unwrap().to_string();
+    assert_eq!(announcement["dismissible"], true);
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "Announcement");
+    assert_eq!(event["data"]["id"], announcement_id);
+    assert_eq!(event["data"]["severity"], "warning");
+
+    let response = app
+        .post(
+            "/api/admin/announcements",
+            &admin.access_token,
+            json!({ "message": "Read-only until migration finishes", "severity": "critical", "dismissible": false }),
+        )
+        .await;
+    let pinned: Value = response.json().await.unwrap();
+    let pinned_id = pinned["id"].as_str().unwrap().to_string();
+    next_event(&mut socket).await;
+
+    let active_ids = |token: String| {
+        let app = &app;
+        async move {
+            let response = app.get("/api/announcements/active", &token).await;
+            assert_eq!(response.status(), 200);
+            let active: Vec<Value> = response.json().await.unwrap();
+            active.iter().map(|a| a["id"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+        }
+    };
+    let active = active_ids(user.access_token.clone()).await;
+    assert!(active.contains(&announcement_id) && active.contains(&pinned_id));
+
+    let response = app.post(&format!("/api/announcements/{}/dismiss", announcement_id), &user.access_token, json!({})).await;
+    assert_eq!(response.status(), 204);
+    let response = app.post(&format!("/api/announcements/{}/dismiss", pinned_id), &user.access_token, json!({})).await;
+    assert_eq!(response.status(), 400);
+
+    // Dismissals are per user
+    let active = active_ids(user.access_token.clone()).await;
+    assert!(!active.contains(&announcement_id) && active.contains(&pinned_id));
+    assert!(active_ids(admin.access_token.clone()).await.contains(&announcement_id));
+
+    // Ending it is an update, pushed like any other
+    let response = app
+        .put(
+            &format!("/api/admin/announcements/{}", pinned_id),
+            &admin.access_token,
+            json!({ "ends_at": chrono::Utc::now() }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["data"]["id"], pinned_id);
+    assert!(!event["data"]["ends_at"].is_null());
+    assert!(!active_ids(user.access_token.clone()).await.contains(&pinned_id));
+
+    let response = app.delete(&format!("/api/admin/announcements/{}", announcement_id), &admin.access_token).await;
+    assert_eq!(response.status(), 204);
+    let response = app.get("/api/admin/announcements", &admin.access_token).await;
+    let all: Vec<Value> = response.json().await.unwrap();
+    assert!(all.iter().all(|a| a["id"] != announcement_id.as_str()));
+}
+
+#[tokio::test]
+async fn test_team_join_policy_adds_new_members_to_projects() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("policy").await;
+    let joiner = app.register_user("joiner").await;
+    let veteran = app.register_user("veteran").await;
+    let team_id = app.create_team(&admin, "Onboarding").await;
+    let general_id = app.create_project(&admin, team_id, "General").await;
+    let private_id = app.create_project(&admin, team_id, "Private").await;
+    let role_in = |user: &TestUser, project_id: Uuid| {
+        let path = format!("/api/projects/{}/my-permissions", project_id);
+        let token = user.access_token.clone();
+        let app = &app;
+        async move {
+            let response = app.get(&path, &token).await;
+            if response.status() != 200 {
+                return Value::Null;
+            }
+            response.json::<Value>().await.unwrap()["role"].clone()
+        }
+    };
+
+    let response = app.get(&format!("/api/teams/{}/settings", team_id), &admin.access_token).await;
+    let settings: Value = response.json().await.unwrap();
+    assert_eq!(settings["auto_add_to_projects"], "none");
+    assert_eq!(settings["default_project_role"], "Member");
+
+    // The veteran joins before there is a policy and has an explicit role
+    app.add_team_member(&admin, team_id, &veteran, "Member").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", general_id),
+            &admin.access_token,
+            json!({ "user_id": veteran.id, "role": "Admin" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let path = format!("/api/teams/{}/settings", team_id);
+    let response = app
+        .put(&path, &admin.access_token, json!({ "auto_add_to_projects": "all", "project_ids": [general_id] }))
+        .await;
+    assert_eq!(response.status(), 400);
+    let other_team = app.create_team(&admin, "Elsewhere").await;
+    let foreign_id = app.create_project(&admin, other_team, "Foreign").await;
+    let response = app
+        .put(&path, &admin.access_token, json!({ "auto_add_to_projects": "selected", "project_ids": [foreign_id] }))
+        .await;
+    assert_eq!(response.status(), 400);
+
+    let response = app
+        .put(
+            &path,
+            &admin.access_token,
+            json!({ "auto_add_to_projects": "selected", "project_ids": [general_id], "default_project_role": "Editor" }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let settings: Value = response.json().await.unwrap();
+    assert_eq!(settings["project_ids"], json!([general_id]));
+
+    app.add_team_member(&admin, team_id, &joiner, "Member").await;
+    assert_eq!(role_in(&joiner, general_id).await, "Editor");
+    assert_eq!(role_in(&joiner, private_id).await, Value::Null);
+    // Changing the policy doesn't touch existing memberships
+    assert_eq!(role_in(&veteran, general_id).await, "Admin");
+
+    // New projects can take in the whole team; explicit roles still win
+    let response = app
+        .post(
+            &format!("/api/teams/{}/projects", team_id),
+            &admin.access_token,
+            json!({ "name": "Everyone", "team_id": team_id, "auto_add_members": true }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let everyone: Value = response.json().await.unwrap();
+    let everyone_id: Uuid = everyone["id"].as_str().unwrap().parse().unwrap();
+    assert_eq!(role_in(&admin, everyone_id).await, "Admin");
+    assert_eq!(role_in(&joiner, everyone_id).await, "Editor");
+    assert_eq!(role_in(&veteran, everyone_id).await, "Editor");
+}
+
+#[tokio::test]
+async fn test_create_task_duplicate_check() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("dupes").await;
+    let team_id = app.create_team(&owner, "Triage").await;
+    let project_id = app.create_project(&owner, team_id, "Bugs").await;
+    let original = app.create_task(&owner, project_id, "Login button does nothing on Safari").await;
+    let done = app.create_task(&owner, project_id, "Crash when uploading avatar").await;
+    let done_id = done["id"].as_str().unwrap();
+    let response = app
+        .post(
+            &format!("/api/tasks/{}/move", done_id),
+            &owner.access_token,
+            json!({ "task_id": done_id, "status": "Done", "position": 0 }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+
+    let path = format!("/api/projects/{}/tasks", project_id);
+    let response = app
+        .post(&path, &owner.access_token, json!({ "title": "Login button does nothing in Safari", "check_duplicates": true }))
+        .await;
+    assert_eq!(response.status(), 409);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "POSSIBLE_DUPLICATE");
+    assert_eq!(body["error"]["tasks"][0]["id"], original["id"]);
+    assert_eq!(body["error"]["tasks"][0]["number"], original["number"]);
+    assert_eq!(body["error"]["tasks"].as_array().unwrap().len(), 1);
+
+    // Unrelated titles and done tasks don't count
+    let response = app
+        .post(&path, &owner.access_token, json!({ "title": "Export board as CSV", "check_duplicates": true }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let response = app
+        .post(&path, &owner.access_token, json!({ "title": "Crash when uploading an avatar", "check_duplicates": true }))
+        .await;
+    assert_eq!(response.status(), 201);
+
+    // Without the flag nothing is checked, unless the project asks for it
+    let response = app.post(&path, &owner.access_token, json!({ "title": "Login button does nothing on Safari" })).await;
+    assert_eq!(response.status(), 201);
+    let response = app
+        .put(&format!("/api/projects/{}/workflow", project_id), &owner.access_token, json!({ "check_duplicates": true }))
+        .await;
+    assert_eq!(response.status(), 200);
+    let response = app.post(&path, &owner.access_token, json!({ "title": "Safari: login button does nothing" })).await;
+    assert_eq!(response.status(), 409);
+
+    let response = app
+        .post(&path, &owner.access_token, json!({ "title": "Safari: login button does nothing", "force": true }))
+        .await;
+    assert_eq!(response.status(), 201);
+}
+
+#[tokio::test]
+async fn test_onboarding_tasks_for_new_project_members() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("mentor").await;
+    let newcomer = app.register_user("newcomer").await;
+    let guest = app.register_user("guest").await;
+    let team_id = app.create_team(&admin, "Onboarding").await;
+    let project_id = app.create_project(&admin, team_id, "Platform").await;
+    app.add_team_member(&admin, team_id, &newcomer, "Member").await;
+    app.add_team_member(&admin, team_id, &guest, "Member").await;
+
+    let path = format!("/api/projects/{}/onboarding-template", project_id);
+    let response = app.get(&path, &admin.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.json::<Value>().await.unwrap()["tasks"], json!([]));
+
+    let response = app
+        .put(&path, &admin.access_token, json!({ "tasks": [{ "title": "x", "due_in_days": 1000 }] }))
+        .await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    let fields: Vec<&str> = body["error"]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, vec!["tasks[0].title", "tasks[0].due_in_days"]);
+
+    let template = json!({
+        "tasks": [
+            { "title": "Read the handbook", "tags": ["onboarding"], "due_in_days": 3 },
+            { "title": "Set up a dev environment", "priority": "High" }
+        ]
+    });
+    let response = app.put(&path, &admin.access_token, template).await;
+    assert_eq!(response.status(), 200);
+
+    let (mut socket, _) = connect_async(app.ws_url(&admin.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": project_id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+
+    let members_path = format!("/api/projects/{}/members", project_id);
+    let response = app
+        .post(&members_path, &admin.access_token, json!({ "user_id": newcomer.id, "role": "Member" }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let member: Value = response.json().await.unwrap();
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "MemberAdded");
+    assert_eq!(event["data"]["member"]["id"], newcomer.id.to_string());
+    let tasks = event["data"]["onboarding_tasks"].as_array().unwrap();
+    assert_eq!(tasks.len(), 2);
+    assert!(tasks.iter().all(|task| task["assigned_to"] == newcomer.id.to_string() && task["status"] == "Todo"));
+    let joined_on = chrono::DateTime::parse_from_rfc3339(member["joined_at"].as_str().unwrap()).unwrap().date_naive();
+    let due = chrono::DateTime::parse_from_rfc3339(tasks[0]["due_date"].as_str().unwrap()).unwrap().date_naive();
+    assert_eq!(due, joined_on + chrono::Duration::days(3));
+    assert_eq!(tasks[0]["is_all_day"], true);
+    assert_eq!(tasks[1]["due_date"], Value::Null);
+    assert_eq!(tasks[1]["priority"], "High");
+
+    // The newcomer can't change the template, and skipping creates nothing
+    let response = app.put(&path, &newcomer.access_token, json!({ "tasks": [] })).await;
+    assert_eq!(response.status(), 403);
+    let response = app
+        .post(&members_path, &admin.access_token, json!({ "user_id": guest.id, "role": "Viewer", "skip_onboarding": true }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "MemberAdded");
+    assert_eq!(event["data"]["onboarding_tasks"], json!([]));
+
+    let response = app.get(&format!("/api/projects/{}/tasks", project_id), &admin.access_token).await;
+    let tasks: Value = response.json().await.unwrap();
+    assert_eq!(tasks.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_board_viewers_presence() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("lookout").await;
+    let viewer = app.register_user("onlooker").await;
+    let outsider = app.register_user("passerby").await;
+    let team_id = app.create_team(&owner, "Presence").await;
+    let project_id = app.create_project(&owner, team_id, "Watched").await;
+    app.add_team_member(&owner, team_id, &viewer, "Member").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", project_id),
+            &owner.access_token,
+            json!({ "user_id": viewer.id, "role": "Member" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let mut board_ids = Vec::new();
+    for name in ["Sprint", "Backlog"] {
+        let response = app
+            .post(&format!("/api/projects/{}/boards", project_id), &owner.access_token, json!({ "name": name }))
+            .await;
+        assert_eq!(response.status(), 201);
+        let board: Value = response.json().await.unwrap();
+        board_ids.push(board["id"].as_str().unwrap().to_string());
+    }
+
+    let (mut owner_socket, _) = connect_async(app.ws_url(&owner.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut owner_socket).await["type"], "AuthenticationSuccess");
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": project_id } });
+    owner_socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert_eq!(next_event(&mut owner_socket).await["type"], "SubscriptionSuccess");
+
+    let (mut viewer_socket, _) = connect_async(app.ws_url(&viewer.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut viewer_socket).await["type"], "AuthenticationSuccess");
+    let viewing = |board_id: &str| json!({ "type": "ViewingBoard", "data": { "board_id": board_id } }).to_string();
+
+    viewer_socket.send(Message::Text(viewing(&board_ids[0]))).await.unwrap();
+    let event = next_event(&mut owner_socket).await;
+    assert_eq!(event["type"], "BoardViewersChanged");
+    assert_eq!(event["data"]["board_id"], board_ids[0]);
+    assert_eq!(event["data"]["user"]["id"], viewer.id.to_string());
+    assert_eq!(event["data"]["joined"], true);
+    assert_eq!(event["data"]["viewers"], json!([viewer.id]));
+
+    let board: Value = app.get(&format!("/api/boards/{}", board_ids[0]), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(board["viewers"].as_array().unwrap().len(), 1);
+    assert_eq!(board["viewers"][0]["id"], viewer.id.to_string());
+
+    // Switching boards leaves the old one before joining the new one
+    viewer_socket.send(Message::Text(viewing(&board_ids[1]))).await.unwrap();
+    let event = next_event(&mut owner_socket).await;
+    assert_eq!(event["data"]["board_id"], board_ids[0]);
+    assert_eq!(event["data"]["joined"], false);
+    assert_eq!(event["data"]["viewers"], json!([]));
+    let event = next_event(&mut owner_socket).await;
+    assert_eq!(event["data"]["board_id"], board_ids[1]);
+    assert_eq!(event["data"]["joined"], true);
+    assert_eq!(event["data"]["viewers"], json!([viewer.id]));
+
+    let board: Value = app.get(&format!("/api/boards/{}", board_ids[0]), &owner.access_token).await.json().await.unwrap();
+    assert!(board["viewers"].as_array().unwrap().is_empty());
+
+    // Boards of other projects are refused without closing the connection
+    let (mut outsider_socket, _) = connect_async(app.ws_url(&outsider.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut outsider_socket).await["type"], "AuthenticationSuccess");
+    outsider_socket.send(Message::Text(viewing(&board_ids[0]))).await.unwrap();
+    assert_eq!(next_event(&mut outsider_socket).await["type"], "Error");
+    outsider_socket.send(Message::Text(viewing(&Uuid::new_v4().to_string()))).await.unwrap();
+    assert_eq!(next_event(&mut outsider_socket).await["type"], "Error");
+
+    // Disconnecting leaves the board
+    viewer_socket.close(None).await.unwrap();
+    let event = next_event(&mut owner_socket).await;
+    assert_eq!(event["type"], "BoardViewersChanged");
+    assert_eq!(event["data"]["board_id"], board_ids[1]);
+    assert_eq!(event["data"]["joined"], false);
+    assert_eq!(event["data"]["viewers"], json!([]));
+}
+
+#[tokio::test]
+async fn test_scheduled_project_reports() {
+    use simplecards::jobs::{self, JobHandler};
+    use simplecards::reports::{self, ReportSchedulerJob};
+
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("reporter").await;
+    let outsider = app.register_user("notreported").await;
+    let team_id = app.create_team(&owner, "Reports").await;
+    let project_id = app.create_project(&owner, team_id, "Website").await;
+    let other_project_id = app.create_project(&owner, team_id, "Other").await;
+    let reports_path = format!("/api/projects/{}/reports", project_id);
+
+    let status_task = app.create_task(&owner, project_id, "Weekly status").await;
+    let status_task_id = status_task["id"].as_str().unwrap();
+    let other_task = app.create_task(&owner, other_project_id, "Elsewhere").await;
+    let done = app.create_task(&owner, project_id, "Launch page").await;
+    let response = app
+        .put(&format!("/api/tasks/{}", done["id"].as_str().unwrap()), &owner.access_token, json!({ "status": "Done" }))
+        .await;
+    assert_eq!(response.status(), 200);
+
+    let field_errors = |body: &Value| -> Vec<String> {
+        body["error"]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| error["field"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let response = app
+        .post(&reports_path, &owner.access_token, json!({ "name": "", "cadence": "daily", "sections": [], "hour": 25 }))
+        .await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(field_errors(&body), vec!["name", "sections", "hour", "recipients"]);
+
+    // Recipients have to belong to the project
+    let response = app
+        .post(
+            &reports_path,
+            &owner.access_token,
+            json!({
+                "name": "Status",
+                "cadence": "daily",
+                "sections": ["completed"],
+                "recipients": { "user_ids": [outsider.id], "comment_task_id": other_task["id"] }
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(field_errors(&body), vec!["recipients.user_ids", "recipients.comment_task_id"]);
+
+    let response = app
+        .post(
+            &reports_path,
+            &owner.access_token,
+            json!({
+                "name": "Status",
+                "cadence": "daily",
+                "hour": 0,
+                "sections": ["completed", "created", "overdue", "members"],
+                "recipients": { "user_ids": [owner.id], "comment_task_id": status_task_id }
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let report: Value = response.json().await.unwrap();
+    assert_eq!(report["weekday"], 5);
+    assert_eq!(report["timezone"], "UTC");
+    let report_id: Uuid = report["id"].as_str().unwrap().parse().unwrap();
+
+    let response = app.get(&format!("{}/{}/preview", reports_path, report_id), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let preview: Value = response.json().await.unwrap();
+    let markdown = preview["markdown"].as_str().unwrap();
+    assert!(markdown.starts_with("## Status: Website"));
+    assert!(markdown.contains("### Completed (1)"));
+    assert!(markdown.contains("Launch page"));
+    assert!(markdown.contains("### Newly created (2)"));
+
+    // Created yesterday, so today's run is due
+    sqlx::query("UPDATE project_reports SET created_at = NOW() - INTERVAL '2 days' WHERE id = $1")
+        .bind(report_id)
+        .execute(app.database.pool())
+        .await
+        .unwrap();
+    let handler = ReportSchedulerJob::new(app.database.clone(), app.websocket.clone());
+    let job = jobs::enqueue(app.database.pool(), reports::REPORT_SCHEDULER_JOB, &json!({})).await.unwrap();
+    handler.run(&job).await.unwrap();
+    // Already sent for today
+    handler.run(&job).await.unwrap();
+
+    let response = app.get(&format!("/api/tasks/{}/comments", status_task_id), &owner.access_token).await;
+    let comments: Value = response.json().await.unwrap();
+    let comments = comments.as_array().unwrap();
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0]["is_system"], true);
+    assert_eq!(comments[0]["pinned"], true);
+    assert!(comments[0]["content"].as_str().unwrap().starts_with("## Status: Website"));
+
+    let response = app.get(&reports_path, &owner.access_token).await;
+    let listed: Value = response.json().await.unwrap();
+    assert!(listed[0]["last_sent_at"].is_string());
+
+    let response = app.delete(&format!("{}/{}", reports_path, report_id), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let response = app.delete(&format!("{}/{}", reports_path, report_id), &owner.access_token).await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_convert_comment_to_task() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("converter").await;
+    let guest = app.register_user("convertguest").await;
+    let team_id = app.create_team(&owner, "Conversions").await;
+    app.add_team_member(&owner, team_id, &guest, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Website").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", project_id),
+            &owner.access_token,
+            json!({ "user_id": guest.id, "role": "Guest" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let source = app.create_task(&owner, project_id, "Plan the launch").await;
+    let source_id = source["id"].as_str().unwrap();
+
+    let response = app
+        .post(
+            &format!("/api/tasks/{}/comments", source_id),
+            &guest.access_token,
+            json!({ "content": "\nUpdate the pricing page\nThe old tiers are still listed." }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let comment: Value = response.json().await.unwrap();
+    let convert_path = format!("/api/comments/{}/convert-to-task", comment["id"].as_str().unwrap());
+
+    // Guests can comment but not create tasks
+    let response = app.post(&convert_path, &guest.access_token, json!({})).await;
+    assert_eq!(response.status(), 403);
+    let response = app.post(&convert_path, &owner.access_token, json!({ "assigned_to": Uuid::new_v4() })).await;
+    assert_eq!(response.status(), 400);
+
+    let (mut ws, _) = connect_async(app.ws_url(&owner.access_token)).await.unwrap();
+    ws.send(Message::Text(json!({ "type": "Subscribe", "data": { "project_id": project_id } }).to_string()))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let response = app.post(&convert_path, &owner.access_token, json!({ "assigned_to": owner.id })).await;
+    assert_eq!(response.status(), 201);
+    let task: Value = response.json().await.unwrap();
+    assert_eq!(task["title"], "Update the pricing page");
+    assert_eq!(task["description"], "\nUpdate the pricing page\nThe old tiers are still listed.");
+    assert_eq!(task["assigned_to"], owner.id.to_string());
+    let task_id = task["id"].as_str().unwrap();
+
+    let created = loop {
+        let event = next_event(&mut ws).await;
+        if event["type"] == "TaskCreated" {
+            break event;
+        }
+    };
+    assert_eq!(created["data"]["task"]["id"], task_id);
+
+    let response = app.get(&format!("/api/tasks/{}", task_id), &owner.access_token).await;
+    let details: Value = response.json().await.unwrap();
+    assert_eq!(details["relations"][0]["relation"], "relates_to");
+    assert_eq!(details["relations"][0]["task"]["id"], source_id);
+
+    for (id, expected) in [(source_id, "Converted a comment into task #"), (task_id, "Created from a comment on task #")] {
+        let response = app.get(&format!("/api/tasks/{}/comments", id), &owner.access_token).await;
+        let comments: Value = response.json().await.unwrap();
+        let note = comments.as_array().unwrap().last().unwrap();
+        assert_eq!(note["is_system"], true);
+        assert!(note["content"].as_str().unwrap().starts_with(expected));
+    }
+}
+
+#[tokio::test]
+async fn test_comment_and_task_context() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("linker").await;
+    let outsider = app.register_user("linkoutsider").await;
+    let team_id = app.create_team(&owner, "Links").await;
+    let project_id = app.create_project(&owner, team_id, "Website").await;
+    let task = app.create_task(&owner, project_id, "Launch page").await;
+    let task_id = task["id"].as_str().unwrap();
+    let comments_path = format!("/api/tasks/{}/comments", task_id);
+
+    let mut comment_ids = Vec::new();
+    for n in 0..23 {
+        let response = app.post(&comments_path, &owner.access_token, json!({ "content": format!("Comment {}", n) })).await;
+        assert_eq!(response.status(), 201);
+        let comment: Value = response.json().await.unwrap();
+        comment_ids.push(comment["id"].as_str().unwrap().to_string());
+    }
+
+    let response = app.get(&format!("/api/comments/{}/context", comment_ids[21]), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let context: Value = response.json().await.unwrap();
+    assert_eq!(context["comment"]["content"], "Comment 21");
+    assert_eq!(context["user"]["id"], owner.id.to_string());
+    assert_eq!(context["task"]["id"], task_id);
+    assert_eq!(context["task"]["title"], "Launch page");
+    assert_eq!(context["project_id"], project_id.to_string());
+    assert_eq!(context["offset"], 21);
+    assert_eq!(context["page"], 2);
+    assert_eq!(context["page_size"], 20);
+
+    // The page the context points at contains the comment
+    let response = app.get(&format!("{}?limit=20&offset=20", comments_path), &owner.access_token).await;
+    let page: Value = response.json().await.unwrap();
+    let page = page.as_array().unwrap();
+    assert_eq!(page.len(), 3);
+    assert_eq!(page[1]["id"], comment_ids[21].as_str());
+
+    let response = app.get(&format!("/api/comments/{}/context", comment_ids[0]), &outsider.access_token).await;
+    assert_eq!(response.status(), 404);
+    let response = app.get(&format!("/api/comments/{}/context", Uuid::new_v4()), &owner.access_token).await;
+    assert_eq!(response.status(), 404);
+
+    let response = app.put(&format!("/api/tasks/{}", task_id), &owner.access_token, json!({ "status": "InProgress" })).await;
+    assert_eq!(response.status(), 200);
+    let response = app.get(&format!("/api/projects/{}/boards", project_id), &owner.access_token).await;
+    let boards: Value = response.json().await.unwrap();
+
+    let response = app.get(&format!("/api/tasks/{}/context", task_id), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let context: Value = response.json().await.unwrap();
+    assert_eq!(context["project"]["id"], project_id.to_string());
+    assert_eq!(context["project"]["name"], "Website");
+    assert!(boards[0]["is_default"].as_bool().unwrap());
+    assert_eq!(context["board"]["id"], boards[0]["id"]);
+    assert_eq!(context["column_id"], "InProgress");
+
+    let response = app.get(&format!("/api/tasks/{}/context", task_id), &outsider.access_token).await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_team_and_project_counters() {
+    use simplecards::maintenance;
+
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("counter").await;
+    let member = app.register_user("countee").await;
+    let team_id = app.create_team(&owner, "Tally").await;
+    let project_id = app.create_project(&owner, team_id, "Ledger").await;
+    let trashed = app.create_project(&owner, team_id, "Scratch").await;
+    app.add_team_member(&owner, team_id, &member, "Member").await;
+
+    let mut task_ids = Vec::new();
+    for title in ["One", "Two", "Three"] {
+        let task = app.create_task(&owner, project_id, title).await;
+        task_ids.push(task["id"].as_str().unwrap().to_string());
+    }
+    let response = app.put(&format!("/api/tasks/{}", task_ids[0]), &owner.access_token, json!({ "status": "Done" })).await;
+    assert_eq!(response.status(), 200);
+    let response = app.delete(&format!("/api/tasks/{}", task_ids[2]), &owner.access_token).await;
+    assert!(response.status().is_success());
+    let response = app.delete(&format!("/api/projects/{}", trashed), &owner.access_token).await;
+    assert!(response.status().is_success());
+
+    let team: Value = app.get(&format!("/api/teams/{}", team_id), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(team["counts"], json!({ "member_count": 2, "project_count": 1 }));
+    let project: Value = app.get(&format!("/api/projects/{}", project_id), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(project["counts"], json!({ "member_count": 1, "task_count": 2, "open_task_count": 1 }));
+
+    let mut conn = app.database.pool().acquire().await.unwrap();
+    assert!(maintenance::counter_drift(&mut conn, Some(project_id)).await.unwrap().is_empty());
+
+    sqlx::query("UPDATE project_counters SET task_count = 7 WHERE project_id = $1")
+        .bind(project_id)
+        .execute(app.database.pool())
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM team_counters WHERE team_id = $1")
+        .bind(team_id)
+        .execute(app.database.pool())
+        .await
+        .unwrap();
+
+    let issues = maintenance::counter_drift(&mut conn, Some(project_id)).await.unwrap();
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[0].project_id, None);
+    assert_eq!(issues[0].subject_id, Some(team_id));
+    assert_eq!(issues[0].detail, "Team counters are missing");
+    assert_eq!(issues[1].project_id, Some(project_id));
+    assert_eq!(issues[1].detail, "task_count is 7 but should be 2");
+
+    let report = maintenance::repair(app.database.pool(), &app.config.attachments, Some(project_id), false).await.unwrap();
+    assert_eq!(report.issue_count, 2);
+    assert!(maintenance::counter_drift(&mut conn, Some(project_id)).await.unwrap().is_empty());
+    let team: Value = app.get(&format!("/api/teams/{}", team_id), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(team["counts"], json!({ "member_count": 2, "project_count": 1 }));
+}
+
+#[tokio::test]
+async fn test_board_yaml_export_and_import() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("exporter").await;
+    let outsider = app.register_user("exportoutsider").await;
+    let team_id = app.create_team(&owner, "Sharing").await;
+    let project_id = app.create_project(&owner, team_id, "Source").await;
+    let target_id = app.create_project(&owner, team_id, "Target").await;
+
+    let response = app
+        .post(
+            &format!("/api/projects/{}/boards", project_id),
+            &owner.access_token,
+            json!({
+                "name": "Bug triage",
+                "description": "Incoming: bugs # until scheduled",
+                "columns": ["New", "Investigating", "Fixing", "Done"],
+                "config": { "wip_mode": "Advisory", "wip_limits": [{ "column_id": "InProgress", "limit": 3 }] },
+                "swimlane_config": { "group_by": "priority", "lanes": [{ "key": "Critical", "collapsed": true }] }
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let board: Value = response.json().await.unwrap();
+    let board_id = board["id"].as_str().unwrap();
+
+    let export_path = format!("/api/boards/{}/export?format=yaml", board_id);
+    let response = app.get(&export_path, &outsider.access_token).await;
+    assert_eq!(response.status(), 404);
+    let response = app.get(&format!("/api/boards/{}/export?format=xml", board_id), &owner.access_token).await;
+    assert_eq!(response.status(), 400);
+
+    let response = app.get(&export_path, &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/yaml");
+    assert!(response.headers()["content-disposition"].to_str().unwrap().contains("bug-triage.yaml"));
+    let yaml = response.text().await.unwrap();
+    assert!(!yaml.contains(board_id));
+
+    let import = |body: String| {
+        app.client
+            .post(app.url(&format!("/api/projects/{}/boards/import", target_id)))
+            .bearer_auth(&owner.access_token)
+            .header("Content-Type", "application/yaml")
+            .body(body)
+            .send()
+    };
+    let response = import(yaml.clone()).await.unwrap();
+    assert_eq!(response.status(), 201);
+    let imported: Value = response.json().await.unwrap();
+    assert_eq!(imported["project_id"], target_id.to_string());
+    for field in ["name", "description", "columns", "config", "swimlane_config"] {
+        assert_eq!(imported[field], board[field], "{}", field);
+    }
+    let reexported = app
+        .get(&format!("/api/boards/{}/export", imported["id"].as_str().unwrap()), &owner.access_token)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(reexported, yaml);
+
+    // Every invalid value is reported under its path
+    let invalid = "version: 1\nname: Copy\ncolumns: [Todo, todo]\nconfig:\n  wip_limits:\n  - column_id: Todo\n    limit: 0\n";
+    let response = import(invalid.to_string()).await.unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    let fields: Vec<&str> = body["error"]["fields"].as_array().unwrap().iter().map(|f| f["field"].as_str().unwrap()).collect();
+    assert_eq!(fields, vec!["columns[1]", "config.wip_limits[0]"]);
+
+    let response = import("version: 1\nname: Copy\ncolumns: [Todo]\nswimlanes: {}\n".to_string()).await.unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["fields"][0]["field"], "yaml");
+    assert_eq!(body["error"]["fields"][0]["reason"]["line"], 4);
+}
+
+#[tokio::test]
+async fn test_stale_task_detection() {
+    use chrono::{Duration, Utc};
+    use simplecards::staleness;
+
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("staleowner").await;
+    let assignee = app.register_user("staleassignee").await;
+    let team_id = app.create_team(&owner, "Staleness").await;
+    app.add_team_member(&owner, team_id, &assignee, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Backlog").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", project_id),
+            &owner.access_token,
+            json!({ "user_id": assignee.id, "role": "Editor" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let rules_path = format!("/api/projects/{}/staleness", project_id);
+
+    let response = app
+        .put(
+            &rules_path,
+            &owner.access_token,
+            json!({ "enabled": true, "thresholds": [{ "status": "Done", "days": 3 }, { "status": "Todo", "days": 0 }] }),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    let fields: Vec<&str> = body["error"]["fields"].as_array().unwrap().iter().map(|f| f["field"].as_str().unwrap()).collect();
+    assert_eq!(fields, vec!["thresholds[0].status", "thresholds[1].days"]);
+
+    let rules = json!({
+        "enabled": true,
+        "thresholds": [{ "status": "Todo", "days": 21 }, { "status": "InProgress", "days": 7 }],
+        "post_comment": true
+    });
+    let response = app.put(&rules_path, &assignee.access_token, rules.clone()).await;
+    assert_eq!(response.status(), 403);
+    let response = app.put(&rules_path, &owner.access_token, rules.clone()).await;
+    assert_eq!(response.status(), 200);
+    let response = app.get(&rules_path, &assignee.access_token).await;
+    assert_eq!(response.json::<Value>().await.unwrap(), rules);
+
+    let waiting = app.create_task(&owner, project_id, "Waiting in Todo").await;
+    let waiting_id = waiting["id"].as_str().unwrap().to_string();
+    let response = app.put(&format!("/api/tasks/{}", waiting_id), &owner.access_token, json!({ "assigned_to": assignee.id })).await;
+    assert_eq!(response.status(), 200);
+    let started = app.create_task(&owner, project_id, "Started").await;
+    let started_id = started["id"].as_str().unwrap().to_string();
+    let response = app.put(&format!("/api/tasks/{}", started_id), &owner.access_token, json!({ "status": "InProgress" })).await;
+    assert_eq!(response.status(), 200);
+    let in_review = app.create_task(&owner, project_id, "In review").await;
+    let in_review_id = in_review["id"].as_str().unwrap().to_string();
+    let response = app.put(&format!("/api/tasks/{}", in_review_id), &owner.access_token, json!({ "status": "Review" })).await;
+    assert_eq!(response.status(), 200);
+
+    let (app, owner, assignee) = (&app, &owner, &assignee);
+    let pool = app.database.pool();
+    let now = Utc::now();
+    // How many of this project's tasks a scan marked; scans cover every project
+    // in the database
+    let scan = |days: i64| async move {
+        staleness::scan(pool, &app.websocket, now + Duration::days(days))
+            .await
+            .map(|marked| marked.get(&project_id).copied().unwrap_or(0))
+    };
+    let stale_ids = |stale: bool| {
+        let path = format!("/api/projects/{}/tasks?stale={}", project_id, stale);
+        async move {
+            let tasks: Value = app.get(&path, &owner.access_token).await.json().await.unwrap();
+            let mut ids: Vec<String> = tasks.as_array().unwrap().iter().map(|t| t["id"].as_str().unwrap().to_string()).collect();
+            ids.sort();
+            ids
+        }
+    };
+    let sorted = |mut ids: Vec<String>| {
+        ids.sort();
+        ids
+    };
+    let stale_notifications = || async move {
+        let notifications: Value = app.get("/api/users/me/notifications", &assignee.access_token).await.json().await.unwrap();
+        notifications.as_array().unwrap().iter().filter(|n| n["kind"] == "TaskStale").cloned().collect::<Vec<Value>>()
+    };
+    let comments = |task_id: &str| {
+        let path = format!("/api/tasks/{}/comments", task_id);
+        async move {
+            let comments: Value = app.get(&path, &owner.access_token).await.json().await.unwrap();
+            comments.as_array().unwrap().clone()
+        }
+    };
+
+    // Only InProgress has passed its threshold after 8 days
+    assert_eq!(scan(6).await.unwrap(), 0);
+    assert!(stale_ids(true).await.is_empty());
+    assert_eq!(scan(8).await.unwrap(), 1);
+    assert_eq!(stale_ids(true).await, vec![started_id.clone()]);
+    // Unassigned, so nobody is notified, but the comment is posted
+    assert!(stale_notifications().await.is_empty());
+    assert_eq!(comments(&started_id).await.len(), 1);
+
+    assert_eq!(scan(22).await.unwrap(), 1);
+    assert_eq!(stale_ids(true).await, sorted(vec![waiting_id.clone(), started_id.clone()]));
+    assert_eq!(stale_ids(false).await, vec![in_review_id.clone()]);
+    let notifications = stale_notifications().await;
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0]["task_id"], waiting_id.as_str());
+    assert!(notifications[0]["actor_id"].is_null());
+    let waiting_comments = comments(&waiting_id).await;
+    assert_eq!(waiting_comments.len(), 1);
+    assert_eq!(waiting_comments[0]["is_system"], true);
+    assert!(waiting_comments[0]["content"].as_str().unwrap().contains("in Todo for 22 days"));
+
+    // Tasks that stay stale are only nudged once
+    assert_eq!(scan(23).await.unwrap(), 0);
+    assert_eq!(stale_notifications().await.len(), 1);
+    assert_eq!(comments(&waiting_id).await.len(), 1);
+
+    let tasks: Value = app.get(&format!("/api/projects/{}/tasks?fields=id,stale", project_id), &owner.access_token).await.json().await.unwrap();
+    let waiting_item = tasks.as_array().unwrap().iter().find(|t| t["id"] == waiting_id.as_str()).unwrap();
+    assert_eq!(waiting_item, &json!({ "id": waiting_id.as_str(), "stale": true }));
+
+    // A status change ends staleness right away and restarts the clock
+    let response = app.put(&format!("/api/tasks/{}", waiting_id), &assignee.access_token, json!({ "status": "InProgress" })).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(stale_ids(true).await, vec![started_id.clone()]);
+    scan(3).await.unwrap();
+    assert!(!stale_ids(true).await.contains(&waiting_id));
+
+    // Turning detection off unmarks everything at the next scan
+    let response = app.put(&rules_path, &owner.access_token, json!({ "enabled": false })).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(scan(30).await.unwrap(), 0);
+    assert!(stale_ids(true).await.is_empty());
+    assert_eq!(stale_notifications().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_publish_and_use_project_templates() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("templater").await;
+    let outsider = app.register_user("templateuser").await;
+    let admin = app.register_user("templateadmin").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id)
+        .await
+        .unwrap();
+    let team_id = app.create_team(&owner, "Templates").await;
+    let other_team_id = app.create_team(&owner, "Elsewhere").await;
+    let outsider_team_id = app.create_team(&outsider, "Outside").await;
+    let project_id = app.create_project(&owner, team_id, "Sprint setup").await;
+
+    let workflow = json!({ "transitions": [{ "from": "Todo", "to": ["InProgress"] }], "check_duplicates": true });
+    let response = app.put(&format!("/api/projects/{}/workflow", project_id), &owner.access_token, workflow.clone()).await;
+    assert_eq!(response.status(), 200);
+    let response = app
+        .post(
+            &format!("/api/projects/{}/boards", project_id),
+            &owner.access_token,
+            json!({ "name": "QA", "columns": ["Testing", "Verified"] }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let task = app.create_task(&owner, project_id, "Not part of the template").await;
+    assert!(task["id"].is_string());
+
+    let (app, owner) = (&app, &owner);
+    let publish_path = format!("/api/projects/{}/publish-template", project_id);
+    let publish = |body: Value| {
+        let path = publish_path.clone();
+        async move { app.post(&path, &owner.access_token, body).await }
+    };
+    let response = publish(json!({ "name": " " })).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["fields"][0]["field"], "name");
+
+    let response = publish(json!({ "name": "Sprint", "description": "Two boards" })).await;
+    assert_eq!(response.status(), 201);
+    let team_template: Value = response.json().await.unwrap();
+    assert_eq!(team_template["visibility"], "team");
+    assert!(team_template["approved_at"].is_string());
+    let team_template_id = team_template["id"].as_str().unwrap().to_string();
+
+    // Later edits to the source don't change the snapshot
+    let response = app.put(&format!("/api/projects/{}/workflow", project_id), &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 200);
+
+    let create_from = |user: &TestUser, team_id: Uuid, template_id: &str| {
+        let token = user.access_token.clone();
+        let body = json!({ "name": "From template", "team_id": team_id, "template_id": template_id });
+        async move { app.post(&format!("/api/teams/{}/projects", team_id), &token, body).await }
+    };
+    let response = create_from(owner, other_team_id, &team_template_id).await;
+    assert_eq!(response.status(), 201);
+    let created: Value = response.json().await.unwrap();
+    assert!(created["description"].is_null());
+    let created_id = created["id"].as_str().unwrap();
+
+    let response = app.get(&format!("/api/projects/{}/workflow", created_id), &owner.access_token).await;
+    assert_eq!(response.json::<Value>().await.unwrap(), workflow);
+    let source_boards: Value = app.get(&format!("/api/projects/{}/boards", project_id), &owner.access_token).await.json().await.unwrap();
+    let boards: Value = app.get(&format!("/api/projects/{}/boards", created_id), &owner.access_token).await.json().await.unwrap();
+    let boards = boards.as_array().unwrap();
+    assert_eq!(boards.len(), 2);
+    for (board, source) in boards.iter().zip(source_boards.as_array().unwrap()) {
+        for field in ["name", "columns", "config", "is_default"] {
+            assert_eq!(board[field], source[field], "{}", field);
+        }
+    }
+    let tasks: Value = app.get(&format!("/api/projects/{}/tasks", created_id), &owner.access_token).await.json().await.unwrap();
+    assert!(tasks.as_array().unwrap().is_empty());
+
+    // Team templates are for the team's members only
+    let ids = |templates: Value| -> Vec<String> {
+        templates.as_array().unwrap().iter().map(|t| t["id"].as_str().unwrap().to_string()).collect()
+    };
+    let templates: Value = app.get("/api/templates", &outsider.access_token).await.json().await.unwrap();
+    assert!(ids(templates).is_empty());
+    let response = create_from(&outsider, outsider_team_id, &team_template_id).await;
+    assert_eq!(response.status(), 404);
+
+    // Instance templates wait for an instance admin
+    let response = publish(json!({ "name": "Company standard", "visibility": "instance" })).await;
+    assert_eq!(response.status(), 201);
+    let instance_template: Value = response.json().await.unwrap();
+    assert!(instance_template["approved_at"].is_null());
+    let instance_template_id = instance_template["id"].as_str().unwrap().to_string();
+
+    let templates: Value = app.get("/api/templates", &owner.access_token).await.json().await.unwrap();
+    assert_eq!(ids(templates), vec![instance_template_id.clone(), team_template_id.clone()]);
+    let response = create_from(owner, team_id, &instance_template_id).await;
+    assert_eq!(response.status(), 404);
+
+    let approve_path = format!("/api/admin/templates/{}/approve", instance_template_id);
+    let response = app.post(&approve_path, &owner.access_token, json!({})).await;
+    assert_eq!(response.status(), 403);
+    let pending: Value = app.get("/api/admin/templates", &admin.access_token).await.json().await.unwrap();
+    assert_eq!(ids(pending), vec![instance_template_id.clone()]);
+    let response = app.post(&approve_path, &admin.access_token, json!({})).await;
+    assert_eq!(response.status(), 200);
+    let response = app.post(&approve_path, &admin.access_token, json!({})).await;
+    assert_eq!(response.status(), 404);
+
+    let templates: Value = app.get("/api/templates", &outsider.access_token).await.json().await.unwrap();
+    assert_eq!(ids(templates), vec![instance_template_id.clone()]);
+    let response = create_from(&outsider, outsider_team_id, &instance_template_id).await;
+    assert_eq!(response.status(), 201);
+
+    // Team admins and instance admins can unpublish
+    let response = app.delete(&format!("/api/templates/{}", team_template_id), &outsider.access_token).await;
+    assert_eq!(response.status(), 403);
+    let response = app.delete(&format!("/api/templates/{}", team_template_id), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let response = app.delete(&format!("/api/templates/{}", instance_template_id), &admin.access_token).await;
+    assert_eq!(response.status(), 204);
+    let templates: Value = app.get("/api/templates", &owner.access_token).await.json().await.unwrap();
+    assert!(ids(templates).is_empty());
+}
+
+#[tokio::test]
+async fn test_merge_duplicate_account() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("mergeowner").await;
+    let user = app.register_user("mergeuser").await;
+    let duplicate = app.register_user("mergedup").await;
+    let admin = app.register_user("mergeadmin").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id)
+        .await
+        .unwrap();
+
+    // Both accounts are in the first team and project, with different roles
+    let team_id = app.create_team(&owner, "Shared").await;
+    app.add_team_member(&owner, team_id, &user, "Member").await;
+    app.add_team_member(&owner, team_id, &duplicate, "Admin").await;
+    let project_id = app.create_project(&owner, team_id, "Shared project").await;
+    for (member, role) in [(&user, "Editor"), (&duplicate, "Viewer")] {
+        let response = app
+            .post(
+                &format!("/api/projects/{}/members", project_id),
+                &owner.access_token,
+                json!({ "user_id": member.id, "role": role }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+    // Only the duplicate is in the second team, and owns a project there
+    let other_team_id = app.create_team(&owner, "Elsewhere").await;
+    app.add_team_member(&owner, other_team_id, &duplicate, "Member").await;
+    let own_project_id = app.create_project(&duplicate, other_team_id, "Side project").await;
+
+    let response = app
+        .post(
+            &format!("/api/projects/{}/tasks", own_project_id),
+            &duplicate.access_token,
+            json!({ "title": "Follow up", "assigned_to": duplicate.id }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let task: Value = response.json().await.unwrap();
+    let task_id = task["id"].as_str().unwrap();
+    let response = app
+        .post(&format!("/api/tasks/{}/comments", task_id), &duplicate.access_token, json!({ "content": "On it" }))
+        .await;
+    assert_eq!(response.status(), 201);
+
+    // Control of the other account has to be proven
+    let merge = |email: &str, password: &str| {
+        app.post("/api/users/me/merge", &user.access_token, json!({ "email": email, "password": password }))
+    };
+    let response = merge(&duplicate.email, "WrongPassword1!").await;
+    assert_eq!(response.status(), 403);
+    let response = merge(&user.email, TEST_PASSWORD).await;
+    assert_eq!(response.status(), 400);
+
+    let response = merge(&duplicate.email, TEST_PASSWORD).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["merged_user_id"], duplicate.id.to_string());
+    assert_eq!(body["team_memberships"], 1);
+    assert_eq!(body["project_memberships"], 1);
+    assert_eq!(body["assigned_tasks"], 1);
+    assert_eq!(body["comments"], 1);
+    assert_eq!(
+        body["team_conflicts"],
+        json!([{ "id": team_id, "kept_role": "Admin", "dropped_role": "Member" }])
+    );
+    assert_eq!(
+        body["project_conflicts"],
+        json!([{ "id": project_id, "kept_role": "Editor", "dropped_role": "Viewer" }])
+    );
+
+    // The higher role is kept and the duplicate's memberships are gone
+    let team: Value = app.get(&format!("/api/teams/{}", team_id), &user.access_token).await.json().await.unwrap();
+    let members = team["members"].as_array().unwrap();
+    assert_eq!(members.len(), 2);
+    assert_eq!(team["counts"]["member_count"], 2);
+    let membership = members.iter().find(|member| member["user"]["id"] == user.id.to_string()).unwrap();
+    assert_eq!(membership["role"], "Admin");
+    let response = app.get(&format!("/api/projects/{}/members", project_id), &user.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["total"], 2);
+    let response = app.get(&format!("/api/projects/{}/members?role=Editor", project_id), &user.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["members"][0]["user"]["id"], user.id.to_string());
+
+    let response = app.get(&format!("/api/teams/{}", other_team_id), &user.access_token).await;
+    assert_eq!(response.status(), 200);
+    let response = app.get(&format!("/api/tasks/{}", task_id), &user.access_token).await;
+    let task: Value = response.json().await.unwrap();
+    assert_eq!(task["assigned_to"], user.id.to_string());
+    let response = app.get(&format!("/api/tasks/{}/comments", task_id), &user.access_token).await;
+    let comments: Value = response.json().await.unwrap();
+    assert!(comments.to_string().contains(&user.id.to_string()));
+
+    // The duplicate is deactivated and can't be merged again
+    let response = app
+        .post_public("/api/auth/login", json!({ "email": duplicate.email, "password": TEST_PASSWORD }))
+        .await;
+    assert_eq!(response.status(), 401);
+    let response = merge(&duplicate.email, TEST_PASSWORD).await;
+    assert_eq!(response.status(), 403);
+
+    let response = app.get("/api/admin/audit-log?limit=500", &admin.access_token).await;
+    let entries: Value = response.json().await.unwrap();
+    assert!(entries.as_array().unwrap().iter().any(|entry| entry["action"] == "AccountsMerged"
+        && entry["actor_id"] == user.id.to_string()
+        && entry["subject_id"] == duplicate.id.to_string()));
+}
+
+#[tokio::test]
+async fn test_details_embed_a_limited_member_list() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("bigteam").await;
+    let team_id = app.create_team(&owner, "Big team").await;
+    let project_id = app.create_project(&owner, team_id, "Big project").await;
+
+    let (app, owner) = (&app, &owner);
+    let add_members = |count: usize| async move {
+        for _ in 0..count {
+            let user = app.register_user("crowd").await;
+            app.add_team_member(owner, team_id, &user, "Member").await;
+            let response = app
+                .post(
+                    &format!("/api/projects/{}/members", project_id),
+                    &owner.access_token,
+                    json!({ "user_id": user.id, "role": "Member" }),
+                )
+                .await;
+            assert_eq!(response.status(), 201);
+        }
+    };
+    let team_path = format!("/api/teams/{}", team_id);
+    let project_path = format!("/api/projects/{}", project_id);
+    let body_size = |path: String| async move { app.get(&path, &owner.access_token).await.bytes().await.unwrap().len() };
+
+    add_members(11).await;
+    let team_size = body_size(team_path.clone()).await;
+    let project_size = body_size(project_path.clone()).await;
+
+    // Past the embedded members, a bigger team doesn't make the details bigger.
+    // Only timestamp precision may differ between the embedded members.
+    add_members(4).await;
+    assert!(body_size(team_path.clone()).await.abs_diff(team_size) < 64);
+    assert!(body_size(project_path.clone()).await.abs_diff(project_size) < 64);
+
+    for path in [&team_path, &project_path] {
+        let body: Value = app.get(path, &owner.access_token).await.json().await.unwrap();
+        assert_eq!(body["member_count"], 16);
+        assert_eq!(body["members"].as_array().unwrap().len(), 10);
+        assert_eq!(body["members"][0]["user"]["id"], owner.id.to_string());
+
+        let response = app.get(&format!("{}?embed=members", path), &owner.access_token).await;
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["member_count"], 16);
+        assert_eq!(body["members"].as_array().unwrap().len(), 16);
+
+        let response = app.get(&format!("{}?embed=everything", path), &owner.access_token).await;
+        assert_eq!(response.status(), 400);
+    }
+
+    // The rest come from the paginated members endpoints
+    let response = app.get(&format!("{}/members?page=2&per_page=10", team_path), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["total"], 16);
+    assert_eq!(body["members"].as_array().unwrap().len(), 6);
+    let response = app.get(&format!("{}/members?role=Admin", team_path), &owner.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["total"], 1);
+    let response = app.get(&format!("{}/members?page=2&per_page=10", project_path), &owner.access_token).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["members"].as_array().unwrap().len(), 6);
+}
+
+#[tokio::test]
+async fn test_event_schemas_and_test_delivery() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("integrator").await;
+    let outsider = app.register_user("eventoutsider").await;
+    let team_id = app.create_team(&owner, "Integrations").await;
+    let project_id = app.create_project(&owner, team_id, "Sandbox").await;
+
+    let response = app.get("/api/events/schemas", &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let registry: Value = response.json().await.unwrap();
+    let events = registry["events"].as_array().unwrap();
+    let created = events.iter().find(|event| event["type"] == "TaskCreated").unwrap();
+    assert_eq!(created["sent_by"], "server");
+    assert_eq!(created["schema"]["properties"]["data"]["$ref"], "#/definitions/TaskEventData");
+    assert!(registry["definitions"]["Task"]["properties"]["title"].is_object());
+    let subscribe = events.iter().find(|event| event["type"] == "Subscribe").unwrap();
+    assert_eq!(subscribe["sent_by"], "client");
+
+    let (mut socket, _) = connect_async(app.ws_url(&owner.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+
+    let path = format!("/api/projects/{}/events/test", project_id);
+    let response = app.post(&path, &owner.access_token, json!({ "type": "TaskCreated" })).await;
+    assert_eq!(response.status(), 200);
+    let sent: Value = response.json().await.unwrap();
+    assert_eq!(sent["type"], "TaskCreated");
+    assert_eq!(sent["data"]["task"]["project_id"], project_id.to_string());
+    assert_eq!(sent["data"]["user"]["id"], owner.id.to_string());
+    assert_eq!(next_event(&mut socket).await, sent);
+
+    // No real task was created
+    let tasks: Value = app.get(&format!("/api/projects/{}/tasks", project_id), &owner.access_token).await.json().await.unwrap();
+    assert!(tasks.as_array().unwrap().is_empty());
+
+    for event_type in ["Subscribe", "TaskExploded"] {
+        let response = app.post(&path, &owner.access_token, json!({ "type": event_type })).await;
+        assert_eq!(response.status(), 400);
+    }
+    let response = app.post(&path, &outsider.access_token, json!({ "type": "TaskCreated" })).await;
+    assert_eq!(response.status(), 403);
+}
+
+#[tokio::test]
+async fn test_mentions_and_group_mentions() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("mentionowner").await;
+    let editor = app.register_user("mentioneditor").await;
+    let member = app.register_user("mentionmember").await;
+
+    let team_id = app.create_team(&owner, "Mentions").await;
+    app.add_team_member(&owner, team_id, &editor, "Member").await;
+    app.add_team_member(&owner, team_id, &member, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Mentions").await;
+    for (user, role) in [(&editor, "Editor"), (&member, "Member")] {
+        let response = app
+            .post(
+                &format!("/api/projects/{}/members", project_id),
+                &owner.access_token,
+                json!({ "user_id": user.id, "role": role }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+
+    let response = app
+        .post(
+            &format!("/api/projects/{}/tasks", project_id),
+            &owner.access_token,
+            json!({ "title": "Release", "description": format!("@{} please review", member.username), "assigned_to": member.id }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let task: Value = response.json().await.unwrap();
+    let task_id = task["id"].as_str().unwrap().to_string();
+    let comments_path = format!("/api/tasks/{}/comments", task_id);
+    let mentions_path = format!("/api/tasks/{}/mentions", task_id);
+
+    // Members can't use group mentions by default
+    let response = app.post(&comments_path, &member.access_token, json!({ "content": "@project ready?" })).await;
+    assert_eq!(response.status(), 403);
+
+    let content = format!("@project @{} heads up", owner.username);
+    let response = app.post(&comments_path, &editor.access_token, json!({ "content": content })).await;
+    assert_eq!(response.status(), 201);
+    let comment: Value = response.json().await.unwrap();
+
+    // The description mention, then the comment's: the owner by name and the
+    // member through @project, without the author
+    let response = app.get(&mentions_path, &member.access_token).await;
+    assert_eq!(response.status(), 200);
+    let mentions: Vec<Value> = response.json().await.unwrap();
+    assert_eq!(mentions.len(), 3);
+    assert_eq!(mentions[0]["user"]["id"], member.id.to_string());
+    assert!(mentions[0]["comment_id"].is_null());
+    let from_comment: Vec<(&str, &Value)> = mentions[1..]
+        .iter()
+        .map(|mention| {
+            assert_eq!(mention["comment_id"], comment["id"]);
+            (mention["user"]["username"].as_str().unwrap(), &mention["group_token"])
+        })
+        .collect();
+    assert!(from_comment.contains(&(owner.username.as_str(), &Value::Null)));
+    assert!(from_comment.contains(&(member.username.as_str(), &json!("project"))));
+
+    let response = app.get("/api/users/me/notifications", &member.access_token).await;
+    let notifications: Vec<Value> = response.json().await.unwrap();
+    let mentioned = notifications.iter().filter(|n| n["kind"] == "TaskMentioned").count();
+    assert_eq!(mentioned, 2);
+
+    // Editing the description only notifies users it newly mentions
+    let response = app
+        .put(
+            &format!("/api/tasks/{}", task_id),
+            &owner.access_token,
+            json!({ "description": format!("@{} please review, thanks", member.username) }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let response = app.get(&mentions_path, &member.access_token).await;
+    let mentions: Vec<Value> = response.json().await.unwrap();
+    assert_eq!(mentions.len(), 3);
+
+    // Admins choose who may use group mentions
+    let settings_path = format!("/api/projects/{}/mention-settings", project_id);
+    let response = app.put(&settings_path, &editor.access_token, json!({ "group_mention_role": "Member" })).await;
+    assert_eq!(response.status(), 403);
+    let response = app.put(&settings_path, &owner.access_token, json!({ "group_mention_role": "Member" })).await;
+    assert_eq!(response.status(), 200);
+    let response = app.get(&settings_path, &member.access_token).await;
+    let settings: Value = response.json().await.unwrap();
+    assert_eq!(settings["group_mention_role"], "Member");
+    // Only reaches the assignee, who is the author
+    let response = app.post(&comments_path, &member.access_token, json!({ "content": "@assignees on it" })).await;
+    assert_eq!(response.status(), 201);
+
+    // A few group mentions per hour and user
+    for _ in 0..4 {
+        let response = app.post(&comments_path, &editor.access_token, json!({ "content": "@project again" })).await;
+        assert_eq!(response.status(), 201);
+    }
+    let response = app.post(&comments_path, &editor.access_token, json!({ "content": "@project again" })).await;
+    assert_eq!(response.status(), 429);
+    // Individual mentions are not limited
+    let content = format!("@{} only you", member.username);
+    let response = app.post(&comments_path, &editor.access_token, json!({ "content": content })).await;
+    assert_eq!(response.status(), 201);
+}
+
+#[tokio::test]
+async fn test_weekly_agenda() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("agenda").await;
+    let team_id = app.create_team(&user, "Agenda").await;
+    let project_id = app.create_project(&user, team_id, "Planning").await;
+
+    let tasks_path = format!("/api/projects/{}/tasks", project_id);
+    for (title, due_date) in [
+        ("Write agenda report", "2024-06-05"),
+        ("Review (budget)", "2024-06-07T09:00:00Z"),
+        ("Outside the week", "2024-06-12"),
+    ] {
+        let response = app
+            .post(
+                &tasks_path,
+                &user.access_token,
+                json!({ "title": title, "assigned_to": user.id, "due_date": due_date, "priority": "High" }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+
+    let response = app.get("/api/users/me/agenda.pdf?week=2024-W23", &user.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/pdf");
+    assert_eq!(response.headers()["cache-control"], "private, max-age=3600");
+    let pdf = response.bytes().await.unwrap();
+    assert!(pdf.starts_with(b"%PDF-"));
+    assert!(pdf.ends_with(b"%%EOF\n"));
+    // The cross-reference table points at the objects
+    let text = String::from_utf8_lossy(&pdf);
+    let startxref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+    assert!(pdf[startxref..].starts_with(b"xref\n"));
+    for (number, entry) in String::from_utf8_lossy(&pdf[startxref..]).lines().skip(3).take_while(|line| !line.starts_with("trailer")).enumerate() {
+        let offset: usize = entry[..10].parse().unwrap();
+        assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", number + 1).as_bytes()));
+    }
+    assert!(text.contains("/Count 1"));
+    let contains = |needle: &str| pdf.windows(needle.len()).any(|window| window == needle.as_bytes());
+    assert!(contains("Write agenda report \\(Planning, High\\)"));
+    assert!(contains("09:00  Review \\(budget\\)"));
+    assert!(!contains("Outside the week"));
+
+    let response = app.get("/api/users/me/agenda.pdf?week=2024-W23&format=html", &user.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/html; charset=utf-8");
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<h2>Wednesday, June 5</h2>"));
+    assert!(html.contains("Review (budget)"));
+
+    // Served from the cache for an hour
+    let response = app
+        .post(
+            &tasks_path,
+            &user.access_token,
+            json!({ "title": "Added later", "assigned_to": user.id, "due_date": "2024-06-04" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let response = app.get("/api/users/me/agenda.pdf?week=2024-W23", &user.access_token).await;
+    let cached = response.bytes().await.unwrap();
+    assert_eq!(cached, pdf);
+
+    for query in ["week=2024-W99", "week=2024-06-03", "week=2024-W23&format=docx"] {
+        let response = app.get(&format!("/api/users/me/agenda.pdf?{}", query), &user.access_token).await;
+        assert_eq!(response.status(), 400, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn test_board_automation_rules() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("autoowner").await;
+    let alice = app.register_user("autoalice").await;
+    let bob = app.register_user("autobob").await;
+    let team_id = app.create_team(&owner, "Automations").await;
+    let project_id = app.create_project(&owner, team_id, "QA").await;
+    for user in [&alice, &bob] {
+        app.add_team_member(&owner, team_id, user, "Member").await;
+        let response = app
+            .post(&format!("/api/projects/{}/members", project_id), &owner.access_token, json!({ "user_id": user.id, "role": "Editor" }))
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+    let response = app
+        .post(&format!("/api/projects/{}/boards", project_id), &owner.access_token, json!({ "name": "Handoff" }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let board: Value = response.json().await.unwrap();
+    let automations_path = format!("/api/boards/{}/automations", board["id"].as_str().unwrap());
+
+    let outsider = Uuid::new_v4();
+    let response = app
+        .put(
+            &automations_path,
+            &owner.access_token,
+            json!({ "rules": [{
+                "name": "QA handoff",
+                "trigger": { "type": "moved_to_column", "column_id": "Review" },
+                "actions": [{ "type": "assign_round_robin", "user_ids": [alice.id, outsider] }]
+            }] }),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["fields"][0]["field"], "rules[0].actions[0].user_ids");
+
+    let rules = json!({ "rules": [{
+        "name": "QA handoff",
+        "trigger": { "type": "moved_to_column", "column_id": "Review" },
+        "actions": [
+            { "type": "assign_round_robin", "user_ids": [alice.id, bob.id] },
+            { "type": "add_label", "label": "needs-review" },
+            { "type": "set_priority", "priority": "High" },
+            { "type": "post_system_comment", "content": "Ready for QA" }
+        ]
+    }] });
+    let response = app.put(&automations_path, &alice.access_token, rules.clone()).await;
+    assert_eq!(response.status(), 403);
+    let response = app.put(&automations_path, &owner.access_token, rules).await;
+    assert_eq!(response.status(), 200);
+    let saved: Value = response.json().await.unwrap();
+    assert!(saved["rules"][0]["id"].is_string());
+    let response = app.get(&automations_path, &bob.access_token).await;
+    assert_eq!(response.json::<Value>().await.unwrap(), saved);
+
+    let move_to = |task_id: String, status: &'static str| {
+        let (app, token) = (&app, owner.access_token.clone());
+        async move {
+            let response = app
+                .post(&format!("/api/tasks/{}/move", task_id), &token, json!({ "task_id": task_id, "status": status, "position": 0 }))
+                .await;
+            assert_eq!(response.status(), 200);
+            response.json::<Value>().await.unwrap()
+        }
+    };
+    let comments = |task_id: String| {
+        let (app, token) = (&app, owner.access_token.clone());
+        async move {
+            let comments: Value = app.get(&format!("/api/tasks/{}/comments", task_id), &token).await.json().await.unwrap();
+            comments.as_array().unwrap().clone()
+        }
+    };
+
+    let first = app.create_task(&owner, project_id, "Check login").await;
+    let first_id = first["id"].as_str().unwrap().to_string();
+    let moved = move_to(first_id.clone(), "Review").await;
+    assert_eq!(moved["assigned_to"], json!(alice.id));
+    assert_eq!(moved["tags"], json!(["needs-review"]));
+    assert_eq!(moved["priority"], "High");
+    let history = comments(first_id.clone()).await;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0]["is_system"], true);
+    let content = history[0]["content"].as_str().unwrap();
+    assert!(content.starts_with("Ready for QA\n\nAutomation \"QA handoff\": assigned to"), "{}", content);
+    assert!(content.ends_with("added label \"needs-review\", set priority to High."), "{}", content);
+
+    // Moving back in changes nothing that is already done
+    move_to(first_id.clone(), "InProgress").await;
+    let moved = move_to(first_id.clone(), "Review").await;
+    assert_eq!(moved["assigned_to"], json!(alice.id));
+    assert_eq!(moved["tags"], json!(["needs-review"]));
+    assert_eq!(comments(first_id.clone()).await.len(), 1);
+
+    // Lowering the priority again only sets the priority back
+    let response = app.put(&format!("/api/tasks/{}", first_id), &owner.access_token, json!({ "priority": "Low", "status": "Todo" })).await;
+    assert_eq!(response.status(), 200);
+    move_to(first_id.clone(), "Review").await;
+    let history = comments(first_id.clone()).await;
+    assert_eq!(history.len(), 2);
+    assert!(history.iter().any(|c| c["content"] == "Automation \"QA handoff\": set priority to High."));
+
+    let second = app.create_task(&owner, project_id, "Check signup").await;
+    let moved = move_to(second["id"].as_str().unwrap().to_string(), "Review").await;
+    assert_eq!(moved["assigned_to"], json!(bob.id));
+}
+
+#[tokio::test]
+async fn test_cycle_time_analytics() {
+    use chrono::{Duration, TimeZone, Utc};
+
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("cycleowner").await;
+    let team_id = app.create_team(&owner, "Cycle time").await;
+    let project_id = app.create_project(&owner, team_id, "Delivery").await;
+    let pool = app.database.pool();
+
+    let moved = app.create_task(&owner, project_id, "Moved across the board").await;
+    let moved_id: Uuid = moved["id"].as_str().unwrap().parse().unwrap();
+    for status in ["InProgress", "Review", "Done"] {
+        let response = app
+            .post(
+                &format!("/api/tasks/{}/move", moved_id),
+                &owner.access_token,
+                json!({ "task_id": moved_id, "status": status, "position": 0 }),
+            )
+            .await;
+        assert_eq!(response.status(), 200);
+    }
+    let closed = app.create_task(&owner, project_id, "Closed right away").await;
+    let closed_id: Uuid = closed["id"].as_str().unwrap().parse().unwrap();
+    let response = app.put(&format!("/api/tasks/{}", closed_id), &owner.access_token, json!({ "status": "Done" })).await;
+    assert_eq!(response.status(), 200);
+    let open = app.create_task(&owner, project_id, "Still open").await;
+    let response = app.put(&format!("/api/tasks/{}", open["id"].as_str().unwrap()), &owner.access_token, json!({ "status": "Review" })).await;
+    assert_eq!(response.status(), 200);
+
+    let recorded: Vec<(String, Option<Uuid>)> = sqlx::query_as(
+        "SELECT to_status::text, changed_by FROM task_status_transitions WHERE task_id = $1 ORDER BY changed_at",
+    )
+    .bind(moved_id)
+    .fetch_all(pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        recorded,
+        vec![
+            ("inprogress".to_string(), Some(owner.id)),
+            ("review".to_string(), Some(owner.id)),
+            ("done".to_string(), Some(owner.id)),
+        ]
+    );
+
+    // Pin the timeline: created at 9:00, started 10:00, in review 12:00, done 13:00
+    let start = Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap();
+    let pin = |task_id: Uuid, to_status: &'static str, hours: i64| async move {
+        sqlx::query("UPDATE task_status_transitions SET changed_at = $3 WHERE task_id = $1 AND to_status::text = $2")
+            .bind(task_id)
+            .bind(to_status)
+            .bind(start + Duration::hours(hours))
+            .execute(pool)
+            .await
+            .unwrap();
+    };
+    sqlx::query("UPDATE tasks SET created_at = $2 WHERE id = ANY($1)")
+        .bind(vec![moved_id, closed_id])
+        .bind(start)
+        .execute(pool)
+        .await
+        .unwrap();
+    pin(moved_id, "inprogress", 1).await;
+    pin(moved_id, "review", 3).await;
+    pin(moved_id, "done", 4).await;
+    pin(closed_id, "done", 2).await;
+
+    let path = format!("/api/projects/{}/analytics/cycle-time?from=2024-06-01T00:00:00Z&to=2024-06-10T00:00:00Z", project_id);
+    let response = app.get(&path, &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let report: Value = response.json().await.unwrap();
+    assert_eq!(report["completed"], 2);
+    let tasks = report["tasks"].as_array().unwrap();
+    assert_eq!(tasks[0]["task_id"], json!(closed_id));
+    assert_eq!(tasks[0]["cycle_time_seconds"], 0);
+    assert_eq!(tasks[0]["lead_time_seconds"], 7200);
+    assert_eq!(tasks[0]["time_in_status"], json!({ "Todo": 7200, "InProgress": 0, "Review": 0, "Done": 0 }));
+    assert_eq!(tasks[1]["task_id"], json!(moved_id));
+    assert_eq!(tasks[1]["started_at"], "2024-06-03T10:00:00Z");
+    assert_eq!(tasks[1]["cycle_time_seconds"], 10800);
+    assert_eq!(tasks[1]["lead_time_seconds"], 14400);
+    assert_eq!(tasks[1]["time_in_status"], json!({ "Todo": 3600, "InProgress": 7200, "Review": 3600, "Done": 0 }));
+    assert_eq!(report["cycle_time"], json!({ "p50": 5400, "p85": 9180, "p95": 10260 }));
+    assert_eq!(report["lead_time"], json!({ "p50": 10800, "p85": 13320, "p95": 14040 }));
+
+    let response = app.get(&format!("{}&format=csv", path), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+    let csv = response.text().await.unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("task_id,number,title,"));
+    assert!(lines[2].starts_with(&format!("{},", moved_id)));
+    assert!(lines[2].ends_with(",14400,10800,3600,7200,3600,0"), "{}", lines[2]);
+
+    // Completed outside the window
+    let response = app
+        .get(&format!("/api/projects/{}/analytics/cycle-time?from=2024-06-04T00:00:00Z&to=2024-06-10T00:00:00Z", project_id), &owner.access_token)
+        .await;
+    let report: Value = response.json().await.unwrap();
+    assert_eq!(report["completed"], 0);
+    assert_eq!(report["cycle_time"], json!({ "p50": null, "p85": null, "p95": null }));
+
+    for query in ["format=xml", "from=2024-06-10T00:00:00Z&to=2024-06-01T00:00:00Z"] {
+        let response = app.get(&format!("/api/projects/{}/analytics/cycle-time?{}", project_id, query), &owner.access_token).await;
+        assert_eq!(response.status(), 400, "{}", query);
+    }
+    let outsider = app.register_user("cycleoutsider").await;
+    let response = app.get(&path, &outsider.access_token).await;
+    assert_eq!(response.status(), 403);
+}
+
+#[tokio::test]
+async fn test_session_rate_limit_headers() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("ratelimited").await;
+
+    let remaining = |response: &reqwest::Response, name: &str| -> i64 {
+        response.headers()[name].to_str().unwrap().parse().unwrap()
+    };
+    let first = app.get("/api/users/me", &user.access_token).await;
+    assert_eq!(first.status(), 200);
+    let limit = remaining(&first, "x-ratelimit-limit");
+    assert!(remaining(&first, "x-ratelimit-remaining") < limit);
+    assert!(remaining(&first, "x-ratelimit-reset") > chrono::Utc::now().timestamp());
+
+    // Every response counts, errors included
+    let second = app.get(&format!("/api/tasks/{}", Uuid::new_v4()), &user.access_token).await;
+    assert_eq!(second.status(), 404);
+    assert_eq!(remaining(&second, "x-ratelimit-remaining"), remaining(&first, "x-ratelimit-remaining") - 1);
+
+    // Each user has their own bucket
+    let other = app.register_user("notlimited").await;
+    let response = app.get("/api/users/me", &other.access_token).await;
+    assert!(remaining(&response, "x-ratelimit-remaining") > remaining(&second, "x-ratelimit-remaining"));
+
+    // Public endpoints aren't counted
+    let response = app.client.get(app.url("/api/config")).send().await.unwrap();
+    assert!(!response.headers().contains_key("x-ratelimit-limit"));
+}
+
+#[tokio::test]
+async fn test_team_retention_policies() {
+    use chrono::{Duration, Utc};
+    use simplecards::{attachments, database::{models::AttachmentScanStatus, queries::AttachmentQueries}, retention};
+
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("retainer").await;
+    let member = app.register_user("retainee").await;
+    let team_id = app.create_team(&owner, "Compliance").await;
+    app.add_team_member(&owner, team_id, &member, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Old stuff").await;
+    let task = app.create_task(&owner, project_id, "Long running").await;
+    let task_id: Uuid = task["id"].as_str().unwrap().parse().unwrap();
+    let pool = app.database.pool();
+    let retention_path = format!("/api/teams/{}/retention", team_id);
+    let comments_path = format!("/api/tasks/{}/comments", task_id);
+
+    let mut comment_ids = Vec::new();
+    for content in ["Ancient", "Ancient but pinned", "Recent"] {
+        let comment: Value = app.post(&comments_path, &owner.access_token, json!({ "content": content })).await.json().await.unwrap();
+        comment_ids.push(comment["id"].as_str().unwrap().parse::<Uuid>().unwrap());
+    }
+    sqlx::query("UPDATE task_comments SET created_at = NOW() - INTERVAL '3 years' WHERE id = ANY($1)")
+        .bind(&comment_ids[..2])
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE task_comments SET pinned = true WHERE id = $1")
+        .bind(comment_ids[1])
+        .execute(pool)
+        .await
+        .unwrap();
+
+    let attachment_id = Uuid::new_v4();
+    let storage_path = format!("{}/{}", task_id, attachment_id);
+    let file = app.config.attachments.upload_dir.join(&storage_path);
+    tokio::fs::create_dir_all(file.parent().unwrap()).await.unwrap();
+    tokio::fs::write(&file, b"old report").await.unwrap();
+    AttachmentQueries::create_attachment(
+        pool,
+        attachment_id,
+        task_id,
+        Some(owner.id),
+        "report.txt",
+        "text/plain",
+        10,
+        &storage_path,
+        &attachments::content_hash(b"old report"),
+        AttachmentScanStatus::Clean,
+    )
+    .await
+    .unwrap();
+    sqlx::query("UPDATE task_attachments SET created_at = NOW() - INTERVAL '1 year' WHERE id = $1")
+        .bind(attachment_id)
+        .execute(pool)
+        .await
+        .unwrap();
+
+    // Admins only, and not below the minimums
+    let policy = json!({ "comment_max_age_years": 2, "attachment_max_age_months": 6 });
+    assert_eq!(app.put(&retention_path, &member.access_token, policy.clone()).await.status(), 403);
+    let response = app.put(&retention_path, &owner.access_token, json!({ "comment_max_age_years": 0, "attachment_max_age_months": 1 })).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["fields"].as_array().unwrap().len(), 2);
+    let response = app.put(&retention_path, &owner.access_token, policy.clone()).await;
+    assert_eq!(response.status(), 200);
+    let saved: Value = app.get(&retention_path, &member.access_token).await.json().await.unwrap();
+    assert_eq!(saved, json!({ "comment_max_age_years": 2, "attachment_max_age_months": 6, "closed_task_max_age_days": null }));
+
+    // The dry run leaves the pinned comment out
+    let preview_path = format!("{}/preview", retention_path);
+    assert_eq!(app.get(&preview_path, &member.access_token).await.status(), 403);
+    let preview: Value = app.get(&preview_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(preview["to_expire"], json!({ "comments": 1, "attachments": 1, "attachment_bytes": 10, "tasks": 0 }));
+    assert_eq!(preview["to_delete"], json!({ "comments": 0, "attachments": 0, "attachment_bytes": 0, "tasks": 0 }));
+    let listed: Value = app.get(&comments_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(listed.as_array().unwrap().len(), 3);
+
+    // Soft delete first
+    let now = Utc::now();
+    retention::sweep(pool, &app.config.attachments, now).await.unwrap();
+    let listed: Value = app.get(&comments_path, &owner.access_token).await.json().await.unwrap();
+    let listed: Vec<&str> = listed.as_array().unwrap().iter().map(|comment| comment["content"].as_str().unwrap()).collect();
+    assert_eq!(listed, vec!["Ancient but pinned", "Recent"]);
+    let response = app.get(&format!("/api/attachments/{}/download", attachment_id), &owner.access_token).await;
+    assert_eq!(response.status(), 404);
+    assert!(file.exists());
+
+    let entry: (Value,) = sqlx::query_as("SELECT details FROM audit_log WHERE action = 'retention_applied' AND path = $1")
+        .bind(&retention_path)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    assert_eq!(entry.0["expired"], json!({ "comments": 1, "attachments": 1, "attachment_bytes": 10, "tasks": 0 }));
+
+    // A more lenient policy brings the attachment back during the grace period
+    app.put(&retention_path, &owner.access_token, json!({ "comment_max_age_years": 2, "attachment_max_age_months": 24 })).await;
+    retention::sweep(pool, &app.config.attachments, now).await.unwrap();
+    let response = app.get(&format!("/api/attachments/{}/download", attachment_id), &owner.access_token).await;
+    assert_ne!(response.status(), 404);
+    app.put(&retention_path, &owner.access_token, policy).await;
+    retention::sweep(pool, &app.config.attachments, now).await.unwrap();
+
+    // Gone for good after it, files included; the pinned comment stays
+    let later = now + Duration::days(retention::grace_days() + 1);
+    let preview = retention::preview(pool, team_id, later).await.unwrap();
+    assert_eq!((preview.to_delete.comments, preview.to_delete.attachments), (1, 1));
+    retention::sweep(pool, &app.config.attachments, later).await.unwrap();
+    let remaining: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM task_comments WHERE task_id = $1 ORDER BY created_at")
+        .bind(task_id)
+        .fetch_all(pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, comment_ids[1..].to_vec());
+    let attachments: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM task_attachments WHERE id = $1")
+        .bind(attachment_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    assert_eq!(attachments, 0);
+    assert!(!file.exists());
+}
+
+#[tokio::test]
+async fn test_attachment_storage_usage_and_cap() {
+    use simplecards::database::{models::AttachmentScanStatus, queries::AttachmentQueries};
+    use simplecards::{attachments, maintenance, quotas, utils::errors::AppError};
+
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("hoarder").await;
+    let admin = app.register_user("storageadmin").await;
+    let outsider = app.register_user("storagepeek").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id)
+        .await
+        .unwrap();
+    let team_id = app.create_team(&owner, "Packrats").await;
+    let project_id = app.create_project(&owner, team_id, "Attic").await;
+
+    // One attachment with its file, one whose file never made it to disk
+    let mut task_ids = Vec::new();
+    for (title, content, with_file) in [("Kept", &b"0123456789"[..], true), ("Lost", &b"abcde"[..], false)] {
+        let task = app.create_task(&owner, project_id, title).await;
+        let task_id: Uuid = task["id"].as_str().unwrap().parse().unwrap();
+        let attachment_id = Uuid::new_v4();
+        let storage_path = format!("{}/{}", task_id, attachment_id);
+        if with_file {
+            let path = app.config.attachments.upload_dir.join(&storage_path);
+            tokio::fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+            tokio::fs::write(&path, content).await.unwrap();
+        }
+        AttachmentQueries::create_attachment(
+            app.database.pool(),
+            attachment_id,
+            task_id,
+            Some(owner.id),
+            "file.bin",
+            "application/octet-stream",
+            content.len() as i64,
+            &storage_path,
+            &attachments::content_hash(content),
+            AttachmentScanStatus::Clean,
+        )
+        .await
+        .unwrap();
+        task_ids.push(task_id);
+    }
+
+    let usage_path = format!("/api/projects/{}/usage", project_id);
+    let response = app.get(&usage_path, &outsider.access_token).await;
+    assert_eq!(response.status(), 403);
+    let usage: Value = app.get(&usage_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(usage["attachment_count"], 2);
+    assert_eq!(usage["attachment_bytes"], 15);
+
+    let team_usage: Value = app.get(&format!("/api/teams/{}/usage", team_id), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(team_usage["attachment_bytes"], 15);
+    assert_eq!(team_usage["tasks_per_project"][0]["attachment_bytes"], 15);
+
+    let response = app.get("/api/admin/storage", &owner.access_token).await;
+    assert_eq!(response.status(), 403);
+    let report: Value = app.get("/api/admin/storage?limit=500", &admin.access_token).await.json().await.unwrap();
+    let row = report.as_array().unwrap().iter().find(|row| row["project_id"] == project_id.to_string()).unwrap();
+    assert_eq!(row["team_name"], "Packrats");
+    assert_eq!(row["attachment_bytes"], 15);
+
+    let response = app
+        .put(&format!("/api/admin/teams/{}/limits", team_id), &admin.access_token, json!({ "max_attachment_bytes_per_project": 20 }))
+        .await;
+    assert_eq!(response.status(), 200);
+    assert!(quotas::check_attachment_storage(app.database.pool(), team_id, 15, 5).await.is_ok());
+    match quotas::check_attachment_storage(app.database.pool(), team_id, 15, 6).await {
+        Err(AppError::PayloadTooLarge(message)) => assert!(message.contains("15 of 20 bytes")),
+        other => panic!("expected payload error, got {:?}", other),
+    }
+
+    let mut conn = app.database.pool().acquire().await.unwrap();
+    let issues = maintenance::attachment_files(&mut conn, &app.config.attachments, Some(project_id)).await.unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].detail, format!("File {}/{} is missing", task_ids[1], issues[0].subject_id.unwrap()));
+
+    // Deleting the task takes its attachment's bytes along
+    let response = app.delete(&format!("/api/tasks/{}", task_ids[1]), &owner.access_token).await;
+    assert!(response.status().is_success());
+    let usage: Value = app.get(&usage_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(usage["attachment_count"], 1);
+    assert_eq!(usage["attachment_bytes"], 10);
+    assert!(maintenance::counter_drift(&mut conn, Some(project_id)).await.unwrap().is_empty());
+
+    sqlx::query("UPDATE project_counters SET attachment_bytes = 0 WHERE project_id = $1")
+        .bind(project_id)
+        .execute(app.database.pool())
+        .await
+        .unwrap();
+    let issues = maintenance::counter_drift(&mut conn, Some(project_id)).await.unwrap();
+    assert_eq!(issues[0].detail, "attachment_bytes is 0 but should be 10");
+    maintenance::repair(app.database.pool(), &app.config.attachments, Some(project_id), false).await.unwrap();
+    assert!(maintenance::counter_drift(&mut conn, Some(project_id)).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_scoped_api_tokens() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("embedder").await;
+    let outsider = app.register_user("embedoutsider").await;
+    let team_id = app.create_team(&owner, "Wiki").await;
+    let project_id = app.create_project(&owner, team_id, "Bugs").await;
+    let other_project = app.create_project(&owner, team_id, "Roadmap").await;
+    let task = app.create_task(&owner, project_id, "Crash on save").await;
+    let other_task = app.create_task(&owner, other_project, "Plan Q3").await;
+    let boards: Value = app.get(&format!("/api/projects/{}/boards", project_id), &owner.access_token).await.json().await.unwrap();
+    let board_id = boards[0]["id"].as_str().unwrap().to_string();
+
+    let response = app.post("/api/users/me/tokens", &owner.access_token, json!({ "name": " ", "scopes": [] })).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    let fields: Vec<&str> = body["error"]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, vec!["name", "scopes"]);
+    let response = app.post("/api/users/me/tokens", &owner.access_token, json!({ "name": "Wiki", "scopes": ["write:tasks"] })).await;
+    assert!(response.status().is_client_error());
+    let response = app
+        .post("/api/users/me/tokens", &outsider.access_token, json!({ "name": "Peek", "scopes": ["read:tasks"], "project_ids": [project_id] }))
+        .await;
+    assert_eq!(response.status(), 400);
+
+    let response = app
+        .post(
+            "/api/users/me/tokens",
+            &owner.access_token,
+            json!({ "name": "Wiki widget", "scopes": ["read:tasks", "read:tasks"], "project_ids": [project_id] }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let created: Value = response.json().await.unwrap();
+    assert_eq!(created["scopes"], json!(["read:tasks"]));
+    let token = created["token"].as_str().unwrap().to_string();
+    let token_id = created["id"].as_str().unwrap().to_string();
+
+    // In scope: reads of the token's project
+    let response = app.get(&format!("/api/projects/{}/tasks", project_id), &token).await;
+    assert_eq!(response.status(), 200);
+    let response = app.get(&format!("/api/tasks/{}", task["id"].as_str().unwrap()), &token).await;
+    assert_eq!(response.status(), 200);
+
+    // Missing scope, other projects, writes and unclassified routes
+    let response = app.get(&format!("/api/boards/{}", board_id), &token).await;
+    assert_eq!(response.status(), 403);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["message"], "Token is missing the read:boards scope");
+    let response = app.get(&format!("/api/projects/{}/tasks", other_project), &token).await;
+    assert_eq!(response.status(), 403);
+    let response = app.get(&format!("/api/tasks/{}", other_task["id"].as_str().unwrap()), &token).await;
+    assert_eq!(response.status(), 403);
+    let response = app.post(&format!("/api/projects/{}/tasks", project_id), &token, json!({ "title": "Nope" })).await;
+    assert_eq!(response.status(), 403);
+    let response = app.get(&format!("/api/teams/{}", team_id), &token).await;
+    assert_eq!(response.status(), 403);
+    let response = app.post("/api/users/me/tokens", &token, json!({ "name": "More", "scopes": ["read:boards"] })).await;
+    assert_eq!(response.status(), 403);
+
+    // The secret is never listed, but the use is
+    let tokens: Value = app.get("/api/users/me/tokens", &owner.access_token).await.json().await.unwrap();
+    assert_eq!(tokens.as_array().unwrap().len(), 1);
+    assert!(tokens[0].get("token").is_none());
+    assert!(tokens[0]["last_used_at"].is_string());
+
+    let response = app.delete(&format!("/api/users/me/tokens/{}", token_id), &outsider.access_token).await;
+    assert_eq!(response.status(), 404);
+    let response = app.delete(&format!("/api/users/me/tokens/{}", token_id), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let response = app.get(&format!("/api/projects/{}/tasks", project_id), &token).await;
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn test_project_only_guests() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("hostadmin").await;
+    let teammate = app.register_user("hostmember").await;
+    let outsider = app.register_user("freelancer").await;
+    let team_id = app.create_team(&owner, "Agency host").await;
+    app.add_team_member(&owner, team_id, &teammate, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Client site").await;
+    let other_project = app.create_project(&owner, team_id, "Internal").await;
+    let task = app.create_task(&owner, project_id, "Review copy").await;
+    let invitations_path = format!("/api/projects/{}/invitations", project_id);
+
+    let response = app.post(&invitations_path, &owner.access_token, json!({ "email": "x@agency.example", "role": "Member" })).await;
+    assert_eq!(response.status(), 400);
+    let response = app.post(&invitations_path, &teammate.access_token, json!({ "email": "x@agency.example" })).await;
+    assert_eq!(response.status(), 403);
+
+    let response = app.post(&invitations_path, &owner.access_token, json!({ "email": " Contractor@Agency.example " })).await;
+    assert_eq!(response.status(), 201);
+    let invitation: Value = response.json().await.unwrap();
+    assert_eq!(invitation["email"], "contractor@agency.example");
+    assert_eq!(invitation["role"], "Guest");
+    let token = invitation["token"].as_str().unwrap().to_string();
+    let pending: Value = app.get(&invitations_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(pending.as_array().unwrap().len(), 1);
+    assert!(pending[0].get("token").is_none());
+
+    // Registering with the invitation joins the project, and nothing else
+    let register = |invitation: &str| {
+        let username = format!("contractor_{}", &Uuid::new_v4().simple().to_string()[..12]);
+        json!({
+            "email": format!("{}@agency.example", username),
+            "username": username,
+            "display_name": "Contractor",
+            "password": TEST_PASSWORD,
+            "invitation": invitation,
+        })
+    };
+    let response = app.post_public("/api/auth/register", register("not-a-real-invitation")).await;
+    assert_eq!(response.status(), 400);
+    let response = app.post_public("/api/auth/register", register(&token)).await;
+    assert_eq!(response.status(), 201);
+    let body: Value = response.json().await.unwrap();
+    let guest_token = body["access_token"].as_str().unwrap().to_string();
+    let guest_id: Uuid = body["user"]["id"].as_str().unwrap().parse().unwrap();
+    let pending: Value = app.get(&invitations_path, &owner.access_token).await.json().await.unwrap();
+    assert!(pending.as_array().unwrap().is_empty());
+    let response = app.post("/api/invitations/accept", &outsider.access_token, json!({ "token": token })).await;
+    assert_eq!(response.status(), 400);
+
+    let projects: Value = app.get("/api/projects", &guest_token).await.json().await.unwrap();
+    let ids: Vec<&str> = projects.as_array().unwrap().iter().map(|project| project["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec![project_id.to_string()]);
+    let teams: Value = app.get("/api/teams", &guest_token).await.json().await.unwrap();
+    assert!(teams.as_array().unwrap().is_empty());
+    let permissions: Value = app.get(&format!("/api/projects/{}/my-permissions", project_id), &guest_token).await.json().await.unwrap();
+    assert_eq!(permissions["role"], "Guest");
+    let response = app.post(&format!("/api/tasks/{}/comments", task["id"].as_str().unwrap()), &guest_token, json!({ "content": "Typo in the header" })).await;
+    assert_eq!(response.status(), 201);
+    let response = app.get(&format!("/api/projects/{}/tasks", other_project), &guest_token).await;
+    assert_eq!(response.status(), 403);
+
+    // Every team endpoint is closed, reads and writes alike
+    let body = json!({
+        "name": "Taken over",
+        "team_id": team_id,
+        "user_id": guest_id,
+        "role": "Admin",
+        "auto_add_to_projects": "all",
+        "comment_max_age_years": 1,
+    });
+    let routes = include_str!("../src/lib.rs").lines().filter_map(|line| {
+        let rest = line.trim().strip_prefix(".route(\"/teams/:team_id")?;
+        let (path, handler) = rest.split_once("\", ")?;
+        Some((path.to_string(), handler.split('(').next()?.to_string()))
+    });
+    let mut probed = 0;
+    for (path, method) in routes {
+        let path = format!("/api/teams/{}{}", team_id, path)
+            .replace(":user_id", &owner.id.to_string())
+            .replace(":project_id", &other_project.to_string())
+            .replace(":export_id", &Uuid::new_v4().to_string());
+        let response = match method.as_str() {
+            "get" => app.get(&path, &guest_token).await,
+            "post" => app.post(&path, &guest_token, body.clone()).await,
+            "put" => app.put(&path, &guest_token, body.clone()).await,
+            "delete" => app.delete(&path, &guest_token).await,
+            other => panic!("unexpected method {} for {}", other, path),
+        };
+        assert_eq!(response.status(), 403, "{} {}", method, path);
+        probed += 1;
+    }
+    assert!(probed >= 17);
+    let team: Value = app.get(&format!("/api/teams/{}", team_id), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(team["name"], "Agency host");
+    assert_eq!(team["member_count"], 2);
+
+    // Only guests and viewers may be outside the team
+    let members_path = |project_id: Uuid| format!("/api/projects/{}/members", project_id);
+    let response = app.put(&format!("{}/{}", members_path(project_id), guest_id), &owner.access_token, json!({ "role": "Member" })).await;
+    assert_eq!(response.status(), 400);
+    let response = app.post(&members_path(other_project), &owner.access_token, json!({ "user_id": guest_id, "role": "Editor" })).await;
+    assert_eq!(response.status(), 400);
+    let response = app.post(&members_path(other_project), &owner.access_token, json!({ "user_id": guest_id, "role": "Viewer" })).await;
+    assert_eq!(response.status(), 201);
+
+    // Existing accounts accept with the token; members can't use one
+    let response = app.post(&invitations_path, &owner.access_token, json!({ "email": "teammate@agency.example", "role": "Viewer" })).await;
+    let token: Value = response.json().await.unwrap();
+    let response = app.post("/api/invitations/accept", &teammate.access_token, json!({ "token": token["token"] })).await;
+    assert_eq!(response.status(), 200);
+    let response = app.post(&invitations_path, &owner.access_token, json!({ "email": "other@agency.example" })).await;
+    let token: Value = response.json().await.unwrap();
+    let response = app.post("/api/invitations/accept", &owner.access_token, json!({ "token": token["token"] })).await;
+    assert_eq!(response.status(), 409);
+    let response = app.post("/api/invitations/accept", &outsider.access_token, json!({ "token": token["token"] })).await;
+    assert_eq!(response.status(), 200);
+    let member: Value = response.json().await.unwrap();
+    assert_eq!(member["role"], "Guest");
+    assert_eq!(member["user_id"], outsider.id.to_string());
+
+    let response = app.delete(&format!("{}/{}", invitations_path, token["id"].as_str().unwrap()), &owner.access_token).await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_user_activity_only_covers_shared_projects() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("activeowner").await;
+    let colleague = app.register_user("colleague").await;
+    let contractor = app.register_user("contractor").await;
+    let stranger = app.register_user("stranger").await;
+    let team_id = app.create_team(&owner, "Studio").await;
+    app.add_team_member(&owner, team_id, &colleague, "Member").await;
+    app.add_team_member(&owner, team_id, &contractor, "Member").await;
+    let shared = app.create_project(&owner, team_id, "Shared").await;
+    let private = app.create_project(&owner, team_id, "Private").await;
+    let response = app
+        .post(&format!("/api/projects/{}/members", shared), &owner.access_token, json!({ "user_id": colleague.id, "role": "Editor" }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let response = app
+        .post(&format!("/api/projects/{}/members", private), &owner.access_token, json!({ "user_id": contractor.id, "role": "Editor" }))
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let visible = app.create_task(&owner, shared, "Plan launch").await;
+    let hidden = app.create_task(&owner, private, "Salary review").await;
+    for (task, status) in [(&visible, "InProgress"), (&visible, "Done"), (&hidden, "Done")] {
+        let response = app
+            .put(&format!("/api/tasks/{}", task["id"].as_str().unwrap()), &owner.access_token, json!({ "status": status }))
+            .await;
+        assert_eq!(response.status(), 200);
+    }
+    for task in [&visible, &hidden] {
+        let response = app
+            .post(&format!("/api/tasks/{}/comments", task["id"].as_str().unwrap()), &owner.access_token, json!({ "content": "Noted" }))
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+    // The colleague's own work in the shared project isn't the owner's activity
+    app.create_task(&colleague, shared, "Draft invite").await;
+
+    let path = format!("/api/users/{}/activity", owner.id);
+    let page: Value = app.get(&path, &colleague.access_token).await.json().await.unwrap();
+    assert_eq!(page["user"]["id"], owner.id.to_string());
+    assert_eq!(page["total"], 4);
+    let activity = page["activity"].as_array().unwrap();
+    let kinds: Vec<&str> = activity.iter().map(|entry| entry["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["commented", "task_completed", "status_changed", "task_created"]);
+    assert!(activity.iter().all(|entry| entry["project_id"] == shared.to_string() && entry["task"]["id"] == visible["id"]));
+    assert_eq!(activity[1]["from_status"], "InProgress");
+    assert_eq!(activity[1]["to_status"], "Done");
+    assert!(activity[0]["comment_id"].is_string());
+    assert_eq!(activity[0]["day"], activity[0]["at"].as_str().unwrap()[..10]);
+
+    // Only the private project overlaps for the contractor
+    let page: Value = app.get(&path, &contractor.access_token).await.json().await.unwrap();
+    assert_eq!(page["total"], 3);
+    assert!(page["activity"].as_array().unwrap().iter().all(|entry| entry["task"]["id"] == hidden["id"]));
+
+    // The owner sees everything, since they're in both projects
+    let page: Value = app.get(&path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(page["total"], 7);
+
+    let page: Value = app.get(&format!("{}?page=2&per_page=3", path), &colleague.access_token).await.json().await.unwrap();
+    assert_eq!(page["total"], 4);
+    assert_eq!(page["activity"].as_array().unwrap().len(), 1);
+    assert_eq!(page["activity"][0]["kind"], "task_created");
+
+    let page: Value = app.get(&path, &stranger.access_token).await.json().await.unwrap();
+    assert_eq!(page["total"], 0);
+    assert_eq!(page["activity"].as_array().unwrap().len(), 0);
+
+    // Leaving the project hides the activity again
+    let response = app.delete(&format!("/api/projects/{}/members/{}", shared, colleague.id), &owner.access_token).await;
+    assert_eq!(response.status(), 204);
+    let page: Value = app.get(&path, &colleague.access_token).await.json().await.unwrap();
+    assert_eq!(page["total"], 0);
+
+    let response = app.get(&format!("/api/users/{}/activity", Uuid::new_v4()), &colleague.access_token).await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_server_timing_and_query_metrics() {
+    let app = TestApp::spawn().await;
+    let user = app.register_user("timed").await;
+
+    let response = app.get("/api/users/me", &user.access_token).await;
+    assert_eq!(response.status(), 200);
+    let header = response.headers()["x-server-timing"].to_str().unwrap().to_string();
+    assert!(header.starts_with("db;dur="), "{}", header);
+    assert!(header.contains(", app;dur="), "{}", header);
+    assert!(!header.contains("desc=\"0 queries\""), "{}", header);
+
+    let response = app.get("/health", &user.access_token).await;
+    assert!(response.headers()["x-server-timing"].to_str().unwrap().contains("desc=\"0 queries\""));
+
+    let response = app.get("/api/admin/metrics", &user.access_token).await;
+    assert_eq!(response.status(), 403);
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), user.id)
+        .await
+        .unwrap();
+
+    let metrics: Value = app.get("/api/admin/metrics", &user.access_token).await.json().await.unwrap();
+    let stats = metrics["queries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|stats| stats["name"] == "UserQueries::get_user_by_id")
+        .expect("get_user_by_id was timed");
+    assert!(stats["count"].as_u64().unwrap() >= 1);
+    assert!(stats["p95_ms"].as_f64().unwrap() <= stats["max_ms"].as_f64().unwrap());
+    assert!(metrics["slow_query_ms"].is_u64());
+}
+
+#[tokio::test]
+async fn test_assignment_suggestions() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("suggestowner").await;
+    let editor = app.register_user("suggesteditor").await;
+    let specialist = app.register_user("specialist").await;
+    let busy = app.register_user("busy").await;
+    let viewer = app.register_user("onlooker").await;
+    let team_id = app.create_team(&owner, "Suggestions").await;
+    let project_id = app.create_project(&owner, team_id, "Platform").await;
+    let other_project = app.create_project(&owner, team_id, "Other").await;
+    for (user, role) in [(&editor, "Editor"), (&specialist, "Member"), (&busy, "Member"), (&viewer, "Viewer")] {
+        app.add_team_member(&owner, team_id, user, "Member").await;
+        let response = app
+            .post(&format!("/api/projects/{}/members", project_id), &owner.access_token, json!({ "user_id": user.id, "role": role }))
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+
+    let tasks_path = format!("/api/projects/{}/tasks", project_id);
+    let response = app
+        .post(&tasks_path, &owner.access_token, json!({ "title": "Old endpoint", "tags": ["backend"], "assigned_to": specialist.id }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let done: Value = response.json().await.unwrap();
+    let response = app.put(&format!("/api/tasks/{}", done["id"].as_str().unwrap()), &owner.access_token, json!({ "status": "Done" })).await;
+    assert_eq!(response.status(), 200);
+    for title in ["One", "Two", "Three"] {
+        let response = app.post(&tasks_path, &owner.access_token, json!({ "title": title, "assigned_to": busy.id })).await;
+        assert_eq!(response.status(), 201);
+    }
+    let response = app.post(&tasks_path, &owner.access_token, json!({ "title": "New endpoint", "tags": ["backend", "api"] })).await;
+    let task: Value = response.json().await.unwrap();
+
+    let path = format!("/api/projects/{}/assignment-suggestions?task_id={}", project_id, task["id"].as_str().unwrap());
+    let response = app.get(&path, &specialist.access_token).await;
+    assert_eq!(response.status(), 403);
+
+    let response = app.get(&path, &editor.access_token).await;
+    assert_eq!(response.status(), 200);
+    let suggestions: Vec<Value> = response.json().await.unwrap();
+    let order: Vec<&str> = suggestions.iter().map(|s| s["user"]["id"].as_str().unwrap()).collect();
+    let expected = [specialist.id, owner.id, editor.id, busy.id].map(|id| id.to_string());
+    assert_eq!(order, expected);
+
+    assert_eq!(suggestions[0]["matched_labels"], json!(["backend"]));
+    assert_eq!(suggestions[0]["factors"]["labels"]["value"], 1);
+    assert_eq!(suggestions[0]["score"], 0.7);
+    // The owner completed a task recently
+    assert_eq!(suggestions[1]["factors"]["activity"]["value"], 1);
+    assert_eq!(suggestions[3]["factors"]["load"]["value"], 3);
+    assert_eq!(suggestions[3]["role"], "Member");
+
+    let elsewhere = app.create_task(&owner, other_project, "Elsewhere").await;
+    let response = app
+        .get(&format!("/api/projects/{}/assignment-suggestions?task_id={}", project_id, elsewhere["id"].as_str().unwrap()), &owner.access_token)
+        .await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_quick_switch() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("switcher").await;
+    let stranger = app.register_user("switchstranger").await;
+    let team_id = app.create_team(&owner, "Quicksilver").await;
+    let relaunch = app.create_project(&owner, team_id, "Website relaunch").await;
+    let mobile = app.create_project(&owner, team_id, "Mobile app").await;
+    // Creating the task marks it read for its author
+    let task = app.create_task(&owner, mobile, "Website copy").await;
+    let other_team = app.create_team(&stranger, "Elsewhere").await;
+    app.create_project(&stranger, other_team, "Website secrets").await;
+
+    let response = app.get("/api/quick-switch?q=web", &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let results: Vec<Value> = response.json().await.unwrap();
+    let names: Vec<&str> = results.iter().map(|r| r["name"].as_str().unwrap()).collect();
+    // The task was just read, the project never opened
+    assert_eq!(names, ["Website copy", "Website relaunch"]);
+    assert_eq!(results[0]["type"], "task");
+    assert_eq!(results[0]["id"], task["id"]);
+    assert_eq!(results[0]["context"]["name"], "Mobile app");
+    assert_eq!(results[1]["type"], "project");
+    assert_eq!(results[1]["context"], json!({ "id": team_id, "name": "Quicksilver" }));
+
+    // Typos still match through the trigrams
+    let response = app.get("/api/quick-switch?q=relauch", &owner.access_token).await;
+    let results: Vec<Value> = response.json().await.unwrap();
+    assert_eq!(results[0]["id"], json!(relaunch));
+
+    // Opening a project puts it and its team first
+    let response = app.get(&format!("/api/projects/{}", mobile), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    let response = app.get("/api/quick-switch", &owner.access_token).await;
+    let results: Vec<Value> = response.json().await.unwrap();
+    let first: Vec<(&str, &str)> = results[..3].iter().map(|r| (r["type"].as_str().unwrap(), r["name"].as_str().unwrap())).collect();
+    assert_eq!(first, [("project", "Mobile app"), ("team", "Quicksilver"), ("task", "Website copy")]);
+
+    for i in 0..16 {
+        app.create_project(&owner, team_id, &format!("Bulk {:02}", i)).await;
+    }
+    let response = app.get("/api/quick-switch?q=", &owner.access_token).await;
+    let results: Vec<Value> = response.json().await.unwrap();
+    assert_eq!(results.len(), 15);
+
+    let response = app.get(&format!("/api/quick-switch?q={}", "x".repeat(201)), &owner.access_token).await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_import_users_from_csv() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("importer").await;
+    let existing = app.register_user("existing").await;
+    let team_id = app.create_team(&admin, "Imported").await;
+    let suffix = &Uuid::new_v4().simple().to_string()[..12];
+    let new_email = format!("new_{}@example.com", suffix);
+
+    let csv = format!(
+        "Email,Username,Display_Name,Team\n\
+         {new_email},new_{suffix},\"Newton, Isaac\",{team_id}\n\
+         {existing},copy_{suffix},Copy,\n\
+         not-an-email,bad_{suffix},Bad,\n\
+         loner_{suffix}@example.com,loner_{suffix},Loner,No such team\n",
+        existing = existing.email.to_uppercase(),
+    );
+    let path = "/api/admin/users/import";
+
+    assert_eq!(post_csv(&app, path, &admin.access_token, csv.clone()).await.status(), 403);
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id)
+        .await
+        .unwrap();
+
+    let response = post_csv(&app, path, &admin.access_token, csv.clone()).await;
+    assert_eq!(response.status(), 200);
+    let report: Value = response.json().await.unwrap();
+    assert_eq!((report["created"].as_u64(), report["skipped_duplicate"].as_u64(), report["invalid"].as_u64()), (Some(1), Some(1), Some(2)));
+    let rows = report["rows"].as_array().unwrap();
+    assert_eq!(rows[0]["line"], 2);
+    assert_eq!(rows[0]["status"], "created");
+    assert_eq!(rows[0]["team_id"], team_id.to_string());
+    assert_eq!(rows[1]["status"], "skipped_duplicate");
+    assert_eq!(rows[2]["status"], "invalid");
+    assert!(rows[2]["reason"].is_string());
+    assert_eq!(rows[3]["reason"], "Team No such team not found");
+
+    // Importing again creates nothing
+    let report: Value = post_csv(&app, path, &admin.access_token, csv).await.json().await.unwrap();
+    assert_eq!(report["created"], 0);
+    assert_eq!(report["skipped_duplicate"], 2);
+
+    let members: Value = app.get(&format!("/api/teams/{}/members", team_id), &admin.access_token).await.json().await.unwrap();
+    assert!(members.to_string().contains(&format!("new_{}", suffix)));
+
+    // The invitation email carries the link to choose a password
+    let link: String = sqlx::query_scalar(
+        "SELECT payload->'context'->>'link' FROM jobs WHERE job_type = 'send_email' AND payload->>'to' = $1",
+    )
+    .bind(&new_email)
+    .fetch_one(app.database.pool())
+    .await
+    .unwrap();
+    let token = link.rsplit('/').next().unwrap();
+
+    let reset = json!({ "token": token, "password": "Chosen-Pass-123" });
+    assert_eq!(app.post_public("/api/auth/password-reset", reset.clone()).await.status(), 204);
+    assert_eq!(app.post_public("/api/auth/password-reset", reset).await.status(), 400);
+    let response = app
+        .post_public("/api/auth/login", json!({ "email": new_email, "password": "Chosen-Pass-123" }))
+        .await;
+    assert_eq!(response.status(), 200);
+
+    let mut too_many = String::from("email,username,display_name\n");
+    for i in 0..=simplecards::user_import::MAX_ROWS {
+        too_many.push_str(&format!("cap{i}_{suffix}@example.com,cap{i}_{suffix},Cap\n"));
+    }
+    assert_eq!(post_csv(&app, path, &admin.access_token, too_many).await.status(), 400);
+    let response = app.post_public("/api/auth/login", json!({ "email": format!("cap0_{}@example.com", suffix), "password": "x" })).await;
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn test_task_snapshots() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("snapowner").await;
+    let outsider = app.register_user("snapoutsider").await;
+    let team_id = app.create_team(&owner, "Snapshots").await;
+    let project_id = app.create_project(&owner, team_id, "Release").await;
+    let task = app.create_task(&owner, project_id, "Ship <v2>").await;
+    let task_id = task["id"].as_str().unwrap();
+    let response = app
+        .put(
+            &format!("/api/tasks/{}", task_id),
+            &owner.access_token,
+            json!({ "description": "**Plan**\n\n- [x] Tests\n- [ ] Deploy", "assigned_to": owner.id, "tags": ["release"] }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    app.post(&format!("/api/tasks/{}/comments", task_id), &owner.access_token, json!({ "content": "Looks good" })).await;
+
+    let path = format!("/api/tasks/{}/snapshot", task_id);
+    let snapshot: Value = app.get(&path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(snapshot["title"], "Ship <v2>");
+    assert_eq!(snapshot["description_html"], "<p><strong>Plan</strong></p>\n<ul>\n<li><input disabled=\"\" type=\"checkbox\" checked=\"\"/>\nTests</li>\n<li><input disabled=\"\" type=\"checkbox\"/>\nDeploy</li>\n</ul>\n");
+    assert_eq!(snapshot["checklist"], json!([{ "text": "Tests", "done": true }, { "text": "Deploy", "done": false }]));
+    assert_eq!(snapshot["assignee"]["id"], owner.id.to_string());
+    assert_eq!(snapshot["labels"], json!(["release"]));
+    assert!(snapshot.get("comments").is_none());
+
+    let response = app.get(&format!("{}?format=html&comments=true", path), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/html; charset=utf-8");
+    let html = response.text().await.unwrap();
+    assert!(html.contains("Ship &lt;v2&gt;"));
+    assert!(html.contains("Looks good"));
+    assert_eq!(app.get(&format!("{}?format=pdf", path), &owner.access_token).await.status(), 400);
+    assert_eq!(app.get(&path, &outsider.access_token).await.status(), 404);
+
+    let snapshots_path = format!("/api/tasks/{}/snapshots", task_id);
+    let response = app
+        .post(&snapshots_path, &owner.access_token, json!({ "include_comments": true, "note": "Shipped in 2.0" }))
+        .await;
+    assert_eq!(response.status(), 201);
+    let stored: Value = response.json().await.unwrap();
+    assert_eq!(stored["note"], "Shipped in 2.0");
+    assert_eq!(stored["created_by"]["id"], owner.id.to_string());
+    assert_eq!(stored["snapshot"]["comments"][0]["content"], "Looks good");
+    let snapshot_id = stored["id"].as_str().unwrap();
+    assert_eq!(app.post(&snapshots_path, &outsider.access_token, json!({})).await.status(), 404);
+
+    // Later changes don't reach the stored copy
+    app.put(&format!("/api/tasks/{}", task_id), &owner.access_token, json!({ "title": "Ship v3" })).await;
+    let stored_path = format!("{}/{}", snapshots_path, snapshot_id);
+    let stored: Value = app.get(&stored_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(stored["snapshot"]["title"], "Ship <v2>");
+    let html = app.get(&format!("{}?format=html", stored_path), &owner.access_token).await.text().await.unwrap();
+    assert!(html.contains("Shipped in 2.0"));
+
+    let list: Value = app.get(&snapshots_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert!(list[0].get("snapshot").is_none());
+    assert_eq!(app.get(&stored_path, &outsider.access_token).await.status(), 404);
+
+    let updated = sqlx::query("UPDATE task_snapshots SET note = 'edited' WHERE id = $1")
+        .bind(Uuid::parse_str(snapshot_id).unwrap())
+        .execute(app.database.pool())
+        .await;
+    assert!(updated.is_err());
+}
+
+#[tokio::test]
+async fn test_content_screening_holds_spam_for_moderation() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("screener").await;
+    let owner = app.register_user("screened").await;
+    let spammer = app.register_user("linkdropper").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id).await.unwrap();
+    let team_id = app.create_team(&owner, "Screened").await;
+    app.add_team_member(&owner, team_id, &spammer, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Open forum").await;
+    let response = app
+        .post(
+            &format!("/api/projects/{}/members", project_id),
+            &owner.access_token,
+            json!({ "user_id": spammer.id, "role": "Member" }),
+        )
+        .await;
+    assert_eq!(response.status(), 201);
+    let task = app.create_task(&owner, project_id, "Welcome").await;
+    let comments_path = format!("/api/tasks/{}/comments", task["id"].as_str().unwrap());
+    let spam = "cheap https://a.example https://b.example www.c.example";
+
+    // Off until the flag is on for the team
+    let response = app.post(&comments_path, &spammer.access_token, json!({ "content": spam })).await;
+    assert_eq!(response.status(), 201);
+    let flag_path = "/api/admin/flags/content_screening";
+    let response = app.put(flag_path, &admin.access_token, json!({ "enabled": false })).await;
+    assert_eq!(response.status(), 200);
+    let response = app
+        .put(&format!("{}/teams/{}", flag_path, team_id), &admin.access_token, json!({ "enabled": true }))
+        .await;
+    assert_eq!(response.status(), 200);
+
+    let (mut socket, _) = connect_async(app.ws_url(&owner.access_token)).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "AuthenticationSuccess");
+    let subscribe = json!({ "type": "Subscribe", "data": { "project_id": project_id } });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+    assert_eq!(next_event(&mut socket).await["type"], "SubscriptionSuccess");
+
+    let response = app.post(&comments_path, &owner.access_token, json!({ "content": "Hello and welcome" })).await;
+    assert_eq!(response.status(), 201);
+    let response = app.post(&comments_path, &spammer.access_token, json!({ "content": spam })).await;
+    assert_eq!(response.status(), 202);
+    let held_comment: Value = response.json().await.unwrap();
+    assert_eq!(held_comment["pending_moderation"], true);
+    let response = app
+        .post(
+            &format!("/api/projects/{}/tasks", project_id),
+            &spammer.access_token,
+            json!({ "title": "Deals", "description": spam }),
+        )
+        .await;
+    assert_eq!(response.status(), 202);
+    let held_task: Value = response.json().await.unwrap();
+    let held_task_path = format!("/api/tasks/{}", held_task["id"].as_str().unwrap());
+
+    // Only the author sees held content, marked as pending
+    let comments: Value = app.get(&comments_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(comments.as_array().unwrap().len(), 2);
+    let comments: Value = app.get(&comments_path, &spammer.access_token).await.json().await.unwrap();
+    let pending: Vec<&Value> = comments.as_array().unwrap().iter().filter(|c| c["pending_moderation"] == true).collect();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0]["id"], held_comment["id"]);
+    assert_eq!(app.get(&held_task_path, &owner.access_token).await.status(), 404);
+    let details: Value = app.get(&held_task_path, &spammer.access_token).await.json().await.unwrap();
+    assert_eq!(details["pending_moderation"], true);
+
+    let response = app.get("/api/admin/moderation", &owner.access_token).await;
+    assert_eq!(response.status(), 403);
+    let queue: Value = app.get("/api/admin/moderation", &admin.access_token).await.json().await.unwrap();
+    let items: Vec<&Value> = queue.as_array().unwrap().iter().filter(|item| item["project_id"] == project_id.to_string()).collect();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0]["content_type"], "comment");
+    assert_eq!(items[0]["author"]["id"], spammer.id.to_string());
+    assert_eq!(items[0]["reasons"][0], "3 of 4 words are links");
+    assert_eq!(items[0]["language"], Value::Null);
+    assert_eq!(items[1]["content_type"], "task");
+    assert_eq!(items[1]["content"], format!("Deals\n\n{}", spam));
+
+    // Approval publishes the comment with its original timestamp
+    let approve_path = format!("/api/admin/moderation/{}/approve", items[0]["id"].as_str().unwrap());
+    let response = app.post(&approve_path, &admin.access_token, json!({})).await;
+    assert_eq!(response.status(), 200);
+    let approved: Value = response.json().await.unwrap();
+    assert_eq!(approved["status"], "approved");
+    assert_eq!(approved["reviewed_by"], admin.id.to_string());
+    let response = app.post(&approve_path, &admin.access_token, json!({})).await;
+    assert_eq!(response.status(), 409);
+
+    let mut created = Vec::new();
+    while created.len() < 2 {
+        let event = next_event(&mut socket).await;
+        if event["type"] == "CommentCreated" {
+            created.push(event["data"]["comment"]["id"].clone());
+        }
+    }
+    assert_eq!(created[1], held_comment["id"]);
+    let comments: Value = app.get(&comments_path, &owner.access_token).await.json().await.unwrap();
+    let published = comments.as_array().unwrap().iter().find(|c| c["id"] == held_comment["id"]).unwrap();
+    assert_eq!(published["created_at"], held_comment["created_at"]);
+    assert_eq!(published["pending_moderation"], false);
+
+    // Rejected content stays hidden
+    let reject_path = format!("/api/admin/moderation/{}/reject", items[1]["id"].as_str().unwrap());
+    let rejected: Value = app.post(&reject_path, &admin.access_token, json!({})).await.json().await.unwrap();
+    assert_eq!(rejected["status"], "rejected");
+    let tasks: Value = app.get(&format!("/api/projects/{}/tasks", project_id), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(tasks.as_array().unwrap().len(), 1);
+    let queue: Value = app.get("/api/admin/moderation?status=rejected", &admin.access_token).await.json().await.unwrap();
+    assert!(queue.as_array().unwrap().iter().any(|item| item["id"] == items[1]["id"]));
+}
+
+#[tokio::test]
+async fn test_team_audit_export() {
+    use simplecards::audit_export::{self, AuditExportJob};
+    use simplecards::jobs::JobHandler;
+
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("auditor").await;
+    let member = app.register_user("audited").await;
+    let team_id = app.create_team(&owner, "Compliance").await;
+    app.add_team_member(&owner, team_id, &member, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Ledger").await;
+    let task = app.create_task(&owner, project_id, "Close the books").await;
+    let task_path = format!("/api/tasks/{}", task["id"].as_str().unwrap());
+    assert_eq!(app.put(&task_path, &owner.access_token, json!({ "status": "InProgress" })).await.status(), 200);
+    let response = app.post(&format!("{}/comments", task_path), &owner.access_token, json!({ "content": "Q3 done" })).await;
+    assert_eq!(response.status(), 201);
+
+    let now = chrono::Utc::now();
+    let window = format!(
+        "from={}&to={}",
+        (now - chrono::Duration::hours(1)).format("%Y-%m-%dT%H:%M:%SZ"),
+        (now + chrono::Duration::hours(1)).format("%Y-%m-%dT%H:%M:%SZ")
+    );
+    let path = format!("/api/teams/{}/audit/export?{}", team_id, window);
+    assert_eq!(app.get(&path, &member.access_token).await.status(), 403);
+    let reversed = format!("/api/teams/{}/audit/export?from={}&to={}", team_id, now.format("%Y-%m-%dT%H:%M:%SZ"), "2020-01-01T00:00:00Z");
+    assert_eq!(app.get(&reversed, &owner.access_token).await.status(), 400);
+
+    let response = app.get(&path, &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+    let csv = response.text().await.unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "at,action,actor_id,actor,ip_address,entity_type,entity_id,project_id,project_name,summary");
+    assert!(lines[1].contains(",task_created,") && lines[1].contains("Created task #1 Close the books"));
+    assert!(lines[2].contains("Moved task #1 Close the books from Todo to InProgress"));
+    assert!(lines[3].contains(",comment_added,") && lines[3].contains(&format!(",{},", owner.username)));
+    // The export itself is audited, with the client's address
+    assert!(lines[4].starts_with(&format!("{}", now.format("%Y-%m-%d"))) && lines[4].contains(",audit_exported,"));
+    assert!(lines[4].contains(",127.0.0.1,"));
+
+    let response = app.get(&format!("{}&format=jsonl", path), &owner.access_token).await;
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let entries: Vec<Value> = response.text().await.unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[0]["action"], "task_created");
+    assert_eq!(entries[0]["project_name"], "Ledger");
+    assert_eq!(entries[4]["entity_type"], "team");
+    assert_eq!(entries[4]["actor_id"], owner.id.to_string());
+
+    // Large exports are written by a job and announced to the admin
+    std::env::set_var("AUDIT_EXPORT_SYNC_LIMIT", "2");
+    let (mut ws, _) = connect_async(app.ws_url(&owner.access_token)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let response = app.get(&path, &owner.access_token).await;
+    std::env::remove_var("AUDIT_EXPORT_SYNC_LIMIT");
+    assert_eq!(response.status(), 202);
+    let export: Value = response.json().await.unwrap();
+    assert_eq!(export["status"], "pending");
+    let export_path = format!("/api/teams/{}/audit/exports/{}", team_id, export["id"].as_str().unwrap());
+    assert_eq!(app.get(&format!("{}/download", export_path), &owner.access_token).await.status(), 409);
+
+    let job = audit_export::queue(app.database.pool(), export["id"].as_str().unwrap().parse().unwrap()).await.unwrap();
+    AuditExportJob::new(app.database.clone(), app.websocket.clone(), app.config.attachments.clone()).run(&job).await.unwrap();
+
+    let event = loop {
+        let message = tokio::time::timeout(Duration::from_secs(2), ws.next()).await.unwrap().unwrap().unwrap();
+        if let Message::Text(text) = message {
+            let event: Value = serde_json::from_str(&text).unwrap();
+            if event["type"] == "AuditExportReady" {
+                break event;
+            }
+        }
+    };
+    assert_eq!(event["data"]["notification"]["kind"], "AuditExportReady");
+    assert_eq!(event["data"]["export"]["entry_count"], 6);
+    assert_eq!(event["data"]["download_url"], format!("{}/download", export_path));
+
+    let status: Value = app.get(&export_path, &owner.access_token).await.json().await.unwrap();
+    assert_eq!(status["status"], "ready");
+    assert_eq!(app.get(&export_path, &member.access_token).await.status(), 403);
+    let response = app.get(&format!("{}/download", export_path), &owner.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap().lines().count(), 7);
+
+    // A few exports per day and team
+    assert_eq!(app.get(&path, &owner.access_token).await.status(), 429);
+}
+
+#[tokio::test]
+async fn test_notification_settings_matrix() {
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("quietowner").await;
+    let alice = app.register_user("quietalice").await;
+    let outsider = app.register_user("quietoutsider").await;
+
+    let team_id = app.create_team(&owner, "Quiet").await;
+    app.add_team_member(&owner, team_id, &alice, "Member").await;
+    let project_id = app.create_project(&owner, team_id, "Quiet").await;
+    let other_project_id = app.create_project(&owner, team_id, "Loud").await;
+    for project_id in [project_id, other_project_id] {
+        let response = app
+            .post(
+                &format!("/api/projects/{}/members", project_id),
+                &owner.access_token,
+                json!({ "user_id": alice.id, "role": "Editor" }),
+            )
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+    let response = app.put("/api/users/me/digest", &alice.access_token, json!({ "frequency": "Weekly" })).await;
+    assert_eq!(response.status(), 200);
+
+    // Assigns a task to alice, comments on it and mentions her; returns the
+    // kinds of notification she got for it
+    let exercise = |project_id: Uuid| {
+        let (app, owner, alice) = (&app, &owner, &alice);
+        async move {
+            let due_date = (chrono::Utc::now() + chrono::Duration::days(2)).to_rfc3339();
+            let response = app
+                .post(
+                    &format!("/api/projects/{}/tasks", project_id),
+                    &owner.access_token,
+                    json!({ "title": "Settings check", "assigned_to": alice.id, "due_date": due_date }),
+                )
+                .await;
+            assert_eq!(response.status(), 201);
+            let task: Value = response.json().await.unwrap();
+            let task_id = task["id"].as_str().unwrap().to_string();
+
+            let comments_path = format!("/api/tasks/{}/comments", task_id);
+            for content in ["Looks good".to_string(), format!("@{} can you check?", alice.username)] {
+                let response = app.post(&comments_path, &owner.access_token, json!({ "content": content })).await;
+                assert_eq!(response.status(), 201);
+            }
+
+            let response = app.get("/api/users/me/notifications", &alice.access_token).await;
+            let notifications: Vec<Value> = response.json().await.unwrap();
+            let mut kinds: Vec<String> = notifications
+                .iter()
+                .filter(|n| n["task_id"] == task_id.as_str())
+                .map(|n| n["kind"].as_str().unwrap().to_string())
+                .collect();
+            kinds.sort();
+            (task_id, kinds)
+        }
+    };
+
+    let settings_path = format!("/api/projects/{}/notification-settings", project_id);
+    for (level, expected) in [
+        ("all", vec!["TaskAssigned", "TaskCommented", "TaskMentioned"]),
+        ("mentions_only", vec!["TaskMentioned"]),
+        ("none", vec![]),
+    ] {
+        let levels: Value = ["mention", "assignment", "comment", "reminder", "digest"]
+            .iter()
+            .map(|event_type| (event_type.to_string(), json!(level)))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+        let response = app.put(&settings_path, &alice.access_token, levels).await;
+        assert_eq!(response.status(), 200, "{}", level);
+        let settings: Vec<Value> = response.json().await.unwrap();
+        assert_eq!(settings.len(), 5);
+        assert!(settings.iter().all(|setting| setting["level"] == level && setting["inherited"] == false));
+
+        let (task_id, kinds) = exercise(project_id).await;
+        assert_eq!(kinds, expected, "{}", level);
+
+        let response = app.post("/api/users/me/digest/preview", &alice.access_token, json!({})).await;
+        assert_eq!(response.status(), 200);
+        let preview: Value = response.json().await.unwrap();
+        let due_soon = preview["digest"]["due_soon"].as_array().unwrap();
+        assert_eq!(due_soon.iter().any(|task| task["id"] == task_id.as_str()), level == "all", "{}", level);
+        let mentions = preview["digest"]["mentions"].as_array().unwrap();
+        assert_eq!(mentions.iter().any(|mention| mention["task_id"] == task_id.as_str()), level != "none", "{}", level);
+    }
+
+    // User defaults apply to projects without a setting of their own
+    let response = app.put("/api/users/me/notification-settings", &alice.access_token, json!({ "comment": "none" })).await;
+    assert_eq!(response.status(), 200);
+    let defaults: Vec<Value> = response.json().await.unwrap();
+    let comment = defaults.iter().find(|setting| setting["event_type"] == "comment").unwrap();
+    assert_eq!(comment["level"], "none");
+    assert_eq!(comment["inherited"], false);
+    let (_, kinds) = exercise(other_project_id).await;
+    assert_eq!(kinds, ["TaskAssigned", "TaskMentioned"]);
+
+    // A project setting wins over the default, and null reverts to it
+    let other_settings_path = format!("/api/projects/{}/notification-settings", other_project_id);
+    let response = app.put(&other_settings_path, &alice.access_token, json!({ "comment": "all" })).await;
+    assert_eq!(response.status(), 200);
+    let (_, kinds) = exercise(other_project_id).await;
+    assert_eq!(kinds, ["TaskAssigned", "TaskCommented", "TaskMentioned"]);
+
+    let response = app.put(&other_settings_path, &alice.access_token, json!({ "comment": null })).await;
+    let settings: Vec<Value> = response.json().await.unwrap();
+    let comment = settings.iter().find(|setting| setting["event_type"] == "comment").unwrap();
+    assert_eq!(comment["level"], "none");
+    assert_eq!(comment["inherited"], true);
+
+    let response = app.put(&other_settings_path, &alice.access_token, json!({ "comment": "loud" })).await;
+    assert_eq!(response.status(), 400);
+    let response = app.get(&settings_path, &outsider.access_token).await;
+    assert_eq!(response.status(), 403);
+}
+
+#[tokio::test]
+async fn test_api_token_quotas() {
+    let app = TestApp::spawn().await;
+    let admin = app.register_user("quotaadmin").await;
+    let user = app.register_user("quotascript").await;
+    simplecards::database::queries::UserQueries::grant_instance_admin(app.database.pool(), admin.id)
+        .await
+        .unwrap();
+    let team_id = app.create_team(&user, "Scripts").await;
+    let project_id = app.create_project(&user, team_id, "Sync").await;
+    let tasks_path = format!("/api/projects/{}/tasks", project_id);
+
+    let create_token = |name: &str| {
+        let (app, token, name) = (&app, user.access_token.clone(), name.to_string());
+        async move {
+            let response = app.post("/api/users/me/tokens", &token, json!({ "name": name, "scopes": ["read:tasks"] })).await;
+            assert_eq!(response.status(), 201);
+            let created: Value = response.json().await.unwrap();
+            created["token"].as_str().unwrap().to_string()
+        }
+    };
+    let (first, second) = (create_token("Nightly sync").await, create_token("Dashboard").await);
+
+    // Only admins set overrides, and only positive ones
+    let path = format!("/api/admin/users/{}/token-rate-limit", user.id);
+    let response = app.put(&path, &user.access_token, json!({ "requests_per_hour": 1000 })).await;
+    assert_eq!(response.status(), 403);
+    let response = app.put(&path, &admin.access_token, json!({ "requests_per_hour": 0 })).await;
+    assert_eq!(response.status(), 400);
+    let response = app.put(&path, &admin.access_token, json!({ "requests_per_hour": 2 })).await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["effective"], 2);
+
+    let header = |response: &reqwest::Response, name: &str| -> i64 {
+        response.headers()[name].to_str().unwrap().parse().unwrap()
+    };
+    let response = app.get(&tasks_path, &first).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(header(&response, "x-ratelimit-limit"), 2);
+    assert_eq!(header(&response, "x-ratelimit-remaining"), 1);
+    app.get(&tasks_path, &first).await;
+    let response = app.get(&tasks_path, &first).await;
+    assert_eq!(response.status(), 429);
+    assert_eq!(header(&response, "x-ratelimit-remaining"), 0);
+
+    // The exhausted token throttles neither the other token nor the user's session
+    let response = app.get(&tasks_path, &second).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(header(&response, "x-ratelimit-remaining"), 1);
+    let response = app.get("/api/users/me/tokens", &user.access_token).await;
+    assert_eq!(response.status(), 200);
+    assert!(header(&response, "x-ratelimit-limit") > 2);
+
+    let tokens: Value = response.json().await.unwrap();
+    let quotas: Vec<(i64, i64)> = tokens
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|token| (token["quota"]["limit"].as_i64().unwrap(), token["quota"]["remaining"].as_i64().unwrap()))
+        .collect();
+    assert_eq!(quotas, vec![(2, 0), (2, 1)]);
+    assert!(tokens[0]["quota"]["reset_at"].is_string());
+
+    // Clearing the override restores the default and lets the token through again
+    let response = app.put(&path, &admin.access_token, json!({ "requests_per_hour": null })).await;
+    let body: Value = response.json().await.unwrap();
+    assert!(body["effective"].as_i64().unwrap() > 2);
+    assert_eq!(app.get(&tasks_path, &first).await.status(), 200);
+}
+
+#[tokio::test]
+async fn test_closed_task_retention() {
+    use chrono::{Duration, Utc};
+    use simplecards::{attachments, database::{models::AttachmentScanStatus, queries::AttachmentQueries}, retention};
+
+    let app = TestApp::spawn().await;
+    let owner = app.register_user("archivist").await;
+    let team_id = app.create_team(&owner, "Tidy").await;
+    let project_id = app.create_project(&owner, team_id, "Done and dusted").await;
+    let pool = app.database.pool();
+    let retention_path = format!("/api/teams/{}/retention", team_id);
+    let tasks_path = format!("/api/projects/{}/tasks", project_id);
+
+    let mut ids = Vec::new();
+    for title in ["Closed long ago", "Closed but linked", "Closed recently", "Still open", "Open for ages"] {
+        let task = app.create_task(&owner, project_id, title).await;
+        ids.push(task["id"].as_str().unwrap().parse::<Uuid>().unwrap());
+    }
+    let [old, linked, recent, open, ancient_open] = ids[..] else { unreachable!() };
+    for id in [old, linked, recent] {
+        let response = app.put(&format!("/api/tasks/{}", id), &owner.access_token, json!({ "status": "Done" })).await;
+        assert_eq!(response.status(), 200);
+    }
+    sqlx::query("UPDATE tasks SET status_changed_at = NOW() - INTERVAL '100 days' WHERE id = ANY($1)")
+        .bind(vec![old, linked, ancient_open])
+        .execute(pool)
+        .await
+        .unwrap();
+    let response = app
+        .post(&format!("/api/tasks/{}/relations", linked), &owner.access_token, json!({ "task_id": open, "relation": "relates_to" }))
+        .await;
+    assert_eq!(response.status(), 201);
+
+    let attachment_id = Uuid::new_v4();
+    let storage_path = format!("{}/{}", old, attachment_id);
+    let file = app.config.attachments.upload_dir.join(&storage_path);
+    tokio::fs::create_dir_all(file.parent().unwrap()).await.unwrap();
+    tokio::fs::write(&file, b"final notes").await.unwrap();
+    AttachmentQueries::create_attachment(
+        pool,
+        attachment_id,
+        old,
+        Some(owner.id),
+        "notes.txt",
+        "text/plain",
+        11,
+        &storage_path,
+        &attachments::content_hash(b"final notes"),
+        AttachmentScanStatus::Clean,
+    )
+    .await
+    .unwrap();
+
+    let response = app.put(&retention_path, &owner.access_token, json!({ "closed_task_max_age_days": 7 })).await;
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["fields"][0]["field"], "closed_task_max_age_days");
+    let response = app.put(&retention_path, &owner.access_token, json!({ "closed_task_max_age_days": 30 })).await;
+    assert_eq!(response.status(), 200);
+
+    // Open tasks and tasks related to an open one are left alone
+    let preview: Value = app.get(&format!("{}/preview", retention_path), &owner.access_token).await.json().await.unwrap();
+    assert_eq!(preview["to_expire"], json!({ "comments": 0, "attachments": 0, "attachment_bytes": 0, "tasks": 1 }));
+
+    // Archived first: hidden, but still there
+    let now = Utc::now();
+    retention::sweep(pool, &app.config.attachments, now).await.unwrap();
+    let titles = |tasks: Value| -> Vec<String> {
+        let mut titles: Vec<String> = tasks.as_array().unwrap().iter().map(|task| task["title"].as_str().unwrap().to_string()).collect();
+        titles.sort();
+        titles
+    };
+    let listed = titles(app.get(&tasks_path, &owner.access_token).await.json().await.unwrap());
+    assert_eq!(listed, vec!["Closed but linked", "Closed recently", "Open for ages", "Still open"]);
+    assert_eq!(app.get(&format!("/api/tasks/{}", old), &owner.access_token).await.status(), 404);
+    let archived: bool = sqlx::query_scalar("SELECT expired_at IS NOT NULL FROM tasks WHERE id = $1")
+        .bind(old)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    assert!(archived);
+    assert!(file.exists());
+    let entry: (Value,) = sqlx::query_as("SELECT details FROM audit_log WHERE action = 'retention_applied' AND path = $1")
+        .bind(&retention_path)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    assert_eq!(entry.0["expired"]["tasks"], 1);
+
+    // Once the related task is closed too, the linked one goes the same way
+    let response = app.put(&format!("/api/tasks/{}", open), &owner.access_token, json!({ "status": "Done" })).await;
+    assert_eq!(response.status(), 200);
+    retention::sweep(pool, &app.config.attachments, now).await.unwrap();
+    assert_eq!(app.get(&format!("/api/tasks/{}", linked), &owner.access_token).await.status(), 404);
+
+    // Deleted for good after the grace period, attachment files included
+    let later = now + Duration::days(retention::grace_days() + 1);
+    let preview = retention::preview(pool, team_id, later).await.unwrap();
+    assert_eq!(preview.to_delete.tasks, 2);
+    retention::sweep(pool, &app.config.attachments, later).await.unwrap();
+    let mut remaining: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM tasks WHERE project_id = $1")
+        .bind(project_id)
+        .fetch_all(pool)
+        .await
+        .unwrap();
+    remaining.sort();
+    let mut kept = vec![recent, open, ancient_open];
+    kept.sort();
+    assert_eq!(remaining, kept);
+    assert!(!file.exists());
+}
+
+async fn post_csv(app: &TestApp, path: &str, token: &str, body: String) -> reqwest::Response {
+    app.client
+        .post(app.url(path))
+        .bearer_auth(token)
+        .header("Content-Type", "text/csv")
+        .body(body)
+        .send()
+        .await
+        .unwrap()
+}
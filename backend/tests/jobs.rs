@@ -0,0 +1,162 @@
+mod common;
+
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+use simplecards::config::Config;
+use simplecards::database::{connection::Database, models::{Job, JobStatus}, queries::JobQueries};
+use simplecards::jobs::{self, worker::JobWorker, JobHandler, JobRegistry};
+
+struct FlakyHandler {
+    calls: Arc<AtomicUsize>,
+    failures: usize,
+}
+
+#[async_trait]
+impl JobHandler for FlakyHandler {
+    async fn run(&self, _job: &Job) -> anyhow::Result<()> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        if call < self.failures {
+            anyhow::bail!("transient failure {}", call);
+        }
+        Ok(())
+    }
+}
+
+// A worker fails the jobs it has no handler for, so tests running one take turns
+static WORKER_TURN: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[tokio::test]
+async fn test_worker_executes_and_retries_jobs() {
+    let _turn = WORKER_TURN.lock().await;
+    dotenvy::dotenv().ok();
+    let database = Database::new(&Config::test_default().database).await.unwrap();
+    let job_type = format!("test_flaky_{}", uuid::Uuid::new_v4().simple());
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut registry = JobRegistry::new();
+    registry.register(&job_type, FlakyHandler { calls: calls.clone(), failures: 1 });
+
+    let job = jobs::enqueue(database.pool(), &job_type, &serde_json::json!({ "n": 1 })).await.unwrap();
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let worker = tokio::spawn(JobWorker::new(database.clone(), registry).run(shutdown_rx));
+
+    // First attempt fails and is rescheduled with backoff rather than dropped
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    shutdown_tx.send(true).unwrap();
+    worker.await.unwrap();
+
+    let listed = JobQueries::list_jobs(database.pool(), None, Some(&job_type), 10).await.unwrap();
+    let stored = listed.iter().find(|j| j.id == job.id).unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(stored.status, JobStatus::Pending);
+    assert_eq!(stored.attempts, 1);
+    assert!(stored.last_error.as_deref().unwrap().contains("transient failure"));
+    assert!(stored.run_at > chrono::Utc::now());
+}
+
+// Records the `n` of each job it runs and fails `n == fail_on` the first time
+struct RecordingHandler {
+    seen: Arc<Mutex<Vec<i64>>>,
+    fail_on: i64,
+}
+
+#[async_trait]
+impl JobHandler for RecordingHandler {
+    async fn run(&self, job: &Job) -> anyhow::Result<()> {
+        let n = job.payload["n"].as_i64().unwrap();
+        let mut seen = self.seen.lock().unwrap();
+        let first = !seen.contains(&n);
+        seen.push(n);
+        if n == self.fail_on && first {
+            anyhow::bail!("delivery {} failed", n);
+        }
+        Ok(())
+    }
+}
+
+// Runs a worker until `done` holds for the jobs, and a little longer to catch
+// jobs that run when they shouldn't
+async fn run_worker(
+    database: &Database,
+    job_type: &str,
+    seen: &Arc<Mutex<Vec<i64>>>,
+    job_ids: &[Uuid],
+    done: impl Fn(&[Job]) -> bool,
+) -> Vec<Job> {
+    let mut registry = JobRegistry::new();
+    registry.register(job_type, RecordingHandler { seen: seen.clone(), fail_on: 2 });
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let worker = tokio::spawn(JobWorker::new(database.clone(), registry).run(shutdown_rx));
+
+    let list = || async {
+        let listed = JobQueries::list_jobs(database.pool(), None, Some(job_type), 50).await.unwrap();
+        job_ids.iter().map(|id| listed.iter().find(|job| job.id == *id).unwrap().clone()).collect::<Vec<_>>()
+    };
+    for _ in 0..50 {
+        if done(&list().await) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    shutdown_tx.send(true).unwrap();
+    worker.await.unwrap();
+    list().await
+}
+
+#[tokio::test]
+async fn test_ordered_jobs_halt_on_failure_and_resume_in_order() {
+    let _turn = WORKER_TURN.lock().await;
+    dotenvy::dotenv().ok();
+    let database = Database::new(&Config::test_default().database).await.unwrap();
+    let job_type = format!("test_ordered_{}", Uuid::new_v4().simple());
+    let key = format!("{}:{}", job_type, Uuid::new_v4());
+    let other_key = format!("{}:{}", job_type, Uuid::new_v4());
+
+    let mut job_ids = Vec::new();
+    for n in 1..=4 {
+        let job = jobs::enqueue_ordered(database.pool(), &job_type, &key, &serde_json::json!({ "n": n })).await.unwrap();
+        assert_eq!(job.sequence, Some(n));
+        job_ids.push(job.id);
+    }
+    // Another destination isn't held up by this one
+    let other = jobs::enqueue_ordered(database.pool(), &job_type, &other_key, &serde_json::json!({ "n": 10 })).await.unwrap();
+    assert_eq!(other.sequence, Some(1));
+    job_ids.push(other.id);
+    // The second delivery fails for good on its first attempt
+    sqlx::query("UPDATE jobs SET max_attempts = 1 WHERE id = $1")
+        .bind(job_ids[1])
+        .execute(database.pool())
+        .await
+        .unwrap();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let jobs = run_worker(&database, &job_type, &seen, &job_ids, |jobs| {
+        jobs[1].status == JobStatus::Failed && jobs[4].status == JobStatus::Completed
+    })
+    .await;
+    let statuses: Vec<JobStatus> = jobs.iter().map(|job| job.status).collect();
+    assert_eq!(
+        statuses,
+        [JobStatus::Completed, JobStatus::Failed, JobStatus::Pending, JobStatus::Pending, JobStatus::Completed]
+    );
+    let mut ran = seen.lock().unwrap().clone();
+    ran.retain(|n| *n != 10);
+    assert_eq!(ran, [1, 2]);
+
+    // Resuming retries the failed delivery before the ones behind it
+    assert_eq!(JobQueries::resume_ordered(database.pool(), &key).await.unwrap(), 1);
+    let jobs = run_worker(&database, &job_type, &seen, &job_ids, |jobs| {
+        jobs.iter().all(|job| job.status == JobStatus::Completed)
+    })
+    .await;
+    assert!(jobs.iter().all(|job| job.status == JobStatus::Completed));
+    let mut ran = seen.lock().unwrap().clone();
+    ran.retain(|n| *n != 10);
+    assert_eq!(ran, [1, 2, 2, 3, 4]);
+}